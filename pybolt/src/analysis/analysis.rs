@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::analysis::dominators::{DominanceFrontiers, DominatorTree, PostDominatorTree};
+use crate::analysis::liveness::Liveness;
+use crate::analysis::loops::LoopNest;
+use crate::analysis::ranges::ValueRanges;
+use crate::ir::ir::Function;
+
+/// The analyses computed so far for one function.
+#[derive(Debug, Clone, Default)]
+struct FunctionAnalyses {
+    dominators: Option<Rc<DominatorTree>>,
+    post_dominators: Option<Rc<PostDominatorTree>>,
+    frontiers: Option<Rc<DominanceFrontiers>>,
+    loops: Option<Rc<LoopNest>>,
+    liveness: Option<Rc<Liveness>>,
+    ranges: Option<Rc<ValueRanges>>,
+}
+
+/// Caches the analyses of each function of a module, by function name, so
+/// that passes share them. An analysis is computed the first time it is
+/// asked for and kept until the function is invalidated, which must happen
+/// whenever a pass changes it. Analyses are handed out as `Rc`, so a pass
+/// can hold several while asking for more.
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisManager {
+    functions: HashMap<String, FunctionAnalyses>,
+}
+
+impl AnalysisManager {
+    pub fn new() -> Self {
+        AnalysisManager::default()
+    }
+
+    fn cached(&mut self, function: &Function) -> &mut FunctionAnalyses {
+        self.functions.entry(function.name.clone()).or_default()
+    }
+
+    pub fn dominators(&mut self, function: &Function) -> Rc<DominatorTree> {
+        self.cached(function).dominators.get_or_insert_with(|| Rc::new(DominatorTree::compute(function))).clone()
+    }
+
+    pub fn post_dominators(&mut self, function: &Function) -> Rc<PostDominatorTree> {
+        self.cached(function).post_dominators.get_or_insert_with(|| Rc::new(PostDominatorTree::compute(function))).clone()
+    }
+
+    pub fn dominance_frontiers(&mut self, function: &Function) -> Rc<DominanceFrontiers> {
+        if let Some(frontiers) = &self.cached(function).frontiers {
+            return frontiers.clone();
+        }
+        let dominators = self.dominators(function);
+        let frontiers = Rc::new(DominanceFrontiers::compute(function, &dominators));
+        self.cached(function).frontiers = Some(frontiers.clone());
+        frontiers
+    }
+
+    pub fn loops(&mut self, function: &Function) -> Rc<LoopNest> {
+        if let Some(loops) = &self.cached(function).loops {
+            return loops.clone();
+        }
+        let dominators = self.dominators(function);
+        let loops = Rc::new(LoopNest::compute(function, &dominators));
+        self.cached(function).loops = Some(loops.clone());
+        loops
+    }
+
+    pub fn liveness(&mut self, function: &Function) -> Rc<Liveness> {
+        self.cached(function).liveness.get_or_insert_with(|| Rc::new(Liveness::compute(function))).clone()
+    }
+
+    pub fn ranges(&mut self, function: &Function) -> Rc<ValueRanges> {
+        if let Some(ranges) = &self.cached(function).ranges {
+            return ranges.clone();
+        }
+        let dominators = self.dominators(function);
+        let ranges = Rc::new(ValueRanges::compute(function, &dominators));
+        self.cached(function).ranges = Some(ranges.clone());
+        ranges
+    }
+
+    /// Drops the analyses of the function named `name`.
+    pub fn invalidate(&mut self, name: &str) {
+        self.functions.remove(name);
+    }
+
+    pub fn invalidate_all(&mut self) {
+        self.functions.clear();
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::ir::{Block, Function};
+
+/// The dominator tree of the blocks reachable from the entry block: `a`
+/// dominates `b` when every path from the entry to `b` goes through `a`.
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    tree: Tree,
+}
+
+impl DominatorTree {
+    pub fn compute(function: &Function) -> Self {
+        let blocks = function.layout.clone();
+        let index: HashMap<Block, usize> = blocks.iter().enumerate().map(|(index, block)| (*block, index)).collect();
+        let successors: Vec<Vec<usize>> = blocks
+            .iter()
+            .map(|block| function.successors(*block).iter().filter_map(|successor| index.get(successor).copied()).collect())
+            .collect();
+        let root = index[&function.entry()];
+        DominatorTree { tree: Tree::compute(&blocks, root, &successors) }
+    }
+
+    /// The immediate dominator of `block`, or `None` for the entry block and
+    /// unreachable blocks.
+    pub fn idom(&self, block: Block) -> Option<Block> {
+        self.tree.parent(block)
+    }
+
+    /// The blocks whose immediate dominator is `block`, in layout order.
+    pub fn children(&self, block: Block) -> &[Block] {
+        self.tree.children(block)
+    }
+
+    pub fn is_reachable(&self, block: Block) -> bool {
+        self.tree.contains(block)
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`. A block
+    /// dominates itself; an unreachable block dominates nothing and is
+    /// dominated by nothing.
+    pub fn dominates(&self, a: Block, b: Block) -> bool {
+        self.tree.is_ancestor(a, b)
+    }
+
+    pub fn strictly_dominates(&self, a: Block, b: Block) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The reachable blocks, each before its successors except along back
+    /// edges.
+    pub fn reverse_postorder(&self) -> &[Block] {
+        &self.tree.order
+    }
+}
+
+/// The post-dominator tree: `a` post-dominates `b` when every path from `b`
+/// to a return goes through `a`. Blocks ending in `ret` or `unreachable`
+/// are the exits; blocks that cannot reach an exit, such as those of an
+/// infinite loop, are not part of the tree.
+#[derive(Debug, Clone)]
+pub struct PostDominatorTree {
+    tree: Tree,
+}
+
+impl PostDominatorTree {
+    pub fn compute(function: &Function) -> Self {
+        // The reverse graph, rooted at a virtual exit after the blocks.
+        let blocks = function.layout.clone();
+        let index: HashMap<Block, usize> = blocks.iter().enumerate().map(|(index, block)| (*block, index)).collect();
+        let exit = blocks.len();
+        let mut successors: Vec<Vec<usize>> = vec![Vec::new(); blocks.len() + 1];
+        for (from, block) in blocks.iter().enumerate() {
+            let targets = function.successors(*block);
+            if targets.is_empty() {
+                successors[exit].push(from);
+            }
+            for target in targets {
+                if let Some(to) = index.get(&target) {
+                    successors[*to].push(from);
+                }
+            }
+        }
+        // The virtual exit stands in for a block that is not in the layout.
+        let mut nodes = blocks;
+        nodes.push(Block(u32::MAX));
+        let mut tree = Tree::compute(&nodes, exit, &successors);
+        tree.remove_root();
+        PostDominatorTree { tree }
+    }
+
+    /// The immediate post-dominator of `block`, or `None` when it is an
+    /// exit, the exits it reaches have no block in common, or it reaches
+    /// none.
+    pub fn ipdom(&self, block: Block) -> Option<Block> {
+        self.tree.parent(block)
+    }
+
+    pub fn children(&self, block: Block) -> &[Block] {
+        self.tree.children(block)
+    }
+
+    /// Whether `block` can reach a `ret` or `unreachable`.
+    pub fn reaches_exit(&self, block: Block) -> bool {
+        self.tree.contains(block)
+    }
+
+    /// Whether every path from `b` to an exit goes through `a`. A block
+    /// post-dominates itself.
+    pub fn post_dominates(&self, a: Block, b: Block) -> bool {
+        self.tree.is_ancestor(a, b)
+    }
+}
+
+/// The dominance frontier of each block: the blocks where its dominance
+/// ends, that is, the blocks it does not strictly dominate but one of whose
+/// predecessors it dominates. This is where definitions in the block meet
+/// others and need a block parameter.
+#[derive(Debug, Clone)]
+pub struct DominanceFrontiers {
+    frontiers: HashMap<Block, Vec<Block>>,
+}
+
+impl DominanceFrontiers {
+    /// Computes the frontiers as in Cooper, Harvey and Kennedy: walking up
+    /// from each predecessor of a join block to its immediate dominator.
+    pub fn compute(function: &Function, dominators: &DominatorTree) -> Self {
+        let mut frontiers: HashMap<Block, Vec<Block>> = HashMap::new();
+        let predecessors = function.predecessors();
+        for block in &function.layout {
+            let Some(idom) = dominators.idom(*block) else {
+                continue;
+            };
+            let predecessors = &predecessors[block];
+            if predecessors.len() < 2 {
+                continue;
+            }
+            for predecessor in predecessors {
+                let mut runner = *predecessor;
+                while dominators.is_reachable(runner) && runner != idom {
+                    let frontier = frontiers.entry(runner).or_default();
+                    if !frontier.contains(block) {
+                        frontier.push(*block);
+                    }
+                    match dominators.idom(runner) {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        DominanceFrontiers { frontiers }
+    }
+
+    /// The dominance frontier of `block`, in layout order.
+    pub fn frontier(&self, block: Block) -> &[Block] {
+        self.frontiers.get(&block).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The iterated dominance frontier of `blocks`: the blocks where a
+    /// variable defined in each of them needs a block parameter.
+    pub fn iterated(&self, blocks: &[Block]) -> HashSet<Block> {
+        let mut result = HashSet::new();
+        let mut worklist = blocks.to_vec();
+        while let Some(block) = worklist.pop() {
+            for frontier in self.frontier(block) {
+                if result.insert(*frontier) {
+                    worklist.push(*frontier);
+                }
+            }
+        }
+        result
+    }
+}
+
+/// A tree of immediate dominators over the nodes reachable from a root,
+/// computed with the iterative algorithm of Cooper, Harvey and Kennedy, "A
+/// Simple, Fast Dominance Algorithm".
+#[derive(Debug, Clone)]
+struct Tree {
+    parent: HashMap<Block, Block>,
+    children: HashMap<Block, Vec<Block>>,
+    /// The nodes in reverse postorder from the root.
+    order: Vec<Block>,
+    /// The preorder number of each node in the tree and the largest number
+    /// in its subtree, so that ancestry is a comparison.
+    intervals: HashMap<Block, (usize, usize)>,
+}
+
+impl Tree {
+    /// `nodes[i]` has the edges `successors[i]`, given as indices.
+    fn compute(nodes: &[Block], root: usize, successors: &[Vec<usize>]) -> Self {
+        let order = reverse_postorder(root, successors);
+        let mut position = vec![usize::MAX; nodes.len()];
+        for (index, node) in order.iter().enumerate() {
+            position[*node] = index;
+        }
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+        for (from, targets) in successors.iter().enumerate() {
+            for to in targets {
+                predecessors[*to].push(from);
+            }
+        }
+        // Indexed by position in reverse postorder; the root is its own.
+        let mut idom: Vec<Option<usize>> = vec![None; order.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for index in 1..order.len() {
+                let mut new_idom: Option<usize> = None;
+                for predecessor in &predecessors[order[index]] {
+                    let predecessor = position[*predecessor];
+                    if predecessor == usize::MAX || idom[predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => predecessor,
+                        Some(current) => intersect(&idom, predecessor, current),
+                    });
+                }
+                if new_idom.is_some() && idom[index] != new_idom {
+                    idom[index] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        let mut tree = Tree {
+            parent: HashMap::new(),
+            children: HashMap::new(),
+            order: order.iter().map(|node| nodes[*node]).collect(),
+            intervals: HashMap::new(),
+        };
+        for (index, parent) in idom.iter().enumerate().skip(1) {
+            let (node, parent) = (nodes[order[index]], nodes[order[parent.expect("reachable nodes have a dominator")]]);
+            tree.parent.insert(node, parent);
+            tree.children.entry(parent).or_default().push(node);
+        }
+        // Children in the order of `nodes`, for stable output.
+        let rank: HashMap<Block, usize> = nodes.iter().enumerate().map(|(index, node)| (*node, index)).collect();
+        for children in tree.children.values_mut() {
+            children.sort_by_key(|child| rank[child]);
+        }
+        tree.number(nodes[root]);
+        tree
+    }
+
+    fn number(&mut self, root: Block) {
+        self.intervals.clear();
+        let mut counter = 0;
+        // Each node, and whether its subtree is done.
+        let mut stack = vec![(root, false)];
+        while let Some((node, done)) = stack.pop() {
+            if done {
+                let start = self.intervals[&node].0;
+                self.intervals.insert(node, (start, counter - 1));
+                continue;
+            }
+            self.intervals.insert(node, (counter, counter));
+            counter += 1;
+            stack.push((node, true));
+            for child in self.children(node).iter().rev() {
+                stack.push((*child, false));
+            }
+        }
+    }
+
+    /// Takes out the root, a virtual node, so that its children become
+    /// roots themselves.
+    fn remove_root(&mut self) {
+        let root = self.order.remove(0);
+        self.intervals.remove(&root);
+        for child in self.children.remove(&root).unwrap_or_default() {
+            self.parent.remove(&child);
+        }
+    }
+
+    fn parent(&self, node: Block) -> Option<Block> {
+        self.parent.get(&node).copied()
+    }
+
+    fn children(&self, node: Block) -> &[Block] {
+        self.children.get(&node).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    fn contains(&self, node: Block) -> bool {
+        self.intervals.contains_key(&node)
+    }
+
+    fn is_ancestor(&self, a: Block, b: Block) -> bool {
+        match (self.intervals.get(&a), self.intervals.get(&b)) {
+            (Some((start, end)), Some((number, _))) => start <= number && number <= end,
+            _ => false,
+        }
+    }
+}
+
+fn intersect(idom: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while a > b {
+            a = idom[a].expect("processed nodes have a dominator");
+        }
+        while b > a {
+            b = idom[b].expect("processed nodes have a dominator");
+        }
+    }
+    a
+}
+
+/// The nodes reachable from `root`, in reverse postorder of a depth-first
+/// search.
+fn reverse_postorder(root: usize, successors: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; successors.len()];
+    visited[root] = true;
+    let mut postorder = Vec::new();
+    // Each node with the index of the next successor to visit.
+    let mut stack = vec![(root, 0)];
+    while let Some((node, next)) = stack.pop() {
+        if let Some(successor) = successors[node].get(next) {
+            stack.push((node, next + 1));
+            if !visited[*successor] {
+                visited[*successor] = true;
+                stack.push((*successor, 0));
+            }
+        } else {
+            postorder.push(node);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
@@ -0,0 +1,94 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::analysis::dominators::DominatorTree;
+use crate::ir::ir::{Block, Function, Value};
+
+/// The values live at the start and end of each block: those that may still
+/// be used on some path from there.
+///
+/// A block's parameters are defined on entry to it, and the arguments of a
+/// branch are used at the end of the block branching, so a parameter is
+/// never live into its own block and an argument is live out of a block only
+/// if it is used further on.
+#[derive(Debug, Clone, Default)]
+pub struct Liveness {
+    live_in: HashMap<Block, BTreeSet<Value>>,
+    live_out: HashMap<Block, BTreeSet<Value>>,
+}
+
+impl Liveness {
+    /// Computes liveness by iterating to a fixed point over the blocks in
+    /// postorder, so that most blocks see their successors' results first.
+    pub fn compute(function: &Function) -> Self {
+        let mut uses: HashMap<Block, BTreeSet<Value>> = HashMap::new();
+        let mut defs: HashMap<Block, BTreeSet<Value>> = HashMap::new();
+        for block in &function.layout {
+            let data = function.block(*block);
+            let mut defined: BTreeSet<Value> = data.params.iter().copied().collect();
+            let mut used = BTreeSet::new();
+            for inst in &data.insts {
+                for operand in function.inst(*inst).kind.operands() {
+                    if !defined.contains(&operand) {
+                        used.insert(operand);
+                    }
+                }
+                defined.extend(function.result(*inst));
+            }
+            for operand in function.terminator(*block).map(|terminator| terminator.operands()).unwrap_or_default() {
+                if !defined.contains(&operand) {
+                    used.insert(operand);
+                }
+            }
+            uses.insert(*block, used);
+            defs.insert(*block, defined);
+        }
+
+        let dominators = DominatorTree::compute(function);
+        let mut order: Vec<Block> = dominators.reverse_postorder().iter().rev().copied().collect();
+        // Unreachable blocks, after the others.
+        order.extend(function.layout.iter().filter(|block| !dominators.is_reachable(**block)));
+        let mut liveness = Liveness::default();
+        for block in &function.layout {
+            liveness.live_in.insert(*block, uses[block].clone());
+            liveness.live_out.insert(*block, BTreeSet::new());
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &order {
+                let mut live_out = BTreeSet::new();
+                for successor in function.successors(*block) {
+                    if let Some(live_in) = liveness.live_in.get(&successor) {
+                        live_out.extend(live_in);
+                    }
+                }
+                let mut live_in = uses[block].clone();
+                live_in.extend(live_out.difference(&defs[block]));
+                if live_in != liveness.live_in[block] || live_out != liveness.live_out[block] {
+                    liveness.live_in.insert(*block, live_in);
+                    liveness.live_out.insert(*block, live_out);
+                    changed = true;
+                }
+            }
+        }
+        liveness
+    }
+
+    /// The values live on entry to `block`, in order.
+    pub fn live_in(&self, block: Block) -> &BTreeSet<Value> {
+        &self.live_in[&block]
+    }
+
+    /// The values live on exit from `block`, in order.
+    pub fn live_out(&self, block: Block) -> &BTreeSet<Value> {
+        &self.live_out[&block]
+    }
+
+    pub fn is_live_in(&self, value: Value, block: Block) -> bool {
+        self.live_in.get(&block).is_some_and(|live| live.contains(&value))
+    }
+
+    pub fn is_live_out(&self, value: Value, block: Block) -> bool {
+        self.live_out.get(&block).is_some_and(|live| live.contains(&value))
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::dominators::DominatorTree;
+use crate::ir::ir::{Block, Function};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Loop(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct LoopData {
+    /// The block that dominates the loop, which every iteration enters
+    /// through.
+    pub header: Block,
+    /// The blocks of the loop, in layout order, header included. The blocks
+    /// of inner loops are included too.
+    pub blocks: Vec<Block>,
+    /// The blocks that branch back to the header.
+    pub latches: Vec<Block>,
+    pub parent: Option<Loop>,
+    pub children: Vec<Loop>,
+    /// 1 for an outermost loop.
+    pub depth: u32,
+}
+
+/// The natural loops of a function and how they nest. A loop is found from
+/// each back edge, an edge to a block that dominates its source; back edges
+/// to the same header make one loop. Loops whose entry is not dominated by
+/// a single header (irreducible control flow) are not found.
+#[derive(Debug, Clone, Default)]
+pub struct LoopNest {
+    loops: Vec<LoopData>,
+    /// The innermost loop of each block in a loop.
+    innermost: HashMap<Block, Loop>,
+}
+
+impl LoopNest {
+    pub fn compute(function: &Function, dominators: &DominatorTree) -> Self {
+        let predecessors = function.predecessors();
+        let position: HashMap<Block, usize> = function.layout.iter().enumerate().map(|(index, block)| (*block, index)).collect();
+        let mut loops: Vec<LoopData> = Vec::new();
+        for header in dominators.reverse_postorder() {
+            let latches: Vec<Block> = predecessors[header]
+                .iter()
+                .copied()
+                .filter(|predecessor| dominators.dominates(*header, *predecessor))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            // The blocks that reach a latch without going through the header.
+            let mut blocks = HashSet::from([*header]);
+            let mut worklist = latches.clone();
+            while let Some(block) = worklist.pop() {
+                if dominators.is_reachable(block) && blocks.insert(block) {
+                    worklist.extend(&predecessors[&block]);
+                }
+            }
+            let mut blocks: Vec<Block> = blocks.into_iter().collect();
+            blocks.sort_by_key(|block| position[block]);
+            loops.push(LoopData { header: *header, blocks, latches, parent: None, children: Vec::new(), depth: 0 });
+        }
+        // Headers come in reverse postorder, so an enclosing loop comes
+        // before the loops it contains; the last one containing a block is
+        // its innermost.
+        let mut innermost = HashMap::new();
+        for (index, data) in loops.iter().enumerate() {
+            for block in &data.blocks {
+                innermost.insert(*block, Loop(index as u32));
+            }
+        }
+        for index in 0..loops.len() {
+            let parent = (0..index).rev().find(|outer| loops[*outer].blocks.contains(&loops[index].header));
+            if let Some(parent) = parent {
+                loops[index].parent = Some(Loop(parent as u32));
+                loops[index].depth = loops[parent].depth + 1;
+                loops[parent].children.push(Loop(index as u32));
+            } else {
+                loops[index].depth = 1;
+            }
+        }
+        LoopNest { loops, innermost }
+    }
+
+    /// Every loop, each after the loops that contain it.
+    pub fn loops(&self) -> impl Iterator<Item = Loop> {
+        (0..self.loops.len() as u32).map(Loop)
+    }
+
+    pub fn loop_data(&self, l: Loop) -> &LoopData {
+        &self.loops[l.0 as usize]
+    }
+
+    /// The loops not contained in any other.
+    pub fn top_level(&self) -> Vec<Loop> {
+        self.loops().filter(|l| self.loop_data(*l).parent.is_none()).collect()
+    }
+
+    pub fn innermost_loop(&self, block: Block) -> Option<Loop> {
+        self.innermost.get(&block).copied()
+    }
+
+    /// The number of loops containing `block`.
+    pub fn depth(&self, block: Block) -> u32 {
+        self.innermost_loop(block).map(|l| self.loop_data(l).depth).unwrap_or(0)
+    }
+
+    pub fn is_header(&self, block: Block) -> bool {
+        self.innermost_loop(block).is_some_and(|l| self.loop_data(l).header == block)
+    }
+
+    pub fn contains(&self, l: Loop, block: Block) -> bool {
+        self.loop_data(l).blocks.contains(&block)
+    }
+
+    /// The edges leaving the loop, as (block in the loop, block outside).
+    pub fn exits(&self, function: &Function, l: Loop) -> Vec<(Block, Block)> {
+        let data = self.loop_data(l);
+        let mut exits = Vec::new();
+        for block in &data.blocks {
+            for successor in function.successors(*block) {
+                if !data.blocks.contains(&successor) {
+                    exits.push((*block, successor));
+                }
+            }
+        }
+        exits
+    }
+
+    /// The block that enters the loop, if there is exactly one and it
+    /// branches only to the header, so that code can be hoisted into it.
+    pub fn preheader(&self, function: &Function, l: Loop) -> Option<Block> {
+        let data = self.loop_data(l);
+        let predecessors = function.predecessors();
+        let outside: Vec<Block> =
+            predecessors[&data.header].iter().copied().filter(|predecessor| !data.blocks.contains(predecessor)).collect();
+        match outside.as_slice() {
+            [preheader] if function.successors(*preheader) == [data.header] => Some(*preheader),
+            _ => None,
+        }
+    }
+}
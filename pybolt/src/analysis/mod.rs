@@ -0,0 +1,5 @@
+pub mod analysis;
+pub mod dominators;
+pub mod liveness;
+pub mod loops;
+pub mod ranges;
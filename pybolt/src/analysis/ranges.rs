@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use crate::analysis::dominators::DominatorTree;
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Constant, Function, InstKind, Terminator, Type, UnaryOp, Value, ValueDef};
+
+/// How many times the interval of a block parameter may grow before it is
+/// widened to infinity in the direction it grows in, so that loops end.
+const WIDEN_AFTER: usize = 3;
+
+/// How many times every interval is computed again once widened, to win
+/// back the bounds that the comparisons of loops give.
+const NARROWING_ROUNDS: usize = 2;
+
+/// The integers from `low` to `high`, both included. Bounds are `i64`s or
+/// infinite: a lower bound beyond 64 bits is `NEG_INFINITY`, an upper one
+/// `INFINITY`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub low: i128,
+    pub high: i128,
+}
+
+pub const NEG_INFINITY: i128 = i128::MIN;
+pub const INFINITY: i128 = i128::MAX;
+
+impl Interval {
+    /// Every integer, which an `int` may be.
+    pub const FULL: Interval = Interval { low: NEG_INFINITY, high: INFINITY };
+    /// Every `i64`.
+    pub const I64: Interval = Interval { low: i64::MIN as i128, high: i64::MAX as i128 };
+
+    /// The interval of the bounds, which may be beyond 64 bits.
+    pub fn new(low: i128, high: i128) -> Self {
+        let low = if low < i64::MIN as i128 { NEG_INFINITY } else { low.min(i64::MAX as i128) };
+        let high = if high > i64::MAX as i128 { INFINITY } else { high.max(i64::MIN as i128) };
+        Interval { low, high }
+    }
+
+    pub fn constant(value: i64) -> Self {
+        Interval { low: value as i128, high: value as i128 }
+    }
+
+    /// What a value of the type may be, if it is an integer type.
+    pub fn of_type(ty: Type) -> Option<Interval> {
+        match ty {
+            Type::Int => Some(Interval::FULL),
+            Type::I64 => Some(Interval::I64),
+            _ => None,
+        }
+    }
+
+    pub fn fits_i64(&self) -> bool {
+        Interval::I64.contains(self)
+    }
+
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.low <= other.low && other.high <= self.high
+    }
+
+    /// The smallest interval holding both.
+    pub fn union(&self, other: &Interval) -> Interval {
+        Interval { low: self.low.min(other.low), high: self.high.max(other.high) }
+    }
+
+    /// The integers in both, or `self` if there are none: the code that
+    /// sees no value is never run, and may as well see any.
+    pub fn intersect(&self, other: &Interval) -> Interval {
+        let (low, high) = (self.low.max(other.low), self.high.min(other.high));
+        if low <= high {
+            Interval { low, high }
+        } else {
+            *self
+        }
+    }
+
+    pub fn add(&self, other: &Interval) -> Interval {
+        Interval::new(self.low.saturating_add(other.low), self.high.saturating_add(other.high))
+    }
+
+    pub fn sub(&self, other: &Interval) -> Interval {
+        Interval::new(self.low.saturating_sub(other.high), self.high.saturating_sub(other.low))
+    }
+
+    pub fn mul(&self, other: &Interval) -> Interval {
+        let products = [
+            self.low.saturating_mul(other.low),
+            self.low.saturating_mul(other.high),
+            self.high.saturating_mul(other.low),
+            self.high.saturating_mul(other.high),
+        ];
+        let low = products.iter().min().expect("there are four products");
+        let high = products.iter().max().expect("there are four products");
+        Interval::new(*low, *high)
+    }
+
+    pub fn neg(&self) -> Interval {
+        Interval::new(self.high.saturating_neg(), self.low.saturating_neg())
+    }
+
+    /// Python's `//`, which rounds towards negative infinity.
+    pub fn floor_div(&self, other: &Interval) -> Interval {
+        if other.low > 0 && self.low >= 0 {
+            // A larger divisor only brings the quotient closer to zero.
+            return Interval::new(self.low / other.high.min(i64::MAX as i128), self.high / other.low);
+        }
+        let finite = [self.low, self.high, other.low, other.high].iter().all(|bound| *bound != NEG_INFINITY && *bound != INFINITY);
+        if !finite || (other.low <= 0 && other.high >= 0) {
+            return Interval::FULL;
+        }
+        let quotients = [
+            self.low.div_euclid(other.low),
+            self.low.div_euclid(other.high),
+            self.high.div_euclid(other.low),
+            self.high.div_euclid(other.high),
+        ];
+        // `div_euclid` rounds down for positive divisors; for negative
+        // ones the quotient may be one more than Python's.
+        let low = quotients.iter().min().expect("there are four quotients");
+        let high = quotients.iter().max().expect("there are four quotients");
+        if other.low > 0 {
+            Interval::new(*low, *high)
+        } else {
+            Interval::new(low - 1, *high)
+        }
+    }
+
+    /// Python's `%`, which takes the sign of the divisor.
+    pub fn modulo(&self, other: &Interval) -> Interval {
+        if other.low > 0 {
+            let high = other.high.saturating_sub(1);
+            return if self.low >= 0 { Interval::new(0, high.min(self.high)) } else { Interval::new(0, high) };
+        }
+        if other.high < 0 {
+            return Interval::new(other.low.saturating_add(1), 0);
+        }
+        Interval::FULL
+    }
+
+    /// The values `self` keeps where `self op other` holds.
+    fn refine(&self, op: CompareOp, other: &Interval) -> Interval {
+        let bound = match op {
+            CompareOp::Lt => Interval::new(NEG_INFINITY, other.high.saturating_sub(1)),
+            CompareOp::Le => Interval::new(NEG_INFINITY, other.high),
+            CompareOp::Gt => Interval::new(other.low.saturating_add(1), INFINITY),
+            CompareOp::Ge => Interval::new(other.low, INFINITY),
+            CompareOp::Eq => *other,
+            CompareOp::Ne if other.low == other.high && other.low == self.low => Interval::new(self.low + 1, INFINITY),
+            CompareOp::Ne if other.low == other.high && other.high == self.high => Interval::new(NEG_INFINITY, self.high - 1),
+            CompareOp::Ne => Interval::FULL,
+        };
+        self.intersect(&bound)
+    }
+
+    /// Whether `self op other` holds for every pair of values, if it is
+    /// the same for all of them.
+    pub fn compare(&self, op: CompareOp, other: &Interval) -> Option<bool> {
+        let always = |left: &Interval, right: &Interval| left.high < right.low;
+        let result = match op {
+            CompareOp::Lt if always(self, other) => true,
+            CompareOp::Lt if self.low >= other.high => false,
+            CompareOp::Le if self.high <= other.low => true,
+            CompareOp::Le if always(other, self) => false,
+            CompareOp::Gt | CompareOp::Ge => return other.compare(swapped(op), self),
+            CompareOp::Eq | CompareOp::Ne if always(self, other) || always(other, self) => op == CompareOp::Ne,
+            CompareOp::Eq | CompareOp::Ne if self.low == self.high && *self == *other => op == CompareOp::Eq,
+            _ => return None,
+        };
+        Some(result)
+    }
+}
+
+/// `op` with its operands swapped: `a < b` is `b > a`.
+fn swapped(op: CompareOp) -> CompareOp {
+    match op {
+        CompareOp::Lt => CompareOp::Gt,
+        CompareOp::Le => CompareOp::Ge,
+        CompareOp::Gt => CompareOp::Lt,
+        CompareOp::Ge => CompareOp::Le,
+        op => op,
+    }
+}
+
+/// The opposite of `op`: `a < b` is false when `a >= b` holds.
+fn negated(op: CompareOp) -> CompareOp {
+    match op {
+        CompareOp::Eq => CompareOp::Ne,
+        CompareOp::Ne => CompareOp::Eq,
+        CompareOp::Lt => CompareOp::Ge,
+        CompareOp::Le => CompareOp::Gt,
+        CompareOp::Gt => CompareOp::Le,
+        CompareOp::Ge => CompareOp::Lt,
+    }
+}
+
+/// Something a branch condition tells about a value where it holds:
+/// `value op bound`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fact {
+    value: Value,
+    op: CompareOp,
+    bound: Value,
+}
+
+/// Value-range analysis: the interval of every `int` and `i64` of a
+/// function. Arithmetic computes on intervals, as an `int` would without
+/// ever overflowing and an `i64` wrapping around. A block parameter holds
+/// what its branches pass, which loops make grow until it is widened to
+/// infinity; the comparisons of branches then narrow it back, so that the
+/// counter of a loop up to 100 ends up between its start and 100. A value
+/// is also narrower in the blocks that only a branch on a comparison of it
+/// reaches, such as the body of that loop, where it is below 100, and so
+/// are the `cast`s of it between `int` and `i64`, which have its value.
+/// A length is never negative, that of a list just made is known, and the
+/// position `check_index` gives is below it.
+#[derive(Debug, Clone)]
+pub struct ValueRanges {
+    intervals: HashMap<Value, Interval>,
+    /// What the branch conditions tell in each reachable block, about the
+    /// integers that are not casts.
+    facts: HashMap<Block, Vec<Fact>>,
+    /// The integer each cast of one to another integer type is a cast of,
+    /// through casts of casts.
+    casts: HashMap<Value, Value>,
+}
+
+impl ValueRanges {
+    pub fn compute(function: &Function, dominators: &DominatorTree) -> Self {
+        let predecessors = function.predecessors();
+        let mut casts = HashMap::new();
+        for inst in function.layout_insts() {
+            if let Some(result) = function.result(inst) {
+                let cast = uncast(function, result);
+                if cast != result {
+                    casts.insert(result, cast);
+                }
+            }
+        }
+        let mut facts: HashMap<Block, Vec<Fact>> = HashMap::new();
+        for block in dominators.reverse_postorder() {
+            let mut known = Vec::new();
+            if let Some(idom) = dominators.idom(*block) {
+                known = facts[&idom].clone();
+                if predecessors[block].len() == 1 {
+                    known.extend(edge_facts(function, idom, *block));
+                }
+            }
+            facts.insert(*block, known);
+        }
+        let mut ranges = ValueRanges { intervals: HashMap::new(), facts, casts };
+        for param in &function.block(function.entry()).params {
+            if let Some(interval) = Interval::of_type(function.value_type(*param)) {
+                ranges.intervals.insert(*param, interval);
+            }
+        }
+
+        let mut growths: HashMap<Value, usize> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for block in dominators.reverse_postorder() {
+                for (index, param) in function.block(*block).params.iter().enumerate() {
+                    let Some(mut interval) = ranges.param(function, &predecessors, *block, index) else {
+                        continue;
+                    };
+                    if let Some(old) = ranges.intervals.get(param) {
+                        interval = interval.union(old);
+                        if interval == *old {
+                            continue;
+                        }
+                        let growth = growths.entry(*param).or_default();
+                        *growth += 1;
+                        if *growth > WIDEN_AFTER {
+                            let low = if interval.low < old.low { NEG_INFINITY } else { interval.low };
+                            let high = if interval.high > old.high { INFINITY } else { interval.high };
+                            interval = Interval::new(low, high).intersect(&Interval::of_type(function.value_type(*param)).unwrap_or(Interval::FULL));
+                        }
+                    }
+                    ranges.intervals.insert(*param, interval);
+                    changed = true;
+                }
+                changed |= ranges.compute_insts(function, *block, false);
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for _ in 0..NARROWING_ROUNDS {
+            for block in dominators.reverse_postorder() {
+                for (index, param) in function.block(*block).params.iter().enumerate() {
+                    if let (Some(interval), Some(old)) = (ranges.param(function, &predecessors, *block, index), ranges.interval(*param)) {
+                        ranges.intervals.insert(*param, old.intersect(&interval));
+                    }
+                }
+                ranges.compute_insts(function, *block, true);
+            }
+        }
+        ranges
+    }
+
+    /// The interval of `value`, if it is an integer of a reachable block.
+    pub fn interval(&self, value: Value) -> Option<Interval> {
+        self.intervals.get(&value).copied()
+    }
+
+    /// The interval of `value` where it is used in `block`, narrowed by the
+    /// branches that lead there.
+    pub fn interval_in(&self, value: Value, block: Block) -> Option<Interval> {
+        let facts = self.facts.get(&block).map(Vec::as_slice).unwrap_or_default();
+        self.refined(value, facts)
+    }
+
+    /// Whether a branch that leads to `block` tells that `value` is less
+    /// than `bound`, whatever their intervals.
+    pub fn is_less_than(&self, value: Value, bound: Value, block: Block) -> bool {
+        let uncast = |value: Value| self.casts.get(&value).copied().unwrap_or(value);
+        let facts = self.facts.get(&block).map(Vec::as_slice).unwrap_or_default();
+        facts.iter().any(|fact| fact.value == uncast(value) && fact.op == CompareOp::Lt && uncast(fact.bound) == uncast(bound))
+    }
+
+    fn refined(&self, value: Value, facts: &[Fact]) -> Option<Interval> {
+        let mut interval = self.interval(value)?;
+        let uncast = self.casts.get(&value).copied().unwrap_or(value);
+        for fact in facts.iter().filter(|fact| fact.value == uncast) {
+            if let Some(bound) = self.interval(fact.bound) {
+                interval = interval.refine(fact.op, &bound);
+            }
+        }
+        Some(interval)
+    }
+
+    /// What parameter `index` of `block` may be, from what the branches
+    /// computed so far pass it, or `None` if none was yet.
+    fn param(&self, function: &Function, predecessors: &HashMap<Block, Vec<Block>>, block: Block, index: usize) -> Option<Interval> {
+        let whole = Interval::of_type(function.value_type(function.block(block).params[index]))?;
+        let mut interval: Option<Interval> = None;
+        for predecessor in predecessors[&block].iter().filter(|predecessor| self.facts.contains_key(predecessor)) {
+            let Some(terminator) = function.terminator(*predecessor) else {
+                continue;
+            };
+            for target in terminator.successors().into_iter().filter(|target| target.block == block) {
+                let mut facts = self.facts[predecessor].clone();
+                facts.extend(edge_facts(function, *predecessor, block));
+                if let Some(passed) = self.refined(target.arguments[index], &facts) {
+                    interval = Some(interval.map_or(passed, |interval| interval.union(&passed)));
+                }
+            }
+        }
+        interval.map(|interval| interval.intersect(&whole))
+    }
+
+    /// Computes the intervals of the results of the instructions of
+    /// `block`, only narrowing them if `narrow` is set. Returns whether one
+    /// changed.
+    fn compute_insts(&mut self, function: &Function, block: Block, narrow: bool) -> bool {
+        let mut changed = false;
+        for inst in &function.block(block).insts {
+            let Some(result) = function.result(*inst) else {
+                continue;
+            };
+            let ty = function.value_type(result);
+            let Some(whole) = Interval::of_type(ty) else {
+                continue;
+            };
+            let operand = |value: Value| self.interval_in(value, block).unwrap_or(Interval::FULL);
+            let mut interval = match &function.inst(*inst).kind {
+                InstKind::Const(Constant::Int(value)) => Interval::constant(*value),
+                InstKind::Binary { op, left, right } => {
+                    let (left, right) = (operand(*left), operand(*right));
+                    match op {
+                        BinaryOp::Add => left.add(&right),
+                        BinaryOp::Sub => left.sub(&right),
+                        BinaryOp::Mul => left.mul(&right),
+                        BinaryOp::FloorDiv => left.floor_div(&right),
+                        BinaryOp::Mod => left.modulo(&right),
+                        BinaryOp::Div => whole,
+                    }
+                }
+                InstKind::Unary { op: UnaryOp::Neg, operand: value } => operand(*value).neg(),
+                InstKind::Cast(value) if function.value_type(*value) == Type::Bool => Interval::new(0, 1),
+                InstKind::Cast(value) => operand(*value),
+                InstKind::Len(value) => match function.value(*value).def {
+                    ValueDef::Result(made) => match &function.inst(made).kind {
+                        InstKind::List(items) | InstKind::Tuple(items) => Interval::constant(items.len() as i64),
+                        _ => Interval::new(0, i64::MAX as i128),
+                    },
+                    _ => Interval::new(0, i64::MAX as i128),
+                },
+                // The position is below the length, and the index itself
+                // if that is not negative.
+                InstKind::CheckIndex { index, length } => {
+                    let position = Interval::new(0, (operand(*length).high - 1).max(0));
+                    match operand(*index) {
+                        index if index.low >= 0 => position.intersect(&index),
+                        _ => position,
+                    }
+                }
+                _ => whole,
+            };
+            // An `i64` wraps around rather than leave its interval.
+            if !whole.contains(&interval) {
+                interval = whole;
+            }
+            if narrow {
+                interval = self.intervals.get(&result).map_or(interval, |old| old.intersect(&interval));
+            }
+            if self.intervals.get(&result) != Some(&interval) {
+                self.intervals.insert(result, interval);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// What holds along the branch from `from` to `to`, when `from` ends with
+/// a `cond_br` on a comparison of integers to other blocks.
+fn edge_facts(function: &Function, from: Block, to: Block) -> Vec<Fact> {
+    let Some(Terminator::CondBr { condition, then, otherwise }) = function.terminator(from) else {
+        return Vec::new();
+    };
+    if then.block == otherwise.block {
+        return Vec::new();
+    }
+    let mut holds = to == then.block;
+    let mut condition = *condition;
+    loop {
+        let ValueDef::Result(inst) = function.value(condition).def else {
+            return Vec::new();
+        };
+        match function.inst(inst).kind {
+            InstKind::Unary { op: UnaryOp::Not, operand } => {
+                condition = operand;
+                holds = !holds;
+            }
+            InstKind::Compare { op, left, right } if Interval::of_type(function.value_type(left)).is_some() => {
+                let op = if holds { op } else { negated(op) };
+                let (value, bound) = (uncast(function, left), uncast(function, right));
+                return vec![Fact { value, op, bound: right }, Fact { value: bound, op: swapped(op), bound: left }];
+            }
+            _ => return Vec::new(),
+        }
+    }
+}
+
+/// The integer `value` is a cast of, through casts between `int` and `i64`,
+/// or `value` itself.
+fn uncast(function: &Function, value: Value) -> Value {
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return value;
+    };
+    match function.inst(inst).kind {
+        InstKind::Cast(operand) if Interval::of_type(function.value_type(operand)).is_some() && Interval::of_type(function.value_type(value)).is_some() => {
+            uncast(function, operand)
+        }
+        _ => value,
+    }
+}
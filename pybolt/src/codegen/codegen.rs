@@ -1,101 +1,277 @@
-use crate::ir::ir::{IRGenerator, IR};
+use std::collections::HashMap;
+
+use crate::ir::generator::MAIN;
+use crate::ir::ir::{
+    BinaryOp, Block, BlockCall, Class, CompareOp, Constant, Frame, Function, Inst, InstKind, Module, Terminator, Type, UnaryOp,
+    Value,
+};
+use crate::runtime::runtime::{self, Arithmetic, Comparison, Instruction};
 
 #[derive(Debug, Clone)]
 pub enum MachineInstruction {
-    LoadImmediate { dest: String, value: i32 },
+    Function { name: String, parameters: Vec<String> },
+    LoadImmediate { dest: String, value: runtime::Value },
+    Copy { dest: String, src: String },
     Add { dest: String, src1: String, src2: String },
     Sub { dest: String, src1: String, src2: String },
     Mul { dest: String, src1: String, src2: String },
     Div { dest: String, src1: String, src2: String },
-    Print { var: String },
+    FloorDiv { dest: String, src1: String, src2: String },
+    Mod { dest: String, src1: String, src2: String },
+    Neg { dest: String, src: String },
+    Compare { dest: String, comparison: Comparison, src1: String, src2: String },
+    I64Arithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    I64Neg { dest: String, src: String },
+    FloatArithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    FloatNeg { dest: String, src: String },
+    FloatCompare { dest: String, comparison: Comparison, src1: String, src2: String },
+    ObjectArithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    ObjectNeg { dest: String, src: String },
+    ObjectCompare { dest: String, comparison: Comparison, src1: String, src2: String },
+    Not { dest: String, src: String },
+    ToInt { dest: String, src: String },
+    ToFloat { dest: String, src: String },
+    Box { dest: String, src: String },
+    Unbox { dest: String, src: String, class: runtime::Class },
+    TypeGuard { dest: String, src: String, class: runtime::Class },
+    Truth { dest: String, src: String },
+    MakeTuple { dest: String, items: Vec<String> },
+    Unpack { dest: String, src: String, index: usize, length: usize },
+    MakeList { dest: String, items: Vec<String> },
+    Len { dest: String, src: String },
+    CheckIndex { dest: String, index: String, length: String },
+    GetItem { dest: String, src: String, index: String },
+    SetItem { list: String, index: String, src: String },
+    Jump { target: usize },
+    JumpIfFalse { condition: String, target: usize },
+    Call { dest: String, function: String, arguments: Vec<String> },
+    Return { value: Option<String> },
+    Unreachable,
+    LoadGlobal { dest: String, name: String },
+    StoreGlobal { name: String, src: String },
+    Print { vars: Vec<String> },
 }
 
+/// Translates IR to machine instructions, `__main__` first. Each SSA value
+/// gets a register of its own, and block arguments become copies into the
+/// registers of the block parameters before the jump. Operations pick the
+/// `int`, `i64`, `float` or object instruction by the type of their
+/// operands; the `int` ones check for overflow and fall back to big
+/// integers. The frames of each instruction made from an IR instruction
+/// are kept, inlined functions included, for tracebacks.
 pub struct CodeGenerator {
     instructions: Vec<MachineInstruction>,
+    frames: HashMap<usize, Vec<Frame>>,
     temp_var_counter: usize,
 }
 
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CodeGenerator {
     pub fn new() -> Self {
         CodeGenerator {
             instructions: Vec::new(),
+            frames: HashMap::new(),
             temp_var_counter: 0,
         }
     }
 
-    pub fn generate(&mut self, ir_generator: &IRGenerator) -> Result<Vec<MachineInstruction>, String> {
-        let ir = ir_generator.get_ir();
-        for node in ir {
-            self.visit_node(node)?;
+    pub fn generate(&mut self, module: &Module) -> Result<Vec<MachineInstruction>, String> {
+        let main = module.functions.iter().filter(|function| function.name == MAIN);
+        let others = module.functions.iter().filter(|function| function.name != MAIN);
+        for function in main.chain(others) {
+            self.function(function)?;
         }
         println!("Code generation completed.");
         Ok(self.instructions.clone())
     }
 
-    fn visit_node(&mut self, node: &IR) -> Result<(), String> {
-        match node {
-            IR::VariableDeclaration { name, value } => {
-                self.instructions.push(MachineInstruction::LoadImmediate {
-                    dest: name.clone(),
-                    value: *value,
-                });
+    /// The frames of a traceback through the generated instructions, as
+    /// `Runtime::traceback` gives them, outermost first. An instruction
+    /// in inlined code stands for the frames of the functions inlined.
+    pub fn traceback(&self, instructions: &[usize]) -> Vec<Frame> {
+        Frame::of_source(instructions.iter().flat_map(|index| self.frames.get(index).cloned().unwrap_or_default()).collect())
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), String> {
+        self.instructions.push(MachineInstruction::Function {
+            name: function.name.clone(),
+            parameters: function.params().iter().map(|param| register(*param)).collect(),
+        });
+        let mut starts: HashMap<Block, usize> = HashMap::new();
+        // Jumps whose target is the start of a block, patched at the end.
+        let mut fixups: Vec<(usize, Block)> = Vec::new();
+        for (position, block) in function.layout.iter().enumerate() {
+            starts.insert(*block, self.instructions.len());
+            for inst in &function.block(*block).insts {
+                self.instruction(function, *inst)?;
+            }
+            let next = function.layout.get(position + 1).copied();
+            match function.terminator(*block) {
+                Some(Terminator::Br(target)) => {
+                    self.copies(function, target);
+                    if Some(target.block) != next {
+                        fixups.push((self.instructions.len(), target.block));
+                        self.instructions.push(MachineInstruction::Jump { target: 0 });
+                    }
+                }
+                Some(Terminator::CondBr { condition, then, otherwise }) => {
+                    let branch = self.instructions.len();
+                    self.instructions.push(MachineInstruction::JumpIfFalse { condition: register(*condition), target: 0 });
+                    self.copies(function, then);
+                    fixups.push((self.instructions.len(), then.block));
+                    self.instructions.push(MachineInstruction::Jump { target: 0 });
+                    let else_start = self.instructions.len();
+                    if let MachineInstruction::JumpIfFalse { target, .. } = &mut self.instructions[branch] {
+                        *target = else_start;
+                    }
+                    self.copies(function, otherwise);
+                    if Some(otherwise.block) != next {
+                        fixups.push((self.instructions.len(), otherwise.block));
+                        self.instructions.push(MachineInstruction::Jump { target: 0 });
+                    }
+                }
+                Some(Terminator::Return(value)) => {
+                    self.instructions.push(MachineInstruction::Return { value: value.map(register) });
+                }
+                Some(Terminator::Unreachable) => self.instructions.push(MachineInstruction::Unreachable),
+                None => return Err(format!("Block without a terminator in '{}'", function.name)),
+            }
+        }
+        for (index, block) in fixups {
+            if let MachineInstruction::Jump { target } = &mut self.instructions[index] {
+                *target = starts[&block];
             }
-            IR::BinaryOperation { operator, left, right } => {
-                let left_temp = self.generate_temp_var();
-                let right_temp = self.generate_temp_var();
-                self.visit_node(left)?;
-                self.visit_node(right)?;
-                self.instructions.push(MachineInstruction::LoadImmediate {
-                    dest: left_temp.clone(),
-                    value: self.extract_immediate_value(left)?,
-                });
-                self.instructions.push(MachineInstruction::LoadImmediate {
-                    dest: right_temp.clone(),
-                    value: self.extract_immediate_value(right)?,
-                });
-                let result_var = self.generate_temp_var();
-                let instruction = match operator.as_str() {
-                    "+" => MachineInstruction::Add {
-                        dest: result_var.clone(),
-                        src1: left_temp,
-                        src2: right_temp,
-                    },
-                    "-" => MachineInstruction::Sub {
-                        dest: result_var.clone(),
-                        src1: left_temp,
-                        src2: right_temp,
-                    },
-                    "*" => MachineInstruction::Mul {
-                        dest: result_var.clone(),
-                        src1: left_temp,
-                        src2: right_temp,
-                    },
-                    "/" => MachineInstruction::Div {
-                        dest: result_var.clone(),
-                        src1: left_temp,
-                        src2: right_temp,
-                    },
-                    _ => return Err(format!("Unknown operator: {}", operator)),
+        }
+        Ok(())
+    }
+
+    fn instruction(&mut self, function: &Function, inst: Inst) -> Result<(), String> {
+        let data = function.inst(inst);
+        let dest = data.result.map(register).unwrap_or_default();
+        let instruction = match &data.kind {
+            InstKind::Const(constant) => MachineInstruction::LoadImmediate {
+                dest,
+                value: match constant {
+                    Constant::Int(value) => runtime::Value::Int(*value),
+                    Constant::BigInt(value) => runtime::Value::BigInt(value.clone()),
+                    Constant::Float(value) => runtime::Value::Float(*value),
+                    Constant::Bool(value) => runtime::Value::Bool(*value),
+                    Constant::None => runtime::Value::None,
+                    Constant::Str(value) => runtime::Value::Str(value.as_str().into()),
+                },
+            },
+            InstKind::Unary { op: UnaryOp::Neg, operand } => {
+                let src = register(*operand);
+                match function.value_type(*operand) {
+                    Type::F64 => MachineInstruction::FloatNeg { dest, src },
+                    Type::Object => MachineInstruction::ObjectNeg { dest, src },
+                    Type::I64 => MachineInstruction::I64Neg { dest, src },
+                    _ => MachineInstruction::Neg { dest, src },
+                }
+            }
+            InstKind::Unary { op: UnaryOp::Not, operand } => MachineInstruction::Not { dest, src: register(*operand) },
+            InstKind::Binary { op, left, right } => {
+                let (src1, src2) = (register(*left), register(*right));
+                let arithmetic = match op {
+                    BinaryOp::Add => Arithmetic::Add,
+                    BinaryOp::Sub => Arithmetic::Sub,
+                    BinaryOp::Mul => Arithmetic::Mul,
+                    BinaryOp::Div => Arithmetic::Div,
+                    BinaryOp::FloorDiv => Arithmetic::FloorDiv,
+                    BinaryOp::Mod => Arithmetic::Mod,
+                };
+                match (function.value_type(*left), op) {
+                    (Type::F64, _) => MachineInstruction::FloatArithmetic { dest, arithmetic, src1, src2 },
+                    (Type::Object, _) => MachineInstruction::ObjectArithmetic { dest, arithmetic, src1, src2 },
+                    (Type::I64, BinaryOp::Div) => return Err(format!("True division of i64 values in '{}'", function.name)),
+                    (Type::I64, _) => MachineInstruction::I64Arithmetic { dest, arithmetic, src1, src2 },
+                    (_, BinaryOp::Add) => MachineInstruction::Add { dest, src1, src2 },
+                    (_, BinaryOp::Sub) => MachineInstruction::Sub { dest, src1, src2 },
+                    (_, BinaryOp::Mul) => MachineInstruction::Mul { dest, src1, src2 },
+                    (_, BinaryOp::Div) => MachineInstruction::Div { dest, src1, src2 },
+                    (_, BinaryOp::FloorDiv) => MachineInstruction::FloorDiv { dest, src1, src2 },
+                    (_, BinaryOp::Mod) => MachineInstruction::Mod { dest, src1, src2 },
+                }
+            }
+            InstKind::Compare { op, left, right } => {
+                let comparison = match op {
+                    CompareOp::Eq => Comparison::Equal,
+                    CompareOp::Ne => Comparison::NotEqual,
+                    CompareOp::Lt => Comparison::Less,
+                    CompareOp::Le => Comparison::LessEqual,
+                    CompareOp::Gt => Comparison::Greater,
+                    CompareOp::Ge => Comparison::GreaterEqual,
                 };
-                self.instructions.push(instruction);
+                let (src1, src2) = (register(*left), register(*right));
+                match function.value_type(*left) {
+                    Type::F64 => MachineInstruction::FloatCompare { dest, comparison, src1, src2 },
+                    Type::Object => MachineInstruction::ObjectCompare { dest, comparison, src1, src2 },
+                    _ => MachineInstruction::Compare { dest, comparison, src1, src2 },
+                }
             }
-            IR::Immediate { value, dest } => {
-                self.instructions.push(MachineInstruction::LoadImmediate {
-                    dest: dest.clone(),
-                    value: *value,
-                });
+            InstKind::Cast(value) => match data.result.map(|result| function.value_type(result)) {
+                Some(Type::F64) => MachineInstruction::ToFloat { dest, src: register(*value) },
+                _ => MachineInstruction::ToInt { dest, src: register(*value) },
+            },
+            InstKind::Box(value) => MachineInstruction::Box { dest, src: register(*value) },
+            InstKind::Unbox(value) => {
+                let ty = data.result.map(|result| function.value_type(result)).unwrap_or(Type::Object);
+                let class = Class::of_unboxed(ty).ok_or_else(|| format!("Unboxing into {} in '{}'", ty, function.name))?;
+                MachineInstruction::Unbox { dest, src: register(*value), class: runtime_class(class) }
             }
-            IR::Print { var } => {
-                self.instructions.push(MachineInstruction::Print { var: var.clone() });
+            InstKind::Guard { value, class } => {
+                MachineInstruction::TypeGuard { dest, src: register(*value), class: runtime_class(*class) }
             }
-        }
+            InstKind::Truth(value) => MachineInstruction::Truth { dest, src: register(*value) },
+            InstKind::Tuple(values) => MachineInstruction::MakeTuple { dest, items: values.iter().map(|value| register(*value)).collect() },
+            InstKind::Unpack { tuple, index, length } => {
+                MachineInstruction::Unpack { dest, src: register(*tuple), index: *index, length: *length }
+            }
+            InstKind::List(values) => MachineInstruction::MakeList { dest, items: values.iter().map(|value| register(*value)).collect() },
+            InstKind::Len(value) => MachineInstruction::Len { dest, src: register(*value) },
+            InstKind::CheckIndex { index, length } => {
+                MachineInstruction::CheckIndex { dest, index: register(*index), length: register(*length) }
+            }
+            InstKind::GetItem { list, index } => MachineInstruction::GetItem { dest, src: register(*list), index: register(*index) },
+            InstKind::SetItem { list, index, value } => {
+                MachineInstruction::SetItem { list: register(*list), index: register(*index), src: register(*value) }
+            }
+            InstKind::Call { function, arguments } => MachineInstruction::Call {
+                dest,
+                function: function.clone(),
+                arguments: arguments.iter().map(|argument| register(*argument)).collect(),
+            },
+            InstKind::LoadGlobal(name) => MachineInstruction::LoadGlobal { dest, name: name.clone() },
+            InstKind::StoreGlobal { name, value } => MachineInstruction::StoreGlobal { name: name.clone(), src: register(*value) },
+            InstKind::Print(values) => MachineInstruction::Print { vars: values.iter().map(|value| register(*value)).collect() },
+        };
+        self.frames.insert(self.instructions.len(), function.frames(inst));
+        self.instructions.push(instruction);
         Ok(())
     }
 
-    fn extract_immediate_value(&self, ir: &IR) -> Result<i32, String> {
-        match ir {
-            IR::Immediate { value, .. } => Ok(*value),
-            _ => Err("Expected immediate value".to_string()),
+    /// Copies the arguments of an edge into the parameters of its target.
+    /// The copies happen as if all at once, through temporaries, since a
+    /// parameter may also be an argument.
+    fn copies(&mut self, function: &Function, target: &BlockCall) {
+        let params = &function.block(target.block).params;
+        let moves: Vec<(Value, Value)> =
+            params.iter().zip(&target.arguments).filter(|(param, argument)| param != argument).map(|(p, a)| (*p, *a)).collect();
+        if let [(param, argument)] = moves.as_slice() {
+            self.instructions.push(MachineInstruction::Copy { dest: register(*param), src: register(*argument) });
+            return;
+        }
+        let temps: Vec<String> = moves.iter().map(|_| self.generate_temp_var()).collect();
+        for ((_, argument), temp) in moves.iter().zip(&temps) {
+            self.instructions.push(MachineInstruction::Copy { dest: temp.clone(), src: register(*argument) });
+        }
+        for ((param, _), temp) in moves.iter().zip(temps) {
+            self.instructions.push(MachineInstruction::Copy { dest: register(*param), src: temp });
         }
     }
 
@@ -105,3 +281,71 @@ impl CodeGenerator {
         temp_var
     }
 }
+
+fn register(value: Value) -> String {
+    format!("v{}", value.0)
+}
+
+fn runtime_class(class: Class) -> runtime::Class {
+    match class {
+        Class::Int => runtime::Class::Int,
+        Class::Float => runtime::Class::Float,
+        Class::Bool => runtime::Class::Bool,
+        Class::Str => runtime::Class::Str,
+        Class::NoneType => runtime::Class::NoneType,
+    }
+}
+
+impl From<MachineInstruction> for Instruction {
+    fn from(instruction: MachineInstruction) -> Self {
+        match instruction {
+            MachineInstruction::Function { name, parameters } => Instruction::Function { name, parameters },
+            MachineInstruction::LoadImmediate { dest, value } => Instruction::LoadImmediate { var: dest, value },
+            MachineInstruction::Copy { dest, src } => Instruction::Copy { dest, src },
+            MachineInstruction::Add { dest, src1, src2 } => Instruction::Add { dest, src1, src2 },
+            MachineInstruction::Sub { dest, src1, src2 } => Instruction::Sub { dest, src1, src2 },
+            MachineInstruction::Mul { dest, src1, src2 } => Instruction::Mul { dest, src1, src2 },
+            MachineInstruction::Div { dest, src1, src2 } => Instruction::Div { dest, src1, src2 },
+            MachineInstruction::FloorDiv { dest, src1, src2 } => Instruction::FloorDiv { dest, src1, src2 },
+            MachineInstruction::Mod { dest, src1, src2 } => Instruction::Mod { dest, src1, src2 },
+            MachineInstruction::Neg { dest, src } => Instruction::Neg { dest, src },
+            MachineInstruction::Compare { dest, comparison, src1, src2 } => Instruction::Compare { dest, comparison, src1, src2 },
+            MachineInstruction::I64Arithmetic { dest, arithmetic, src1, src2 } => {
+                Instruction::I64Arithmetic { dest, arithmetic, src1, src2 }
+            }
+            MachineInstruction::I64Neg { dest, src } => Instruction::I64Neg { dest, src },
+            MachineInstruction::FloatArithmetic { dest, arithmetic, src1, src2 } => {
+                Instruction::FloatArithmetic { dest, arithmetic, src1, src2 }
+            }
+            MachineInstruction::FloatNeg { dest, src } => Instruction::FloatNeg { dest, src },
+            MachineInstruction::FloatCompare { dest, comparison, src1, src2 } => Instruction::FloatCompare { dest, comparison, src1, src2 },
+            MachineInstruction::ObjectArithmetic { dest, arithmetic, src1, src2 } => {
+                Instruction::ObjectArithmetic { dest, arithmetic, src1, src2 }
+            }
+            MachineInstruction::ObjectNeg { dest, src } => Instruction::ObjectNeg { dest, src },
+            MachineInstruction::ObjectCompare { dest, comparison, src1, src2 } => Instruction::ObjectCompare { dest, comparison, src1, src2 },
+            MachineInstruction::Not { dest, src } => Instruction::Not { dest, src },
+            MachineInstruction::ToInt { dest, src } => Instruction::ToInt { dest, src },
+            MachineInstruction::ToFloat { dest, src } => Instruction::ToFloat { dest, src },
+            MachineInstruction::Box { dest, src } => Instruction::Box { dest, src },
+            MachineInstruction::Unbox { dest, src, class } => Instruction::Unbox { dest, src, class },
+            MachineInstruction::TypeGuard { dest, src, class } => Instruction::TypeGuard { dest, src, class },
+            MachineInstruction::Truth { dest, src } => Instruction::Truth { dest, src },
+            MachineInstruction::MakeTuple { dest, items } => Instruction::MakeTuple { dest, items },
+            MachineInstruction::Unpack { dest, src, index, length } => Instruction::Unpack { dest, src, index, length },
+            MachineInstruction::MakeList { dest, items } => Instruction::MakeList { dest, items },
+            MachineInstruction::Len { dest, src } => Instruction::Len { dest, src },
+            MachineInstruction::CheckIndex { dest, index, length } => Instruction::CheckIndex { dest, index, length },
+            MachineInstruction::GetItem { dest, src, index } => Instruction::GetItem { dest, src, index },
+            MachineInstruction::SetItem { list, index, src } => Instruction::SetItem { list, index, src },
+            MachineInstruction::Jump { target } => Instruction::Jump { target },
+            MachineInstruction::JumpIfFalse { condition, target } => Instruction::JumpIfFalse { condition, target },
+            MachineInstruction::Call { dest, function, arguments } => Instruction::Call { dest, function, arguments },
+            MachineInstruction::Return { value } => Instruction::Return { value },
+            MachineInstruction::Unreachable => Instruction::Unreachable,
+            MachineInstruction::LoadGlobal { dest, name } => Instruction::LoadGlobal { dest, name },
+            MachineInstruction::StoreGlobal { name, src } => Instruction::StoreGlobal { name, src },
+            MachineInstruction::Print { vars } => Instruction::Print { vars },
+        }
+    }
+}
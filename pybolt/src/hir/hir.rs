@@ -0,0 +1,524 @@
+use std::fmt;
+
+use crate::hir::visitor::for_each_child;
+use crate::lexer::lexer::Span;
+use crate::parser::parser::ParameterKind;
+use crate::parser::serialize::{python_bytes_repr, python_string_repr};
+use crate::runtime::bigint::BigInt;
+use crate::semantic::scope::{ScopeId, SymbolKind};
+use crate::semantic::types::Type;
+
+/// The high-level IR of a module: the AST after semantic analysis, with
+/// every name resolved to its symbol, every expression typed, and the
+/// constructs that only exist for convenience desugared.
+///
+/// - `x += v` is an assignment of an in-place binary operation. An attribute
+///   or subscript target is evaluated once into temporaries.
+/// - `def` and `class` statements are assignments of a function or class
+///   object, passed through the decorators from the innermost outwards.
+///   Lambdas are functions that return their body.
+/// - A comprehension is a call of a nested function, which gets the first
+///   iterable as its argument and builds the result in a loop. A generator
+///   expression is a generator function that yields the elements.
+/// - `with` is a `try` that calls `__enter__` and `__exit__` (PEP 343).
+/// - `global`, `nonlocal`, `pass` and annotations without a value are gone;
+///   their effect is in the symbols and types.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub scope: ScopeId,
+    pub body: Vec<Stmt>,
+    /// Number of temporaries used by the module body.
+    pub temporaries: usize,
+}
+
+/// A name resolved to where it lives at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// The scope the name appears in.
+    pub scope: ScopeId,
+    pub kind: SymbolKind,
+    /// The fast local slot or closure index, as in the symbol table.
+    pub slot: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    /// The `def` name, `<lambda>`, `<listcomp>`, `<setcomp>` or `<genexpr>`.
+    pub name: String,
+    pub scope: ScopeId,
+    pub parameters: Vec<Param>,
+    pub returns: Type,
+    pub body: Vec<Stmt>,
+    /// Locals captured by nested functions, in closure order.
+    pub cells: Vec<String>,
+    /// Variables of enclosing functions, in closure order.
+    pub frees: Vec<String>,
+    pub temporaries: usize,
+    pub is_generator: bool,
+    pub span: Span,
+}
+
+/// A parameter. Its target is a temporary for the iterable parameter of a
+/// desugared comprehension.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub target: Target,
+    pub kind: ParameterKind,
+    pub ty: Type,
+    pub default: Option<Expr>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Class {
+    pub name: String,
+    pub scope: ScopeId,
+    pub bases: Vec<Expr>,
+    pub keywords: Vec<Keyword>,
+    pub body: Vec<Stmt>,
+    pub temporaries: usize,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum StmtKind {
+    Expr(Expr),
+    /// `a = b = value`: the value is assigned to each target from left to
+    /// right.
+    Assign { targets: Vec<Target>, value: Expr },
+    If { condition: Expr, body: Vec<Stmt>, orelse: Vec<Stmt> },
+    While { condition: Expr, body: Vec<Stmt>, orelse: Vec<Stmt> },
+    For { target: Target, iterable: Expr, body: Vec<Stmt>, orelse: Vec<Stmt> },
+    Return(Option<Expr>),
+    Try { body: Vec<Stmt>, handlers: Vec<Handler>, orelse: Vec<Stmt>, finalbody: Vec<Stmt> },
+    /// `raise` with no exception re-raises the one being handled.
+    Raise { exception: Option<Expr>, cause: Option<Expr> },
+    /// `from module import *`.
+    ImportStar { module: String },
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone)]
+pub struct Handler {
+    pub exception_type: Option<Expr>,
+    pub target: Option<Target>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub enum Target {
+    Name(Symbol),
+    Temp(usize),
+    Attribute { value: Box<Expr>, attribute: String },
+    Subscript { value: Box<Expr>, index: Box<Expr> },
+    /// Unpacking into a tuple or list of targets.
+    Tuple(Vec<Target>),
+    Starred(Box<Target>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
+    Int(i64),
+    /// An int literal that does not fit in an `i64`.
+    BigInt(BigInt),
+    Float(f64),
+    /// An imaginary literal, by its imaginary part.
+    Imaginary(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    /// An f-string, by its parts: `Str`s and `FormattedValue`s.
+    JoinedStr(Vec<Expr>),
+    /// A replacement field of an f-string; the format spec is a `JoinedStr`.
+    FormattedValue { value: Box<Expr>, conversion: Option<char>, format_spec: Option<Box<Expr>> },
+    Bool(bool),
+    None,
+    Ellipsis,
+    Name(Symbol),
+    /// A value the compiler introduced while desugaring.
+    Temp(usize),
+    Unary { operator: String, operand: Box<Expr> },
+    /// `inplace` is set for the operation of an augmented assignment, which
+    /// tries `__iadd__` and friends first.
+    Binary { left: Box<Expr>, operator: String, right: Box<Expr>, inplace: bool },
+    BoolOp { operator: String, values: Vec<Expr> },
+    Compare { left: Box<Expr>, operators: Vec<String>, comparators: Vec<Expr> },
+    Conditional { condition: Box<Expr>, body: Box<Expr>, orelse: Box<Expr> },
+    Call { function: Box<Expr>, arguments: Vec<Expr>, keywords: Vec<Keyword> },
+    Attribute { value: Box<Expr>, attribute: String },
+    Subscript { value: Box<Expr>, index: Box<Expr> },
+    Slice { lower: Option<Box<Expr>>, upper: Option<Box<Expr>>, step: Option<Box<Expr>> },
+    Starred(Box<Expr>),
+    List(Vec<Expr>),
+    Tuple(Vec<Expr>),
+    Set(Vec<Expr>),
+    Dict(Vec<(Expr, Expr)>),
+    /// Creates a function object; default values are evaluated here.
+    Function(Box<Function>),
+    /// Runs a class body and creates the class.
+    Class(Box<Class>),
+    /// Imports a module. With `top_level`, evaluates to the top-level
+    /// package, as `import a.b` binds `a`.
+    Import { module: String, top_level: bool },
+    Yield(Box<Expr>),
+}
+
+/// A `name=value` argument; `name` is `None` for `**mapping`.
+#[derive(Debug, Clone)]
+pub struct Keyword {
+    pub name: Option<String>,
+    pub value: Expr,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, ty: Type) -> Self {
+        Expr { kind, ty }
+    }
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+// ----- Printing ---------------------------------------------------------------
+//
+// The textual form is Python-like. Names show how they were resolved
+// (`x@local0`, `n@free1`, `len@builtin`), temporaries are `$0`, `$1`, ...,
+// and assignments and expression statements end with the type of their value.
+// Functions and classes are printed after the statement that creates them,
+// one level deeper.
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_block(f, &self.body, 0)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            SymbolKind::Local => "local",
+            SymbolKind::Cell => "cell",
+            SymbolKind::Free => "free",
+            SymbolKind::Global => "global",
+            SymbolKind::Builtin => "builtin",
+        };
+        write!(f, "{}@{}", self.name, kind)?;
+        if let Some(slot) = self.slot {
+            write!(f, "{}", slot)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Name(symbol) => write!(f, "{}", symbol),
+            Target::Temp(index) => write!(f, "${}", index),
+            Target::Attribute { value, attribute } => write!(f, "{}.{}", Operand(value), attribute),
+            Target::Subscript { value, index } => write!(f, "{}[{}]", Operand(value), index),
+            Target::Tuple(elements) => {
+                let elements: Vec<String> = elements.iter().map(ToString::to_string).collect();
+                write!(f, "({})", elements.join(", "))
+            }
+            Target::Starred(inner) => write!(f, "*{}", inner),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExprKind::Int(value) => write!(f, "{}", value),
+            ExprKind::BigInt(value) => write!(f, "{}", value),
+            ExprKind::Float(value) => write!(f, "{:?}", value),
+            ExprKind::Imaginary(value) => write!(f, "{:?}j", value),
+            ExprKind::Str(value) => write!(f, "{}", python_string_repr(value)),
+            ExprKind::Bytes(value) => write!(f, "{}", python_bytes_repr(value)),
+            ExprKind::JoinedStr(parts) => {
+                write!(f, "f\"")?;
+                write_fstring_parts(f, parts)?;
+                write!(f, "\"")
+            }
+            ExprKind::FormattedValue { .. } => write_fstring_parts(f, std::slice::from_ref(self)),
+            ExprKind::Bool(true) => write!(f, "True"),
+            ExprKind::Bool(false) => write!(f, "False"),
+            ExprKind::None => write!(f, "None"),
+            ExprKind::Ellipsis => write!(f, "..."),
+            ExprKind::Name(symbol) => write!(f, "{}", symbol),
+            ExprKind::Temp(index) => write!(f, "${}", index),
+            ExprKind::Unary { operator, operand } => {
+                let space = if operator == "not" { " " } else { "" };
+                write!(f, "{}{}{}", operator, space, Operand(operand))
+            }
+            ExprKind::Binary { left, operator, right, inplace } => {
+                let assign = if *inplace { "=" } else { "" };
+                write!(f, "{} {}{} {}", Operand(left), operator, assign, Operand(right))
+            }
+            ExprKind::BoolOp { operator, values } => {
+                let values: Vec<String> = values.iter().map(|value| Operand(value).to_string()).collect();
+                write!(f, "{}", values.join(&format!(" {} ", operator)))
+            }
+            ExprKind::Compare { left, operators, comparators } => {
+                write!(f, "{}", Operand(left))?;
+                for (operator, comparator) in operators.iter().zip(comparators) {
+                    write!(f, " {} {}", operator, Operand(comparator))?;
+                }
+                Ok(())
+            }
+            ExprKind::Conditional { condition, body, orelse } => {
+                write!(f, "{} if {} else {}", Operand(body), Operand(condition), Operand(orelse))
+            }
+            ExprKind::Call { function, arguments, keywords } => {
+                let mut parts: Vec<String> = arguments.iter().map(ToString::to_string).collect();
+                parts.extend(keywords.iter().map(|keyword| match &keyword.name {
+                    Some(name) => format!("{}={}", name, keyword.value),
+                    None => format!("**{}", keyword.value),
+                }));
+                write!(f, "{}({})", Operand(function), parts.join(", "))
+            }
+            ExprKind::Attribute { value, attribute } => write!(f, "{}.{}", Operand(value), attribute),
+            ExprKind::Subscript { value, index } => write!(f, "{}[{}]", Operand(value), index),
+            ExprKind::Slice { lower, upper, step } => {
+                for (index, part) in [lower, upper, step].into_iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ":")?;
+                    }
+                    if let Some(part) = part {
+                        write!(f, "{}", part)?;
+                    }
+                }
+                Ok(())
+            }
+            ExprKind::Starred(value) => write!(f, "*{}", Operand(value)),
+            ExprKind::List(elements) => write!(f, "[{}]", join(elements)),
+            ExprKind::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            ExprKind::Tuple(elements) => write!(f, "({})", join(elements)),
+            // `{}` is an empty dict; `{*()}` is Python's empty set literal.
+            ExprKind::Set(elements) if elements.is_empty() => write!(f, "{{*()}}"),
+            ExprKind::Set(elements) => write!(f, "{{{}}}", join(elements)),
+            ExprKind::Dict(entries) => {
+                let entries: Vec<String> = entries.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+            ExprKind::Function(function) => write!(f, "<def {} [scope {}]>", function.name, function.scope),
+            ExprKind::Class(class) => write!(f, "<class {} [scope {}]>", class.name, class.scope),
+            ExprKind::Import { module, top_level: true } => write!(f, "__import__({})", python_string_repr(module)),
+            ExprKind::Import { module, top_level: false } => write!(f, "import_module({})", python_string_repr(module)),
+            ExprKind::Yield(value) => write!(f, "yield {}", value),
+        }
+    }
+}
+
+/// Parenthesizes compound expressions used as operands.
+struct Operand<'a>(&'a Expr);
+
+impl fmt::Display for Operand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.kind {
+            ExprKind::Unary { .. }
+            | ExprKind::Binary { .. }
+            | ExprKind::BoolOp { .. }
+            | ExprKind::Compare { .. }
+            | ExprKind::Conditional { .. }
+            | ExprKind::Yield(_) => write!(f, "({})", self.0),
+            _ => write!(f, "{}", self.0),
+        }
+    }
+}
+
+fn join(elements: &[Expr]) -> String {
+    elements.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// The parts of an f-string between its quotes: the text, with its braces
+/// doubled, and the replacement fields.
+fn write_fstring_parts(f: &mut fmt::Formatter<'_>, parts: &[Expr]) -> fmt::Result {
+    for part in parts {
+        match &part.kind {
+            ExprKind::Str(text) => {
+                let quoted = python_string_repr(text);
+                let escaped = quoted[1..quoted.len() - 1].replace('"', "\\\"");
+                write!(f, "{}", escaped.replace('{', "{{").replace('}', "}}"))?;
+            }
+            ExprKind::FormattedValue { value, conversion, format_spec } => {
+                write!(f, "{{{}", value)?;
+                if let Some(conversion) = conversion {
+                    write!(f, "!{}", conversion)?;
+                }
+                if let Some(format_spec) = format_spec {
+                    write!(f, ":")?;
+                    if let ExprKind::JoinedStr(parts) = &format_spec.kind {
+                        write_fstring_parts(f, parts)?;
+                    }
+                }
+                write!(f, "}}")?;
+            }
+            _ => write!(f, "{{{}}}", part)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_block(f: &mut fmt::Formatter<'_>, statements: &[Stmt], indent: usize) -> fmt::Result {
+    if statements.is_empty() {
+        return writeln!(f, "{:indent$}pass", "", indent = indent);
+    }
+    for statement in statements {
+        write_statement(f, statement, indent)?;
+    }
+    Ok(())
+}
+
+fn write_statement(f: &mut fmt::Formatter<'_>, statement: &Stmt, indent: usize) -> fmt::Result {
+    let pad = " ".repeat(indent);
+    match &statement.kind {
+        StmtKind::Expr(expr) => {
+            writeln!(f, "{}{}  # {}", pad, expr, expr.ty)?;
+            write_definitions(f, expr, indent + 4)
+        }
+        StmtKind::Assign { targets, value } => {
+            let targets: Vec<String> = targets.iter().map(ToString::to_string).collect();
+            writeln!(f, "{}{} = {}  # {}", pad, targets.join(" = "), value, value.ty)?;
+            write_definitions(f, value, indent + 4)
+        }
+        StmtKind::If { condition, body, orelse } => {
+            writeln!(f, "{}if {}:", pad, condition)?;
+            write_definitions(f, condition, indent + 4)?;
+            write_block(f, body, indent + 4)?;
+            write_else(f, orelse, indent)
+        }
+        StmtKind::While { condition, body, orelse } => {
+            writeln!(f, "{}while {}:", pad, condition)?;
+            write_definitions(f, condition, indent + 4)?;
+            write_block(f, body, indent + 4)?;
+            write_else(f, orelse, indent)
+        }
+        StmtKind::For { target, iterable, body, orelse } => {
+            writeln!(f, "{}for {} in {}:", pad, target, iterable)?;
+            write_definitions(f, iterable, indent + 4)?;
+            write_block(f, body, indent + 4)?;
+            write_else(f, orelse, indent)
+        }
+        StmtKind::Return(Some(value)) => {
+            writeln!(f, "{}return {}  # {}", pad, value, value.ty)?;
+            write_definitions(f, value, indent + 4)
+        }
+        StmtKind::Return(None) => writeln!(f, "{}return", pad),
+        StmtKind::Try { body, handlers, orelse, finalbody } => {
+            writeln!(f, "{}try:", pad)?;
+            write_block(f, body, indent + 4)?;
+            for handler in handlers {
+                match (&handler.exception_type, &handler.target) {
+                    (Some(exception_type), Some(target)) => writeln!(f, "{}except {} as {}:", pad, exception_type, target)?,
+                    (Some(exception_type), None) => writeln!(f, "{}except {}:", pad, exception_type)?,
+                    (None, Some(target)) => writeln!(f, "{}except as {}:", pad, target)?,
+                    (None, None) => writeln!(f, "{}except:", pad)?,
+                }
+                write_block(f, &handler.body, indent + 4)?;
+            }
+            write_else(f, orelse, indent)?;
+            if !finalbody.is_empty() {
+                writeln!(f, "{}finally:", pad)?;
+                write_block(f, finalbody, indent + 4)?;
+            }
+            Ok(())
+        }
+        StmtKind::Raise { exception, cause } => {
+            write!(f, "{}raise", pad)?;
+            if let Some(exception) = exception {
+                write!(f, " {}", exception)?;
+            }
+            if let Some(cause) = cause {
+                write!(f, " from {}", cause)?;
+            }
+            writeln!(f)
+        }
+        StmtKind::ImportStar { module } => writeln!(f, "{}from {} import *", pad, module),
+        StmtKind::Break => writeln!(f, "{}break", pad),
+        StmtKind::Continue => writeln!(f, "{}continue", pad),
+    }
+}
+
+fn write_else(f: &mut fmt::Formatter<'_>, orelse: &[Stmt], indent: usize) -> fmt::Result {
+    if orelse.is_empty() {
+        return Ok(());
+    }
+    writeln!(f, "{:indent$}else:", "", indent = indent)?;
+    write_block(f, orelse, indent + 4)
+}
+
+/// Prints the functions and classes created by an expression.
+fn write_definitions(f: &mut fmt::Formatter<'_>, expr: &Expr, indent: usize) -> fmt::Result {
+    struct Definitions<'a>(Vec<&'a Expr>);
+
+    impl<'a> Definitions<'a> {
+        fn collect(&mut self, expr: &'a Expr) {
+            match &expr.kind {
+                ExprKind::Function(_) | ExprKind::Class(_) => {
+                    for_each_child(expr, |child| self.collect(child));
+                    self.0.push(expr);
+                }
+                _ => for_each_child(expr, |child| self.collect(child)),
+            }
+        }
+    }
+
+    let mut definitions = Definitions(Vec::new());
+    definitions.collect(expr);
+    for definition in definitions.0 {
+        let pad = " ".repeat(indent);
+        match &definition.kind {
+            ExprKind::Function(function) => {
+                let parameters: Vec<String> = function.parameters.iter().map(parameter_string).collect();
+                write!(f, "{}def {}({}) -> {} [scope {}", pad, function.name, parameters.join(", "), function.returns, function.scope)?;
+                if !function.cells.is_empty() {
+                    write!(f, ", cells: {}", function.cells.join(", "))?;
+                }
+                if !function.frees.is_empty() {
+                    write!(f, ", frees: {}", function.frees.join(", "))?;
+                }
+                if function.is_generator {
+                    write!(f, ", generator")?;
+                }
+                writeln!(f, "]:")?;
+                write_block(f, &function.body, indent + 4)?;
+            }
+            ExprKind::Class(class) => {
+                writeln!(f, "{}class {} [scope {}]:", pad, class.name, class.scope)?;
+                write_block(f, &class.body, indent + 4)?;
+            }
+            _ => unreachable!("only definitions are collected"),
+        }
+    }
+    Ok(())
+}
+
+fn parameter_string(parameter: &Param) -> String {
+    let prefix = match parameter.kind {
+        ParameterKind::VarPositional => "*",
+        ParameterKind::VarKeyword => "**",
+        _ => "",
+    };
+    let mut out = format!("{}{}: {}", prefix, parameter.target, parameter.ty);
+    if let Some(default) = &parameter.default {
+        out.push_str(&format!(" = {}", default));
+    }
+    out
+}
@@ -0,0 +1,677 @@
+use crate::hir::hir::{Class, Expr, ExprKind, Function, Handler, Keyword, Module, Param, Stmt, StmtKind, Symbol, Target};
+use crate::lexer::lexer::Span;
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler, FunctionDefinition,
+    ImportAlias, KeywordArgument, Parameter, ParameterKind, WithItem,
+};
+use crate::parser::visitor::{walk_parameters, Visitor};
+use crate::semantic::builtins::is_builtin;
+use crate::semantic::checker::TypeInfo;
+use crate::semantic::scope::{ScopeId, SymbolKind, SymbolTable};
+use crate::semantic::types::Type;
+
+/// Lowers a checked program to HIR.
+///
+/// Nested scopes are paired with the symbol table by walking the AST in the
+/// order the symbol table was built: each `def`, `class`, `lambda` and
+/// comprehension takes the next child scope of the scope it appears in.
+pub struct Lowerer<'a> {
+    table: &'a SymbolTable,
+    types: &'a TypeInfo,
+    /// The scopes being lowered, innermost last, with how many of their
+    /// child scopes have been entered.
+    scopes: Vec<(ScopeId, usize)>,
+    /// The temporaries used so far by each body being lowered.
+    temporaries: Vec<usize>,
+    span: Span,
+}
+
+impl<'a> Lowerer<'a> {
+    /// `program` must be the node the symbol table and types were built
+    /// from, as expression types are looked up by node.
+    pub fn lower(program: &ASTNode, table: &'a SymbolTable, types: &'a TypeInfo) -> Module {
+        let mut lowerer =
+            Lowerer { table, types, scopes: vec![(table.root(), 0)], temporaries: vec![0], span: Span::default() };
+        let body = match program {
+            ASTNode::Program(statements) => lowerer.block(statements),
+            other => lowerer.block(std::slice::from_ref(other)),
+        };
+        Module { scope: table.root(), body, temporaries: lowerer.temporaries[0] }
+    }
+
+    // ----- Scopes -----------------------------------------------------------
+
+    fn scope(&self) -> ScopeId {
+        self.scopes.last().expect("the module scope is never left").0
+    }
+
+    /// The scope of the next `def`, `class`, `lambda` or comprehension.
+    fn next_scope(&mut self) -> ScopeId {
+        let (current, entered) = self.scopes.last_mut().expect("the module scope is never left");
+        let scope = self.table.scope(*current).children[*entered];
+        *entered += 1;
+        scope
+    }
+
+    /// Runs `body` in `scope`, returning its result and the number of
+    /// temporaries it used.
+    fn in_scope<T>(&mut self, scope: ScopeId, body: impl FnOnce(&mut Self) -> T) -> (T, usize) {
+        self.scopes.push((scope, 0));
+        self.temporaries.push(0);
+        let result = body(self);
+        self.scopes.pop();
+        (result, self.temporaries.pop().unwrap_or_default())
+    }
+
+    /// Skips an expression that is not lowered, such as an annotation, while
+    /// keeping count of the scopes inside it.
+    fn skip(&mut self, node: &ASTNode) {
+        let mut counter = ScopeCounter(0);
+        counter.visit_node(node);
+        if let Some((_, entered)) = self.scopes.last_mut() {
+            *entered += counter.0;
+        }
+    }
+
+    fn temp(&mut self) -> usize {
+        let count = self.temporaries.last_mut().expect("the module body is never left");
+        *count += 1;
+        *count - 1
+    }
+
+    fn symbol(&self, name: &str) -> Symbol {
+        let scope = self.scope();
+        let (kind, slot) = match self.table.lookup(scope, name) {
+            Some(symbol) => (symbol.kind, symbol.slot),
+            None if is_builtin(name) => (SymbolKind::Builtin, None),
+            None => (SymbolKind::Global, None),
+        };
+        Symbol { name: name.to_string(), scope, kind, slot }
+    }
+
+    fn type_of(&self, node: &ASTNode) -> Type {
+        self.types.expression(node).cloned().unwrap_or(Type::Any)
+    }
+
+    fn stmt(&self, kind: StmtKind) -> Stmt {
+        Stmt::new(kind, self.span)
+    }
+
+    // ----- Statements -------------------------------------------------------
+
+    fn block(&mut self, statements: &[ASTNode]) -> Vec<Stmt> {
+        let mut out = Vec::new();
+        for statement in statements {
+            let outer = self.span;
+            self.span = statement.span().unwrap_or(outer);
+            self.statement(statement, &mut out);
+            self.span = outer;
+        }
+        out
+    }
+
+    fn statement(&mut self, statement: &ASTNode, out: &mut Vec<Stmt>) {
+        match statement {
+            ASTNode::Program(statements) => out.extend(self.block(statements)),
+            ASTNode::VariableDeclaration { name, value, .. } => {
+                let value = self.expr(value);
+                out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Name(self.symbol(name))], value }));
+            }
+            ASTNode::ExpressionStatement { expression, .. } => {
+                let expr = self.expr(expression);
+                out.push(self.stmt(StmtKind::Expr(expr)));
+            }
+            ASTNode::Assignment { targets, value, .. } => {
+                let value = self.expr(value);
+                let targets = targets.iter().map(|target| self.target(target)).collect();
+                out.push(self.stmt(StmtKind::Assign { targets, value }));
+            }
+            ASTNode::AugmentedAssignment { target, operator, value, .. } => {
+                self.augmented_assignment(statement, target, operator, value, out)
+            }
+            ASTNode::AnnotatedAssignment { target, annotation, value, .. } => {
+                self.skip(annotation);
+                match value {
+                    Some(value) => {
+                        let value = self.expr(value);
+                        let targets = vec![self.target(target)];
+                        out.push(self.stmt(StmtKind::Assign { targets, value }));
+                    }
+                    None => self.skip(target),
+                }
+            }
+            ASTNode::If { condition, body, orelse, .. } => {
+                let condition = self.expr(condition);
+                let body = self.block(body);
+                let orelse = self.block(orelse);
+                out.push(self.stmt(StmtKind::If { condition, body, orelse }));
+            }
+            ASTNode::While { condition, body, orelse, .. } => {
+                let condition = self.expr(condition);
+                let body = self.block(body);
+                let orelse = self.block(orelse);
+                out.push(self.stmt(StmtKind::While { condition, body, orelse }));
+            }
+            ASTNode::For { target, iterable, body, orelse, .. } => {
+                let iterable = self.expr(iterable);
+                let target = self.target(target);
+                let body = self.block(body);
+                let orelse = self.block(orelse);
+                out.push(self.stmt(StmtKind::For { target, iterable, body, orelse }));
+            }
+            ASTNode::FunctionDefinition(function) => {
+                let value = self.function_definition(function);
+                out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Name(self.symbol(&function.name))], value }));
+            }
+            ASTNode::ClassDefinition(class) => {
+                let value = self.class_definition(class);
+                out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Name(self.symbol(&class.name))], value }));
+            }
+            ASTNode::Return { value, .. } => {
+                let value = value.as_ref().map(|value| self.expr(value));
+                out.push(self.stmt(StmtKind::Return(value)));
+            }
+            ASTNode::Try { body, handlers, orelse, finalbody, .. } => {
+                let body = self.block(body);
+                let handlers = handlers.iter().map(|handler| self.handler(handler)).collect();
+                let orelse = self.block(orelse);
+                let finalbody = self.block(finalbody);
+                out.push(self.stmt(StmtKind::Try { body, handlers, orelse, finalbody }));
+            }
+            ASTNode::Raise { exception, cause, .. } => {
+                let exception = exception.as_ref().map(|exception| self.expr(exception));
+                let cause = cause.as_ref().map(|cause| self.expr(cause));
+                out.push(self.stmt(StmtKind::Raise { exception, cause }));
+            }
+            ASTNode::With { items, body, .. } => self.with_statement(items, body, out),
+            ASTNode::Import { names, .. } => {
+                for name in names {
+                    let module = ExprKind::Import { module: name.name.clone(), top_level: name.alias.is_none() };
+                    let value = Expr::new(module, Type::Any);
+                    out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Name(self.symbol(name.bound_name()))], value }));
+                }
+            }
+            ASTNode::ImportFrom { module, names, .. } => self.import_from(module, names, out),
+            ASTNode::Break { .. } => out.push(self.stmt(StmtKind::Break)),
+            ASTNode::Continue { .. } => out.push(self.stmt(StmtKind::Continue)),
+            ASTNode::Global { .. } | ASTNode::Nonlocal { .. } | ASTNode::Pass { .. } => {}
+            expression => {
+                let expr = self.expr(expression);
+                out.push(self.stmt(StmtKind::Expr(expr)));
+            }
+        }
+    }
+
+    /// `target op= value`. The object and index of an attribute or subscript
+    /// target are evaluated once, into temporaries.
+    fn augmented_assignment(&mut self, statement: &ASTNode, target: &ASTNode, operator: &str, value: &ASTNode, out: &mut Vec<Stmt>) {
+        let operator = operator.trim_end_matches('=').to_string();
+        let current = self.type_of(target);
+        let (left, target) = match target {
+            ASTNode::Attribute { value: object, attribute } => {
+                let object = self.expr(object);
+                let object = self.hold(object, out);
+                let left = Expr::new(ExprKind::Attribute { value: Box::new(object.clone()), attribute: attribute.clone() }, current);
+                (left, Target::Attribute { value: Box::new(object), attribute: attribute.clone() })
+            }
+            ASTNode::Subscript { value: object, index } => {
+                let object = self.expr(object);
+                let object = self.hold(object, out);
+                let index = self.expr(index);
+                let index = self.hold(index, out);
+                let left = Expr::new(
+                    ExprKind::Subscript { value: Box::new(object.clone()), index: Box::new(index.clone()) },
+                    current,
+                );
+                (left, Target::Subscript { value: Box::new(object), index: Box::new(index) })
+            }
+            name => (self.expr(name), self.target(name)),
+        };
+        let right = self.expr(value);
+        let result = ExprKind::Binary { left: Box::new(left), operator, right: Box::new(right), inplace: true };
+        let value = Expr::new(result, self.type_of(statement));
+        out.push(self.stmt(StmtKind::Assign { targets: vec![target], value }));
+    }
+
+    /// Stores a value in a new temporary, returning the temporary.
+    fn hold(&mut self, value: Expr, out: &mut Vec<Stmt>) -> Expr {
+        let temp = self.temp();
+        let ty = value.ty.clone();
+        out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Temp(temp)], value }));
+        Expr::new(ExprKind::Temp(temp), ty)
+    }
+
+    /// `with a as x, b: body` is `with a as x: with b: body`, and each `with`
+    /// follows PEP 343:
+    ///
+    /// ```text
+    /// $manager = a
+    /// $exit = $manager.__exit__
+    /// $value = $manager.__enter__()
+    /// $ok = True
+    /// try:
+    ///     try:
+    ///         x = $value
+    ///         body
+    ///     except:
+    ///         $ok = False
+    ///         if not $exit($error.__class__, $error, $error.__traceback__):
+    ///             raise
+    /// finally:
+    ///     if $ok:
+    ///         $exit(None, None, None)
+    /// ```
+    fn with_statement(&mut self, items: &[WithItem], body: &[ASTNode], out: &mut Vec<Stmt>) {
+        let Some((item, rest)) = items.split_first() else {
+            out.extend(self.block(body));
+            return;
+        };
+        let manager = self.expr(&item.context);
+        let entered = match &manager.ty {
+            Type::Instance(class) => match self.types.context().member(class, "__enter__") {
+                Some(Type::Callable(signature)) => signature.returns,
+                _ => Type::Any,
+            },
+            _ => Type::Any,
+        };
+        let manager = self.hold(manager, out);
+        let exit = self.hold(attribute(manager.clone(), "__exit__"), out);
+        let value = self.hold(Expr::new(call(attribute(manager, "__enter__"), Vec::new()).kind, entered), out);
+        let ok = self.temp();
+        out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Temp(ok)], value: constant(ExprKind::Bool(true)) }));
+
+        let mut inner = Vec::new();
+        if let Some(target) = &item.target {
+            let target = self.target(target);
+            inner.push(self.stmt(StmtKind::Assign { targets: vec![target], value }));
+        }
+        self.with_statement(rest, body, &mut inner);
+
+        let error = self.temp();
+        let error_value = Expr::new(ExprKind::Temp(error), Type::Any);
+        let arguments =
+            vec![attribute(error_value.clone(), "__class__"), error_value.clone(), attribute(error_value, "__traceback__")];
+        let suppressed = call(exit.clone(), arguments);
+        let handler = Handler {
+            exception_type: None,
+            target: Some(Target::Temp(error)),
+            body: vec![
+                self.stmt(StmtKind::Assign { targets: vec![Target::Temp(ok)], value: constant(ExprKind::Bool(false)) }),
+                self.stmt(StmtKind::If {
+                    condition: Expr::new(ExprKind::Unary { operator: "not".to_string(), operand: Box::new(suppressed) }, Type::Bool),
+                    body: vec![self.stmt(StmtKind::Raise { exception: None, cause: None })],
+                    orelse: Vec::new(),
+                }),
+            ],
+            span: self.span,
+        };
+        let inner = self.stmt(StmtKind::Try { body: inner, handlers: vec![handler], orelse: Vec::new(), finalbody: Vec::new() });
+        let none = || constant(ExprKind::None);
+        let finalbody = vec![self.stmt(StmtKind::If {
+            condition: Expr::new(ExprKind::Temp(ok), Type::Bool),
+            body: vec![self.stmt(StmtKind::Expr(call(exit, vec![none(), none(), none()])))],
+            orelse: Vec::new(),
+        })];
+        out.push(self.stmt(StmtKind::Try { body: vec![inner], handlers: Vec::new(), orelse: Vec::new(), finalbody }));
+    }
+
+    fn import_from(&mut self, module: &str, names: &[ImportAlias], out: &mut Vec<Stmt>) {
+        if names.iter().any(|name| name.name == "*") {
+            out.push(self.stmt(StmtKind::ImportStar { module: module.to_string() }));
+            return;
+        }
+        let mut source = Expr::new(ExprKind::Import { module: module.to_string(), top_level: false }, Type::Any);
+        if names.len() > 1 {
+            source = self.hold(source, out);
+        }
+        for name in names {
+            let value = attribute(source.clone(), &name.name);
+            out.push(self.stmt(StmtKind::Assign { targets: vec![Target::Name(self.symbol(name.bound_name()))], value }));
+        }
+    }
+
+    fn handler(&mut self, handler: &ExceptHandler) -> Handler {
+        let outer = std::mem::replace(&mut self.span, handler.span);
+        let exception_type = handler.exception_type.as_ref().map(|exception_type| self.expr(exception_type));
+        let target = handler.name.as_ref().map(|name| Target::Name(self.symbol(name)));
+        let body = self.block(&handler.body);
+        self.span = outer;
+        Handler { exception_type, target, body, span: handler.span }
+    }
+
+    /// A `def` is the function object passed through its decorators.
+    fn function_definition(&mut self, function: &FunctionDefinition) -> Expr {
+        let decorators: Vec<Expr> = function.decorators.iter().map(|decorator| self.expr(decorator)).collect();
+        let defaults = self.defaults(&function.parameters);
+        if let Some(returns) = &function.returns {
+            self.skip(returns);
+        }
+        let scope = self.next_scope();
+        let signature = self.types.signature(scope).cloned();
+        let ((parameters, body), temporaries) = self.in_scope(scope, |lowerer| {
+            let types = signature.as_ref().and_then(|signature| signature.parameters.as_ref());
+            let parameters = function
+                .parameters
+                .iter()
+                .zip(defaults)
+                .enumerate()
+                .map(|(index, (parameter, default))| {
+                    let ty = types.and_then(|types| types.get(index)).map_or(Type::Any, |ty| ty.annotation.clone());
+                    lowerer.parameter(parameter, ty, default)
+                })
+                .collect();
+            (parameters, lowerer.block(&function.body))
+        });
+        let returns = signature.as_ref().map_or(Type::Any, |signature| signature.returns.clone());
+        let ty = signature.map_or(Type::Any, |signature| Type::Callable(Box::new(signature)));
+        let value = Expr::new(
+            ExprKind::Function(Box::new(Function {
+                name: function.name.clone(),
+                scope,
+                parameters,
+                returns,
+                body,
+                cells: self.closure(scope, SymbolKind::Cell),
+                frees: self.closure(scope, SymbolKind::Free),
+                temporaries,
+                is_generator: false,
+                span: function.span,
+            })),
+            ty,
+        );
+        decorate(decorators, value)
+    }
+
+    fn class_definition(&mut self, class: &ClassDefinition) -> Expr {
+        let decorators: Vec<Expr> = class.decorators.iter().map(|decorator| self.expr(decorator)).collect();
+        let bases = class.bases.iter().map(|base| self.expr(base)).collect();
+        let keywords = self.keywords(&class.keywords);
+        let scope = self.next_scope();
+        let (body, temporaries) = self.in_scope(scope, |lowerer| lowerer.block(&class.body));
+        let value = Expr::new(
+            ExprKind::Class(Box::new(Class {
+                name: class.name.clone(),
+                scope,
+                bases,
+                keywords,
+                body,
+                temporaries,
+                span: class.span,
+            })),
+            Type::ClassObject(Box::new(Type::Instance(class.name.clone()))),
+        );
+        decorate(decorators, value)
+    }
+
+    /// Lowers the default values, skipping the annotations.
+    fn defaults(&mut self, parameters: &[Parameter]) -> Vec<Option<Expr>> {
+        parameters
+            .iter()
+            .map(|parameter| {
+                if let Some(annotation) = &parameter.annotation {
+                    self.skip(annotation);
+                }
+                parameter.default.as_ref().map(|default| self.expr(default))
+            })
+            .collect()
+    }
+
+    fn parameter(&self, parameter: &Parameter, ty: Type, default: Option<Expr>) -> Param {
+        Param { target: Target::Name(self.symbol(&parameter.name)), kind: parameter.kind, ty, default }
+    }
+
+    fn closure(&self, scope: ScopeId, kind: SymbolKind) -> Vec<String> {
+        let scope = self.table.scope(scope);
+        let symbols = if kind == SymbolKind::Cell { scope.cells().collect::<Vec<_>>() } else { scope.frees().collect() };
+        symbols.into_iter().map(|symbol| symbol.name.clone()).collect()
+    }
+
+    fn target(&mut self, target: &ASTNode) -> Target {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => Target::Name(self.symbol(name)),
+            ASTNode::Attribute { value, attribute } => {
+                Target::Attribute { value: Box::new(self.expr(value)), attribute: attribute.clone() }
+            }
+            ASTNode::Subscript { value, index } => {
+                let value = Box::new(self.expr(value));
+                Target::Subscript { value, index: Box::new(self.expr(index)) }
+            }
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                Target::Tuple(elements.iter().map(|element| self.target(element)).collect())
+            }
+            ASTNode::Starred(inner) => Target::Starred(Box::new(self.target(inner))),
+            other => unreachable!("the parser only accepts assignable targets, not {:?}", other),
+        }
+    }
+
+    // ----- Expressions ------------------------------------------------------
+
+    fn expr(&mut self, node: &ASTNode) -> Expr {
+        let ty = self.type_of(node);
+        let kind = match node {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => ExprKind::Name(self.symbol(name)),
+            ASTNode::Expression(ASTNodeType::Number(value)) | ASTNode::Number(value) => ExprKind::Int(*value),
+            ASTNode::BigNumber(value) => ExprKind::BigInt(value.clone()),
+            ASTNode::Expression(ASTNodeType::BinaryOperation { operator, left, right })
+            | ASTNode::BinaryOperation { left, operator, right } => ExprKind::Binary {
+                left: Box::new(self.expr(left)),
+                operator: operator.clone(),
+                right: Box::new(self.expr(right)),
+                inplace: false,
+            },
+            ASTNode::Float(value) => ExprKind::Float(*value),
+            ASTNode::Imaginary(value) => ExprKind::Imaginary(*value),
+            ASTNode::StringLiteral(value) => ExprKind::Str(value.clone()),
+            ASTNode::Bytes(value) => ExprKind::Bytes(value.clone()),
+            // An f-string without replacement fields is a plain string.
+            ASTNode::JoinedStr(parts) => match &parts[..] {
+                [] => ExprKind::Str(String::new()),
+                [ASTNode::StringLiteral(text)] => ExprKind::Str(text.clone()),
+                _ => ExprKind::JoinedStr(self.exprs(parts)),
+            },
+            ASTNode::FormattedValue { value, conversion, format_spec } => ExprKind::FormattedValue {
+                value: Box::new(self.expr(value)),
+                conversion: *conversion,
+                format_spec: format_spec.as_ref().map(|format_spec| Box::new(self.expr(format_spec))),
+            },
+            ASTNode::Boolean(value) => ExprKind::Bool(*value),
+            ASTNode::NoneLiteral => ExprKind::None,
+            ASTNode::Ellipsis => ExprKind::Ellipsis,
+            ASTNode::UnaryOperation { operator, operand } => {
+                ExprKind::Unary { operator: operator.clone(), operand: Box::new(self.expr(operand)) }
+            }
+            ASTNode::BooleanOperation { operator, values } => {
+                ExprKind::BoolOp { operator: operator.clone(), values: self.exprs(values) }
+            }
+            ASTNode::Comparison { left, operators, comparators } => ExprKind::Compare {
+                left: Box::new(self.expr(left)),
+                operators: operators.clone(),
+                comparators: self.exprs(comparators),
+            },
+            ASTNode::Conditional { condition, body, orelse } => ExprKind::Conditional {
+                condition: Box::new(self.expr(condition)),
+                body: Box::new(self.expr(body)),
+                orelse: Box::new(self.expr(orelse)),
+            },
+            ASTNode::Call { function, arguments, keywords, .. } => ExprKind::Call {
+                function: Box::new(self.expr(function)),
+                arguments: self.exprs(arguments),
+                keywords: self.keywords(keywords),
+            },
+            ASTNode::Attribute { value, attribute } => {
+                ExprKind::Attribute { value: Box::new(self.expr(value)), attribute: attribute.clone() }
+            }
+            ASTNode::Subscript { value, index } => {
+                ExprKind::Subscript { value: Box::new(self.expr(value)), index: Box::new(self.expr(index)) }
+            }
+            ASTNode::Slice { lower, upper, step } => ExprKind::Slice {
+                lower: lower.as_ref().map(|lower| Box::new(self.expr(lower))),
+                upper: upper.as_ref().map(|upper| Box::new(self.expr(upper))),
+                step: step.as_ref().map(|step| Box::new(self.expr(step))),
+            },
+            ASTNode::Starred(value) => ExprKind::Starred(Box::new(self.expr(value))),
+            ASTNode::List(elements) => ExprKind::List(self.exprs(elements)),
+            ASTNode::Tuple(elements) => ExprKind::Tuple(self.exprs(elements)),
+            ASTNode::Set(elements) => ExprKind::Set(self.exprs(elements)),
+            ASTNode::Dict(entries) => {
+                ExprKind::Dict(entries.iter().map(|(key, value)| (self.expr(key), self.expr(value))).collect())
+            }
+            ASTNode::Comprehension { kind, element, generators } => return self.comprehension(*kind, element, generators, ty),
+            ASTNode::Lambda { parameters, body } => return self.lambda(parameters, body, ty),
+            // Statements never appear where an expression is expected.
+            _ => ExprKind::None,
+        };
+        Expr::new(kind, ty)
+    }
+
+    fn exprs(&mut self, nodes: &[ASTNode]) -> Vec<Expr> {
+        nodes.iter().map(|node| self.expr(node)).collect()
+    }
+
+    fn keywords(&mut self, keywords: &[KeywordArgument]) -> Vec<Keyword> {
+        keywords.iter().map(|keyword| Keyword { name: keyword.name.clone(), value: self.expr(&keyword.value) }).collect()
+    }
+
+    fn lambda(&mut self, parameters: &[Parameter], body: &ASTNode, ty: Type) -> Expr {
+        let defaults = self.defaults(parameters);
+        let scope = self.next_scope();
+        let ((parameters, body), temporaries) = self.in_scope(scope, |lowerer| {
+            let parameters: Vec<Param> = parameters
+                .iter()
+                .zip(defaults)
+                .map(|(parameter, default)| lowerer.parameter(parameter, Type::Any, default))
+                .collect();
+            let body = lowerer.expr(body);
+            (parameters, body)
+        });
+        let returns = body.ty.clone();
+        let function = Function {
+            name: "<lambda>".to_string(),
+            scope,
+            parameters,
+            returns,
+            body: vec![self.stmt(StmtKind::Return(Some(body)))],
+            cells: self.closure(scope, SymbolKind::Cell),
+            frees: self.closure(scope, SymbolKind::Free),
+            temporaries,
+            is_generator: false,
+            span: self.span,
+        };
+        Expr::new(ExprKind::Function(Box::new(function)), ty)
+    }
+
+    /// `[element for x in first if condition]` is a call of a function that
+    /// gets `first` as its argument:
+    ///
+    /// ```text
+    /// def <listcomp>($0):
+    ///     $1 = []
+    ///     for x in $0:
+    ///         if condition:
+    ///             $1.append(element)
+    ///     return $1
+    /// ```
+    ///
+    /// Set comprehensions `add` to a set, and generator expressions `yield`
+    /// each element instead.
+    fn comprehension(&mut self, kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator], ty: Type) -> Expr {
+        let first = match generators.first() {
+            Some(generator) => self.expr(&generator.iterable),
+            None => constant(ExprKind::Tuple(Vec::new())),
+        };
+        let scope = self.next_scope();
+        let ((parameter, body), temporaries) = self.in_scope(scope, |lowerer| {
+            let iterator = lowerer.temp();
+            let result = (kind != ComprehensionKind::Generator).then(|| lowerer.temp());
+            let mut loops = Vec::new();
+            for (index, generator) in generators.iter().enumerate() {
+                let iterable = match index {
+                    0 => Expr::new(ExprKind::Temp(iterator), first.ty.clone()),
+                    _ => lowerer.expr(&generator.iterable),
+                };
+                let target = lowerer.target(&generator.target);
+                let conditions = lowerer.exprs(&generator.conditions);
+                loops.push((target, iterable, conditions));
+            }
+            let element = lowerer.expr(element);
+
+            let innermost = match (kind, result) {
+                (ComprehensionKind::List, Some(result)) | (ComprehensionKind::Set, Some(result)) => {
+                    let method = if kind == ComprehensionKind::List { "append" } else { "add" };
+                    let collection = Expr::new(ExprKind::Temp(result), ty.clone());
+                    call(attribute(collection, method), vec![element])
+                }
+                _ => Expr::new(ExprKind::Yield(Box::new(element)), Type::Any),
+            };
+            let mut body = vec![lowerer.stmt(StmtKind::Expr(innermost))];
+            for (target, iterable, conditions) in loops.into_iter().rev() {
+                for condition in conditions.into_iter().rev() {
+                    body = vec![lowerer.stmt(StmtKind::If { condition, body, orelse: Vec::new() })];
+                }
+                body = vec![lowerer.stmt(StmtKind::For { target, iterable, body, orelse: Vec::new() })];
+            }
+            if let Some(result) = result {
+                let empty = if kind == ComprehensionKind::List { ExprKind::List(Vec::new()) } else { ExprKind::Set(Vec::new()) };
+                body.insert(0, lowerer.stmt(StmtKind::Assign { targets: vec![Target::Temp(result)], value: Expr::new(empty, ty.clone()) }));
+                body.push(lowerer.stmt(StmtKind::Return(Some(Expr::new(ExprKind::Temp(result), ty.clone())))));
+            }
+            let parameter =
+                Param { target: Target::Temp(iterator), kind: ParameterKind::PositionalOnly, ty: first.ty.clone(), default: None };
+            (parameter, body)
+        });
+        let is_generator = kind == ComprehensionKind::Generator;
+        let function = Function {
+            name: self.table.scope(scope).name.clone(),
+            scope,
+            parameters: vec![parameter],
+            returns: if is_generator { Type::Any } else { ty.clone() },
+            body,
+            cells: self.closure(scope, SymbolKind::Cell),
+            frees: self.closure(scope, SymbolKind::Free),
+            temporaries,
+            is_generator,
+            span: self.span,
+        };
+        let function = Expr::new(ExprKind::Function(Box::new(function)), Type::Any);
+        Expr::new(ExprKind::Call { function: Box::new(function), arguments: vec![first], keywords: Vec::new() }, ty)
+    }
+}
+
+fn constant(kind: ExprKind) -> Expr {
+    let ty = match kind {
+        ExprKind::Bool(_) => Type::Bool,
+        ExprKind::None => Type::None,
+        _ => Type::Any,
+    };
+    Expr::new(kind, ty)
+}
+
+fn attribute(value: Expr, attribute: &str) -> Expr {
+    Expr::new(ExprKind::Attribute { value: Box::new(value), attribute: attribute.to_string() }, Type::Any)
+}
+
+fn call(function: Expr, arguments: Vec<Expr>) -> Expr {
+    Expr::new(ExprKind::Call { function: Box::new(function), arguments, keywords: Vec::new() }, Type::Any)
+}
+
+/// Applies decorators, listed outermost first, to a definition.
+fn decorate(decorators: Vec<Expr>, definition: Expr) -> Expr {
+    decorators.into_iter().rev().fold(definition, |value, decorator| call(decorator, vec![value]))
+}
+
+/// Counts the scopes an expression creates directly, for `Lowerer::skip`.
+struct ScopeCounter(usize);
+
+impl Visitor for ScopeCounter {
+    fn visit_lambda(&mut self, parameters: &[Parameter], _body: &ASTNode) {
+        walk_parameters(self, parameters);
+        self.0 += 1;
+    }
+
+    fn visit_comprehension(&mut self, _kind: ComprehensionKind, _element: &ASTNode, generators: &[ComprehensionGenerator]) {
+        if let Some(first) = generators.first() {
+            self.visit_node(&first.iterable);
+        }
+        self.0 += 1;
+    }
+}
@@ -0,0 +1,3 @@
+pub mod hir;
+pub mod lower;
+pub mod visitor;
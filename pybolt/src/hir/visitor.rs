@@ -0,0 +1,199 @@
+use crate::hir::hir::{Class, Expr, ExprKind, Function, Stmt, StmtKind, Target};
+
+/// Read-only traversal over the HIR, in evaluation order.
+///
+/// Every method defaults to the matching `walk_*` function. Functions and
+/// classes are entered through `visit_function` and `visit_class`, which
+/// visit the default values, bases and keywords before the body.
+pub trait Visitor: Sized {
+    fn visit_block(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.visit_stmt(statement);
+        }
+    }
+
+    fn visit_stmt(&mut self, statement: &Stmt) {
+        walk_stmt(self, statement);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_target(&mut self, target: &Target) {
+        walk_target(self, target);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_class(&mut self, class: &Class) {
+        walk_class(self, class);
+    }
+}
+
+pub fn walk_stmt<V: Visitor>(visitor: &mut V, statement: &Stmt) {
+    match &statement.kind {
+        StmtKind::Expr(expr) => visitor.visit_expr(expr),
+        StmtKind::Assign { targets, value } => {
+            visitor.visit_expr(value);
+            for target in targets {
+                visitor.visit_target(target);
+            }
+        }
+        StmtKind::If { condition, body, orelse } | StmtKind::While { condition, body, orelse } => {
+            visitor.visit_expr(condition);
+            visitor.visit_block(body);
+            visitor.visit_block(orelse);
+        }
+        StmtKind::For { target, iterable, body, orelse } => {
+            visitor.visit_expr(iterable);
+            visitor.visit_target(target);
+            visitor.visit_block(body);
+            visitor.visit_block(orelse);
+        }
+        StmtKind::Return(value) => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::Try { body, handlers, orelse, finalbody } => {
+            visitor.visit_block(body);
+            for handler in handlers {
+                if let Some(exception_type) = &handler.exception_type {
+                    visitor.visit_expr(exception_type);
+                }
+                if let Some(target) = &handler.target {
+                    visitor.visit_target(target);
+                }
+                visitor.visit_block(&handler.body);
+            }
+            visitor.visit_block(orelse);
+            visitor.visit_block(finalbody);
+        }
+        StmtKind::Raise { exception, cause } => {
+            for value in [exception, cause].into_iter().flatten() {
+                visitor.visit_expr(value);
+            }
+        }
+        StmtKind::ImportStar { .. } | StmtKind::Break | StmtKind::Continue => {}
+    }
+}
+
+pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Expr) {
+    match &expr.kind {
+        ExprKind::Function(function) => visitor.visit_function(function),
+        ExprKind::Class(class) => visitor.visit_class(class),
+        _ => for_each_child(expr, |child| visitor.visit_expr(child)),
+    }
+}
+
+pub fn walk_target<V: Visitor>(visitor: &mut V, target: &Target) {
+    match target {
+        Target::Name(_) | Target::Temp(_) => {}
+        Target::Attribute { value, .. } => visitor.visit_expr(value),
+        Target::Subscript { value, index } => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(index);
+        }
+        Target::Tuple(elements) => {
+            for element in elements {
+                visitor.visit_target(element);
+            }
+        }
+        Target::Starred(inner) => visitor.visit_target(inner),
+    }
+}
+
+pub fn walk_function<V: Visitor>(visitor: &mut V, function: &Function) {
+    for default in function.parameters.iter().filter_map(|parameter| parameter.default.as_ref()) {
+        visitor.visit_expr(default);
+    }
+    visitor.visit_block(&function.body);
+}
+
+pub fn walk_class<V: Visitor>(visitor: &mut V, class: &Class) {
+    for base in &class.bases {
+        visitor.visit_expr(base);
+    }
+    for keyword in &class.keywords {
+        visitor.visit_expr(&keyword.value);
+    }
+    visitor.visit_block(&class.body);
+}
+
+/// Calls `f` on the subexpressions an expression evaluates itself, in
+/// evaluation order: the default values of a function and the bases and
+/// keywords of a class, but not their bodies.
+pub fn for_each_child<'a>(expr: &'a Expr, mut f: impl FnMut(&'a Expr)) {
+    match &expr.kind {
+        ExprKind::Int(_)
+        | ExprKind::BigInt(_)
+        | ExprKind::Float(_)
+        | ExprKind::Imaginary(_)
+        | ExprKind::Str(_)
+        | ExprKind::Bytes(_)
+        | ExprKind::Bool(_)
+        | ExprKind::None
+        | ExprKind::Ellipsis
+        | ExprKind::Name(_)
+        | ExprKind::Temp(_)
+        | ExprKind::Import { .. } => {}
+        ExprKind::Unary { operand: value, .. }
+        | ExprKind::Attribute { value, .. }
+        | ExprKind::Starred(value)
+        | ExprKind::Yield(value) => f(value),
+        ExprKind::Binary { left, right, .. } => {
+            f(left);
+            f(right);
+        }
+        ExprKind::FormattedValue { value, format_spec, .. } => {
+            f(value);
+            if let Some(format_spec) = format_spec {
+                f(format_spec);
+            }
+        }
+        ExprKind::BoolOp { values: elements, .. }
+        | ExprKind::JoinedStr(elements)
+        | ExprKind::List(elements)
+        | ExprKind::Tuple(elements)
+        | ExprKind::Set(elements) => elements.iter().for_each(f),
+        ExprKind::Compare { left, comparators, .. } => {
+            f(left);
+            comparators.iter().for_each(f);
+        }
+        ExprKind::Conditional { condition, body, orelse } => {
+            f(condition);
+            f(body);
+            f(orelse);
+        }
+        ExprKind::Call { function, arguments, keywords } => {
+            f(function);
+            arguments.iter().for_each(&mut f);
+            keywords.iter().for_each(|keyword| f(&keyword.value));
+        }
+        ExprKind::Subscript { value, index } => {
+            f(value);
+            f(index);
+        }
+        ExprKind::Slice { lower, upper, step } => {
+            for part in [lower, upper, step].into_iter().flatten() {
+                f(part);
+            }
+        }
+        ExprKind::Dict(entries) => {
+            for (key, value) in entries {
+                f(key);
+                f(value);
+            }
+        }
+        ExprKind::Function(function) => {
+            function.parameters.iter().filter_map(|parameter| parameter.default.as_ref()).for_each(f);
+        }
+        ExprKind::Class(class) => {
+            class.bases.iter().for_each(&mut f);
+            class.keywords.iter().for_each(|keyword| f(&keyword.value));
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::ir::{
+    BinaryOp, Block, BlockCall, Class, CompareOp, Constant, Function, Inst, InstKind, Terminator, Type, UnaryOp, Value,
+};
+use crate::lexer::lexer::Span;
+
+/// A variable of the source program, which the builder turns into SSA
+/// values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Variable(pub u32);
+
+/// Builds a function one block at a time, appending instructions to the
+/// current block.
+///
+/// Variables are put into SSA form as the function is built, following
+/// Braun et al., "Simple and Efficient Construction of Static Single
+/// Assignment Form": `use_var` looks a variable up through the predecessors
+/// of the current block and adds block parameters where definitions meet.
+/// A block must be sealed with `seal_block` once all of its predecessors
+/// are known; until then, lookups through it add parameters that are only
+/// completed when it is sealed.
+///
+/// Parameters added with `append_block_param` must be added before any
+/// branch to the block. `finish` removes the unreachable blocks and the
+/// parameters that turned out to be unnecessary.
+pub struct FunctionBuilder<'a> {
+    function: &'a mut Function,
+    current: Option<Block>,
+    span: Span,
+    variables: Vec<Type>,
+    definitions: HashMap<(Variable, Block), Value>,
+    sealed: HashSet<Block>,
+    /// The blocks that have been switched to, which are laid out in that
+    /// order.
+    placed: HashSet<Block>,
+    /// Parameters added to unsealed blocks, which get their arguments when
+    /// the block is sealed.
+    incomplete: HashMap<Block, Vec<(Variable, Value)>>,
+    predecessors: HashMap<Block, Vec<Block>>,
+}
+
+impl<'a> FunctionBuilder<'a> {
+    /// Starts building `function` in its entry block, which is sealed.
+    pub fn new(function: &'a mut Function) -> Self {
+        let entry = function.entry();
+        FunctionBuilder {
+            function,
+            current: Some(entry),
+            span: Span::default(),
+            variables: Vec::new(),
+            definitions: HashMap::new(),
+            sealed: HashSet::from([entry]),
+            placed: HashSet::from([entry]),
+            incomplete: HashMap::new(),
+            predecessors: HashMap::new(),
+        }
+    }
+
+    pub fn function(&self) -> &Function {
+        self.function
+    }
+
+    pub fn params(&self) -> Vec<Value> {
+        self.function.params().to_vec()
+    }
+
+    pub fn value_type(&self, value: Value) -> Type {
+        self.function.value_type(value)
+    }
+
+    /// Sets the source span of the instructions built from now on.
+    pub fn set_span(&mut self, span: Span) {
+        self.span = span;
+    }
+
+    pub fn create_block(&mut self) -> Block {
+        self.function.create_block()
+    }
+
+    pub fn append_block_param(&mut self, block: Block, ty: Type) -> Value {
+        self.function.append_block_param(block, ty)
+    }
+
+    /// Makes `block` the current block. The first time, it also moves the
+    /// block to the end of the layout, so that blocks are laid out in the
+    /// order they are built.
+    pub fn switch_to_block(&mut self, block: Block) {
+        if self.placed.insert(block) {
+            self.function.layout.retain(|other| *other != block);
+            self.function.layout.push(block);
+        }
+        self.current = Some(block);
+    }
+
+    pub fn current_block(&self) -> Option<Block> {
+        self.current
+    }
+
+    /// Whether the current block has its terminator, so that nothing more
+    /// can be added to it.
+    pub fn is_filled(&self) -> bool {
+        match self.current {
+            Some(block) => self.function.block(block).terminator.is_some(),
+            None => true,
+        }
+    }
+
+    /// Whether the current block is the entry block or has a predecessor.
+    pub fn is_reachable(&self) -> bool {
+        match self.current {
+            Some(block) => block == self.function.entry() || self.predecessors.contains_key(&block),
+            None => false,
+        }
+    }
+
+    // ----- Variables --------------------------------------------------------
+
+    pub fn declare_variable(&mut self, ty: Type) -> Variable {
+        self.variables.push(ty);
+        Variable(self.variables.len() as u32 - 1)
+    }
+
+    pub fn variable_type(&self, variable: Variable) -> Type {
+        self.variables[variable.0 as usize]
+    }
+
+    pub fn def_var(&mut self, variable: Variable, value: Value) {
+        let block = self.current_or_panic();
+        self.definitions.insert((variable, block), value);
+    }
+
+    /// The value of a variable in the current block. On a path where it was
+    /// never assigned it reads as `Constant::zero` of its type, or as a
+    /// boxed `None` for an `object`.
+    pub fn use_var(&mut self, variable: Variable) -> Value {
+        let block = self.current_or_panic();
+        self.read_variable(variable, block)
+    }
+
+    fn read_variable(&mut self, variable: Variable, block: Block) -> Value {
+        if let Some(value) = self.definitions.get(&(variable, block)) {
+            return *value;
+        }
+        let ty = self.variable_type(variable);
+        let predecessors = self.predecessors.get(&block).cloned().unwrap_or_default();
+        let value = if !self.sealed.contains(&block) {
+            let param = self.function.append_block_param(block, ty);
+            self.incomplete.entry(block).or_default().push((variable, param));
+            param
+        } else if predecessors.len() == 1 {
+            self.read_variable(variable, predecessors[0])
+        } else if predecessors.is_empty() {
+            let constant = match ty {
+                Type::Object => Constant::None,
+                _ => Constant::zero(ty).expect("variables do not hold pointers"),
+            };
+            let inst = self.function.make_inst(InstKind::Const(constant), Some(ty), self.span);
+            self.function.block_mut(block).insts.insert(0, inst);
+            self.function.result(inst).expect("constants have a result")
+        } else {
+            let param = self.function.append_block_param(block, ty);
+            self.definitions.insert((variable, block), param);
+            self.add_param_arguments(variable, block, &predecessors);
+            param
+        };
+        self.definitions.insert((variable, block), value);
+        value
+    }
+
+    fn add_param_arguments(&mut self, variable: Variable, block: Block, predecessors: &[Block]) {
+        for predecessor in predecessors {
+            let argument = self.read_variable(variable, *predecessor);
+            if let Some(terminator) = &mut self.function.block_mut(*predecessor).terminator {
+                for target in terminator.successors_mut() {
+                    if target.block == block {
+                        target.arguments.push(argument);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Declares that every predecessor of `block` has been built.
+    pub fn seal_block(&mut self, block: Block) {
+        if !self.sealed.insert(block) {
+            return;
+        }
+        let predecessors = self.predecessors.get(&block).cloned().unwrap_or_default();
+        for (variable, _) in self.incomplete.remove(&block).unwrap_or_default() {
+            self.add_param_arguments(variable, block, &predecessors);
+        }
+    }
+
+    pub fn seal_all_blocks(&mut self) {
+        for block in self.function.layout.clone() {
+            self.seal_block(block);
+        }
+    }
+
+    // ----- Instructions -----------------------------------------------------
+
+    fn current_or_panic(&self) -> Block {
+        self.current.expect("no current block")
+    }
+
+    /// Appends an instruction to the current block.
+    pub fn push(&mut self, kind: InstKind, result: Option<Type>) -> Inst {
+        assert!(!self.is_filled(), "the current block already has a terminator");
+        let block = self.current_or_panic();
+        let inst = self.function.make_inst(kind, result, self.span);
+        self.function.block_mut(block).insts.push(inst);
+        inst
+    }
+
+    fn push_value(&mut self, kind: InstKind, ty: Type) -> Value {
+        let inst = self.push(kind, Some(ty));
+        self.function.result(inst).expect("the instruction was given a result")
+    }
+
+    pub fn constant(&mut self, constant: Constant) -> Value {
+        let ty = constant.ty();
+        self.push_value(InstKind::Const(constant), ty)
+    }
+
+    /// A constant boxed into an `object`.
+    pub fn object_constant(&mut self, constant: Constant) -> Value {
+        self.push_value(InstKind::Const(constant), Type::Object)
+    }
+
+    /// An `i64` constant; `constant` makes an `int` one.
+    pub fn iconst(&mut self, value: i64) -> Value {
+        self.push_value(InstKind::Const(Constant::Int(value)), Type::I64)
+    }
+
+    pub fn fconst(&mut self, value: f64) -> Value {
+        self.constant(Constant::Float(value))
+    }
+
+    pub fn bconst(&mut self, value: bool) -> Value {
+        self.constant(Constant::Bool(value))
+    }
+
+    pub fn none(&mut self) -> Value {
+        self.constant(Constant::None)
+    }
+
+    pub fn unary(&mut self, op: UnaryOp, operand: Value) -> Value {
+        let ty = self.value_type(operand);
+        self.push_value(InstKind::Unary { op, operand }, ty)
+    }
+
+    pub fn binary(&mut self, op: BinaryOp, left: Value, right: Value) -> Value {
+        let ty = match self.value_type(left) {
+            Type::Int if op == BinaryOp::Div => Type::F64,
+            ty => ty,
+        };
+        self.push_value(InstKind::Binary { op, left, right }, ty)
+    }
+
+    pub fn compare(&mut self, op: CompareOp, left: Value, right: Value) -> Value {
+        self.push_value(InstKind::Compare { op, left, right }, Type::Bool)
+    }
+
+    /// Converts a `bool`, `i64` or `int` to `ty`; see `InstKind::Cast`.
+    pub fn cast(&mut self, value: Value, ty: Type) -> Value {
+        self.push_value(InstKind::Cast(value), ty)
+    }
+
+    pub fn box_value(&mut self, value: Value) -> Value {
+        self.push_value(InstKind::Box(value), Type::Object)
+    }
+
+    /// Unboxes an `object` into `ty`, which must be unboxed.
+    pub fn unbox(&mut self, value: Value, ty: Type) -> Value {
+        self.push_value(InstKind::Unbox(value), ty)
+    }
+
+    pub fn guard(&mut self, value: Value, class: Class) -> Value {
+        self.push_value(InstKind::Guard { value, class }, Type::Bool)
+    }
+
+    pub fn truth(&mut self, value: Value) -> Value {
+        self.push_value(InstKind::Truth(value), Type::Bool)
+    }
+
+    /// A tuple of the `object`s.
+    pub fn tuple(&mut self, items: Vec<Value>) -> Value {
+        self.push_value(InstKind::Tuple(items), Type::Object)
+    }
+
+    /// Item `index` of `value` unpacked into `length` values; see
+    /// `InstKind::Unpack`.
+    pub fn unpack(&mut self, value: Value, index: usize, length: usize) -> Value {
+        self.push_value(InstKind::Unpack { tuple: value, index, length }, Type::Object)
+    }
+
+    /// A new list of the `object`s.
+    pub fn list(&mut self, items: Vec<Value>) -> Value {
+        self.push_value(InstKind::List(items), Type::Object)
+    }
+
+    pub fn len(&mut self, value: Value) -> Value {
+        self.push_value(InstKind::Len(value), Type::Int)
+    }
+
+    /// The position `index` stands for in a list of `length` items; see
+    /// `InstKind::CheckIndex`.
+    pub fn check_index(&mut self, index: Value, length: Value) -> Value {
+        self.push_value(InstKind::CheckIndex { index, length }, Type::Int)
+    }
+
+    pub fn get_item(&mut self, list: Value, index: Value) -> Value {
+        self.push_value(InstKind::GetItem { list, index }, Type::Object)
+    }
+
+    pub fn set_item(&mut self, list: Value, index: Value, value: Value) {
+        self.push(InstKind::SetItem { list, index, value }, None);
+    }
+
+    pub fn call(&mut self, function: &str, arguments: Vec<Value>, returns: Type) -> Value {
+        self.push_value(InstKind::Call { function: function.to_string(), arguments }, returns)
+    }
+
+    pub fn load_global(&mut self, name: &str, ty: Type) -> Value {
+        self.push_value(InstKind::LoadGlobal(name.to_string()), ty)
+    }
+
+    pub fn store_global(&mut self, name: &str, value: Value) {
+        self.push(InstKind::StoreGlobal { name: name.to_string(), value }, None);
+    }
+
+    pub fn print(&mut self, values: Vec<Value>) {
+        self.push(InstKind::Print(values), None);
+    }
+
+    // ----- Terminators ------------------------------------------------------
+
+    fn terminate(&mut self, terminator: Terminator) {
+        assert!(!self.is_filled(), "the current block already has a terminator");
+        let block = self.current_or_panic();
+        for target in terminator.successors() {
+            assert!(!self.sealed.contains(&target.block), "branch to a sealed block");
+            let predecessors = self.predecessors.entry(target.block).or_default();
+            if !predecessors.contains(&block) {
+                predecessors.push(block);
+            }
+        }
+        self.function.block_mut(block).terminator = Some(terminator);
+    }
+
+    pub fn br(&mut self, block: Block, arguments: Vec<Value>) {
+        self.terminate(Terminator::Br(BlockCall { block, arguments }));
+    }
+
+    pub fn cond_br(&mut self, condition: Value, then: Block, then_arguments: Vec<Value>, otherwise: Block, otherwise_arguments: Vec<Value>) {
+        self.terminate(Terminator::CondBr {
+            condition,
+            then: BlockCall { block: then, arguments: then_arguments },
+            otherwise: BlockCall { block: otherwise, arguments: otherwise_arguments },
+        });
+    }
+
+    pub fn ret(&mut self, value: Option<Value>) {
+        self.terminate(Terminator::Return(value));
+    }
+
+    pub fn unreachable(&mut self) {
+        self.terminate(Terminator::Unreachable);
+    }
+
+    /// Seals the remaining blocks, then removes the unreachable blocks and
+    /// the block parameters that always receive the same value.
+    pub fn finish(mut self) {
+        self.seal_all_blocks();
+        self.function.remove_unreachable_blocks();
+        self.function.remove_trivial_block_params();
+    }
+}
@@ -0,0 +1,997 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::hir::hir::{self, Expr, ExprKind, Stmt, StmtKind, Target};
+use crate::hir::visitor::{walk_class, walk_expr, walk_function, walk_stmt, walk_target, Visitor};
+use crate::ir::builder::{FunctionBuilder, Variable};
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Constant, Function, InlineHint, InstKind, Module, Type, UnaryOp, Value, ValueDef};
+use crate::parser::parser::ParameterKind;
+use crate::runtime::bigint::BigInt;
+use crate::semantic::scope::SymbolKind;
+use crate::semantic::types::Type as PyType;
+
+/// The function that runs the module body.
+pub const MAIN: &str = "__main__";
+
+/// The module whose decorators `inline` and `noinline` tell the inliner
+/// what to do with a function. It needs no code at runtime.
+pub const PYBOLT: &str = "pybolt";
+
+/// Generates IR from the HIR produced by `SemanticAnalyzer::analyze`.
+///
+/// The module body becomes the function `__main__` and each module-level
+/// `def` a function of its own, called directly. Module-level names that a
+/// function reads or writes are globals; every other variable is an SSA
+/// value. `int`, `float`, `bool` and `None` values are unboxed; `str`
+/// values, values of unknown type and variables assigned values of several
+/// types are boxed objects. Tuples and lists are objects too, of boxed
+/// items; a list is indexed with `check_index` before `get_item` and
+/// `set_item`. Other containers are not supported yet, and `for` loops
+/// only go over `range`.
+///
+/// The only decorators are `@pybolt.inline` and `@pybolt.noinline`, once
+/// `pybolt` is imported, which set the inlining hint of the function.
+pub struct IRGenerator {
+    ir: Module,
+}
+
+impl Default for IRGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IRGenerator {
+    pub fn new() -> Self {
+        IRGenerator { ir: Module::new() }
+    }
+
+    pub fn generate(&mut self, module: &hir::Module) -> Result<Module, String> {
+        let globals = Globals::collect(module)?;
+        let mut ir = Module::new();
+        let mut main = Function::new(MAIN, &[], Type::None);
+        FunctionGenerator::generate(&globals, &mut main, &[], &module.body, true)?;
+        ir.functions.push(main);
+        for (name, function, hint) in &globals.definitions {
+            let signature = &globals.functions[name];
+            let mut generated = Function::new(name, &signature.parameters, signature.returns);
+            generated.inline = *hint;
+            let parameters: Vec<String> = function
+                .parameters
+                .iter()
+                .map(|parameter| match &parameter.target {
+                    Target::Name(symbol) => symbol.name.clone(),
+                    _ => String::new(),
+                })
+                .collect();
+            FunctionGenerator::generate(&globals, &mut generated, &parameters, &function.body, false)?;
+            ir.functions.push(generated);
+        }
+        self.ir = ir;
+        Ok(self.ir.clone())
+    }
+
+    pub fn get_ir(&self) -> &Module {
+        &self.ir
+    }
+}
+
+/// The IR type of a value of a Python type.
+fn ir_type(ty: &PyType, what: &str) -> Result<Type, String> {
+    match ty {
+        PyType::Int => Ok(Type::Int),
+        PyType::Float => Ok(Type::F64),
+        PyType::Bool => Ok(Type::Bool),
+        PyType::None => Ok(Type::None),
+        PyType::Str | PyType::Any => Ok(Type::Object),
+        PyType::Tuple(types) if types.iter().all(|ty| ir_type(ty, what).is_ok()) => Ok(Type::Object),
+        PyType::List(element) if ir_type(element, what).is_ok() => Ok(Type::Object),
+        PyType::Union(types) if types.iter().all(|ty| ir_type(ty, what).is_ok()) => Ok(Type::Object),
+        other => Err(format!("{} has type '{}', which IR generation does not support yet", what, other)),
+    }
+}
+
+/// The type of a variable holding values of `types`: their type if they
+/// all have the same, and `object` otherwise.
+fn join(types: &[Type]) -> Type {
+    match types {
+        [first, rest @ ..] if rest.iter().all(|ty| ty == first) => *first,
+        [] => Type::None,
+        _ => Type::Object,
+    }
+}
+
+fn unsupported(what: &str) -> String {
+    format!("IR generation does not support {} yet", what)
+}
+
+struct Signature {
+    parameters: Vec<Type>,
+    names: Vec<String>,
+    defaults: Vec<Option<Constant>>,
+    returns: Type,
+}
+
+/// What the generator knows about the module as a whole.
+struct Globals<'h> {
+    /// The module-level functions that are only bound by their `def`, in
+    /// order, with their inlining hints. Calls of them are direct.
+    definitions: Vec<(String, &'h hir::Function, InlineHint)>,
+    functions: HashMap<String, Signature>,
+    /// The module-level names that functions use, which live in globals
+    /// rather than in SSA values of `__main__`.
+    shared: HashSet<String>,
+}
+
+impl<'h> Globals<'h> {
+    fn collect(module: &'h hir::Module) -> Result<Self, String> {
+        let mut bindings = Bindings::default();
+        bindings.visit_block(&module.body);
+
+        let imports_pybolt = bindings.counts.get(PYBOLT) == Some(&1) && module.body.iter().any(is_pybolt_import);
+        let mut definitions = Vec::new();
+        let mut functions = HashMap::new();
+        for statement in &module.body {
+            let StmtKind::Assign { targets, value } = &statement.kind else {
+                continue;
+            };
+            let ([Target::Name(symbol)], Some((function, hint))) = (targets.as_slice(), definition(value)) else {
+                continue;
+            };
+            if bindings.counts.get(&symbol.name) == Some(&1) && (hint == InlineHint::Default || imports_pybolt) {
+                functions.insert(symbol.name.clone(), Self::signature(function)?);
+                definitions.push((symbol.name.clone(), function, hint));
+            }
+        }
+        Ok(Globals { definitions, functions, shared: bindings.shared })
+    }
+
+    fn signature(function: &hir::Function) -> Result<Signature, String> {
+        let mut parameters = Vec::new();
+        let mut names = Vec::new();
+        let mut defaults = Vec::new();
+        for parameter in &function.parameters {
+            let Target::Name(symbol) = &parameter.target else {
+                return Err(unsupported("unpacking parameters"));
+            };
+            if !matches!(parameter.kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword) {
+                return Err(unsupported(&format!("the parameter '{}' of '{}'", symbol.name, function.name)));
+            }
+            parameters.push(ir_type(&parameter.ty, &format!("The parameter '{}' of '{}'", symbol.name, function.name))?);
+            names.push(symbol.name.clone());
+            defaults.push(match &parameter.default {
+                None => None,
+                Some(default) => match constant(default) {
+                    Some(constant) => Some(constant),
+                    None => return Err(unsupported(&format!("the non-constant default of '{}'", symbol.name))),
+                },
+            });
+        }
+        let what = format!("The return value of '{}'", function.name);
+        let returns = match &function.returns {
+            PyType::Any => {
+                let mut returns = Returns::default();
+                returns.visit_block(&function.body);
+                let types = returns.types.iter().map(|ty| ir_type(ty, &what)).collect::<Result<Vec<_>, _>>()?;
+                join(&types)
+            }
+            returns => ir_type(returns, &what)?,
+        };
+        Ok(Signature { parameters, names, defaults, returns })
+    }
+}
+
+/// The function a `def` creates, with the inlining hint of its decorator,
+/// if it has no other decorators.
+fn definition(expr: &Expr) -> Option<(&hir::Function, InlineHint)> {
+    match &expr.kind {
+        ExprKind::Function(function) => Some((function, InlineHint::Default)),
+        ExprKind::Call { function: decorator, arguments, keywords } if keywords.is_empty() => {
+            let ExprKind::Attribute { value, attribute } = &decorator.kind else {
+                return None;
+            };
+            let hint = match attribute.as_str() {
+                "inline" => InlineHint::Always,
+                "noinline" => InlineHint::Never,
+                _ => return None,
+            };
+            match (&value.kind, arguments.as_slice()) {
+                (ExprKind::Name(symbol), [argument]) if symbol.name == PYBOLT && symbol.kind == SymbolKind::Global => {
+                    match definition(argument)? {
+                        (function, InlineHint::Default) => Some((function, hint)),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Whether the statement is `import pybolt`.
+fn is_pybolt_import(statement: &Stmt) -> bool {
+    match &statement.kind {
+        StmtKind::Assign { targets, value } => matches!(
+            (targets.as_slice(), &value.kind),
+            ([Target::Name(symbol)], ExprKind::Import { module, .. }) if symbol.name == PYBOLT && module == PYBOLT
+        ),
+        _ => false,
+    }
+}
+
+fn constant(expr: &Expr) -> Option<Constant> {
+    match &expr.kind {
+        ExprKind::Int(value) => Some(Constant::Int(*value)),
+        ExprKind::BigInt(value) => Some(Constant::BigInt(Rc::new(value.clone()))),
+        ExprKind::Float(value) => Some(Constant::Float(*value)),
+        ExprKind::Bool(value) => Some(Constant::Bool(*value)),
+        ExprKind::None => Some(Constant::None),
+        ExprKind::Str(value) => Some(Constant::Str(value.clone())),
+        ExprKind::Unary { operator, operand } if operator == "-" => match &operand.kind {
+            ExprKind::Int(value) => Some(Constant::Int(-value)),
+            ExprKind::BigInt(value) => Some(big_constant(-value)),
+            ExprKind::Float(value) => Some(Constant::Float(-value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// An int constant, which is a `BigInt` only when it does not fit in an
+/// `i64`, as `-9223372036854775808` does.
+fn big_constant(value: BigInt) -> Constant {
+    match value.to_i64() {
+        Some(value) => Constant::Int(value),
+        None => Constant::BigInt(Rc::new(value)),
+    }
+}
+
+/// Counts the bindings of each module-level name, and collects the global
+/// names used inside functions.
+#[derive(Default)]
+struct Bindings {
+    counts: HashMap<String, usize>,
+    shared: HashSet<String>,
+    depth: usize,
+}
+
+impl Visitor for Bindings {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let ExprKind::Name(symbol) = &expr.kind {
+            if self.depth > 0 && symbol.kind == SymbolKind::Global {
+                self.shared.insert(symbol.name.clone());
+            }
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_target(&mut self, target: &Target) {
+        if let Target::Name(symbol) = target {
+            if self.depth == 0 {
+                *self.counts.entry(symbol.name.clone()).or_default() += 1;
+            } else if symbol.kind == SymbolKind::Global {
+                self.shared.insert(symbol.name.clone());
+                *self.counts.entry(symbol.name.clone()).or_default() += 1;
+            }
+        }
+        walk_target(self, target);
+    }
+
+    fn visit_function(&mut self, function: &hir::Function) {
+        self.depth += 1;
+        walk_function(self, function);
+        self.depth -= 1;
+    }
+
+    fn visit_class(&mut self, class: &hir::Class) {
+        self.depth += 1;
+        walk_class(self, class);
+        self.depth -= 1;
+    }
+}
+
+/// The types of the values a function returns, not counting nested
+/// functions.
+#[derive(Default)]
+struct Returns {
+    types: Vec<PyType>,
+}
+
+impl Visitor for Returns {
+    fn visit_stmt(&mut self, statement: &Stmt) {
+        if let StmtKind::Return(Some(value)) = &statement.kind {
+            self.types.push(value.ty.clone());
+        }
+        walk_stmt(self, statement);
+    }
+
+    fn visit_function(&mut self, _function: &hir::Function) {}
+}
+
+/// The types of the values assigned to each local and temporary of a
+/// function, not counting nested functions.
+#[derive(Default)]
+struct Assignments {
+    types: HashMap<String, Vec<PyType>>,
+}
+
+impl Assignments {
+    fn record(&mut self, target: &Target, ty: &PyType) {
+        let name = match target {
+            Target::Name(symbol) => symbol.name.clone(),
+            Target::Temp(index) => format!("${}", index),
+            _ => return,
+        };
+        if *ty != PyType::Never {
+            self.types.entry(name).or_default().push(ty.clone());
+        }
+    }
+}
+
+impl Visitor for Assignments {
+    fn visit_stmt(&mut self, statement: &Stmt) {
+        match &statement.kind {
+            StmtKind::Assign { value, .. } if matches!(value.kind, ExprKind::Class(_)) || definition(value).is_some() => {}
+            StmtKind::Assign { targets, value } => {
+                for target in targets {
+                    self.record(target, &value.ty);
+                }
+            }
+            StmtKind::For { target, .. } => self.record(target, &PyType::Int),
+            _ => {}
+        }
+        walk_stmt(self, statement);
+    }
+
+    fn visit_function(&mut self, _function: &hir::Function) {}
+}
+
+struct FunctionGenerator<'g, 'h, 'f> {
+    globals: &'g Globals<'h>,
+    builder: FunctionBuilder<'f>,
+    /// The SSA variables of locals, by name, and of temporaries, as `$n`.
+    variables: HashMap<String, Variable>,
+    /// The type of each variable, which holds all the values assigned to
+    /// it.
+    types: HashMap<String, Type>,
+    /// The `continue` and `break` targets of the enclosing loops.
+    loops: Vec<(Block, Block)>,
+    is_module: bool,
+    returns: Type,
+}
+
+impl<'g, 'h, 'f> FunctionGenerator<'g, 'h, 'f> {
+    fn generate(globals: &'g Globals<'h>, function: &'f mut Function, parameters: &[String], body: &[Stmt], is_module: bool) -> Result<(), String> {
+        let returns = function.return_type;
+        let name = function.name.clone();
+        let mut assignments = Assignments::default();
+        assignments.visit_block(body);
+        let mut types: HashMap<String, Vec<Type>> = HashMap::new();
+        for (parameter, ty) in parameters.iter().zip(function.param_types()) {
+            types.entry(parameter.clone()).or_default().push(ty);
+        }
+        for (variable, assigned) in &assignments.types {
+            for ty in assigned {
+                types.entry(variable.clone()).or_default().push(ir_type(ty, &format!("'{}'", variable))?);
+            }
+        }
+        let mut generator = FunctionGenerator {
+            globals,
+            builder: FunctionBuilder::new(function),
+            variables: HashMap::new(),
+            types: types.into_iter().map(|(variable, types)| (variable, join(&types))).collect(),
+            loops: Vec::new(),
+            is_module,
+            returns,
+        };
+        for (parameter, value) in parameters.iter().zip(generator.builder.params()) {
+            generator.define(parameter, value)?;
+        }
+        generator.block(body)?;
+        if !generator.builder.is_filled() {
+            if returns == Type::None {
+                generator.builder.ret(None);
+            } else if returns == Type::Object {
+                let none = generator.builder.object_constant(Constant::None);
+                generator.builder.ret(Some(none));
+            } else if generator.builder.is_reachable() {
+                return Err(format!("'{}' can end without returning a value of type {}", name, returns));
+            } else {
+                generator.builder.unreachable();
+            }
+        }
+        generator.builder.finish();
+        Ok(())
+    }
+
+    // ----- Statements -------------------------------------------------------
+
+    /// Lowers statements until one of them ends the current block; the rest
+    /// can never run.
+    fn block(&mut self, statements: &[Stmt]) -> Result<(), String> {
+        for statement in statements {
+            if self.builder.is_filled() {
+                break;
+            }
+            self.builder.set_span(statement.span);
+            self.statement(statement)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> Result<(), String> {
+        match &statement.kind {
+            StmtKind::Expr(expr) => {
+                self.expr(expr)?;
+            }
+            StmtKind::Assign { targets, value } => {
+                if let ([Target::Name(symbol)], Some(_)) = (targets.as_slice(), definition(value)) {
+                    if self.is_module && self.globals.functions.contains_key(&symbol.name) {
+                        return Ok(());
+                    }
+                    return Err(unsupported("nested and redefined functions"));
+                }
+                if self.is_module && is_pybolt_import(statement) {
+                    return Ok(());
+                }
+                let value = self.expr(value)?;
+                for target in targets {
+                    self.assign(target, value)?;
+                }
+            }
+            StmtKind::If { condition, body, orelse } => {
+                let condition = self.condition(condition)?;
+                let then = self.builder.create_block();
+                let merge = self.builder.create_block();
+                let otherwise = if orelse.is_empty() { merge } else { self.builder.create_block() };
+                self.builder.cond_br(condition, then, Vec::new(), otherwise, Vec::new());
+                self.builder.seal_block(then);
+                self.builder.switch_to_block(then);
+                self.block(body)?;
+                self.jump(merge);
+                if !orelse.is_empty() {
+                    self.builder.seal_block(otherwise);
+                    self.builder.switch_to_block(otherwise);
+                    self.block(orelse)?;
+                    self.jump(merge);
+                }
+                self.builder.seal_block(merge);
+                self.builder.switch_to_block(merge);
+            }
+            StmtKind::While { condition, body, orelse } => {
+                let header = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit = self.builder.create_block();
+                let otherwise = if orelse.is_empty() { exit } else { self.builder.create_block() };
+                self.builder.br(header, Vec::new());
+                self.builder.switch_to_block(header);
+                let condition = self.condition(condition)?;
+                self.builder.cond_br(condition, body_block, Vec::new(), otherwise, Vec::new());
+                self.loop_body(body_block, body, header, exit)?;
+                self.jump(header);
+                self.builder.seal_block(header);
+                self.loop_else(otherwise, orelse, exit)?;
+            }
+            StmtKind::For { target, iterable, body, orelse } => self.for_range(target, iterable, body, orelse)?,
+            StmtKind::Return(value) => {
+                if self.is_module {
+                    return Err("'return' outside function".to_string());
+                }
+                let value = match value {
+                    Some(value) => self.expr(value)?,
+                    None => self.builder.none(),
+                };
+                let value = self.convert(value, self.returns, "The return value")?;
+                self.builder.ret(if self.returns == Type::None { None } else { Some(value) });
+            }
+            StmtKind::Break => {
+                let (_, exit) = *self.loops.last().ok_or("'break' outside loop")?;
+                self.builder.br(exit, Vec::new());
+            }
+            StmtKind::Continue => {
+                let (next, _) = *self.loops.last().ok_or("'continue' not properly in loop")?;
+                self.builder.br(next, Vec::new());
+            }
+            StmtKind::Try { .. } | StmtKind::Raise { .. } => return Err(unsupported("exceptions")),
+            StmtKind::ImportStar { .. } => return Err(unsupported("imports")),
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, block: Block) {
+        if !self.builder.is_filled() {
+            self.builder.br(block, Vec::new());
+        }
+    }
+
+    fn loop_body(&mut self, block: Block, body: &[Stmt], next: Block, exit: Block) -> Result<(), String> {
+        self.builder.seal_block(block);
+        self.builder.switch_to_block(block);
+        self.loops.push((next, exit));
+        let result = self.block(body);
+        self.loops.pop();
+        result
+    }
+
+    /// The `else` of a loop runs when its condition is false, and `break`
+    /// skips it.
+    fn loop_else(&mut self, otherwise: Block, orelse: &[Stmt], exit: Block) -> Result<(), String> {
+        if otherwise != exit {
+            self.builder.seal_block(otherwise);
+            self.builder.switch_to_block(otherwise);
+            self.block(orelse)?;
+            self.jump(exit);
+        }
+        self.builder.seal_block(exit);
+        self.builder.switch_to_block(exit);
+        Ok(())
+    }
+
+    /// `for target in range(start, stop, step)` counts in a variable of its
+    /// own, so assigning to the target in the body doesn't change the
+    /// iteration. The step must be a constant, which tells the direction.
+    fn for_range(&mut self, target: &Target, iterable: &Expr, body: &[Stmt], orelse: &[Stmt]) -> Result<(), String> {
+        let ExprKind::Call { function, arguments, keywords } = &iterable.kind else {
+            return Err(unsupported("'for' loops over anything but 'range'"));
+        };
+        let is_range = matches!(&function.kind, ExprKind::Name(symbol) if symbol.name == "range" && symbol.kind == SymbolKind::Builtin);
+        if !is_range || !keywords.is_empty() || arguments.is_empty() || arguments.len() > 3 {
+            return Err(unsupported("'for' loops over anything but 'range'"));
+        }
+        let step = match arguments.get(2) {
+            None => 1,
+            Some(step) => match constant(step) {
+                Some(Constant::Int(step)) if step != 0 => step,
+                Some(Constant::Int(_)) => return Err("range() arg 3 must not be zero".to_string()),
+                _ => return Err(unsupported("'range' with a step that is not a constant")),
+            },
+        };
+        let start = match arguments.len() {
+            1 => self.builder.constant(Constant::Int(0)),
+            _ => self.int_expr(&arguments[0])?,
+        };
+        let stop = self.int_expr(&arguments[if arguments.len() == 1 { 0 } else { 1 }])?;
+
+        let counter = self.builder.declare_variable(Type::Int);
+        self.builder.def_var(counter, start);
+        let header = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let latch = self.builder.create_block();
+        let exit = self.builder.create_block();
+        let otherwise = if orelse.is_empty() { exit } else { self.builder.create_block() };
+        self.builder.br(header, Vec::new());
+
+        self.builder.switch_to_block(header);
+        let current = self.builder.use_var(counter);
+        let op = if step > 0 { CompareOp::Lt } else { CompareOp::Gt };
+        let condition = self.builder.compare(op, current, stop);
+        self.builder.cond_br(condition, body_block, Vec::new(), otherwise, Vec::new());
+
+        self.builder.seal_block(body_block);
+        self.builder.switch_to_block(body_block);
+        self.assign(target, current)?;
+        self.loops.push((latch, exit));
+        let result = self.block(body);
+        self.loops.pop();
+        result?;
+        self.jump(latch);
+
+        self.builder.seal_block(latch);
+        self.builder.switch_to_block(latch);
+        let current = self.builder.use_var(counter);
+        let step = self.builder.constant(Constant::Int(step));
+        let next = self.builder.binary(BinaryOp::Add, current, step);
+        self.builder.def_var(counter, next);
+        self.builder.br(header, Vec::new());
+        self.builder.seal_block(header);
+        self.loop_else(otherwise, orelse, exit)
+    }
+
+    fn assign(&mut self, target: &Target, value: Value) -> Result<(), String> {
+        match target {
+            Target::Name(symbol) => match symbol.kind {
+                SymbolKind::Global if !self.is_module || self.globals.shared.contains(&symbol.name) => {
+                    self.builder.store_global(&symbol.name, value);
+                    Ok(())
+                }
+                SymbolKind::Cell | SymbolKind::Free => Err(unsupported("closures")),
+                _ => self.define(&symbol.name, value),
+            },
+            Target::Temp(index) => self.define(&format!("${}", index), value),
+            Target::Tuple(targets) if !targets.iter().any(|target| matches!(target, Target::Starred(_))) => {
+                let value = self.convert(value, Type::Object, "The unpacked value")?;
+                for (index, target) in targets.iter().enumerate() {
+                    let item = self.builder.unpack(value, index, targets.len());
+                    self.assign(target, item)?;
+                }
+                Ok(())
+            }
+            Target::Tuple(_) | Target::Starred(_) => Err(unsupported("starred unpacking")),
+            Target::Subscript { value: list, index } if matches!(list.ty, PyType::List(_)) => {
+                let (list, index) = self.list_index(list, index)?;
+                let value = self.convert(value, Type::Object, "A list item")?;
+                self.builder.set_item(list, index, value);
+                Ok(())
+            }
+            Target::Attribute { .. } | Target::Subscript { .. } => Err(unsupported("attributes and subscripts of anything but lists")),
+        }
+    }
+
+    fn define(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let variable = match self.variables.get(name) {
+            Some(variable) => *variable,
+            None => {
+                let ty = self.types.get(name).copied().unwrap_or(self.builder.value_type(value));
+                let variable = self.builder.declare_variable(ty);
+                self.variables.insert(name.to_string(), variable);
+                variable
+            }
+        };
+        let value = self.convert(value, self.builder.variable_type(variable), &format!("'{}'", name))?;
+        self.builder.def_var(variable, value);
+        Ok(())
+    }
+
+    /// Converts a value to `ty`: a `bool` can be used as an `int` and both
+    /// as an `f64`, any value is boxed into an `object`, and an `object` is
+    /// unboxed, which raises `TypeError` if it is of the wrong class.
+    fn convert(&mut self, value: Value, ty: Type, what: &str) -> Result<Value, String> {
+        match (self.builder.value_type(value), ty) {
+            (from, to) if from == to => Ok(value),
+            (Type::Bool, Type::Int | Type::F64) | (Type::Int, Type::F64) => Ok(self.builder.cast(value, ty)),
+            (_, Type::Object) => Ok(self.builder.box_value(value)),
+            (Type::Object, _) => Ok(self.builder.unbox(value, ty)),
+            (from, to) => Err(format!("{} holds both {} and {} values, which IR generation does not support yet", what, to, from)),
+        }
+    }
+
+    // ----- Expressions ------------------------------------------------------
+
+    fn expr(&mut self, expr: &Expr) -> Result<Value, String> {
+        match &expr.kind {
+            ExprKind::Int(value) => Ok(self.builder.constant(Constant::Int(*value))),
+            ExprKind::BigInt(value) => Ok(self.builder.constant(Constant::BigInt(Rc::new(value.clone())))),
+            ExprKind::Float(value) => Ok(self.builder.fconst(*value)),
+            ExprKind::Str(value) => Ok(self.builder.object_constant(Constant::Str(value.clone()))),
+            ExprKind::Bool(value) => Ok(self.builder.bconst(*value)),
+            ExprKind::None => Ok(self.builder.none()),
+            ExprKind::Name(symbol) => self.name(symbol, &expr.ty),
+            ExprKind::Temp(index) => match self.variables.get(&format!("${}", index)) {
+                Some(variable) => Ok(self.builder.use_var(*variable)),
+                None => Err(format!("the temporary ${} is used before it is assigned", index)),
+            },
+            ExprKind::Unary { operator, operand } => match operator.as_str() {
+                "not" => {
+                    let condition = self.condition(operand)?;
+                    Ok(self.builder.unary(UnaryOp::Not, condition))
+                }
+                "-" => {
+                    let operand = self.expr(operand)?;
+                    let operand = match self.builder.value_type(operand) {
+                        Type::Bool => self.builder.cast(operand, Type::Int),
+                        Type::Int | Type::F64 | Type::Object => operand,
+                        _ => self.builder.box_value(operand),
+                    };
+                    Ok(self.builder.unary(UnaryOp::Neg, operand))
+                }
+                "+" => {
+                    let operand = self.expr(operand)?;
+                    match self.builder.value_type(operand) {
+                        Type::Bool => Ok(self.builder.cast(operand, Type::Int)),
+                        Type::Int | Type::F64 => Ok(operand),
+                        _ => Err(unsupported("unary '+' on values that may not be numbers")),
+                    }
+                }
+                other => Err(unsupported(&format!("the operator '{}'", other))),
+            },
+            ExprKind::Binary { left, inplace: true, .. } if matches!(left.ty, PyType::List(_)) => {
+                Err(unsupported("in-place operators on lists"))
+            }
+            ExprKind::Binary { left, operator, right, .. } => {
+                let op = match operator.as_str() {
+                    "+" => BinaryOp::Add,
+                    "-" => BinaryOp::Sub,
+                    "*" => BinaryOp::Mul,
+                    "/" => BinaryOp::Div,
+                    "//" => BinaryOp::FloorDiv,
+                    "%" => BinaryOp::Mod,
+                    other => return Err(unsupported(&format!("the operator '{}'", other))),
+                };
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                self.arithmetic(op, left, right)
+            }
+            ExprKind::Compare { left, operators, comparators } => self.compare(left, operators, comparators),
+            ExprKind::BoolOp { operator, values } => self.bool_op(operator, values, &expr.ty),
+            ExprKind::Conditional { condition, body, orelse } => {
+                let ty = ir_type(&expr.ty, "The conditional expression")?;
+                let condition = self.condition(condition)?;
+                let then = self.builder.create_block();
+                let otherwise = self.builder.create_block();
+                let merge = self.builder.create_block();
+                let result = self.builder.append_block_param(merge, ty);
+                self.builder.cond_br(condition, then, Vec::new(), otherwise, Vec::new());
+                for (block, value) in [(then, body), (otherwise, orelse)] {
+                    self.builder.seal_block(block);
+                    self.builder.switch_to_block(block);
+                    let value = self.expr(value)?;
+                    let value = self.convert(value, ty, "The conditional expression")?;
+                    self.builder.br(merge, vec![value]);
+                }
+                self.builder.seal_block(merge);
+                self.builder.switch_to_block(merge);
+                Ok(result)
+            }
+            ExprKind::Call { function, arguments, keywords } => self.call(function, arguments, keywords),
+            ExprKind::Ellipsis => Err(unsupported(&format!("values of type '{}'", expr.ty))),
+            ExprKind::Subscript { value: list, index } if matches!(list.ty, PyType::List(_)) => {
+                let ty = ir_type(&expr.ty, "The list item")?;
+                let (list, index) = self.list_index(list, index)?;
+                let item = self.builder.get_item(list, index);
+                self.convert(item, ty, "The list item")
+            }
+            ExprKind::Attribute { .. } | ExprKind::Subscript { .. } | ExprKind::Slice { .. } => {
+                Err(unsupported("attributes and subscripts of anything but lists"))
+            }
+            ExprKind::Tuple(items) if !items.iter().any(|item| matches!(item.kind, ExprKind::Starred(_))) => {
+                let mut values = Vec::new();
+                for item in items {
+                    let value = self.expr(item)?;
+                    values.push(self.convert(value, Type::Object, "A tuple item")?);
+                }
+                Ok(self.builder.tuple(values))
+            }
+            ExprKind::List(items) if !items.iter().any(|item| matches!(item.kind, ExprKind::Starred(_))) => {
+                let mut values = Vec::new();
+                for item in items {
+                    let value = self.expr(item)?;
+                    values.push(self.convert(value, Type::Object, "A list item")?);
+                }
+                Ok(self.builder.list(values))
+            }
+            ExprKind::List(_) | ExprKind::Tuple(_) | ExprKind::Set(_) | ExprKind::Dict(_) | ExprKind::Starred(_) => {
+                Err(unsupported("containers"))
+            }
+            ExprKind::Function(_) | ExprKind::Class(_) => Err(unsupported("nested functions and classes")),
+            ExprKind::Import { .. } => Err(unsupported("imports")),
+            ExprKind::Yield(_) => Err(unsupported("generators")),
+            ExprKind::Imaginary(_) => Err(unsupported("complex numbers")),
+            ExprKind::Bytes(_) => Err(unsupported("bytes")),
+            ExprKind::JoinedStr(_) | ExprKind::FormattedValue { .. } => Err(unsupported("f-strings")),
+        }
+    }
+
+    /// An argument of `range`: an `int`, a `bool` converted to one or an
+    /// object unboxed into one.
+    fn int_expr(&mut self, expr: &Expr) -> Result<Value, String> {
+        let value = self.expr(expr)?;
+        match self.builder.value_type(value) {
+            Type::Int => Ok(value),
+            Type::Bool | Type::Object => self.convert(value, Type::Int, "The argument of 'range'"),
+            other => Err(unsupported(&format!("'range' with {} arguments", other))),
+        }
+    }
+
+    /// The list and the checked position of `list[index]`.
+    fn list_index(&mut self, list: &Expr, index: &Expr) -> Result<(Value, Value), String> {
+        let list = self.expr(list)?;
+        let index = self.expr(index)?;
+        let index = self.convert(index, Type::Int, "The list index")?;
+        let length = self.builder.len(list);
+        Ok((list, self.builder.check_index(index, length)))
+    }
+
+    /// Arithmetic on `int` values if both operands are `int`s or `bool`s,
+    /// on `f64` values if they are numbers otherwise, and on objects if
+    /// either may be something else.
+    fn arithmetic(&mut self, op: BinaryOp, left: Value, right: Value) -> Result<Value, String> {
+        let ty = match (self.builder.value_type(left), self.builder.value_type(right)) {
+            (Type::Int | Type::Bool, Type::Int | Type::Bool) => Type::Int,
+            (Type::Int | Type::F64 | Type::Bool, Type::Int | Type::F64 | Type::Bool) => Type::F64,
+            _ => Type::Object,
+        };
+        let what = format!("An operand of {}", op.name());
+        let left = self.convert(left, ty, &what)?;
+        let right = self.convert(right, ty, &what)?;
+        Ok(self.builder.binary(op, left, right))
+    }
+
+    fn name(&mut self, symbol: &hir::Symbol, ty: &PyType) -> Result<Value, String> {
+        match symbol.kind {
+            SymbolKind::Builtin => Err(unsupported(&format!("the builtin '{}' other than in calls", symbol.name))),
+            SymbolKind::Global if self.globals.functions.contains_key(&symbol.name) => {
+                Err(unsupported(&format!("the function '{}' other than in calls", symbol.name)))
+            }
+            SymbolKind::Global if !self.is_module || self.globals.shared.contains(&symbol.name) => {
+                let ty = ir_type(ty, &format!("'{}'", symbol.name))?;
+                Ok(self.builder.load_global(&symbol.name, ty))
+            }
+            SymbolKind::Cell | SymbolKind::Free => Err(unsupported("closures")),
+            _ => match self.variables.get(&symbol.name) {
+                Some(variable) => Ok(self.builder.use_var(*variable)),
+                None => Err(format!("name '{}' is not defined", symbol.name)),
+            },
+        }
+    }
+
+    /// The truth value of an expression, as a `bool`.
+    fn condition(&mut self, expr: &Expr) -> Result<Value, String> {
+        let value = self.expr(expr)?;
+        Ok(self.truth(value))
+    }
+
+    fn truth(&mut self, value: Value) -> Value {
+        match self.builder.value_type(value) {
+            Type::Bool => value,
+            Type::Int => {
+                let zero = self.builder.constant(Constant::Int(0));
+                self.builder.compare(CompareOp::Ne, value, zero)
+            }
+            Type::I64 => {
+                let zero = self.builder.iconst(0);
+                self.builder.compare(CompareOp::Ne, value, zero)
+            }
+            Type::F64 => {
+                let zero = self.builder.fconst(0.0);
+                self.builder.compare(CompareOp::Ne, value, zero)
+            }
+            Type::None => self.builder.bconst(false),
+            Type::Object => self.builder.truth(value),
+            Type::Ptr => {
+                let value = self.builder.box_value(value);
+                self.builder.truth(value)
+            }
+        }
+    }
+
+    /// `a < b < c` is `a < b and b < c`, with `b` evaluated once.
+    fn compare(&mut self, left: &Expr, operators: &[String], comparators: &[Expr]) -> Result<Value, String> {
+        let mut left = self.expr(left)?;
+        if let ([operator], [comparator]) = (operators, comparators) {
+            let right = self.expr(comparator)?;
+            return self.comparison(operator, left, right);
+        }
+        let merge = self.builder.create_block();
+        let result = self.builder.append_block_param(merge, Type::Bool);
+        for (index, (operator, comparator)) in operators.iter().zip(comparators).enumerate() {
+            let right = self.expr(comparator)?;
+            let condition = self.comparison(operator, left, right)?;
+            if index + 1 == operators.len() {
+                self.builder.br(merge, vec![condition]);
+            } else {
+                let next = self.builder.create_block();
+                self.builder.cond_br(condition, next, Vec::new(), merge, vec![condition]);
+                self.builder.seal_block(next);
+                self.builder.switch_to_block(next);
+            }
+            left = right;
+        }
+        self.builder.seal_block(merge);
+        self.builder.switch_to_block(merge);
+        Ok(result)
+    }
+
+    fn comparison(&mut self, operator: &str, left: Value, right: Value) -> Result<Value, String> {
+        let op = match operator {
+            "==" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            other => return Err(unsupported(&format!("the operator '{}'", other))),
+        };
+        // Numbers compare unboxed, like arithmetic, and anything else as
+        // objects. Python compares an `int` with a `float` exactly, so it
+        // is only converted when that cannot round it.
+        let ty = match (self.builder.value_type(left), self.builder.value_type(right)) {
+            (Type::Bool, Type::Bool) => Type::Bool,
+            (Type::Int | Type::Bool, Type::Int | Type::Bool) => Type::Int,
+            (Type::Int, Type::F64) if !self.is_exact_in_float(left) => Type::Object,
+            (Type::F64, Type::Int) if !self.is_exact_in_float(right) => Type::Object,
+            (Type::Int | Type::F64 | Type::Bool, Type::Int | Type::F64 | Type::Bool) => Type::F64,
+            _ => Type::Object,
+        };
+        let what = format!("An operand of {}", op.name());
+        let left = self.convert(left, ty, &what)?;
+        let right = self.convert(right, ty, &what)?;
+        Ok(self.builder.compare(op, left, right))
+    }
+
+    /// Whether `value` is an `int` constant that converts to a float
+    /// without rounding.
+    fn is_exact_in_float(&self, value: Value) -> bool {
+        let function = self.builder.function();
+        match function.value(value).def {
+            ValueDef::Result(inst) => {
+                matches!(function.inst(inst).kind, InstKind::Const(Constant::Int(value)) if value.unsigned_abs() <= 1 << f64::MANTISSA_DIGITS)
+            }
+            ValueDef::Param(_) => false,
+        }
+    }
+
+    /// `a and b` is `a` if `a` is false and `b` otherwise; `a or b` is `a`
+    /// if `a` is true.
+    fn bool_op(&mut self, operator: &str, values: &[Expr], ty: &PyType) -> Result<Value, String> {
+        let ty = ir_type(ty, &format!("The '{}' expression", operator))?;
+        let merge = self.builder.create_block();
+        let result = self.builder.append_block_param(merge, ty);
+        for (index, value) in values.iter().enumerate() {
+            let value = self.expr(value)?;
+            let value = self.convert(value, ty, &format!("The '{}' expression", operator))?;
+            if index + 1 == values.len() {
+                self.builder.br(merge, vec![value]);
+                break;
+            }
+            let condition = self.truth(value);
+            let next = self.builder.create_block();
+            if operator == "and" {
+                self.builder.cond_br(condition, next, Vec::new(), merge, vec![value]);
+            } else {
+                self.builder.cond_br(condition, merge, vec![value], next, Vec::new());
+            }
+            self.builder.seal_block(next);
+            self.builder.switch_to_block(next);
+        }
+        self.builder.seal_block(merge);
+        self.builder.switch_to_block(merge);
+        Ok(result)
+    }
+
+    fn call(&mut self, function: &Expr, arguments: &[Expr], keywords: &[hir::Keyword]) -> Result<Value, String> {
+        let ExprKind::Name(symbol) = &function.kind else {
+            return Err(unsupported("calls of anything but functions by name"));
+        };
+        if symbol.kind == SymbolKind::Builtin && symbol.name == "print" {
+            if !keywords.is_empty() {
+                return Err(unsupported("keyword arguments to 'print'"));
+            }
+            let values = arguments.iter().map(|argument| self.expr(argument)).collect::<Result<Vec<_>, _>>()?;
+            self.builder.print(values);
+            return Ok(self.builder.none());
+        }
+        if symbol.kind == SymbolKind::Builtin && symbol.name == "len" {
+            let [argument] = arguments else {
+                return Err(unsupported("this call of 'len'"));
+            };
+            if !keywords.is_empty() || matches!(argument.kind, ExprKind::Starred(_)) {
+                return Err(unsupported("this call of 'len'"));
+            }
+            let value = self.expr(argument)?;
+            let value = self.convert(value, Type::Object, "The argument of 'len'")?;
+            return Ok(self.builder.len(value));
+        }
+        let signature = match self.globals.functions.get(&symbol.name) {
+            Some(signature) if symbol.kind == SymbolKind::Global => signature,
+            _ => return Err(unsupported(&format!("calls of '{}'", symbol.name))),
+        };
+        let mut values: Vec<Option<Value>> = vec![None; signature.parameters.len()];
+        for (index, argument) in arguments.iter().enumerate() {
+            if index >= values.len() || matches!(argument.kind, ExprKind::Starred(_)) {
+                return Err(unsupported(&format!("this call of '{}'", symbol.name)));
+            }
+            values[index] = Some(self.expr(argument)?);
+        }
+        for keyword in keywords {
+            let index = keyword
+                .name
+                .as_ref()
+                .and_then(|name| signature.names.iter().position(|parameter| parameter == name))
+                .ok_or_else(|| unsupported(&format!("this call of '{}'", symbol.name)))?;
+            values[index] = Some(self.expr(&keyword.value)?);
+        }
+        let mut converted = Vec::new();
+        for (index, value) in values.into_iter().enumerate() {
+            let value = match (value, &signature.defaults[index]) {
+                (Some(value), _) => value,
+                (None, Some(default)) if signature.parameters[index] == Type::Object => self.builder.object_constant(default.clone()),
+                (None, Some(default)) => self.builder.constant(default.clone()),
+                (None, None) => return Err(format!("missing argument '{}' in call of '{}'", signature.names[index], symbol.name)),
+            };
+            let what = format!("The argument '{}' of '{}'", signature.names[index], symbol.name);
+            converted.push(self.convert(value, signature.parameters[index], &what)?);
+        }
+        Ok(self.builder.call(&symbol.name, converted, signature.returns))
+    }
+}
@@ -1,82 +1,1109 @@
-use crate::parser::parser::{Parser, ASTNode, ASTNodeType};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
+use crate::lexer::lexer::Span;
+use crate::runtime::bigint::BigInt;
+
+/// An SSA value: a block parameter or the result of an instruction. Each
+/// value is defined exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Block(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Inst(pub u32);
+
+/// The type of a value. `i64`, `f64`, `bool` and `ptr` are unboxed machine
+/// values; `object` is a boxed Python object of any class, whose operations
+/// dispatch on the class at runtime. `none` has the single value `None`.
+///
+/// `int` is a Python `int`: an `i64` while it fits, and a big integer
+/// otherwise. Its arithmetic checks for overflow and continues on big
+/// integers, where `i64` arithmetic wraps around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Type {
+    Int,
+    I64,
+    F64,
+    Bool,
+    /// The data of a `str`.
+    Ptr,
+    None,
+    Object,
+}
+
+impl Type {
+    /// Whether arithmetic works on values of the type.
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(self, Type::Int | Type::I64 | Type::F64 | Type::Object)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::I64 => write!(f, "i64"),
+            Type::F64 => write!(f, "f64"),
+            Type::Bool => write!(f, "bool"),
+            Type::Ptr => write!(f, "ptr"),
+            Type::None => write!(f, "none"),
+            Type::Object => write!(f, "object"),
+        }
+    }
+}
+
+/// The classes of the objects a type guard can test for, each with the
+/// unboxed type of its values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Int,
+    Float,
+    Bool,
+    Str,
+    NoneType,
+}
+
+impl Class {
+    pub const ALL: [Class; 5] = [Class::Int, Class::Float, Class::Bool, Class::Str, Class::NoneType];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Class::Int => "int",
+            Class::Float => "float",
+            Class::Bool => "bool",
+            Class::Str => "str",
+            Class::NoneType => "NoneType",
+        }
+    }
+
+    pub fn unboxed(&self) -> Type {
+        match self {
+            Class::Int => Type::Int,
+            Class::Float => Type::F64,
+            Class::Bool => Type::Bool,
+            Class::Str => Type::Ptr,
+            Class::NoneType => Type::None,
+        }
+    }
+
+    /// The class whose values unbox to `ty`, if `ty` is unboxed.
+    pub fn of_unboxed(ty: Type) -> Option<Class> {
+        Class::ALL.into_iter().find(|class| class.unboxed() == ty)
+    }
+}
+
+/// A constant has the unboxed type of its class, or is boxed when the
+/// instruction defining it has the type `object`. An `Int` may also be an
+/// `i64`; a `BigInt` does not fit in one.
 #[derive(Debug, Clone)]
-pub enum IR {
-    VariableDeclaration { name: String, value: i32 },
-    BinaryOperation { operator: String, left: Box<IR>, right: Box<IR> },
-    Immediate { value: i32, dest: String },
-    Print { var: String },
+pub enum Constant {
+    Int(i64),
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    Bool(bool),
+    None,
+    Str(String),
 }
 
-pub struct IRGenerator {
-    ir: Vec<IR>,
+impl Constant {
+    /// The value a variable reads as on a path where it was never assigned,
+    /// for the unboxed types other than `ptr`.
+    pub fn zero(ty: Type) -> Option<Constant> {
+        match ty {
+            Type::Int | Type::I64 => Some(Constant::Int(0)),
+            Type::F64 => Some(Constant::Float(0.0)),
+            Type::Bool => Some(Constant::Bool(false)),
+            Type::None => Some(Constant::None),
+            Type::Ptr | Type::Object => None,
+        }
+    }
+
+    pub fn class(&self) -> Class {
+        match self {
+            Constant::Int(_) | Constant::BigInt(_) => Class::Int,
+            Constant::Float(_) => Class::Float,
+            Constant::Bool(_) => Class::Bool,
+            Constant::None => Class::NoneType,
+            Constant::Str(_) => Class::Str,
+        }
+    }
+
+    /// The unboxed type of the constant.
+    pub fn ty(&self) -> Type {
+        self.class().unboxed()
+    }
 }
 
-impl IRGenerator {
-    pub fn new() -> Self {
-        IRGenerator { ir: Vec::new() }
+/// Floats are equal when their bits are, so that a constant equals itself
+/// even when it is a NaN.
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constant::Int(left), Constant::Int(right)) => left == right,
+            (Constant::BigInt(left), Constant::BigInt(right)) => left == right,
+            (Constant::Float(left), Constant::Float(right)) => left.to_bits() == right.to_bits(),
+            (Constant::Bool(left), Constant::Bool(right)) => left == right,
+            (Constant::None, Constant::None) => true,
+            (Constant::Str(left), Constant::Str(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Constant {}
+
+impl Hash for Constant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Constant::Int(value) => value.hash(state),
+            Constant::BigInt(value) => value.hash(state),
+            Constant::Float(value) => value.to_bits().hash(state),
+            Constant::Bool(value) => value.hash(state),
+            Constant::None => {}
+            Constant::Str(value) => value.hash(state),
+        }
+    }
+}
+
+/// Floats print as Rust does, always with a `.` or an exponent, and strings
+/// quoted with Rust's escapes.
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Int(value) => write!(f, "{}", value),
+            Constant::BigInt(value) => write!(f, "{}", value),
+            Constant::Float(value) => write!(f, "{:?}", value),
+            Constant::Bool(value) => write!(f, "{}", value),
+            Constant::None => write!(f, "none"),
+            Constant::Str(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+/// Arithmetic with Python's semantics: `floordiv` rounds towards negative
+/// infinity and `mod` takes the sign of the divisor. `div` is true division,
+/// which `i64` values do not have, and which gives an `f64` for `int`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl UnaryOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "neg",
+            UnaryOp::Not => "not",
+        }
+    }
+}
+
+impl BinaryOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::FloorDiv => "floordiv",
+            BinaryOp::Mod => "mod",
+        }
+    }
+}
+
+impl CompareOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "eq",
+            CompareOp::Ne => "ne",
+            CompareOp::Lt => "lt",
+            CompareOp::Le => "le",
+            CompareOp::Gt => "gt",
+            CompareOp::Ge => "ge",
+        }
+    }
+}
+
+/// An instruction. Arithmetic, negation and comparisons work on unboxed
+/// values, or on `object` values by dispatching on their classes, as Python
+/// does; both operands have the same type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InstKind {
+    Const(Constant),
+    Unary { op: UnaryOp, operand: Value },
+    Binary { op: BinaryOp, left: Value, right: Value },
+    Compare { op: CompareOp, left: Value, right: Value },
+    /// Converts a `bool` to an `int`, `i64` or `f64`, an `i64` to an `int`
+    /// or `f64`, or an `int` to an `f64`, which raises `OverflowError` if
+    /// it is too large.
+    Cast(Value),
+    /// Boxes an unboxed value into an `object`.
+    Box(Value),
+    /// Unboxes an `object` into the type of the result, raising `TypeError`
+    /// if its class does not fit: an `i64` also unboxes from a `bool`, and
+    /// an `f64` from an `int` or a `bool`.
+    Unbox(Value),
+    /// Whether an `object` is exactly of the class, so that it can be
+    /// unboxed on a fast path.
+    Guard { value: Value, class: Class },
+    /// The truth value of an `object`, as a `bool`.
+    Truth(Value),
+    /// A tuple of the `object`s, allocated on the heap.
+    Tuple(Vec<Value>),
+    /// Item `index` of an `object` unpacked into `length` values, as by
+    /// `a, b = t`, raising `TypeError` if it is not a tuple and `ValueError`
+    /// if its length differs.
+    Unpack { tuple: Value, index: usize, length: usize },
+    /// A list of the `object`s, allocated on the heap: a new object each
+    /// time, which the values holding it share.
+    List(Vec<Value>),
+    /// The length of an `object`, as an `int`, raising `TypeError` if it has
+    /// none. A list keeps the length it is made with.
+    Len(Value),
+    /// The position an `int` index stands for in a list of `length` items,
+    /// counting from the end if it is negative, raising `IndexError` if
+    /// there is none.
+    CheckIndex { index: Value, length: Value },
+    /// The item of a list at a position that `check_index` gave.
+    GetItem { list: Value, index: Value },
+    /// Stores an `object` into a list at a position that `check_index` gave.
+    SetItem { list: Value, index: Value, value: Value },
+    /// A direct call of a function of the module.
+    Call { function: String, arguments: Vec<Value> },
+    LoadGlobal(String),
+    StoreGlobal { name: String, value: Value },
+    /// Python's `print`: the values separated by spaces, then a newline.
+    Print(Vec<Value>),
+}
+
+impl InstKind {
+    /// The name the instruction is printed with.
+    pub fn opcode(&self) -> &'static str {
+        match self {
+            InstKind::Const(_) => "const",
+            InstKind::Unary { op, .. } => op.name(),
+            InstKind::Binary { op, .. } => op.name(),
+            InstKind::Compare { op, .. } => op.name(),
+            InstKind::Cast(_) => "cast",
+            InstKind::Box(_) => "box",
+            InstKind::Unbox(_) => "unbox",
+            InstKind::Guard { .. } => "guard",
+            InstKind::Truth(_) => "truth",
+            InstKind::Tuple(_) => "tuple",
+            InstKind::Unpack { .. } => "unpack",
+            InstKind::List(_) => "list",
+            InstKind::Len(_) => "len",
+            InstKind::CheckIndex { .. } => "check_index",
+            InstKind::GetItem { .. } => "get_item",
+            InstKind::SetItem { .. } => "set_item",
+            InstKind::Call { .. } => "call",
+            InstKind::LoadGlobal(_) => "load_global",
+            InstKind::StoreGlobal { .. } => "store_global",
+            InstKind::Print(_) => "print",
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            InstKind::Const(_) | InstKind::LoadGlobal(_) => Vec::new(),
+            InstKind::Unary { operand, .. }
+            | InstKind::Cast(operand)
+            | InstKind::Box(operand)
+            | InstKind::Unbox(operand)
+            | InstKind::Guard { value: operand, .. }
+            | InstKind::Truth(operand)
+            | InstKind::Unpack { tuple: operand, .. }
+            | InstKind::Len(operand)
+            | InstKind::StoreGlobal { value: operand, .. } => vec![*operand],
+            InstKind::Binary { left, right, .. }
+            | InstKind::Compare { left, right, .. }
+            | InstKind::CheckIndex { index: left, length: right }
+            | InstKind::GetItem { list: left, index: right } => vec![*left, *right],
+            InstKind::SetItem { list, index, value } => vec![*list, *index, *value],
+            InstKind::Call { arguments: values, .. } | InstKind::Tuple(values) | InstKind::List(values) | InstKind::Print(values) => {
+                values.clone()
+            }
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            InstKind::Const(_) | InstKind::LoadGlobal(_) => Vec::new(),
+            InstKind::Unary { operand, .. }
+            | InstKind::Cast(operand)
+            | InstKind::Box(operand)
+            | InstKind::Unbox(operand)
+            | InstKind::Guard { value: operand, .. }
+            | InstKind::Truth(operand)
+            | InstKind::Unpack { tuple: operand, .. }
+            | InstKind::Len(operand)
+            | InstKind::StoreGlobal { value: operand, .. } => vec![operand],
+            InstKind::Binary { left, right, .. }
+            | InstKind::Compare { left, right, .. }
+            | InstKind::CheckIndex { index: left, length: right }
+            | InstKind::GetItem { list: left, index: right } => vec![left, right],
+            InstKind::SetItem { list, index, value } => vec![list, index, value],
+            InstKind::Call { arguments: values, .. } | InstKind::Tuple(values) | InstKind::List(values) | InstKind::Print(values) => {
+                values.iter_mut().collect()
+            }
+        }
+    }
+
+    /// What the instruction does besides computing its result, whatever
+    /// the types of its operands. See also `Function::effects`.
+    pub fn effects(&self) -> Effects<'_> {
+        match self {
+            // The callee may do anything.
+            InstKind::Call { .. } => {
+                Effects { reads: Memory::Everything, writes: Memory::Everything, may_raise: true, prints: true, allocates: true }
+            }
+            InstKind::LoadGlobal(name) => Effects { reads: Memory::Global(name), ..Effects::NONE },
+            InstKind::StoreGlobal { name, .. } => Effects { writes: Memory::Global(name), ..Effects::NONE },
+            InstKind::Print(_) => Effects { prints: true, ..Effects::NONE },
+            // ZeroDivisionError.
+            InstKind::Binary { op: BinaryOp::Div | BinaryOp::FloorDiv | BinaryOp::Mod, .. } => Effects { may_raise: true, ..Effects::NONE },
+            // TypeError, ValueError for an unpacking and IndexError for an
+            // index.
+            InstKind::Unbox(_) | InstKind::Unpack { .. } | InstKind::Len(_) | InstKind::CheckIndex { .. } => {
+                Effects { may_raise: true, ..Effects::NONE }
+            }
+            InstKind::List(_) => Effects { allocates: true, ..Effects::NONE },
+            InstKind::GetItem { .. } => Effects { reads: Memory::Lists, ..Effects::NONE },
+            InstKind::SetItem { .. } => Effects { writes: Memory::Lists, ..Effects::NONE },
+            _ => Effects::NONE,
+        }
+    }
+
+    /// Whether the instruction does anything besides computing its result:
+    /// writes memory, prints, calls a function or may raise, whatever the
+    /// types of its operands. See also `Function::has_side_effects`.
+    pub fn has_side_effects(&self) -> bool {
+        self.effects().has_side_effects()
+    }
+}
+
+/// The memory an instruction reads or writes. Values themselves are
+/// immutable, except for lists, so the only memory is that of the global
+/// variables and the items of lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Memory<'a> {
+    Nothing,
+    /// The global variable of the name.
+    Global(&'a str),
+    /// The items of every list.
+    Lists,
+    Everything,
+}
+
+impl Memory<'_> {
+    pub fn includes_global(&self, name: &str) -> bool {
+        match self {
+            Memory::Nothing | Memory::Lists => false,
+            Memory::Global(global) => *global == name,
+            Memory::Everything => true,
+        }
+    }
+
+    /// Whether some of the memory is in both.
+    pub fn overlaps(&self, other: &Memory) -> bool {
+        match (self, other) {
+            (Memory::Nothing, _) | (_, Memory::Nothing) => false,
+            (Memory::Everything, _) | (_, Memory::Everything) => true,
+            (Memory::Global(name), other) | (other, Memory::Global(name)) => other.includes_global(name),
+            (Memory::Lists, Memory::Lists) => true,
+        }
+    }
+}
+
+/// The effect model of the IR: what an instruction does besides computing
+/// its result. An instruction that only reads memory computes the same
+/// result again as long as nothing writes that memory in between, and one
+/// that has no effects, or only may raise, whenever its operands are the
+/// same: if it raised, the second computation is not reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Effects<'a> {
+    pub reads: Memory<'a>,
+    pub writes: Memory<'a>,
+    /// May raise an exception.
+    pub may_raise: bool,
+    /// Writes output.
+    pub prints: bool,
+    /// Makes a new mutable object, so that an instruction that does is
+    /// never replaced by an earlier one, nor moved out of a loop.
+    pub allocates: bool,
+}
+
+impl Effects<'_> {
+    pub const NONE: Effects<'static> =
+        Effects { reads: Memory::Nothing, writes: Memory::Nothing, may_raise: false, prints: false, allocates: false };
+
+    /// Whether the instruction must stay even when its result is unused.
+    pub fn has_side_effects(&self) -> bool {
+        self.writes != Memory::Nothing || self.may_raise || self.prints
+    }
+
+    /// Whether the instruction computes the same result from the same
+    /// operands, wherever it is: it reads and writes no memory, prints and
+    /// allocates nothing, but may raise.
+    pub fn is_pure(&self) -> bool {
+        self.reads == Memory::Nothing && self.writes == Memory::Nothing && !self.prints && !self.allocates
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InstData {
+    pub kind: InstKind,
+    pub result: Option<Value>,
+    pub span: Span,
+    /// Where the instruction was inlined, if it comes from the body of
+    /// another function. `span` is then a place in that function.
+    pub inlined_at: Option<Rc<InlinedAt>>,
+}
+
+/// A call that was replaced by the body of the function it called. Calls
+/// inlined into inlined code form a chain, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlinedAt {
+    /// The function whose body was inlined.
+    pub function: String,
+    /// The span of the call.
+    pub call: Span,
+    /// Where the call itself was inlined.
+    pub caller: Option<Rc<InlinedAt>>,
+}
+
+impl InlinedAt {
+    /// The chain of `inlined_at`, followed by `outer` where it ends: where
+    /// an instruction inlined at `inlined_at` ends up once the function it
+    /// is in is itself inlined at `outer`.
+    pub fn within(inlined_at: Option<&Rc<InlinedAt>>, outer: &Rc<InlinedAt>) -> Rc<InlinedAt> {
+        match inlined_at {
+            None => outer.clone(),
+            Some(inlined_at) => Rc::new(InlinedAt {
+                function: inlined_at.function.clone(),
+                call: inlined_at.call,
+                caller: Some(InlinedAt::within(inlined_at.caller.as_ref(), outer)),
+            }),
+        }
+    }
+}
+
+/// A frame of a traceback: a function, and the place in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub function: String,
+    pub span: Span,
+}
+
+impl Frame {
+    /// The frames of the program among `frames`, outermost first: without
+    /// those of the calls a function makes to its own specialization,
+    /// which are at no place in the source.
+    pub fn of_source(frames: Vec<Frame>) -> Vec<Frame> {
+        let mut source: Vec<Frame> = Vec::with_capacity(frames.len());
+        for frame in frames.into_iter().rev() {
+            let synthetic = source.last().is_some_and(|inner| inner.function == frame.function) && frame.span == Span::default();
+            if !synthetic {
+                source.push(frame);
+            }
+        }
+        source.reverse();
+        source
+    }
+}
+
+/// Whether a function is to be inlined, as its `@pybolt.inline` or
+/// `@pybolt.noinline` decorator asks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InlineHint {
+    /// The inliner decides.
+    #[default]
+    Default,
+    /// Inline at every call.
+    Always,
+    /// Never inline.
+    Never,
+}
+
+/// An edge to a block, passing a value for each of its parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockCall {
+    pub block: Block,
+    pub arguments: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Br(BlockCall),
+    CondBr { condition: Value, then: BlockCall, otherwise: BlockCall },
+    /// Returns a value, or `None` from a function returning `none`.
+    Return(Option<Value>),
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn successors(&self) -> Vec<&BlockCall> {
+        match self {
+            Terminator::Br(target) => vec![target],
+            Terminator::CondBr { then, otherwise, .. } => vec![then, otherwise],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockCall> {
+        match self {
+            Terminator::Br(target) => vec![target],
+            Terminator::CondBr { then, otherwise, .. } => vec![then, otherwise],
+            Terminator::Return(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The values the terminator uses, including the block arguments.
+    pub fn operands(&self) -> Vec<Value> {
+        let mut operands = match self {
+            Terminator::CondBr { condition, .. } => vec![*condition],
+            Terminator::Return(Some(value)) => vec![*value],
+            _ => Vec::new(),
+        };
+        for target in self.successors() {
+            operands.extend(&target.arguments);
+        }
+        operands
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Br(target) => target.arguments.iter_mut().collect(),
+            Terminator::CondBr { condition, then, otherwise } => {
+                let mut operands = vec![condition];
+                operands.extend(then.arguments.iter_mut());
+                operands.extend(otherwise.arguments.iter_mut());
+                operands
+            }
+            Terminator::Return(Some(value)) => vec![value],
+            Terminator::Return(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+/// A basic block: parameters, which take the place of phi nodes, then
+/// instructions, then exactly one terminator once the block is complete.
+#[derive(Debug, Clone, Default)]
+pub struct BlockData {
+    pub params: Vec<Value>,
+    pub insts: Vec<Inst>,
+    pub terminator: Option<Terminator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueDef {
+    Param(Block),
+    Result(Inst),
+}
+
+#[derive(Debug, Clone)]
+pub struct ValueData {
+    pub ty: Type,
+    pub def: ValueDef,
+}
+
+/// A function in SSA form. Blocks, instructions and values live in arenas
+/// indexed by `Block`, `Inst` and `Value`; `layout` holds the blocks that are
+/// part of the function, in order, and a block's `insts` the instructions
+/// that are part of it. Removing a block or instruction only takes it out
+/// of the layout.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    /// The function of the program this one comes from, which tracebacks
+    /// name: `name`, or the function a specialization copies.
+    pub origin: String,
+    pub return_type: Type,
+    pub blocks: Vec<BlockData>,
+    pub insts: Vec<InstData>,
+    pub values: Vec<ValueData>,
+    /// The first block is the entry block, whose parameters are the
+    /// function's.
+    pub layout: Vec<Block>,
+    pub inline: InlineHint,
+}
+
+impl Function {
+    pub fn new(name: &str, params: &[Type], return_type: Type) -> Self {
+        let mut function = Function {
+            name: name.to_string(),
+            origin: name.to_string(),
+            return_type,
+            blocks: Vec::new(),
+            insts: Vec::new(),
+            values: Vec::new(),
+            layout: Vec::new(),
+            inline: InlineHint::Default,
+        };
+        let entry = function.create_block();
+        for ty in params {
+            function.append_block_param(entry, *ty);
+        }
+        function
+    }
+
+    pub fn entry(&self) -> Block {
+        self.layout[0]
+    }
+
+    pub fn params(&self) -> &[Value] {
+        &self.block(self.entry()).params
+    }
+
+    pub fn param_types(&self) -> Vec<Type> {
+        self.params().iter().map(|param| self.value_type(*param)).collect()
+    }
+
+    pub fn block(&self, block: Block) -> &BlockData {
+        &self.blocks[block.0 as usize]
+    }
+
+    pub fn block_mut(&mut self, block: Block) -> &mut BlockData {
+        &mut self.blocks[block.0 as usize]
+    }
+
+    pub fn inst(&self, inst: Inst) -> &InstData {
+        &self.insts[inst.0 as usize]
+    }
+
+    pub fn inst_mut(&mut self, inst: Inst) -> &mut InstData {
+        &mut self.insts[inst.0 as usize]
+    }
+
+    pub fn value(&self, value: Value) -> &ValueData {
+        &self.values[value.0 as usize]
+    }
+
+    pub fn value_type(&self, value: Value) -> Type {
+        self.value(value).ty
+    }
+
+    /// Creates an empty block at the end of the layout.
+    pub fn create_block(&mut self) -> Block {
+        let block = Block(self.blocks.len() as u32);
+        self.blocks.push(BlockData::default());
+        self.layout.push(block);
+        block
+    }
+
+    pub fn append_block_param(&mut self, block: Block, ty: Type) -> Value {
+        let value = self.make_value(ty, ValueDef::Param(block));
+        self.block_mut(block).params.push(value);
+        value
+    }
+
+    /// Creates an instruction, with a result if `result` is given, without
+    /// placing it in a block.
+    pub fn make_inst(&mut self, kind: InstKind, result: Option<Type>, span: Span) -> Inst {
+        let inst = Inst(self.insts.len() as u32);
+        let result = result.map(|ty| self.make_value(ty, ValueDef::Result(inst)));
+        self.insts.push(InstData { kind, result, span, inlined_at: None });
+        inst
+    }
+
+    /// Creates an instruction as `make_inst` does, with the debug info of
+    /// `like`, the instruction it computes for: its span and the calls it
+    /// was inlined at.
+    pub fn make_inst_like(&mut self, like: Inst, kind: InstKind, result: Option<Type>) -> Inst {
+        let InstData { span, inlined_at, .. } = self.inst(like).clone();
+        let inst = self.make_inst(kind, result, span);
+        self.inst_mut(inst).inlined_at = inlined_at;
+        inst
+    }
+
+    fn make_value(&mut self, ty: Type, def: ValueDef) -> Value {
+        let value = Value(self.values.len() as u32);
+        self.values.push(ValueData { ty, def });
+        value
+    }
+
+    pub fn result(&self, inst: Inst) -> Option<Value> {
+        self.inst(inst).result
+    }
+
+    /// The frames of a traceback through the instruction, outermost first:
+    /// the function, then each function inlined on the way to the
+    /// instruction, at the call that was inlined.
+    pub fn frames(&self, inst: Inst) -> Vec<Frame> {
+        let data = self.inst(inst);
+        let mut frames = Vec::new();
+        let mut span = data.span;
+        let mut inlined_at = data.inlined_at.as_deref();
+        while let Some(site) = inlined_at {
+            frames.push(Frame { function: site.function.clone(), span });
+            span = site.call;
+            inlined_at = site.caller.as_deref();
+        }
+        frames.push(Frame { function: self.origin.clone(), span });
+        frames.reverse();
+        Frame::of_source(frames)
+    }
+
+    /// What the instruction does besides computing its result. Besides
+    /// the effects of `InstKind::effects`, operations on `object` values
+    /// may raise `TypeError`, and an `int` cast to `f64` `OverflowError`.
+    pub fn effects(&self, inst: Inst) -> Effects<'_> {
+        let kind = &self.inst(inst).kind;
+        let mut effects = kind.effects();
+        effects.may_raise |= matches!(kind, InstKind::Unary { .. } | InstKind::Binary { .. } | InstKind::Compare { .. })
+            && kind.operands().iter().any(|operand| self.value_type(*operand) == Type::Object)
+            || matches!(kind, InstKind::Cast(value) if self.value_type(*value) == Type::Int);
+        effects
+    }
+
+    /// Whether the instruction has side effects. Instructions without them
+    /// can be removed when their result is unused.
+    pub fn has_side_effects(&self, inst: Inst) -> bool {
+        self.effects(inst).has_side_effects()
+    }
+
+    pub fn terminator(&self, block: Block) -> Option<&Terminator> {
+        self.block(block).terminator.as_ref()
+    }
+
+    pub fn successors(&self, block: Block) -> Vec<Block> {
+        let mut successors: Vec<Block> = Vec::new();
+        for target in self.terminator(block).map(Terminator::successors).unwrap_or_default() {
+            if !successors.contains(&target.block) {
+                successors.push(target.block);
+            }
+        }
+        successors
+    }
+
+    /// The predecessors of every block in the layout, in layout order.
+    pub fn predecessors(&self) -> HashMap<Block, Vec<Block>> {
+        let mut predecessors: HashMap<Block, Vec<Block>> = self.layout.iter().map(|block| (*block, Vec::new())).collect();
+        for block in &self.layout {
+            for successor in self.successors(*block) {
+                predecessors.entry(successor).or_default().push(*block);
+            }
+        }
+        predecessors
+    }
+
+    /// The instructions of the layout, in order.
+    pub fn layout_insts(&self) -> impl Iterator<Item = Inst> + '_ {
+        self.layout.iter().flat_map(move |block| self.block(*block).insts.iter().copied())
     }
 
-    pub fn generate(&mut self, parser: &Parser) -> Result<Vec<IR>, String> {
-        let ast = parser.get_ast();
-        self.visit_node(&ast)?;
-        println!("IR generation completed.");
-        Ok(self.ir.clone())
+    pub fn instruction_count(&self) -> usize {
+        self.layout.iter().map(|block| self.block(*block).insts.len()).sum()
     }
 
-    fn visit_node(&mut self, node: &ASTNode) -> Result<(), String> {
-        match node {
-            ASTNode::Program(nodes) => {
-                for child in nodes {
-                    self.visit_node(child)?;
+    /// Replaces every use of `old` by `new`.
+    pub fn replace_uses(&mut self, old: Value, new: Value) {
+        for block in self.layout.clone() {
+            for inst in self.block(block).insts.clone() {
+                for operand in self.inst_mut(inst).kind.operands_mut() {
+                    if *operand == old {
+                        *operand = new;
+                    }
                 }
             }
-            ASTNode::VariableDeclaration { name, value } => {
-                if let ASTNode::Number(n) = **value {
-                    self.ir.push(IR::VariableDeclaration { name: name.clone(), value: n });
-                } else {
-                    return Err("Expected a number in variable declaration".to_string());
+            if let Some(terminator) = &mut self.block_mut(block).terminator {
+                for operand in terminator.operands_mut() {
+                    if *operand == old {
+                        *operand = new;
+                    }
                 }
             }
-            ASTNode::BinaryOperation { operator, left, right } => {
-                let left_ir = self.generate_ir_from_node(left)?;
-                let right_ir = self.generate_ir_from_node(right)?;
-                self.ir.push(IR::BinaryOperation {
-                    operator: operator.clone(),
-                    left: Box::new(left_ir),
-                    right: Box::new(right_ir),
-                });
+        }
+    }
+
+    /// Takes the blocks that cannot be reached from the entry block out of
+    /// the layout. Returns whether any were removed.
+    pub fn remove_unreachable_blocks(&mut self) -> bool {
+        let mut reachable = HashSet::from([self.entry()]);
+        let mut worklist = vec![self.entry()];
+        while let Some(block) = worklist.pop() {
+            for successor in self.successors(block) {
+                if reachable.insert(successor) {
+                    worklist.push(successor);
+                }
             }
-            ASTNode::Number(n) => {
-                self.ir.push(IR::Immediate { value: *n, dest: "temp".to_string() });
+        }
+        let before = self.layout.len();
+        self.layout.retain(|block| reachable.contains(block));
+        self.layout.len() != before
+    }
+
+    /// Removes the parameter at `index` of `block`, and the argument for it
+    /// from every branch to the block. Its uses must be replaced as well.
+    pub fn remove_block_param(&mut self, block: Block, index: usize) {
+        self.block_mut(block).params.remove(index);
+        for predecessor in self.layout.clone() {
+            if let Some(terminator) = &mut self.block_mut(predecessor).terminator {
+                for target in terminator.successors_mut() {
+                    if target.block == block {
+                        target.arguments.remove(index);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes the block parameters that receive the same value, or
+    /// themselves, from every predecessor, replacing them with that value.
+    /// Returns whether any were removed.
+    pub fn remove_trivial_block_params(&mut self) -> bool {
+        let mut removed_any = false;
+        loop {
+            let mut removed = false;
+            let predecessors = self.predecessors();
+            let layout = self.layout.clone();
+            for block in layout.into_iter().skip(1) {
+                let edges: Vec<Block> = predecessors.get(&block).cloned().unwrap_or_default();
+                if edges.is_empty() {
+                    continue;
+                }
+                for index in (0..self.block(block).params.len()).rev() {
+                    let param = self.block(block).params[index];
+                    let mut incoming = None;
+                    let mut trivial = true;
+                    for predecessor in &edges {
+                        for target in self.terminator(*predecessor).map(Terminator::successors).unwrap_or_default() {
+                            if target.block != block || target.arguments[index] == param {
+                                continue;
+                            }
+                            match incoming {
+                                None => incoming = Some(target.arguments[index]),
+                                Some(value) if value == target.arguments[index] => {}
+                                Some(_) => trivial = false,
+                            }
+                        }
+                    }
+                    let (true, Some(value)) = (trivial, incoming) else {
+                        continue;
+                    };
+                    self.remove_block_param(block, index);
+                    self.replace_uses(param, value);
+                    removed = true;
+                }
             }
-            ASTNode::Expression(ASTNodeType::Identifier(name)) => {
-                self.ir.push(IR::Print { var: name.clone() });
+            if !removed {
+                return removed_any;
             }
-            _ => return Err("Unsupported AST node type".to_string()),
+            removed_any = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Module {
+    pub functions: Vec<Function>,
+}
+
+impl Module {
+    pub fn new() -> Self {
+        Module::default()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    pub fn function_mut(&mut self, name: &str) -> Option<&mut Function> {
+        self.functions.iter_mut().find(|function| function.name == name)
+    }
+
+    /// The number of calls of each function that is called.
+    pub fn call_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for function in &self.functions {
+            for inst in function.layout_insts() {
+                if let InstKind::Call { function: callee, .. } = &function.inst(inst).kind {
+                    *counts.entry(callee.clone()).or_default() += 1;
+                }
+            }
+        }
+        counts
+    }
+}
+
+/// Prints a function with its values and blocks numbered in layout order,
+/// and its inlining hint, if any, after the return type:
+///
+/// ```text
+/// fn @add(i64, i64) -> i64 {
+/// bb0(%0: i64, %1: i64):
+///     %2: i64 = add %0, %1
+///     ret %2
+/// }
+/// ```
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = Names::new(self);
+        let params: Vec<String> = self.param_types().iter().map(ToString::to_string).collect();
+        let hint = match self.inline {
+            InlineHint::Default => "",
+            InlineHint::Always => " inline",
+            InlineHint::Never => " noinline",
+        };
+        writeln!(f, "fn @{}({}) -> {}{} {{", self.name, params.join(", "), self.return_type, hint)?;
+        for block in &self.layout {
+            let data = self.block(*block);
+            write!(f, "{}", names.block(*block))?;
+            if !data.params.is_empty() {
+                let params: Vec<String> = data
+                    .params
+                    .iter()
+                    .map(|param| format!("{}: {}", names.value(*param), self.value_type(*param)))
+                    .collect();
+                write!(f, "({})", params.join(", "))?;
+            }
+            writeln!(f, ":")?;
+            for inst in &data.insts {
+                writeln!(f, "    {}", names.inst(self, *inst))?;
+            }
+            match &data.terminator {
+                Some(terminator) => writeln!(f, "    {}", names.terminator(terminator))?,
+                None => writeln!(f, "    ; missing terminator")?,
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
         }
         Ok(())
     }
+}
+
+/// The printed names of a function's values and blocks.
+struct Names {
+    values: HashMap<Value, usize>,
+    blocks: HashMap<Block, usize>,
+}
+
+impl Names {
+    fn new(function: &Function) -> Self {
+        let mut values = HashMap::new();
+        let mut blocks = HashMap::new();
+        for block in &function.layout {
+            blocks.insert(*block, blocks.len());
+            let data = function.block(*block);
+            for param in &data.params {
+                values.insert(*param, values.len());
+            }
+            for inst in &data.insts {
+                if let Some(result) = function.result(*inst) {
+                    values.insert(result, values.len());
+                }
+            }
+        }
+        Names { values, blocks }
+    }
+
+    /// Values outside the layout print with their arena index and a `!`.
+    fn value(&self, value: Value) -> String {
+        match self.values.get(&value) {
+            Some(index) => format!("%{}", index),
+            None => format!("%!{}", value.0),
+        }
+    }
+
+    fn values(&self, values: &[Value]) -> String {
+        values.iter().map(|value| self.value(*value)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn block(&self, block: Block) -> String {
+        match self.blocks.get(&block) {
+            Some(index) => format!("bb{}", index),
+            None => format!("bb!{}", block.0),
+        }
+    }
 
-    fn generate_ir_from_node(&mut self, node: &ASTNode) -> Result<IR, String> {
-        match node {
-            ASTNode::Number(n) => Ok(IR::Immediate { value: *n, dest: "temp".to_string() }),
-            ASTNode::BinaryOperation { operator, left, right } => {
-                let left_ir = self.generate_ir_from_node(left)?;
-                let right_ir = self.generate_ir_from_node(right)?;
-                Ok(IR::BinaryOperation {
-                    operator: operator.clone(),
-                    left: Box::new(left_ir),
-                    right: Box::new(right_ir),
-                })
+    fn block_call(&self, target: &BlockCall) -> String {
+        if target.arguments.is_empty() {
+            self.block(target.block)
+        } else {
+            format!("{}({})", self.block(target.block), self.values(&target.arguments))
+        }
+    }
+
+    fn inst(&self, function: &Function, inst: Inst) -> String {
+        let data = function.inst(inst);
+        let text = match &data.kind {
+            InstKind::Const(constant) => format!("const {}", constant),
+            InstKind::Unary { op, operand } => format!("{} {}", op.name(), self.value(*operand)),
+            InstKind::Binary { op, left, right } => format!("{} {}, {}", op.name(), self.value(*left), self.value(*right)),
+            InstKind::Compare { op, left, right } => format!("{} {}, {}", op.name(), self.value(*left), self.value(*right)),
+            InstKind::Cast(value) => format!("cast {}", self.value(*value)),
+            InstKind::Box(value) => format!("box {}", self.value(*value)),
+            InstKind::Unbox(value) => format!("unbox {}", self.value(*value)),
+            InstKind::Guard { value, class } => format!("guard {}, {}", self.value(*value), class.name()),
+            InstKind::Truth(value) => format!("truth {}", self.value(*value)),
+            InstKind::Tuple(values) => format!("tuple {}", self.values(values)).trim_end().to_string(),
+            InstKind::Unpack { tuple, index, length } => format!("unpack {}, {}, {}", self.value(*tuple), index, length),
+            InstKind::List(values) => format!("list {}", self.values(values)).trim_end().to_string(),
+            InstKind::Len(value) => format!("len {}", self.value(*value)),
+            InstKind::CheckIndex { index, length } => format!("check_index {}, {}", self.value(*index), self.value(*length)),
+            InstKind::GetItem { list, index } => format!("get_item {}, {}", self.value(*list), self.value(*index)),
+            InstKind::SetItem { list, index, value } => {
+                format!("set_item {}, {}, {}", self.value(*list), self.value(*index), self.value(*value))
             }
-            ASTNode::Expression(ASTNodeType::Identifier(name)) => Ok(IR::Print { var: name.clone() }),
-            _ => Err("Unsupported AST node type".to_string()),
+            InstKind::Call { function, arguments } => format!("call @{}({})", function, self.values(arguments)),
+            InstKind::LoadGlobal(name) => format!("load_global @{}", name),
+            InstKind::StoreGlobal { name, value } => format!("store_global @{}, {}", name, self.value(*value)),
+            InstKind::Print(values) => format!("print {}", self.values(values)).trim_end().to_string(),
+        };
+        match data.result {
+            Some(result) => format!("{}: {} = {}", self.value(result), function.value_type(result), text),
+            None => text,
         }
     }
 
-    // Add the get_ir method
-    pub fn get_ir(&self) -> &Vec<IR> {
-        &self.ir
+    fn terminator(&self, terminator: &Terminator) -> String {
+        match terminator {
+            Terminator::Br(target) => format!("br {}", self.block_call(target)),
+            Terminator::CondBr { condition, then, otherwise } => format!(
+                "cond_br {}, {}, {}",
+                self.value(*condition),
+                self.block_call(then),
+                self.block_call(otherwise)
+            ),
+            Terminator::Return(Some(value)) => format!("ret {}", self.value(*value)),
+            Terminator::Return(None) => "ret".to_string(),
+            Terminator::Unreachable => "unreachable".to_string(),
+        }
     }
 }
@@ -1 +1,5 @@
-
+pub mod builder;
+pub mod generator;
+pub mod ir;
+pub mod parser;
+pub mod verifier;
@@ -0,0 +1,579 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+use crate::ir::ir::{
+    BinaryOp, Block, BlockCall, Class, CompareOp, Constant, Function, InlineHint, Inst, InstKind, Module, Terminator, Type,
+    UnaryOp, Value,
+};
+use crate::lexer::lexer::Span;
+use crate::runtime::bigint::BigInt;
+
+/// Parses IR in the text format that `Module` prints, so that printing the
+/// result gives back the same text, up to the numbering of values and
+/// blocks. Hand-written IR may name values and blocks with any identifier,
+/// as in `%sum` or `loop`, and may use a value before the line defining it.
+/// A `;` starts a comment that runs to the end of the line.
+///
+/// ```text
+/// fn @add(i64, i64) -> i64 {
+/// bb0(%0: i64, %1: i64):
+///     %2: i64 = add %0, %1
+///     ret %2
+/// }
+/// ```
+///
+/// The parser checks the syntax and that every name is defined once; the
+/// rest, such as types matching, is left to the verifier.
+pub fn parse(text: &str) -> Result<Module, String> {
+    let lines = tokenize(text)?;
+    let mut module = Module::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let (function, next) = parse_function(&lines, index)?;
+        if module.function(&function.name).is_some() {
+            return Err(format!("line {}: function @{} is defined twice", lines[index].number, function.name));
+        }
+        module.functions.push(function);
+        index = next;
+    }
+    Ok(module)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// An opcode, type, block name or constant.
+    Word(String),
+    /// `@name`
+    Global(String),
+    /// `%name`
+    Local(String),
+    /// A string in double quotes, unescaped.
+    Str(String),
+    Punct(char),
+    Arrow,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Global(name) => format!("'@{}'", name),
+            Token::Local(name) => format!("'%{}'", name),
+            Token::Str(value) => format!("{:?}", value),
+            Token::Punct(c) => format!("'{}'", c),
+            Token::Arrow => "'->'".to_string(),
+        }
+    }
+}
+
+/// A line with tokens, and a cursor over them.
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn read_word(chars: &mut Peekable<Chars>, mut word: String) -> String {
+    loop {
+        while let Some(c) = chars.next_if(|c| is_word_char(*c)) {
+            word.push(c);
+        }
+        // The sign of the exponent of a float, as in `1e-7`.
+        let is_number = word.trim_start_matches('-').starts_with(|c: char| c.is_ascii_digit());
+        match chars.peek() {
+            Some('-' | '+') if is_number && word.ends_with('e') => word.push(chars.next().expect("peeked")),
+            _ => return word,
+        }
+    }
+}
+
+/// Reads a string up to its closing quote, undoing the escapes of Rust's
+/// `{:?}`.
+fn read_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => value.push(match chars.next()? {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                'u' => {
+                    if chars.next()? != '{' {
+                        return None;
+                    }
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| *c != '}') {
+                        digits.push(c);
+                    }
+                    chars.next()?;
+                    char::from_u32(u32::from_str_radix(&digits, 16).ok()?)?
+                }
+                c @ ('\\' | '"' | '\'') => c,
+                _ => return None,
+            }),
+            c => value.push(c),
+        }
+    }
+}
+
+/// Splits the text into lines of tokens, leaving out the comments and the
+/// blank lines. The format is line-based: a line holds a function header,
+/// a block header, an instruction, a terminator or a closing brace.
+fn tokenize(text: &str) -> Result<Vec<Line>, String> {
+    let mut lines = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let mut tokens = Vec::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ';' => break,
+                c if c.is_whitespace() => {}
+                '"' => match read_string(&mut chars) {
+                    Some(value) => tokens.push(Token::Str(value)),
+                    None => return Err(format!("line {}: invalid string", number)),
+                },
+                '@' | '%' => {
+                    let name = read_word(&mut chars, String::new());
+                    if name.is_empty() {
+                        return Err(format!("line {}: expected a name after '{}'", number, c));
+                    }
+                    tokens.push(if c == '@' { Token::Global(name) } else { Token::Local(name) });
+                }
+                '-' if chars.peek() == Some(&'>') => {
+                    chars.next();
+                    tokens.push(Token::Arrow);
+                }
+                '(' | ')' | ',' | ':' | '=' | '{' | '}' => tokens.push(Token::Punct(c)),
+                c if is_word_char(c) || c == '-' => tokens.push(Token::Word(read_word(&mut chars, c.to_string()))),
+                c => return Err(format!("line {}: unexpected character '{}'", number, c)),
+            }
+        }
+        if !tokens.is_empty() {
+            lines.push(Line { number, tokens, position: 0 });
+        }
+    }
+    Ok(lines)
+}
+
+impl Line {
+    fn error(&self, message: &str) -> String {
+        format!("line {}: {}", self.number, message)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.tokens.get(self.position) {
+            Some(token) => self.error(&format!("expected {}, found {}", expected, token.describe())),
+            None => self.error(&format!("expected {} at the end of the line", expected)),
+        }
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.is_punct(c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<(), String> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", c)))
+        }
+    }
+
+    fn word(&mut self, expected: &str) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.position += 1;
+                Ok(word)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn index(&mut self) -> Result<usize, String> {
+        let word = self.word("an index")?;
+        word.parse().map_err(|_| self.error(&format!("invalid index '{}'", word)))
+    }
+
+    fn global(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Global(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name starting with '@'")),
+        }
+    }
+
+    fn local(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Local(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    /// An integer of any size, a float such as `1.5`, `1e100` or `inf`,
+    /// `true`, `false`, `none` or a string.
+    fn constant(&mut self) -> Result<Constant, String> {
+        if let Some(Token::Str(value)) = self.peek() {
+            let value = value.clone();
+            self.position += 1;
+            return Ok(Constant::Str(value));
+        }
+        let word = self.word("a constant")?;
+        Ok(match word.as_str() {
+            "true" => Constant::Bool(true),
+            "false" => Constant::Bool(false),
+            "none" => Constant::None,
+            _ => match (word.parse(), BigInt::parse(&word), word.parse()) {
+                (Ok(value), _, _) => Constant::Int(value),
+                (_, Some(value), _) => Constant::BigInt(Rc::new(value)),
+                (_, _, Ok(value)) => Constant::Float(value),
+                _ => return Err(self.error(&format!("invalid constant '{}'", word))),
+            },
+        })
+    }
+
+    fn ty(&mut self) -> Result<Type, String> {
+        let word = self.word("a type")?;
+        match word.as_str() {
+            "int" => Ok(Type::Int),
+            "i64" => Ok(Type::I64),
+            "f64" => Ok(Type::F64),
+            "bool" => Ok(Type::Bool),
+            "ptr" => Ok(Type::Ptr),
+            "none" => Ok(Type::None),
+            "object" => Ok(Type::Object),
+            _ => Err(self.error(&format!("unknown type '{}'", word))),
+        }
+    }
+
+    /// Items separated by commas, up to a closing parenthesis.
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Line) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = Vec::new();
+        if self.eat_punct(')') {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat_punct(')') {
+                return Ok(items);
+            }
+            self.expect_punct(',')?;
+        }
+    }
+
+    fn end(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.unexpected("the end of the line")),
+        }
+    }
+}
+
+const TERMINATORS: [&str; 4] = ["br", "cond_br", "ret", "unreachable"];
+
+/// The values and blocks of a function being parsed, by name.
+struct Scope {
+    values: HashMap<String, Value>,
+    blocks: HashMap<String, Block>,
+}
+
+impl Scope {
+    fn value(&self, line: &mut Line) -> Result<Value, String> {
+        let name = line.local()?;
+        self.values.get(&name).copied().ok_or_else(|| line.error(&format!("undefined value %{}", name)))
+    }
+
+    fn values(&self, line: &mut Line) -> Result<Vec<Value>, String> {
+        line.expect_punct('(')?;
+        line.list(|line| self.value(line))
+    }
+
+    /// Values separated by commas, up to the end of the line.
+    fn operands(&self, line: &mut Line) -> Result<Vec<Value>, String> {
+        let mut values = Vec::new();
+        if line.peek().is_some() {
+            values.push(self.value(line)?);
+            while line.eat_punct(',') {
+                values.push(self.value(line)?);
+            }
+        }
+        Ok(values)
+    }
+
+    fn block_call(&self, line: &mut Line) -> Result<BlockCall, String> {
+        let name = line.word("a block")?;
+        let block = *self.blocks.get(&name).ok_or_else(|| line.error(&format!("undefined block {}", name)))?;
+        let arguments = if line.is_punct('(') { self.values(line)? } else { Vec::new() };
+        Ok(BlockCall { block, arguments })
+    }
+}
+
+/// Parses the function starting at `lines[start]`, returning it and the
+/// index of the line after it.
+fn parse_function(lines: &[Line], start: usize) -> Result<(Function, usize), String> {
+    let mut header = lines[start].clone();
+    match header.word("'fn'")?.as_str() {
+        "fn" => {}
+        _ => return Err(header.error("expected 'fn'")),
+    }
+    let name = header.global()?;
+    header.expect_punct('(')?;
+    let params = header.list(Line::ty)?;
+    if header.peek() != Some(&Token::Arrow) {
+        return Err(header.unexpected("'->'"));
+    }
+    header.position += 1;
+    let return_type = header.ty()?;
+    let inline = match header.peek() {
+        Some(Token::Word(word)) if word == "inline" => InlineHint::Always,
+        Some(Token::Word(word)) if word == "noinline" => InlineHint::Never,
+        _ => InlineHint::Default,
+    };
+    if inline != InlineHint::Default {
+        header.position += 1;
+    }
+    header.expect_punct('{')?;
+    header.end()?;
+
+    let mut function = Function::new(&name, &params, return_type);
+    function.inline = inline;
+    let mut scope = Scope { values: HashMap::new(), blocks: HashMap::new() };
+    // The instructions and terminators, to be parsed once every value and
+    // block is known.
+    let mut insts: Vec<(Inst, Line)> = Vec::new();
+    let mut terminators: Vec<(Block, Line)> = Vec::new();
+    let mut current: Option<Block> = None;
+    let mut index = start + 1;
+    loop {
+        let Some(line) = lines.get(index) else {
+            return Err(format!("line {}: function @{} is not closed with '}}'", header.number, name));
+        };
+        let mut line = line.clone();
+        index += 1;
+        if line.tokens == [Token::Punct('}')] {
+            break;
+        }
+        if line.tokens.last() == Some(&Token::Punct(':')) {
+            let label = line.word("a block")?;
+            let block = if scope.blocks.is_empty() { function.entry() } else { function.create_block() };
+            if scope.blocks.insert(label.clone(), block).is_some() {
+                return Err(line.error(&format!("block {} is defined twice", label)));
+            }
+            let block_params = if line.eat_punct('(') {
+                line.list(|line| {
+                    let name = line.local()?;
+                    line.expect_punct(':')?;
+                    Ok((name, line.ty()?))
+                })?
+            } else {
+                Vec::new()
+            };
+            if block == function.entry() {
+                let types: Vec<Type> = block_params.iter().map(|(_, ty)| *ty).collect();
+                if types != params {
+                    return Err(line.error("the parameters of the entry block must match the function's"));
+                }
+            }
+            for (position, (name, ty)) in block_params.into_iter().enumerate() {
+                let value = if block == function.entry() {
+                    function.params()[position]
+                } else {
+                    function.append_block_param(block, ty)
+                };
+                if scope.values.insert(name.clone(), value).is_some() {
+                    return Err(line.error(&format!("value %{} is defined twice", name)));
+                }
+            }
+            line.expect_punct(':')?;
+            line.end()?;
+            current = Some(block);
+            continue;
+        }
+        let Some(block) = current else {
+            return Err(line.error("expected a block before the first instruction"));
+        };
+        if function.terminator(block).is_some() || terminators.last().is_some_and(|(last, _)| *last == block) {
+            return Err(line.error("instruction after the terminator of the block"));
+        }
+        if matches!(line.peek(), Some(Token::Word(word)) if TERMINATORS.contains(&word.as_str())) {
+            terminators.push((block, line));
+            continue;
+        }
+        let result = if let Some(Token::Local(_)) = line.peek() {
+            let name = line.local()?;
+            line.expect_punct(':')?;
+            let ty = line.ty()?;
+            line.expect_punct('=')?;
+            Some((name, ty))
+        } else {
+            None
+        };
+        // A placeholder, until the operands can be resolved.
+        let placeholder = InstKind::Const(Constant::None);
+        let inst = function.make_inst(placeholder, result.as_ref().map(|(_, ty)| *ty), Span::default());
+        if let (Some((name, _)), Some(value)) = (&result, function.result(inst)) {
+            if scope.values.insert(name.clone(), value).is_some() {
+                return Err(line.error(&format!("value %{} is defined twice", name)));
+            }
+        }
+        function.block_mut(block).insts.push(inst);
+        insts.push((inst, line));
+    }
+    if scope.blocks.is_empty() {
+        return Err(format!("line {}: function @{} has no blocks", header.number, name));
+    }
+
+    for (inst, mut line) in insts {
+        let has_result = function.result(inst).is_some();
+        let kind = parse_inst(&scope, &mut line, has_result)?;
+        function.inst_mut(inst).kind = kind;
+    }
+    for (block, mut line) in terminators {
+        let terminator = parse_terminator(&scope, &mut line)?;
+        function.block_mut(block).terminator = Some(terminator);
+    }
+    Ok((function, index))
+}
+
+fn parse_inst(scope: &Scope, line: &mut Line, has_result: bool) -> Result<InstKind, String> {
+    let opcode = line.word("an instruction")?;
+    let binary_op = match opcode.as_str() {
+        "add" => Some(BinaryOp::Add),
+        "sub" => Some(BinaryOp::Sub),
+        "mul" => Some(BinaryOp::Mul),
+        "div" => Some(BinaryOp::Div),
+        "floordiv" => Some(BinaryOp::FloorDiv),
+        "mod" => Some(BinaryOp::Mod),
+        _ => None,
+    };
+    let compare_op = match opcode.as_str() {
+        "eq" => Some(CompareOp::Eq),
+        "ne" => Some(CompareOp::Ne),
+        "lt" => Some(CompareOp::Lt),
+        "le" => Some(CompareOp::Le),
+        "gt" => Some(CompareOp::Gt),
+        "ge" => Some(CompareOp::Ge),
+        _ => None,
+    };
+    let kind = if let Some(op) = binary_op {
+        let left = scope.value(line)?;
+        line.expect_punct(',')?;
+        InstKind::Binary { op, left, right: scope.value(line)? }
+    } else if let Some(op) = compare_op {
+        let left = scope.value(line)?;
+        line.expect_punct(',')?;
+        InstKind::Compare { op, left, right: scope.value(line)? }
+    } else {
+        match opcode.as_str() {
+            "const" => InstKind::Const(line.constant()?),
+            "neg" => InstKind::Unary { op: UnaryOp::Neg, operand: scope.value(line)? },
+            "not" => InstKind::Unary { op: UnaryOp::Not, operand: scope.value(line)? },
+            "cast" => InstKind::Cast(scope.value(line)?),
+            "box" => InstKind::Box(scope.value(line)?),
+            "unbox" => InstKind::Unbox(scope.value(line)?),
+            "guard" => {
+                let value = scope.value(line)?;
+                line.expect_punct(',')?;
+                let name = line.word("a class")?;
+                let class = Class::ALL
+                    .into_iter()
+                    .find(|class| class.name() == name)
+                    .ok_or_else(|| line.error(&format!("unknown class '{}'", name)))?;
+                InstKind::Guard { value, class }
+            }
+            "truth" => InstKind::Truth(scope.value(line)?),
+            "tuple" => InstKind::Tuple(scope.operands(line)?),
+            "unpack" => {
+                let tuple = scope.value(line)?;
+                line.expect_punct(',')?;
+                let index = line.index()?;
+                line.expect_punct(',')?;
+                InstKind::Unpack { tuple, index, length: line.index()? }
+            }
+            "list" => InstKind::List(scope.operands(line)?),
+            "len" => InstKind::Len(scope.value(line)?),
+            "check_index" => {
+                let index = scope.value(line)?;
+                line.expect_punct(',')?;
+                InstKind::CheckIndex { index, length: scope.value(line)? }
+            }
+            "get_item" => {
+                let list = scope.value(line)?;
+                line.expect_punct(',')?;
+                InstKind::GetItem { list, index: scope.value(line)? }
+            }
+            "set_item" => {
+                let list = scope.value(line)?;
+                line.expect_punct(',')?;
+                let index = scope.value(line)?;
+                line.expect_punct(',')?;
+                InstKind::SetItem { list, index, value: scope.value(line)? }
+            }
+            "call" => {
+                let function = line.global()?;
+                InstKind::Call { function, arguments: scope.values(line)? }
+            }
+            "load_global" => InstKind::LoadGlobal(line.global()?),
+            "store_global" => {
+                let name = line.global()?;
+                line.expect_punct(',')?;
+                InstKind::StoreGlobal { name, value: scope.value(line)? }
+            }
+            "print" => InstKind::Print(scope.operands(line)?),
+            _ => return Err(line.error(&format!("unknown instruction '{}'", opcode))),
+        }
+    };
+    line.end()?;
+    let produces_value = !matches!(kind, InstKind::StoreGlobal { .. } | InstKind::SetItem { .. } | InstKind::Print(_));
+    match (produces_value, has_result) {
+        (true, false) if !matches!(kind, InstKind::Call { .. }) => Err(line.error(&format!("'{}' needs a result", opcode))),
+        (false, true) => Err(line.error(&format!("'{}' has no result", opcode))),
+        _ => Ok(kind),
+    }
+}
+
+fn parse_terminator(scope: &Scope, line: &mut Line) -> Result<Terminator, String> {
+    let terminator = match line.word("a terminator")?.as_str() {
+        "br" => Terminator::Br(scope.block_call(line)?),
+        "cond_br" => {
+            let condition = scope.value(line)?;
+            line.expect_punct(',')?;
+            let then = scope.block_call(line)?;
+            line.expect_punct(',')?;
+            Terminator::CondBr { condition, then, otherwise: scope.block_call(line)? }
+        }
+        "ret" => Terminator::Return(if line.peek().is_some() { Some(scope.value(line)?) } else { None }),
+        _ => Terminator::Unreachable,
+    };
+    line.end()?;
+    Ok(terminator)
+}
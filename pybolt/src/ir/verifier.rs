@@ -0,0 +1,409 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::dominators::DominatorTree;
+use crate::ir::ir::{
+    BinaryOp, Block, BlockCall, Class, Constant, Function, Inst, InstKind, Module, Terminator, Type, UnaryOp, Value, ValueDef,
+};
+
+/// Checks that every function of the module is well formed; see
+/// `verify_function`. Calls are also checked against the signature of the
+/// function they call.
+pub fn verify(module: &Module) -> Result<(), String> {
+    for function in &module.functions {
+        verify_function(function, Some(module))?;
+    }
+    Ok(())
+}
+
+/// Checks that:
+///
+/// - every block of the layout ends in a terminator, and branches only to
+///   blocks of the layout other than the entry block;
+/// - every branch passes one argument of the right type for each parameter
+///   of its target;
+/// - every instruction is in one block at most, and every value used is
+///   defined in the layout and dominates its use;
+/// - operands and results have the types their instruction expects.
+///
+/// The error lists the problems found, followed by the function as text.
+/// Without a module, calls are not checked.
+pub fn verify_function(function: &Function, module: Option<&Module>) -> Result<(), String> {
+    let mut verifier = Verifier {
+        function,
+        module,
+        dominators: DominatorTree::compute(function),
+        positions: HashMap::new(),
+        params: HashSet::new(),
+        errors: Vec::new(),
+    };
+    verifier.run();
+    if verifier.errors.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "{}\n\n{}",
+        verifier.errors.iter().map(|error| format!("error in @{}: {}", function.name, error)).collect::<Vec<_>>().join("\n"),
+        function
+    ))
+}
+
+struct Verifier<'a> {
+    function: &'a Function,
+    module: Option<&'a Module>,
+    dominators: DominatorTree,
+    /// The block of each instruction in the layout and its index there.
+    positions: HashMap<Inst, (Block, usize)>,
+    /// The parameters of the blocks in the layout.
+    params: HashSet<Value>,
+    errors: Vec<String>,
+}
+
+impl Verifier<'_> {
+    fn error(&mut self, message: String) {
+        self.errors.push(message);
+    }
+
+    fn run(&mut self) {
+        let function = self.function;
+        if function.layout.is_empty() {
+            self.error("the function has no blocks".to_string());
+            return;
+        }
+        let mut seen = HashSet::new();
+        for block in &function.layout {
+            if block.0 as usize >= function.blocks.len() || !seen.insert(*block) {
+                self.error(format!("block {} is laid out twice or does not exist", block.0));
+                return;
+            }
+            let data = function.block(*block);
+            for param in &data.params {
+                let defined = (param.0 as usize) < function.values.len() && function.value(*param).def == ValueDef::Param(*block);
+                if !self.params.insert(*param) || !defined {
+                    self.error(format!("parameter {} of block {} is not defined by it", param.0, block.0));
+                }
+            }
+            for (index, inst) in data.insts.iter().enumerate() {
+                if inst.0 as usize >= function.insts.len() {
+                    self.error(format!("instruction {} in block {} does not exist", inst.0, block.0));
+                    return;
+                }
+                if self.positions.insert(*inst, (*block, index)).is_some() {
+                    self.error(format!("instruction {} is in more than one place", inst.0));
+                }
+                if let Some(result) = function.result(*inst) {
+                    if (result.0 as usize) >= function.values.len() || function.value(result).def != ValueDef::Result(*inst) {
+                        self.error(format!("the result of instruction {} is not defined by it", inst.0));
+                    }
+                }
+            }
+        }
+        let return_type = function.return_type;
+        for block in &function.layout {
+            let data = function.block(*block);
+            for (index, inst) in data.insts.iter().enumerate() {
+                self.check_inst(*block, index, *inst);
+            }
+            let end = data.insts.len();
+            match &data.terminator {
+                None => self.error(format!("{} has no terminator", self.block_name(*block))),
+                Some(terminator) => {
+                    for operand in terminator.operands() {
+                        self.check_use(operand, *block, end);
+                    }
+                    match terminator {
+                        Terminator::Br(target) => self.check_block_call(*block, target),
+                        Terminator::CondBr { condition, then, otherwise } => {
+                            self.expect_type(*condition, Type::Bool, "the condition of cond_br");
+                            self.check_block_call(*block, then);
+                            self.check_block_call(*block, otherwise);
+                        }
+                        Terminator::Return(Some(value)) => self.expect_type(*value, return_type, "the returned value"),
+                        Terminator::Return(None) if return_type != Type::None => {
+                            self.error(format!("{} returns nothing from a function returning {}", self.block_name(*block), return_type))
+                        }
+                        Terminator::Return(None) | Terminator::Unreachable => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// The name the block prints with.
+    fn block_name(&self, block: Block) -> String {
+        match self.function.layout.iter().position(|other| *other == block) {
+            Some(index) => format!("bb{}", index),
+            None => format!("bb!{}", block.0),
+        }
+    }
+
+    /// Whether `value` exists and is defined in the layout.
+    fn is_defined(&self, value: Value) -> bool {
+        if value.0 as usize >= self.function.values.len() {
+            return false;
+        }
+        match self.function.value(value).def {
+            ValueDef::Param(_) => self.params.contains(&value),
+            ValueDef::Result(inst) => self.positions.contains_key(&inst),
+        }
+    }
+
+    /// Checks a use of `value` before position `index` of `block`, where the
+    /// terminator comes after the last instruction.
+    fn check_use(&mut self, value: Value, block: Block, index: usize) {
+        if !self.is_defined(value) {
+            self.error(format!("{} uses value {}, which was deleted or never defined", self.block_name(block), value.0));
+            return;
+        }
+        if !self.dominators.is_reachable(block) {
+            return;
+        }
+        let dominates = match self.function.value(value).def {
+            ValueDef::Param(def) => self.dominators.dominates(def, block),
+            ValueDef::Result(inst) => {
+                let (def, position) = self.positions[&inst];
+                if def == block {
+                    position < index
+                } else {
+                    self.dominators.dominates(def, block)
+                }
+            }
+        };
+        if !dominates {
+            self.error(format!("a use of {} in {} is not dominated by its definition", self.value_name(value), self.block_name(block)));
+        }
+    }
+
+    fn value_name(&self, value: Value) -> String {
+        // Numbered as in the printed function.
+        let mut index = 0;
+        for block in &self.function.layout {
+            let data = self.function.block(*block);
+            for param in &data.params {
+                if *param == value {
+                    return format!("%{}", index);
+                }
+                index += 1;
+            }
+            for inst in &data.insts {
+                if let Some(result) = self.function.result(*inst) {
+                    if result == value {
+                        return format!("%{}", index);
+                    }
+                    index += 1;
+                }
+            }
+        }
+        format!("%!{}", value.0)
+    }
+
+    fn expect_type(&mut self, value: Value, expected: Type, what: &str) {
+        if !self.is_defined(value) {
+            return;
+        }
+        let ty = self.function.value_type(value);
+        if ty != expected {
+            self.error(format!("{} ({}) is {}, expected {}", what, self.value_name(value), ty, expected));
+        }
+    }
+
+    /// Checks that `value` has one of the `allowed` types, and returns its
+    /// type if it does.
+    fn expect_one_of(&mut self, value: Value, allowed: &[Type], what: &str) -> Option<Type> {
+        if !self.is_defined(value) {
+            return None;
+        }
+        let ty = self.function.value_type(value);
+        if allowed.contains(&ty) {
+            return Some(ty);
+        }
+        let mut expected: Vec<String> = allowed.iter().map(ToString::to_string).collect();
+        let last = expected.pop().unwrap_or_default();
+        let expected = if expected.is_empty() { last } else { format!("{} or {}", expected.join(", "), last) };
+        self.error(format!("{} ({}) is {}, expected {}", what, self.value_name(value), ty, expected));
+        None
+    }
+
+    fn check_block_call(&mut self, from: Block, target: &BlockCall) {
+        let function = self.function;
+        if target.block == function.entry() {
+            self.error(format!("{} branches to the entry block", self.block_name(from)));
+            return;
+        }
+        if !function.layout.contains(&target.block) {
+            self.error(format!("{} branches to a block that is not in the layout", self.block_name(from)));
+            return;
+        }
+        let params = &function.block(target.block).params;
+        if params.len() != target.arguments.len() {
+            self.error(format!(
+                "{} passes {} arguments to {}, which has {} parameters",
+                self.block_name(from),
+                target.arguments.len(),
+                self.block_name(target.block),
+                params.len()
+            ));
+            return;
+        }
+        for (param, argument) in params.iter().zip(&target.arguments) {
+            let ty = function.value_type(*param);
+            if self.is_defined(*argument) && function.value_type(*argument) != ty {
+                let what = format!("the argument for {} from {}", self.value_name(*param), self.block_name(from));
+                self.expect_type(*argument, ty, &what);
+            }
+        }
+    }
+
+    fn check_inst(&mut self, block: Block, index: usize, inst: Inst) {
+        let function = self.function;
+        let data = function.inst(inst);
+        for operand in data.kind.operands() {
+            self.check_use(operand, block, index);
+        }
+        let result = data.result.map(|result| function.value_type(result));
+        // The type of the result, if there should be one. When an operand
+        // has the wrong type, the result is not checked.
+        let expected = match &data.kind {
+            InstKind::Const(_) if result == Some(Type::Object) => Some(Type::Object),
+            InstKind::Const(Constant::Int(_)) if result == Some(Type::I64) => Some(Type::I64),
+            InstKind::Const(constant) => Some(constant.ty()),
+            InstKind::Unary { op: UnaryOp::Neg, operand } => {
+                self.expect_one_of(*operand, &[Type::Int, Type::I64, Type::F64, Type::Object], "the operand of neg").or(result)
+            }
+            InstKind::Unary { op: UnaryOp::Not, operand } => {
+                self.expect_type(*operand, Type::Bool, "the operand of not");
+                Some(Type::Bool)
+            }
+            InstKind::Binary { op, left, right } => {
+                let allowed: &[Type] = match op {
+                    BinaryOp::Div => &[Type::Int, Type::F64, Type::Object],
+                    _ => &[Type::Int, Type::I64, Type::F64, Type::Object],
+                };
+                match self.expect_one_of(*left, allowed, &format!("the left operand of {}", op.name())) {
+                    Some(ty) => {
+                        self.expect_type(*right, ty, &format!("the right operand of {}", op.name()));
+                        Some(if ty == Type::Int && *op == BinaryOp::Div { Type::F64 } else { ty })
+                    }
+                    None => result,
+                }
+            }
+            InstKind::Compare { op, left, right } => {
+                let allowed = [Type::Int, Type::I64, Type::F64, Type::Bool, Type::Object];
+                if let Some(ty) = self.expect_one_of(*left, &allowed, &format!("the left operand of {}", op.name())) {
+                    self.expect_type(*right, ty, &format!("the right operand of {}", op.name()));
+                }
+                Some(Type::Bool)
+            }
+            InstKind::Cast(value) => match (self.expect_one_of(*value, &[Type::Bool, Type::I64, Type::Int], "the operand of cast"), result) {
+                (Some(Type::Bool), Some(ty @ (Type::Int | Type::I64 | Type::F64))) => Some(ty),
+                (Some(Type::Bool), _) => Some(Type::Int),
+                (Some(Type::I64), Some(ty @ (Type::Int | Type::F64))) => Some(ty),
+                (Some(Type::I64), _) => Some(Type::Int),
+                (Some(_), _) => Some(Type::F64),
+                (None, _) => result,
+            },
+            InstKind::Box(value) => {
+                let unboxed = [Type::Int, Type::I64, Type::F64, Type::Bool, Type::Ptr, Type::None];
+                self.expect_one_of(*value, &unboxed, "the operand of box");
+                Some(Type::Object)
+            }
+            InstKind::Unbox(value) => {
+                self.expect_type(*value, Type::Object, "the operand of unbox");
+                match result {
+                    Some(Type::Object) => {
+                        let name = self.value_name(data.result.expect("the instruction has a result"));
+                        self.error(format!("{} is unboxed into an object", name));
+                    }
+                    Some(ty) if Class::of_unboxed(ty).is_none() => {
+                        let name = self.value_name(data.result.expect("the instruction has a result"));
+                        self.error(format!("{} is unboxed into {}, which no class unboxes to", name, ty));
+                    }
+                    _ => {}
+                }
+                result.or(Some(Type::Int))
+            }
+            InstKind::Guard { value, .. } | InstKind::Truth(value) => {
+                let what = if matches!(data.kind, InstKind::Guard { .. }) { "the operand of guard" } else { "the operand of truth" };
+                self.expect_type(*value, Type::Object, what);
+                Some(Type::Bool)
+            }
+            InstKind::Tuple(values) => {
+                for value in values {
+                    self.expect_type(*value, Type::Object, "an item of tuple");
+                }
+                Some(Type::Object)
+            }
+            InstKind::Unpack { tuple, index, length } => {
+                self.expect_type(*tuple, Type::Object, "the operand of unpack");
+                if index >= length {
+                    let name = self.value_name(data.result.expect("the instruction has a result"));
+                    self.error(format!("{} unpacks item {} of {}", name, index, length));
+                }
+                Some(Type::Object)
+            }
+            InstKind::List(values) => {
+                for value in values {
+                    self.expect_type(*value, Type::Object, "an item of list");
+                }
+                Some(Type::Object)
+            }
+            InstKind::Len(value) => {
+                self.expect_type(*value, Type::Object, "the operand of len");
+                Some(Type::Int)
+            }
+            InstKind::CheckIndex { index, length } => {
+                self.expect_type(*index, Type::Int, "the index of check_index");
+                self.expect_type(*length, Type::Int, "the length of check_index");
+                Some(Type::Int)
+            }
+            InstKind::GetItem { list, index } => {
+                self.expect_type(*list, Type::Object, "the list of get_item");
+                self.expect_type(*index, Type::Int, "the index of get_item");
+                Some(Type::Object)
+            }
+            InstKind::SetItem { list, index, value } => {
+                self.expect_type(*list, Type::Object, "the list of set_item");
+                self.expect_type(*index, Type::Int, "the index of set_item");
+                self.expect_type(*value, Type::Object, "the value of set_item");
+                None
+            }
+            InstKind::Call { function: callee, arguments } => self.check_call(callee, arguments, result),
+            InstKind::LoadGlobal(_) => result.or(Some(Type::None)),
+            InstKind::StoreGlobal { .. } | InstKind::Print(_) => None,
+        };
+        match (expected, result) {
+            (Some(expected), Some(ty)) if expected != ty => {
+                let name = self.value_name(data.result.expect("the instruction has a result"));
+                self.error(format!("{} is {}, expected {}", name, ty, expected))
+            }
+            (Some(_), None) if !matches!(data.kind, InstKind::Call { .. }) => {
+                self.error(format!("an instruction in {} has no result", self.block_name(block)))
+            }
+            (None, Some(_)) => {
+                let name = self.value_name(data.result.expect("the instruction has a result"));
+                self.error(format!("{} is defined by an instruction without a result", name))
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks the arguments of a call against the callee's signature and
+    /// returns the type of its result.
+    fn check_call(&mut self, callee: &str, arguments: &[Value], result: Option<Type>) -> Option<Type> {
+        let Some(module) = self.module else {
+            return result;
+        };
+        let Some(target) = module.function(callee) else {
+            self.error(format!("call of the undefined function @{}", callee));
+            return result;
+        };
+        let params = target.param_types();
+        if params.len() != arguments.len() {
+            self.error(format!("call of @{} with {} arguments instead of {}", callee, arguments.len(), params.len()));
+        } else {
+            for (position, (argument, ty)) in arguments.iter().zip(params).enumerate() {
+                self.expect_type(*argument, ty, &format!("argument {} of the call of @{}", position + 1, callee));
+            }
+        }
+        result.map(|_| target.return_type)
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TokenType {
     Keyword,
@@ -5,6 +7,10 @@ pub enum TokenType {
     Literal,
     Operator,
     Punctuation,
+    Newline,
+    Indent,
+    Dedent,
+    Unknown,
     Eof
 }
 
@@ -14,47 +20,303 @@ pub struct Token {
     pub lexeme: String,
 }
 
+/// Byte range of a token or node in the source text.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TriviaKind {
+    Whitespace,
+    Newline,
+    Comment,
+}
+
+/// Source text that carries no meaning for the parser but is needed to
+/// reproduce the input exactly.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+/// A token together with its position and the trivia that precedes it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    pub leading_trivia: Vec<Trivia>,
+}
+
+impl SpannedToken {
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        self.write_source(&mut source);
+        source
+    }
+
+    pub fn write_source(&self, out: &mut String) {
+        for trivia in &self.leading_trivia {
+            out.push_str(&trivia.text);
+        }
+        out.push_str(&self.token.lexeme);
+    }
+}
+
+const KEYWORDS: &[&str] = &[
+    "let", "if", "elif", "else", "while", "for", "in", "def", "return", "class", "pass", "break",
+    "continue", "and", "or", "not", "is", "None", "True", "False", "global", "nonlocal", "import",
+    "from", "as", "try", "except", "finally", "raise", "with", "lambda", "yield", "assert", "del",
+];
+
+const OPERATORS: &[&str] = &[
+    "**=", "//=", ">>=", "<<=", "**", "//", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "%=",
+    "&=", "|=", "^=", "@=", ":=", "&&", "||", "<<", ">>", "+", "-", "*", "/", "%", "=", "<", ">",
+    "&", "|", "^", "~", "@", "!",
+];
+
+const PUNCTUATION: &[&str] = &["->", "...", "(", ")", "{", "}", "[", "]", ",", ";", ".", ":"];
+
+const STRING_PREFIXES: &[&str] = &["r", "u", "b", "f", "br", "rb", "fr", "rf"];
+
+/// Returns the 1-based line and column of a byte offset in `source`.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rfind('\n').map_or(before.chars().count(), |newline| before[newline + 1..].chars().count()) + 1;
+    (line, column)
+}
+
 pub struct Lexer<'a> {
     input: &'a str,
     position: usize,
+    finished: bool,
+    pending: VecDeque<SpannedToken>,
+    indent_stack: Vec<usize>,
+    bracket_depth: usize,
+    line_has_token: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
-        Lexer { input, position: 0 }
+        Lexer {
+            input,
+            position: 0,
+            finished: false,
+            pending: VecDeque::new(),
+            indent_stack: Vec::new(),
+            bracket_depth: 0,
+            line_has_token: false,
+        }
+    }
+
+    /// A lexer of `input` from `start` to `end` only, as inside brackets: the
+    /// expression of a replacement field of an f-string, whose spans are
+    /// those of `input`.
+    pub fn within(input: &'a str, start: usize, end: usize) -> Self {
+        Lexer { input: &input[..end], position: start, bracket_depth: 1, ..Lexer::new(input) }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.input
     }
 
     pub fn next_token(&mut self) -> Option<Token> {
-        self.skip_whitespace();
-        if self.position >= self.input.len() {
+        self.next_spanned_token()
+            .map(|spanned| spanned.token)
+            .filter(|token| token.token_type != TokenType::Eof)
+    }
+
+    /// Returns the next token along with its span and leading trivia.
+    ///
+    /// Like Python's tokenizer, a newline that ends a logical line is a
+    /// `Newline` token and changes in indentation produce `Indent` and
+    /// `Dedent` tokens; blank lines, comments and newlines inside brackets
+    /// are trivia. The indentation of the first line is the base level.
+    ///
+    /// Once the input is exhausted a single `Eof` token is returned that
+    /// carries any trailing trivia, so concatenating every token's source
+    /// reproduces the input exactly.
+    pub fn next_spanned_token(&mut self) -> Option<SpannedToken> {
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+        if self.finished {
             return None;
         }
-        let current_char = self.input.as_bytes()[self.position] as char;
-        if current_char.is_alphabetic() {
-            return Some(self.lex_identifier_or_keyword());
+        let leading_trivia = self.lex_trivia();
+        let start = self.position;
+        let mut tokens = Vec::new();
+        match self.peek_char() {
+            None => {
+                self.finished = true;
+                if self.line_has_token {
+                    self.line_has_token = false;
+                    tokens.push(self.layout_token(TokenType::Newline, start));
+                }
+                while self.indent_stack.len() > 1 {
+                    self.indent_stack.pop();
+                    tokens.push(self.layout_token(TokenType::Dedent, start));
+                }
+                tokens.push(self.layout_token(TokenType::Eof, start));
+            }
+            Some('\n') | Some('\r') => {
+                self.skip_newline();
+                self.line_has_token = false;
+                tokens.push(SpannedToken {
+                    token: Token { token_type: TokenType::Newline, lexeme: self.input[start..self.position].to_string() },
+                    span: Span::new(start, self.position),
+                    leading_trivia: Vec::new(),
+                });
+            }
+            Some(c) => {
+                if !self.line_has_token && self.bracket_depth == 0 {
+                    self.lex_indentation(start, &mut tokens);
+                }
+                self.line_has_token = true;
+                let token = match c {
+                    c if c.is_alphabetic() || c == '_' => self.lex_identifier_or_keyword(),
+                    c if c.is_ascii_digit() => self.lex_number(),
+                    '.' if self.peek_nth_char(1).is_some_and(|c| c.is_ascii_digit()) => self.lex_number(),
+                    '"' | '\'' => self.lex_string(),
+                    _ => self.lex_operator_or_punctuation(),
+                };
+                match token.lexeme.as_str() {
+                    "(" | "[" | "{" => self.bracket_depth += 1,
+                    ")" | "]" | "}" => self.bracket_depth = self.bracket_depth.saturating_sub(1),
+                    _ => {}
+                }
+                tokens.push(SpannedToken { token, span: Span::new(start, self.position), leading_trivia: Vec::new() });
+            }
         }
-        if current_char.is_digit(10) {
-            return Some(self.lex_number());
+        let mut tokens = tokens.into_iter();
+        let mut first = tokens.next().expect("at least one token is produced");
+        first.leading_trivia = leading_trivia;
+        self.pending.extend(tokens);
+        Some(first)
+    }
+
+    /// Returns a token to the front of the stream, used by the parser to look
+    /// one token ahead.
+    pub(crate) fn push_back(&mut self, token: SpannedToken) {
+        self.pending.push_front(token);
+    }
+
+    fn layout_token(&self, token_type: TokenType, position: usize) -> SpannedToken {
+        SpannedToken {
+            token: Token { token_type, lexeme: String::new() },
+            span: Span::new(position, position),
+            leading_trivia: Vec::new(),
         }
-        if "+-*/=;".contains(current_char) {
-            return Some(self.lex_operator_or_punctuation());
+    }
+
+    fn lex_indentation(&mut self, start: usize, tokens: &mut Vec<SpannedToken>) {
+        let line_start = self.input[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let width = self.input[line_start..start]
+            .chars()
+            .fold(0, |width, c| if c == '\t' { (width / 8 + 1) * 8 } else { width + 1 });
+        let current = match self.indent_stack.last() {
+            Some(current) => *current,
+            None => {
+                self.indent_stack.push(width);
+                return;
+            }
+        };
+        if width > current {
+            self.indent_stack.push(width);
+            tokens.push(self.layout_token(TokenType::Indent, start));
+        } else {
+            while self.indent_stack.len() > 1 && width < *self.indent_stack.last().unwrap_or(&0) {
+                self.indent_stack.pop();
+                tokens.push(self.layout_token(TokenType::Dedent, start));
+            }
+            if self.indent_stack.last().is_some_and(|level| width != *level) {
+                // A dedent to a level that was never opened; the parser
+                // rejects the empty unknown token.
+                tokens.push(self.layout_token(TokenType::Unknown, start));
+            }
         }
-        None
     }
 
-    fn skip_whitespace(&mut self) {
-        while self.position < self.input.len() && self.input.as_bytes()[self.position].is_ascii_whitespace() {
+    fn skip_newline(&mut self) {
+        if self.peek_char() == Some('\r') {
+            self.position += 1;
+        }
+        if self.peek_char() == Some('\n') {
             self.position += 1;
         }
     }
 
+    fn peek_char(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.input[self.position..].chars().nth(n)
+    }
+
+    fn advance_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek_char() {
+            if !predicate(c) {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn lex_trivia(&mut self) -> Vec<Trivia> {
+        let mut trivia = Vec::new();
+        while let Some(c) = self.peek_char() {
+            let start = self.position;
+            let kind = match c {
+                '\n' | '\r' if self.line_has_token && self.bracket_depth == 0 => break,
+                '\n' | '\r' => {
+                    self.skip_newline();
+                    TriviaKind::Newline
+                }
+                '\\' if matches!(self.peek_nth_char(1), Some('\n') | Some('\r')) => {
+                    self.position += 1;
+                    self.skip_newline();
+                    TriviaKind::Newline
+                }
+                '#' => {
+                    self.advance_while(|c| c != '\n' && c != '\r');
+                    TriviaKind::Comment
+                }
+                c if c.is_whitespace() => {
+                    self.advance_while(|c| c.is_whitespace() && c != '\n' && c != '\r');
+                    TriviaKind::Whitespace
+                }
+                _ => break,
+            };
+            trivia.push(Trivia { kind, text: self.input[start..self.position].to_string() });
+        }
+        trivia
+    }
+
     fn lex_identifier_or_keyword(&mut self) -> Token {
         let start = self.position;
-        while self.position < self.input.len() && self.input.as_bytes()[self.position].is_ascii_alphabetic() {
-            self.position += 1;
-        }
+        self.advance_while(|c| c.is_alphanumeric() || c == '_');
         let lexeme = &self.input[start..self.position];
-        let token_type = if lexeme == "let" {
+        if matches!(self.peek_char(), Some('"') | Some('\''))
+            && STRING_PREFIXES.contains(&lexeme.to_ascii_lowercase().as_str())
+        {
+            self.position = start;
+            return self.lex_string();
+        }
+        let token_type = if KEYWORDS.contains(&lexeme) {
             TokenType::Keyword
         } else {
             TokenType::Identifier
@@ -67,7 +329,33 @@ impl<'a> Lexer<'a> {
 
     fn lex_number(&mut self) -> Token {
         let start = self.position;
-        while self.position < self.input.len() && self.input.as_bytes()[self.position].is_ascii_digit() {
+        let radix = match (self.peek_char(), self.peek_nth_char(1)) {
+            (Some('0'), Some('x' | 'X')) => Some(16),
+            (Some('0'), Some('o' | 'O')) => Some(8),
+            (Some('0'), Some('b' | 'B')) => Some(2),
+            _ => None,
+        };
+        if let Some(radix) = radix {
+            self.position += 2;
+            self.advance_while(|c| c.is_digit(radix) || c == '_');
+            return Token {
+                token_type: TokenType::Literal,
+                lexeme: self.input[start..self.position].to_string(),
+            };
+        }
+        self.advance_while(|c| c.is_ascii_digit() || c == '_');
+        if self.peek_char() == Some('.') && self.peek_nth_char(1).is_none_or(|c| !c.is_alphabetic() && c != '_') {
+            self.position += 1;
+            self.advance_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        if matches!(self.peek_char(), Some('e') | Some('E')) {
+            let sign_len = usize::from(matches!(self.peek_nth_char(1), Some('+') | Some('-')));
+            if self.peek_nth_char(1 + sign_len).is_some_and(|c| c.is_ascii_digit()) {
+                self.position += 1 + sign_len;
+                self.advance_while(|c| c.is_ascii_digit() || c == '_');
+            }
+        }
+        if matches!(self.peek_char(), Some('j') | Some('J')) {
             self.position += 1;
         }
         let lexeme = &self.input[start..self.position];
@@ -77,17 +365,62 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    fn lex_string(&mut self) -> Token {
+        let start = self.position;
+        self.advance_while(|c| c.is_alphabetic());
+        let quote = self.peek_char().unwrap_or('"');
+        let rest = &self.input[self.position..];
+        let triple: String = std::iter::repeat_n(quote, 3).collect();
+        let is_triple = rest.starts_with(&triple);
+        self.position += if is_triple { 3 } else { 1 };
+        let mut terminated = false;
+        while let Some(c) = self.peek_char() {
+            if c == '\\' {
+                self.position += 1;
+                if let Some(escaped) = self.peek_char() {
+                    self.position += escaped.len_utf8();
+                }
+                continue;
+            }
+            if is_triple {
+                if self.input[self.position..].starts_with(&triple) {
+                    self.position += 3;
+                    terminated = true;
+                    break;
+                }
+            } else if c == quote {
+                self.position += 1;
+                terminated = true;
+                break;
+            } else if c == '\n' {
+                break;
+            }
+            self.position += c.len_utf8();
+        }
+        Token {
+            token_type: if terminated { TokenType::Literal } else { TokenType::Unknown },
+            lexeme: self.input[start..self.position].to_string(),
+        }
+    }
+
     fn lex_operator_or_punctuation(&mut self) -> Token {
-        let current_char = self.input.as_bytes()[self.position] as char;
-        self.position += 1;
-        let token_type = if current_char == ';' {
-            TokenType::Punctuation
-        } else {
-            TokenType::Operator
+        let rest = &self.input[self.position..];
+        let longest_match = |candidates: &[&'static str]| {
+            candidates.iter().copied().filter(|c| rest.starts_with(c)).max_by_key(|c| c.len())
         };
+        let (token_type, lexeme) = match (longest_match(PUNCTUATION), longest_match(OPERATORS)) {
+            (Some(p), Some(op)) if op.len() > p.len() => (TokenType::Operator, op),
+            (Some(p), _) => (TokenType::Punctuation, p),
+            (None, Some(op)) => (TokenType::Operator, op),
+            (None, None) => {
+                let c = self.peek_char().unwrap_or_default();
+                (TokenType::Unknown, &rest[..c.len_utf8()])
+            }
+        };
+        self.position += lexeme.len();
         Token {
             token_type,
-            lexeme: current_char.to_string(),
+            lexeme: lexeme.to_string(),
         }
     }
 }
@@ -2,7 +2,7 @@ pub mod lexer;
 
 #[cfg(test)]
 mod tests {
-    use super::lexer::{Lexer, Token, TokenType};
+    use super::lexer::{Lexer, Span, Token, TokenType, TriviaKind};
 
     fn assert_token(lexer: &mut Lexer, expected_type: TokenType, expected_lexeme: &str) {
         assert_eq!(
@@ -98,4 +98,43 @@ mod tests {
             assert_token(&mut lexer, expected_type, expected_lexeme);
         }
     }
+
+    #[test]
+    fn test_spanned_token_trivia() {
+        let input = "let x = (1  # one\n+ 2)";
+        let mut lexer = Lexer::new(input);
+        let mut source = String::new();
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_spanned_token() {
+            token.write_source(&mut source);
+            tokens.push(token);
+        }
+        assert_eq!(source, input);
+        assert_eq!(tokens[1].span, Span::new(4, 5));
+        let plus = tokens.iter().find(|t| t.token.lexeme == "+").unwrap();
+        let kinds: Vec<_> = plus.leading_trivia.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![TriviaKind::Whitespace, TriviaKind::Comment, TriviaKind::Newline]);
+        assert_eq!(tokens.last().unwrap().token.token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_indentation_tokens() {
+        let mut lexer = Lexer::new("if x:\n    y\n\n    # note\nz\n");
+        let expected = vec![
+            (TokenType::Keyword, "if"),
+            (TokenType::Identifier, "x"),
+            (TokenType::Punctuation, ":"),
+            (TokenType::Newline, "\n"),
+            (TokenType::Indent, ""),
+            (TokenType::Identifier, "y"),
+            (TokenType::Newline, "\n"),
+            (TokenType::Dedent, ""),
+            (TokenType::Identifier, "z"),
+            (TokenType::Newline, "\n"),
+        ];
+        for (expected_type, expected_lexeme) in expected {
+            assert_token(&mut lexer, expected_type, expected_lexeme);
+        }
+        assert_eq!(lexer.next_token(), None);
+    }
 }
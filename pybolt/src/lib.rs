@@ -1,7 +1,11 @@
+#![allow(clippy::module_inception)]
+
 pub mod lexer;
 pub mod parser;
 pub mod semantic;
+pub mod hir;
 pub mod ir;
+pub mod analysis;
 pub mod optimizer;
 pub mod codegen;
 pub mod runtime;
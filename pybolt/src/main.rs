@@ -1,21 +1,309 @@
-mod lexer;
-mod parser;
-mod semantic;
-mod ir;
-mod optimizer;
-mod codegen;
-mod runtime;
-
-use lexer::lexer::Lexer;
-use parser::parser::Parser;
-use semantic::semantic::SemanticAnalyzer;
-use ir::ir::IRGenerator;
-use optimizer::optimizer::Optimizer;
-use codegen::codegen::CodeGenerator;
-use runtime::runtime::{Runtime, Instruction};
+use std::fs;
+use std::path::PathBuf;
+
+use clap::{Parser as ClapParser, ValueEnum};
+
+use pybolt::lexer::lexer::{line_column, Lexer};
+use pybolt::parser::parser::Parser;
+use pybolt::parser::serialize;
+use pybolt::semantic::diagnostic::Severity;
+use pybolt::semantic::lint::{LintConfig, LintLevel};
+use pybolt::semantic::semantic::SemanticAnalyzer;
+use pybolt::ir::generator::{IRGenerator, MAIN};
+use pybolt::optimizer::inliner::Inliner;
+use pybolt::optimizer::instrumentation::{DumpPasses, Instrumentation};
+use pybolt::optimizer::optimizer::{OptLevel, Optimizer};
+use pybolt::optimizer::remarks::{self, RemarkFilter, RemarkFormat};
+use pybolt::codegen::codegen::CodeGenerator;
+use pybolt::runtime::runtime::{Runtime, Instruction};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// The AST as JSON
+    Ast,
+    /// The AST in the format of CPython's `ast.dump`
+    AstDump,
+    /// The typed, desugared HIR that IR generation consumes
+    Hir,
+    /// The SSA IR, before optimization
+    Ir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Level {
+    /// No optimization
+    #[value(name = "0")]
+    O0,
+    /// Run each pass once
+    #[value(name = "1")]
+    O1,
+    /// Run the passes until they no longer change the IR
+    #[value(name = "2")]
+    O2,
+    /// Optimize for speed, even at the cost of size
+    #[value(name = "3")]
+    O3,
+    /// Optimize for size
+    #[value(name = "s")]
+    Os,
+}
+
+impl From<Level> for OptLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::O0 => OptLevel::O0,
+            Level::O1 => OptLevel::O1,
+            Level::O2 => OptLevel::O2,
+            Level::O3 => OptLevel::O3,
+            Level::Os => OptLevel::Os,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RemarksFormat {
+    /// One line per remark
+    Human,
+    /// LLVM-style optimization records
+    Yaml,
+    /// A JSON array
+    Json,
+}
+
+impl From<RemarksFormat> for RemarkFormat {
+    fn from(format: RemarksFormat) -> Self {
+        match format {
+            RemarksFormat::Human => RemarkFormat::Human,
+            RemarksFormat::Yaml => RemarkFormat::Yaml,
+            RemarksFormat::Json => RemarkFormat::Json,
+        }
+    }
+}
+
+#[derive(ClapParser, Debug)]
+#[command(version, about = "A high-performance Python compiler")]
+struct Cli {
+    /// Source file to compile; a built-in example is used when omitted
+    input: Option<PathBuf>,
+
+    /// Print an intermediate representation and stop instead of running
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+
+    /// Stop after semantic analysis; exit with status 1 if there are errors
+    #[arg(long)]
+    check: bool,
+
+    /// Silence a lint rule, given by code (PB001) or name, or `all`
+    #[arg(short = 'A', long = "allow", value_name = "RULE")]
+    allow: Vec<String>,
+
+    /// Report a lint rule as a warning
+    #[arg(short = 'W', long = "warn", value_name = "RULE")]
+    warn: Vec<String>,
+
+    /// Report a lint rule as an error
+    #[arg(short = 'D', long = "deny", value_name = "RULE")]
+    deny: Vec<String>,
+
+    /// Optimization level
+    #[arg(short = 'O', value_enum, default_value = "2")]
+    opt_level: Level,
+
+    /// Run these passes, separated by commas, instead of those of the level
+    #[arg(long, value_name = "PASSES")]
+    passes: Option<String>,
+
+    /// Add a pass, given by name or short name, to the pipeline
+    #[arg(long = "enable-pass", value_name = "PASS")]
+    enable_passes: Vec<String>,
+
+    /// Remove a pass from the pipeline
+    #[arg(long = "disable-pass", value_name = "PASS")]
+    disable_passes: Vec<String>,
+
+    /// Inline functions of up to this many instructions at every call
+    #[arg(long, value_name = "SIZE")]
+    inline_threshold: Option<usize>,
+
+    /// Log the time spent in each optimization pass
+    #[arg(long)]
+    time_passes: bool,
+
+    /// Log the counters of the optimization passes
+    #[arg(long)]
+    stats: bool,
+
+    /// Log the IR before each optimization pass
+    #[arg(long)]
+    print_before_all: bool,
+
+    /// Log the IR after each optimization pass
+    #[arg(long)]
+    print_after_all: bool,
+
+    /// Log how each optimization pass changes the IR, as a diff
+    #[arg(long)]
+    print_diff_all: bool,
+
+    /// Log the IR before the given pass
+    #[arg(long, value_name = "PASS")]
+    print_before: Vec<String>,
+
+    /// Log the IR after the given pass
+    #[arg(long, value_name = "PASS")]
+    print_after: Vec<String>,
+
+    /// Log how the given pass changes the IR, as a diff
+    #[arg(long, value_name = "PASS")]
+    print_diff: Vec<String>,
+
+    /// Report optimization remarks of the passes matching a regex: applied
+    /// (-Rpass=REGEX), missed (-Rpass-missed=REGEX) or analyses
+    /// (-Rpass-analysis=REGEX)
+    #[arg(short = 'R', value_name = "KIND=REGEX")]
+    remarks: Vec<String>,
+
+    /// Format of optimization remarks
+    #[arg(long, value_enum, default_value = "human")]
+    remarks_format: RemarksFormat,
+}
+
+impl Cli {
+    fn lint_config(&self) -> Result<LintConfig, String> {
+        let mut config = LintConfig::default();
+        for (rules, level) in [(&self.allow, LintLevel::Allow), (&self.warn, LintLevel::Warn), (&self.deny, LintLevel::Deny)] {
+            for rule in rules {
+                config.set(rule, level)?;
+            }
+        }
+        Ok(config)
+    }
+
+    fn optimizer(&self) -> Result<Optimizer, String> {
+        let mut optimizer = match &self.passes {
+            Some(passes) => Optimizer::with_pipeline(passes)?,
+            None => Optimizer::with_level(self.opt_level.into()),
+        };
+        for pass in &self.enable_passes {
+            optimizer.enable(pass)?;
+        }
+        for pass in &self.disable_passes {
+            optimizer.disable(pass)?;
+        }
+        if let Some(threshold) = self.inline_threshold {
+            optimizer.set_inliner(Inliner { threshold, ..OptLevel::from(self.opt_level).inliner() });
+        }
+        optimizer.set_instrumentation(self.instrumentation()?);
+        Ok(optimizer)
+    }
+
+    fn remark_filter(&self) -> Result<RemarkFilter, String> {
+        let mut filter = RemarkFilter::new();
+        for option in &self.remarks {
+            filter.add(option)?;
+        }
+        Ok(filter)
+    }
+
+    fn instrumentation(&self) -> Result<Instrumentation, String> {
+        let dump_passes = |all: bool, names: &[String]| -> Result<DumpPasses, String> {
+            let mut passes = if all { DumpPasses::All } else { DumpPasses::None };
+            for name in names {
+                passes.add(name)?;
+            }
+            Ok(passes)
+        };
+        Ok(Instrumentation {
+            time_passes: self.time_passes,
+            statistics: self.stats,
+            print_before: dump_passes(self.print_before_all, &self.print_before)?,
+            print_after: dump_passes(self.print_after_all, &self.print_after)?,
+            print_diff: dump_passes(self.print_diff_all, &self.print_diff)?,
+        })
+    }
+}
+
+/// Analyzes the parsed program, printing its warnings, and exits with every
+/// error found if there are any.
+fn analyze(parser: &Parser, lint_config: LintConfig) -> SemanticAnalyzer {
+    let mut analyzer = SemanticAnalyzer::with_lint_config(lint_config);
+    let result = analyzer.analyze(parser);
+    for warning in analyzer.diagnostics().iter().filter(|diagnostic| diagnostic.severity == Severity::Warning) {
+        eprintln!("{}", warning.render(parser.source()));
+    }
+    if let Err(errors) = result {
+        eprintln!("{}", errors);
+        std::process::exit(1);
+    }
+    analyzer
+}
 
 fn main() {
-    let input = "let x = 42;";
+    let cli = Cli::parse();
+    let input = match &cli.input {
+        Some(path) => fs::read_to_string(path).expect("Failed to read input file"),
+        None => "let x = 42;".to_string(),
+    };
+    let input = input.as_str();
+    let lint_config = cli.lint_config().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(2);
+    });
+    let mut optimizer = cli.optimizer().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(2);
+    });
+    let remark_filter = cli.remark_filter().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(2);
+    });
+    // What the optimizer is asked to report is logged at the info level.
+    let default_filter = if cli.instrumentation().is_ok_and(|instrumentation| instrumentation.is_enabled()) { "info" } else { "warn" };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_filter))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+
+    if cli.check {
+        let mut parser = Parser::new(Lexer::new(input));
+        if let Err(error) = parser.parse() {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        analyze(&parser, lint_config);
+        return;
+    }
+
+    if let Some(emit) = cli.emit {
+        let mut parser = Parser::new(Lexer::new(input));
+        let ast = match parser.parse() {
+            Ok(ast) => ast,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(1);
+            }
+        };
+        match emit {
+            Emit::Ast => println!("{}", serialize::to_json(&ast)),
+            Emit::AstDump => println!("{}", serialize::dump(&ast)),
+            Emit::Hir => {
+                let analyzer = analyze(&parser, lint_config);
+                print!("{}", analyzer.hir().expect("analysis lowers the program"));
+            }
+            Emit::Ir => {
+                let analyzer = analyze(&parser, lint_config);
+                match IRGenerator::new().generate(analyzer.hir().expect("analysis lowers the program")) {
+                    Ok(ir) => print!("{}", ir),
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        return;
+    }
 
     let mut lexer = Lexer::new(input);
     println!("Lexing...");
@@ -29,23 +317,40 @@ fn main() {
     println!("Parsing...");
     parser.parse().expect("Parsing failed");
 
-    let mut semantic_analyzer = SemanticAnalyzer::new();
     println!("Semantic Analysis...");
-    semantic_analyzer.analyze(&parser).expect("Semantic analysis failed");
+    let semantic_analyzer = analyze(&parser, lint_config);
+    println!("Semantic analysis completed.");
 
     let mut ir_generator = IRGenerator::new();
     println!("IR Generation...");
-    let ir = ir_generator.generate(&parser).expect("IR generation failed");
+    let ir = ir_generator.generate(semantic_analyzer.hir().expect("analysis lowers the program")).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    });
+    println!("IR generation completed.");
 
-    let mut optimizer = Optimizer::new();
     println!("Optimization...");
     let optimized_ir = optimizer.optimize(ir).expect("Optimization failed");
+    if remark_filter.is_enabled() {
+        let shown: Vec<_> = optimizer.remarks().iter().filter(|remark| remark_filter.matches(remark)).cloned().collect();
+        eprint!("{}", remarks::render(&shown, input, cli.remarks_format.into()));
+    }
 
     let mut code_generator = CodeGenerator::new();
     println!("Code Generation...");
     let instructions = code_generator.generate(&optimized_ir).expect("Code generation failed");
 
-    let mut runtime = Runtime::new(instructions);  // Provide the necessary argument
+    let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
     println!("Running...");
-    runtime.run().expect("Runtime execution failed");
+    if let Err(error) = runtime.run() {
+        let file = cli.input.as_ref().map_or("<string>".to_string(), |path| path.display().to_string());
+        eprintln!("Traceback (most recent call last):");
+        for frame in code_generator.traceback(runtime.traceback()) {
+            let (line, _) = line_column(input, frame.span.start);
+            let function = if frame.function == MAIN { "<module>" } else { frame.function.as_str() };
+            eprintln!("  File \"{}\", line {}, in {}", file, line, function);
+        }
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
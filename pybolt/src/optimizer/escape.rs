@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::ir::ir::{Block, Function, Inst, InstKind, Terminator, Type, Value};
+
+/// How a tuple escapes: it leaves the function, or becomes an object that
+/// code other than unpacking sees, so that it has to exist on the heap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Escape {
+    /// Passed to the function.
+    Call(String),
+    Returned,
+    /// Stored to the global.
+    Global(String),
+    Printed,
+    /// Used by an instruction that needs the object, such as `add` or
+    /// `tuple`, or unpacked into another number of values.
+    Operation(&'static str),
+    /// Passed to a block parameter that may hold other values.
+    Merged,
+}
+
+impl Escape {
+    /// Completes "it is ...".
+    pub fn describe(&self) -> String {
+        match self {
+            Escape::Call(function) => format!("passed to `{}`", function),
+            Escape::Returned => "returned".to_string(),
+            Escape::Global(name) => format!("stored to the global `{}`", name),
+            Escape::Printed => "printed".to_string(),
+            Escape::Operation(opcode) => format!("used by `{}`", opcode),
+            Escape::Merged => "merged with other values".to_string(),
+        }
+    }
+}
+
+/// Escape analysis of the tuples of a function. A tuple that is only
+/// unpacked, into as many values as it has items, never needs to exist as
+/// an object: its items can be used instead. The same holds for a block
+/// parameter that only ever holds such tuples, of one length, and is only
+/// unpacked, so tuples flowing around a loop or out of a conditional
+/// expression do not escape either.
+///
+/// Tuples are the only aggregates analyzed: lists are shared and may be
+/// changed through any value holding them, and the IR has no objects with
+/// fields. The analysis looks at one function, so a tuple it
+/// returns escapes, even when every caller unpacks it; it goes only where
+/// the function is inlined. A tuple that escapes in several ways is
+/// reported as returned only when that is the only way.
+pub struct EscapeAnalysis {
+    /// The tuples and block parameters that do not escape, with their
+    /// number of items.
+    pub aggregates: HashMap<Value, usize>,
+    /// The tuples that escape, with the first reason found.
+    pub escaping: Vec<(Inst, Escape)>,
+}
+
+/// A use of a value.
+#[derive(Clone, Copy)]
+enum Use {
+    Inst(Inst),
+    /// Passed as argument `index` of a branch to `block`.
+    Argument { block: Block, index: usize },
+    Return,
+}
+
+impl EscapeAnalysis {
+    pub fn analyze(function: &Function) -> Self {
+        let mut uses: HashMap<Value, Vec<Use>> = HashMap::new();
+        let mut incoming: HashMap<Value, Vec<Value>> = HashMap::new();
+        let mut tuples: HashMap<Value, Inst> = HashMap::new();
+        let mut aggregates: HashMap<Value, usize> = HashMap::new();
+        for block in &function.layout {
+            for inst in &function.block(*block).insts {
+                let kind = &function.inst(*inst).kind;
+                for operand in kind.operands() {
+                    uses.entry(operand).or_default().push(Use::Inst(*inst));
+                }
+                if let (InstKind::Tuple(items), Some(result)) = (kind, function.result(*inst)) {
+                    tuples.insert(result, *inst);
+                    aggregates.insert(result, items.len());
+                }
+            }
+            match function.terminator(*block) {
+                Some(Terminator::Return(Some(value))) => uses.entry(*value).or_default().push(Use::Return),
+                Some(terminator) => {
+                    for target in terminator.successors() {
+                        let params = &function.block(target.block).params;
+                        for (index, (argument, param)) in target.arguments.iter().zip(params).enumerate() {
+                            uses.entry(*argument).or_default().push(Use::Argument { block: target.block, index });
+                            incoming.entry(*param).or_default().push(*argument);
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+
+        // Block parameters start out holding tuples of the length of one
+        // they receive, if all they receive may be tuples.
+        loop {
+            let mut changed = false;
+            for (param, values) in &incoming {
+                if aggregates.contains_key(param) || function.value_type(*param) != Type::Object {
+                    continue;
+                }
+                let may_be_tuples = values.iter().all(|value| aggregates.contains_key(value) || incoming.contains_key(value));
+                if let Some(length) = values.iter().find_map(|value| aggregates.get(value)).filter(|_| may_be_tuples) {
+                    aggregates.insert(*param, *length);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        // Then whatever escapes is removed, until nothing else does.
+        let mut escaping: HashMap<Value, Escape> = HashMap::new();
+        loop {
+            let mut escaped = Vec::new();
+            for (value, length) in &aggregates {
+                let merged = incoming.get(value).is_some_and(|values| values.iter().any(|value| aggregates.get(value) != Some(length)));
+                let escape = if merged {
+                    Some(Escape::Merged)
+                } else {
+                    let (returns, others): (Vec<Use>, Vec<Use>) =
+                        uses.get(value).into_iter().flatten().partition(|use_| matches!(use_, Use::Return));
+                    others.iter().chain(&returns).find_map(|use_| escape(function, *use_, *value, *length, &aggregates, &escaping))
+                };
+                if let Some(escape) = escape {
+                    escaped.push((*value, escape));
+                }
+            }
+            if escaped.is_empty() {
+                break;
+            }
+            for (value, escape) in escaped {
+                aggregates.remove(&value);
+                escaping.insert(value, escape);
+            }
+        }
+
+        let mut escaping: Vec<(Inst, Escape)> =
+            escaping.into_iter().filter_map(|(value, escape)| tuples.get(&value).map(|inst| (*inst, escape))).collect();
+        escaping.sort_by_key(|(inst, _)| inst.0);
+        EscapeAnalysis { aggregates, escaping }
+    }
+}
+
+/// How `value`, a tuple of `length` items, escapes through `use_`, if it
+/// does. Passed to a block parameter that escaped, it escapes the same way.
+fn escape(
+    function: &Function,
+    use_: Use,
+    value: Value,
+    length: usize,
+    aggregates: &HashMap<Value, usize>,
+    escaping: &HashMap<Value, Escape>,
+) -> Option<Escape> {
+    match use_ {
+        Use::Return => Some(Escape::Returned),
+        Use::Argument { block, index } => {
+            let param = function.block(block).params[index];
+            match escaping.get(&param) {
+                Some(escape) => Some(escape.clone()),
+                None => (aggregates.get(&param) != Some(&length)).then_some(Escape::Merged),
+            }
+        }
+        Use::Inst(inst) => match &function.inst(inst).kind {
+            InstKind::Unpack { tuple, length: unpacked, .. } if *tuple == value && *unpacked == length => None,
+            InstKind::Call { function, .. } => Some(Escape::Call(function.clone())),
+            InstKind::StoreGlobal { name, .. } => Some(Escape::Global(name.clone())),
+            InstKind::Print(_) => Some(Escape::Printed),
+            kind => Some(Escape::Operation(kind.opcode())),
+        },
+    }
+}
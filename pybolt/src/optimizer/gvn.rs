@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::dominators::DominatorTree;
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Function, Inst, InstKind, Memory, Module, Type, Value};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Global value numbering. An instruction is removed when an equal one,
+/// on the same operands, dominates it and always computes the same result:
+/// - pure instructions, including those that may raise, such as `unbox`,
+///   and `guard`s, whose object cannot change class;
+/// - `load_global`s of a global that was loaded or stored on every path
+///   since, with no store to it or call in between, which is where an
+///   attribute load would be once objects have attributes.
+///
+/// What an instruction reads and writes comes from the effect model,
+/// `Function::effects`.
+pub struct GlobalValueNumbering;
+
+impl Optimization for GlobalValueNumbering {
+    fn name(&self) -> &'static str {
+        "gvn"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut changed = false;
+        for function in &mut ir.functions {
+            let dominators = context.analyses.dominators(function);
+            changed |= number_values(function, &dominators, context);
+        }
+        Ok(changed)
+    }
+}
+
+/// The globals whose value is known at a point, with the value.
+type KnownGlobals = HashMap<String, Value>;
+
+fn number_values(function: &mut Function, dominators: &DominatorTree, context: &mut PassContext) -> bool {
+    let mut replacements: HashMap<Value, Value> = HashMap::new();
+    let mut removed: HashSet<Inst> = HashSet::new();
+    let known_on_entry = known_globals(function, dominators);
+
+    // Reverse postorder visits a block after its dominators.
+    let mut numbered: HashMap<(InstKind, Type), Vec<(Value, Block)>> = HashMap::new();
+    for block in dominators.reverse_postorder().to_vec() {
+        let mut known = known_on_entry[&block].clone();
+        // The instruction that last made a global unknown in the block.
+        let mut clobbered: HashMap<String, &'static str> = HashMap::new();
+        for inst in function.block(block).insts.clone() {
+            for operand in function.inst_mut(inst).kind.operands_mut() {
+                if let Some(replacement) = replacements.get(operand) {
+                    *operand = *replacement;
+                }
+            }
+            let effects = function.effects(inst);
+            let data = function.inst(inst);
+            let (kind, span) = (data.kind.clone(), data.span);
+            let (reads, writes) = (effects.reads, effects.writes);
+
+            if let (Some(result), InstKind::LoadGlobal(name)) = (data.result, &kind) {
+                match known.get(name) {
+                    Some(value) if function.value_type(*value) == function.value_type(result) => {
+                        let message = format!("removed `load_global @{}`: the value of the global is already known", name);
+                        context.remark(RemarkKind::Passed, &function.name, span, message);
+                        replacements.insert(result, *value);
+                        removed.insert(inst);
+                    }
+                    _ => {
+                        if let Some(opcode) = clobbered.get(name) {
+                            let message = format!("could not reuse the earlier value of global `{}`: a `{}` in between may change it", name, opcode);
+                            context.remark(RemarkKind::Missed, &function.name, span, message);
+                        }
+                        known.insert(name.clone(), result);
+                    }
+                }
+                continue;
+            }
+            match writes {
+                Memory::Nothing | Memory::Lists => {}
+                Memory::Global(name) => {
+                    known.remove(name);
+                    clobbered.insert(name.to_string(), kind.opcode());
+                }
+                Memory::Everything => {
+                    for name in known.keys() {
+                        clobbered.insert(name.clone(), kind.opcode());
+                    }
+                    known.clear();
+                }
+            }
+            if let InstKind::StoreGlobal { name, value } = &kind {
+                known.insert(name.clone(), *value);
+                clobbered.remove(name);
+            }
+
+            let Some(result) = function.result(inst) else {
+                continue;
+            };
+            if reads != Memory::Nothing || !effects.is_pure() {
+                continue;
+            }
+            let key = (canonical(function, kind.clone()), function.value_type(result));
+            let candidates = numbered.entry(key).or_default();
+            match candidates.iter().find(|(_, def)| dominators.dominates(*def, block)) {
+                Some((value, _)) => {
+                    let message = format!("removed `{}`: it computes the same value as an earlier one", kind.opcode());
+                    context.remark(RemarkKind::Passed, &function.name, span, message);
+                    replacements.insert(result, *value);
+                    removed.insert(inst);
+                }
+                None => candidates.push((result, block)),
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        return false;
+    }
+    for block in function.layout.clone() {
+        function.block_mut(block).insts.retain(|inst| !removed.contains(inst));
+        for inst in function.block(block).insts.clone() {
+            for operand in function.inst_mut(inst).kind.operands_mut() {
+                *operand = resolve(&replacements, *operand);
+            }
+        }
+        if let Some(terminator) = &mut function.block_mut(block).terminator {
+            for operand in terminator.operands_mut() {
+                *operand = resolve(&replacements, *operand);
+            }
+        }
+    }
+    let loads = removed.iter().filter(|inst| matches!(function.inst(**inst).kind, InstKind::LoadGlobal(_))).count();
+    context.count("redundant loads removed", loads);
+    context.count("redundant instructions removed", removed.len() - loads);
+    true
+}
+
+/// Follows a chain of replacements to its end. A replacement is never
+/// itself replaced when it is recorded, but a known global may have been.
+fn resolve(replacements: &HashMap<Value, Value>, mut value: Value) -> Value {
+    while let Some(replacement) = replacements.get(&value) {
+        value = *replacement;
+    }
+    value
+}
+
+/// Orders the operands of commutative operations on numbers and bools, so
+/// that `a + b` and `b + a` are numbered alike. On objects the order is
+/// kept, since `+` on strings is not commutative.
+fn canonical(function: &Function, kind: InstKind) -> InstKind {
+    let commutes = |left: Value| matches!(function.value_type(left), Type::Int | Type::I64 | Type::F64 | Type::Bool);
+    match kind {
+        InstKind::Binary { op: op @ (BinaryOp::Add | BinaryOp::Mul), left, right } if commutes(left) && right < left => {
+            InstKind::Binary { op, left: right, right: left }
+        }
+        InstKind::Compare { op: op @ (CompareOp::Eq | CompareOp::Ne), left, right } if commutes(left) && right < left => {
+            InstKind::Compare { op, left: right, right: left }
+        }
+        kind => kind,
+    }
+}
+
+/// The globals whose value is known on entry to each reachable block: those
+/// loaded or stored on every path to it, as the same value, and written by
+/// nothing since. This is an available-expressions problem, solved by
+/// iterating over the blocks in reverse postorder.
+fn known_globals(function: &Function, dominators: &DominatorTree) -> HashMap<Block, KnownGlobals> {
+    let order = dominators.reverse_postorder();
+    let predecessors = function.predecessors();
+    // `None` stands for every global, before a block is first reached.
+    let mut known_on_exit: HashMap<Block, Option<KnownGlobals>> = order.iter().map(|block| (*block, None)).collect();
+    let mut known_on_entry: HashMap<Block, KnownGlobals> = HashMap::new();
+    loop {
+        let mut changed = false;
+        for block in order {
+            let mut known: Option<KnownGlobals> = if *block == function.entry() { Some(KnownGlobals::new()) } else { None };
+            for predecessor in predecessors.get(block).into_iter().flatten() {
+                let Some(Some(exit)) = known_on_exit.get(predecessor) else {
+                    continue;
+                };
+                known = Some(match known {
+                    None => exit.clone(),
+                    Some(known) => known.into_iter().filter(|(name, value)| exit.get(name) == Some(value)).collect(),
+                });
+            }
+            let known = known.unwrap_or_default();
+            let exit = transfer(function, *block, known.clone());
+            known_on_entry.insert(*block, known);
+            if known_on_exit[block].as_ref() != Some(&exit) {
+                known_on_exit.insert(*block, Some(exit));
+                changed = true;
+            }
+        }
+        if !changed {
+            return known_on_entry;
+        }
+    }
+}
+
+/// The globals known after the block, given those known before it.
+fn transfer(function: &Function, block: Block, mut known: KnownGlobals) -> KnownGlobals {
+    for inst in &function.block(block).insts {
+        match &function.inst(*inst).kind {
+            InstKind::LoadGlobal(name) => {
+                if !known.contains_key(name) {
+                    known.insert(name.clone(), function.result(*inst).expect("loads have a result"));
+                }
+            }
+            InstKind::StoreGlobal { name, value } => {
+                known.insert(name.clone(), *value);
+            }
+            _ => match function.effects(*inst).writes {
+                Memory::Nothing | Memory::Lists => {}
+                Memory::Global(name) => {
+                    known.remove(name);
+                }
+                Memory::Everything => known.clear(),
+            },
+        }
+    }
+    known
+}
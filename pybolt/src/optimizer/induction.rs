@@ -0,0 +1,221 @@
+use crate::analysis::loops::{Loop, LoopNest};
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Constant, Function, Inst, InstKind, Module, Type, Value, ValueDef};
+use crate::optimizer::loops::{counted_loop, i64_operand, induction_variables, inst_blocks, int_constant, is_invariant, to_i64, CountedLoop};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Induction variable simplification. The `int` counter of a counted loop,
+/// such as the `i` of `for i in range(...)`, becomes an `i64`, which adds
+/// and compares without the overflow checks of an `int`, when its values
+/// are known to fit: the start and the bound are constants, or `i64`s and
+/// the counter goes by one up to a `lt` or down to a `gt`. What the loop
+/// does with the counter then uses a `cast` of it to `int`.
+pub struct InductionVariableSimplification;
+
+impl Optimization for InductionVariableSimplification {
+    fn name(&self) -> &'static str {
+        "indvars"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut simplified = 0;
+        for function in &mut ir.functions {
+            let nest = context.analyses.loops(function);
+            for l in nest.loops() {
+                let Some(preheader) = nest.preheader(function, l) else {
+                    continue;
+                };
+                let blocks_of = inst_blocks(function);
+                let Some(counted) = counted_loop(function, &blocks_of, &nest, l, preheader) else {
+                    continue;
+                };
+                if function.value_type(counted.iv.param) != Type::Int {
+                    continue;
+                }
+                let span = function.inst(counted.compare).span;
+                if fits_i64(function, &counted) {
+                    simplify(function, &nest, l, preheader, &counted);
+                    context.remark(RemarkKind::Passed, &function.name, span, "turned the `int` loop counter into an `i64`");
+                    simplified += 1;
+                } else {
+                    let message = "could not turn the `int` loop counter into an `i64`: its values may not fit in 64 bits";
+                    context.remark(RemarkKind::Missed, &function.name, span, message);
+                }
+            }
+        }
+        context.count("loop counters simplified", simplified);
+        Ok(simplified > 0)
+    }
+}
+
+/// Whether every value of the counter of the loop, the one ending it
+/// included, is an `i64`.
+fn fits_i64(function: &Function, counted: &CountedLoop) -> bool {
+    if let Some((low, high)) = counted.range(function) {
+        return low >= i64::MIN as i128 && high <= i64::MAX as i128;
+    }
+    let by_one = counted.iv.step.abs() == 1 && matches!(counted.op, CompareOp::Lt | CompareOp::Gt);
+    by_one && i64_operand(function, counted.iv.start).is_some() && i64_operand(function, counted.bound).is_some()
+}
+
+/// Makes an instruction for `like` at the end of `block` and returns its
+/// result.
+fn append(function: &mut Function, block: Block, kind: InstKind, ty: Type, like: Inst) -> Value {
+    let inst = function.make_inst_like(like, kind, Some(ty));
+    function.block_mut(block).insts.push(inst);
+    function.result(inst).expect("the instruction has a result")
+}
+
+/// Adds `value` to the arguments of every branch from `from` to `to`.
+fn add_argument(function: &mut Function, from: Block, to: Block, value: Value) {
+    if let Some(terminator) = &mut function.block_mut(from).terminator {
+        for target in terminator.successors_mut() {
+            if target.block == to {
+                target.arguments.push(value);
+            }
+        }
+    }
+}
+
+/// Replaces the `int` counter by an `i64` one, which the comparison of the
+/// header uses directly.
+fn simplify(function: &mut Function, nest: &LoopNest, l: Loop, preheader: Block, counted: &CountedLoop) {
+    let data = nest.loop_data(l);
+    let like = counted.compare;
+    let mut constants = Vec::new();
+    let [start, bound] = [counted.iv.start, counted.bound].map(|value| {
+        let operand = i64_operand(function, value).expect("the values of the counter fit in an i64");
+        to_i64(function, operand, like, &mut constants)
+    });
+    function.block_mut(preheader).insts.extend(constants);
+    let step = append(function, preheader, InstKind::Const(Constant::Int(counted.iv.step)), Type::I64, like);
+    let counter = function.append_block_param(data.header, Type::I64);
+    add_argument(function, preheader, data.header, start);
+    for latch in &data.latches {
+        let next = append(function, *latch, InstKind::Binary { op: BinaryOp::Add, left: counter, right: step }, Type::I64, like);
+        add_argument(function, *latch, data.header, next);
+    }
+    let cast = function.make_inst_like(like, InstKind::Cast(counter), Some(Type::Int));
+    function.block_mut(data.header).insts.insert(0, cast);
+    let value = function.result(cast).expect("casts have a result");
+    function.replace_uses(counted.iv.param, value);
+    function.inst_mut(counted.compare).kind = InstKind::Compare { op: counted.op, left: counter, right: bound };
+    function.remove_block_param(data.header, counted.iv.index);
+}
+
+/// Strength reduction. A multiplication, in a loop, of a basic induction
+/// variable by a loop-invariant factor becomes an induction variable of its
+/// own: the preheader computes its first value, and every iteration adds
+/// the step times the factor instead of multiplying. An `int` counter that
+/// `indvars` turned into an `i64` is reduced through its `cast`.
+pub struct StrengthReduction;
+
+impl Optimization for StrengthReduction {
+    fn name(&self) -> &'static str {
+        "strength-reduction"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut reduced = 0;
+        for function in &mut ir.functions {
+            let nest = context.analyses.loops(function);
+            for l in nest.loops() {
+                let Some(preheader) = nest.preheader(function, l) else {
+                    continue;
+                };
+                for multiplication in multiplications(function, &nest, l, preheader) {
+                    reduce(function, &nest, l, preheader, &multiplication);
+                    let message = "replaced `mul` of an induction variable by an addition in each iteration";
+                    context.remark(RemarkKind::Passed, &function.name, function.inst(multiplication.inst).span, message);
+                    reduced += 1;
+                }
+            }
+        }
+        context.count("multiplications reduced", reduced);
+        Ok(reduced > 0)
+    }
+}
+
+/// A multiplication of an induction variable by a loop-invariant factor.
+struct Multiplication {
+    inst: Inst,
+    start: Value,
+    step: i64,
+    /// Whether the variable is an `i64` cast to the `int` multiplied.
+    cast: bool,
+    factor: Value,
+    ty: Type,
+}
+
+fn multiplications(function: &Function, nest: &LoopNest, l: Loop, preheader: Block) -> Vec<Multiplication> {
+    let blocks_of = inst_blocks(function);
+    let variables = induction_variables(function, nest, l, preheader);
+    let variable = |value: Value| {
+        if let Some(iv) = variables.iter().find(|iv| iv.param == value) {
+            return Some((iv, false));
+        }
+        let ValueDef::Result(inst) = function.value(value).def else {
+            return None;
+        };
+        match function.inst(inst).kind {
+            InstKind::Cast(operand) if function.value_type(value) == Type::Int => {
+                variables.iter().find(|iv| iv.param == operand).map(|iv| (iv, true))
+            }
+            _ => None,
+        }
+    };
+    let mut multiplications = Vec::new();
+    for block in &nest.loop_data(l).blocks {
+        for inst in &function.block(*block).insts {
+            let InstKind::Binary { op: BinaryOp::Mul, left, right } = function.inst(*inst).kind else {
+                continue;
+            };
+            let Some(ty) = function.result(*inst).map(|result| function.value_type(result)) else {
+                continue;
+            };
+            if !matches!(ty, Type::Int | Type::I64) {
+                continue;
+            }
+            let (iv, cast, factor) = match (variable(left), variable(right)) {
+                (Some((iv, cast)), _) if is_invariant(function, &blocks_of, nest, l, right) => (iv, cast, right),
+                (_, Some((iv, cast))) if is_invariant(function, &blocks_of, nest, l, left) => (iv, cast, left),
+                _ => continue,
+            };
+            multiplications.push(Multiplication { inst: *inst, start: iv.start, step: iv.step, cast, factor, ty });
+        }
+    }
+    multiplications
+}
+
+/// Replaces the multiplication by a new parameter of the header.
+fn reduce(function: &mut Function, nest: &LoopNest, l: Loop, preheader: Block, multiplication: &Multiplication) {
+    let data = nest.loop_data(l);
+    let &Multiplication { inst, start, step, cast, factor, ty } = multiplication;
+    let like = inst;
+    let start = if cast { append(function, preheader, InstKind::Cast(start), Type::Int, like) } else { start };
+    let first = append(function, preheader, InstKind::Binary { op: BinaryOp::Mul, left: start, right: factor }, ty, like);
+    // An `int` product that overflows is left to the `mul`, which makes it
+    // a big integer; `i64`s wrap around, as the additions will.
+    let product = int_constant(function, factor).and_then(|factor| match ty {
+        Type::I64 => Some(step.wrapping_mul(factor)),
+        _ => step.checked_mul(factor),
+    });
+    let delta = match product {
+        Some(product) => append(function, preheader, InstKind::Const(Constant::Int(product)), ty, like),
+        None => {
+            let step = append(function, preheader, InstKind::Const(Constant::Int(step)), ty, like);
+            append(function, preheader, InstKind::Binary { op: BinaryOp::Mul, left: step, right: factor }, ty, like)
+        }
+    };
+    let param = function.append_block_param(data.header, ty);
+    add_argument(function, preheader, data.header, first);
+    for latch in &data.latches {
+        let next = append(function, *latch, InstKind::Binary { op: BinaryOp::Add, left: param, right: delta }, ty, like);
+        add_argument(function, *latch, data.header, next);
+    }
+    let result = function.result(inst).expect("multiplications have a result");
+    function.replace_uses(result, param);
+    for block in &data.blocks {
+        function.block_mut(*block).insts.retain(|other| *other != inst);
+    }
+}
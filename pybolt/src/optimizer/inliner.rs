@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+use crate::ir::ir::{
+    Block, BlockCall, Constant, Function, InlineHint, InlinedAt, Inst, InstKind, Module, Terminator, Type, Value,
+};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Replaces calls by the body of the function they call. A call is inlined
+/// when its function:
+/// - is marked `@pybolt.inline`, unless it is recursive;
+/// - is called only there;
+/// - has at most `threshold` instructions, or twice as many if it is a leaf,
+///   which calls no function: its body then has nothing left to hide from
+///   the optimizations of the caller.
+///
+/// Functions marked `@pybolt.noinline` are never inlined, and a recursive
+/// function is inlined into itself at most `recursion_limit` times. The
+/// inlined instructions record the call they replaced, so that tracebacks
+/// still show the frames of the inlined functions (`Function::frames`).
+/// Functions whose calls were all inlined are removed, `__main__` aside.
+pub struct Inliner {
+    /// The size, in instructions, up to which a function is inlined at
+    /// every call.
+    pub threshold: usize,
+    /// How many times a recursive function may be inlined into itself.
+    pub recursion_limit: usize,
+}
+
+impl Inliner {
+    pub const DEFAULT_THRESHOLD: usize = 20;
+
+    pub fn new() -> Self {
+        Inliner { threshold: Self::DEFAULT_THRESHOLD, recursion_limit: 0 }
+    }
+}
+
+impl Default for Inliner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimization for Inliner {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let calls_before = ir.call_counts();
+        let mut inlined = 0;
+        for index in bottom_up(ir) {
+            let caller = &ir.functions[index];
+            let calls: Vec<Inst> =
+                caller.layout_insts().filter(|inst| matches!(caller.inst(*inst).kind, InstKind::Call { .. })).collect();
+            for inst in calls {
+                let InstKind::Call { function: name, .. } = &ir.functions[index].inst(inst).kind else {
+                    continue;
+                };
+                let Some(callee) = ir.function(name).cloned() else {
+                    continue;
+                };
+                let caller = &mut ir.functions[index];
+                let span = caller.inst(inst).span;
+                match self.decide(caller, inst, &callee, calls_before.get(&callee.name).copied().unwrap_or(0)) {
+                    Ok(reason) => {
+                        let message = format!("inlined `{}` into `{}`: {}", callee.name, caller.name, reason);
+                        context.remark(RemarkKind::Passed, &caller.name, span, message);
+                        inline_call(caller, inst, &callee);
+                        inlined += 1;
+                    }
+                    Err(reason) => {
+                        let message = format!("did not inline `{}` into `{}`: {}", callee.name, caller.name, reason);
+                        context.remark(RemarkKind::Missed, &caller.name, span, message);
+                    }
+                }
+            }
+        }
+        if inlined == 0 {
+            return Ok(false);
+        }
+
+        let calls_after = ir.call_counts();
+        let functions_before = ir.functions.len();
+        ir.functions.retain(|function| !calls_before.contains_key(&function.name) || calls_after.contains_key(&function.name));
+        context.count("calls inlined", inlined);
+        context.count("functions removed", functions_before - ir.functions.len());
+        Ok(true)
+    }
+}
+
+impl Inliner {
+    /// Whether to inline the call of `callee` at `inst`, with the reason.
+    fn decide(&self, caller: &Function, inst: Inst, callee: &Function, calls: usize) -> Result<String, String> {
+        if callee.inline == InlineHint::Never {
+            return Err("it is marked `@pybolt.noinline`".to_string());
+        }
+        // The times the callee is already on the stack at the call.
+        let mut depth = (caller.name == callee.name) as usize;
+        let mut inlined_at = caller.inst(inst).inlined_at.as_deref();
+        while let Some(site) = inlined_at {
+            depth += (site.function == callee.name) as usize;
+            inlined_at = site.caller.as_deref();
+        }
+        if depth > self.recursion_limit {
+            return Err(format!("it is recursive, and the recursion limit is {}", self.recursion_limit));
+        }
+        if callee.inline == InlineHint::Always {
+            return Ok("it is marked `@pybolt.inline`".to_string());
+        }
+        if calls == 1 && depth == 0 {
+            return Ok("this is its only call".to_string());
+        }
+        let size = callee.instruction_count();
+        let is_leaf = !callee.layout_insts().any(|inst| matches!(callee.inst(inst).kind, InstKind::Call { .. }));
+        let threshold = if is_leaf { 2 * self.threshold } else { self.threshold };
+        let kind = if is_leaf { "leaf function" } else { "function" };
+        if size <= threshold {
+            Ok(format!("the {} has {} instructions, within the threshold of {}", kind, size, threshold))
+        } else {
+            Err(format!("the {} has {} instructions, over the threshold of {}", kind, size, threshold))
+        }
+    }
+}
+
+/// The indices of the functions, callees before their callers where the
+/// call graph has no cycles, so that a function is inlined with what was
+/// inlined into it.
+fn bottom_up(ir: &Module) -> Vec<usize> {
+    fn visit(ir: &Module, index: usize, visited: &mut HashSet<usize>, order: &mut Vec<usize>) {
+        if !visited.insert(index) {
+            return;
+        }
+        let function = &ir.functions[index];
+        for inst in function.layout_insts() {
+            if let InstKind::Call { function: callee, .. } = &function.inst(inst).kind {
+                if let Some(callee) = ir.functions.iter().position(|function| function.name == *callee) {
+                    visit(ir, callee, visited, order);
+                }
+            }
+        }
+        order.push(index);
+    }
+
+    let mut visited = HashSet::new();
+    let mut order = Vec::new();
+    for index in 0..ir.functions.len() {
+        visit(ir, index, &mut visited, &mut order);
+    }
+    order
+}
+
+/// Replaces the call `inst` by a copy of the body of `callee`. The body of
+/// a function of a single block takes the place of the call. Otherwise the
+/// entry block of the copy continues the block of the call, the other
+/// blocks are laid out after it, and the instructions after the call move
+/// to a new block, which the returns of the copy branch to with the value
+/// returned.
+fn inline_call(caller: &mut Function, inst: Inst, callee: &Function) {
+    let call = caller.inst(inst).clone();
+    let InstKind::Call { arguments, .. } = &call.kind else {
+        unreachable!("only calls are inlined");
+    };
+    let site = Rc::new(InlinedAt { function: callee.origin.clone(), call: call.span, caller: call.inlined_at.clone() });
+    let block = *caller.layout.iter().find(|block| caller.block(**block).insts.contains(&inst)).expect("the call is in the layout");
+    let position = caller.block(block).insts.iter().position(|other| *other == inst).expect("the call is in its block");
+
+    let mut values: HashMap<Value, Value> = callee.params().iter().copied().zip(arguments.iter().copied()).collect();
+    let mut blocks: HashMap<Block, Block> = HashMap::from([(callee.entry(), block)]);
+    if let (&[entry], Some(Terminator::Return(returned))) = (callee.layout.as_slice(), callee.terminator(callee.entry())) {
+        let mut copies = copy_insts(caller, callee, &site, &blocks, &mut values).remove(&entry).unwrap_or_default();
+        let returned = returned.map(|value| values[&value]);
+        let returned = match (call.result, returned) {
+            (Some(_), None) => Some(none(caller, &site, &mut copies)),
+            (_, returned) => returned,
+        };
+        caller.block_mut(block).insts.splice(position..=position, copies);
+        if let (Some(result), Some(returned)) = (call.result, returned) {
+            caller.replace_uses(result, returned);
+        }
+        return;
+    }
+
+    let rest = caller.block_mut(block).insts.split_off(position + 1);
+    caller.block_mut(block).insts.pop();
+    let terminator = caller.block_mut(block).terminator.take();
+    let layout_end = caller.layout.len();
+    for callee_block in &callee.layout[1..] {
+        let copy = caller.create_block();
+        blocks.insert(*callee_block, copy);
+        for param in &callee.block(*callee_block).params {
+            let value = caller.append_block_param(copy, callee.value_type(*param));
+            values.insert(*param, value);
+        }
+    }
+    let continuation = caller.create_block();
+    caller.block_mut(continuation).insts = rest;
+    caller.block_mut(continuation).terminator = terminator;
+    let result = call.result.map(|result| (result, caller.append_block_param(continuation, caller.value_type(result))));
+
+    let mut copies = copy_insts(caller, callee, &site, &blocks, &mut values);
+    for callee_block in &callee.layout {
+        let copy = blocks[callee_block];
+        let mut insts = copies.remove(callee_block).unwrap_or_default();
+        let terminator = match callee.terminator(*callee_block).cloned() {
+            Some(Terminator::Return(value)) => {
+                let arguments = match (result, value) {
+                    (None, _) => Vec::new(),
+                    (Some(_), Some(value)) => vec![values[&value]],
+                    (Some(_), None) => vec![none(caller, &site, &mut insts)],
+                };
+                Terminator::Br(BlockCall { block: continuation, arguments })
+            }
+            Some(mut terminator) => {
+                for operand in terminator.operands_mut() {
+                    *operand = values[operand];
+                }
+                for target in terminator.successors_mut() {
+                    target.block = blocks[&target.block];
+                }
+                terminator
+            }
+            None => Terminator::Unreachable,
+        };
+        caller.block_mut(copy).insts.extend(insts);
+        caller.block_mut(copy).terminator = Some(terminator);
+    }
+    if let Some((result, param)) = result {
+        caller.replace_uses(result, param);
+    }
+
+    // Lay the copied blocks and the continuation out right after the block.
+    let added = caller.layout.split_off(layout_end);
+    let after = caller.layout.iter().position(|other| *other == block).expect("the block is in the layout") + 1;
+    caller.layout.splice(after..after, added);
+}
+
+/// Copies the instructions of `callee` into `caller`, by block, with their
+/// operands mapped through `values`, which the results are added to. The
+/// blocks of the callee must be mapped to those of the caller already.
+fn copy_insts(
+    caller: &mut Function,
+    callee: &Function,
+    site: &Rc<InlinedAt>,
+    blocks: &HashMap<Block, Block>,
+    values: &mut HashMap<Value, Value>,
+) -> HashMap<Block, Vec<Inst>> {
+    let mut copies: HashMap<Block, Vec<Inst>> = HashMap::new();
+    for callee_block in callee.layout.iter().filter(|block| blocks.contains_key(block)) {
+        for callee_inst in &callee.block(*callee_block).insts {
+            let data = callee.inst(*callee_inst);
+            let copy = caller.make_inst(data.kind.clone(), data.result.map(|value| callee.value_type(value)), data.span);
+            caller.inst_mut(copy).inlined_at = Some(InlinedAt::within(data.inlined_at.as_ref(), site));
+            if let (Some(original), Some(value)) = (data.result, caller.result(copy)) {
+                values.insert(original, value);
+            }
+            copies.entry(*callee_block).or_default().push(copy);
+        }
+    }
+    for copy in copies.values().flatten() {
+        for operand in caller.inst_mut(*copy).kind.operands_mut() {
+            *operand = values[operand];
+        }
+    }
+    copies
+}
+
+/// A `none` for the result of a call of a function returning `none`,
+/// which the function does not return, added to `insts`.
+fn none(caller: &mut Function, site: &Rc<InlinedAt>, insts: &mut Vec<Inst>) -> Value {
+    let none = caller.make_inst(InstKind::Const(Constant::None), Some(Type::None), site.call);
+    caller.inst_mut(none).inlined_at = Some(site.clone());
+    insts.push(none);
+    caller.result(none).expect("constants have a result")
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::optimizer::optimizer::pass;
+
+/// Which passes to dump the IR around.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DumpPasses {
+    #[default]
+    None,
+    All,
+    /// The passes with these names.
+    Only(Vec<&'static str>),
+}
+
+impl DumpPasses {
+    /// Adds the pass, given by name or short name.
+    pub fn add(&mut self, name: &str) -> Result<(), String> {
+        let name = pass(name).ok_or_else(|| format!("Unknown optimization pass '{}'", name))?.name;
+        match self {
+            DumpPasses::None => *self = DumpPasses::Only(vec![name]),
+            DumpPasses::All => {}
+            DumpPasses::Only(names) => names.push(name),
+        }
+        Ok(())
+    }
+
+    pub fn includes(&self, name: &str) -> bool {
+        match self {
+            DumpPasses::None => false,
+            DumpPasses::All => true,
+            DumpPasses::Only(names) => names.contains(&name),
+        }
+    }
+}
+
+/// What the optimizer reports, through the `log` crate, while it runs.
+#[derive(Debug, Clone, Default)]
+pub struct Instrumentation {
+    /// Log how long each pass took.
+    pub time_passes: bool,
+    /// Log the counters of the passes.
+    pub statistics: bool,
+    pub print_before: DumpPasses,
+    pub print_after: DumpPasses,
+    /// Log how each pass that changed the IR changed it, as a diff.
+    pub print_diff: DumpPasses,
+}
+
+impl Instrumentation {
+    /// Whether anything is to be reported.
+    pub fn is_enabled(&self) -> bool {
+        self.time_passes
+            || self.statistics
+            || self.print_before != DumpPasses::None
+            || self.print_after != DumpPasses::None
+            || self.print_diff != DumpPasses::None
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassTiming {
+    pub runs: usize,
+    /// The runs that changed the IR.
+    pub changes: usize,
+    pub time: Duration,
+}
+
+/// The time spent in each pass and the counters passes keep, such as the
+/// number of constants folded, by pass.
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    /// In the order the passes first ran.
+    timings: Vec<(&'static str, PassTiming)>,
+    counters: BTreeMap<(&'static str, &'static str), usize>,
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    pub fn record_run(&mut self, pass: &'static str, changed: bool, time: Duration) {
+        let index = match self.timings.iter().position(|(name, _)| *name == pass) {
+            Some(index) => index,
+            None => {
+                self.timings.push((pass, PassTiming::default()));
+                self.timings.len() - 1
+            }
+        };
+        let timing = &mut self.timings[index].1;
+        timing.runs += 1;
+        timing.changes += changed as usize;
+        timing.time += time;
+    }
+
+    pub fn add(&mut self, pass: &'static str, counter: &'static str, amount: usize) {
+        *self.counters.entry((pass, counter)).or_default() += amount;
+    }
+
+    pub fn timing(&self, pass: &str) -> Option<&PassTiming> {
+        self.timings.iter().find(|(name, _)| *name == pass).map(|(_, timing)| timing)
+    }
+
+    pub fn counter(&self, pass: &str, counter: &str) -> usize {
+        self.counters.iter().find(|((name, key), _)| *name == pass && *key == counter).map_or(0, |(_, amount)| *amount)
+    }
+
+    pub fn timings(&self) -> impl Iterator<Item = (&'static str, &PassTiming)> {
+        self.timings.iter().map(|(name, timing)| (*name, timing))
+    }
+
+    pub fn counters(&self) -> impl Iterator<Item = (&'static str, &'static str, usize)> + '_ {
+        self.counters.iter().map(|((pass, counter), amount)| (*pass, *counter, *amount))
+    }
+
+    /// A table of the time spent in each pass.
+    pub fn timing_report(&self) -> TimingReport<'_> {
+        TimingReport(self)
+    }
+}
+
+/// Lists the counters, one per line, as `<amount> <pass> - <counter>`.
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.counters.values().map(|amount| amount.to_string().len()).max().unwrap_or(0);
+        for ((pass, counter), amount) in &self.counters {
+            writeln!(f, "{:>width$} {} - {}", amount, pass, counter, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct TimingReport<'a>(&'a Statistics);
+
+impl fmt::Display for TimingReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let total: Duration = self.0.timings.iter().map(|(_, timing)| timing.time).sum();
+        let width = self.0.timings.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("total".len());
+        writeln!(f, "{:<width$}  {:>4}  {:>7}  {:>10}", "pass", "runs", "changed", "time", width = width)?;
+        for (name, timing) in &self.0.timings {
+            let time = format!("{:.3}ms", timing.time.as_secs_f64() * 1000.0);
+            writeln!(f, "{:<width$}  {:>4}  {:>7}  {:>10}", name, timing.runs, timing.changes, time, width = width)?;
+        }
+        let time = format!("{:.3}ms", total.as_secs_f64() * 1000.0);
+        writeln!(f, "{:<width$}  {:>4}  {:>7}  {:>10}", "total", "", "", time, width = width)
+    }
+}
+
+/// The lines of context kept around each change in a diff.
+const CONTEXT: usize = 2;
+
+/// A unified diff of two texts, line by line, with `@@ -l,n +l,n @@`
+/// headers. It is empty when the texts are the same.
+pub fn diff(before: &str, after: &str) -> String {
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let edits = edit_script(&old, &new);
+    if edits.iter().all(|edit| matches!(edit, Edit::Keep(..))) {
+        return String::new();
+    }
+
+    let mut output = String::new();
+    let mut start = 0;
+    while start < edits.len() {
+        // Find the next change, and the hunk around it: it ends where more
+        // than twice the context is unchanged.
+        let Some(first_change) = edits[start..].iter().position(|edit| !matches!(edit, Edit::Keep(..))) else {
+            break;
+        };
+        let first_change = start + first_change;
+        let hunk_start = first_change.saturating_sub(CONTEXT).max(start);
+        let mut hunk_end = first_change;
+        let mut unchanged = 0;
+        for (index, edit) in edits.iter().enumerate().skip(first_change) {
+            if matches!(edit, Edit::Keep(..)) {
+                unchanged += 1;
+                if unchanged > 2 * CONTEXT {
+                    break;
+                }
+            } else {
+                unchanged = 0;
+                hunk_end = index + 1;
+            }
+        }
+        let hunk_end = (hunk_end + CONTEXT).min(edits.len());
+        let hunk = &edits[hunk_start..hunk_end];
+
+        let (old_start, new_start) = match hunk[0] {
+            Edit::Keep(old_line, new_line) => (old_line, new_line),
+            Edit::Delete(old_line, new_line) | Edit::Insert(old_line, new_line) => (old_line, new_line),
+        };
+        let old_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Insert(..))).count();
+        let new_count = hunk.iter().filter(|edit| !matches!(edit, Edit::Delete(..))).count();
+        output += &format!("@@ -{},{} +{},{} @@\n", old_start + 1, old_count, new_start + 1, new_count);
+        for edit in hunk {
+            match *edit {
+                Edit::Keep(old_line, _) => output += &format!(" {}\n", old[old_line]),
+                Edit::Delete(old_line, _) => output += &format!("-{}\n", old[old_line]),
+                Edit::Insert(_, new_line) => output += &format!("+{}\n", new[new_line]),
+            }
+        }
+        start = hunk_end;
+    }
+    output
+}
+
+/// One step from the old lines to the new ones, with the index of the
+/// next old and new line.
+#[derive(Debug, Clone, Copy)]
+enum Edit {
+    Keep(usize, usize),
+    Delete(usize, usize),
+    Insert(usize, usize),
+}
+
+/// The shortest edit script, from a longest common subsequence of the
+/// lines that lie between the common prefix and suffix.
+fn edit_script(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(old_line, new_line)| old_line == new_line).count();
+    let suffix =
+        old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(old_line, new_line)| old_line == new_line).count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // lengths[i][j] is the length of a longest common subsequence of
+    // old_middle[i..] and new_middle[j..].
+    let mut lengths = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|line| Edit::Keep(line, line)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() || j < new_middle.len() {
+        let (old_line, new_line) = (prefix + i, prefix + j);
+        if i < old_middle.len() && j < new_middle.len() && old_middle[i] == new_middle[j] {
+            edits.push(Edit::Keep(old_line, new_line));
+            i += 1;
+            j += 1;
+        } else if j == new_middle.len() || (i < old_middle.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            edits.push(Edit::Delete(old_line, new_line));
+            i += 1;
+        } else {
+            edits.push(Edit::Insert(old_line, new_line));
+            j += 1;
+        }
+    }
+    edits.extend((0..suffix).map(|line| Edit::Keep(old.len() - suffix + line, new.len() - suffix + line)));
+    edits
+}
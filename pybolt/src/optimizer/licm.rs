@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use crate::analysis::dominators::DominatorTree;
+use crate::analysis::loops::{Loop, LoopNest};
+use crate::ir::ir::{Block, Function, InstKind, Memory, Module, Value};
+use crate::optimizer::loops::{inst_blocks, is_invariant};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Loop-invariant code motion. Instructions of a loop that compute the same
+/// value in every iteration move to its preheader, to run once. That is an
+/// instruction whose operands are defined outside the loop, or moved out,
+/// and which:
+/// - writes no memory and prints nothing;
+/// - reads no global that an instruction of the loop may write, which a
+///   call may always do;
+/// - if it may raise, is in the header before anything else there with side
+///   effects, so that it would raise on entering the loop anyway.
+///
+/// Inner loops go first, so that what leaves them can then leave the loops
+/// around them. Loops without a preheader are left alone.
+pub struct LoopInvariantCodeMotion;
+
+impl Optimization for LoopInvariantCodeMotion {
+    fn name(&self) -> &'static str {
+        "licm"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut hoisted = 0;
+        for function in &mut ir.functions {
+            let dominators = context.analyses.dominators(function);
+            let nest = context.analyses.loops(function);
+            // Loops come after the loops containing them.
+            for l in nest.loops().collect::<Vec<_>>().into_iter().rev() {
+                if let Some(preheader) = nest.preheader(function, l) {
+                    hoisted += hoist(function, &dominators, &nest, l, preheader, context);
+                }
+            }
+        }
+        context.count("instructions hoisted", hoisted);
+        Ok(hoisted > 0)
+    }
+}
+
+/// Moves the invariant instructions of the loop to the end of its
+/// preheader. Returns how many were moved.
+fn hoist(
+    function: &mut Function,
+    dominators: &DominatorTree,
+    nest: &LoopNest,
+    l: Loop,
+    preheader: Block,
+    context: &mut PassContext,
+) -> usize {
+    let data = nest.loop_data(l);
+    let blocks_of = inst_blocks(function);
+    let insts: Vec<_> = data.blocks.iter().flat_map(|block| function.block(*block).insts.clone()).collect();
+    // The first instruction of the loop writing each memory, by opcode.
+    let writers: Vec<(Memory, &'static str)> = insts
+        .iter()
+        .map(|inst| (function.effects(*inst).writes, function.inst(*inst).kind.opcode()))
+        .filter(|(writes, _)| *writes != Memory::Nothing)
+        .collect();
+    let writer = |memory: &Memory| writers.iter().find(|(writes, _)| writes.overlaps(memory)).map(|(_, opcode)| *opcode);
+
+    let mut hoisted: HashSet<Value> = HashSet::new();
+    let mut moved = Vec::new();
+    // Reverse postorder visits the definition of an operand before its uses.
+    for block in dominators.reverse_postorder().iter().copied().filter(|block| nest.contains(l, *block)) {
+        let mut side_effects_before = false;
+        for inst in function.block(block).insts.clone() {
+            let effects = function.effects(inst);
+            let data = function.inst(inst);
+            let invariant_operands = data
+                .kind
+                .operands()
+                .into_iter()
+                .all(|operand| hoisted.contains(&operand) || is_invariant(function, &blocks_of, nest, l, operand));
+            let Some(result) = data.result.filter(|_| invariant_operands) else {
+                side_effects_before |= effects.has_side_effects();
+                continue;
+            };
+            let blocker = if effects.writes != Memory::Nothing || effects.prints || effects.allocates {
+                Some(None)
+            } else if effects.reads != Memory::Nothing {
+                writer(&effects.reads).map(|opcode| Some(format!("a `{}` in the loop may change it", opcode)))
+            } else if effects.may_raise && (block != nest.loop_data(l).header || side_effects_before) {
+                Some(Some("it may raise, where the loop would not have".to_string()))
+            } else {
+                None
+            };
+            match blocker {
+                None => {
+                    // Constants move out of every loop; they are not worth a remark.
+                    if !matches!(data.kind, InstKind::Const(_)) {
+                        let message = format!("hoisted `{}` out of the loop", data.kind.opcode());
+                        context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                    }
+                    hoisted.insert(result);
+                    moved.push(inst);
+                }
+                Some(reason) => {
+                    if let Some(reason) = reason {
+                        let message = format!("could not hoist `{}` out of the loop: {}", data.kind.opcode(), reason);
+                        context.remark(RemarkKind::Missed, &function.name, data.span, message);
+                    }
+                    side_effects_before |= effects.has_side_effects();
+                }
+            }
+        }
+    }
+    for block in &data.blocks {
+        function.block_mut(*block).insts.retain(|inst| !moved.contains(inst));
+    }
+    function.block_mut(preheader).insts.extend(&moved);
+    moved.len()
+}
@@ -0,0 +1,81 @@
+use crate::analysis::loops::{Loop, LoopNest};
+use crate::ir::ir::{Block, BlockCall, Function, Module, Value};
+use crate::optimizer::loops::{arguments_to, counted_loop, inst_blocks, is_invariant, values_used_outside};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Loop deletion. A loop without side effects, none of whose values is used
+/// after it, only takes time, and is removed when it is known to end: it is
+/// a counted loop, whose counter reaches its bound, and contains no other
+/// loop. Its preheader then branches straight to its exit. A loop that may
+/// raise, such as one dividing, has side effects and stays.
+pub struct LoopDeletion;
+
+impl Optimization for LoopDeletion {
+    fn name(&self) -> &'static str {
+        "loop-deletion"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut deleted = 0;
+        for function in &mut ir.functions {
+            // Deleting a loop changes the others; find them again after each.
+            loop {
+                let nest = context.analyses.loops(function);
+                let Some((preheader, exit)) = nest.loops().find_map(|l| deletion(function, &nest, l, context)) else {
+                    break;
+                };
+                if let Some(terminator) = &mut function.block_mut(preheader).terminator {
+                    for target in terminator.successors_mut() {
+                        *target = exit.clone();
+                    }
+                }
+                function.remove_unreachable_blocks();
+                context.analyses.invalidate(&function.name);
+                deleted += 1;
+            }
+        }
+        context.count("loops deleted", deleted);
+        Ok(deleted > 0)
+    }
+}
+
+/// The preheader of the loop and the branch that replaces its branch to
+/// the header, if the loop can be deleted.
+fn deletion(function: &Function, nest: &LoopNest, l: Loop, context: &mut PassContext) -> Option<(Block, BlockCall)> {
+    let data = nest.loop_data(l);
+    let preheader = nest.preheader(function, l)?;
+    let blocks_of = inst_blocks(function);
+    let has_effects = data.blocks.iter().flat_map(|block| &function.block(*block).insts).any(|inst| function.has_side_effects(*inst));
+    if has_effects || !values_used_outside(function, &blocks_of, &data.blocks).is_empty() {
+        return None;
+    }
+    // Every exit must go to the same block, with the same arguments from
+    // outside the loop.
+    let mut exit: Option<BlockCall> = None;
+    for (block, target) in nest.exits(function, l) {
+        let arguments: Vec<Value> = arguments_to(function, block, target)?;
+        if !arguments.iter().all(|argument| is_invariant(function, &blocks_of, nest, l, *argument)) {
+            return None;
+        }
+        let call = BlockCall { block: target, arguments };
+        match &exit {
+            Some(other) if *other != call => return None,
+            _ => exit = Some(call),
+        }
+    }
+    let exit = exit?;
+
+    let counted = counted_loop(function, &blocks_of, nest, l, preheader);
+    let span = match &counted {
+        Some(counted) => function.inst(counted.compare).span,
+        None => function.block(data.header).insts.first().map(|inst| function.inst(*inst).span).unwrap_or_default(),
+    };
+    if !data.children.is_empty() || !counted.is_some_and(|counted| counted.terminates(function)) {
+        let message = "did not delete the loop, which has no effect: it may not end";
+        context.remark(RemarkKind::Missed, &function.name, span, message);
+        return None;
+    }
+    context.remark(RemarkKind::Passed, &function.name, span, "deleted a loop that has no effect");
+    Some((preheader, exit))
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use crate::analysis::loops::{Loop, LoopNest};
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Constant, Function, Inst, InstKind, Terminator, Type, Value, ValueDef};
+
+/// The block of each instruction of the layout.
+pub(crate) fn inst_blocks(function: &Function) -> HashMap<Inst, Block> {
+    let mut blocks = HashMap::new();
+    for block in &function.layout {
+        for inst in &function.block(*block).insts {
+            blocks.insert(*inst, *block);
+        }
+    }
+    blocks
+}
+
+/// The block defining `value`, if it is in the layout.
+pub(crate) fn defining_block(function: &Function, inst_blocks: &HashMap<Inst, Block>, value: Value) -> Option<Block> {
+    match function.value(value).def {
+        ValueDef::Param(block) => Some(block),
+        ValueDef::Result(inst) => inst_blocks.get(&inst).copied(),
+    }
+}
+
+/// Whether `value` is defined outside the loop, so that it is the same in
+/// every iteration.
+pub(crate) fn is_invariant(function: &Function, inst_blocks: &HashMap<Inst, Block>, nest: &LoopNest, l: Loop, value: Value) -> bool {
+    defining_block(function, inst_blocks, value).is_some_and(|block| !nest.contains(l, block))
+}
+
+/// The value of `value` when it is an integer constant.
+pub(crate) fn int_constant(function: &Function, value: Value) -> Option<i64> {
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return None;
+    };
+    match function.inst(inst).kind {
+        InstKind::Const(Constant::Int(value)) => Some(value),
+        _ => None,
+    }
+}
+
+/// An `int` that is known to be an `i64`.
+pub(crate) enum I64Operand {
+    Constant(i64),
+    /// Cast from the `i64`.
+    Cast(Value),
+}
+
+pub(crate) fn i64_operand(function: &Function, value: Value) -> Option<I64Operand> {
+    if let Some(constant) = int_constant(function, value) {
+        return Some(I64Operand::Constant(constant));
+    }
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return None;
+    };
+    match function.inst(inst).kind {
+        InstKind::Cast(operand) if function.value_type(operand) == Type::I64 => Some(I64Operand::Cast(operand)),
+        _ => None,
+    }
+}
+
+/// The `i64` for an `I64Operand`. A constant is made anew for `like` and
+/// added to `insts`, for the caller to place.
+pub(crate) fn to_i64(function: &mut Function, operand: I64Operand, like: Inst, insts: &mut Vec<Inst>) -> Value {
+    match operand {
+        I64Operand::Cast(value) => value,
+        I64Operand::Constant(constant) => {
+            let inst = function.make_inst_like(like, InstKind::Const(Constant::Int(constant)), Some(Type::I64));
+            insts.push(inst);
+            function.result(inst).expect("constants have a result")
+        }
+    }
+}
+
+/// The arguments `block` passes to `target`.
+pub(crate) fn arguments_to(function: &Function, block: Block, target: Block) -> Option<Vec<Value>> {
+    let terminator = function.terminator(block)?;
+    terminator.successors().into_iter().find(|call| call.block == target).map(|call| call.arguments.clone())
+}
+
+/// The values defined in the blocks of `blocks` that are used outside them.
+pub(crate) fn values_used_outside(function: &Function, inst_blocks: &HashMap<Inst, Block>, blocks: &[Block]) -> Vec<Value> {
+    let mut used = Vec::new();
+    for block in function.layout.iter().filter(|block| !blocks.contains(block)) {
+        let data = function.block(*block);
+        let operands = data.insts.iter().flat_map(|inst| function.inst(*inst).kind.operands());
+        for operand in operands.chain(data.terminator.iter().flat_map(Terminator::operands)) {
+            let defined_inside = defining_block(function, inst_blocks, operand).is_some_and(|def| blocks.contains(&def));
+            if defined_inside && !used.contains(&operand) {
+                used.push(operand);
+            }
+        }
+    }
+    used
+}
+
+/// A basic induction variable: a parameter of the header of a loop that
+/// starts at the value the preheader passes and grows by the same constant
+/// on every back edge, where the latch passes `add param, step`.
+#[derive(Debug, Clone)]
+pub(crate) struct InductionVariable {
+    pub param: Value,
+    /// The position of the parameter in the header.
+    pub index: usize,
+    pub start: Value,
+    pub step: i64,
+}
+
+/// The basic induction variables of the loop, an `int` or `i64` each.
+pub(crate) fn induction_variables(function: &Function, nest: &LoopNest, l: Loop, preheader: Block) -> Vec<InductionVariable> {
+    let data = nest.loop_data(l);
+    let Some(starts) = arguments_to(function, preheader, data.header) else {
+        return Vec::new();
+    };
+    let mut variables = Vec::new();
+    for (index, param) in function.block(data.header).params.iter().copied().enumerate() {
+        if !matches!(function.value_type(param), Type::Int | Type::I64) {
+            continue;
+        }
+        let mut steps = data.latches.iter().map(|latch| {
+            let next = arguments_to(function, *latch, data.header)?[index];
+            increment(function, next, param)
+        });
+        let Some(Some(step)) = steps.next() else {
+            continue;
+        };
+        if steps.all(|other| other == Some(step)) && step != 0 {
+            variables.push(InductionVariable { param, index, start: starts[index], step });
+        }
+    }
+    variables
+}
+
+/// The constant `value` adds to `param`, when it is `param` plus or minus
+/// a constant.
+fn increment(function: &Function, value: Value, param: Value) -> Option<i64> {
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return None;
+    };
+    match function.inst(inst).kind {
+        InstKind::Binary { op: BinaryOp::Add, left, right } if left == param => int_constant(function, right),
+        InstKind::Binary { op: BinaryOp::Add, left, right } if right == param => int_constant(function, left),
+        InstKind::Binary { op: BinaryOp::Sub, left, right } if left == param => int_constant(function, right)?.checked_neg(),
+        _ => None,
+    }
+}
+
+/// A loop whose header runs it while an induction variable has not passed
+/// a loop-invariant bound, as the loops of `for i in range(...)` do: the
+/// header ends with `cond_br (lt iv, bound), body, exit`, or `gt` when the
+/// variable goes down.
+#[derive(Debug, Clone)]
+pub(crate) struct CountedLoop {
+    pub iv: InductionVariable,
+    /// The comparison of the header.
+    pub compare: Inst,
+    pub op: CompareOp,
+    pub bound: Value,
+    pub exit: Block,
+}
+
+pub(crate) fn counted_loop(
+    function: &Function,
+    inst_blocks: &HashMap<Inst, Block>,
+    nest: &LoopNest,
+    l: Loop,
+    preheader: Block,
+) -> Option<CountedLoop> {
+    let header = nest.loop_data(l).header;
+    let Some(Terminator::CondBr { condition, then, otherwise }) = function.terminator(header) else {
+        return None;
+    };
+    if !nest.contains(l, then.block) || nest.contains(l, otherwise.block) {
+        return None;
+    }
+    let ValueDef::Result(compare) = function.value(*condition).def else {
+        return None;
+    };
+    let InstKind::Compare { op, left, right } = function.inst(compare).kind else {
+        return None;
+    };
+    let (op, param, bound) = match (op, left, right) {
+        (op, param, bound) if is_invariant(function, inst_blocks, nest, l, bound) => (op, param, bound),
+        (op, bound, param) if is_invariant(function, inst_blocks, nest, l, bound) => (mirrored(op), param, bound),
+        _ => return None,
+    };
+    let iv = induction_variables(function, nest, l, preheader).into_iter().find(|iv| iv.param == param)?;
+    let towards_bound = match op {
+        CompareOp::Lt | CompareOp::Le => iv.step > 0,
+        CompareOp::Gt | CompareOp::Ge => iv.step < 0,
+        CompareOp::Eq | CompareOp::Ne => false,
+    };
+    if !towards_bound || function.value_type(bound) != function.value_type(param) {
+        return None;
+    }
+    Some(CountedLoop { iv, compare, op, bound, exit: otherwise.block })
+}
+
+/// The comparison with its operands swapped.
+fn mirrored(op: CompareOp) -> CompareOp {
+    match op {
+        CompareOp::Lt => CompareOp::Gt,
+        CompareOp::Le => CompareOp::Ge,
+        CompareOp::Gt => CompareOp::Lt,
+        CompareOp::Ge => CompareOp::Le,
+        op => op,
+    }
+}
+
+impl CountedLoop {
+    /// The number of times the header lets the loop run, when the start
+    /// and the bound are constants. A `break` may end it sooner.
+    pub fn trip_count(&self, function: &Function) -> Option<i128> {
+        let start = int_constant(function, self.iv.start)? as i128;
+        let bound = int_constant(function, self.bound)? as i128;
+        let step = self.iv.step.unsigned_abs() as i128;
+        let distance = match self.op {
+            CompareOp::Lt => bound - start,
+            CompareOp::Le => bound - start + 1,
+            CompareOp::Gt => start - bound,
+            CompareOp::Ge => start - bound + 1,
+            CompareOp::Eq | CompareOp::Ne => return None,
+        };
+        Some(if distance <= 0 { 0 } else { (distance + step - 1) / step })
+    }
+
+    /// The values the induction variable takes, the one that ends the loop
+    /// included, when the start and the bound are constants.
+    pub fn range(&self, function: &Function) -> Option<(i128, i128)> {
+        let start = int_constant(function, self.iv.start)? as i128;
+        let last = start + self.trip_count(function)? * self.iv.step as i128;
+        Some((start.min(last), start.max(last)))
+    }
+
+    /// Whether the loop ends: an `int` never wraps around, so it reaches
+    /// the bound, and an `i64` does when it cannot go past the largest or
+    /// smallest `i64` before.
+    pub fn terminates(&self, function: &Function) -> bool {
+        if function.value_type(self.iv.param) == Type::Int {
+            return true;
+        }
+        let fits = self.range(function).is_some_and(|(low, high)| low >= i64::MIN as i128 && high <= i64::MAX as i128);
+        fits || (matches!(self.op, CompareOp::Lt | CompareOp::Gt) && self.iv.step.abs() == 1)
+    }
+}
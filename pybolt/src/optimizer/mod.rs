@@ -0,0 +1,16 @@
+pub mod escape;
+pub mod gvn;
+pub mod induction;
+pub mod inliner;
+pub mod instrumentation;
+pub mod licm;
+pub mod loop_deletion;
+pub mod loops;
+pub mod narrowing;
+pub mod optimizer;
+pub mod remarks;
+pub mod sccp;
+pub mod specialize;
+pub mod sroa;
+pub mod unboxing;
+pub mod unroll;
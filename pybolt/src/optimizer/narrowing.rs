@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+
+use crate::analysis::ranges::ValueRanges;
+use crate::ir::ir::{BinaryOp, Constant, Function, Inst, InstKind, Module, Type, UnaryOp, Value};
+use crate::optimizer::loops::{i64_operand, to_i64, I64Operand};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Range narrowing, driven by value-range analysis. An `int` operation
+/// other than `/` whose operands are `i64`s, such as a loop counter that
+/// `indvars` simplified and constants, is computed on `i64`s, without the
+/// overflow check of an `int`, when its result is known to fit; what uses
+/// it sees a `cast` of the `i64` to `int`, so a chain of operations
+/// narrows one after the other. A comparison of integers whose intervals
+/// decide it, as a test of a loop counter inside the loop often is,
+/// becomes a constant. A `check_index` whose index is known not to be
+/// negative and to be below the length, by its interval or by the branch
+/// of a loop over `range(len(xs))`, is removed for the index itself.
+pub struct RangeNarrowing;
+
+impl Optimization for RangeNarrowing {
+    fn name(&self) -> &'static str {
+        "range-narrowing"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let (mut narrowed, mut decided, mut checked) = (0, 0, 0);
+        for function in &mut ir.functions {
+            let ranges = context.analyses.ranges(function);
+            let (function_narrowed, function_decided, function_checked) = narrow_function(function, &ranges, context);
+            if function_narrowed + function_decided + function_checked > 0 {
+                context.analyses.invalidate(&function.name);
+            }
+            narrowed += function_narrowed;
+            decided += function_decided;
+            checked += function_checked;
+        }
+        context.count("overflow checks removed", narrowed);
+        context.count("comparisons decided", decided);
+        context.count("bounds checks removed", checked);
+        Ok(narrowed + decided + checked > 0)
+    }
+}
+
+/// Returns the number of operations computed on `i64`s, of comparisons
+/// replaced by constants and of bounds checks removed.
+fn narrow_function(function: &mut Function, ranges: &ValueRanges, context: &mut PassContext) -> (usize, usize, usize) {
+    // Dead instructions, such as the `int` counter `indvars` replaced, are
+    // left to `dce` rather than narrowed.
+    let mut used: HashSet<Value> = HashSet::new();
+    for block in &function.layout {
+        for inst in &function.block(*block).insts {
+            used.extend(function.inst(*inst).kind.operands());
+        }
+        used.extend(function.terminator(*block).iter().flat_map(|terminator| terminator.operands()));
+    }
+    let (mut narrowed, mut decided, mut checked) = (0, 0, 0);
+    for block in function.layout.clone() {
+        let mut index = 0;
+        while index < function.block(block).insts.len() {
+            let inst = function.block(block).insts[index];
+            let data = function.inst(inst).clone();
+            if !data.result.is_some_and(|result| used.contains(&result)) {
+                index += 1;
+                continue;
+            }
+            let opcode = data.kind.opcode();
+            if let (InstKind::CheckIndex { index: position, length }, Some(result)) = (&data.kind, data.result) {
+                let in_bounds = match (ranges.interval_in(*position, block), ranges.interval_in(*length, block)) {
+                    (Some(position_interval), Some(length_interval)) if position_interval.low >= 0 => {
+                        position_interval.high < length_interval.low || ranges.is_less_than(*position, *length, block)
+                    }
+                    _ => false,
+                };
+                if in_bounds {
+                    function.replace_uses(result, *position);
+                    function.block_mut(block).insts.remove(index);
+                    let message = "removed `check_index`: the index is always in bounds".to_string();
+                    context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                    checked += 1;
+                    continue;
+                }
+                let message = "kept `check_index`: the index may be negative or beyond the length".to_string();
+                context.remark(RemarkKind::Missed, &function.name, data.span, message);
+            }
+            let mut added = Vec::new();
+            let operands = match data.kind {
+                InstKind::Binary { op: BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::FloorDiv | BinaryOp::Mod, left, right } => vec![left, right],
+                InstKind::Unary { op: UnaryOp::Neg, operand } => vec![operand],
+                InstKind::Compare { op, left, right } => {
+                    let decision = match (ranges.interval_in(left, block), ranges.interval_in(right, block)) {
+                        (Some(left), Some(right)) => left.compare(op, &right),
+                        _ => None,
+                    };
+                    if let Some(value) = decision {
+                        function.inst_mut(inst).kind = InstKind::Const(Constant::Bool(value));
+                        let message = format!("removed `{}` that is always {}", opcode, value);
+                        context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                        decided += 1;
+                    }
+                    Vec::new()
+                }
+                _ => Vec::new(),
+            };
+            let result = data.result.filter(|result| function.value_type(*result) == Type::Int);
+            let narrowed_operands: Option<Vec<I64Operand>> = operands.iter().map(|operand| i64_operand(function, *operand)).collect();
+            if let (Some(result), Some(narrowed_operands)) = (result, narrowed_operands.filter(|operands| !operands.is_empty())) {
+                match ranges.interval(result) {
+                    Some(interval) if interval.fits_i64() => {
+                        let mut kind = data.kind.clone();
+                        for (operand, narrowed_operand) in kind.operands_mut().into_iter().zip(narrowed_operands) {
+                            *operand = to_i64(function, narrowed_operand, inst, &mut added);
+                        }
+                        let computed = push(function, kind, inst, &mut added);
+                        function.inst_mut(inst).kind = InstKind::Cast(computed);
+                        let message = format!("computed `{}` on `i64`s: its result is between {} and {}", opcode, interval.low, interval.high);
+                        context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                        narrowed += 1;
+                    }
+                    Some(_) => {
+                        let message = format!("kept the overflow check of `{}`: its result may not fit in 64 bits", opcode);
+                        context.remark(RemarkKind::Missed, &function.name, data.span, message);
+                    }
+                    None => {}
+                }
+            }
+            index += added.len() + 1;
+            function.block_mut(block).insts.splice(index - 1 - added.len()..index - 1 - added.len(), added);
+        }
+    }
+    (narrowed, decided, checked)
+}
+
+/// Makes an `i64` instruction for `like`, added to `added`, and returns its
+/// result.
+fn push(function: &mut Function, kind: InstKind, like: Inst, added: &mut Vec<Inst>) -> Value {
+    let inst = function.make_inst_like(like, kind, Some(Type::I64));
+    added.push(inst);
+    function.result(inst).expect("the instruction has a result")
+}
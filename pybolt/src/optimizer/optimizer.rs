@@ -1,86 +1,630 @@
-use crate::ir::ir::{IR as IRNode, IRGenerator};
 use std::collections::HashMap;
+use std::collections::HashSet;
 
+use std::time::Instant;
+
+use log::{debug, info};
+
+use crate::analysis::analysis::AnalysisManager;
+use crate::ir::ir::{BinaryOp, Class, CompareOp, Constant, Function, InstKind, Module, Type, UnaryOp, Value};
+use crate::ir::verifier::verify;
+use crate::lexer::lexer::Span;
+use crate::optimizer::gvn::GlobalValueNumbering;
+use crate::optimizer::induction::{InductionVariableSimplification, StrengthReduction};
+use crate::optimizer::inliner::Inliner;
+use crate::optimizer::instrumentation::{diff, Instrumentation, Statistics};
+use crate::optimizer::licm::LoopInvariantCodeMotion;
+use crate::optimizer::loop_deletion::LoopDeletion;
+use crate::optimizer::narrowing::RangeNarrowing;
+use crate::optimizer::remarks::{Remark, RemarkKind};
+use crate::optimizer::sccp::Sccp;
+use crate::optimizer::specialize::TypeSpecialization;
+use crate::optimizer::sroa::ScalarReplacement;
+use crate::optimizer::unboxing::Unboxing;
+use crate::optimizer::unroll::LoopUnrolling;
+use crate::runtime::runtime::{self, Arithmetic, Comparison, Value as RuntimeValue};
+
+/// How many times a pipeline is run at most while it keeps changing the IR.
+pub const MAX_ITERATIONS: usize = 10;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum OptLevel {
+    /// No optimization.
+    O0,
+    /// The cheaper passes, each run once.
+    O1,
+    /// The pipeline runs until it no longer changes the IR.
+    #[default]
+    O2,
+    /// As `O2`, for passes that may make the code larger to make it faster.
+    O3,
+    /// As `O2`, favouring small code.
+    Os,
+}
+
+impl OptLevel {
+    /// The names of the passes the level runs, in order. Only `O3`
+    /// unrolls loops, and `Os` does not specialize functions, which copies
+    /// them.
+    pub fn pipeline(self) -> Vec<&'static str> {
+        match self {
+            OptLevel::O0 => vec![],
+            OptLevel::O1 => vec!["sccp", "dead-code-elimination"],
+            OptLevel::O2 => vec![
+                "specialize",
+                "inline",
+                "sroa",
+                "unbox",
+                "sccp",
+                "gvn",
+                "licm",
+                "strength-reduction",
+                "indvars",
+                "range-narrowing",
+                "loop-deletion",
+                "dead-code-elimination",
+            ],
+            OptLevel::Os => vec![
+                "inline",
+                "sroa",
+                "unbox",
+                "sccp",
+                "gvn",
+                "licm",
+                "strength-reduction",
+                "indvars",
+                "range-narrowing",
+                "loop-deletion",
+                "dead-code-elimination",
+            ],
+            OptLevel::O3 => vec![
+                "specialize",
+                "inline",
+                "sroa",
+                "unbox",
+                "sccp",
+                "gvn",
+                "licm",
+                "strength-reduction",
+                "indvars",
+                "range-narrowing",
+                "loop-unroll",
+                "loop-deletion",
+                "dead-code-elimination",
+            ],
+        }
+    }
+
+    /// The inliner of the level: `O3` inlines larger functions, and
+    /// unrolls recursion once, and `Os` only functions about the size of
+    /// a call.
+    pub fn inliner(self) -> Inliner {
+        match self {
+            OptLevel::O3 => Inliner { threshold: 3 * Inliner::DEFAULT_THRESHOLD, recursion_limit: 1 },
+            OptLevel::Os => Inliner { threshold: 2, ..Inliner::new() },
+            _ => Inliner::new(),
+        }
+    }
+
+    pub fn max_iterations(self) -> usize {
+        match self {
+            OptLevel::O0 | OptLevel::O1 => 1,
+            OptLevel::O2 | OptLevel::O3 | OptLevel::Os => MAX_ITERATIONS,
+        }
+    }
+}
+
+pub struct PassInfo {
+    pub name: &'static str,
+    pub short_name: &'static str,
+    pub description: &'static str,
+    pub create: fn() -> Box<dyn Optimization>,
+}
+
+pub const PASSES: &[PassInfo] = &[
+    PassInfo {
+        name: "constant-folding",
+        short_name: "fold",
+        description: "replaces operations on constants by their result",
+        create: || Box::new(ConstantFolding),
+    },
+    PassInfo {
+        name: "inline",
+        short_name: "inline",
+        description: "replaces calls of small functions, and functions called once, by their body",
+        create: || Box::new(Inliner::new()),
+    },
+    PassInfo {
+        name: "specialize",
+        short_name: "specialize",
+        description: "copies functions without annotations for the int and float arguments of their calls",
+        create: || Box::new(TypeSpecialization),
+    },
+    PassInfo {
+        name: "sroa",
+        short_name: "sroa",
+        description: "replaces tuples that do not escape their function by their items",
+        create: || Box::new(ScalarReplacement),
+    },
+    PassInfo {
+        name: "unbox",
+        short_name: "unbox",
+        description: "computes on the unboxed values of boxed ints, floats and bools",
+        create: || Box::new(Unboxing),
+    },
+    PassInfo {
+        name: "sccp",
+        short_name: "sccp",
+        description: "propagates constants through variables and branches, and deletes unreachable blocks",
+        create: || Box::new(Sccp),
+    },
+    PassInfo {
+        name: "gvn",
+        short_name: "gvn",
+        description: "removes computations and global loads that repeat an earlier one",
+        create: || Box::new(GlobalValueNumbering),
+    },
+    PassInfo {
+        name: "licm",
+        short_name: "licm",
+        description: "moves computations that are the same in every iteration of a loop before it",
+        create: || Box::new(LoopInvariantCodeMotion),
+    },
+    PassInfo {
+        name: "strength-reduction",
+        short_name: "sr",
+        description: "replaces multiplications of loop counters by additions in each iteration",
+        create: || Box::new(StrengthReduction),
+    },
+    PassInfo {
+        name: "indvars",
+        short_name: "indvars",
+        description: "turns the int counters of range loops into plain 64-bit integers",
+        create: || Box::new(InductionVariableSimplification),
+    },
+    PassInfo {
+        name: "range-narrowing",
+        short_name: "narrow",
+        description: "removes int overflow checks and comparisons that the ranges of values make unnecessary",
+        create: || Box::new(RangeNarrowing),
+    },
+    PassInfo {
+        name: "loop-unroll",
+        short_name: "unroll",
+        description: "replaces loops that run a few times by a copy of their body for each time",
+        create: || Box::new(LoopUnrolling::new()),
+    },
+    PassInfo {
+        name: "loop-deletion",
+        short_name: "loop-delete",
+        description: "removes loops without side effects whose results are unused",
+        create: || Box::new(LoopDeletion),
+    },
+    PassInfo {
+        name: "dead-code-elimination",
+        short_name: "dce",
+        description: "removes unused instructions without side effects",
+        create: || Box::new(DeadCodeElimination),
+    },
+];
+
+/// Looks a pass up by name (`dead-code-elimination`) or short name (`dce`).
+pub fn pass(name: &str) -> Option<&'static PassInfo> {
+    PASSES.iter().find(|pass| pass.name == name || pass.short_name == name)
+}
+
+fn create_pass(name: &str) -> Result<Box<dyn Optimization>, String> {
+    pass(name).map(|pass| (pass.create)()).ok_or_else(|| format!("Unknown optimization pass '{}'", name))
+}
+
+/// What a pass is given besides the module: the cached analyses, the
+/// statistics to count what it does in, and the remarks it makes.
+#[derive(Debug, Default)]
+pub struct PassContext {
+    pub analyses: AnalysisManager,
+    pub statistics: Statistics,
+    pub remarks: Vec<Remark>,
+    /// The pass that is running.
+    pass: &'static str,
+}
+
+impl PassContext {
+    pub fn new() -> Self {
+        PassContext::default()
+    }
+
+    /// Adds `amount` to a counter of the running pass, such as
+    /// "constants folded".
+    pub fn count(&mut self, counter: &'static str, amount: usize) {
+        if amount > 0 {
+            self.statistics.add(self.pass, counter, amount);
+        }
+    }
+
+    /// Records a remark of the running pass about `function`. A pipeline
+    /// runs passes repeatedly, so a remark made before is not repeated.
+    pub fn remark(&mut self, kind: RemarkKind, function: &str, span: Span, message: impl Into<String>) {
+        let remark = Remark { kind, pass: self.pass, function: function.to_string(), span, message: message.into() };
+        if !self.remarks.contains(&remark) {
+            self.remarks.push(remark);
+        }
+    }
+}
+
+/// The pass manager. It runs a pipeline of passes over a module, again and
+/// again until no pass changes it or `max_iterations` runs are done, and
+/// keeps the analyses of each function until a pass changes the module.
 pub struct Optimizer {
     optimizations: Vec<Box<dyn Optimization>>,
+    max_iterations: usize,
+    instrumentation: Instrumentation,
+    context: PassContext,
+}
+
+impl Default for Optimizer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Optimizer {
     pub fn new() -> Self {
-        Optimizer {
-            optimizations: vec![Box::new(ConstantFolding), Box::new(DeadCodeElimination)],
+        Self::with_level(OptLevel::default())
+    }
+
+    pub fn with_level(level: OptLevel) -> Self {
+        let optimizations = level.pipeline().into_iter().map(|name| create_pass(name).expect("levels name known passes")).collect();
+        let mut optimizer = Self::with_iterations(optimizations, level.max_iterations());
+        optimizer.set_inliner(level.inliner());
+        optimizer
+    }
+
+    /// An optimizer running the passes named in `pipeline`, separated by
+    /// commas (`fold,dce`), until they no longer change the IR.
+    pub fn with_pipeline(pipeline: &str) -> Result<Self, String> {
+        let optimizations = pipeline
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(create_pass)
+            .collect::<Result<_, _>>()?;
+        Ok(Self::with_iterations(optimizations, MAX_ITERATIONS))
+    }
+
+    /// An optimizer running the given passes once, in order.
+    pub fn with_optimizations(optimizations: Vec<Box<dyn Optimization>>) -> Self {
+        Self::with_iterations(optimizations, 1)
+    }
+
+    fn with_iterations(optimizations: Vec<Box<dyn Optimization>>, max_iterations: usize) -> Self {
+        Optimizer { optimizations, max_iterations, instrumentation: Instrumentation::default(), context: PassContext::new() }
+    }
+
+    pub fn set_max_iterations(&mut self, max_iterations: usize) {
+        self.max_iterations = max_iterations;
+    }
+
+    pub fn set_instrumentation(&mut self, instrumentation: Instrumentation) {
+        self.instrumentation = instrumentation;
+    }
+
+    /// Replaces the inliner of the pipeline, if it has one, to change its
+    /// threshold or recursion limit.
+    pub fn set_inliner(&mut self, inliner: Inliner) {
+        if let Some(optimization) = self.optimizations.iter_mut().find(|optimization| optimization.name() == inliner.name()) {
+            *optimization = Box::new(inliner);
         }
     }
 
-    pub fn optimize(&mut self, ir: Vec<IRNode>) -> Result<Vec<IRNode>, String> {
+    /// Adds the pass, given by name or short name, at the end of the
+    /// pipeline unless it is already in it.
+    pub fn enable(&mut self, name: &str) -> Result<(), String> {
+        let pass = create_pass(name)?;
+        if !self.optimizations.iter().any(|optimization| optimization.name() == pass.name()) {
+            self.optimizations.push(pass);
+        }
+        Ok(())
+    }
+
+    /// Removes the pass, given by name or short name, from the pipeline.
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        let pass = create_pass(name)?;
+        self.optimizations.retain(|optimization| optimization.name() != pass.name());
+        Ok(())
+    }
+
+    /// The names of the passes in the pipeline, in order.
+    pub fn pipeline(&self) -> Vec<&'static str> {
+        self.optimizations.iter().map(|optimization| optimization.name()).collect()
+    }
+
+    /// The time spent in each pass and the counters of the passes, over
+    /// every module optimized so far.
+    pub fn statistics(&self) -> &Statistics {
+        &self.context.statistics
+    }
+
+    /// The remarks the passes made, over every module optimized so far.
+    pub fn remarks(&self) -> &[Remark] {
+        &self.context.remarks
+    }
+
+    /// Runs the pipeline until it reaches a fixed point. In debug builds
+    /// the IR is verified before the first pass and after each one that
+    /// changed it, so that a pass that breaks it is caught straight away.
+    pub fn optimize(&mut self, ir: Module) -> Result<Module, String> {
         let mut optimized_ir = ir;
+        if cfg!(debug_assertions) {
+            verify(&optimized_ir).map_err(|error| format!("Invalid IR before optimization:\n{}", error))?;
+        }
 
-        for optimization in &self.optimizations {
-            optimized_ir = optimization.apply(optimized_ir)?;
+        let mut iterations = 0;
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let mut changed = false;
+            for index in 0..self.optimizations.len() {
+                changed |= self.run(index, &mut optimized_ir)?;
+            }
+            if !changed {
+                break;
+            }
         }
 
-        println!("Optimization completed.");
+        if self.instrumentation.time_passes {
+            info!("Pass execution times:\n{}", self.context.statistics.timing_report());
+        }
+        if self.instrumentation.statistics {
+            info!("Pass statistics:\n{}", self.context.statistics);
+        }
+        debug!("Optimization completed after {} iteration(s)", iterations);
         Ok(optimized_ir)
     }
+
+    /// Runs a pass of the pipeline, timing it and dumping the IR around it
+    /// as asked.
+    fn run(&mut self, index: usize, ir: &mut Module) -> Result<bool, String> {
+        let optimization = &self.optimizations[index];
+        let name = optimization.name();
+        if self.instrumentation.print_before.includes(name) {
+            info!("IR before {}:\n{}", name, ir);
+        }
+        let before = self.instrumentation.print_diff.includes(name).then(|| ir.to_string());
+
+        self.context.pass = name;
+        let start = Instant::now();
+        let changed = optimization.apply(ir, &mut self.context)?;
+        let time = start.elapsed();
+        self.context.statistics.record_run(name, changed, time);
+        debug!("{} {} the IR in {:?}", name, if changed { "changed" } else { "did not change" }, time);
+
+        if changed {
+            self.context.analyses.invalidate_all();
+            if cfg!(debug_assertions) {
+                verify(ir).map_err(|error| format!("Invalid IR after {}:\n{}", name, error))?;
+            }
+            if let Some(before) = before {
+                info!("IR changes by {}:\n{}", name, diff(&before, &ir.to_string()));
+            }
+        }
+        if self.instrumentation.print_after.includes(name) {
+            info!("IR after {}:\n{}", name, ir);
+        }
+        Ok(changed)
+    }
 }
 
 pub trait Optimization {
-    fn apply(&self, ir: Vec<IRNode>) -> Result<Vec<IRNode>, String>;
+    /// The name of the pass, in kebab case.
+    fn name(&self) -> &'static str;
+
+    /// Transforms the module and returns whether it changed anything. The
+    /// analyses in `context` describe the module as it was given; a pass
+    /// that changes a function and then asks for its analyses again must
+    /// invalidate them first. All of them are invalidated after a pass
+    /// that changed the module.
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String>;
 }
 
+/// Replaces operations on constants by their result. Operations that would
+/// raise at runtime, such as a division by zero, are left alone.
 pub struct ConstantFolding;
 
 impl Optimization for ConstantFolding {
-    fn apply(&self, ir: Vec<IRNode>) -> Result<Vec<IRNode>, String> {
-        let mut new_ir = Vec::new();
-
-        for instruction in ir {
-            match instruction {
-                IRNode::BinaryOperation { left, operator, right } => {
-                    if let (IRNode::Immediate { value: left_val, .. }, IRNode::Immediate { value: right_val, .. }) = (*left, *right) {
-                        let result = match operator.as_str() {
-                            "+" => left_val + right_val,
-                            "-" => left_val - right_val,
-                            "*" => left_val * right_val,
-                            "/" => left_val / right_val,
-                            _ => return Err(format!("Unknown operator: {}", operator)),
-                        };
-                        new_ir.push(IRNode::Immediate { value: result, dest: "temp".to_string() });
-                    } else {
-                        new_ir.push(IRNode::BinaryOperation { left, operator, right });
-                    }
-                }
-                _ => new_ir.push(instruction),
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut folded = 0;
+        for function in &mut ir.functions {
+            folded += fold_constants(function, context);
+        }
+        context.count("constants folded", folded);
+        Ok(folded > 0)
+    }
+}
+
+/// Returns the number of instructions folded. Operations on constants
+/// that are left because they would raise get a missed remark.
+fn fold_constants(function: &mut Function, context: &mut PassContext) -> usize {
+    let mut constants: HashMap<Value, Constant> = HashMap::new();
+    let mut folded = 0;
+    // A value can be used in a block laid out before the one defining it,
+    // so repeat until nothing more folds.
+    loop {
+        let mut changed = false;
+        for inst in function.layout_insts().collect::<Vec<_>>() {
+            let data = function.inst(inst);
+            let Some(result) = data.result else {
+                continue;
+            };
+            if let InstKind::Const(constant) = &data.kind {
+                constants.insert(result, constant.clone());
+                continue;
+            }
+            if let Some(constant) = fold(&data.kind, function.value_type(result), &constants) {
+                let message = format!("folded `{}` into `{}`", data.kind.opcode(), constant);
+                context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                function.inst_mut(inst).kind = InstKind::Const(constant.clone());
+                constants.insert(result, constant);
+                folded += 1;
+                changed = true;
+            } else if is_foldable(&data.kind) && data.kind.operands().iter().all(|operand| constants.contains_key(operand)) {
+                let message = format!("could not fold `{}` of constants: it raises at runtime", data.kind.opcode());
+                context.remark(RemarkKind::Missed, &function.name, data.span, message);
             }
         }
+        if !changed {
+            return folded;
+        }
+    }
+}
+
+/// Whether `fold` computes the instruction when its operands are constants.
+pub(crate) fn is_foldable(kind: &InstKind) -> bool {
+    matches!(
+        kind,
+        InstKind::Unary { .. }
+            | InstKind::Binary { .. }
+            | InstKind::Compare { .. }
+            | InstKind::Cast(_)
+            | InstKind::Box(_)
+            | InstKind::Unbox(_)
+            | InstKind::Guard { .. }
+            | InstKind::Truth(_)
+            | InstKind::CheckIndex { .. }
+    )
+}
+
+/// The result, of type `ty`, of an instruction whose operands are all
+/// constants, computed as the runtime would: with big integers where an
+/// `int` overflows, and wrapping around for `i64`s.
+pub(crate) fn fold(kind: &InstKind, ty: Type, constants: &HashMap<Value, Constant>) -> Option<Constant> {
+    let get = |value: &Value| constants.get(value).map(to_runtime);
+    let get_i64 = |value: &Value| match constants.get(value) {
+        Some(Constant::Int(value)) => Some(*value),
+        _ => None,
+    };
+    let folded = match kind {
+        InstKind::Unary { op: UnaryOp::Neg, operand } if ty == Type::I64 => RuntimeValue::Int(get_i64(operand)?.wrapping_neg()),
+        InstKind::Unary { op: UnaryOp::Neg, operand } => runtime::negate(&get(operand)?).ok()?,
+        InstKind::Unary { op: UnaryOp::Not, operand } => RuntimeValue::Bool(!get(operand)?.is_true()),
+        InstKind::Binary { op, left, right } => {
+            let arithmetic = match op {
+                BinaryOp::Add => Arithmetic::Add,
+                BinaryOp::Sub => Arithmetic::Sub,
+                BinaryOp::Mul => Arithmetic::Mul,
+                BinaryOp::Div => Arithmetic::Div,
+                BinaryOp::FloorDiv => Arithmetic::FloorDiv,
+                BinaryOp::Mod => Arithmetic::Mod,
+            };
+            if ty == Type::I64 {
+                RuntimeValue::Int(runtime::i64_arithmetic(arithmetic, get_i64(left)?, get_i64(right)?).ok()?)
+            } else {
+                runtime::arithmetic(arithmetic, &get(left)?, &get(right)?).ok()?
+            }
+        }
+        InstKind::Compare { op, left, right } => {
+            let comparison = match op {
+                CompareOp::Eq => Comparison::Equal,
+                CompareOp::Ne => Comparison::NotEqual,
+                CompareOp::Lt => Comparison::Less,
+                CompareOp::Le => Comparison::LessEqual,
+                CompareOp::Gt => Comparison::Greater,
+                CompareOp::Ge => Comparison::GreaterEqual,
+            };
+            RuntimeValue::Bool(runtime::compare(comparison, &get(left)?, &get(right)?).ok()?)
+        }
+        InstKind::Cast(value) => match (get(value)?, ty) {
+            (RuntimeValue::Bool(value), Type::Int | Type::I64) => RuntimeValue::Int(value as i64),
+            (value, Type::F64) => RuntimeValue::Float(value.to_float().ok()?),
+            (value, _) => value,
+        },
+        InstKind::Box(value) => get(value)?,
+        InstKind::Unbox(value) => {
+            let class = match Class::of_unboxed(ty)? {
+                Class::Int => runtime::Class::Int,
+                Class::Float => runtime::Class::Float,
+                Class::Bool => runtime::Class::Bool,
+                Class::Str => runtime::Class::Str,
+                Class::NoneType => runtime::Class::NoneType,
+            };
+            runtime::unbox(class, &get(value)?).ok()?
+        }
+        InstKind::Guard { value, class } => RuntimeValue::Bool(get(value)?.class().name() == class.name()),
+        InstKind::Truth(value) => RuntimeValue::Bool(get(value)?.is_true()),
+        InstKind::CheckIndex { index, length } => runtime::check_index(&get(index)?, &get(length)?).ok()?,
+        _ => return None,
+    };
+    Some(match folded {
+        RuntimeValue::Int(value) => Constant::Int(value),
+        RuntimeValue::BigInt(value) => Constant::BigInt(value),
+        RuntimeValue::Float(value) => Constant::Float(value),
+        RuntimeValue::Bool(value) => Constant::Bool(value),
+        RuntimeValue::Str(value) => Constant::Str(value.to_string()),
+        RuntimeValue::None => Constant::None,
+        // Tuples and lists have no constants; they are never folded into.
+        RuntimeValue::Tuple(_) | RuntimeValue::List(_) => return None,
+    })
+}
 
-        Ok(new_ir)
+fn to_runtime(constant: &Constant) -> RuntimeValue {
+    match constant {
+        Constant::Int(value) => RuntimeValue::Int(*value),
+        Constant::BigInt(value) => RuntimeValue::BigInt(value.clone()),
+        Constant::Float(value) => RuntimeValue::Float(*value),
+        Constant::Bool(value) => RuntimeValue::Bool(*value),
+        Constant::None => RuntimeValue::None,
+        Constant::Str(value) => RuntimeValue::Str(value.as_str().into()),
     }
 }
 
+/// Removes the instructions without side effects whose results are unused.
 pub struct DeadCodeElimination;
 
 impl Optimization for DeadCodeElimination {
-    fn apply(&self, ir: Vec<IRNode>) -> Result<Vec<IRNode>, String> {
-        let mut used_vars = HashMap::new();
-        let mut new_ir = Vec::new();
-
-        for instruction in ir.iter().rev() {
-            match instruction {
-                IRNode::Immediate { dest, .. } => {
-                    if used_vars.contains_key(dest) {
-                        new_ir.push(instruction.clone());
-                        used_vars.remove(dest);
-                    }
-                }
-                IRNode::Print { var } => {
-                    new_ir.push(instruction.clone());
-                    used_vars.insert(var.clone(), true);
-                }
-                _ => new_ir.push(instruction.clone()),
-            }
+    fn name(&self) -> &'static str {
+        "dead-code-elimination"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut removed = 0;
+        for function in &mut ir.functions {
+            removed += eliminate_dead_code(function, context);
         }
+        context.count("instructions removed", removed);
+        Ok(removed > 0)
+    }
+}
 
-        Ok(new_ir.into_iter().rev().collect())
+/// Returns the number of instructions removed.
+fn eliminate_dead_code(function: &mut Function, context: &mut PassContext) -> usize {
+    let mut removed = 0;
+    loop {
+        let mut used: HashSet<Value> = HashSet::new();
+        for block in &function.layout {
+            for inst in &function.block(*block).insts {
+                used.extend(function.inst(*inst).kind.operands());
+            }
+            if let Some(terminator) = function.terminator(*block) {
+                used.extend(terminator.operands());
+            }
+        }
+        let removed_before = removed;
+        for block in function.layout.clone() {
+            let insts = function.block(block).insts.clone();
+            let (live, dead): (Vec<_>, Vec<_>) = insts.iter().copied().partition(|inst| {
+                function.has_side_effects(*inst) || function.result(*inst).is_some_and(|result| used.contains(&result))
+            });
+            for inst in &dead {
+                let data = function.inst(*inst);
+                context.remark(RemarkKind::Passed, &function.name, data.span, format!("removed unused `{}`", data.kind.opcode()));
+            }
+            removed += dead.len();
+            function.block_mut(block).insts = live;
+        }
+        if removed == removed_before {
+            return removed;
+        }
     }
 }
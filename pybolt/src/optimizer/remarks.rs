@@ -0,0 +1,153 @@
+use regex::Regex;
+
+use crate::lexer::lexer::{line_column, Span};
+use crate::parser::serialize::write_json_string;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RemarkKind {
+    /// An optimization was applied.
+    Passed,
+    /// An optimization was considered but not applied.
+    Missed,
+    /// A finding of an analysis that explains the others.
+    Analysis,
+}
+
+impl RemarkKind {
+    /// The `-R` option that shows remarks of the kind.
+    pub fn option(self) -> &'static str {
+        match self {
+            RemarkKind::Passed => "pass",
+            RemarkKind::Missed => "pass-missed",
+            RemarkKind::Analysis => "pass-analysis",
+        }
+    }
+}
+
+/// What a pass did, or did not do and why, at a place in the source.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Remark {
+    pub kind: RemarkKind,
+    pub pass: &'static str,
+    pub function: String,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Remark {
+    /// Formats the remark as `line:column: remark: message`, followed by
+    /// the option that enables it, as clang does.
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = line_column(source, self.span.start);
+        format!("{}:{}: remark: {} [-R{}={}]", line, column, self.message, self.kind.option(), self.pass)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RemarkFormat {
+    /// One line per remark, like diagnostics.
+    #[default]
+    Human,
+    /// A YAML document per remark, as in LLVM's optimization records.
+    Yaml,
+    /// A JSON array of remarks.
+    Json,
+}
+
+/// Writes the remarks in `format`, with the lines and columns of their
+/// spans in `source`.
+pub fn render(remarks: &[Remark], source: &str, format: RemarkFormat) -> String {
+    let mut out = String::new();
+    match format {
+        RemarkFormat::Human => {
+            for remark in remarks {
+                out += &remark.render(source);
+                out.push('\n');
+            }
+        }
+        RemarkFormat::Yaml => {
+            for remark in remarks {
+                let (line, column) = line_column(source, remark.span.start);
+                let kind = match remark.kind {
+                    RemarkKind::Passed => "Passed",
+                    RemarkKind::Missed => "Missed",
+                    RemarkKind::Analysis => "Analysis",
+                };
+                out += &format!("--- !{}\n", kind);
+                out += &format!("Pass:            {}\n", remark.pass);
+                out += &format!("Function:        {}\n", yaml_string(&remark.function));
+                out += &format!("DebugLoc:        {{ Line: {}, Column: {} }}\n", line, column);
+                out += &format!("Message:         {}\n", yaml_string(&remark.message));
+                out += "...\n";
+            }
+        }
+        RemarkFormat::Json => {
+            out.push('[');
+            for (index, remark) in remarks.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                let (line, column) = line_column(source, remark.span.start);
+                let kind = match remark.kind {
+                    RemarkKind::Passed => "passed",
+                    RemarkKind::Missed => "missed",
+                    RemarkKind::Analysis => "analysis",
+                };
+                out += &format!("{{\"kind\":\"{}\",\"pass\":\"{}\",\"function\":", kind, remark.pass);
+                write_json_string(&remark.function, &mut out);
+                out += &format!(",\"line\":{},\"column\":{},\"span\":[{},{}],\"message\":", line, column, remark.span.start, remark.span.end);
+                write_json_string(&remark.message, &mut out);
+                out.push('}');
+            }
+            out += "]\n";
+        }
+    }
+    out
+}
+
+/// A single-quoted YAML scalar, in which quotes are doubled.
+fn yaml_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Which remarks to show: those of the passes whose names match one of the
+/// patterns given for their kind, as with clang's `-Rpass=<regex>`.
+#[derive(Debug, Clone, Default)]
+pub struct RemarkFilter {
+    passed: Vec<Regex>,
+    missed: Vec<Regex>,
+    analysis: Vec<Regex>,
+}
+
+impl RemarkFilter {
+    pub fn new() -> Self {
+        RemarkFilter::default()
+    }
+
+    /// Adds a pattern given as `pass=<regex>`, `pass-missed=<regex>` or
+    /// `pass-analysis=<regex>`.
+    pub fn add(&mut self, option: &str) -> Result<(), String> {
+        let (kind, pattern) = option.split_once('=').ok_or_else(|| format!("Expected <kind>=<regex> in -R{}", option))?;
+        let patterns = match kind {
+            "pass" => &mut self.passed,
+            "pass-missed" => &mut self.missed,
+            "pass-analysis" => &mut self.analysis,
+            _ => return Err(format!("Unknown remark kind '{}', expected pass, pass-missed or pass-analysis", kind)),
+        };
+        patterns.push(Regex::new(pattern).map_err(|error| format!("Invalid remark pattern '{}': {}", pattern, error))?);
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.passed.is_empty() || !self.missed.is_empty() || !self.analysis.is_empty()
+    }
+
+    pub fn matches(&self, remark: &Remark) -> bool {
+        let patterns = match remark.kind {
+            RemarkKind::Passed => &self.passed,
+            RemarkKind::Missed => &self.missed,
+            RemarkKind::Analysis => &self.analysis,
+        };
+        patterns.iter().any(|pattern| pattern.is_match(remark.pass))
+    }
+}
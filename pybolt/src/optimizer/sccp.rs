@@ -0,0 +1,292 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::ir::{Block, Constant, Function, Inst, InstKind, Module, Terminator, Value, ValueDef};
+use crate::lexer::lexer::Span;
+use crate::optimizer::optimizer::{fold, is_foldable, Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Sparse conditional constant propagation (Wegman and Zadeck). Values are
+/// assumed constant until shown otherwise, and only the edges of branches
+/// whose condition may take them are followed, so constants flow through
+/// block parameters and loops, and branches on them are decided. Then the
+/// constant values are replaced by constants, decided branches become
+/// jumps and the blocks no longer reached are deleted. Folding is that of
+/// `ConstantFolding`, so what would raise at runtime is left alone.
+pub struct Sccp;
+
+impl Optimization for Sccp {
+    fn name(&self) -> &'static str {
+        "sccp"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut changed = false;
+        for function in &mut ir.functions {
+            let solution = Solver::solve(function);
+            changed |= rewrite(function, &solution, context);
+        }
+        Ok(changed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Lattice {
+    /// Not known yet: no executable path defines the value so far.
+    Unknown,
+    Constant(Constant),
+    /// Not a constant.
+    Varying,
+}
+
+impl Lattice {
+    fn meet(&self, other: &Lattice) -> Lattice {
+        match (self, other) {
+            (Lattice::Unknown, other) | (other, Lattice::Unknown) => other.clone(),
+            (Lattice::Constant(left), Lattice::Constant(right)) if left == right => self.clone(),
+            _ => Lattice::Varying,
+        }
+    }
+}
+
+/// What the solver found: the value of each value, and the blocks and edges
+/// some execution may take.
+struct Solution {
+    values: HashMap<Value, Lattice>,
+    executable_blocks: HashSet<Block>,
+    /// Edges by block and index among its terminator's successors.
+    executable_edges: HashSet<(Block, usize)>,
+}
+
+impl Solution {
+    fn constant(&self, value: Value) -> Option<&Constant> {
+        match self.values.get(&value) {
+            Some(Lattice::Constant(constant)) => Some(constant),
+            _ => None,
+        }
+    }
+}
+
+/// Where a value is used.
+#[derive(Debug, Clone, Copy)]
+enum User {
+    Inst(Block, Inst),
+    Terminator(Block),
+}
+
+struct Solver<'a> {
+    function: &'a Function,
+    values: HashMap<Value, Lattice>,
+    executable_blocks: HashSet<Block>,
+    /// Edges by block and index among its terminator's successors.
+    executable_edges: HashSet<(Block, usize)>,
+    users: HashMap<Value, Vec<User>>,
+    block_worklist: Vec<Block>,
+    value_worklist: Vec<Value>,
+}
+
+impl<'a> Solver<'a> {
+    fn solve(function: &'a Function) -> Solution {
+        let mut users: HashMap<Value, Vec<User>> = HashMap::new();
+        for block in &function.layout {
+            for inst in &function.block(*block).insts {
+                for operand in function.inst(*inst).kind.operands() {
+                    users.entry(operand).or_default().push(User::Inst(*block, *inst));
+                }
+            }
+            for operand in function.terminator(*block).map(Terminator::operands).unwrap_or_default() {
+                users.entry(operand).or_default().push(User::Terminator(*block));
+            }
+        }
+
+        let mut solver = Solver {
+            function,
+            values: HashMap::new(),
+            executable_blocks: HashSet::from([function.entry()]),
+            executable_edges: HashSet::new(),
+            users,
+            block_worklist: vec![function.entry()],
+            value_worklist: Vec::new(),
+        };
+        for param in function.params() {
+            solver.values.insert(*param, Lattice::Varying);
+        }
+
+        loop {
+            if let Some(block) = solver.block_worklist.pop() {
+                for inst in &function.block(block).insts {
+                    solver.visit_inst(*inst);
+                }
+                solver.visit_terminator(block);
+            } else if let Some(value) = solver.value_worklist.pop() {
+                for user in solver.users.get(&value).cloned().unwrap_or_default() {
+                    match user {
+                        User::Inst(block, inst) if solver.executable_blocks.contains(&block) => solver.visit_inst(inst),
+                        User::Terminator(block) if solver.executable_blocks.contains(&block) => solver.visit_terminator(block),
+                        _ => {}
+                    }
+                }
+            } else {
+                return Solution {
+                    values: solver.values,
+                    executable_blocks: solver.executable_blocks,
+                    executable_edges: solver.executable_edges,
+                };
+            }
+        }
+    }
+
+    fn get(&self, value: Value) -> &Lattice {
+        self.values.get(&value).unwrap_or(&Lattice::Unknown)
+    }
+
+    /// Lowers the value to its meet with `lattice`.
+    fn update(&mut self, value: Value, lattice: &Lattice) {
+        let old = self.get(value);
+        let new = old.meet(lattice);
+        if new != *old {
+            self.values.insert(value, new);
+            self.value_worklist.push(value);
+        }
+    }
+
+    fn visit_inst(&mut self, inst: Inst) {
+        let data = self.function.inst(inst);
+        let Some(result) = data.result else {
+            return;
+        };
+        let lattice = match &data.kind {
+            InstKind::Const(constant) => Lattice::Constant(constant.clone()),
+            kind if is_foldable(kind) => {
+                let mut constants = HashMap::new();
+                let mut lattice = None;
+                for operand in kind.operands() {
+                    match self.get(operand) {
+                        Lattice::Unknown => lattice = lattice.or(Some(Lattice::Unknown)),
+                        Lattice::Varying => lattice = Some(Lattice::Varying),
+                        Lattice::Constant(constant) => {
+                            constants.insert(operand, constant.clone());
+                        }
+                    }
+                }
+                lattice.unwrap_or_else(|| match fold(kind, self.function.value_type(result), &constants) {
+                    Some(constant) => Lattice::Constant(constant),
+                    None => Lattice::Varying,
+                })
+            }
+            _ => Lattice::Varying,
+        };
+        self.update(result, &lattice);
+    }
+
+    fn visit_terminator(&mut self, block: Block) {
+        let Some(terminator) = self.function.terminator(block) else {
+            return;
+        };
+        let taken: Vec<usize> = match terminator {
+            Terminator::Br(_) => vec![0],
+            Terminator::CondBr { condition, .. } => match self.get(*condition) {
+                Lattice::Unknown => vec![],
+                Lattice::Constant(Constant::Bool(true)) => vec![0],
+                Lattice::Constant(Constant::Bool(false)) => vec![1],
+                _ => vec![0, 1],
+            },
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
+        };
+        let successors = terminator.successors();
+        for index in taken {
+            let target = successors[index];
+            self.executable_edges.insert((block, index));
+            let params = &self.function.block(target.block).params;
+            for (param, argument) in params.iter().zip(&target.arguments) {
+                let lattice = self.get(*argument).clone();
+                self.update(*param, &lattice);
+            }
+            if self.executable_blocks.insert(target.block) {
+                self.block_worklist.push(target.block);
+            }
+        }
+    }
+}
+
+/// Applies what the solver found. Returns whether the function changed.
+fn rewrite(function: &mut Function, solution: &Solution, context: &mut PassContext) -> bool {
+    let mut propagated = 0;
+    let mut pruned = 0;
+    let layout: Vec<Block> = function.layout.iter().copied().filter(|block| solution.executable_blocks.contains(block)).collect();
+
+    for block in &layout {
+        for inst in function.block(*block).insts.clone() {
+            let data = function.inst(inst);
+            let (Some(result), false) = (data.result, matches!(data.kind, InstKind::Const(_))) else {
+                continue;
+            };
+            match solution.constant(result) {
+                Some(constant) => {
+                    let message = format!("folded `{}` into `{}`", data.kind.opcode(), constant);
+                    context.remark(RemarkKind::Passed, &function.name, data.span, message);
+                    function.inst_mut(inst).kind = InstKind::Const(constant.clone());
+                    propagated += 1;
+                }
+                None if is_foldable(&data.kind)
+                    && data.kind.operands().iter().all(|operand| solution.constant(*operand).is_some()) =>
+                {
+                    let message = format!("could not fold `{}` of constants: it raises at runtime", data.kind.opcode());
+                    context.remark(RemarkKind::Missed, &function.name, data.span, message);
+                }
+                None => {}
+            }
+        }
+
+        // Block parameters that are constant become constants at the start
+        // of the block.
+        for index in (0..function.block(*block).params.len()).rev() {
+            let param = function.block(*block).params[index];
+            let Some(constant) = solution.constant(param) else {
+                continue;
+            };
+            let first = function.block(*block).insts.first().copied();
+            let span = first.map_or(Span::default(), |inst| function.inst(inst).span);
+            let message = format!("block parameter is always `{}`", constant);
+            context.remark(RemarkKind::Passed, &function.name, span, message);
+            let (kind, ty) = (InstKind::Const(constant.clone()), Some(function.value_type(param)));
+            let inst = match first {
+                Some(first) => function.make_inst_like(first, kind, ty),
+                None => function.make_inst(kind, ty, span),
+            };
+            function.block_mut(*block).insts.insert(0, inst);
+            let value = function.result(inst).expect("constants have a result");
+            function.replace_uses(param, value);
+            function.remove_block_param(*block, index);
+            propagated += 1;
+        }
+
+        if let Some(Terminator::CondBr { condition, then, otherwise }) = function.terminator(*block) {
+            let taken = match (solution.executable_edges.contains(&(*block, 0)), solution.executable_edges.contains(&(*block, 1))) {
+                (true, false) => then.clone(),
+                (false, true) => otherwise.clone(),
+                _ => continue,
+            };
+            let span = match function.value(*condition).def {
+                ValueDef::Result(inst) => function.inst(inst).span,
+                ValueDef::Param(_) => Span::default(),
+            };
+            let always = if taken == *then { "true" } else { "false" };
+            let message = format!("the condition is always {}, so the other branch was removed", always);
+            context.remark(RemarkKind::Passed, &function.name, span, message);
+            function.block_mut(*block).terminator = Some(Terminator::Br(taken));
+            pruned += 1;
+        }
+    }
+
+    let blocks_before = function.layout.len();
+    function.remove_unreachable_blocks();
+    let removed = blocks_before - function.layout.len();
+    if removed > 0 {
+        function.remove_trivial_block_params();
+    }
+
+    context.count("constants propagated", propagated);
+    context.count("branches pruned", pruned);
+    context.count("blocks removed", removed);
+    propagated + pruned + removed > 0
+}
@@ -0,0 +1,301 @@
+use crate::ir::ir::{BlockCall, Class, Function, Inst, InstKind, Module, Terminator, Type, Value, ValueDef};
+use crate::lexer::lexer::Span;
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// The classes of the arguments a function is specialized for, one per
+/// parameter: `None` where the parameter stays an `object`.
+type Signature = Vec<Option<Class>>;
+
+/// Type specialization. A function with `object` parameters, as one without
+/// annotations has, gets a copy specialized for the classes of the arguments
+/// its calls are known to pass: ints and floats, boxed from an `int` or
+/// `f64` or constant. The copy, named after the classes, such as `f.int.int`,
+/// which no Python function can be named, takes the unboxed values, and the
+/// calls pass them directly; `unbox` then computes on them unboxed.
+///
+/// The first time a function is specialized, its own entry speculates on
+/// the same classes for the calls whose arguments are not known: it guards
+/// the classes of its arguments, calls the copy with them unboxed if they
+/// match, and deoptimizes, going on with the generic body, if any does not.
+/// A function gets at most `MAX_SPECIALIZATIONS` copies, and a function
+/// whose calls were all specialized is removed, `__main__` aside.
+pub struct TypeSpecialization;
+
+impl TypeSpecialization {
+    pub const MAX_SPECIALIZATIONS: usize = 4;
+}
+
+impl Optimization for TypeSpecialization {
+    fn name(&self) -> &'static str {
+        "specialize"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let calls_before = ir.call_counts();
+        let mut specialized_calls = 0;
+        let mut specialized_functions = 0;
+        for index in 0..ir.functions.len() {
+            let calls: Vec<Inst> = ir.functions[index]
+                .layout_insts()
+                .filter(|inst| matches!(ir.functions[index].inst(*inst).kind, InstKind::Call { .. }))
+                .collect();
+            for inst in calls {
+                let caller = &ir.functions[index];
+                let InstKind::Call { function: name, arguments } = &caller.inst(inst).kind else {
+                    continue;
+                };
+                let Some(callee) = ir.function(name).filter(|callee| is_specializable(callee)) else {
+                    continue;
+                };
+                let signature = signature(caller, callee, arguments);
+                if signature.iter().all(Option::is_none) {
+                    continue;
+                }
+                let (caller_name, callee_name, span) = (caller.name.clone(), callee.name.clone(), caller.inst(inst).span);
+                let specialized = specialized_name(&callee_name, &signature);
+                if ir.function(&specialized).is_none() {
+                    let copies = ir.functions.iter().filter(|function| is_specialization_of(function, &callee_name)).count();
+                    if copies >= Self::MAX_SPECIALIZATIONS {
+                        let message = format!(
+                            "did not specialize `{}` for {}: it has {} specializations already",
+                            callee_name,
+                            describe(&signature),
+                            copies
+                        );
+                        context.remark(RemarkKind::Missed, &caller_name, span, message);
+                        continue;
+                    }
+                    let copy = specialize(callee, &signature, &specialized);
+                    ir.functions.push(copy);
+                    if copies == 0 {
+                        let generic = ir.function_mut(&callee_name).expect("the callee is in the module");
+                        speculate(generic, &signature, &specialized);
+                    }
+                    specialized_functions += 1;
+                }
+                let message = format!("specialized the call of `{}` for {}", callee_name, describe(&signature));
+                context.remark(RemarkKind::Passed, &caller_name, span, message);
+                let returns = ir.function(&specialized).expect("the specialization is in the module").return_type;
+                specialize_call(&mut ir.functions[index], inst, &signature, &specialized, returns);
+                specialized_calls += 1;
+            }
+        }
+        if specialized_calls == 0 {
+            return Ok(false);
+        }
+
+        let calls_after = ir.call_counts();
+        ir.functions.retain(|function| !calls_before.contains_key(&function.name) || calls_after.contains_key(&function.name));
+        context.count("functions specialized", specialized_functions);
+        context.count("calls specialized", specialized_calls);
+        Ok(true)
+    }
+}
+
+/// Whether `function` has `object` parameters and is not a specialization
+/// itself.
+fn is_specializable(function: &Function) -> bool {
+    !function.name.contains('.') && function.param_types().contains(&Type::Object)
+}
+
+fn is_specialization_of(function: &Function, name: &str) -> bool {
+    function.name.strip_prefix(name).is_some_and(|rest| rest.starts_with('.'))
+}
+
+/// `f.int.object` for `f` specialized for an int as its first argument.
+fn specialized_name(name: &str, signature: &Signature) -> String {
+    let classes: Vec<&str> = signature.iter().map(|class| class.map_or("object", |class| class.name())).collect();
+    format!("{}.{}", name, classes.join("."))
+}
+
+/// `(int, object)`.
+fn describe(signature: &Signature) -> String {
+    let classes: Vec<&str> = signature.iter().map(|class| class.map_or("object", |class| class.name())).collect();
+    format!("({})", classes.join(", "))
+}
+
+/// The class of each `object` argument of the call that is known to be an
+/// int or a float.
+fn signature(caller: &Function, callee: &Function, arguments: &[Value]) -> Signature {
+    callee
+        .param_types()
+        .iter()
+        .zip(arguments)
+        .map(|(ty, argument)| match ty {
+            Type::Object => known_class(caller, *argument).filter(|class| matches!(class, Class::Int | Class::Float)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The class of `value`, an `object`, when it is a boxed value or a
+/// constant.
+pub(crate) fn known_class(function: &Function, value: Value) -> Option<Class> {
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return None;
+    };
+    match &function.inst(inst).kind {
+        InstKind::Box(unboxed) => match function.value_type(*unboxed) {
+            Type::I64 => Some(Class::Int),
+            ty => Class::of_unboxed(ty),
+        },
+        InstKind::Const(constant) => Some(constant.class()),
+        _ => None,
+    }
+}
+
+/// A copy of `function` whose parameters of a known class take its unboxed
+/// values, boxed again on entry. The copy of a function that speculates
+/// starts from its generic body.
+fn specialize(function: &Function, signature: &Signature, name: &str) -> Function {
+    let mut copy = function.clone();
+    copy.name = name.to_string();
+    skip_speculation(&mut copy);
+    let entry = copy.entry();
+    let mut boxes = Vec::new();
+    for (param, class) in copy.params().to_vec().into_iter().zip(signature) {
+        let Some(class) = class else {
+            continue;
+        };
+        let boxed = copy.make_inst(InstKind::Box(param), Some(Type::Object), Span::default());
+        let value = copy.result(boxed).expect("boxes have a result");
+        copy.replace_uses(param, value);
+        copy.values[param.0 as usize].ty = class.unboxed();
+        boxes.push(boxed);
+    }
+    copy.block_mut(entry).insts.splice(0..0, boxes);
+    copy
+}
+
+/// Makes the entry of the generic `function` guard the classes of
+/// `signature` and call its specialization when they match. The generic
+/// body moves to a block of its own, which a failed guard goes on with.
+fn speculate(function: &mut Function, signature: &Signature, specialized: &str) {
+    let entry = function.entry();
+    let params = function.params().to_vec();
+    let layout_end = function.layout.len();
+
+    let generic = function.create_block();
+    let moved: Vec<Value> = params.iter().map(|param| function.append_block_param(generic, function.value_type(*param))).collect();
+    let data = function.block_mut(entry);
+    let (insts, terminator) = (std::mem::take(&mut data.insts), data.terminator.take());
+    function.block_mut(generic).insts = insts;
+    function.block_mut(generic).terminator = terminator;
+    for (param, moved) in params.iter().zip(&moved) {
+        function.replace_uses(*param, *moved);
+    }
+    let deoptimize = BlockCall { block: generic, arguments: params.clone() };
+
+    // A chain of guards, one block each, ending in the fast path.
+    let mut block = entry;
+    for (param, class) in params.iter().zip(signature) {
+        let Some(class) = class else {
+            continue;
+        };
+        let guard = function.make_inst(InstKind::Guard { value: *param, class: *class }, Some(Type::Bool), Span::default());
+        function.block_mut(block).insts.push(guard);
+        let next = function.create_block();
+        let condition = function.result(guard).expect("guards have a result");
+        let then = BlockCall { block: next, arguments: Vec::new() };
+        function.block_mut(block).terminator = Some(Terminator::CondBr { condition, then, otherwise: deoptimize.clone() });
+        block = next;
+    }
+    let mut arguments = Vec::new();
+    for (param, class) in params.iter().zip(signature) {
+        match class {
+            Some(class) => {
+                let unbox = function.make_inst(InstKind::Unbox(*param), Some(class.unboxed()), Span::default());
+                function.block_mut(block).insts.push(unbox);
+                arguments.push(function.result(unbox).expect("unboxes have a result"));
+            }
+            None => arguments.push(*param),
+        }
+    }
+    let call = function.make_inst(
+        InstKind::Call { function: specialized.to_string(), arguments },
+        Some(function.return_type),
+        Span::default(),
+    );
+    function.block_mut(block).insts.push(call);
+    let returned = function.result(call).filter(|_| function.return_type != Type::None);
+    function.block_mut(block).terminator = Some(Terminator::Return(returned));
+
+    // The guards and the fast path come before the generic body.
+    let mut added = function.layout.split_off(layout_end);
+    added.rotate_left(1);
+    function.layout.splice(1..1, added);
+}
+
+/// Makes the entry of `function`, if it guards the first class its
+/// speculation checks, the generic body again.
+fn skip_speculation(function: &mut Function) {
+    let entry = function.entry();
+    let params = function.params().to_vec();
+    let data = function.block(entry);
+    let [guard] = data.insts[..] else {
+        return;
+    };
+    let Some(Terminator::CondBr { condition, otherwise, .. }) = data.terminator.clone() else {
+        return;
+    };
+    let guards_param = matches!(function.inst(guard).kind, InstKind::Guard { value, .. } if params.contains(&value));
+    if !guards_param || function.result(guard) != Some(condition) || otherwise.arguments != params {
+        return;
+    }
+    let generic = otherwise.block;
+    for (moved, param) in function.block(generic).params.clone().into_iter().zip(params) {
+        function.replace_uses(moved, param);
+    }
+    let data = function.block_mut(generic);
+    let (insts, terminator) = (std::mem::take(&mut data.insts), data.terminator.take());
+    function.block_mut(entry).insts = insts;
+    function.block_mut(entry).terminator = terminator;
+    function.remove_unreachable_blocks();
+}
+
+/// Makes the call `inst` call the specialization, with the unboxed values
+/// of the arguments of a known class. A specialization an earlier round of
+/// the pipeline made may return an unboxed value, which the call boxes.
+fn specialize_call(caller: &mut Function, inst: Inst, signature: &Signature, specialized: &str, returns: Type) {
+    let InstKind::Call { arguments, .. } = caller.inst(inst).kind.clone() else {
+        unreachable!("only calls are specialized");
+    };
+    let mut constants = Vec::new();
+    let mut unboxed = Vec::new();
+    for (argument, class) in arguments.into_iter().zip(signature) {
+        let Some(class) = class else {
+            unboxed.push(argument);
+            continue;
+        };
+        let ValueDef::Result(def) = caller.value(argument).def else {
+            unreachable!("arguments of a known class are defined by instructions");
+        };
+        match caller.inst(def).kind.clone() {
+            InstKind::Box(value) if caller.value_type(value) == class.unboxed() => unboxed.push(value),
+            InstKind::Box(value) => {
+                // An `i64`, as an `int`.
+                let cast = caller.make_inst_like(inst, InstKind::Cast(value), Some(class.unboxed()));
+                constants.push(cast);
+                unboxed.push(caller.result(cast).expect("casts have a result"));
+            }
+            InstKind::Const(constant) => {
+                let copy = caller.make_inst_like(inst, InstKind::Const(constant), Some(class.unboxed()));
+                constants.push(copy);
+                unboxed.push(caller.result(copy).expect("constants have a result"));
+            }
+            _ => unreachable!("arguments of a known class are boxed or constant"),
+        }
+    }
+    caller.inst_mut(inst).kind = InstKind::Call { function: specialized.to_string(), arguments: unboxed };
+    let block = *caller.layout.iter().find(|block| caller.block(**block).insts.contains(&inst)).expect("the call is in the layout");
+    let position = caller.block(block).insts.iter().position(|other| *other == inst).expect("the call is in its block");
+    if let Some(result) = caller.result(inst).filter(|result| caller.value_type(*result) != returns) {
+        let boxed = caller.make_inst_like(inst, InstKind::Box(result), Some(Type::Object));
+        let value = caller.result(boxed).expect("boxes have a result");
+        caller.replace_uses(result, value);
+        caller.values[result.0 as usize].ty = returns;
+        caller.block_mut(block).insts.insert(position + 1, boxed);
+    }
+    caller.block_mut(block).insts.splice(position..position, constants);
+}
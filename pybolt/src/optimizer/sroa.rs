@@ -0,0 +1,127 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::ir::{Function, Inst, InstKind, Module, Type, Value};
+use crate::optimizer::escape::{Escape, EscapeAnalysis};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Scalar replacement of aggregates. The tuples that escape analysis finds
+/// do not escape are never made: their unpackings use the items instead,
+/// and a block parameter that holds them becomes one parameter per item.
+/// The temporary tuple of `a, b = b, a + b` goes, and so does the one a
+/// function returns once it is inlined into a caller that unpacks it; a
+/// tuple that leaves the function stays on the heap. Only tuples are
+/// replaced, and a returned tuple only through inlining: the pass does not
+/// change what a function returns, since its callers would need to change
+/// too.
+pub struct ScalarReplacement;
+
+impl Optimization for ScalarReplacement {
+    fn name(&self) -> &'static str {
+        "sroa"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut replaced = 0;
+        for function in &mut ir.functions {
+            // The items of a tuple broken up may be tuples that only
+            // escaped into it; look again after each round.
+            loop {
+                let analysis = EscapeAnalysis::analyze(function);
+                if analysis.aggregates.is_empty() {
+                    for (inst, escape) in analysis.escaping {
+                        let message = match escape {
+                            Escape::Returned => format!(
+                                "kept a tuple on the heap: it is returned, and only goes where `{}` is inlined into a caller that unpacks it",
+                                function.name
+                            ),
+                            escape => format!("kept a tuple on the heap: it is {}", escape.describe()),
+                        };
+                        context.remark(RemarkKind::Missed, &function.name, function.inst(inst).span, message);
+                    }
+                    break;
+                }
+                replaced += replace(function, &analysis.aggregates, context);
+                context.analyses.invalidate(&function.name);
+            }
+        }
+        context.count("tuples replaced by their items", replaced);
+        Ok(replaced > 0)
+    }
+}
+
+/// Replaces the `aggregates` by their items. Returns the number of tuples
+/// removed.
+fn replace(function: &mut Function, aggregates: &HashMap<Value, usize>, context: &mut PassContext) -> usize {
+    let mut items: HashMap<Value, Vec<Value>> = HashMap::new();
+    let mut tuples: HashSet<Inst> = HashSet::new();
+    for block in function.layout.clone() {
+        for inst in function.block(block).insts.clone() {
+            let (InstKind::Tuple(values), Some(result)) = (&function.inst(inst).kind, function.result(inst)) else {
+                continue;
+            };
+            if aggregates.contains_key(&result) {
+                items.insert(result, values.clone());
+                tuples.insert(inst);
+                let message = format!("replaced a tuple of {} items by its items", values.len());
+                context.remark(RemarkKind::Passed, &function.name, function.inst(inst).span, message);
+            }
+        }
+        for param in function.block(block).params.clone() {
+            if let Some(length) = aggregates.get(&param) {
+                let values = (0..*length).map(|_| function.append_block_param(block, Type::Object)).collect();
+                items.insert(param, values);
+            }
+        }
+    }
+
+    // Branches pass the items of what they passed for each parameter
+    // replaced, in the order of the parameters.
+    for block in function.layout.clone() {
+        let Some(mut terminator) = function.terminator(block).cloned() else {
+            continue;
+        };
+        for target in terminator.successors_mut() {
+            let params = &function.block(target.block).params;
+            let passed: Vec<Value> = target
+                .arguments
+                .iter()
+                .zip(params)
+                .filter(|(_, param)| aggregates.contains_key(param))
+                .flat_map(|(argument, _)| items[argument].clone())
+                .collect();
+            target.arguments.extend(passed);
+        }
+        function.block_mut(block).terminator = Some(terminator);
+    }
+    for block in function.layout.clone() {
+        let params = function.block(block).params.clone();
+        for (index, param) in params.iter().enumerate().rev() {
+            if aggregates.contains_key(param) {
+                function.remove_block_param(block, index);
+            }
+        }
+    }
+
+    // An item may be the result of an unpacking replaced before.
+    let mut unpacks = HashSet::new();
+    let mut replaced: HashMap<Value, Value> = HashMap::new();
+    for inst in function.layout_insts().collect::<Vec<_>>() {
+        let InstKind::Unpack { tuple, index, .. } = function.inst(inst).kind else {
+            continue;
+        };
+        if let (Some(values), Some(result)) = (items.get(&tuple), function.result(inst)) {
+            let mut value = values[index];
+            while let Some(other) = replaced.get(&value) {
+                value = *other;
+            }
+            function.replace_uses(result, value);
+            replaced.insert(result, value);
+            unpacks.insert(inst);
+        }
+    }
+    for block in function.layout.clone() {
+        function.block_mut(block).insts.retain(|inst| !tuples.contains(inst) && !unpacks.contains(inst));
+    }
+    tuples.len()
+}
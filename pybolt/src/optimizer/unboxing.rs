@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use crate::ir::ir::{BinaryOp, Block, CompareOp, Constant, Function, Inst, InstKind, Module, Terminator, Type, UnaryOp, Value, ValueDef};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+use crate::optimizer::specialize::known_class;
+
+/// Unboxing. Operations on `object`s that were boxed from unboxed values, or
+/// are constants, compute on the unboxed values instead, with the same
+/// result: arithmetic and comparisons of ints and floats, where an int meets
+/// a float as Python converts it, `unbox`es of boxed values, `guard`s of
+/// objects of a known class and truth tests. A function returning only
+/// boxed values of one type returns them unboxed, and its calls box them,
+/// which the callers can then unbox in turn.
+pub struct Unboxing;
+
+impl Optimization for Unboxing {
+    fn name(&self) -> &'static str {
+        "unbox"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut unboxed = 0;
+        for function in &mut ir.functions {
+            unboxed += unbox_operations(function, context);
+        }
+        let returns = unbox_returns(ir);
+        context.count("operations unboxed", unboxed);
+        context.count("returns unboxed", returns);
+        Ok(unboxed + returns > 0)
+    }
+}
+
+/// Returns the number of instructions rewritten.
+fn unbox_operations(function: &mut Function, context: &mut PassContext) -> usize {
+    let mut rewritten = 0;
+    let mut removed: HashSet<Inst> = HashSet::new();
+    for block in function.layout.clone() {
+        let mut index = 0;
+        while index < function.block(block).insts.len() {
+            let inst = function.block(block).insts[index];
+            let mut added = Vec::new();
+            if let Some(message) = unbox(function, inst, &mut added, &mut removed) {
+                context.remark(RemarkKind::Passed, &function.name, function.inst(inst).span, message);
+                rewritten += 1;
+            }
+            index += added.len() + 1;
+            function.block_mut(block).insts.splice(index - 1 - added.len()..index - 1 - added.len(), added);
+        }
+    }
+    for block in function.layout.clone() {
+        function.block_mut(block).insts.retain(|inst| !removed.contains(inst));
+    }
+    rewritten
+}
+
+/// Rewrites `inst` to compute on unboxed values, if its operands are known,
+/// with the instructions it needs added to `added`, to go before it. Returns
+/// the remark to make.
+fn unbox(function: &mut Function, inst: Inst, added: &mut Vec<Inst>, removed: &mut HashSet<Inst>) -> Option<String> {
+    let data = function.inst(inst).clone();
+    let result = data.result?;
+    let opcode = data.kind.opcode();
+    match data.kind {
+        InstKind::Binary { op, left, right } if function.value_type(result) == Type::Object => {
+            let (left, right) = arithmetic_operands(function, left, right, inst, added)?;
+            let ty = match (function.value_type(left), op) {
+                (Type::Int, BinaryOp::Div) => Type::F64,
+                (ty, _) => ty,
+            };
+            let computed = push(function, InstKind::Binary { op, left, right }, ty, inst, added);
+            function.inst_mut(inst).kind = InstKind::Box(computed);
+            Some(format!("computed `{}` on unboxed values", opcode))
+        }
+        InstKind::Unary { op: UnaryOp::Neg, operand } if function.value_type(result) == Type::Object => {
+            let operand = unboxed(function, operand, inst, added).filter(|value| is_number(function, *value))?;
+            let ty = function.value_type(operand);
+            let computed = push(function, InstKind::Unary { op: UnaryOp::Neg, operand }, ty, inst, added);
+            function.inst_mut(inst).kind = InstKind::Box(computed);
+            Some(format!("computed `{}` on unboxed values", opcode))
+        }
+        InstKind::Compare { op, left, right } if function.value_type(left) == Type::Object => {
+            // An int compares exactly with a float, so only the same types
+            // compare unboxed.
+            let (left_class, right_class) = (known_class(function, left)?, known_class(function, right)?);
+            if left_class != right_class {
+                return None;
+            }
+            let left = unboxed(function, left, inst, added).filter(|value| is_number(function, *value))?;
+            let right = unboxed(function, right, inst, added)?;
+            function.inst_mut(inst).kind = InstKind::Compare { op, left, right };
+            Some(format!("computed `{}` on unboxed values", opcode))
+        }
+        InstKind::Unbox(value) => {
+            let ty = function.value_type(result);
+            let class = known_class(function, value)?;
+            let converts = match (class.unboxed(), ty) {
+                (from, to) if from == to => true,
+                (Type::Int, Type::F64) | (Type::Bool, Type::Int | Type::F64) => true,
+                _ => false,
+            };
+            if !converts {
+                return None;
+            }
+            let value = unboxed(function, value, inst, added)?;
+            if function.value_type(value) == ty {
+                function.replace_uses(result, value);
+                removed.insert(inst);
+            } else {
+                function.inst_mut(inst).kind = InstKind::Cast(value);
+            }
+            Some("removed `unbox` of a boxed value".to_string())
+        }
+        InstKind::Guard { value, class } => {
+            let known = known_class(function, value)?;
+            function.inst_mut(inst).kind = InstKind::Const(Constant::Bool(known == class));
+            Some("removed `guard` of an object of a known class".to_string())
+        }
+        InstKind::Truth(value) => {
+            let value = unboxed(function, value, inst, added)?;
+            match function.value_type(value) {
+                Type::Bool => {
+                    function.replace_uses(result, value);
+                    removed.insert(inst);
+                }
+                ty @ (Type::Int | Type::F64) => {
+                    let zero = push(function, InstKind::Const(Constant::zero(ty)?), ty, inst, added);
+                    function.inst_mut(inst).kind = InstKind::Compare { op: CompareOp::Ne, left: value, right: zero };
+                }
+                _ => return None,
+            }
+            Some("removed `truth` of a boxed value".to_string())
+        }
+        _ => None,
+    }
+}
+
+fn is_number(function: &Function, value: Value) -> bool {
+    matches!(function.value_type(value), Type::Int | Type::F64)
+}
+
+/// The unboxed operands of an arithmetic operation on `object`s, both ints
+/// or both floats, with an int converted to a float if the other is one.
+fn arithmetic_operands(function: &mut Function, left: Value, right: Value, like: Inst, added: &mut Vec<Inst>) -> Option<(Value, Value)> {
+    let numbers = [known_class(function, left)?, known_class(function, right)?];
+    if !numbers.iter().all(|class| matches!(class.unboxed(), Type::Int | Type::F64)) {
+        return None;
+    }
+    let mut left = unboxed(function, left, like, added)?;
+    let mut right = unboxed(function, right, like, added)?;
+    match (function.value_type(left), function.value_type(right)) {
+        (Type::Int, Type::F64) => left = push(function, InstKind::Cast(left), Type::F64, like, added),
+        (Type::F64, Type::Int) => right = push(function, InstKind::Cast(right), Type::F64, like, added),
+        _ => {}
+    }
+    Some((left, right))
+}
+
+/// The unboxed value of `value`, an `object` that was boxed or is a
+/// constant, with the constant added to `added`. An `i64` becomes an `int`,
+/// to compute as the object would.
+fn unboxed(function: &mut Function, value: Value, like: Inst, added: &mut Vec<Inst>) -> Option<Value> {
+    let ValueDef::Result(def) = function.value(value).def else {
+        return None;
+    };
+    match function.inst(def).kind.clone() {
+        InstKind::Box(value) if function.value_type(value) == Type::I64 => Some(push(function, InstKind::Cast(value), Type::Int, like, added)),
+        InstKind::Box(value) => Some(value),
+        InstKind::Const(constant @ (Constant::Int(_) | Constant::BigInt(_) | Constant::Float(_) | Constant::Bool(_))) => {
+            let ty = constant.ty();
+            Some(push(function, InstKind::Const(constant), ty, like, added))
+        }
+        _ => None,
+    }
+}
+
+/// Makes an instruction for `like`, added to `added`, and returns its
+/// result.
+fn push(function: &mut Function, kind: InstKind, ty: Type, like: Inst, added: &mut Vec<Inst>) -> Value {
+    let inst = function.make_inst_like(like, kind, Some(ty));
+    added.push(inst);
+    function.result(inst).expect("the instruction has a result")
+}
+
+/// Makes the functions that return only boxed values of one type return
+/// them unboxed, boxing the results of their calls instead. Returns how
+/// many functions changed.
+fn unbox_returns(ir: &mut Module) -> usize {
+    let mut changed = Vec::new();
+    for function in &mut ir.functions {
+        if function.name == "__main__" || function.return_type != Type::Object {
+            continue;
+        }
+        let returns: Vec<(Block, Option<Value>)> = function
+            .layout
+            .iter()
+            .filter_map(|block| match function.terminator(*block) {
+                Some(Terminator::Return(value)) => Some((*block, value.and_then(|value| boxed(function, value)))),
+                _ => None,
+            })
+            .collect();
+        let Some(ty) = returns.first().and_then(|(_, value)| *value).map(|value| function.value_type(value)) else {
+            continue;
+        };
+        let same_type = returns.iter().all(|(_, value)| value.is_some_and(|value| function.value_type(value) == ty));
+        if !same_type || !matches!(ty, Type::Int | Type::F64 | Type::Bool) {
+            continue;
+        }
+        for (block, value) in returns {
+            function.block_mut(block).terminator = Some(Terminator::Return(value));
+        }
+        function.return_type = ty;
+        changed.push((function.name.clone(), ty));
+    }
+    for function in &mut ir.functions {
+        for block in function.layout.clone() {
+            for (index, inst) in function.block(block).insts.clone().into_iter().enumerate().rev() {
+                let data = function.inst(inst);
+                let (InstKind::Call { function: callee, .. }, Some(result)) = (&data.kind, data.result) else {
+                    continue;
+                };
+                let Some(&(_, ty)) = changed.iter().find(|(name, _)| name == callee) else {
+                    continue;
+                };
+                if function.value_type(result) != Type::Object {
+                    continue;
+                }
+                let boxed = function.make_inst_like(inst, InstKind::Box(result), Some(Type::Object));
+                let value = function.result(boxed).expect("boxes have a result");
+                function.replace_uses(result, value);
+                function.values[result.0 as usize].ty = ty;
+                function.block_mut(block).insts.insert(index + 1, boxed);
+            }
+        }
+    }
+    changed.len()
+}
+
+/// The value boxed into `value`.
+fn boxed(function: &Function, value: Value) -> Option<Value> {
+    let ValueDef::Result(inst) = function.value(value).def else {
+        return None;
+    };
+    match function.inst(inst).kind {
+        InstKind::Box(value) => Some(value),
+        _ => None,
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::loops::{Loop, LoopNest};
+use crate::ir::ir::{Block, Function, Module, Terminator, Value};
+use crate::optimizer::loops::{counted_loop, defining_block, inst_blocks, values_used_outside};
+use crate::optimizer::optimizer::{Optimization, PassContext};
+use crate::optimizer::remarks::RemarkKind;
+
+/// Full loop unrolling. A counted loop that the header runs a constant
+/// number of times, at most `max_trip_count`, is replaced by as many copies
+/// of its blocks, each entering the next, when they have at most `max_size`
+/// instructions in all; constant propagation then specializes each copy on
+/// its value of the counter. The loop itself stays after the copies,
+/// entered with the counter at its bound, for `sccp` to remove.
+///
+/// Only innermost loops that leave from the header alone, to a block that
+/// nothing else branches to, are unrolled.
+pub struct LoopUnrolling {
+    pub max_trip_count: usize,
+    pub max_size: usize,
+}
+
+impl LoopUnrolling {
+    pub const DEFAULT_MAX_TRIP_COUNT: usize = 8;
+    pub const DEFAULT_MAX_SIZE: usize = 64;
+
+    pub fn new() -> Self {
+        LoopUnrolling { max_trip_count: Self::DEFAULT_MAX_TRIP_COUNT, max_size: Self::DEFAULT_MAX_SIZE }
+    }
+}
+
+impl Default for LoopUnrolling {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimization for LoopUnrolling {
+    fn name(&self) -> &'static str {
+        "loop-unroll"
+    }
+
+    fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+        let mut unrolled = 0;
+        for function in &mut ir.functions {
+            let mut tried: HashSet<Block> = HashSet::new();
+            // Unrolling changes the loops; find them again after each.
+            loop {
+                let nest = context.analyses.loops(function);
+                let mut candidate = None;
+                for l in nest.loops() {
+                    if tried.insert(nest.loop_data(l).header) {
+                        if let Some(trip_count) = self.trip_count(function, &nest, l, context) {
+                            candidate = Some((l, trip_count));
+                            break;
+                        }
+                    }
+                }
+                let Some((l, trip_count)) = candidate else {
+                    break;
+                };
+                unroll(function, &nest, l, trip_count);
+                context.analyses.invalidate(&function.name);
+                unrolled += 1;
+            }
+        }
+        context.count("loops unrolled", unrolled);
+        Ok(unrolled > 0)
+    }
+}
+
+impl LoopUnrolling {
+    /// The number of copies to replace the loop with, if it is unrolled.
+    fn trip_count(&self, function: &Function, nest: &LoopNest, l: Loop, context: &mut PassContext) -> Option<usize> {
+        let data = nest.loop_data(l);
+        let preheader = nest.preheader(function, l)?;
+        let blocks_of = inst_blocks(function);
+        let counted = counted_loop(function, &blocks_of, nest, l, preheader)?;
+        let trip_count = counted.trip_count(function).filter(|count| *count > 0)?;
+        let span = function.inst(counted.compare).span;
+        let mut missed = |reason: String| {
+            context.remark(RemarkKind::Missed, &function.name, span, format!("did not unroll the loop: {}", reason));
+            None
+        };
+        if !data.children.is_empty() {
+            return missed("it contains another loop".to_string());
+        }
+        let exits = nest.exits(function, l);
+        let single_exit = exits == [(data.header, counted.exit)] && function.predecessors()[&counted.exit] == [data.header];
+        let escaping = values_used_outside(function, &blocks_of, &data.blocks);
+        if !single_exit || escaping.iter().any(|value| defining_block(function, &blocks_of, *value) != Some(data.header)) {
+            return missed("it leaves from more than its header".to_string());
+        }
+        if trip_count > self.max_trip_count as i128 {
+            return missed(format!("it runs {} times, more than the limit of {}", trip_count, self.max_trip_count));
+        }
+        let size: usize = data.blocks.iter().map(|block| function.block(*block).insts.len()).sum();
+        let trip_count = trip_count as usize;
+        if size * trip_count > self.max_size {
+            let message = format!(
+                "{} copies of its {} instructions would be more than the limit of {}",
+                trip_count, size, self.max_size
+            );
+            return missed(message);
+        }
+        let message = format!("unrolled the loop, which runs {} times", trip_count);
+        context.remark(RemarkKind::Passed, &function.name, span, message);
+        Some(trip_count)
+    }
+}
+
+/// Puts `count` copies of the loop before it. The values of the header used
+/// after the loop are passed to its exit first, as the copies leave to it
+/// too.
+fn unroll(function: &mut Function, nest: &LoopNest, l: Loop, count: usize) {
+    let data = nest.loop_data(l);
+    let exit = nest.exits(function, l)[0].1;
+    let blocks_of = inst_blocks(function);
+    for value in values_used_outside(function, &blocks_of, &data.blocks) {
+        let param = function.append_block_param(exit, function.value_type(value));
+        if let Some(terminator) = &mut function.block_mut(data.header).terminator {
+            for target in terminator.successors_mut() {
+                if target.block == exit {
+                    target.arguments.push(value);
+                }
+            }
+        }
+        replace_uses_outside(function, &data.blocks, value, param);
+    }
+    for _ in 0..count {
+        peel(function, &data.blocks, data.header);
+    }
+}
+
+/// Replaces the uses of `old` by `new` outside of `blocks`.
+fn replace_uses_outside(function: &mut Function, blocks: &[Block], old: Value, new: Value) {
+    for block in function.layout.clone().into_iter().filter(|block| !blocks.contains(block)) {
+        for inst in function.block(block).insts.clone() {
+            for operand in function.inst_mut(inst).kind.operands_mut() {
+                if *operand == old {
+                    *operand = new;
+                }
+            }
+        }
+        if let Some(terminator) = &mut function.block_mut(block).terminator {
+            for operand in terminator.operands_mut() {
+                if *operand == old {
+                    *operand = new;
+                }
+            }
+        }
+    }
+}
+
+/// Copies the blocks of the loop, which its entries now branch to, and whose
+/// back edges go to the loop itself. The copies are laid out before it.
+fn peel(function: &mut Function, blocks: &[Block], header: Block) {
+    let predecessors = function.predecessors();
+    let entries: Vec<Block> = predecessors[&header].iter().copied().filter(|block| !blocks.contains(block)).collect();
+
+    let layout_end = function.layout.len();
+    let mut values: HashMap<Value, Value> = HashMap::new();
+    let mut copies: HashMap<Block, Block> = HashMap::new();
+    for block in blocks {
+        let copy = function.create_block();
+        copies.insert(*block, copy);
+        for param in function.block(*block).params.clone() {
+            let value = function.append_block_param(copy, function.value_type(param));
+            values.insert(param, value);
+        }
+        for inst in function.block(*block).insts.clone() {
+            let data = function.inst(inst).clone();
+            let copy_inst = function.make_inst_like(inst, data.kind, data.result.map(|value| function.value_type(value)));
+            if let (Some(original), Some(value)) = (data.result, function.result(copy_inst)) {
+                values.insert(original, value);
+            }
+            function.block_mut(copy).insts.push(copy_inst);
+        }
+    }
+    for block in blocks {
+        let copy = copies[block];
+        for inst in function.block(copy).insts.clone() {
+            for operand in function.inst_mut(inst).kind.operands_mut() {
+                *operand = values.get(operand).copied().unwrap_or(*operand);
+            }
+        }
+        let mut terminator = function.terminator(*block).cloned().unwrap_or(Terminator::Unreachable);
+        for operand in terminator.operands_mut() {
+            *operand = values.get(operand).copied().unwrap_or(*operand);
+        }
+        for target in terminator.successors_mut() {
+            if target.block != header {
+                target.block = copies.get(&target.block).copied().unwrap_or(target.block);
+            }
+        }
+        function.block_mut(copy).terminator = Some(terminator);
+    }
+    for entry in entries {
+        if let Some(terminator) = &mut function.block_mut(entry).terminator {
+            for target in terminator.successors_mut() {
+                if target.block == header {
+                    target.block = copies[&header];
+                }
+            }
+        }
+    }
+
+    let added = function.layout.split_off(layout_end);
+    let position = function.layout.iter().position(|block| *block == header).expect("the header is in the layout");
+    function.layout.splice(position..position, added);
+}
@@ -0,0 +1,175 @@
+use crate::lexer::lexer::{Span, SpannedToken, TokenType};
+
+/// The kind of an interior node in the concrete syntax tree.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyntaxKind {
+    Program,
+    Block,
+    VariableDeclaration,
+    ExpressionStatement,
+    Assignment,
+    AugmentedAssignment,
+    AnnotatedAssignment,
+    ImportStatement,
+    IfStatement,
+    WhileStatement,
+    ForStatement,
+    ElseClause,
+    TryStatement,
+    ExceptClause,
+    FinallyClause,
+    WithStatement,
+    WithItem,
+    FunctionDefinition,
+    ClassDefinition,
+    Decorator,
+    ParameterList,
+    Parameter,
+    ReturnStatement,
+    RaiseStatement,
+    GlobalStatement,
+    NonlocalStatement,
+    PassStatement,
+    BreakStatement,
+    ContinueStatement,
+    BinaryExpression,
+    UnaryExpression,
+    BooleanExpression,
+    ComparisonExpression,
+    ConditionalExpression,
+    LambdaExpression,
+    ParenthesizedExpression,
+    TupleExpression,
+    ListExpression,
+    DictOrSetExpression,
+    Comprehension,
+    ComprehensionClause,
+    StarredExpression,
+    CallExpression,
+    ArgumentList,
+    Argument,
+    AttributeExpression,
+    SubscriptExpression,
+    SliceExpression,
+    Name,
+    Number,
+    String,
+    Constant,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum CstElement {
+    Node(CstNode),
+    Token(SpannedToken),
+}
+
+/// A lossless syntax tree node.
+///
+/// Every token consumed by the parser is kept, together with the whitespace,
+/// comments and newlines in front of it, so `to_source` returns the exact
+/// text the tree was parsed from.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct CstNode {
+    pub kind: SyntaxKind,
+    pub children: Vec<CstElement>,
+}
+
+impl CstNode {
+    pub fn to_source(&self) -> String {
+        let mut source = String::new();
+        for token in self.tokens() {
+            token.write_source(&mut source);
+        }
+        source
+    }
+
+    /// All tokens below this node in source order, including `Eof`.
+    pub fn tokens(&self) -> Vec<&SpannedToken> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a SpannedToken>) {
+        for child in &self.children {
+            match child {
+                CstElement::Node(node) => node.collect_tokens(tokens),
+                CstElement::Token(token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// Span of the node's tokens, excluding leading trivia and `Eof`.
+    pub fn span(&self) -> Span {
+        let tokens: Vec<_> = self
+            .tokens()
+            .into_iter()
+            .filter(|token| token.token.token_type != TokenType::Eof)
+            .collect();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => first.span.to(last.span),
+            _ => Span::default(),
+        }
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = &CstNode> {
+        self.children.iter().filter_map(|child| match child {
+            CstElement::Node(node) => Some(node),
+            CstElement::Token(_) => None,
+        })
+    }
+}
+
+/// Position in the builder that a node can later be started at, used for
+/// constructs such as binary expressions whose kind is only known after
+/// their first operand has been parsed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Checkpoint(usize);
+
+pub(crate) struct CstBuilder {
+    stack: Vec<(SyntaxKind, Vec<CstElement>)>,
+}
+
+impl CstBuilder {
+    pub(crate) fn new(root: SyntaxKind) -> Self {
+        CstBuilder { stack: vec![(root, Vec::new())] }
+    }
+
+    pub(crate) fn start_node(&mut self, kind: SyntaxKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.current_children().len())
+    }
+
+    pub(crate) fn start_node_at(&mut self, checkpoint: Checkpoint, kind: SyntaxKind) {
+        let children = self.current_children_mut().split_off(checkpoint.0);
+        self.stack.push((kind, children));
+    }
+
+    pub(crate) fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("unbalanced syntax tree builder");
+        self.current_children_mut().push(CstElement::Node(CstNode { kind, children }));
+    }
+
+    pub(crate) fn token(&mut self, token: SpannedToken) {
+        self.current_children_mut().push(CstElement::Token(token));
+    }
+
+    pub(crate) fn finish(mut self) -> CstNode {
+        while self.stack.len() > 1 {
+            self.finish_node();
+        }
+        let (kind, children) = self.stack.pop().expect("unbalanced syntax tree builder");
+        CstNode { kind, children }
+    }
+
+    fn current_children(&self) -> &Vec<CstElement> {
+        &self.stack.last().expect("unbalanced syntax tree builder").1
+    }
+
+    fn current_children_mut(&mut self) -> &mut Vec<CstElement> {
+        &mut self.stack.last_mut().expect("unbalanced syntax tree builder").1
+    }
+}
@@ -0,0 +1,4 @@
+pub mod cst;
+pub mod parser;
+pub mod serialize;
+pub mod visitor;
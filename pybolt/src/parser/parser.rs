@@ -1,128 +1,1383 @@
-use crate::lexer::lexer::{Lexer, Token, TokenType};
+use crate::lexer::lexer::{Lexer, Span, SpannedToken, Token, TokenType};
+use crate::parser::cst::{Checkpoint, CstBuilder, CstNode, SyntaxKind};
+use crate::runtime::bigint::BigInt;
 
 #[derive(Debug, Clone)]
 pub enum ASTNode {
     Program(Vec<ASTNode>),
-    VariableDeclaration { name: String, value: Box<ASTNode> },
+    VariableDeclaration { name: String, value: Box<ASTNode>, span: Span },
     Expression(ASTNodeType),
-    Number(i32),
+    Number(i64),
+    /// An integer literal that does not fit in an `i64`.
+    BigNumber(BigInt),
     BinaryOperation { left: Box<ASTNode>, operator: String, right: Box<ASTNode> },
+    Float(f64),
+    /// An imaginary literal such as `2j`, by its imaginary part.
+    Imaginary(f64),
+    StringLiteral(String),
+    /// A bytes literal such as `b"\x00"`.
+    Bytes(Vec<u8>),
+    /// An f-string, by its parts: `StringLiteral`s and `FormattedValue`s.
+    JoinedStr(Vec<ASTNode>),
+    /// A replacement field of an f-string, `{value!conversion:format_spec}`,
+    /// whose format spec is a `JoinedStr` of its own.
+    FormattedValue { value: Box<ASTNode>, conversion: Option<char>, format_spec: Option<Box<ASTNode>> },
+    Boolean(bool),
+    NoneLiteral,
+    Ellipsis,
+    UnaryOperation { operator: String, operand: Box<ASTNode> },
+    BooleanOperation { operator: String, values: Vec<ASTNode> },
+    Comparison { left: Box<ASTNode>, operators: Vec<String>, comparators: Vec<ASTNode> },
+    Conditional { condition: Box<ASTNode>, body: Box<ASTNode>, orelse: Box<ASTNode> },
+    Call { function: Box<ASTNode>, arguments: Vec<ASTNode>, keywords: Vec<KeywordArgument>, span: Span },
+    Attribute { value: Box<ASTNode>, attribute: String },
+    Subscript { value: Box<ASTNode>, index: Box<ASTNode> },
+    Slice { lower: Option<Box<ASTNode>>, upper: Option<Box<ASTNode>>, step: Option<Box<ASTNode>> },
+    Starred(Box<ASTNode>),
+    List(Vec<ASTNode>),
+    Tuple(Vec<ASTNode>),
+    Set(Vec<ASTNode>),
+    Dict(Vec<(ASTNode, ASTNode)>),
+    Comprehension { kind: ComprehensionKind, element: Box<ASTNode>, generators: Vec<ComprehensionGenerator> },
+    Lambda { parameters: Vec<Parameter>, body: Box<ASTNode> },
+    ExpressionStatement { expression: Box<ASTNode>, span: Span },
+    Assignment { targets: Vec<ASTNode>, value: Box<ASTNode>, span: Span },
+    AugmentedAssignment { target: Box<ASTNode>, operator: String, value: Box<ASTNode>, span: Span },
+    AnnotatedAssignment { target: Box<ASTNode>, annotation: Box<ASTNode>, value: Option<Box<ASTNode>>, span: Span },
+    If { condition: Box<ASTNode>, body: Vec<ASTNode>, orelse: Vec<ASTNode>, span: Span },
+    While { condition: Box<ASTNode>, body: Vec<ASTNode>, orelse: Vec<ASTNode>, span: Span },
+    For { target: Box<ASTNode>, iterable: Box<ASTNode>, body: Vec<ASTNode>, orelse: Vec<ASTNode>, span: Span },
+    FunctionDefinition(FunctionDefinition),
+    ClassDefinition(ClassDefinition),
+    Return { value: Option<Box<ASTNode>>, span: Span },
+    Try { body: Vec<ASTNode>, handlers: Vec<ExceptHandler>, orelse: Vec<ASTNode>, finalbody: Vec<ASTNode>, span: Span },
+    Raise { exception: Option<Box<ASTNode>>, cause: Option<Box<ASTNode>>, span: Span },
+    With { items: Vec<WithItem>, body: Vec<ASTNode>, span: Span },
+    Import { names: Vec<ImportAlias>, span: Span },
+    /// `from module import names`; `module` keeps the leading dots of a
+    /// relative import.
+    ImportFrom { module: String, names: Vec<ImportAlias>, span: Span },
+    Global { names: Vec<String>, span: Span },
+    Nonlocal { names: Vec<String>, span: Span },
+    Pass { span: Span },
+    Break { span: Span },
+    Continue { span: Span },
 }
 
 #[derive(Debug, Clone)]
 pub enum ASTNodeType {
     Identifier(String),
-    Number(i32),
+    Number(i64),
     BinaryOperation { operator: String, left: Box<ASTNode>, right: Box<ASTNode> },
 }
 
+#[derive(Debug, Clone)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub body: Vec<ASTNode>,
+    pub decorators: Vec<ASTNode>,
+    pub returns: Option<Box<ASTNode>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassDefinition {
+    pub name: String,
+    pub bases: Vec<ASTNode>,
+    pub keywords: Vec<KeywordArgument>,
+    pub body: Vec<ASTNode>,
+    pub decorators: Vec<ASTNode>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    PositionalOnly,
+    PositionalOrKeyword,
+    VarPositional,
+    KeywordOnly,
+    VarKeyword,
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    pub annotation: Option<ASTNode>,
+    pub default: Option<ASTNode>,
+    pub span: Span,
+}
+
+/// `except exception_type as name:`; both are `None` for a bare `except:`.
+#[derive(Debug, Clone)]
+pub struct ExceptHandler {
+    pub exception_type: Option<ASTNode>,
+    pub name: Option<String>,
+    pub body: Vec<ASTNode>,
+    pub span: Span,
+}
+
+/// `context as target` in a `with` statement.
+#[derive(Debug, Clone)]
+pub struct WithItem {
+    pub context: ASTNode,
+    pub target: Option<ASTNode>,
+}
+
+/// `name as alias` in an import; `name` is `*` for `from module import *`.
+#[derive(Debug, Clone)]
+pub struct ImportAlias {
+    pub name: String,
+    pub alias: Option<String>,
+}
+
+impl ImportAlias {
+    /// The name the import binds: the alias, or the first component of a
+    /// dotted module name.
+    pub fn bound_name(&self) -> &str {
+        match &self.alias {
+            Some(alias) => alias,
+            None => self.name.split('.').next().unwrap_or(&self.name),
+        }
+    }
+}
+
+/// A `name=value` argument in a call or class header; `name` is `None` for
+/// `**mapping` unpacking.
+#[derive(Debug, Clone)]
+pub struct KeywordArgument {
+    pub name: Option<String>,
+    pub value: ASTNode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComprehensionKind {
+    List,
+    Set,
+    Generator,
+}
+
+/// One `for target in iterable if condition...` clause of a comprehension.
+#[derive(Debug, Clone)]
+pub struct ComprehensionGenerator {
+    pub target: ASTNode,
+    pub iterable: ASTNode,
+    pub conditions: Vec<ASTNode>,
+}
+
+impl ASTNode {
+    /// Source span of statement nodes and calls; `None` for other expressions.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ASTNode::VariableDeclaration { span, .. }
+            | ASTNode::Call { span, .. }
+            | ASTNode::ExpressionStatement { span, .. }
+            | ASTNode::Assignment { span, .. }
+            | ASTNode::AugmentedAssignment { span, .. }
+            | ASTNode::AnnotatedAssignment { span, .. }
+            | ASTNode::Import { span, .. }
+            | ASTNode::ImportFrom { span, .. }
+            | ASTNode::If { span, .. }
+            | ASTNode::While { span, .. }
+            | ASTNode::For { span, .. }
+            | ASTNode::Return { span, .. }
+            | ASTNode::Try { span, .. }
+            | ASTNode::Raise { span, .. }
+            | ASTNode::With { span, .. }
+            | ASTNode::Global { span, .. }
+            | ASTNode::Nonlocal { span, .. }
+            | ASTNode::Pass { span }
+            | ASTNode::Break { span }
+            | ASTNode::Continue { span } => Some(*span),
+            ASTNode::FunctionDefinition(function) => Some(function.span),
+            ASTNode::ClassDefinition(class) => Some(class.span),
+            _ => None,
+        }
+    }
+}
+
+const AUGMENTED_ASSIGNMENT_OPERATORS: &[&str] =
+    &["+=", "-=", "*=", "/=", "//=", "%=", "**=", "@=", "&=", "|=", "^=", "<<=", ">>="];
+
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
-    current_token: Option<Token>,
+    current: Option<SpannedToken>,
+    previous_end: usize,
     ast: Option<ASTNode>,
+    cst: Option<CstBuilder>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(mut lexer: Lexer<'a>) -> Self {
-        let current_token = lexer.next_token();
-        Parser { lexer, current_token, ast: None }
+        let current = lexer.next_spanned_token();
+        Parser { lexer, current, previous_end: 0, ast: None, cst: None }
     }
 
     pub fn parse(&mut self) -> Result<ASTNode, String> {
         let mut nodes = Vec::new();
-        while self.current_token.is_some() && self.current_token.as_ref().unwrap().token_type != TokenType::Eof {
-            nodes.push(self.parse_statement()?);
+        while self.current_token().is_some_and(|token| token.token_type != TokenType::Eof) {
+            if self.match_token(&TokenType::Newline, "") {
+                self.advance();
+                continue;
+            }
+            self.parse_statement(&mut nodes)?;
         }
+        self.advance();
         let program_node = ASTNode::Program(nodes);
         self.ast = Some(program_node.clone());
         Ok(program_node)
     }
 
+    /// Parses the input like `parse` and additionally returns a lossless
+    /// concrete syntax tree that keeps whitespace, comments and parentheses.
+    pub fn parse_cst(&mut self) -> Result<CstNode, String> {
+        self.cst = Some(CstBuilder::new(SyntaxKind::Program));
+        let result = self.parse();
+        let builder = self.cst.take().expect("CST builder was installed above");
+        result?;
+        Ok(builder.finish())
+    }
+
     pub fn get_ast(&self) -> ASTNode {
         self.ast.clone().unwrap_or(ASTNode::Program(vec![]))
     }
 
-    fn parse_statement(&mut self) -> Result<ASTNode, String> {
-        if self.match_token(&TokenType::Keyword, "let") {
-            self.parse_variable_declaration()
+    /// The source text being parsed.
+    pub fn source(&self) -> &'a str {
+        self.lexer.source()
+    }
+
+    fn parse_statement(&mut self, statements: &mut Vec<ASTNode>) -> Result<(), String> {
+        let token = self.current_token().cloned().ok_or("Unexpected end of input")?;
+        match (&token.token_type, token.lexeme.as_str()) {
+            (TokenType::Keyword, "if") => statements.push(self.parse_if()?),
+            (TokenType::Keyword, "while") => statements.push(self.parse_while()?),
+            (TokenType::Keyword, "for") => statements.push(self.parse_for()?),
+            (TokenType::Keyword, "try") => statements.push(self.parse_try()?),
+            (TokenType::Keyword, "with") => statements.push(self.parse_with()?),
+            (TokenType::Keyword, "def") => statements.push(self.parse_function_definition(Vec::new(), None)?),
+            (TokenType::Keyword, "class") => statements.push(self.parse_class_definition(Vec::new(), None)?),
+            (TokenType::Operator, "@") => statements.push(self.parse_decorated()?),
+            _ => self.parse_simple_statements(statements)?,
+        }
+        Ok(())
+    }
+
+    /// `simple_stmt (';' simple_stmt)* [';'] NEWLINE`
+    fn parse_simple_statements(&mut self, statements: &mut Vec<ASTNode>) -> Result<(), String> {
+        loop {
+            statements.push(self.parse_simple_statement()?);
+            if !self.match_token(&TokenType::Punctuation, ";") {
+                break;
+            }
+            self.advance();
+            if self.at_line_end() {
+                break;
+            }
+        }
+        self.expect_line_end()
+    }
+
+    fn parse_simple_statement(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        let token = self.current_token().cloned().ok_or("Unexpected end of input")?;
+        if token.token_type != TokenType::Keyword {
+            return self.parse_expression_statement();
+        }
+        match token.lexeme.as_str() {
+            "let" => self.parse_variable_declaration(),
+            "pass" | "break" | "continue" => {
+                let kind = match token.lexeme.as_str() {
+                    "pass" => SyntaxKind::PassStatement,
+                    "break" => SyntaxKind::BreakStatement,
+                    _ => SyntaxKind::ContinueStatement,
+                };
+                self.start_node(kind);
+                self.advance();
+                self.finish_node();
+                let span = self.span_from(start);
+                Ok(match token.lexeme.as_str() {
+                    "pass" => ASTNode::Pass { span },
+                    "break" => ASTNode::Break { span },
+                    _ => ASTNode::Continue { span },
+                })
+            }
+            "return" => {
+                self.start_node(SyntaxKind::ReturnStatement);
+                self.advance();
+                let value = if self.at_line_end() || self.match_token(&TokenType::Punctuation, ";") {
+                    None
+                } else {
+                    Some(Box::new(self.parse_expression_list()?))
+                };
+                self.finish_node();
+                Ok(ASTNode::Return { value, span: self.span_from(start) })
+            }
+            "raise" => {
+                self.start_node(SyntaxKind::RaiseStatement);
+                self.advance();
+                let mut exception = None;
+                let mut cause = None;
+                if !self.at_line_end() && !self.match_token(&TokenType::Punctuation, ";") {
+                    exception = Some(Box::new(self.parse_expression()?));
+                    if self.match_token(&TokenType::Keyword, "from") {
+                        self.advance();
+                        cause = Some(Box::new(self.parse_expression()?));
+                    }
+                }
+                self.finish_node();
+                Ok(ASTNode::Raise { exception, cause, span: self.span_from(start) })
+            }
+            "import" | "from" => self.parse_import(),
+            "global" | "nonlocal" => {
+                let is_global = token.lexeme == "global";
+                self.start_node(if is_global { SyntaxKind::GlobalStatement } else { SyntaxKind::NonlocalStatement });
+                self.advance();
+                let mut names = vec![self.expect_token(TokenType::Identifier, "")?.lexeme];
+                while self.match_token(&TokenType::Punctuation, ",") {
+                    self.advance();
+                    names.push(self.expect_token(TokenType::Identifier, "")?.lexeme);
+                }
+                self.finish_node();
+                let span = self.span_from(start);
+                Ok(if is_global { ASTNode::Global { names, span } } else { ASTNode::Nonlocal { names, span } })
+            }
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    /// `import a.b as c, d` or `from .a import (b as c, d)`
+    fn parse_import(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::ImportStatement);
+        let node = if self.match_token(&TokenType::Keyword, "import") {
+            self.advance();
+            let mut names = Vec::new();
+            loop {
+                let name = self.parse_dotted_name()?;
+                names.push(ImportAlias { name, alias: self.parse_import_alias()? });
+                if !self.match_token(&TokenType::Punctuation, ",") {
+                    break;
+                }
+                self.advance();
+            }
+            ASTNode::Import { names, span: Span::default() }
         } else {
-            self.parse_expression()
+            self.expect_token(TokenType::Keyword, "from")?;
+            let mut module = String::new();
+            while self.match_token(&TokenType::Punctuation, ".") || self.match_token(&TokenType::Punctuation, "...") {
+                module.push_str(&self.advance().expect("dot was just matched").lexeme);
+            }
+            if !self.match_token(&TokenType::Keyword, "import") {
+                module.push_str(&self.parse_dotted_name()?);
+            }
+            self.expect_token(TokenType::Keyword, "import")?;
+            let mut names = Vec::new();
+            if self.match_token(&TokenType::Operator, "*") {
+                self.advance();
+                names.push(ImportAlias { name: "*".to_string(), alias: None });
+            } else {
+                let parenthesized = self.match_token(&TokenType::Punctuation, "(");
+                if parenthesized {
+                    self.advance();
+                }
+                loop {
+                    let name = self.expect_token(TokenType::Identifier, "")?.lexeme;
+                    names.push(ImportAlias { name, alias: self.parse_import_alias()? });
+                    if !self.match_token(&TokenType::Punctuation, ",") {
+                        break;
+                    }
+                    self.advance();
+                    if parenthesized && self.match_token(&TokenType::Punctuation, ")") {
+                        break;
+                    }
+                }
+                if parenthesized {
+                    self.expect_token(TokenType::Punctuation, ")")?;
+                }
+            }
+            ASTNode::ImportFrom { module, names, span: Span::default() }
+        };
+        self.finish_node();
+        let span = self.span_from(start);
+        Ok(match node {
+            ASTNode::Import { names, .. } => ASTNode::Import { names, span },
+            ASTNode::ImportFrom { module, names, .. } => ASTNode::ImportFrom { module, names, span },
+            _ => unreachable!("only import statements are built above"),
+        })
+    }
+
+    fn parse_dotted_name(&mut self) -> Result<String, String> {
+        let mut name = self.expect_token(TokenType::Identifier, "")?.lexeme;
+        while self.match_token(&TokenType::Punctuation, ".") {
+            self.advance();
+            name.push('.');
+            name.push_str(&self.expect_token(TokenType::Identifier, "")?.lexeme);
         }
+        Ok(name)
+    }
+
+    fn parse_import_alias(&mut self) -> Result<Option<String>, String> {
+        if !self.match_token(&TokenType::Keyword, "as") {
+            return Ok(None);
+        }
+        self.advance();
+        Ok(Some(self.expect_token(TokenType::Identifier, "")?.lexeme))
     }
 
     fn parse_variable_declaration(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::VariableDeclaration);
         self.expect_token(TokenType::Keyword, "let")?;
         let name = self.expect_token(TokenType::Identifier, "")?.lexeme.clone();
         self.expect_token(TokenType::Operator, "=")?;
         let value = self.parse_expression()?;
-        self.expect_token(TokenType::Punctuation, ";")?;
-        Ok(ASTNode::VariableDeclaration { name, value: Box::new(value) })
+        self.finish_node();
+        Ok(ASTNode::VariableDeclaration { name, value: Box::new(value), span: self.span_from(start) })
     }
 
-    fn parse_expression(&mut self) -> Result<ASTNode, String> {
-        self.parse_binary_operation(0)
+    /// An expression statement, assignment (`a = b = value`), augmented
+    /// assignment (`a += value`) or annotated assignment (`a: int = value`).
+    fn parse_expression_statement(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        let checkpoint = self.checkpoint();
+        let first = self.parse_expression_list()?;
+        if self.match_token(&TokenType::Punctuation, ":") {
+            self.start_node_at(checkpoint, SyntaxKind::AnnotatedAssignment);
+            self.advance();
+            let annotation = self.parse_expression()?;
+            let value = if self.match_token(&TokenType::Operator, "=") {
+                self.advance();
+                Some(Box::new(self.parse_expression_list()?))
+            } else {
+                None
+            };
+            self.finish_node();
+            if !matches!(first, ASTNode::Expression(ASTNodeType::Identifier(_)) | ASTNode::Attribute { .. } | ASTNode::Subscript { .. }) {
+                return Err("Illegal target for annotation".to_string());
+            }
+            return Ok(ASTNode::AnnotatedAssignment {
+                target: Box::new(first),
+                annotation: Box::new(annotation),
+                value,
+                span: self.span_from(start),
+            });
+        }
+        if self.match_token(&TokenType::Operator, "=") {
+            self.start_node_at(checkpoint, SyntaxKind::Assignment);
+            let mut targets = vec![first];
+            let mut value = None;
+            while self.match_token(&TokenType::Operator, "=") {
+                self.advance();
+                let next = self.parse_expression_list()?;
+                if let Some(previous) = value.replace(next) {
+                    targets.push(previous);
+                }
+            }
+            self.finish_node();
+            for target in &targets {
+                self.check_assignment_target(target)?;
+            }
+            let value = value.expect("at least one value follows '='");
+            return Ok(ASTNode::Assignment { targets, value: Box::new(value), span: self.span_from(start) });
+        }
+        if let Some(operator) = self
+            .current_token()
+            .filter(|token| token.token_type == TokenType::Operator)
+            .map(|token| token.lexeme.clone())
+            .filter(|lexeme| AUGMENTED_ASSIGNMENT_OPERATORS.contains(&lexeme.as_str()))
+        {
+            self.start_node_at(checkpoint, SyntaxKind::AugmentedAssignment);
+            self.advance();
+            let value = self.parse_expression_list()?;
+            self.finish_node();
+            if !matches!(first, ASTNode::Expression(ASTNodeType::Identifier(_)) | ASTNode::Attribute { .. } | ASTNode::Subscript { .. }) {
+                return Err("Illegal target for augmented assignment".to_string());
+            }
+            return Ok(ASTNode::AugmentedAssignment {
+                target: Box::new(first),
+                operator: operator.trim_end_matches('=').to_string(),
+                value: Box::new(value),
+                span: self.span_from(start),
+            });
+        }
+        self.start_node_at(checkpoint, SyntaxKind::ExpressionStatement);
+        self.finish_node();
+        Ok(ASTNode::ExpressionStatement { expression: Box::new(first), span: self.span_from(start) })
     }
 
-    fn parse_binary_operation(&mut self, min_precedence: u8) -> Result<ASTNode, String> {
-        let mut left = self.parse_primary()?;
-        while let Some(op) = self.current_token.clone() {
-            if let TokenType::Operator = op.token_type {
-                let precedence = self.get_precedence(&op.lexeme);
-                if precedence >= min_precedence {
-                    self.current_token = self.lexer.next_token();
-                    let mut right = self.parse_primary()?;
-                    while let Some(next_op) = self.current_token.clone() {
-                        if let TokenType::Operator = next_op.token_type {
-                            let next_precedence = self.get_precedence(&next_op.lexeme);
-                            if next_precedence > precedence {
-                                right = self.parse_binary_operation(next_precedence)?;
-                            } else {
-                                break;
-                            }
-                        } else {
-                            break;
-                        }
-                    }
-                    left = ASTNode::BinaryOperation { 
-                        left: Box::new(left), 
-                        operator: op.lexeme, 
-                        right: Box::new(right) 
-                    };
+    fn check_assignment_target(&self, target: &ASTNode) -> Result<(), String> {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(_)) | ASTNode::Attribute { .. } | ASTNode::Subscript { .. } => Ok(()),
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                elements.iter().try_for_each(|element| self.check_assignment_target(element))
+            }
+            ASTNode::Starred(inner) => self.check_assignment_target(inner),
+            _ => Err("Cannot assign to expression".to_string()),
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::IfStatement);
+        self.advance();
+        let condition = self.parse_expression()?;
+        let body = self.parse_block()?;
+        let orelse = if self.match_token(&TokenType::Keyword, "elif") {
+            vec![self.parse_if()?]
+        } else {
+            self.parse_else_block()?
+        };
+        self.finish_node();
+        Ok(ASTNode::If { condition: Box::new(condition), body, orelse, span: self.span_from(start) })
+    }
+
+    fn parse_while(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::WhileStatement);
+        self.advance();
+        let condition = self.parse_expression()?;
+        let body = self.parse_block()?;
+        let orelse = self.parse_else_block()?;
+        self.finish_node();
+        Ok(ASTNode::While { condition: Box::new(condition), body, orelse, span: self.span_from(start) })
+    }
+
+    fn parse_for(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::ForStatement);
+        self.advance();
+        let target = self.parse_target_list()?;
+        self.expect_token(TokenType::Keyword, "in")?;
+        let iterable = self.parse_expression_list()?;
+        let body = self.parse_block()?;
+        let orelse = self.parse_else_block()?;
+        self.finish_node();
+        Ok(ASTNode::For {
+            target: Box::new(target),
+            iterable: Box::new(iterable),
+            body,
+            orelse,
+            span: self.span_from(start),
+        })
+    }
+
+    /// `try: ... (except [type [as name]]: ...)* [else: ...] [finally: ...]`
+    fn parse_try(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::TryStatement);
+        self.advance();
+        let body = self.parse_block()?;
+        let mut handlers: Vec<ExceptHandler> = Vec::new();
+        while self.match_token(&TokenType::Keyword, "except") {
+            if handlers.last().is_some_and(|handler| handler.exception_type.is_none()) {
+                return Err("default 'except:' must be last".to_string());
+            }
+            let handler_start = self.current_start();
+            self.start_node(SyntaxKind::ExceptClause);
+            self.advance();
+            let mut exception_type = None;
+            let mut name = None;
+            if !self.match_token(&TokenType::Punctuation, ":") {
+                exception_type = Some(self.parse_expression_list()?);
+                if self.match_token(&TokenType::Keyword, "as") {
+                    self.advance();
+                    name = Some(self.expect_token(TokenType::Identifier, "")?.lexeme);
+                }
+            }
+            let body = self.parse_block()?;
+            self.finish_node();
+            handlers.push(ExceptHandler { exception_type, name, body, span: self.span_from(handler_start) });
+        }
+        let orelse = if handlers.is_empty() { Vec::new() } else { self.parse_else_block()? };
+        let finalbody = if self.match_token(&TokenType::Keyword, "finally") {
+            self.start_node(SyntaxKind::FinallyClause);
+            self.advance();
+            let body = self.parse_block()?;
+            self.finish_node();
+            body
+        } else if handlers.is_empty() {
+            return Err("expected 'except' or 'finally' block".to_string());
+        } else {
+            Vec::new()
+        };
+        self.finish_node();
+        Ok(ASTNode::Try { body, handlers, orelse, finalbody, span: self.span_from(start) })
+    }
+
+    /// `with context [as target] (',' context [as target])* ':' block`
+    fn parse_with(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        self.start_node(SyntaxKind::WithStatement);
+        self.advance();
+        let mut items = Vec::new();
+        loop {
+            self.start_node(SyntaxKind::WithItem);
+            let context = self.parse_expression()?;
+            let target = if self.match_token(&TokenType::Keyword, "as") {
+                self.advance();
+                let target = self.parse_binary_operation(1)?;
+                self.check_assignment_target(&target)?;
+                Some(target)
+            } else {
+                None
+            };
+            self.finish_node();
+            items.push(WithItem { context, target });
+            if !self.match_token(&TokenType::Punctuation, ",") {
+                break;
+            }
+            self.advance();
+        }
+        let body = self.parse_block()?;
+        self.finish_node();
+        Ok(ASTNode::With { items, body, span: self.span_from(start) })
+    }
+
+    fn parse_else_block(&mut self) -> Result<Vec<ASTNode>, String> {
+        if self.match_token(&TokenType::Keyword, "else") {
+            self.start_node(SyntaxKind::ElseClause);
+            self.advance();
+            let body = self.parse_block()?;
+            self.finish_node();
+            Ok(body)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn parse_decorated(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        let checkpoint = self.checkpoint();
+        let mut decorators = Vec::new();
+        while self.match_token(&TokenType::Operator, "@") {
+            self.start_node(SyntaxKind::Decorator);
+            self.advance();
+            decorators.push(self.parse_expression()?);
+            self.expect_token(TokenType::Newline, "")?;
+            self.finish_node();
+        }
+        if self.match_token(&TokenType::Keyword, "def") {
+            self.parse_function_definition(decorators, Some((checkpoint, start)))
+        } else if self.match_token(&TokenType::Keyword, "class") {
+            self.parse_class_definition(decorators, Some((checkpoint, start)))
+        } else {
+            Err("Expected 'def' or 'class' after decorator".to_string())
+        }
+    }
+
+    /// `decorated` carries the checkpoint and start offset of any decorators
+    /// so they become part of the definition's node and span.
+    fn parse_function_definition(
+        &mut self,
+        decorators: Vec<ASTNode>,
+        decorated: Option<(Option<Checkpoint>, usize)>,
+    ) -> Result<ASTNode, String> {
+        let start = decorated.map_or(self.current_start(), |(_, start)| start);
+        self.start_definition_node(decorated, SyntaxKind::FunctionDefinition);
+        self.expect_token(TokenType::Keyword, "def")?;
+        let name = self.expect_token(TokenType::Identifier, "")?.lexeme;
+        self.start_node(SyntaxKind::ParameterList);
+        self.expect_token(TokenType::Punctuation, "(")?;
+        let parameters = self.parse_parameters(")", true)?;
+        self.expect_token(TokenType::Punctuation, ")")?;
+        self.finish_node();
+        let returns = if self.match_token(&TokenType::Punctuation, "->") {
+            self.advance();
+            Some(Box::new(self.parse_expression()?))
+        } else {
+            None
+        };
+        let body = self.parse_block()?;
+        self.finish_node();
+        Ok(ASTNode::FunctionDefinition(FunctionDefinition {
+            name,
+            parameters,
+            body,
+            decorators,
+            returns,
+            span: self.span_from(start),
+        }))
+    }
+
+    fn parse_class_definition(
+        &mut self,
+        decorators: Vec<ASTNode>,
+        decorated: Option<(Option<Checkpoint>, usize)>,
+    ) -> Result<ASTNode, String> {
+        let start = decorated.map_or(self.current_start(), |(_, start)| start);
+        self.start_definition_node(decorated, SyntaxKind::ClassDefinition);
+        self.expect_token(TokenType::Keyword, "class")?;
+        let name = self.expect_token(TokenType::Identifier, "")?.lexeme;
+        let (bases, keywords) = if self.match_token(&TokenType::Punctuation, "(") {
+            self.start_node(SyntaxKind::ArgumentList);
+            self.advance();
+            let arguments = self.parse_arguments()?;
+            self.expect_token(TokenType::Punctuation, ")")?;
+            self.finish_node();
+            arguments
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let body = self.parse_block()?;
+        self.finish_node();
+        Ok(ASTNode::ClassDefinition(ClassDefinition { name, bases, keywords, body, decorators, span: self.span_from(start) }))
+    }
+
+    fn start_definition_node(&mut self, decorated: Option<(Option<Checkpoint>, usize)>, kind: SyntaxKind) {
+        match decorated {
+            Some((checkpoint, _)) => self.start_node_at(checkpoint, kind),
+            None => self.start_node(kind),
+        }
+    }
+
+    /// Parses a parameter list up to (not including) `closer`. Annotations
+    /// are only allowed in `def` headers, not in lambdas.
+    fn parse_parameters(&mut self, closer: &str, allow_annotations: bool) -> Result<Vec<Parameter>, String> {
+        let mut parameters: Vec<Parameter> = Vec::new();
+        let mut kind = ParameterKind::PositionalOrKeyword;
+        while !self.match_token(&TokenType::Punctuation, closer) {
+            let start = self.current_start();
+            if self.match_token(&TokenType::Operator, "/") {
+                self.advance();
+                for parameter in parameters.iter_mut() {
+                    parameter.kind = ParameterKind::PositionalOnly;
+                }
+            } else if self.match_token(&TokenType::Operator, "*") && self.peek_is_separator() {
+                self.advance();
+                kind = ParameterKind::KeywordOnly;
+            } else {
+                self.start_node(SyntaxKind::Parameter);
+                let parameter_kind = if self.match_token(&TokenType::Operator, "*") {
+                    self.advance();
+                    kind = ParameterKind::KeywordOnly;
+                    ParameterKind::VarPositional
+                } else if self.match_token(&TokenType::Operator, "**") {
+                    self.advance();
+                    ParameterKind::VarKeyword
                 } else {
-                    break;
+                    kind
+                };
+                let name = self.expect_token(TokenType::Identifier, "")?.lexeme;
+                let annotation = if allow_annotations && self.match_token(&TokenType::Punctuation, ":") {
+                    self.advance();
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                let default = if self.match_token(&TokenType::Operator, "=") {
+                    self.advance();
+                    Some(self.parse_expression()?)
+                } else {
+                    None
+                };
+                self.finish_node();
+                if default.is_none()
+                    && matches!(parameter_kind, ParameterKind::PositionalOrKeyword)
+                    && parameters.iter().any(|p| p.default.is_some() && p.kind != ParameterKind::KeywordOnly)
+                {
+                    return Err("Non-default argument follows default argument".to_string());
                 }
+                parameters.push(Parameter { name, kind: parameter_kind, annotation, default, span: self.span_from(start) });
+            }
+            if !self.match_token(&TokenType::Punctuation, ",") {
+                break;
+            }
+            self.advance();
+        }
+        Ok(parameters)
+    }
+
+    fn peek_is_separator(&mut self) -> bool {
+        // A bare `*` is followed by `,` or the closing delimiter rather than a name.
+        !matches!(self.peek_next_token(), Some(token) if token.token_type == TokenType::Identifier)
+    }
+
+    /// `':' (simple_stmts | NEWLINE INDENT statement+ DEDENT)`
+    fn parse_block(&mut self) -> Result<Vec<ASTNode>, String> {
+        self.start_node(SyntaxKind::Block);
+        self.expect_token(TokenType::Punctuation, ":")?;
+        let mut body = Vec::new();
+        if self.match_token(&TokenType::Newline, "") {
+            self.advance();
+            self.expect_token(TokenType::Indent, "")?;
+            while !self.match_token(&TokenType::Dedent, "") && !self.match_token(&TokenType::Eof, "") {
+                if self.match_token(&TokenType::Newline, "") {
+                    self.advance();
+                    continue;
+                }
+                self.parse_statement(&mut body)?;
+            }
+            if self.match_token(&TokenType::Dedent, "") {
+                self.advance();
+            }
+        } else {
+            self.parse_simple_statements(&mut body)?;
+        }
+        self.finish_node();
+        Ok(body)
+    }
+
+    fn at_line_end(&self) -> bool {
+        self.current_token()
+            .is_none_or(|token| matches!(token.token_type, TokenType::Newline | TokenType::Eof | TokenType::Dedent))
+    }
+
+    fn expect_line_end(&mut self) -> Result<(), String> {
+        match self.current_token().map(|token| token.token_type.clone()) {
+            Some(TokenType::Newline) => {
+                self.advance();
+                Ok(())
+            }
+            None | Some(TokenType::Eof) | Some(TokenType::Dedent) => Ok(()),
+            Some(_) => Err(format!("Expected end of statement, found '{}'", self.current_token().map_or("", |t| t.lexeme.as_str()))),
+        }
+    }
+
+    /// `expression (',' expression)* [',']`, producing a tuple when a comma is
+    /// present.
+    fn parse_expression_list(&mut self) -> Result<ASTNode, String> {
+        self.parse_list_of(Self::parse_star_or_expression)
+    }
+
+    /// Like `parse_expression_list` but for assignment targets, which must not
+    /// swallow the `in` of a `for` statement.
+    fn parse_target_list(&mut self) -> Result<ASTNode, String> {
+        let target = self.parse_list_of(|parser| {
+            if parser.match_token(&TokenType::Operator, "*") {
+                parser.parse_star_or_expression()
             } else {
+                parser.parse_binary_operation(1)
+            }
+        })?;
+        self.check_assignment_target(&target)?;
+        Ok(target)
+    }
+
+    fn parse_list_of(&mut self, mut parse_item: impl FnMut(&mut Self) -> Result<ASTNode, String>) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let first = parse_item(self)?;
+        if !self.match_token(&TokenType::Punctuation, ",") {
+            return Ok(first);
+        }
+        self.start_node_at(checkpoint, SyntaxKind::TupleExpression);
+        let mut elements = vec![first];
+        while self.match_token(&TokenType::Punctuation, ",") {
+            self.advance();
+            if self.at_expression_end() {
+                break;
+            }
+            elements.push(parse_item(self)?);
+        }
+        self.finish_node();
+        Ok(ASTNode::Tuple(elements))
+    }
+
+    fn at_expression_end(&self) -> bool {
+        self.at_line_end()
+            || self.current_token().is_some_and(|token| {
+                matches!(token.lexeme.as_str(), ")" | "]" | "}" | "=" | ";" | ":" | "in")
+                    || AUGMENTED_ASSIGNMENT_OPERATORS.contains(&token.lexeme.as_str())
+            })
+    }
+
+    fn parse_star_or_expression(&mut self) -> Result<ASTNode, String> {
+        if self.match_token(&TokenType::Operator, "*") {
+            self.start_node(SyntaxKind::StarredExpression);
+            self.advance();
+            let value = self.parse_binary_operation(1)?;
+            self.finish_node();
+            Ok(ASTNode::Starred(Box::new(value)))
+        } else {
+            self.parse_expression()
+        }
+    }
+
+    fn parse_expression(&mut self) -> Result<ASTNode, String> {
+        if self.match_token(&TokenType::Keyword, "lambda") {
+            return self.parse_lambda();
+        }
+        let checkpoint = self.checkpoint();
+        let body = self.parse_or()?;
+        if !self.match_token(&TokenType::Keyword, "if") {
+            return Ok(body);
+        }
+        self.start_node_at(checkpoint, SyntaxKind::ConditionalExpression);
+        self.advance();
+        let condition = self.parse_or()?;
+        self.expect_token(TokenType::Keyword, "else")?;
+        let orelse = self.parse_expression()?;
+        self.finish_node();
+        Ok(ASTNode::Conditional { condition: Box::new(condition), body: Box::new(body), orelse: Box::new(orelse) })
+    }
+
+    fn parse_lambda(&mut self) -> Result<ASTNode, String> {
+        self.start_node(SyntaxKind::LambdaExpression);
+        self.expect_token(TokenType::Keyword, "lambda")?;
+        let parameters = self.parse_parameters(":", false)?;
+        self.expect_token(TokenType::Punctuation, ":")?;
+        let body = self.parse_expression()?;
+        self.finish_node();
+        Ok(ASTNode::Lambda { parameters, body: Box::new(body) })
+    }
+
+    fn parse_or(&mut self) -> Result<ASTNode, String> {
+        self.parse_boolean_operation("or", Self::parse_and)
+    }
+
+    fn parse_and(&mut self) -> Result<ASTNode, String> {
+        self.parse_boolean_operation("and", Self::parse_not)
+    }
+
+    fn parse_boolean_operation(
+        &mut self,
+        operator: &str,
+        parse_operand: fn(&mut Self) -> Result<ASTNode, String>,
+    ) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let first = parse_operand(self)?;
+        if !self.match_token(&TokenType::Keyword, operator) {
+            return Ok(first);
+        }
+        self.start_node_at(checkpoint, SyntaxKind::BooleanExpression);
+        let mut values = vec![first];
+        while self.match_token(&TokenType::Keyword, operator) {
+            self.advance();
+            values.push(parse_operand(self)?);
+        }
+        self.finish_node();
+        Ok(ASTNode::BooleanOperation { operator: operator.to_string(), values })
+    }
+
+    fn parse_not(&mut self) -> Result<ASTNode, String> {
+        if self.match_token(&TokenType::Keyword, "not") {
+            self.start_node(SyntaxKind::UnaryExpression);
+            self.advance();
+            let operand = self.parse_not()?;
+            self.finish_node();
+            return Ok(ASTNode::UnaryOperation { operator: "not".to_string(), operand: Box::new(operand) });
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let left = self.parse_binary_operation(1)?;
+        let mut operators = Vec::new();
+        let mut comparators = Vec::new();
+        while let Some(operator) = self.comparison_operator() {
+            if operators.is_empty() {
+                self.start_node_at(checkpoint, SyntaxKind::ComparisonExpression);
+            }
+            self.advance();
+            if operator == "is not" || operator == "not in" {
+                self.advance();
+            }
+            operators.push(operator);
+            comparators.push(self.parse_binary_operation(1)?);
+        }
+        if operators.is_empty() {
+            return Ok(left);
+        }
+        self.finish_node();
+        Ok(ASTNode::Comparison { left: Box::new(left), operators, comparators })
+    }
+
+    fn comparison_operator(&mut self) -> Option<String> {
+        let token = self.current_token()?.clone();
+        match (&token.token_type, token.lexeme.as_str()) {
+            (TokenType::Operator, op @ ("==" | "!=" | "<" | ">" | "<=" | ">=")) => Some(op.to_string()),
+            (TokenType::Keyword, "in") => Some("in".to_string()),
+            (TokenType::Keyword, "is") => {
+                let negated = self.peek_next_token().is_some_and(|next| next.lexeme == "not");
+                Some(if negated { "is not" } else { "is" }.to_string())
+            }
+            (TokenType::Keyword, "not") => self
+                .peek_next_token()
+                .filter(|next| next.lexeme == "in")
+                .map(|_| "not in".to_string()),
+            _ => None,
+        }
+    }
+
+    fn parse_binary_operation(&mut self, min_precedence: u8) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let mut left = self.parse_unary()?;
+        while let Some(op) = self.current_token().cloned() {
+            if op.token_type != TokenType::Operator {
                 break;
             }
+            let precedence = self.get_precedence(&op.lexeme);
+            if precedence == 0 || precedence < min_precedence {
+                break;
+            }
+            self.start_node_at(checkpoint, SyntaxKind::BinaryExpression);
+            self.advance();
+            let right = self.parse_binary_operation(precedence + 1)?;
+            self.finish_node();
+            left = ASTNode::BinaryOperation {
+                left: Box::new(left),
+                operator: op.lexeme,
+                right: Box::new(right)
+            };
         }
         Ok(left)
     }
 
+    fn parse_unary(&mut self) -> Result<ASTNode, String> {
+        let is_unary = self
+            .current_token()
+            .is_some_and(|token| token.token_type == TokenType::Operator && matches!(token.lexeme.as_str(), "-" | "+" | "~"));
+        if !is_unary {
+            return self.parse_power();
+        }
+        self.start_node(SyntaxKind::UnaryExpression);
+        let operator = self.advance().expect("unary operator was just matched").lexeme;
+        let operand = self.parse_unary()?;
+        self.finish_node();
+        Ok(ASTNode::UnaryOperation { operator, operand: Box::new(operand) })
+    }
+
+    /// `**` binds tighter than unary minus on its left and is right-associative.
+    fn parse_power(&mut self) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let base = self.parse_postfix()?;
+        if !self.match_token(&TokenType::Operator, "**") {
+            return Ok(base);
+        }
+        self.start_node_at(checkpoint, SyntaxKind::BinaryExpression);
+        self.advance();
+        let exponent = self.parse_unary()?;
+        self.finish_node();
+        Ok(ASTNode::BinaryOperation { left: Box::new(base), operator: "**".to_string(), right: Box::new(exponent) })
+    }
+
+    fn parse_postfix(&mut self) -> Result<ASTNode, String> {
+        let start = self.current_start();
+        let checkpoint = self.checkpoint();
+        let mut expression = self.parse_primary()?;
+        loop {
+            if self.match_token(&TokenType::Punctuation, "(") {
+                self.start_node_at(checkpoint, SyntaxKind::CallExpression);
+                self.start_node(SyntaxKind::ArgumentList);
+                self.advance();
+                let (arguments, keywords) = self.parse_arguments()?;
+                self.expect_token(TokenType::Punctuation, ")")?;
+                self.finish_node();
+                self.finish_node();
+                expression = ASTNode::Call { function: Box::new(expression), arguments, keywords, span: self.span_from(start) };
+            } else if self.match_token(&TokenType::Punctuation, ".") {
+                self.start_node_at(checkpoint, SyntaxKind::AttributeExpression);
+                self.advance();
+                let attribute = self.expect_token(TokenType::Identifier, "")?.lexeme;
+                self.finish_node();
+                expression = ASTNode::Attribute { value: Box::new(expression), attribute };
+            } else if self.match_token(&TokenType::Punctuation, "[") {
+                self.start_node_at(checkpoint, SyntaxKind::SubscriptExpression);
+                self.advance();
+                let index = self.parse_list_of(Self::parse_slice)?;
+                self.expect_token(TokenType::Punctuation, "]")?;
+                self.finish_node();
+                expression = ASTNode::Subscript { value: Box::new(expression), index: Box::new(index) };
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    fn parse_slice(&mut self) -> Result<ASTNode, String> {
+        let checkpoint = self.checkpoint();
+        let lower = if self.match_token(&TokenType::Punctuation, ":") { None } else { Some(self.parse_expression()?) };
+        if !self.match_token(&TokenType::Punctuation, ":") {
+            return lower.ok_or_else(|| "Expected expression".to_string());
+        }
+        self.start_node_at(checkpoint, SyntaxKind::SliceExpression);
+        let mut parts = Vec::new();
+        while self.match_token(&TokenType::Punctuation, ":") && parts.len() < 2 {
+            self.advance();
+            let at_end = self.current_token().is_some_and(|token| matches!(token.lexeme.as_str(), ":" | "]" | ","));
+            parts.push(if at_end { None } else { Some(Box::new(self.parse_expression()?)) });
+        }
+        self.finish_node();
+        let mut parts = parts.into_iter();
+        Ok(ASTNode::Slice {
+            lower: lower.map(Box::new),
+            upper: parts.next().flatten(),
+            step: parts.next().flatten(),
+        })
+    }
+
+    /// Parses call arguments up to (not including) the closing parenthesis.
+    fn parse_arguments(&mut self) -> Result<(Vec<ASTNode>, Vec<KeywordArgument>), String> {
+        let mut arguments = Vec::new();
+        let mut keywords = Vec::new();
+        while !self.match_token(&TokenType::Punctuation, ")") {
+            self.start_node(SyntaxKind::Argument);
+            if self.match_token(&TokenType::Operator, "**") {
+                self.advance();
+                keywords.push(KeywordArgument { name: None, value: self.parse_expression()? });
+            } else if self.match_token(&TokenType::Identifier, "")
+                && self.peek_next_token().is_some_and(|next| next.token_type == TokenType::Operator && next.lexeme == "=")
+            {
+                let name = self.advance().expect("keyword name was just matched").lexeme;
+                self.advance();
+                keywords.push(KeywordArgument { name: Some(name), value: self.parse_expression()? });
+            } else {
+                if !keywords.is_empty() && !self.match_token(&TokenType::Operator, "*") {
+                    return Err("Positional argument follows keyword argument".to_string());
+                }
+                let checkpoint = self.checkpoint();
+                let argument = self.parse_star_or_expression()?;
+                if self.match_token(&TokenType::Keyword, "for") {
+                    arguments.push(self.parse_comprehension(checkpoint, ComprehensionKind::Generator, argument)?);
+                } else {
+                    arguments.push(argument);
+                }
+            }
+            self.finish_node();
+            if !self.match_token(&TokenType::Punctuation, ",") {
+                break;
+            }
+            self.advance();
+        }
+        Ok((arguments, keywords))
+    }
+
     fn parse_primary(&mut self) -> Result<ASTNode, String> {
-        let token = self.current_token.clone().ok_or("Unexpected end of input")?;
-        self.current_token = self.lexer.next_token();
+        let token = self.current_token().cloned().ok_or("Unexpected end of input")?;
 
         match token.token_type {
-            TokenType::Identifier => Ok(ASTNode::Expression(ASTNodeType::Identifier(token.lexeme))),
+            TokenType::Identifier => {
+                self.start_node(SyntaxKind::Name);
+                self.advance();
+                self.finish_node();
+                Ok(ASTNode::Expression(ASTNodeType::Identifier(token.lexeme)))
+            }
+            TokenType::Literal if token.lexeme.starts_with(|c: char| c.is_ascii_digit() || c == '.') => {
+                let digits = token.lexeme.replace('_', "");
+                let radix = match digits.get(..2).map(str::to_ascii_lowercase).as_deref() {
+                    Some("0x") => Some(16),
+                    Some("0o") => Some(8),
+                    Some("0b") => Some(2),
+                    _ => None,
+                };
+                let node = if let Some(radix) = radix {
+                    match i64::from_str_radix(&digits[2..], radix) {
+                        Ok(value) => ASTNode::Number(value),
+                        Err(_) => ASTNode::BigNumber(BigInt::parse_radix(&digits[2..], radix).ok_or_else(|| "Invalid number".to_string())?),
+                    }
+                } else if let Some(imaginary) = digits.strip_suffix(['j', 'J']) {
+                    ASTNode::Imaginary(imaginary.parse::<f64>().map_err(|_| "Invalid number".to_string())?)
+                } else if digits.contains(['.', 'e', 'E']) {
+                    ASTNode::Float(digits.parse::<f64>().map_err(|_| "Invalid number".to_string())?)
+                } else {
+                    match digits.parse::<i64>() {
+                        Ok(value) => ASTNode::Number(value),
+                        Err(_) => ASTNode::BigNumber(BigInt::parse(&digits).ok_or_else(|| "Invalid number".to_string())?),
+                    }
+                };
+                self.start_node(SyntaxKind::Number);
+                self.advance();
+                self.finish_node();
+                Ok(node)
+            }
             TokenType::Literal => {
-                let value = token.lexeme.parse::<i32>().map_err(|_| "Invalid number".to_string())?;
-                Ok(ASTNode::Number(value))
+                self.start_node(SyntaxKind::String);
+                let mut literal = StringLiteral::default();
+                while self.current_token().is_some_and(|token| {
+                    token.token_type == TokenType::Literal && !token.lexeme.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                }) {
+                    let start = self.current_start();
+                    let token = self.advance().expect("string literal was just matched");
+                    literal.push(self.lexer.source(), &token.lexeme, start)?;
+                }
+                self.finish_node();
+                literal.finish()
+            }
+            TokenType::Keyword if matches!(token.lexeme.as_str(), "True" | "False" | "None") => {
+                self.start_node(SyntaxKind::Constant);
+                self.advance();
+                self.finish_node();
+                Ok(match token.lexeme.as_str() {
+                    "True" => ASTNode::Boolean(true),
+                    "False" => ASTNode::Boolean(false),
+                    _ => ASTNode::NoneLiteral,
+                })
             }
-            _ => Err("Unexpected token".to_string())
+            TokenType::Punctuation if token.lexeme == "..." => {
+                self.start_node(SyntaxKind::Constant);
+                self.advance();
+                self.finish_node();
+                Ok(ASTNode::Ellipsis)
+            }
+            TokenType::Punctuation if token.lexeme == "(" => {
+                self.start_node(SyntaxKind::ParenthesizedExpression);
+                self.advance();
+                let checkpoint = self.checkpoint();
+                if self.match_token(&TokenType::Punctuation, ")") {
+                    self.advance();
+                    self.finish_node();
+                    return Ok(ASTNode::Tuple(Vec::new()));
+                }
+                let first = self.parse_star_or_expression()?;
+                let expression = if self.match_token(&TokenType::Keyword, "for") {
+                    self.parse_comprehension(checkpoint, ComprehensionKind::Generator, first)?
+                } else if self.match_token(&TokenType::Punctuation, ",") {
+                    let mut elements = vec![first];
+                    while self.match_token(&TokenType::Punctuation, ",") {
+                        self.advance();
+                        if self.match_token(&TokenType::Punctuation, ")") {
+                            break;
+                        }
+                        elements.push(self.parse_star_or_expression()?);
+                    }
+                    ASTNode::Tuple(elements)
+                } else {
+                    first
+                };
+                self.expect_token(TokenType::Punctuation, ")")?;
+                self.finish_node();
+                Ok(expression)
+            }
+            TokenType::Punctuation if token.lexeme == "[" => {
+                self.start_node(SyntaxKind::ListExpression);
+                self.advance();
+                let checkpoint = self.checkpoint();
+                let mut elements = Vec::new();
+                while !self.match_token(&TokenType::Punctuation, "]") {
+                    let element = self.parse_star_or_expression()?;
+                    if elements.is_empty() && self.match_token(&TokenType::Keyword, "for") {
+                        let comprehension = self.parse_comprehension(checkpoint, ComprehensionKind::List, element)?;
+                        self.expect_token(TokenType::Punctuation, "]")?;
+                        self.finish_node();
+                        return Ok(comprehension);
+                    }
+                    elements.push(element);
+                    if !self.match_token(&TokenType::Punctuation, ",") {
+                        break;
+                    }
+                    self.advance();
+                }
+                self.expect_token(TokenType::Punctuation, "]")?;
+                self.finish_node();
+                Ok(ASTNode::List(elements))
+            }
+            TokenType::Punctuation if token.lexeme == "{" => self.parse_dict_or_set(),
+            TokenType::Unknown if token.lexeme.is_empty() => {
+                Err("Unindent does not match any outer indentation level".to_string())
+            }
+            _ => Err(format!("Unexpected token '{}'", token.lexeme))
+        }
+    }
+
+    fn parse_dict_or_set(&mut self) -> Result<ASTNode, String> {
+        self.start_node(SyntaxKind::DictOrSetExpression);
+        self.expect_token(TokenType::Punctuation, "{")?;
+        let checkpoint = self.checkpoint();
+        let mut entries = Vec::new();
+        let mut elements = Vec::new();
+        while !self.match_token(&TokenType::Punctuation, "}") {
+            let key = self.parse_star_or_expression()?;
+            if self.match_token(&TokenType::Punctuation, ":") {
+                if !elements.is_empty() {
+                    return Err("Cannot mix set elements and dictionary entries".to_string());
+                }
+                self.advance();
+                entries.push((key, self.parse_expression()?));
+            } else {
+                if !entries.is_empty() {
+                    return Err("Cannot mix set elements and dictionary entries".to_string());
+                }
+                if elements.is_empty() && self.match_token(&TokenType::Keyword, "for") {
+                    let comprehension = self.parse_comprehension(checkpoint, ComprehensionKind::Set, key)?;
+                    self.expect_token(TokenType::Punctuation, "}")?;
+                    self.finish_node();
+                    return Ok(comprehension);
+                }
+                elements.push(key);
+            }
+            if !self.match_token(&TokenType::Punctuation, ",") {
+                break;
+            }
+            self.advance();
         }
+        self.expect_token(TokenType::Punctuation, "}")?;
+        self.finish_node();
+        Ok(if elements.is_empty() { ASTNode::Dict(entries) } else { ASTNode::Set(elements) })
+    }
+
+    /// Parses the `for ... in ... if ...` clauses following `element`.
+    fn parse_comprehension(
+        &mut self,
+        checkpoint: Option<Checkpoint>,
+        kind: ComprehensionKind,
+        element: ASTNode,
+    ) -> Result<ASTNode, String> {
+        self.start_node_at(checkpoint, SyntaxKind::Comprehension);
+        let mut generators = Vec::new();
+        while self.match_token(&TokenType::Keyword, "for") {
+            self.start_node(SyntaxKind::ComprehensionClause);
+            self.advance();
+            let target = self.parse_target_list()?;
+            self.expect_token(TokenType::Keyword, "in")?;
+            let iterable = self.parse_or()?;
+            let mut conditions = Vec::new();
+            while self.match_token(&TokenType::Keyword, "if") {
+                self.advance();
+                conditions.push(self.parse_or()?);
+            }
+            self.finish_node();
+            generators.push(ComprehensionGenerator { target, iterable, conditions });
+        }
+        self.finish_node();
+        Ok(ASTNode::Comprehension { kind, element: Box::new(element), generators })
+    }
+
+    fn current_token(&self) -> Option<&Token> {
+        self.current.as_ref().map(|spanned| &spanned.token)
+    }
+
+    fn current_start(&self) -> usize {
+        self.current.as_ref().map_or(self.previous_end, |spanned| spanned.span.start)
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.previous_end.max(start))
+    }
+
+    /// Looks one token past the current one without consuming anything.
+    fn peek_next_token(&mut self) -> Option<Token> {
+        let next = self.lexer.next_spanned_token()?;
+        let token = next.token.clone();
+        self.lexer.push_back(next);
+        Some(token)
+    }
+
+    /// Consumes the current token, recording it in the CST when one is
+    /// being built.
+    fn advance(&mut self) -> Option<Token> {
+        let consumed = self.current.take()?;
+        self.current = self.lexer.next_spanned_token();
+        if !matches!(consumed.token.token_type, TokenType::Newline | TokenType::Indent | TokenType::Dedent | TokenType::Eof) {
+            self.previous_end = consumed.span.end;
+        }
+        let token = consumed.token.clone();
+        if let Some(cst) = self.cst.as_mut() {
+            cst.token(consumed);
+        }
+        Some(token)
     }
 
     fn match_token(&self, token_type: &TokenType, lexeme: &str) -> bool {
-        self.current_token.as_ref().map_or(false, |token| {
+        self.current_token().is_some_and(|token| {
             &token.token_type == token_type && (lexeme.is_empty() || token.lexeme == lexeme)
         })
     }
 
     fn expect_token(&mut self, token_type: TokenType, lexeme: &str) -> Result<Token, String> {
         if self.match_token(&token_type, lexeme) {
-            let token = self.current_token.clone().unwrap();
-            self.current_token = self.lexer.next_token();
-            Ok(token)
+            Ok(self.advance().expect("current token was just matched"))
         } else {
             Err(format!("Expected {:?} '{}'", token_type, lexeme))
         }
@@ -130,10 +1385,313 @@ impl<'a> Parser<'a> {
 
     fn get_precedence(&self, operator: &str) -> u8 {
         match operator {
-            "=" => 1,
-            "+" | "-" => 2,
-            "*" | "/" => 3,
+            "|" => 1,
+            "^" => 2,
+            "&" => 3,
+            "<<" | ">>" => 4,
+            "+" | "-" => 5,
+            "*" | "/" | "//" | "%" | "@" => 6,
             _ => 0,
         }
     }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        if let Some(cst) = self.cst.as_mut() {
+            cst.start_node(kind);
+        }
+    }
+
+    fn start_node_at(&mut self, checkpoint: Option<Checkpoint>, kind: SyntaxKind) {
+        if let (Some(cst), Some(checkpoint)) = (self.cst.as_mut(), checkpoint) {
+            cst.start_node_at(checkpoint, kind);
+        }
+    }
+
+    fn checkpoint(&self) -> Option<Checkpoint> {
+        self.cst.as_ref().map(CstBuilder::checkpoint)
+    }
+
+    fn finish_node(&mut self) {
+        if let Some(cst) = self.cst.as_mut() {
+            cst.finish_node();
+        }
+    }
+}
+
+/// String literals written one after another, which make one value: a
+/// string, an f-string, or bytes.
+#[derive(Default)]
+struct StringLiteral {
+    /// The text of the strings and the replacement fields of the f-strings.
+    parts: Vec<ASTNode>,
+    bytes: Option<Vec<u8>>,
+    formatted: bool,
+    strings: bool,
+}
+
+impl StringLiteral {
+    /// Adds the literal `lexeme`, which starts at `start` in `source`.
+    fn push(&mut self, source: &str, lexeme: &str, start: usize) -> Result<(), String> {
+        let prefix_len = lexeme.find(['"', '\'']).unwrap_or(0);
+        let prefix = lexeme[..prefix_len].to_ascii_lowercase();
+        let raw = prefix.contains('r');
+        let body = &lexeme[prefix_len..];
+        let quote_len = if body.len() >= 6 && (body.starts_with("\"\"\"") || body.starts_with("'''")) { 3 } else { 1 };
+        let inner = body.get(quote_len..body.len().saturating_sub(quote_len)).unwrap_or("");
+        if prefix.contains('b') {
+            self.bytes.get_or_insert_with(Vec::new).extend(decode_bytes(inner, raw)?);
+        } else if prefix.contains('f') {
+            let start = start + prefix_len + quote_len;
+            let mut fstring = FString { source: &source[..start + inner.len()], position: start, raw };
+            for part in fstring.parts(false)? {
+                push_part(&mut self.parts, part);
+            }
+            self.formatted = true;
+            self.strings = true;
+        } else {
+            push_part(&mut self.parts, ASTNode::StringLiteral(unescape(inner, raw, false)?));
+            self.strings = true;
+        }
+        if self.bytes.is_some() && self.strings {
+            return Err("Cannot mix bytes and nonbytes literals".to_string());
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ASTNode, String> {
+        if let Some(bytes) = self.bytes {
+            return Ok(ASTNode::Bytes(bytes));
+        }
+        if self.formatted {
+            return Ok(ASTNode::JoinedStr(self.parts));
+        }
+        Ok(match self.parts.into_iter().next() {
+            Some(text) => text,
+            None => ASTNode::StringLiteral(String::new()),
+        })
+    }
+}
+
+/// Adds `part` to the parts of an f-string, joining text to the text
+/// before it.
+fn push_part(parts: &mut Vec<ASTNode>, part: ASTNode) {
+    match (parts.last_mut(), part) {
+        (_, ASTNode::StringLiteral(text)) if text.is_empty() => {}
+        (Some(ASTNode::StringLiteral(last)), ASTNode::StringLiteral(text)) => last.push_str(&text),
+        (_, part) => parts.push(part),
+    }
+}
+
+/// The text of an f-string between its quotes, from `position` to the end
+/// of `source`, split into text and replacement fields.
+struct FString<'a> {
+    source: &'a str,
+    position: usize,
+    raw: bool,
+}
+
+impl FString<'_> {
+    fn rest(&self) -> &str {
+        &self.source[self.position..]
+    }
+
+    /// The parts up to the end of the f-string or, for a format spec, up
+    /// to the `}` that ends its field.
+    fn parts(&mut self, spec: bool) -> Result<Vec<ASTNode>, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let source = self.source;
+        loop {
+            let rest = &source[self.position..];
+            let Some(c) = rest.chars().next() else {
+                if spec {
+                    return Err("f-string: expecting '}'".to_string());
+                }
+                break;
+            };
+            let length = match c {
+                '{' if rest.starts_with("{{") && !spec => {
+                    text.push('{');
+                    2
+                }
+                '{' => {
+                    push_part(&mut parts, ASTNode::StringLiteral(unescape(&std::mem::take(&mut text), self.raw, false)?));
+                    for part in self.field()? {
+                        push_part(&mut parts, part);
+                    }
+                    0
+                }
+                '}' if spec => break,
+                '}' if rest.starts_with("}}") => {
+                    text.push('}');
+                    2
+                }
+                '}' => return Err("f-string: single '}' is not allowed".to_string()),
+                // The braces of `\N{...}` are not a field.
+                '\\' if !self.raw && rest.starts_with("\\N{") => rest.find('}').map_or(rest.len(), |end| end + 1),
+                '\\' if !self.raw => 1 + rest[1..].chars().next().map_or(0, char::len_utf8),
+                c => c.len_utf8(),
+            };
+            if !matches!(c, '{' | '}') {
+                text.push_str(&rest[..length]);
+            }
+            self.position += length;
+        }
+        push_part(&mut parts, ASTNode::StringLiteral(unescape(&text, self.raw, false)?));
+        Ok(parts)
+    }
+
+    /// The replacement field at `position`, from its `{` to its `}`: a
+    /// `FormattedValue`, after the text of the expression for
+    /// `{expression=}`.
+    fn field(&mut self) -> Result<Vec<ASTNode>, String> {
+        self.position += 1;
+        let start = self.position;
+        let end = self.expression_end()?;
+        if self.source[start..end].trim().is_empty() {
+            return Err("f-string: empty expression not allowed".to_string());
+        }
+        let mut parser = Parser::new(Lexer::within(self.source, start, end));
+        let value = parser.parse_expression_list()?;
+        if let Some(token) = parser.current_token().filter(|token| !matches!(token.token_type, TokenType::Newline | TokenType::Eof)) {
+            return Err(format!("f-string: unexpected '{}'", token.lexeme));
+        }
+        self.position = end;
+
+        let mut parts = Vec::new();
+        let debug = self.rest().starts_with('=');
+        if debug {
+            let spaces = self.rest()[1..].len() - self.rest()[1..].trim_start().len();
+            push_part(&mut parts, ASTNode::StringLiteral(self.source[start..end + 1 + spaces].to_string()));
+            self.position = end + 1 + spaces;
+        }
+        let mut conversion = None;
+        if self.rest().starts_with('!') {
+            conversion = match self.rest()[1..].chars().next() {
+                Some(c @ ('s' | 'r' | 'a')) => Some(c),
+                _ => return Err("f-string: invalid conversion character: expected 's', 'r', or 'a'".to_string()),
+            };
+            self.position += 2;
+        }
+        let mut format_spec = None;
+        if self.rest().starts_with(':') {
+            self.position += 1;
+            format_spec = Some(Box::new(ASTNode::JoinedStr(self.parts(true)?)));
+        }
+        if !self.rest().starts_with('}') {
+            return Err("f-string: expecting '}'".to_string());
+        }
+        self.position += 1;
+        // `{expression=}` shows the `repr` of the value, unless it has a
+        // format spec.
+        if debug && conversion.is_none() && format_spec.is_none() {
+            conversion = Some('r');
+        }
+        parts.push(ASTNode::FormattedValue { value: Box::new(value), conversion, format_spec });
+        Ok(parts)
+    }
+
+    /// Where the expression of the field at `position` ends: at the first
+    /// `}`, `!`, `:` or `=` outside brackets and strings that is not part of
+    /// an operator.
+    fn expression_end(&self) -> Result<usize, String> {
+        let rest = self.rest();
+        let mut depth = 0;
+        let mut quote = None;
+        let mut chars = rest.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            if let Some(open) = quote {
+                if c == open {
+                    quote = None;
+                }
+                continue;
+            }
+            let followed_by_equals = chars.peek().is_some_and(|(_, next)| *next == '=');
+            match c {
+                '\'' | '"' => quote = Some(c),
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' if depth > 0 => depth -= 1,
+                '!' | '=' | '<' | '>' if followed_by_equals => {
+                    chars.next();
+                }
+                '}' | ':' | '!' | '=' if depth == 0 => return Ok(self.position + index),
+                '#' => return Err("f-string expression part cannot include '#'".to_string()),
+                _ => {}
+            }
+        }
+        Err("f-string: expecting '}'".to_string())
+    }
+}
+
+/// Resolves the escape sequences of the text of a string literal, unless it
+/// is raw. In bytes, `\u`, `\U` and `\N` are not escapes, and the chars
+/// stand for bytes.
+fn unescape(text: &str, raw: bool, bytes: bool) -> Result<String, String> {
+    if raw {
+        return Ok(text.to_string());
+    }
+    let mut value = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('\n') => continue,
+            Some(c @ ('\\' | '\'' | '"')) => c,
+            Some('a') => '\x07',
+            Some('b') => '\x08',
+            Some('f') => '\x0c',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('v') => '\x0b',
+            Some(first @ '0'..='7') => {
+                let mut code = first.to_digit(8).expect("an octal digit");
+                for _ in 0..2 {
+                    match chars.next_if(|c| c.is_digit(8)) {
+                        Some(digit) => code = code * 8 + digit.to_digit(8).expect("an octal digit"),
+                        None => break,
+                    }
+                }
+                // Bytes keep the low byte of `\777`, as Python does.
+                char::from_u32(if bytes { code & 0xff } else { code }).expect("octal escapes are at most 0o777")
+            }
+            Some('x') => hex_escape(&mut chars, 2, 'x')?,
+            Some(escape @ ('u' | 'U')) if !bytes => hex_escape(&mut chars, if escape == 'u' { 4 } else { 8 }, escape)?,
+            Some('N') if !bytes => {
+                let name: String = match chars.next_if_eq(&'{') {
+                    Some(_) => chars.by_ref().take_while(|c| *c != '}').collect(),
+                    None => return Err("Malformed \\N character escape".to_string()),
+                };
+                unicode_names2::character(&name).ok_or_else(|| format!("Unknown Unicode character name '{}'", name))?
+            }
+            Some(other) => {
+                value.push('\\');
+                other
+            }
+            None => '\\',
+        };
+        value.push(escaped);
+    }
+    Ok(value)
+}
+
+/// The char of the `digits` hex digits after `\x`, `\u` or `\U`.
+fn hex_escape(chars: &mut std::iter::Peekable<std::str::Chars>, digits: usize, escape: char) -> Result<char, String> {
+    let hex: String = std::iter::from_fn(|| chars.next_if(char::is_ascii_hexdigit)).take(digits).collect();
+    if hex.len() < digits {
+        return Err(format!("Truncated \\{} escape", escape));
+    }
+    let code = u32::from_str_radix(&hex, 16).expect("hex digits");
+    char::from_u32(code).ok_or_else(|| format!("Illegal Unicode character \\{}{}", escape, hex))
+}
+
+/// The bytes of the text of a bytes literal.
+fn decode_bytes(text: &str, raw: bool) -> Result<Vec<u8>, String> {
+    if !text.is_ascii() {
+        return Err("Bytes can only contain ASCII literal characters".to_string());
+    }
+    Ok(unescape(text, raw, true)?.chars().map(|c| c as u8).collect())
 }
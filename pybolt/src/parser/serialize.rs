@@ -0,0 +1,871 @@
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ComprehensionGenerator, ComprehensionKind, ExceptHandler, ImportAlias, KeywordArgument, Parameter,
+    WithItem,
+    ParameterKind,
+};
+
+/// A JSON value, built from the AST before being written out.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    fn node(node_type: &str, fields: Vec<(&'static str, Json)>) -> Json {
+        let mut members = vec![("type", Json::String(node_type.to_string()))];
+        members.extend(fields);
+        Json::Object(members)
+    }
+
+    fn write(&self, out: &mut String) {
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+            Json::Number(value) => out.push_str(value),
+            Json::String(value) => write_json_string(value, out),
+            Json::Array(items) => {
+                out.push('[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    item.write(out);
+                }
+                out.push(']');
+            }
+            Json::Object(members) => {
+                out.push('{');
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    write_json_string(key, out);
+                    out.push(':');
+                    value.write(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Serializes an AST to JSON.
+///
+/// Every node becomes an object whose `"type"` is the `ASTNode` variant name,
+/// with one member per field. Spans are written as `[start, end]` byte
+/// offsets.
+pub fn to_json(node: &ASTNode) -> String {
+    let mut out = String::new();
+    json_node(node).write(&mut out);
+    out
+}
+
+fn json_nodes(nodes: &[ASTNode]) -> Json {
+    Json::Array(nodes.iter().map(json_node).collect())
+}
+
+fn json_optional(node: Option<&ASTNode>) -> Json {
+    node.map_or(Json::Null, json_node)
+}
+
+fn json_string(value: &str) -> Json {
+    Json::String(value.to_string())
+}
+
+fn json_except_handler(handler: &ExceptHandler) -> Json {
+    Json::node(
+        "ExceptHandler",
+        vec![
+            ("exception_type", json_optional(handler.exception_type.as_ref())),
+            ("name", handler.name.as_deref().map_or(Json::Null, json_string)),
+            ("body", json_nodes(&handler.body)),
+            ("span", json_span(&handler.span)),
+        ],
+    )
+}
+
+fn json_with_item(item: &WithItem) -> Json {
+    Json::node(
+        "WithItem",
+        vec![("context", json_node(&item.context)), ("target", json_optional(item.target.as_ref()))],
+    )
+}
+
+fn json_span(span: &crate::lexer::lexer::Span) -> Json {
+    Json::Array(vec![Json::Number(span.start.to_string()), Json::Number(span.end.to_string())])
+}
+
+fn json_node(node: &ASTNode) -> Json {
+    match node {
+        ASTNode::Program(statements) => Json::node("Program", vec![("body", json_nodes(statements))]),
+        ASTNode::VariableDeclaration { name, value, span } => Json::node(
+            "VariableDeclaration",
+            vec![("name", json_string(name)), ("value", json_node(value)), ("span", json_span(span))],
+        ),
+        ASTNode::Expression(expression) => json_expression(expression),
+        ASTNode::Number(value) => Json::node("Number", vec![("value", Json::Number(value.to_string()))]),
+        ASTNode::BigNumber(value) => Json::node("Number", vec![("value", Json::Number(value.to_string()))]),
+        ASTNode::BinaryOperation { left, operator, right } => json_binary_operation(left, operator, right),
+        ASTNode::Float(value) => Json::node("Float", vec![("value", Json::Number(format!("{:?}", value)))]),
+        ASTNode::Imaginary(value) => Json::node("Imaginary", vec![("value", Json::Number(format!("{:?}", value)))]),
+        ASTNode::StringLiteral(value) => Json::node("StringLiteral", vec![("value", json_string(value))]),
+        ASTNode::Bytes(value) => {
+            Json::node("Bytes", vec![("value", Json::Array(value.iter().map(|byte| Json::Number(byte.to_string())).collect()))])
+        }
+        ASTNode::JoinedStr(parts) => Json::node("JoinedStr", vec![("values", json_nodes(parts))]),
+        ASTNode::FormattedValue { value, conversion, format_spec } => Json::node(
+            "FormattedValue",
+            vec![
+                ("value", json_node(value)),
+                ("conversion", conversion.map_or(Json::Null, |conversion| json_string(&conversion.to_string()))),
+                ("format_spec", json_optional(format_spec.as_deref())),
+            ],
+        ),
+        ASTNode::Boolean(value) => Json::node("Boolean", vec![("value", Json::Bool(*value))]),
+        ASTNode::NoneLiteral => Json::node("NoneLiteral", vec![]),
+        ASTNode::Ellipsis => Json::node("Ellipsis", vec![]),
+        ASTNode::UnaryOperation { operator, operand } => Json::node(
+            "UnaryOperation",
+            vec![("operator", json_string(operator)), ("operand", json_node(operand))],
+        ),
+        ASTNode::BooleanOperation { operator, values } => Json::node(
+            "BooleanOperation",
+            vec![("operator", json_string(operator)), ("values", json_nodes(values))],
+        ),
+        ASTNode::Comparison { left, operators, comparators } => Json::node(
+            "Comparison",
+            vec![
+                ("left", json_node(left)),
+                ("operators", Json::Array(operators.iter().map(|op| json_string(op)).collect())),
+                ("comparators", json_nodes(comparators)),
+            ],
+        ),
+        ASTNode::Conditional { condition, body, orelse } => Json::node(
+            "Conditional",
+            vec![("condition", json_node(condition)), ("body", json_node(body)), ("orelse", json_node(orelse))],
+        ),
+        ASTNode::Call { function, arguments, keywords, span } => Json::node(
+            "Call",
+            vec![
+                ("function", json_node(function)),
+                ("arguments", json_nodes(arguments)),
+                ("keywords", json_keywords(keywords)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::Attribute { value, attribute } => {
+            Json::node("Attribute", vec![("value", json_node(value)), ("attribute", json_string(attribute))])
+        }
+        ASTNode::Subscript { value, index } => {
+            Json::node("Subscript", vec![("value", json_node(value)), ("index", json_node(index))])
+        }
+        ASTNode::Slice { lower, upper, step } => Json::node(
+            "Slice",
+            vec![
+                ("lower", json_optional(lower.as_deref())),
+                ("upper", json_optional(upper.as_deref())),
+                ("step", json_optional(step.as_deref())),
+            ],
+        ),
+        ASTNode::Starred(value) => Json::node("Starred", vec![("value", json_node(value))]),
+        ASTNode::List(elements) => Json::node("List", vec![("elements", json_nodes(elements))]),
+        ASTNode::Tuple(elements) => Json::node("Tuple", vec![("elements", json_nodes(elements))]),
+        ASTNode::Set(elements) => Json::node("Set", vec![("elements", json_nodes(elements))]),
+        ASTNode::Dict(entries) => Json::node(
+            "Dict",
+            vec![(
+                "entries",
+                Json::Array(entries.iter().map(|(key, value)| Json::Array(vec![json_node(key), json_node(value)])).collect()),
+            )],
+        ),
+        ASTNode::Comprehension { kind, element, generators } => Json::node(
+            "Comprehension",
+            vec![
+                ("kind", json_string(&format!("{:?}", kind))),
+                ("element", json_node(element)),
+                ("generators", Json::Array(generators.iter().map(json_generator).collect())),
+            ],
+        ),
+        ASTNode::Lambda { parameters, body } => {
+            Json::node("Lambda", vec![("parameters", json_parameters(parameters)), ("body", json_node(body))])
+        }
+        ASTNode::ExpressionStatement { expression, span } => Json::node(
+            "ExpressionStatement",
+            vec![("expression", json_node(expression)), ("span", json_span(span))],
+        ),
+        ASTNode::Assignment { targets, value, span } => Json::node(
+            "Assignment",
+            vec![("targets", json_nodes(targets)), ("value", json_node(value)), ("span", json_span(span))],
+        ),
+        ASTNode::AugmentedAssignment { target, operator, value, span } => Json::node(
+            "AugmentedAssignment",
+            vec![
+                ("target", json_node(target)),
+                ("operator", json_string(operator)),
+                ("value", json_node(value)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::AnnotatedAssignment { target, annotation, value, span } => Json::node(
+            "AnnotatedAssignment",
+            vec![
+                ("target", json_node(target)),
+                ("annotation", json_node(annotation)),
+                ("value", json_optional(value.as_deref())),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::Import { names, span } => {
+            Json::node("Import", vec![("names", json_aliases(names)), ("span", json_span(span))])
+        }
+        ASTNode::ImportFrom { module, names, span } => Json::node(
+            "ImportFrom",
+            vec![("module", json_string(module)), ("names", json_aliases(names)), ("span", json_span(span))],
+        ),
+        ASTNode::If { condition, body, orelse, span } => Json::node(
+            "If",
+            vec![
+                ("condition", json_node(condition)),
+                ("body", json_nodes(body)),
+                ("orelse", json_nodes(orelse)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::While { condition, body, orelse, span } => Json::node(
+            "While",
+            vec![
+                ("condition", json_node(condition)),
+                ("body", json_nodes(body)),
+                ("orelse", json_nodes(orelse)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::For { target, iterable, body, orelse, span } => Json::node(
+            "For",
+            vec![
+                ("target", json_node(target)),
+                ("iterable", json_node(iterable)),
+                ("body", json_nodes(body)),
+                ("orelse", json_nodes(orelse)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::FunctionDefinition(function) => Json::node(
+            "FunctionDefinition",
+            vec![
+                ("name", json_string(&function.name)),
+                ("parameters", json_parameters(&function.parameters)),
+                ("body", json_nodes(&function.body)),
+                ("decorators", json_nodes(&function.decorators)),
+                ("returns", json_optional(function.returns.as_deref())),
+                ("span", json_span(&function.span)),
+            ],
+        ),
+        ASTNode::ClassDefinition(class) => Json::node(
+            "ClassDefinition",
+            vec![
+                ("name", json_string(&class.name)),
+                ("bases", json_nodes(&class.bases)),
+                ("keywords", json_keywords(&class.keywords)),
+                ("body", json_nodes(&class.body)),
+                ("decorators", json_nodes(&class.decorators)),
+                ("span", json_span(&class.span)),
+            ],
+        ),
+        ASTNode::Return { value, span } => {
+            Json::node("Return", vec![("value", json_optional(value.as_deref())), ("span", json_span(span))])
+        }
+        ASTNode::Try { body, handlers, orelse, finalbody, span } => Json::node(
+            "Try",
+            vec![
+                ("body", json_nodes(body)),
+                ("handlers", Json::Array(handlers.iter().map(json_except_handler).collect())),
+                ("orelse", json_nodes(orelse)),
+                ("finalbody", json_nodes(finalbody)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::Raise { exception, cause, span } => Json::node(
+            "Raise",
+            vec![
+                ("exception", json_optional(exception.as_deref())),
+                ("cause", json_optional(cause.as_deref())),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::With { items, body, span } => Json::node(
+            "With",
+            vec![
+                ("items", Json::Array(items.iter().map(json_with_item).collect())),
+                ("body", json_nodes(body)),
+                ("span", json_span(span)),
+            ],
+        ),
+        ASTNode::Global { names, span } => Json::node(
+            "Global",
+            vec![("names", Json::Array(names.iter().map(|name| json_string(name)).collect())), ("span", json_span(span))],
+        ),
+        ASTNode::Nonlocal { names, span } => Json::node(
+            "Nonlocal",
+            vec![("names", Json::Array(names.iter().map(|name| json_string(name)).collect())), ("span", json_span(span))],
+        ),
+        ASTNode::Pass { span } => Json::node("Pass", vec![("span", json_span(span))]),
+        ASTNode::Break { span } => Json::node("Break", vec![("span", json_span(span))]),
+        ASTNode::Continue { span } => Json::node("Continue", vec![("span", json_span(span))]),
+    }
+}
+
+fn json_expression(expression: &ASTNodeType) -> Json {
+    match expression {
+        ASTNodeType::Identifier(name) => Json::node("Identifier", vec![("name", json_string(name))]),
+        ASTNodeType::Number(value) => Json::node("Number", vec![("value", Json::Number(value.to_string()))]),
+        ASTNodeType::BinaryOperation { operator, left, right } => json_binary_operation(left, operator, right),
+    }
+}
+
+fn json_binary_operation(left: &ASTNode, operator: &str, right: &ASTNode) -> Json {
+    Json::node(
+        "BinaryOperation",
+        vec![("left", json_node(left)), ("operator", json_string(operator)), ("right", json_node(right))],
+    )
+}
+
+fn json_keywords(keywords: &[KeywordArgument]) -> Json {
+    Json::Array(
+        keywords
+            .iter()
+            .map(|keyword| {
+                Json::Object(vec![
+                    ("name", keyword.name.as_deref().map_or(Json::Null, json_string)),
+                    ("value", json_node(&keyword.value)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn json_aliases(names: &[ImportAlias]) -> Json {
+    Json::Array(
+        names
+            .iter()
+            .map(|name| {
+                Json::Object(vec![
+                    ("name", json_string(&name.name)),
+                    ("alias", name.alias.as_deref().map_or(Json::Null, json_string)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn json_parameters(parameters: &[Parameter]) -> Json {
+    Json::Array(
+        parameters
+            .iter()
+            .map(|parameter| {
+                Json::Object(vec![
+                    ("name", json_string(&parameter.name)),
+                    ("kind", json_string(&format!("{:?}", parameter.kind))),
+                    ("annotation", json_optional(parameter.annotation.as_ref())),
+                    ("default", json_optional(parameter.default.as_ref())),
+                    ("span", json_span(&parameter.span)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn json_generator(generator: &ComprehensionGenerator) -> Json {
+    Json::Object(vec![
+        ("target", json_node(&generator.target)),
+        ("iterable", json_node(&generator.iterable)),
+        ("conditions", json_nodes(&generator.conditions)),
+    ])
+}
+
+pub(crate) fn write_json_string(value: &str, out: &mut String) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Formats an AST the way CPython's `ast.dump(ast.parse(source))` does
+/// (Python 3.9 to 3.11, default options), so the two parses can be diffed
+/// textually.
+pub fn dump(node: &ASTNode) -> String {
+    match node {
+        ASTNode::Program(statements) => format!("Module(body={}, type_ignores=[])", dump_body(statements)),
+        statement => dump_statement(statement),
+    }
+}
+
+fn dump_body(statements: &[ASTNode]) -> String {
+    let body: Vec<String> = statements.iter().map(dump_statement).collect();
+    format!("[{}]", body.join(", "))
+}
+
+fn dump_list(nodes: &[ASTNode], dump_item: fn(&ASTNode) -> String) -> String {
+    let items: Vec<String> = nodes.iter().map(dump_item).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn dump_statement(node: &ASTNode) -> String {
+    match node {
+        ASTNode::Program(_) => dump(node),
+        ASTNode::VariableDeclaration { name, value, .. } => {
+            format!("Assign(targets=[{}], value={})", dump_name(name, "Store"), dump_expression(value))
+        }
+        ASTNode::ExpressionStatement { expression, .. } => format!("Expr(value={})", dump_expression(expression)),
+        ASTNode::Assignment { targets, value, .. } => format!(
+            "Assign(targets={}, value={})",
+            dump_list(targets, dump_target),
+            dump_expression(value)
+        ),
+        ASTNode::AugmentedAssignment { target, operator, value, .. } => format!(
+            "AugAssign(target={}, op={}(), value={})",
+            dump_target(target),
+            python_operator_name(operator),
+            dump_expression(value)
+        ),
+        ASTNode::AnnotatedAssignment { target, annotation, value, .. } => {
+            let value = value.as_ref().map_or(String::new(), |value| format!(", value={}", dump_expression(value)));
+            let simple = matches!(target.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(_))) as u8;
+            format!(
+                "AnnAssign(target={}, annotation={}{}, simple={})",
+                dump_target(target),
+                dump_expression(annotation),
+                value,
+                simple
+            )
+        }
+        ASTNode::Import { names, .. } => format!("Import(names={})", dump_aliases(names)),
+        ASTNode::ImportFrom { module, names, .. } => {
+            let name = module.trim_start_matches('.');
+            let level = module.len() - name.len();
+            let module = if name.is_empty() { String::new() } else { format!("module={}, ", python_string_repr(name)) };
+            format!("ImportFrom({}names={}, level={})", module, dump_aliases(names), level)
+        }
+        ASTNode::If { condition, body, orelse, .. } => format!(
+            "If(test={}, body={}, orelse={})",
+            dump_expression(condition),
+            dump_body(body),
+            dump_body(orelse)
+        ),
+        ASTNode::While { condition, body, orelse, .. } => format!(
+            "While(test={}, body={}, orelse={})",
+            dump_expression(condition),
+            dump_body(body),
+            dump_body(orelse)
+        ),
+        ASTNode::For { target, iterable, body, orelse, .. } => format!(
+            "For(target={}, iter={}, body={}, orelse={})",
+            dump_target(target),
+            dump_expression(iterable),
+            dump_body(body),
+            dump_body(orelse)
+        ),
+        ASTNode::FunctionDefinition(function) => {
+            let mut out = format!(
+                "FunctionDef(name={}, args={}, body={}, decorator_list={}",
+                python_string_repr(&function.name),
+                dump_arguments(&function.parameters),
+                dump_body(&function.body),
+                dump_list(&function.decorators, dump_expression)
+            );
+            if let Some(returns) = &function.returns {
+                out.push_str(&format!(", returns={}", dump_expression(returns)));
+            }
+            out.push(')');
+            out
+        }
+        ASTNode::ClassDefinition(class) => format!(
+            "ClassDef(name={}, bases={}, keywords={}, body={}, decorator_list={})",
+            python_string_repr(&class.name),
+            dump_list(&class.bases, dump_expression),
+            dump_keywords(&class.keywords),
+            dump_body(&class.body),
+            dump_list(&class.decorators, dump_expression)
+        ),
+        ASTNode::Return { value: Some(value), .. } => format!("Return(value={})", dump_expression(value)),
+        ASTNode::Return { value: None, .. } => "Return()".to_string(),
+        ASTNode::Try { body, handlers, orelse, finalbody, .. } => {
+            let handlers: Vec<String> = handlers.iter().map(dump_except_handler).collect();
+            format!(
+                "Try(body={}, handlers=[{}], orelse={}, finalbody={})",
+                dump_body(body),
+                handlers.join(", "),
+                dump_body(orelse),
+                dump_body(finalbody)
+            )
+        }
+        ASTNode::Raise { exception, cause, .. } => {
+            let mut fields = Vec::new();
+            if let Some(exception) = exception {
+                fields.push(format!("exc={}", dump_expression(exception)));
+            }
+            if let Some(cause) = cause {
+                fields.push(format!("cause={}", dump_expression(cause)));
+            }
+            format!("Raise({})", fields.join(", "))
+        }
+        ASTNode::With { items, body, .. } => {
+            let items: Vec<String> = items.iter().map(dump_with_item).collect();
+            format!("With(items=[{}], body={})", items.join(", "), dump_body(body))
+        }
+        ASTNode::Global { names, .. } => format!("Global(names={})", dump_names(names)),
+        ASTNode::Nonlocal { names, .. } => format!("Nonlocal(names={})", dump_names(names)),
+        ASTNode::Pass { .. } => "Pass()".to_string(),
+        ASTNode::Break { .. } => "Break()".to_string(),
+        ASTNode::Continue { .. } => "Continue()".to_string(),
+        expression => format!("Expr(value={})", dump_expression(expression)),
+    }
+}
+
+fn dump_except_handler(handler: &ExceptHandler) -> String {
+    let mut fields = Vec::new();
+    if let Some(exception_type) = &handler.exception_type {
+        fields.push(format!("type={}", dump_expression(exception_type)));
+    }
+    if let Some(name) = &handler.name {
+        fields.push(format!("name={}", python_string_repr(name)));
+    }
+    fields.push(format!("body={}", dump_body(&handler.body)));
+    format!("ExceptHandler({})", fields.join(", "))
+}
+
+fn dump_with_item(item: &WithItem) -> String {
+    match &item.target {
+        Some(target) => {
+            format!("withitem(context_expr={}, optional_vars={})", dump_expression(&item.context), dump_target(target))
+        }
+        None => format!("withitem(context_expr={})", dump_expression(&item.context)),
+    }
+}
+
+fn dump_aliases(names: &[ImportAlias]) -> String {
+    let names: Vec<String> = names
+        .iter()
+        .map(|name| match &name.alias {
+            Some(alias) => format!("alias(name={}, asname={})", python_string_repr(&name.name), python_string_repr(alias)),
+            None => format!("alias(name={})", python_string_repr(&name.name)),
+        })
+        .collect();
+    format!("[{}]", names.join(", "))
+}
+
+fn dump_names(names: &[String]) -> String {
+    let names: Vec<String> = names.iter().map(|name| python_string_repr(name)).collect();
+    format!("[{}]", names.join(", "))
+}
+
+/// Dumps an assignment target, which CPython marks with a `Store` context.
+fn dump_target(node: &ASTNode) -> String {
+    dump_with_context(node, "Store")
+}
+
+fn dump_expression(node: &ASTNode) -> String {
+    dump_with_context(node, "Load")
+}
+
+fn dump_with_context(node: &ASTNode, context: &str) -> String {
+    match node {
+        ASTNode::Expression(ASTNodeType::Identifier(name)) => dump_name(name, context),
+        ASTNode::Expression(ASTNodeType::Number(value)) | ASTNode::Number(value) => format!("Constant(value={})", value),
+        ASTNode::BigNumber(value) => format!("Constant(value={})", value),
+        ASTNode::Expression(ASTNodeType::BinaryOperation { operator, left, right })
+        | ASTNode::BinaryOperation { left, operator, right } => format!(
+            "BinOp(left={}, op={}(), right={})",
+            dump_expression(left),
+            python_operator_name(operator),
+            dump_expression(right)
+        ),
+        ASTNode::Float(value) => format!("Constant(value={})", python_float_repr(*value)),
+        ASTNode::Imaginary(value) => format!("Constant(value={})", python_imaginary_repr(*value)),
+        ASTNode::StringLiteral(value) => format!("Constant(value={})", python_string_repr(value)),
+        ASTNode::Bytes(value) => format!("Constant(value={})", python_bytes_repr(value)),
+        ASTNode::JoinedStr(parts) => format!("JoinedStr(values={})", dump_list(parts, dump_expression)),
+        ASTNode::FormattedValue { value, conversion, format_spec } => {
+            // CPython gives the conversion as the code of its character.
+            let conversion = conversion.map_or(-1, |conversion| conversion as i32);
+            match format_spec {
+                Some(format_spec) => format!(
+                    "FormattedValue(value={}, conversion={}, format_spec={})",
+                    dump_expression(value),
+                    conversion,
+                    dump_expression(format_spec)
+                ),
+                None => format!("FormattedValue(value={}, conversion={})", dump_expression(value), conversion),
+            }
+        }
+        ASTNode::Boolean(value) => format!("Constant(value={})", if *value { "True" } else { "False" }),
+        ASTNode::NoneLiteral => "Constant(value=None)".to_string(),
+        ASTNode::Ellipsis => "Constant(value=Ellipsis)".to_string(),
+        ASTNode::UnaryOperation { operator, operand } => {
+            let op = match operator.as_str() {
+                "-" => "USub",
+                "+" => "UAdd",
+                "~" => "Invert",
+                _ => "Not",
+            };
+            format!("UnaryOp(op={}(), operand={})", op, dump_expression(operand))
+        }
+        ASTNode::BooleanOperation { operator, values } => format!(
+            "BoolOp(op={}(), values={})",
+            if operator == "and" { "And" } else { "Or" },
+            dump_list(values, dump_expression)
+        ),
+        ASTNode::Comparison { left, operators, comparators } => {
+            let ops: Vec<String> = operators.iter().map(|op| format!("{}()", python_comparison_name(op))).collect();
+            format!(
+                "Compare(left={}, ops=[{}], comparators={})",
+                dump_expression(left),
+                ops.join(", "),
+                dump_list(comparators, dump_expression)
+            )
+        }
+        ASTNode::Conditional { condition, body, orelse } => format!(
+            "IfExp(test={}, body={}, orelse={})",
+            dump_expression(condition),
+            dump_expression(body),
+            dump_expression(orelse)
+        ),
+        ASTNode::Call { function, arguments, keywords, .. } => format!(
+            "Call(func={}, args={}, keywords={})",
+            dump_expression(function),
+            dump_list(arguments, dump_expression),
+            dump_keywords(keywords)
+        ),
+        ASTNode::Attribute { value, attribute } => format!(
+            "Attribute(value={}, attr={}, ctx={}())",
+            dump_expression(value),
+            python_string_repr(attribute),
+            context
+        ),
+        ASTNode::Subscript { value, index } => format!(
+            "Subscript(value={}, slice={}, ctx={}())",
+            dump_expression(value),
+            dump_expression(index),
+            context
+        ),
+        ASTNode::Slice { lower, upper, step } => {
+            let parts: Vec<String> = [("lower", lower), ("upper", upper), ("step", step)]
+                .into_iter()
+                .filter_map(|(field, part)| part.as_ref().map(|part| format!("{}={}", field, dump_expression(part))))
+                .collect();
+            format!("Slice({})", parts.join(", "))
+        }
+        ASTNode::Starred(value) => format!("Starred(value={}, ctx={}())", dump_with_context(value, context), context),
+        ASTNode::List(elements) => format!("List(elts={}, ctx={}())", dump_elements(elements, context), context),
+        ASTNode::Tuple(elements) => format!("Tuple(elts={}, ctx={}())", dump_elements(elements, context), context),
+        ASTNode::Set(elements) => format!("Set(elts={})", dump_list(elements, dump_expression)),
+        ASTNode::Dict(entries) => {
+            let keys: Vec<String> = entries.iter().map(|(key, _)| dump_expression(key)).collect();
+            let values: Vec<String> = entries.iter().map(|(_, value)| dump_expression(value)).collect();
+            format!("Dict(keys=[{}], values=[{}])", keys.join(", "), values.join(", "))
+        }
+        ASTNode::Comprehension { kind, element, generators } => {
+            let name = match kind {
+                ComprehensionKind::List => "ListComp",
+                ComprehensionKind::Set => "SetComp",
+                ComprehensionKind::Generator => "GeneratorExp",
+            };
+            let generators: Vec<String> = generators
+                .iter()
+                .map(|generator| {
+                    format!(
+                        "comprehension(target={}, iter={}, ifs={}, is_async=0)",
+                        dump_target(&generator.target),
+                        dump_expression(&generator.iterable),
+                        dump_list(&generator.conditions, dump_expression)
+                    )
+                })
+                .collect();
+            format!("{}(elt={}, generators=[{}])", name, dump_expression(element), generators.join(", "))
+        }
+        ASTNode::Lambda { parameters, body } => {
+            format!("Lambda(args={}, body={})", dump_arguments(parameters), dump_expression(body))
+        }
+        statement => dump_statement(statement),
+    }
+}
+
+fn dump_elements(elements: &[ASTNode], context: &str) -> String {
+    let items: Vec<String> = elements.iter().map(|element| dump_with_context(element, context)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn dump_name(name: &str, context: &str) -> String {
+    format!("Name(id={}, ctx={}())", python_string_repr(name), context)
+}
+
+fn dump_keywords(keywords: &[KeywordArgument]) -> String {
+    let keywords: Vec<String> = keywords
+        .iter()
+        .map(|keyword| match &keyword.name {
+            Some(name) => format!("keyword(arg={}, value={})", python_string_repr(name), dump_expression(&keyword.value)),
+            None => format!("keyword(value={})", dump_expression(&keyword.value)),
+        })
+        .collect();
+    format!("[{}]", keywords.join(", "))
+}
+
+/// Dumps a parameter list as CPython's `arguments` node.
+fn dump_arguments(parameters: &[Parameter]) -> String {
+    let dump_arg = |parameter: &Parameter| match &parameter.annotation {
+        Some(annotation) => format!("arg(arg={}, annotation={})", python_string_repr(&parameter.name), dump_expression(annotation)),
+        None => format!("arg(arg={})", python_string_repr(&parameter.name)),
+    };
+    let of_kind = |kind: ParameterKind| parameters.iter().filter(move |parameter| parameter.kind == kind);
+    let join = |items: Vec<String>| format!("[{}]", items.join(", "));
+
+    let mut fields = vec![
+        format!("posonlyargs={}", join(of_kind(ParameterKind::PositionalOnly).map(dump_arg).collect())),
+        format!("args={}", join(of_kind(ParameterKind::PositionalOrKeyword).map(dump_arg).collect())),
+    ];
+    if let Some(vararg) = of_kind(ParameterKind::VarPositional).next() {
+        fields.push(format!("vararg={}", dump_arg(vararg)));
+    }
+    fields.push(format!("kwonlyargs={}", join(of_kind(ParameterKind::KeywordOnly).map(dump_arg).collect())));
+    fields.push(format!(
+        "kw_defaults={}",
+        join(
+            of_kind(ParameterKind::KeywordOnly)
+                .map(|parameter| parameter.default.as_ref().map_or("None".to_string(), dump_expression))
+                .collect()
+        )
+    ));
+    if let Some(kwarg) = of_kind(ParameterKind::VarKeyword).next() {
+        fields.push(format!("kwarg={}", dump_arg(kwarg)));
+    }
+    fields.push(format!(
+        "defaults={}",
+        join(
+            parameters
+                .iter()
+                .filter(|parameter| matches!(parameter.kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword))
+                .filter_map(|parameter| parameter.default.as_ref().map(dump_expression))
+                .collect()
+        )
+    ));
+    format!("arguments({})", fields.join(", "))
+}
+
+fn python_operator_name(operator: &str) -> &str {
+    match operator {
+        "+" => "Add",
+        "-" => "Sub",
+        "*" => "Mult",
+        "/" => "Div",
+        "//" => "FloorDiv",
+        "%" => "Mod",
+        "**" => "Pow",
+        "<<" => "LShift",
+        ">>" => "RShift",
+        "|" => "BitOr",
+        "^" => "BitXor",
+        "&" => "BitAnd",
+        "@" => "MatMult",
+        other => other,
+    }
+}
+
+fn python_comparison_name(operator: &str) -> &str {
+    match operator {
+        "==" => "Eq",
+        "!=" => "NotEq",
+        "<" => "Lt",
+        "<=" => "LtE",
+        ">" => "Gt",
+        ">=" => "GtE",
+        "is" => "Is",
+        "is not" => "IsNot",
+        "in" => "In",
+        "not in" => "NotIn",
+        other => other,
+    }
+}
+
+/// Formats a float the way Python's `repr` does for the common cases.
+fn python_float_repr(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    let magnitude = value.abs();
+    if magnitude != 0.0 && !(1e-4..1e16).contains(&magnitude) {
+        let formatted = format!("{:e}", value);
+        let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        return format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs());
+    }
+    format!("{:?}", value)
+}
+
+/// Formats the imaginary number `value`j as Python's `repr` does, which
+/// drops the `.0` of a whole imaginary part.
+fn python_imaginary_repr(value: f64) -> String {
+    let repr = python_float_repr(value);
+    format!("{}j", repr.strip_suffix(".0").unwrap_or(&repr))
+}
+
+/// Quotes bytes the way Python's `repr` does.
+pub(crate) fn python_bytes_repr(value: &[u8]) -> String {
+    let quote = if value.contains(&b'\'') && !value.contains(&b'"') { '"' } else { '\'' };
+    let mut out = String::from("b");
+    out.push(quote);
+    for byte in value {
+        match byte {
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\r' => out.push_str("\\r"),
+            b'\t' => out.push_str("\\t"),
+            byte if *byte as char == quote => {
+                out.push('\\');
+                out.push(quote);
+            }
+            0x20..=0x7e => out.push(*byte as char),
+            byte => out.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    out.push(quote);
+    out
+}
+
+/// Quotes a string the way Python's `repr` does.
+pub(crate) fn python_string_repr(value: &str) -> String {
+    let quote = if value.contains('\'') && !value.contains('"') { '"' } else { '\'' };
+    let mut out = String::new();
+    out.push(quote);
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
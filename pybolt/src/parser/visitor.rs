@@ -0,0 +1,777 @@
+use crate::lexer::lexer::Span;
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler,
+    FunctionDefinition, ImportAlias, KeywordArgument, Parameter, WithItem,
+};
+use crate::runtime::bigint::BigInt;
+
+/// Read-only traversal over the AST.
+///
+/// Every method defaults to the matching `walk_*` function, which visits the
+/// node's children in evaluation order. Implementors override the methods for
+/// the nodes they care about and call the `walk_*` function to keep descending.
+pub trait Visitor: Sized {
+    fn visit_node(&mut self, node: &ASTNode) {
+        walk_node(self, node);
+    }
+
+    fn visit_program(&mut self, statements: &[ASTNode]) {
+        walk_program(self, statements);
+    }
+
+    /// The statements of a function, class, loop or branch body.
+    fn visit_block(&mut self, statements: &[ASTNode]) {
+        walk_block(self, statements);
+    }
+
+    fn visit_variable_declaration(&mut self, name: &str, value: &ASTNode, span: Span) {
+        walk_variable_declaration(self, name, value, span);
+    }
+
+    fn visit_expression_statement(&mut self, expression: &ASTNode, _span: Span) {
+        self.visit_node(expression);
+    }
+
+    fn visit_assignment(&mut self, targets: &[ASTNode], value: &ASTNode, span: Span) {
+        walk_assignment(self, targets, value, span);
+    }
+
+    fn visit_augmented_assignment(&mut self, target: &ASTNode, operator: &str, value: &ASTNode, span: Span) {
+        walk_augmented_assignment(self, target, operator, value, span);
+    }
+
+    fn visit_annotated_assignment(&mut self, target: &ASTNode, annotation: &ASTNode, value: Option<&ASTNode>, span: Span) {
+        walk_annotated_assignment(self, target, annotation, value, span);
+    }
+
+    fn visit_if(&mut self, condition: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], span: Span) {
+        walk_if(self, condition, body, orelse, span);
+    }
+
+    fn visit_while(&mut self, condition: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], span: Span) {
+        walk_while(self, condition, body, orelse, span);
+    }
+
+    fn visit_for(&mut self, target: &ASTNode, iterable: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], span: Span) {
+        walk_for(self, target, iterable, body, orelse, span);
+    }
+
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        walk_function_definition(self, function);
+    }
+
+    fn visit_class_definition(&mut self, class: &ClassDefinition) {
+        walk_class_definition(self, class);
+    }
+
+    /// Annotations and default values of a parameter list, which are
+    /// evaluated in the enclosing scope.
+    fn visit_parameters(&mut self, parameters: &[Parameter]) {
+        walk_parameters(self, parameters);
+    }
+
+    fn visit_return(&mut self, value: Option<&ASTNode>, _span: Span) {
+        if let Some(value) = value {
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &[ASTNode],
+        handlers: &[ExceptHandler],
+        orelse: &[ASTNode],
+        finalbody: &[ASTNode],
+        span: Span,
+    ) {
+        walk_try(self, body, handlers, orelse, finalbody, span);
+    }
+
+    /// An `except` clause. The bound name is not visited as an identifier.
+    fn visit_except_handler(&mut self, handler: &ExceptHandler) {
+        walk_except_handler(self, handler);
+    }
+
+    fn visit_with(&mut self, items: &[WithItem], body: &[ASTNode], span: Span) {
+        walk_with(self, items, body, span);
+    }
+
+    fn visit_raise(&mut self, exception: Option<&ASTNode>, cause: Option<&ASTNode>, _span: Span) {
+        for value in [exception, cause].into_iter().flatten() {
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_import(&mut self, _names: &[ImportAlias], _span: Span) {}
+
+    fn visit_import_from(&mut self, _module: &str, _names: &[ImportAlias], _span: Span) {}
+
+    fn visit_global(&mut self, _names: &[String], _span: Span) {}
+
+    fn visit_nonlocal(&mut self, _names: &[String], _span: Span) {}
+
+    fn visit_pass(&mut self, _span: Span) {}
+
+    fn visit_break(&mut self, _span: Span) {}
+
+    fn visit_continue(&mut self, _span: Span) {}
+
+    fn visit_expression(&mut self, expression: &ASTNodeType) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_binary_operation(&mut self, left: &ASTNode, operator: &str, right: &ASTNode) {
+        walk_binary_operation(self, left, operator, right);
+    }
+
+    fn visit_unary_operation(&mut self, _operator: &str, operand: &ASTNode) {
+        self.visit_node(operand);
+    }
+
+    fn visit_boolean_operation(&mut self, _operator: &str, values: &[ASTNode]) {
+        walk_nodes(self, values);
+    }
+
+    fn visit_comparison(&mut self, left: &ASTNode, _operators: &[String], comparators: &[ASTNode]) {
+        self.visit_node(left);
+        walk_nodes(self, comparators);
+    }
+
+    fn visit_conditional(&mut self, condition: &ASTNode, body: &ASTNode, orelse: &ASTNode) {
+        self.visit_node(condition);
+        self.visit_node(body);
+        self.visit_node(orelse);
+    }
+
+    fn visit_call(&mut self, function: &ASTNode, arguments: &[ASTNode], keywords: &[KeywordArgument], span: Span) {
+        walk_call(self, function, arguments, keywords, span);
+    }
+
+    fn visit_attribute(&mut self, value: &ASTNode, _attribute: &str) {
+        self.visit_node(value);
+    }
+
+    fn visit_subscript(&mut self, value: &ASTNode, index: &ASTNode) {
+        self.visit_node(value);
+        self.visit_node(index);
+    }
+
+    fn visit_slice(&mut self, lower: Option<&ASTNode>, upper: Option<&ASTNode>, step: Option<&ASTNode>) {
+        for part in [lower, upper, step].into_iter().flatten() {
+            self.visit_node(part);
+        }
+    }
+
+    fn visit_starred(&mut self, value: &ASTNode) {
+        self.visit_node(value);
+    }
+
+    fn visit_list(&mut self, elements: &[ASTNode]) {
+        walk_nodes(self, elements);
+    }
+
+    fn visit_tuple(&mut self, elements: &[ASTNode]) {
+        walk_nodes(self, elements);
+    }
+
+    fn visit_set(&mut self, elements: &[ASTNode]) {
+        walk_nodes(self, elements);
+    }
+
+    fn visit_dict(&mut self, entries: &[(ASTNode, ASTNode)]) {
+        for (key, value) in entries {
+            self.visit_node(key);
+            self.visit_node(value);
+        }
+    }
+
+    fn visit_comprehension(&mut self, kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator]) {
+        walk_comprehension(self, kind, element, generators);
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Parameter], body: &ASTNode) {
+        walk_lambda(self, parameters, body);
+    }
+
+    fn visit_joined_str(&mut self, parts: &[ASTNode]) {
+        walk_nodes(self, parts);
+    }
+
+    fn visit_formatted_value(&mut self, value: &ASTNode, _conversion: Option<char>, format_spec: Option<&ASTNode>) {
+        self.visit_node(value);
+        if let Some(format_spec) = format_spec {
+            self.visit_node(format_spec);
+        }
+    }
+
+    fn visit_identifier(&mut self, _name: &str) {}
+
+    fn visit_number(&mut self, _value: i64) {}
+
+    fn visit_big_number(&mut self, _value: &BigInt) {}
+
+    fn visit_float(&mut self, _value: f64) {}
+
+    fn visit_imaginary(&mut self, _value: f64) {}
+
+    fn visit_string(&mut self, _value: &str) {}
+
+    fn visit_bytes(&mut self, _value: &[u8]) {}
+
+    fn visit_boolean(&mut self, _value: bool) {}
+
+    fn visit_none(&mut self) {}
+
+    fn visit_ellipsis(&mut self) {}
+}
+
+pub fn walk_node<V: Visitor>(visitor: &mut V, node: &ASTNode) {
+    match node {
+        ASTNode::Program(statements) => visitor.visit_program(statements),
+        ASTNode::VariableDeclaration { name, value, span } => visitor.visit_variable_declaration(name, value, *span),
+        ASTNode::Expression(expression) => visitor.visit_expression(expression),
+        ASTNode::Number(value) => visitor.visit_number(*value),
+        ASTNode::BigNumber(value) => visitor.visit_big_number(value),
+        ASTNode::BinaryOperation { left, operator, right } => visitor.visit_binary_operation(left, operator, right),
+        ASTNode::Float(value) => visitor.visit_float(*value),
+        ASTNode::Imaginary(value) => visitor.visit_imaginary(*value),
+        ASTNode::StringLiteral(value) => visitor.visit_string(value),
+        ASTNode::Bytes(value) => visitor.visit_bytes(value),
+        ASTNode::JoinedStr(parts) => visitor.visit_joined_str(parts),
+        ASTNode::FormattedValue { value, conversion, format_spec } => {
+            visitor.visit_formatted_value(value, *conversion, format_spec.as_deref())
+        }
+        ASTNode::Boolean(value) => visitor.visit_boolean(*value),
+        ASTNode::NoneLiteral => visitor.visit_none(),
+        ASTNode::Ellipsis => visitor.visit_ellipsis(),
+        ASTNode::UnaryOperation { operator, operand } => visitor.visit_unary_operation(operator, operand),
+        ASTNode::BooleanOperation { operator, values } => visitor.visit_boolean_operation(operator, values),
+        ASTNode::Comparison { left, operators, comparators } => visitor.visit_comparison(left, operators, comparators),
+        ASTNode::Conditional { condition, body, orelse } => visitor.visit_conditional(condition, body, orelse),
+        ASTNode::Call { function, arguments, keywords, span } => visitor.visit_call(function, arguments, keywords, *span),
+        ASTNode::Attribute { value, attribute } => visitor.visit_attribute(value, attribute),
+        ASTNode::Subscript { value, index } => visitor.visit_subscript(value, index),
+        ASTNode::Slice { lower, upper, step } => {
+            visitor.visit_slice(lower.as_deref(), upper.as_deref(), step.as_deref())
+        }
+        ASTNode::Starred(value) => visitor.visit_starred(value),
+        ASTNode::List(elements) => visitor.visit_list(elements),
+        ASTNode::Tuple(elements) => visitor.visit_tuple(elements),
+        ASTNode::Set(elements) => visitor.visit_set(elements),
+        ASTNode::Dict(entries) => visitor.visit_dict(entries),
+        ASTNode::Comprehension { kind, element, generators } => visitor.visit_comprehension(*kind, element, generators),
+        ASTNode::Lambda { parameters, body } => visitor.visit_lambda(parameters, body),
+        ASTNode::ExpressionStatement { expression, span } => visitor.visit_expression_statement(expression, *span),
+        ASTNode::Assignment { targets, value, span } => visitor.visit_assignment(targets, value, *span),
+        ASTNode::AugmentedAssignment { target, operator, value, span } => {
+            visitor.visit_augmented_assignment(target, operator, value, *span)
+        }
+        ASTNode::AnnotatedAssignment { target, annotation, value, span } => {
+            visitor.visit_annotated_assignment(target, annotation, value.as_deref(), *span)
+        }
+        ASTNode::Import { names, span } => visitor.visit_import(names, *span),
+        ASTNode::ImportFrom { module, names, span } => visitor.visit_import_from(module, names, *span),
+        ASTNode::If { condition, body, orelse, span } => visitor.visit_if(condition, body, orelse, *span),
+        ASTNode::While { condition, body, orelse, span } => visitor.visit_while(condition, body, orelse, *span),
+        ASTNode::For { target, iterable, body, orelse, span } => visitor.visit_for(target, iterable, body, orelse, *span),
+        ASTNode::FunctionDefinition(function) => visitor.visit_function_definition(function),
+        ASTNode::ClassDefinition(class) => visitor.visit_class_definition(class),
+        ASTNode::Return { value, span } => visitor.visit_return(value.as_deref(), *span),
+        ASTNode::Try { body, handlers, orelse, finalbody, span } => {
+            visitor.visit_try(body, handlers, orelse, finalbody, *span)
+        }
+        ASTNode::Raise { exception, cause, span } => visitor.visit_raise(exception.as_deref(), cause.as_deref(), *span),
+        ASTNode::With { items, body, span } => visitor.visit_with(items, body, *span),
+        ASTNode::Global { names, span } => visitor.visit_global(names, *span),
+        ASTNode::Nonlocal { names, span } => visitor.visit_nonlocal(names, *span),
+        ASTNode::Pass { span } => visitor.visit_pass(*span),
+        ASTNode::Break { span } => visitor.visit_break(*span),
+        ASTNode::Continue { span } => visitor.visit_continue(*span),
+    }
+}
+
+pub fn walk_nodes<V: Visitor>(visitor: &mut V, nodes: &[ASTNode]) {
+    for node in nodes {
+        visitor.visit_node(node);
+    }
+}
+
+pub fn walk_program<V: Visitor>(visitor: &mut V, statements: &[ASTNode]) {
+    walk_nodes(visitor, statements);
+}
+
+pub fn walk_block<V: Visitor>(visitor: &mut V, statements: &[ASTNode]) {
+    walk_nodes(visitor, statements);
+}
+
+pub fn walk_variable_declaration<V: Visitor>(visitor: &mut V, _name: &str, value: &ASTNode, _span: Span) {
+    visitor.visit_node(value);
+}
+
+pub fn walk_assignment<V: Visitor>(visitor: &mut V, targets: &[ASTNode], value: &ASTNode, _span: Span) {
+    visitor.visit_node(value);
+    walk_nodes(visitor, targets);
+}
+
+pub fn walk_augmented_assignment<V: Visitor>(
+    visitor: &mut V,
+    target: &ASTNode,
+    _operator: &str,
+    value: &ASTNode,
+    _span: Span,
+) {
+    visitor.visit_node(target);
+    visitor.visit_node(value);
+}
+
+/// Visits the annotation, then the value, then the target.
+pub fn walk_annotated_assignment<V: Visitor>(
+    visitor: &mut V,
+    target: &ASTNode,
+    annotation: &ASTNode,
+    value: Option<&ASTNode>,
+    _span: Span,
+) {
+    visitor.visit_node(annotation);
+    if let Some(value) = value {
+        visitor.visit_node(value);
+    }
+    visitor.visit_node(target);
+}
+
+pub fn walk_if<V: Visitor>(visitor: &mut V, condition: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], _span: Span) {
+    visitor.visit_node(condition);
+    visitor.visit_block(body);
+    visitor.visit_block(orelse);
+}
+
+pub fn walk_while<V: Visitor>(visitor: &mut V, condition: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], _span: Span) {
+    visitor.visit_node(condition);
+    visitor.visit_block(body);
+    visitor.visit_block(orelse);
+}
+
+pub fn walk_try<V: Visitor>(
+    visitor: &mut V,
+    body: &[ASTNode],
+    handlers: &[ExceptHandler],
+    orelse: &[ASTNode],
+    finalbody: &[ASTNode],
+    _span: Span,
+) {
+    visitor.visit_block(body);
+    for handler in handlers {
+        visitor.visit_except_handler(handler);
+    }
+    visitor.visit_block(orelse);
+    visitor.visit_block(finalbody);
+}
+
+pub fn walk_except_handler<V: Visitor>(visitor: &mut V, handler: &ExceptHandler) {
+    if let Some(exception_type) = &handler.exception_type {
+        visitor.visit_node(exception_type);
+    }
+    visitor.visit_block(&handler.body);
+}
+
+pub fn walk_with<V: Visitor>(visitor: &mut V, items: &[WithItem], body: &[ASTNode], _span: Span) {
+    for item in items {
+        visitor.visit_node(&item.context);
+        if let Some(target) = &item.target {
+            visitor.visit_node(target);
+        }
+    }
+    visitor.visit_block(body);
+}
+
+pub fn walk_for<V: Visitor>(
+    visitor: &mut V,
+    target: &ASTNode,
+    iterable: &ASTNode,
+    body: &[ASTNode],
+    orelse: &[ASTNode],
+    _span: Span,
+) {
+    visitor.visit_node(iterable);
+    visitor.visit_node(target);
+    visitor.visit_block(body);
+    visitor.visit_block(orelse);
+}
+
+pub fn walk_function_definition<V: Visitor>(visitor: &mut V, function: &FunctionDefinition) {
+    walk_nodes(visitor, &function.decorators);
+    visitor.visit_parameters(&function.parameters);
+    if let Some(returns) = &function.returns {
+        visitor.visit_node(returns);
+    }
+    visitor.visit_block(&function.body);
+}
+
+pub fn walk_class_definition<V: Visitor>(visitor: &mut V, class: &ClassDefinition) {
+    walk_nodes(visitor, &class.decorators);
+    walk_nodes(visitor, &class.bases);
+    for keyword in &class.keywords {
+        visitor.visit_node(&keyword.value);
+    }
+    visitor.visit_block(&class.body);
+}
+
+pub fn walk_parameters<V: Visitor>(visitor: &mut V, parameters: &[Parameter]) {
+    for parameter in parameters {
+        if let Some(annotation) = &parameter.annotation {
+            visitor.visit_node(annotation);
+        }
+        if let Some(default) = &parameter.default {
+            visitor.visit_node(default);
+        }
+    }
+}
+
+pub fn walk_expression<V: Visitor>(visitor: &mut V, expression: &ASTNodeType) {
+    match expression {
+        ASTNodeType::Identifier(name) => visitor.visit_identifier(name),
+        ASTNodeType::Number(value) => visitor.visit_number(*value),
+        ASTNodeType::BinaryOperation { operator, left, right } => visitor.visit_binary_operation(left, operator, right),
+    }
+}
+
+pub fn walk_binary_operation<V: Visitor>(visitor: &mut V, left: &ASTNode, _operator: &str, right: &ASTNode) {
+    visitor.visit_node(left);
+    visitor.visit_node(right);
+}
+
+pub fn walk_call<V: Visitor>(
+    visitor: &mut V,
+    function: &ASTNode,
+    arguments: &[ASTNode],
+    keywords: &[KeywordArgument],
+    _span: Span,
+) {
+    visitor.visit_node(function);
+    walk_nodes(visitor, arguments);
+    for keyword in keywords {
+        visitor.visit_node(&keyword.value);
+    }
+}
+
+/// Visits the generators in evaluation order (iterable, target, conditions)
+/// followed by the element.
+pub fn walk_comprehension<V: Visitor>(
+    visitor: &mut V,
+    _kind: ComprehensionKind,
+    element: &ASTNode,
+    generators: &[ComprehensionGenerator],
+) {
+    for generator in generators {
+        visitor.visit_node(&generator.iterable);
+        visitor.visit_node(&generator.target);
+        walk_nodes(visitor, &generator.conditions);
+    }
+    visitor.visit_node(element);
+}
+
+pub fn walk_lambda<V: Visitor>(visitor: &mut V, parameters: &[Parameter], body: &ASTNode) {
+    visitor.visit_parameters(parameters);
+    visitor.visit_node(body);
+}
+
+/// Mutable traversal over the AST.
+///
+/// Mirrors `Visitor`, but hands out mutable references so a pass can rewrite
+/// nodes in place. Overriding `visit_node_mut` and assigning through the
+/// reference replaces a node entirely, which is how desugaring passes fold one
+/// construct into another.
+pub trait VisitorMut: Sized {
+    fn visit_node_mut(&mut self, node: &mut ASTNode) {
+        walk_node_mut(self, node);
+    }
+
+    fn visit_program_mut(&mut self, statements: &mut Vec<ASTNode>) {
+        walk_nodes_mut(self, statements);
+    }
+
+    fn visit_block_mut(&mut self, statements: &mut Vec<ASTNode>) {
+        walk_nodes_mut(self, statements);
+    }
+
+    fn visit_variable_declaration_mut(&mut self, _name: &mut String, value: &mut ASTNode, _span: Span) {
+        self.visit_node_mut(value);
+    }
+
+    fn visit_expression_statement_mut(&mut self, expression: &mut ASTNode, _span: Span) {
+        self.visit_node_mut(expression);
+    }
+
+    fn visit_assignment_mut(&mut self, targets: &mut Vec<ASTNode>, value: &mut ASTNode, _span: Span) {
+        self.visit_node_mut(value);
+        walk_nodes_mut(self, targets);
+    }
+
+    fn visit_augmented_assignment_mut(&mut self, target: &mut ASTNode, _operator: &mut String, value: &mut ASTNode, _span: Span) {
+        self.visit_node_mut(target);
+        self.visit_node_mut(value);
+    }
+
+    fn visit_annotated_assignment_mut(
+        &mut self,
+        target: &mut ASTNode,
+        annotation: &mut ASTNode,
+        value: Option<&mut ASTNode>,
+        _span: Span,
+    ) {
+        self.visit_node_mut(annotation);
+        if let Some(value) = value {
+            self.visit_node_mut(value);
+        }
+        self.visit_node_mut(target);
+    }
+
+    fn visit_if_mut(&mut self, condition: &mut ASTNode, body: &mut Vec<ASTNode>, orelse: &mut Vec<ASTNode>, _span: Span) {
+        self.visit_node_mut(condition);
+        self.visit_block_mut(body);
+        self.visit_block_mut(orelse);
+    }
+
+    fn visit_while_mut(&mut self, condition: &mut ASTNode, body: &mut Vec<ASTNode>, orelse: &mut Vec<ASTNode>, _span: Span) {
+        self.visit_node_mut(condition);
+        self.visit_block_mut(body);
+        self.visit_block_mut(orelse);
+    }
+
+    fn visit_for_mut(
+        &mut self,
+        target: &mut ASTNode,
+        iterable: &mut ASTNode,
+        body: &mut Vec<ASTNode>,
+        orelse: &mut Vec<ASTNode>,
+        _span: Span,
+    ) {
+        self.visit_node_mut(iterable);
+        self.visit_node_mut(target);
+        self.visit_block_mut(body);
+        self.visit_block_mut(orelse);
+    }
+
+    fn visit_with_mut(&mut self, items: &mut Vec<WithItem>, body: &mut Vec<ASTNode>, _span: Span) {
+        for item in items {
+            self.visit_node_mut(&mut item.context);
+            if let Some(target) = &mut item.target {
+                self.visit_node_mut(target);
+            }
+        }
+        self.visit_block_mut(body);
+    }
+
+    fn visit_function_definition_mut(&mut self, function: &mut FunctionDefinition) {
+        walk_nodes_mut(self, &mut function.decorators);
+        self.visit_parameters_mut(&mut function.parameters);
+        if let Some(returns) = function.returns.as_deref_mut() {
+            self.visit_node_mut(returns);
+        }
+        self.visit_block_mut(&mut function.body);
+    }
+
+    fn visit_class_definition_mut(&mut self, class: &mut ClassDefinition) {
+        walk_nodes_mut(self, &mut class.decorators);
+        walk_nodes_mut(self, &mut class.bases);
+        for keyword in &mut class.keywords {
+            self.visit_node_mut(&mut keyword.value);
+        }
+        self.visit_block_mut(&mut class.body);
+    }
+
+    fn visit_parameters_mut(&mut self, parameters: &mut Vec<Parameter>) {
+        for parameter in parameters {
+            if let Some(annotation) = &mut parameter.annotation {
+                self.visit_node_mut(annotation);
+            }
+            if let Some(default) = &mut parameter.default {
+                self.visit_node_mut(default);
+            }
+        }
+    }
+
+    fn visit_return_mut(&mut self, value: Option<&mut ASTNode>, _span: Span) {
+        if let Some(value) = value {
+            self.visit_node_mut(value);
+        }
+    }
+
+    fn visit_try_mut(
+        &mut self,
+        body: &mut Vec<ASTNode>,
+        handlers: &mut Vec<ExceptHandler>,
+        orelse: &mut Vec<ASTNode>,
+        finalbody: &mut Vec<ASTNode>,
+        _span: Span,
+    ) {
+        self.visit_block_mut(body);
+        for handler in handlers {
+            if let Some(exception_type) = &mut handler.exception_type {
+                self.visit_node_mut(exception_type);
+            }
+            self.visit_block_mut(&mut handler.body);
+        }
+        self.visit_block_mut(orelse);
+        self.visit_block_mut(finalbody);
+    }
+
+    fn visit_raise_mut(&mut self, exception: Option<&mut ASTNode>, cause: Option<&mut ASTNode>, _span: Span) {
+        for value in [exception, cause].into_iter().flatten() {
+            self.visit_node_mut(value);
+        }
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut ASTNodeType) {
+        walk_expression_mut(self, expression);
+    }
+
+    fn visit_binary_operation_mut(&mut self, left: &mut ASTNode, operator: &mut String, right: &mut ASTNode) {
+        walk_binary_operation_mut(self, left, operator, right);
+    }
+
+    fn visit_call_mut(
+        &mut self,
+        function: &mut ASTNode,
+        arguments: &mut Vec<ASTNode>,
+        keywords: &mut Vec<KeywordArgument>,
+        _span: Span,
+    ) {
+        self.visit_node_mut(function);
+        walk_nodes_mut(self, arguments);
+        for keyword in keywords {
+            self.visit_node_mut(&mut keyword.value);
+        }
+    }
+
+    fn visit_comprehension_mut(
+        &mut self,
+        _kind: ComprehensionKind,
+        element: &mut ASTNode,
+        generators: &mut Vec<ComprehensionGenerator>,
+    ) {
+        for generator in generators {
+            self.visit_node_mut(&mut generator.iterable);
+            self.visit_node_mut(&mut generator.target);
+            walk_nodes_mut(self, &mut generator.conditions);
+        }
+        self.visit_node_mut(element);
+    }
+
+    fn visit_lambda_mut(&mut self, parameters: &mut Vec<Parameter>, body: &mut ASTNode) {
+        self.visit_parameters_mut(parameters);
+        self.visit_node_mut(body);
+    }
+
+    fn visit_identifier_mut(&mut self, _name: &mut String) {}
+
+    fn visit_number_mut(&mut self, _value: &mut i64) {}
+}
+
+pub fn walk_node_mut<V: VisitorMut>(visitor: &mut V, node: &mut ASTNode) {
+    match node {
+        ASTNode::Program(statements) => visitor.visit_program_mut(statements),
+        ASTNode::VariableDeclaration { name, value, span } => visitor.visit_variable_declaration_mut(name, value, *span),
+        ASTNode::Expression(expression) => visitor.visit_expression_mut(expression),
+        ASTNode::Number(value) => visitor.visit_number_mut(value),
+        ASTNode::BinaryOperation { left, operator, right } => visitor.visit_binary_operation_mut(left, operator, right),
+        ASTNode::BigNumber(_)
+        | ASTNode::Float(_)
+        | ASTNode::Imaginary(_)
+        | ASTNode::StringLiteral(_)
+        | ASTNode::Bytes(_)
+        | ASTNode::Boolean(_)
+        | ASTNode::NoneLiteral
+        | ASTNode::Ellipsis => {}
+        ASTNode::JoinedStr(parts) => walk_nodes_mut(visitor, parts),
+        ASTNode::FormattedValue { value, format_spec, .. } => {
+            visitor.visit_node_mut(value);
+            if let Some(format_spec) = format_spec {
+                visitor.visit_node_mut(format_spec);
+            }
+        }
+        ASTNode::UnaryOperation { operand, .. } => visitor.visit_node_mut(operand),
+        ASTNode::BooleanOperation { values, .. } => walk_nodes_mut(visitor, values),
+        ASTNode::Comparison { left, comparators, .. } => {
+            visitor.visit_node_mut(left);
+            walk_nodes_mut(visitor, comparators);
+        }
+        ASTNode::Conditional { condition, body, orelse } => {
+            visitor.visit_node_mut(condition);
+            visitor.visit_node_mut(body);
+            visitor.visit_node_mut(orelse);
+        }
+        ASTNode::Call { function, arguments, keywords, span } => visitor.visit_call_mut(function, arguments, keywords, *span),
+        ASTNode::Attribute { value, .. } | ASTNode::Starred(value) => visitor.visit_node_mut(value),
+        ASTNode::Subscript { value, index } => {
+            visitor.visit_node_mut(value);
+            visitor.visit_node_mut(index);
+        }
+        ASTNode::Slice { lower, upper, step } => {
+            for part in [lower, upper, step].into_iter().flatten() {
+                visitor.visit_node_mut(part);
+            }
+        }
+        ASTNode::List(elements) | ASTNode::Tuple(elements) | ASTNode::Set(elements) => walk_nodes_mut(visitor, elements),
+        ASTNode::Dict(entries) => {
+            for (key, value) in entries {
+                visitor.visit_node_mut(key);
+                visitor.visit_node_mut(value);
+            }
+        }
+        ASTNode::Comprehension { kind, element, generators } => visitor.visit_comprehension_mut(*kind, element, generators),
+        ASTNode::Lambda { parameters, body } => visitor.visit_lambda_mut(parameters, body),
+        ASTNode::ExpressionStatement { expression, span } => visitor.visit_expression_statement_mut(expression, *span),
+        ASTNode::Assignment { targets, value, span } => visitor.visit_assignment_mut(targets, value, *span),
+        ASTNode::AugmentedAssignment { target, operator, value, span } => {
+            visitor.visit_augmented_assignment_mut(target, operator, value, *span)
+        }
+        ASTNode::AnnotatedAssignment { target, annotation, value, span } => {
+            visitor.visit_annotated_assignment_mut(target, annotation, value.as_deref_mut(), *span)
+        }
+        ASTNode::If { condition, body, orelse, span } => visitor.visit_if_mut(condition, body, orelse, *span),
+        ASTNode::While { condition, body, orelse, span } => visitor.visit_while_mut(condition, body, orelse, *span),
+        ASTNode::For { target, iterable, body, orelse, span } => visitor.visit_for_mut(target, iterable, body, orelse, *span),
+        ASTNode::FunctionDefinition(function) => visitor.visit_function_definition_mut(function),
+        ASTNode::ClassDefinition(class) => visitor.visit_class_definition_mut(class),
+        ASTNode::Return { value, span } => visitor.visit_return_mut(value.as_deref_mut(), *span),
+        ASTNode::Try { body, handlers, orelse, finalbody, span } => {
+            visitor.visit_try_mut(body, handlers, orelse, finalbody, *span)
+        }
+        ASTNode::Raise { exception, cause, span } => {
+            visitor.visit_raise_mut(exception.as_deref_mut(), cause.as_deref_mut(), *span)
+        }
+        ASTNode::With { items, body, span } => visitor.visit_with_mut(items, body, *span),
+        ASTNode::Import { .. }
+        | ASTNode::ImportFrom { .. }
+        | ASTNode::Global { .. }
+        | ASTNode::Nonlocal { .. }
+        | ASTNode::Pass { .. }
+        | ASTNode::Break { .. }
+        | ASTNode::Continue { .. } => {}
+    }
+}
+
+pub fn walk_nodes_mut<V: VisitorMut>(visitor: &mut V, nodes: &mut [ASTNode]) {
+    for node in nodes {
+        visitor.visit_node_mut(node);
+    }
+}
+
+pub fn walk_expression_mut<V: VisitorMut>(visitor: &mut V, expression: &mut ASTNodeType) {
+    match expression {
+        ASTNodeType::Identifier(name) => visitor.visit_identifier_mut(name),
+        ASTNodeType::Number(value) => visitor.visit_number_mut(value),
+        ASTNodeType::BinaryOperation { operator, left, right } => visitor.visit_binary_operation_mut(left, operator, right),
+    }
+}
+
+pub fn walk_binary_operation_mut<V: VisitorMut>(
+    visitor: &mut V,
+    left: &mut ASTNode,
+    _operator: &mut String,
+    right: &mut ASTNode,
+) {
+    visitor.visit_node_mut(left);
+    visitor.visit_node_mut(right);
+}
@@ -0,0 +1,446 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// An integer of any size, for the Python `int`s that do not fit in an
+/// `i64`. The magnitude is kept in base 2^32, least significant digit
+/// first, without leading zero digits; zero has no digits and is never
+/// negative, so that equal integers have equal representations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut digits: Vec<u32>) -> Self {
+        trim(&mut digits);
+        let negative = negative && !digits.is_empty();
+        BigInt { negative, digits }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The value as an `i64`, if it fits.
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0u64, |value, digit| (value << 32) | u64::from(*digit));
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    /// Parses a decimal integer with an optional `-` sign.
+    pub fn parse(text: &str) -> Option<BigInt> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, text),
+        };
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        // Nine decimal digits at a time, which fit in a digit.
+        let first = digits.len() % 9;
+        let chunks = std::iter::once(&digits[..first]).chain(digits.as_bytes()[first..].chunks(9).map(|chunk| {
+            std::str::from_utf8(chunk).expect("the digits are ASCII")
+        }));
+        for chunk in chunks.filter(|chunk| !chunk.is_empty()) {
+            let scale = 10u32.pow(chunk.len() as u32);
+            let value: u32 = chunk.parse().ok()?;
+            mul_small_add(&mut magnitude, scale, value);
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    /// Parses the digits of a non-negative integer in `radix`, which is at
+    /// most 36.
+    pub fn parse_radix(digits: &str, radix: u32) -> Option<BigInt> {
+        if digits.is_empty() {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for digit in digits.chars() {
+            mul_small_add(&mut magnitude, radix, digit.to_digit(radix)?);
+        }
+        Some(BigInt::new(false, magnitude))
+    }
+
+    /// The integer part of a finite float, or `None` for infinities and
+    /// NaNs.
+    pub fn from_f64(value: f64) -> Option<BigInt> {
+        if !value.is_finite() {
+            return None;
+        }
+        let bits = value.to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let mut mantissa = bits & ((1 << 52) - 1);
+        if exponent != 0 {
+            mantissa |= 1 << 52;
+        }
+        // The value is `mantissa * 2^shift`.
+        let shift = exponent.max(1) - 1075;
+        let magnitude = from_u64(if shift < 0 { mantissa.checked_shr(-shift as u32).unwrap_or(0) } else { mantissa });
+        let magnitude = if shift > 0 { shift_left(&magnitude, shift as usize) } else { magnitude };
+        Some(BigInt::new(value < 0.0, magnitude))
+    }
+
+    /// The nearest float, with ties going to the even one, as Python's
+    /// `float()` rounds; `None` if it is too large for a float.
+    pub fn to_f64(&self) -> Option<f64> {
+        if self.is_zero() {
+            return Some(0.0);
+        }
+        let magnitude = ratio_to_f64(&self.digits, &[1])?;
+        Some(if self.negative { -magnitude } else { magnitude })
+    }
+
+    /// The quotient rounded towards negative infinity and the remainder,
+    /// which has the sign of the divisor, as Python's `divmod` gives them;
+    /// `None` when dividing by zero.
+    pub fn div_mod_floor(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem(&self.digits, &other.digits);
+        let quotient = BigInt::new(self.negative != other.negative, quotient);
+        let remainder = BigInt::new(self.negative, remainder);
+        if !remainder.is_zero() && remainder.negative != other.negative {
+            Some((&quotient - &BigInt::from(1), &remainder + other))
+        } else {
+            Some((quotient, remainder))
+        }
+    }
+
+    /// The quotient as the nearest float, as Python's `/` computes it
+    /// without rounding either operand first; `None` when dividing by zero
+    /// or if the quotient is too large for a float.
+    pub fn true_divide(&self, other: &BigInt) -> Option<f64> {
+        if other.is_zero() {
+            return None;
+        }
+        let negative = self.negative != other.negative;
+        let magnitude = if self.is_zero() { 0.0 } else { ratio_to_f64(&self.digits, &other.digits)? };
+        Some(if negative { -magnitude } else { magnitude })
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        BigInt::new(value < 0, from_u64(value.unsigned_abs()))
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitudes(&self.digits, &other.digits),
+            (true, true) => compare_magnitudes(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.digits.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitudes(&self.digits, &other.digits));
+        }
+        match compare_magnitudes(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitudes(&other.digits, &self.digits)),
+            _ => BigInt::new(self.negative, sub_magnitudes(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, left) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, right) in other.digits.iter().enumerate() {
+                let sum = u64::from(*left) * u64::from(*right) + u64::from(product[i + j]) + carry;
+                product[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            product[i + other.digits.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // Nine decimal digits at a time, least significant first.
+        let mut chunks = Vec::new();
+        let mut magnitude = self.digits.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_small(&mut magnitude, 1_000_000_000));
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        write!(f, "{}", chunks.next().expect("a non-zero integer has digits"))?;
+        for chunk in chunks {
+            write!(f, "{:09}", chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(digits: &mut Vec<u32>) {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+}
+
+fn from_u64(value: u64) -> Vec<u32> {
+    let mut digits = vec![value as u32, (value >> 32) as u32];
+    trim(&mut digits);
+    digits
+}
+
+fn bit_length(digits: &[u32]) -> usize {
+    match digits.last() {
+        Some(last) => digits.len() * 32 - last.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn compare_magnitudes(left: &[u32], right: &[u32]) -> Ordering {
+    left.len().cmp(&right.len()).then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_magnitudes(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let (long, short) = if left.len() >= right.len() { (left, right) } else { (right, left) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (index, digit) in long.iter().enumerate() {
+        let total = u64::from(*digit) + u64::from(short.get(index).copied().unwrap_or(0)) + carry;
+        sum.push(total as u32);
+        carry = total >> 32;
+    }
+    sum.push(carry as u32);
+    sum
+}
+
+/// `left - right`, where `left` is at least `right`.
+fn sub_magnitudes(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(left.len());
+    let mut borrow = 0i64;
+    for (index, digit) in left.iter().enumerate() {
+        let total = i64::from(*digit) - i64::from(right.get(index).copied().unwrap_or(0)) - borrow;
+        difference.push(total as u32);
+        borrow = i64::from(total < 0);
+    }
+    difference
+}
+
+/// `digits * scale + value`, in place.
+fn mul_small_add(digits: &mut Vec<u32>, scale: u32, value: u32) {
+    let mut carry = u64::from(value);
+    for digit in digits.iter_mut() {
+        let total = u64::from(*digit) * u64::from(scale) + carry;
+        *digit = total as u32;
+        carry = total >> 32;
+    }
+    if carry != 0 {
+        digits.push(carry as u32);
+    }
+}
+
+/// Divides in place by a non-zero `divisor` and returns the remainder.
+fn div_small(digits: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for digit in digits.iter_mut().rev() {
+        let current = (remainder << 32) | u64::from(*digit);
+        *digit = (current / u64::from(divisor)) as u32;
+        remainder = current % u64::from(divisor);
+    }
+    trim(digits);
+    remainder as u32
+}
+
+fn shift_left(digits: &[u32], bits: usize) -> Vec<u32> {
+    let (whole, part) = (bits / 32, bits % 32);
+    let mut shifted = vec![0u32; whole];
+    let mut carry = 0u32;
+    for digit in digits {
+        shifted.push(if part == 0 { *digit } else { (digit << part) | carry });
+        carry = if part == 0 { 0 } else { digit >> (32 - part) };
+    }
+    shifted.push(carry);
+    trim(&mut shifted);
+    shifted
+}
+
+/// The truncated quotient and the remainder of two magnitudes, with Knuth's
+/// algorithm D (The Art of Computer Programming, 4.3.1).
+fn div_rem(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitudes(dividend, divisor) == Ordering::Less {
+        return (Vec::new(), dividend.to_vec());
+    }
+    if let [divisor] = divisor {
+        let mut quotient = dividend.to_vec();
+        let remainder = div_small(&mut quotient, *divisor);
+        return (quotient, from_u64(u64::from(remainder)));
+    }
+    // Normalize so that the divisor's top digit has its high bit set,
+    // which keeps the estimated quotient digits off by at most two.
+    let shift = divisor.last().expect("the divisor is not zero").leading_zeros() as usize;
+    let divisor = shift_left(divisor, shift);
+    let mut remainder = shift_left(dividend, shift);
+    remainder.resize(dividend.len() + 1, 0);
+    let n = divisor.len();
+    let (top, next) = (u64::from(divisor[n - 1]), u64::from(divisor[n - 2]));
+    let mut quotient = vec![0u32; remainder.len() - n];
+    for j in (0..quotient.len()).rev() {
+        let numerator = (u64::from(remainder[j + n]) << 32) | u64::from(remainder[j + n - 1]);
+        let mut estimate = numerator / top;
+        let mut rest = numerator % top;
+        while estimate >> 32 != 0 || estimate * next > ((rest << 32) | u64::from(remainder[j + n - 2])) {
+            estimate -= 1;
+            rest += top;
+            if rest >> 32 != 0 {
+                break;
+            }
+        }
+        // Subtract `estimate * divisor`, adding it back if that was one
+        // too many.
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let product = estimate * u64::from(divisor[i]) + carry;
+            carry = product >> 32;
+            let total = i64::from(remainder[i + j]) - borrow - (product & 0xffff_ffff) as i64;
+            remainder[i + j] = total as u32;
+            borrow = i64::from(total < 0);
+        }
+        let total = i64::from(remainder[j + n]) - borrow - carry as i64;
+        remainder[j + n] = total as u32;
+        if total < 0 {
+            estimate -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u64::from(remainder[i + j]) + u64::from(divisor[i]) + carry;
+                remainder[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            remainder[j + n] = remainder[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = estimate as u32;
+    }
+    trim(&mut quotient);
+    remainder.truncate(n);
+    trim(&mut remainder);
+    let remainder = match shift {
+        0 => remainder,
+        _ => {
+            let mut carry = 0u32;
+            let mut shifted: Vec<u32> = remainder
+                .iter()
+                .rev()
+                .map(|digit| {
+                    let value = (digit >> shift) | carry;
+                    carry = digit << (32 - shift);
+                    value
+                })
+                .collect();
+            shifted.reverse();
+            trim(&mut shifted);
+            shifted
+        }
+    };
+    (quotient, remainder)
+}
+
+/// `numerator / denominator` for non-zero magnitudes, correctly rounded
+/// to a float with ties to even, also where it is subnormal; `None` if it
+/// is too large for a float.
+fn ratio_to_f64(numerator: &[u32], denominator: &[u32]) -> Option<f64> {
+    // Scale so that the integer quotient has 55 or 56 bits: the 53 of a
+    // float, and two more to round with. The remainder says whether any
+    // of the bits below them are set.
+    let shift = 55 - (bit_length(numerator) as i64 - bit_length(denominator) as i64);
+    let (quotient, remainder) = if shift >= 0 {
+        div_rem(&shift_left(numerator, shift as usize), denominator)
+    } else {
+        div_rem(numerator, &shift_left(denominator, (-shift) as usize))
+    };
+    let quotient = quotient.iter().rev().fold(0u64, |value, digit| (value << 32) | u64::from(*digit));
+    let inexact = !remainder.is_empty();
+    // The ratio is `quotient * 2^-shift`, and its leading bit is worth
+    // 2^exponent. Subnormals keep fewer bits.
+    let bits = 64 - quotient.leading_zeros() as i64;
+    let exponent = bits - 1 - shift;
+    if exponent > 1023 {
+        return None;
+    }
+    let precision = if exponent >= -1022 { 53 } else { 53 - (-1022 - exponent) };
+    let dropped = bits - precision;
+    if dropped > 57 {
+        return Some(0.0);
+    }
+    let mut kept = quotient >> dropped;
+    let rest = quotient & ((1u64 << dropped) - 1);
+    let half = 1u64 << (dropped - 1);
+    if rest > half || (rest == half && (inexact || kept & 1 == 1)) {
+        kept += 1;
+    }
+    let result = scale_by_power_of_two(kept as f64, dropped - shift);
+    result.is_finite().then_some(result)
+}
+
+/// `value * 2^exponent`, exact as long as the result is representable.
+fn scale_by_power_of_two(mut value: f64, mut exponent: i64) -> f64 {
+    while exponent > 1000 {
+        value *= 2f64.powi(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 {
+        value *= 2f64.powi(-1000);
+        exponent += 1000;
+    }
+    value * 2f64.powi(exponent as i32)
+}
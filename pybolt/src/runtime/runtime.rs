@@ -1,67 +1,920 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::runtime::bigint::BigInt;
+
+/// The deepest call stack before a call fails with `RecursionError`.
+const RECURSION_LIMIT: usize = 1000;
+
+/// A Python object. The machine instructions that work on unboxed values
+/// take the variant of their type; the others dispatch on it.
+///
+/// An `int` is an `Int` when it fits in an `i64`, which is the fast path,
+/// and a `BigInt` only when it does not.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    BigInt(Rc<BigInt>),
+    Float(f64),
+    Bool(bool),
+    Str(Rc<str>),
+    None,
+    Tuple(Rc<[Value]>),
+    /// A list, which the values that hold it share, so that what one
+    /// stores into it the others see.
+    List(Rc<RefCell<Vec<Value>>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Int,
+    Float,
+    Bool,
+    Str,
+    NoneType,
+    Tuple,
+    List,
+}
+
+impl Class {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Class::Int => "int",
+            Class::Float => "float",
+            Class::Bool => "bool",
+            Class::Str => "str",
+            Class::NoneType => "NoneType",
+            Class::Tuple => "tuple",
+            Class::List => "list",
+        }
+    }
+}
+
+impl Value {
+    /// An `int`, which is only big if it does not fit in an `i64`.
+    pub fn from_bigint(value: BigInt) -> Value {
+        match value.to_i64() {
+            Some(value) => Value::Int(value),
+            None => Value::BigInt(Rc::new(value)),
+        }
+    }
+
+    pub fn class(&self) -> Class {
+        match self {
+            Value::Int(_) | Value::BigInt(_) => Class::Int,
+            Value::Float(_) => Class::Float,
+            Value::Bool(_) => Class::Bool,
+            Value::Str(_) => Class::Str,
+            Value::None => Class::NoneType,
+            Value::Tuple(_) => Class::Tuple,
+            Value::List(_) => Class::List,
+        }
+    }
+
+    /// The value of an `int` that fits in an `i64`, or of a `bool`.
+    fn small_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(value) => Some(*value),
+            Value::Bool(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    fn as_int(&self) -> Result<i64, String> {
+        match self {
+            Value::BigInt(_) => Err(too_large("an i64")),
+            other => other.small_int().ok_or_else(|| format!("TypeError: unsupported operand type: '{}'", other.class().name())),
+        }
+    }
+
+    fn as_bigint(&self) -> Result<BigInt, String> {
+        match self {
+            Value::BigInt(value) => Ok(value.as_ref().clone()),
+            other => Ok(BigInt::from(other.as_int()?)),
+        }
+    }
+
+    fn as_float(&self) -> Result<f64, String> {
+        match self {
+            Value::Float(value) => Ok(*value),
+            other => Err(format!("TypeError: unsupported operand type: '{}'", other.class().name())),
+        }
+    }
+
+    /// The value of an `int` or `bool` as a `float`, rounded to the
+    /// nearest one as Python's `float()` does.
+    pub fn to_float(&self) -> Result<f64, String> {
+        match self {
+            Value::Float(value) => Ok(*value),
+            Value::BigInt(value) => value.to_f64().ok_or_else(|| too_large("a float")),
+            other => Ok(other.as_int()? as f64),
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        match self {
+            Value::Int(value) => *value != 0,
+            Value::BigInt(_) => true,
+            Value::Float(value) => *value != 0.0,
+            Value::Bool(value) => *value,
+            Value::Str(value) => !value.is_empty(),
+            Value::None => false,
+            Value::Tuple(items) => !items.is_empty(),
+            Value::List(items) => !items.borrow().is_empty(),
+        }
+    }
+
+    /// The value as Python's `repr` writes it, which differs from `str` for
+    /// strings: they are quoted.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Str(value) => str_repr(value),
+            other => other.to_string(),
+        }
+    }
+
+    /// The items of a list, for indexing it.
+    fn list(&self) -> Result<Rc<RefCell<Vec<Value>>>, String> {
+        match self {
+            Value::List(items) => Ok(items.clone()),
+            other => Err(format!("TypeError: '{}' object is not subscriptable", other.class().name())),
+        }
+    }
+
+    /// The items `a, b = value` unpacks `value` into: those of a tuple or a
+    /// list, or the characters of a string.
+    fn unpack(&self, length: usize) -> Result<Vec<Value>, String> {
+        let items: Vec<Value> = match self {
+            Value::Tuple(items) => items.to_vec(),
+            Value::List(items) => items.borrow().clone(),
+            Value::Str(value) => value.chars().map(|c| Value::Str(c.to_string().into())).collect(),
+            other => return Err(format!("TypeError: cannot unpack non-iterable {} object", other.class().name())),
+        };
+        match items.len() {
+            count if count > length => Err(format!("ValueError: too many values to unpack (expected {})", length)),
+            count if count < length => Err(format!("ValueError: not enough values to unpack (expected {}, got {})", length, count)),
+            _ => Ok(items),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::BigInt(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", float_repr(*value)),
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::Str(value) => write!(f, "{}", value),
+            Value::None => write!(f, "None"),
+            Value::Tuple(items) => {
+                let items: Vec<String> = items.iter().map(Value::repr).collect();
+                match items.as_slice() {
+                    [item] => write!(f, "({},)", item),
+                    _ => write!(f, "({})", items.join(", ")),
+                }
+            }
+            Value::List(items) => {
+                let items: Vec<String> = items.borrow().iter().map(Value::repr).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+        }
+    }
+}
+
+/// A string as Python's `repr` writes it: in single quotes, or double ones
+/// if it contains a single quote and no double one, with backslashes, the
+/// quote and control characters escaped.
+fn str_repr(value: &str) -> String {
+    let quote = if value.contains('\'') && !value.contains('"') { '"' } else { '\'' };
+    let mut text = String::with_capacity(value.len() + 2);
+    text.push(quote);
+    for c in value.chars() {
+        match c {
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if c == quote => {
+                text.push('\\');
+                text.push(c);
+            }
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => text.push_str(&format!("\\x{:02x}", c as u32)),
+            c => text.push(c),
+        }
+    }
+    text.push(quote);
+    text
+}
+
+/// A float as Python's `repr` writes it: the shortest digits that read back
+/// as the same float, with an exponent from 1e16 up and below 1e-4.
+pub fn float_repr(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // Rust switches to an exponent at the same points, but writes it as
+    // `1e16` and `1e-5` rather than `1e+16` and `1e-05`.
+    let text = format!("{:?}", value);
+    match text.split_once('e') {
+        Some((mantissa, exponent)) => {
+            let exponent: i32 = exponent.parse().expect("Rust writes a decimal exponent");
+            format!("{}e{}{:02}", mantissa.trim_end_matches(".0"), if exponent < 0 { '-' } else { '+' }, exponent.abs())
+        }
+        None => text,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    pub fn compare<T: PartialOrd + ?Sized>(&self, left: &T, right: &T) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterEqual => left >= right,
+        }
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::LessEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterEqual => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Mod,
+}
+
+impl Arithmetic {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Arithmetic::Add => "+",
+            Arithmetic::Sub => "-",
+            Arithmetic::Mul => "*",
+            Arithmetic::Div => "/",
+            Arithmetic::FloorDiv => "//",
+            Arithmetic::Mod => "%",
+        }
+    }
+}
+
+/// Python's `//`, which rounds towards negative infinity.
+pub fn floor_divide(left: i64, right: i64) -> Result<i64, String> {
+    if right == 0 {
+        return Err("ZeroDivisionError: integer division or modulo by zero".to_string());
+    }
+    let quotient = left.checked_div(right).ok_or_else(overflow)?;
+    if left % right != 0 && (left < 0) != (right < 0) {
+        Ok(quotient - 1)
+    } else {
+        Ok(quotient)
+    }
+}
+
+/// Python's `%`, whose result has the sign of the divisor.
+pub fn modulo(left: i64, right: i64) -> Result<i64, String> {
+    if right == 0 {
+        return Err("ZeroDivisionError: integer division or modulo by zero".to_string());
+    }
+    let remainder = left.checked_rem(right).unwrap_or(0);
+    if remainder != 0 && (remainder < 0) != (right < 0) {
+        Ok(remainder + right)
+    } else {
+        Ok(remainder)
+    }
+}
+
+fn overflow() -> String {
+    "OverflowError: integer result does not fit in 64 bits".to_string()
+}
+
+fn too_large(what: &str) -> String {
+    format!("OverflowError: int too large to convert to {}", what)
+}
+
+/// Arithmetic on `i64` machine integers: `+`, `-` and `*` wrap around, and
+/// `//` and `%` are Python's. There is no true division.
+pub fn i64_arithmetic(arithmetic: Arithmetic, left: i64, right: i64) -> Result<i64, String> {
+    match arithmetic {
+        Arithmetic::Add => Ok(left.wrapping_add(right)),
+        Arithmetic::Sub => Ok(left.wrapping_sub(right)),
+        Arithmetic::Mul => Ok(left.wrapping_mul(right)),
+        Arithmetic::FloorDiv => floor_divide(left, right),
+        Arithmetic::Mod => modulo(left, right),
+        Arithmetic::Div => Err("SystemError: true division of i64 values".to_string()),
+    }
+}
+
+/// Whether an `i64` converts to a `float` without rounding.
+fn is_exact_in_float(value: i64) -> bool {
+    value.unsigned_abs() <= 1 << f64::MANTISSA_DIGITS
+}
+
+/// Arithmetic on two `int`s, which may also be `bool`s. It is done on
+/// `i64`s, checking for overflow, and on big integers when an operand or
+/// the result does not fit. True division gives the `float` nearest to
+/// the exact quotient.
+pub fn int_arithmetic(arithmetic: Arithmetic, left: &Value, right: &Value) -> Result<Value, String> {
+    if matches!(arithmetic, Arithmetic::Div | Arithmetic::FloorDiv | Arithmetic::Mod) && !right.is_true() {
+        return Err(match arithmetic {
+            Arithmetic::Div => "ZeroDivisionError: division by zero",
+            _ => "ZeroDivisionError: integer division or modulo by zero",
+        }
+        .to_string());
+    }
+    if let (Some(left), Some(right)) = (left.small_int(), right.small_int()) {
+        let result = match arithmetic {
+            Arithmetic::Add => left.checked_add(right),
+            Arithmetic::Sub => left.checked_sub(right),
+            Arithmetic::Mul => left.checked_mul(right),
+            Arithmetic::FloorDiv => floor_divide(left, right).ok(),
+            Arithmetic::Mod => modulo(left, right).ok(),
+            Arithmetic::Div if is_exact_in_float(left) && is_exact_in_float(right) => {
+                return Ok(Value::Float(left as f64 / right as f64));
+            }
+            Arithmetic::Div => None,
+        };
+        if let Some(result) = result {
+            return Ok(Value::Int(result));
+        }
+    }
+    let (left, right) = (left.as_bigint()?, right.as_bigint()?);
+    let result = match arithmetic {
+        Arithmetic::Add => &left + &right,
+        Arithmetic::Sub => &left - &right,
+        Arithmetic::Mul => &left * &right,
+        Arithmetic::FloorDiv => left.div_mod_floor(&right).expect("the divisor is not zero").0,
+        Arithmetic::Mod => left.div_mod_floor(&right).expect("the divisor is not zero").1,
+        Arithmetic::Div => {
+            let quotient = left.true_divide(&right);
+            return quotient.map(Value::Float).ok_or_else(|| "OverflowError: integer division result too large for a float".to_string());
+        }
+    };
+    Ok(Value::from_bigint(result))
+}
+
+/// Unary `-` on an `int` or a `bool`.
+pub fn int_negate(value: &Value) -> Result<Value, String> {
+    match value.small_int().and_then(i64::checked_neg) {
+        Some(negated) => Ok(Value::Int(negated)),
+        None => Ok(Value::from_bigint(-&value.as_bigint()?)),
+    }
+}
+
+/// Compares two `int`s, which may also be `bool`s.
+pub fn int_compare(comparison: Comparison, left: &Value, right: &Value) -> Result<bool, String> {
+    match (left.small_int(), right.small_int()) {
+        (Some(left), Some(right)) => Ok(comparison.compare(&left, &right)),
+        _ => Ok(comparison.compare(&left.as_bigint()?, &right.as_bigint()?)),
+    }
+}
+
+/// How an `int` compares with a `float`. Python compares them exactly,
+/// rather than converting the `int`, which could round it. `None` if the
+/// float is a NaN.
+fn compare_int_float(int: &Value, float: f64) -> Result<Option<Ordering>, String> {
+    if let Some(int) = int.small_int().filter(|int| is_exact_in_float(*int)) {
+        return Ok((int as f64).partial_cmp(&float));
+    }
+    if float.is_nan() {
+        return Ok(None);
+    }
+    if float.is_infinite() {
+        return Ok(Some(if float > 0.0 { Ordering::Less } else { Ordering::Greater }));
+    }
+    let whole = float.trunc();
+    let ordering = int.as_bigint()?.cmp(&BigInt::from_f64(whole).expect("the float is finite"));
+    // Equal integer parts leave the fraction of the float to decide.
+    Ok(Some(ordering.then(whole.partial_cmp(&float).expect("the float is not a NaN"))))
+}
+
+/// Arithmetic on two `float`s, where `//` and `%` round as Python's
+/// `divmod` does.
+pub fn float_arithmetic(arithmetic: Arithmetic, left: f64, right: f64) -> Result<f64, String> {
+    if right == 0.0 {
+        match arithmetic {
+            Arithmetic::Div => return Err("ZeroDivisionError: float division by zero".to_string()),
+            Arithmetic::FloorDiv => return Err("ZeroDivisionError: float floor division by zero".to_string()),
+            Arithmetic::Mod => return Err("ZeroDivisionError: float modulo".to_string()),
+            _ => {}
+        }
+    }
+    Ok(match arithmetic {
+        Arithmetic::Add => left + right,
+        Arithmetic::Sub => left - right,
+        Arithmetic::Mul => left * right,
+        Arithmetic::Div => left / right,
+        Arithmetic::FloorDiv => float_divmod(left, right).0,
+        Arithmetic::Mod => float_divmod(left, right).1,
+    })
+}
+
+/// CPython's `float_divmod`.
+fn float_divmod(left: f64, right: f64) -> (f64, f64) {
+    let mut remainder = left % right;
+    let mut quotient = (left - remainder) / right;
+    if remainder != 0.0 {
+        if (right < 0.0) != (remainder < 0.0) {
+            remainder += right;
+            quotient -= 1.0;
+        }
+    } else {
+        remainder = 0.0f64.copysign(right);
+    }
+    let floor = if quotient != 0.0 {
+        let floor = quotient.floor();
+        if quotient - floor > 0.5 {
+            floor + 1.0
+        } else {
+            floor
+        }
+    } else {
+        0.0f64.copysign(left / right)
+    };
+    (floor, remainder)
+}
+
+/// Arithmetic on objects of any class, as Python does it: `bool`, `int`
+/// and `float` mix, `str` concatenates and repeats, tuples and lists
+/// concatenate, and anything else is a `TypeError`.
+pub fn arithmetic(arithmetic: Arithmetic, left: &Value, right: &Value) -> Result<Value, String> {
+    let is_int = |value: &Value| matches!(value, Value::Int(_) | Value::BigInt(_) | Value::Bool(_));
+    let is_number = |value: &Value| is_int(value) || matches!(value, Value::Float(_));
+    match (left, right) {
+        _ if is_int(left) && is_int(right) => int_arithmetic(arithmetic, left, right),
+        _ if is_number(left) && is_number(right) => Ok(Value::Float(float_arithmetic(arithmetic, left.to_float()?, right.to_float()?)?)),
+        (Value::Str(left), Value::Str(right)) if arithmetic == Arithmetic::Add => Ok(Value::Str(format!("{}{}", left, right).into())),
+        (Value::Tuple(left), Value::Tuple(right)) if arithmetic == Arithmetic::Add => {
+            Ok(Value::Tuple(left.iter().chain(right.iter()).cloned().collect()))
+        }
+        (Value::List(left), Value::List(right)) if arithmetic == Arithmetic::Add => {
+            let items = left.borrow().iter().chain(right.borrow().iter()).cloned().collect();
+            Ok(Value::List(Rc::new(RefCell::new(items))))
+        }
+        (Value::Str(text), count) | (count, Value::Str(text)) if arithmetic == Arithmetic::Mul && is_int(count) => {
+            let count = match count.as_bigint()? {
+                count if count.is_negative() => 0,
+                count => count.to_i64().ok_or_else(|| "OverflowError: cannot fit 'int' into an index-sized integer".to_string())?,
+            };
+            if text.len().checked_mul(count as usize).is_none() {
+                return Err("OverflowError: repeated string is too long".to_string());
+            }
+            Ok(Value::Str(text.repeat(count as usize).into()))
+        }
+        _ => Err(format!(
+            "TypeError: unsupported operand type(s) for {}: '{}' and '{}'",
+            arithmetic.symbol(),
+            left.class().name(),
+            right.class().name()
+        )),
+    }
+}
+
+/// Unary `-` on an object of any class.
+pub fn negate(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::Int(_) | Value::BigInt(_) | Value::Bool(_) => int_negate(value),
+        Value::Float(value) => Ok(Value::Float(-value)),
+        other => Err(format!("TypeError: bad operand type for unary -: '{}'", other.class().name())),
+    }
+}
+
+/// Compares objects of any class: numbers by value, strings by code point,
+/// tuples and lists item by item, and other classes only for equality.
+pub fn compare(comparison: Comparison, left: &Value, right: &Value) -> Result<bool, String> {
+    let is_int = |value: &Value| matches!(value, Value::Int(_) | Value::BigInt(_) | Value::Bool(_));
+    // An ordering compares with `Equal` as its operands compare; a NaN
+    // compares with nothing.
+    let ordered = |ordering: Option<Ordering>| match ordering {
+        Some(ordering) => comparison.compare(&ordering, &Ordering::Equal),
+        None => comparison.compare(&f64::NAN, &0.0),
+    };
+    match (left, right) {
+        _ if is_int(left) && is_int(right) => int_compare(comparison, left, right),
+        (Value::Float(left), Value::Float(right)) => Ok(comparison.compare(left, right)),
+        (int, Value::Float(float)) if is_int(int) => Ok(ordered(compare_int_float(int, *float)?)),
+        (Value::Float(float), int) if is_int(int) => Ok(ordered(compare_int_float(int, *float)?.map(Ordering::reverse))),
+        (Value::Str(left), Value::Str(right)) => Ok(comparison.compare(left.as_ref(), right.as_ref())),
+        (Value::Tuple(left), Value::Tuple(right)) => compare_items(comparison, left, right),
+        (Value::List(left), Value::List(right)) => compare_items(comparison, &left.borrow(), &right.borrow()),
+        _ => match comparison {
+            Comparison::Equal => Ok(left.class() == right.class()),
+            Comparison::NotEqual => Ok(left.class() != right.class()),
+            _ => Err(format!(
+                "TypeError: '{}' not supported between instances of '{}' and '{}'",
+                comparison.symbol(),
+                left.class().name(),
+                right.class().name()
+            )),
+        },
+    }
+}
+
+/// Compares the items of two tuples or two lists: the first items that
+/// differ compare as the sequences do; if there are none, the shorter one
+/// is less.
+fn compare_items(comparison: Comparison, left: &[Value], right: &[Value]) -> Result<bool, String> {
+    for (left, right) in left.iter().zip(right.iter()) {
+        if !compare(Comparison::Equal, left, right)? {
+            return match comparison {
+                Comparison::Equal => Ok(false),
+                Comparison::NotEqual => Ok(true),
+                _ => compare(comparison, left, right),
+            };
+        }
+    }
+    Ok(comparison.compare(&left.len(), &right.len()))
+}
+
+/// Python's `len` of a string, a tuple or a list.
+pub fn len(value: &Value) -> Result<Value, String> {
+    let length = match value {
+        Value::Str(value) => value.chars().count(),
+        Value::Tuple(items) => items.len(),
+        Value::List(items) => items.borrow().len(),
+        other => return Err(format!("TypeError: object of type '{}' has no len()", other.class().name())),
+    };
+    Ok(Value::Int(length as i64))
+}
+
+/// The position `index` stands for in a list of `length` items, counting
+/// from the end if it is negative, or an `IndexError` if there is none.
+pub fn check_index(index: &Value, length: &Value) -> Result<Value, String> {
+    let length = length.as_int()?;
+    let index = match index.as_bigint()?.to_i64() {
+        Some(index) => index,
+        None => return Err("IndexError: cannot fit 'int' into an index-sized integer".to_string()),
+    };
+    let position = if index < 0 { index + length } else { index };
+    if (0..length).contains(&position) {
+        Ok(Value::Int(position))
+    } else {
+        Err("IndexError: list index out of range".to_string())
+    }
+}
+
+/// Unboxes an object whose value should be of `class`. As in Python's
+/// numeric tower, a `bool` unboxes as an `int`, and both as a `float`.
+pub fn unbox(class: Class, value: &Value) -> Result<Value, String> {
+    match (class, value) {
+        (class, value) if class == value.class() => Ok(value.clone()),
+        (Class::Int, Value::Bool(_)) => Ok(Value::Int(value.as_int()?)),
+        (Class::Float, Value::Int(_) | Value::BigInt(_) | Value::Bool(_)) => Ok(Value::Float(value.to_float()?)),
+        _ => Err(format!("TypeError: expected {}, got {}", class.name(), value.class().name())),
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Instruction {
-    LoadImmediate { var: String, value: i32 },
+    /// Starts a function, whose arguments arrive in `parameters`.
+    Function { name: String, parameters: Vec<String> },
+    LoadImmediate { var: String, value: Value },
+    Copy { dest: String, src: String },
+    // Arithmetic and comparisons of `int`s, which may also be `bool`s,
+    // falling back to big integers when a result does not fit in 64 bits.
     Add { dest: String, src1: String, src2: String },
     Sub { dest: String, src1: String, src2: String },
     Mul { dest: String, src1: String, src2: String },
     Div { dest: String, src1: String, src2: String },
-    Print { var: String },
+    FloorDiv { dest: String, src1: String, src2: String },
+    Mod { dest: String, src1: String, src2: String },
+    Neg { dest: String, src: String },
+    Compare { dest: String, comparison: Comparison, src1: String, src2: String },
+    /// Arithmetic on `i64` machine integers, which wraps around.
+    I64Arithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    I64Neg { dest: String, src: String },
+    FloatArithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    FloatNeg { dest: String, src: String },
+    FloatCompare { dest: String, comparison: Comparison, src1: String, src2: String },
+    /// Arithmetic on objects of any class, dispatching on their classes.
+    ObjectArithmetic { dest: String, arithmetic: Arithmetic, src1: String, src2: String },
+    ObjectNeg { dest: String, src: String },
+    ObjectCompare { dest: String, comparison: Comparison, src1: String, src2: String },
+    Not { dest: String, src: String },
+    /// Converts a `bool` to an `int`.
+    ToInt { dest: String, src: String },
+    /// Converts an `int` or a `bool` to a `float`.
+    ToFloat { dest: String, src: String },
+    Box { dest: String, src: String },
+    /// Unboxes an object of `class`, raising `TypeError` for any other.
+    Unbox { dest: String, src: String, class: Class },
+    /// Whether an object is exactly of `class`.
+    TypeGuard { dest: String, src: String, class: Class },
+    Truth { dest: String, src: String },
+    MakeTuple { dest: String, items: Vec<String> },
+    MakeList { dest: String, items: Vec<String> },
+    Len { dest: String, src: String },
+    /// The position of an index in a list of `length` items, raising
+    /// `IndexError` if it is out of range.
+    CheckIndex { dest: String, index: String, length: String },
+    /// The item of a list at a position that `CheckIndex` gave.
+    GetItem { dest: String, src: String, index: String },
+    SetItem { list: String, index: String, src: String },
+    /// Item `index` of an object unpacked into `length` values.
+    Unpack { dest: String, src: String, index: usize, length: usize },
+    Jump { target: usize },
+    JumpIfFalse { condition: String, target: usize },
+    Call { dest: String, function: String, arguments: Vec<String> },
+    /// Returns from the current function; returning from the first one
+    /// ends the program.
+    Return { value: Option<String> },
+    Unreachable,
+    LoadGlobal { dest: String, name: String },
+    StoreGlobal { name: String, src: String },
+    Print { vars: Vec<String> },
 }
 
+struct Frame {
+    registers: HashMap<String, Value>,
+    /// Where to continue in the caller, and the register that receives the
+    /// result.
+    return_to: usize,
+    dest: Option<String>,
+}
+
+/// Runs a program starting from its first instruction. Each call gets a
+/// frame of its own registers; globals are shared.
 pub struct Runtime {
     instructions: Vec<Instruction>,
-    symbol_table: HashMap<String, i32>,
+    functions: HashMap<String, usize>,
+    symbol_table: HashMap<String, Value>,
+    frames: Vec<Frame>,
+    traceback: Vec<usize>,
 }
 
 impl Runtime {
     pub fn new(instructions: Vec<Instruction>) -> Self {
+        let functions = instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, instruction)| match instruction {
+                Instruction::Function { name, .. } => Some((name.clone(), index)),
+                _ => None,
+            })
+            .collect();
         Runtime {
             instructions,
+            functions,
             symbol_table: HashMap::new(),
+            frames: Vec::new(),
+            traceback: Vec::new(),
         }
     }
 
     pub fn run(&mut self) -> Result<(), String> {
-        for instruction in &self.instructions {
-            self.execute(instruction)?;
+        self.frames = vec![Frame { registers: HashMap::new(), return_to: 0, dest: None }];
+        let mut index = match self.instructions.first() {
+            Some(Instruction::Function { .. }) => 1,
+            _ => 0,
+        };
+        while index < self.instructions.len() {
+            let instruction = self.instructions[index].clone();
+            match self.execute(&instruction, index) {
+                Ok(Some(next)) => index = next,
+                Ok(None) => break,
+                Err(error) => {
+                    self.traceback = self.frames.iter().skip(1).map(|frame| frame.return_to - 1).chain([index]).collect();
+                    return Err(error);
+                }
+            }
         }
         Ok(())
     }
 
-    fn execute(&mut self, instruction: &Instruction) -> Result<(), String> {
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    fn get(&mut self, var: &str) -> Result<Value, String> {
+        self.frame().registers.get(var).cloned().ok_or(format!("Undefined variable '{}'", var))
+    }
+
+    fn set(&mut self, var: &str, value: Value) {
+        self.frame().registers.insert(var.to_string(), value);
+    }
+
+    /// Executes one instruction and returns the index of the next one, or
+    /// `None` when the program is done.
+    fn execute(&mut self, instruction: &Instruction, index: usize) -> Result<Option<usize>, String> {
         match instruction {
-            Instruction::LoadImmediate { var, value } => {
-                self.symbol_table.insert(var.clone(), *value);
+            Instruction::Function { .. } => return Ok(None),
+            Instruction::LoadImmediate { var, value } => self.set(var, value.clone()),
+            Instruction::Copy { dest, src } => {
+                let value = self.get(src)?;
+                self.set(dest, value);
             }
             Instruction::Add { dest, src1, src2 }
             | Instruction::Sub { dest, src1, src2 }
             | Instruction::Mul { dest, src1, src2 }
-            | Instruction::Div { dest, src1, src2 } => {
-                let val1 = *self.symbol_table.get(src1).ok_or(format!("Undefined variable '{}'", src1))?;
-                let val2 = *self.symbol_table.get(src2).ok_or(format!("Undefined variable '{}'", src2))?;
-                let result = match instruction {
-                    Instruction::Add { .. } => val1 + val2,
-                    Instruction::Sub { .. } => val1 - val2,
-                    Instruction::Mul { .. } => val1 * val2,
-                    Instruction::Div { .. } => val1 / val2,
-                    _ => unreachable!(),
+            | Instruction::Div { dest, src1, src2 }
+            | Instruction::FloorDiv { dest, src1, src2 }
+            | Instruction::Mod { dest, src1, src2 } => {
+                let arithmetic = match instruction {
+                    Instruction::Add { .. } => Arithmetic::Add,
+                    Instruction::Sub { .. } => Arithmetic::Sub,
+                    Instruction::Mul { .. } => Arithmetic::Mul,
+                    Instruction::Div { .. } => Arithmetic::Div,
+                    Instruction::FloorDiv { .. } => Arithmetic::FloorDiv,
+                    _ => Arithmetic::Mod,
+                };
+                let result = int_arithmetic(arithmetic, &self.get(src1)?, &self.get(src2)?)?;
+                self.set(dest, result);
+            }
+            Instruction::Neg { dest, src } => {
+                let result = int_negate(&self.get(src)?)?;
+                self.set(dest, result);
+            }
+            Instruction::Compare { dest, comparison, src1, src2 } => {
+                let result = int_compare(*comparison, &self.get(src1)?, &self.get(src2)?)?;
+                self.set(dest, Value::Bool(result));
+            }
+            Instruction::I64Arithmetic { dest, arithmetic, src1, src2 } => {
+                let val1 = self.get(src1)?.as_int()?;
+                let val2 = self.get(src2)?.as_int()?;
+                self.set(dest, Value::Int(i64_arithmetic(*arithmetic, val1, val2)?));
+            }
+            Instruction::I64Neg { dest, src } => {
+                let value = self.get(src)?.as_int()?;
+                self.set(dest, Value::Int(value.wrapping_neg()));
+            }
+            Instruction::FloatArithmetic { dest, arithmetic, src1, src2 } => {
+                let val1 = self.get(src1)?.as_float()?;
+                let val2 = self.get(src2)?.as_float()?;
+                self.set(dest, Value::Float(float_arithmetic(*arithmetic, val1, val2)?));
+            }
+            Instruction::FloatNeg { dest, src } => {
+                let value = self.get(src)?.as_float()?;
+                self.set(dest, Value::Float(-value));
+            }
+            Instruction::FloatCompare { dest, comparison, src1, src2 } => {
+                let val1 = self.get(src1)?.as_float()?;
+                let val2 = self.get(src2)?.as_float()?;
+                self.set(dest, Value::Bool(comparison.compare(&val1, &val2)));
+            }
+            Instruction::ObjectArithmetic { dest, arithmetic: op, src1, src2 } => {
+                let result = arithmetic(*op, &self.get(src1)?, &self.get(src2)?)?;
+                self.set(dest, result);
+            }
+            Instruction::ObjectNeg { dest, src } => {
+                let result = negate(&self.get(src)?)?;
+                self.set(dest, result);
+            }
+            Instruction::ObjectCompare { dest, comparison, src1, src2 } => {
+                let result = compare(*comparison, &self.get(src1)?, &self.get(src2)?)?;
+                self.set(dest, Value::Bool(result));
+            }
+            Instruction::Not { dest, src } => {
+                let value = self.get(src)?;
+                self.set(dest, Value::Bool(!value.is_true()));
+            }
+            Instruction::ToInt { dest, src } => {
+                let value = self.get(src)?.as_int()?;
+                self.set(dest, Value::Int(value));
+            }
+            Instruction::ToFloat { dest, src } => {
+                let value = self.get(src)?.to_float()?;
+                self.set(dest, Value::Float(value));
+            }
+            Instruction::Box { dest, src } => {
+                let value = self.get(src)?;
+                self.set(dest, value);
+            }
+            Instruction::Unbox { dest, src, class } => {
+                let value = unbox(*class, &self.get(src)?)?;
+                self.set(dest, value);
+            }
+            Instruction::TypeGuard { dest, src, class } => {
+                let value = self.get(src)?;
+                self.set(dest, Value::Bool(value.class() == *class));
+            }
+            Instruction::Truth { dest, src } => {
+                let value = self.get(src)?;
+                self.set(dest, Value::Bool(value.is_true()));
+            }
+            Instruction::MakeTuple { dest, items } => {
+                let items = items.iter().map(|item| self.get(item)).collect::<Result<Vec<_>, _>>()?;
+                self.set(dest, Value::Tuple(items.into()));
+            }
+            Instruction::MakeList { dest, items } => {
+                let items = items.iter().map(|item| self.get(item)).collect::<Result<Vec<_>, _>>()?;
+                self.set(dest, Value::List(Rc::new(RefCell::new(items))));
+            }
+            Instruction::Len { dest, src } => {
+                let value = len(&self.get(src)?)?;
+                self.set(dest, value);
+            }
+            Instruction::CheckIndex { dest, index, length } => {
+                let position = check_index(&self.get(index)?, &self.get(length)?)?;
+                self.set(dest, position);
+            }
+            Instruction::GetItem { dest, src, index } => {
+                let position = self.get(index)?.as_int()?;
+                let item = self.get(src)?.list()?.borrow().get(position as usize).cloned();
+                self.set(dest, item.ok_or("IndexError: list index out of range")?);
+            }
+            Instruction::SetItem { list, index, src } => {
+                let position = self.get(index)?.as_int()?;
+                let value = self.get(src)?;
+                let list = self.get(list)?.list()?;
+                let mut items = list.borrow_mut();
+                *items.get_mut(position as usize).ok_or("IndexError: list assignment index out of range")? = value;
+            }
+            Instruction::Unpack { dest, src, index, length } => {
+                let mut items = self.get(src)?.unpack(*length)?;
+                self.set(dest, items.swap_remove(*index));
+            }
+            Instruction::Jump { target } => return Ok(Some(*target)),
+            Instruction::JumpIfFalse { condition, target } => {
+                if !self.get(condition)?.is_true() {
+                    return Ok(Some(*target));
+                }
+            }
+            Instruction::Call { dest, function, arguments } => {
+                let start = *self.functions.get(function).ok_or(format!("Undefined function '{}'", function))?;
+                let Instruction::Function { parameters, .. } = &self.instructions[start] else {
+                    unreachable!("functions start with a Function instruction");
                 };
-                self.symbol_table.insert(dest.clone(), result);
+                if self.frames.len() >= RECURSION_LIMIT {
+                    return Err("RecursionError: maximum recursion depth exceeded".to_string());
+                }
+                let mut registers = HashMap::new();
+                for (parameter, argument) in parameters.clone().iter().zip(arguments) {
+                    registers.insert(parameter.clone(), self.get(argument)?);
+                }
+                self.frames.push(Frame { registers, return_to: index + 1, dest: Some(dest.clone()) });
+                return Ok(Some(start + 1));
             }
-            Instruction::Print { var } => {
-                if let Some(value) = self.symbol_table.get(var) {
-                    println!("{} = {}", var, value);
-                } else {
-                    return Err(format!("Undefined variable '{}'", var));
+            Instruction::Return { value } => {
+                let value = match value {
+                    Some(value) => self.get(value)?,
+                    None => Value::None,
+                };
+                if self.frames.len() == 1 {
+                    return Ok(None);
+                }
+                let frame = self.frames.pop().expect("a function is running");
+                if let Some(dest) = frame.dest {
+                    self.set(&dest, value);
                 }
+                return Ok(Some(frame.return_to));
+            }
+            Instruction::Unreachable => return Err("SystemError: reached unreachable code".to_string()),
+            Instruction::LoadGlobal { dest, name } => {
+                let value = self.symbol_table.get(name).cloned().ok_or(format!("NameError: name '{}' is not defined", name))?;
+                self.set(dest, value);
+            }
+            Instruction::StoreGlobal { name, src } => {
+                let value = self.get(src)?;
+                self.symbol_table.insert(name.clone(), value);
+            }
+            Instruction::Print { vars } => {
+                let values = vars.iter().map(|var| self.get(var).map(|value| value.to_string())).collect::<Result<Vec<_>, _>>()?;
+                println!("{}", values.join(" "));
             }
         }
-        Ok(())
+        Ok(Some(index + 1))
+    }
+
+    /// After a run that raised, the instruction each frame was executing,
+    /// outermost first: the calls, then the instruction that raised.
+    pub fn traceback(&self) -> &[usize] {
+        &self.traceback
     }
 
-    pub fn get_var_value(&self, var: &str) -> Option<&i32> {
-        self.symbol_table.get(var)
+    /// The value of a global variable, or else of a register of the first
+    /// function, which is kept once the program ends.
+    pub fn get_var_value(&self, var: &str) -> Option<&Value> {
+        self.symbol_table.get(var).or_else(|| self.frames.first()?.registers.get(var))
     }
 }
@@ -0,0 +1,127 @@
+use crate::parser::parser::ParameterKind;
+use crate::semantic::types::{Signature, SignatureParameter, Type};
+
+/// Names provided by Python's `builtins` module: its functions and classes,
+/// exceptions and warnings included.
+pub const BUILTINS: &[&str] = &[
+    "abs", "aiter", "all", "anext", "any", "ascii", "bin", "bool", "breakpoint", "bytearray", "bytes",
+    "callable", "chr", "classmethod", "compile", "complex", "copyright", "credits", "delattr", "dict",
+    "dir", "divmod", "enumerate", "eval", "exec", "exit", "filter", "float", "format", "frozenset",
+    "getattr", "globals", "hasattr", "hash", "help", "hex", "id", "input", "int", "isinstance",
+    "issubclass", "iter", "len", "license", "list", "locals", "map", "max", "memoryview", "min", "next",
+    "object", "oct", "open", "ord", "pow", "print", "property", "quit", "range", "repr", "reversed",
+    "round", "set", "setattr", "slice", "sorted", "staticmethod", "str", "sum", "super", "tuple", "type",
+    "vars", "zip",
+    "__build_class__", "__debug__", "__doc__", "__file__", "__import__", "__loader__", "__name__",
+    "__package__", "__spec__", "NotImplemented", "Ellipsis",
+    "ArithmeticError", "AssertionError", "AttributeError", "BaseException", "BaseExceptionGroup",
+    "BlockingIOError", "BrokenPipeError", "BufferError", "BytesWarning", "ChildProcessError",
+    "ConnectionAbortedError", "ConnectionError", "ConnectionRefusedError", "ConnectionResetError",
+    "DeprecationWarning", "EOFError", "EncodingWarning", "EnvironmentError", "Exception",
+    "ExceptionGroup", "FileExistsError", "FileNotFoundError", "FloatingPointError", "FutureWarning",
+    "GeneratorExit", "IOError", "ImportError", "ImportWarning", "IndentationError", "IndexError",
+    "InterruptedError", "IsADirectoryError", "KeyError", "KeyboardInterrupt", "LookupError",
+    "MemoryError", "ModuleNotFoundError", "NameError", "NotADirectoryError", "NotImplementedError",
+    "OSError", "OverflowError", "PendingDeprecationWarning", "PermissionError", "ProcessLookupError",
+    "RecursionError", "ReferenceError", "ResourceWarning", "RuntimeError", "RuntimeWarning",
+    "StopAsyncIteration", "StopIteration", "SyntaxError", "SyntaxWarning", "SystemError", "SystemExit",
+    "TabError", "TimeoutError", "TypeError", "UnboundLocalError", "UnicodeDecodeError",
+    "UnicodeEncodeError", "UnicodeError", "UnicodeTranslateError", "UnicodeWarning", "UserWarning",
+    "ValueError", "Warning", "ZeroDivisionError",
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+/// The static type of a builtin name. Builtins without a useful signature are
+/// `Any`.
+pub fn builtin_type(name: &str) -> Type {
+    let class = |instance: Type| Type::ClassObject(Box::new(instance));
+    let function = |parameters: Vec<Type>, returns: Type| Type::Callable(Box::new(Signature::positional(parameters, returns)));
+    // Parameters as (name, kind, has_default), for builtins taking keywords.
+    let named = |parameters: &[(&str, ParameterKind, bool)], returns: Type| {
+        let parameters = parameters
+            .iter()
+            .map(|&(name, kind, has_default)| SignatureParameter {
+                name: Some(name.to_string()),
+                kind,
+                annotation: Type::Any,
+                has_default,
+            })
+            .collect();
+        Type::Callable(Box::new(Signature { parameters: Some(parameters), returns }))
+    };
+    use ParameterKind::{KeywordOnly, PositionalOnly, PositionalOrKeyword, VarPositional};
+    match name {
+        "int" => class(Type::Int),
+        "float" => class(Type::Float),
+        "str" => class(Type::Str),
+        "bool" => class(Type::Bool),
+        "list" => class(Type::List(Box::new(Type::Any))),
+        "set" | "frozenset" => class(Type::Set(Box::new(Type::Any))),
+        "dict" => class(Type::Dict(Box::new(Type::Any), Box::new(Type::Any))),
+        "object" => class(Type::Instance("object".to_string())),
+        "range" => named(
+            &[("start", PositionalOnly, false), ("stop", PositionalOnly, true), ("step", PositionalOnly, true)],
+            Type::Instance("range".to_string()),
+        ),
+        "len" | "hash" | "id" | "ord" => function(vec![Type::Any], Type::Int),
+        "repr" | "ascii" | "chr" | "bin" | "hex" | "oct" => function(vec![Type::Any], Type::Str),
+        "isinstance" | "issubclass" | "hasattr" => function(vec![Type::Any, Type::Any], Type::Bool),
+        "callable" | "all" | "any" => function(vec![Type::Any], Type::Bool),
+        "abs" => function(vec![Type::Any], Type::Any),
+        "divmod" => function(vec![Type::Any, Type::Any], Type::Any),
+        "filter" => function(vec![Type::Any, Type::Any], Type::Any),
+        "reversed" => function(vec![Type::Any], Type::Any),
+        "setattr" => function(vec![Type::Any, Type::Any, Type::Any], Type::None),
+        "delattr" => function(vec![Type::Any, Type::Any], Type::None),
+        "globals" | "locals" => function(Vec::new(), Type::Any),
+        "min" | "max" => named(
+            &[
+                ("iterable", PositionalOnly, false),
+                ("values", VarPositional, false),
+                ("key", KeywordOnly, true),
+                ("default", KeywordOnly, true),
+            ],
+            Type::Any,
+        ),
+        "zip" => named(&[("iterables", VarPositional, false), ("strict", KeywordOnly, true)], Type::Any),
+        "map" => named(
+            &[("function", PositionalOnly, false), ("iterable", PositionalOnly, false), ("iterables", VarPositional, false)],
+            Type::Any,
+        ),
+        "iter" => named(&[("object", PositionalOnly, false), ("sentinel", PositionalOnly, true)], Type::Any),
+        "next" => named(&[("iterator", PositionalOnly, false), ("default", PositionalOnly, true)], Type::Any),
+        "getattr" => named(
+            &[("object", PositionalOnly, false), ("name", PositionalOnly, false), ("default", PositionalOnly, true)],
+            Type::Any,
+        ),
+        "pow" => named(
+            &[("base", PositionalOrKeyword, false), ("exp", PositionalOrKeyword, false), ("mod", PositionalOrKeyword, true)],
+            Type::Any,
+        ),
+        "dir" => named(&[("object", PositionalOnly, true)], Type::List(Box::new(Type::Str))),
+        "vars" => named(&[("object", PositionalOnly, true)], Type::Any),
+        "print" => named(
+            &[
+                ("values", VarPositional, false),
+                ("sep", KeywordOnly, true),
+                ("end", KeywordOnly, true),
+                ("file", KeywordOnly, true),
+                ("flush", KeywordOnly, true),
+            ],
+            Type::None,
+        ),
+        "sorted" => named(
+            &[("iterable", PositionalOnly, false), ("key", KeywordOnly, true), ("reverse", KeywordOnly, true)],
+            Type::List(Box::new(Type::Any)),
+        ),
+        "enumerate" => named(&[("iterable", PositionalOrKeyword, false), ("start", PositionalOrKeyword, true)], Type::Any),
+        "round" => named(&[("number", PositionalOrKeyword, false), ("ndigits", PositionalOrKeyword, true)], Type::Any),
+        "sum" => named(&[("iterable", PositionalOnly, false), ("start", PositionalOrKeyword, true)], Type::Any),
+        "input" => named(&[("prompt", PositionalOnly, true)], Type::Str),
+        "format" => named(&[("value", PositionalOnly, false), ("format_spec", PositionalOnly, true)], Type::Str),
+        _ => Type::Any,
+    }
+}
@@ -0,0 +1,1857 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::lexer::lexer::{Lexer, Span};
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler,
+    FunctionDefinition, KeywordArgument, Parameter, ParameterKind, Parser,
+};
+use crate::semantic::builtins::builtin_type;
+use crate::semantic::diagnostic::Diagnostic;
+use crate::semantic::scope::{ScopeId, SymbolTable};
+use crate::semantic::types::{ClassInfo, ClassKind, Signature, SignatureParameter, Type, TypeContext};
+
+/// What the type checker learned about a program.
+#[derive(Debug, Clone, Default)]
+pub struct TypeInfo {
+    variables: HashMap<(ScopeId, String), Type>,
+    signatures: HashMap<ScopeId, Signature>,
+    /// Expression types, keyed by the address of the expression's node in
+    /// the checked program.
+    expressions: HashMap<usize, Type>,
+    context: TypeContext,
+}
+
+impl TypeInfo {
+    /// The type of a variable over its whole scope: its annotation, or the
+    /// union of everything assigned to it.
+    pub fn variable(&self, scope: ScopeId, name: &str) -> Option<&Type> {
+        self.variables.get(&(scope, name.to_string()))
+    }
+
+    /// The signature of the function that owns `scope`.
+    pub fn signature(&self, scope: ScopeId) -> Option<&Signature> {
+        self.signatures.get(&scope)
+    }
+
+    /// The type of an expression of the checked program where it is
+    /// evaluated, after narrowing, or the type of the new value of an
+    /// augmented assignment. Only valid for nodes of the program passed to
+    /// `TypeChecker::check`, which must not have moved since.
+    pub fn expression(&self, expression: &ASTNode) -> Option<&Type> {
+        self.expressions.get(&node_key(expression))
+    }
+
+    pub fn context(&self) -> &TypeContext {
+        &self.context
+    }
+}
+
+fn node_key(node: &ASTNode) -> usize {
+    node as *const ASTNode as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    Module,
+    Class,
+    Function,
+    Comprehension,
+}
+
+/// The typing state of one scope while it is being checked.
+#[derive(Debug)]
+struct Frame {
+    kind: FrameKind,
+    scope: Option<ScopeId>,
+    /// Annotated types, which later assignments must respect.
+    declared: HashMap<String, Type>,
+    /// The type of each variable at the current point, after narrowing.
+    types: HashMap<String, Type>,
+    /// Names local to the scope according to the symbol table.
+    locals: HashSet<String>,
+    globals: HashSet<String>,
+    /// The declared return type, or `None` in unannotated functions.
+    returns: Option<Type>,
+    /// Set after `return`, `break` or `continue` for the rest of the block.
+    terminated: bool,
+}
+
+impl Frame {
+    fn new(kind: FrameKind, scope: Option<ScopeId>, table: &SymbolTable) -> Self {
+        let locals = scope
+            .map(|scope| {
+                table
+                    .scope(scope)
+                    .symbols()
+                    .iter()
+                    .filter(|symbol| symbol.is_assigned)
+                    .map(|symbol| symbol.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        Frame {
+            kind,
+            scope,
+            declared: HashMap::new(),
+            types: HashMap::new(),
+            locals,
+            globals: HashSet::new(),
+            returns: None,
+            terminated: false,
+        }
+    }
+}
+
+/// Narrowed types implied by a condition: `(name, type)` pairs for when it
+/// is true and for when it is false.
+type Narrowing = (Vec<(String, Type)>, Vec<(String, Type)>);
+
+/// A gradual type checker for PEP 484 annotations.
+///
+/// Unannotated parameters and unknown names are `Any`, which is compatible
+/// with everything, so unannotated code produces no errors. Local variable
+/// types are inferred from assignments and narrowed by `isinstance`,
+/// `is None` and truthiness checks.
+pub struct TypeChecker<'a> {
+    table: &'a SymbolTable,
+    context: TypeContext,
+    type_variables: HashSet<String>,
+    frames: Vec<Frame>,
+    variables: HashMap<(ScopeId, String), Type>,
+    signatures: HashMap<ScopeId, Signature>,
+    expressions: HashMap<usize, Type>,
+    diagnostics: Vec<Diagnostic>,
+    span: Span,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn check(program: &ASTNode, table: &'a SymbolTable) -> (TypeInfo, Vec<Diagnostic>) {
+        let mut checker = TypeChecker {
+            table,
+            context: TypeContext::default(),
+            type_variables: HashSet::new(),
+            frames: vec![Frame::new(FrameKind::Module, Some(table.root()), table)],
+            variables: HashMap::new(),
+            signatures: HashMap::new(),
+            expressions: HashMap::new(),
+            diagnostics: Vec::new(),
+            span: Span::default(),
+        };
+        let statements = match program {
+            ASTNode::Program(statements) => statements.as_slice(),
+            other => std::slice::from_ref(other),
+        };
+        checker.declare(statements);
+        checker.check_block(statements);
+        let info = TypeInfo {
+            variables: checker.variables,
+            signatures: checker.signatures,
+            expressions: checker.expressions,
+            context: checker.context,
+        };
+        (info, checker.diagnostics)
+    }
+
+    fn error(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic::error(message, self.span));
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().expect("the module frame is never popped")
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("the module frame is never popped")
+    }
+
+    // ----- Declarations -----------------------------------------------------
+
+    /// Registers the module's type variables, classes and functions before
+    /// any body is checked, so they can be used before their definition.
+    fn declare(&mut self, statements: &[ASTNode]) {
+        for statement in statements {
+            if let ASTNode::Assignment { targets, value, .. } = statement {
+                if let (Some(ASTNode::Expression(ASTNodeType::Identifier(name))), ASTNode::Call { function, .. }) =
+                    (targets.first(), value.as_ref())
+                {
+                    if annotation_name(function) == Some("TypeVar") {
+                        self.type_variables.insert(name.clone());
+                    }
+                }
+            }
+        }
+
+        let classes: Vec<&ClassDefinition> = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                ASTNode::ClassDefinition(class) => Some(class),
+                _ => None,
+            })
+            .collect();
+        for class in &classes {
+            self.context.add_class(ClassInfo::new(&class.name, ClassKind::Regular));
+        }
+        for class in &classes {
+            self.declare_class(class);
+        }
+        // A name bound to several classes has the members of any of them.
+        for (index, class) in classes.iter().enumerate() {
+            if classes[..index].iter().any(|earlier| earlier.name == class.name) {
+                if let Some(info) = self.context.class_mut(&class.name) {
+                    info.has_unknown_base = true;
+                }
+            }
+        }
+
+        for statement in statements {
+            match statement {
+                ASTNode::FunctionDefinition(function) => {
+                    let ty = self.function_type(function, None);
+                    self.frame_mut().types.insert(function.name.clone(), ty);
+                }
+                ASTNode::ClassDefinition(class) => {
+                    let ty = Type::ClassObject(Box::new(Type::Instance(class.name.clone())));
+                    self.frame_mut().types.insert(class.name.clone(), ty);
+                }
+                ASTNode::AnnotatedAssignment { target, annotation, .. } => {
+                    if let ASTNode::Expression(ASTNodeType::Identifier(name)) = target.as_ref() {
+                        let ty = self.resolve_annotation(annotation);
+                        self.frame_mut().declared.insert(name.clone(), ty);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_class(&mut self, class: &ClassDefinition) {
+        let mut info = ClassInfo::new(&class.name, ClassKind::Regular);
+        for base in &class.bases {
+            // A base from another module is unknown, even if a class of the
+            // program has its name, and so is one the class names itself
+            // after, which is an earlier class of that name.
+            let name = match base {
+                ASTNode::Attribute { value, .. } if !is_typing(value) => None,
+                ASTNode::Subscript { value, .. } if matches!(value.as_ref(), ASTNode::Attribute { value, .. } if !is_typing(value)) => None,
+                base => annotation_name(base).filter(|name| *name != class.name),
+            };
+            match name {
+                Some("Protocol") => info.kind = ClassKind::Protocol,
+                Some("TypedDict") => info.kind = ClassKind::TypedDict,
+                Some("Generic") | Some("object") => {}
+                Some(name) if self.context.class(name).is_some() => {
+                    if self.context.class(name).is_some_and(|base| base.kind == ClassKind::TypedDict) {
+                        info.kind = ClassKind::TypedDict;
+                    }
+                    info.bases.push(name.to_string());
+                }
+                _ => info.has_unknown_base = true,
+            }
+        }
+
+        for statement in &class.body {
+            match statement {
+                ASTNode::AnnotatedAssignment { target, annotation, .. } => {
+                    if let ASTNode::Expression(ASTNodeType::Identifier(name)) = target.as_ref() {
+                        info.fields.push((name.clone(), self.resolve_annotation(annotation)));
+                    }
+                }
+                ASTNode::Assignment { targets, value, .. } => {
+                    for target in targets {
+                        if let ASTNode::Expression(ASTNodeType::Identifier(name)) = target {
+                            info.fields.push((name.clone(), literal_type(value)));
+                        }
+                    }
+                }
+                ASTNode::FunctionDefinition(method) => {
+                    let mut signature = self.signature(method, Some(&class.name));
+                    let decorators: Vec<_> = method.decorators.iter().filter_map(annotation_name).collect();
+                    if decorators.len() < method.decorators.len()
+                        || decorators.iter().any(|decorator| !METHOD_DECORATORS.contains(decorator))
+                    {
+                        // The decorator may replace the method with anything.
+                        info.fields.push((method.name.clone(), Type::Any));
+                        continue;
+                    }
+                    if decorators.contains(&"property") {
+                        info.fields.push((method.name.clone(), signature.returns.clone()));
+                        continue;
+                    }
+                    if decorators.contains(&"staticmethod") {
+                        // Give static methods a placeholder receiver so that
+                        // binding them through an instance removes nothing real.
+                        if let Some(parameters) = signature.parameters.as_mut() {
+                            parameters.insert(0, receiver_parameter(Type::Any));
+                        }
+                    }
+                    self.collect_self_fields(method, &signature, &mut info);
+                    info.methods.push((method.name.clone(), signature));
+                }
+                _ => {}
+            }
+        }
+        self.context.add_class(info);
+    }
+
+    /// Records the attributes a method assigns through `self`.
+    fn collect_self_fields(&self, method: &FunctionDefinition, signature: &Signature, info: &mut ClassInfo) {
+        let Some(receiver) = method.parameters.first().map(|parameter| parameter.name.as_str()) else { return };
+        let parameter_type = |name: &str| {
+            signature
+                .parameters
+                .iter()
+                .flatten()
+                .find(|parameter| parameter.name.as_deref() == Some(name))
+                .map(|parameter| parameter.annotation.clone())
+        };
+        for statement in &method.body {
+            let (target, ty) = match statement {
+                ASTNode::AnnotatedAssignment { target, annotation, .. } => (target.as_ref(), self.resolve_annotation(annotation)),
+                ASTNode::Assignment { targets, value, .. } if targets.len() == 1 => {
+                    let ty = match value.as_ref() {
+                        ASTNode::Expression(ASTNodeType::Identifier(name)) => parameter_type(name).unwrap_or(Type::Any),
+                        // `None` holds the place of a value set later.
+                        ASTNode::NoneLiteral => Type::Any,
+                        other => literal_type(other),
+                    };
+                    (&targets[0], ty)
+                }
+                _ => continue,
+            };
+            if let ASTNode::Attribute { value, attribute } = target {
+                let is_receiver = matches!(value.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if name == receiver);
+                if is_receiver && info.field(attribute).is_none() {
+                    info.fields.push((attribute.clone(), ty));
+                }
+            }
+        }
+    }
+
+    /// The type a `def` binds its name to. Decorators other than the
+    /// builtin method decorators and the inlining hints may replace the
+    /// function, so they make it `Any`.
+    fn function_type(&self, function: &FunctionDefinition, class: Option<&str>) -> Type {
+        if function.decorators.iter().any(|decorator| {
+            !is_inlining_hint(decorator) && !annotation_name(decorator).is_some_and(|name| METHOD_DECORATORS.contains(&name))
+        }) {
+            return Type::Any;
+        }
+        Type::Callable(Box::new(self.signature(function, class)))
+    }
+
+    fn signature(&self, function: &FunctionDefinition, class: Option<&str>) -> Signature {
+        let parameters = function
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(index, parameter)| {
+                let annotation = match (&parameter.annotation, class) {
+                    (Some(annotation), _) => self.resolve_annotation(annotation),
+                    (None, Some(class)) if index == 0 => Type::Instance(class.to_string()),
+                    (None, _) => Type::Any,
+                };
+                SignatureParameter {
+                    name: Some(parameter.name.clone()),
+                    kind: parameter.kind,
+                    annotation,
+                    has_default: parameter.default.is_some(),
+                }
+            })
+            .collect();
+        let returns = match &function.returns {
+            Some(returns) => self.resolve_annotation(returns),
+            None if function.name == "__init__" => Type::None,
+            None => Type::Any,
+        };
+        Signature { parameters: Some(parameters), returns }
+    }
+
+    /// Converts an annotation expression to a type. Unknown names are `Any`.
+    fn resolve_annotation(&self, annotation: &ASTNode) -> Type {
+        match annotation {
+            ASTNode::NoneLiteral => Type::None,
+            ASTNode::StringLiteral(source) => {
+                // A forward reference: parse the string as an expression.
+                let mut parser = Parser::new(Lexer::new(source));
+                match parser.parse() {
+                    Ok(ASTNode::Program(statements)) => match statements.as_slice() {
+                        [ASTNode::ExpressionStatement { expression, .. }] => self.resolve_annotation(expression),
+                        _ => Type::Any,
+                    },
+                    _ => Type::Any,
+                }
+            }
+            ASTNode::BinaryOperation { left, operator, right } if operator == "|" => {
+                Type::union([self.resolve_annotation(left), self.resolve_annotation(right)])
+            }
+            ASTNode::Subscript { value, index } => {
+                let arguments: Vec<&ASTNode> = match index.as_ref() {
+                    ASTNode::Tuple(elements) => elements.iter().collect(),
+                    other => vec![other],
+                };
+                self.resolve_generic(annotation_name(value).unwrap_or(""), &arguments)
+            }
+            other => match annotation_name(other) {
+                Some(name) => self.resolve_name_annotation(name),
+                None => Type::Any,
+            },
+        }
+    }
+
+    fn resolve_name_annotation(&self, name: &str) -> Type {
+        match name {
+            "int" => Type::Int,
+            "float" => Type::Float,
+            "str" => Type::Str,
+            "bool" => Type::Bool,
+            "None" => Type::None,
+            "NoReturn" | "Never" => Type::Never,
+            "list" | "List" => Type::List(Box::new(Type::Any)),
+            "set" | "Set" | "frozenset" | "FrozenSet" => Type::Set(Box::new(Type::Any)),
+            "dict" | "Dict" => Type::Dict(Box::new(Type::Any), Box::new(Type::Any)),
+            "Callable" => Type::Callable(Box::new(Signature::any_arguments(Type::Any))),
+            "type" | "Type" => Type::ClassObject(Box::new(Type::Any)),
+            "object" | "range" => Type::Instance(name.to_string()),
+            name if self.type_variables.contains(name) => Type::TypeVar(name.to_string()),
+            name if self.context.class(name).is_some() => Type::Instance(name.to_string()),
+            _ => Type::Any,
+        }
+    }
+
+    fn resolve_generic(&self, name: &str, arguments: &[&ASTNode]) -> Type {
+        let argument = |index: usize| arguments.get(index).map_or(Type::Any, |argument| self.resolve_annotation(argument));
+        match name {
+            "list" | "List" => Type::List(Box::new(argument(0))),
+            "set" | "Set" | "frozenset" | "FrozenSet" => Type::Set(Box::new(argument(0))),
+            "dict" | "Dict" => Type::Dict(Box::new(argument(0)), Box::new(argument(1))),
+            "tuple" | "Tuple" if arguments.iter().any(|argument| matches!(argument, ASTNode::Ellipsis)) => Type::Any,
+            "tuple" | "Tuple" => match arguments {
+                [ASTNode::Tuple(elements)] if elements.is_empty() => Type::Tuple(Vec::new()),
+                _ => Type::Tuple(arguments.iter().map(|argument| self.resolve_annotation(argument)).collect()),
+            },
+            "Optional" => Type::optional(argument(0)),
+            "Union" => Type::union(arguments.iter().map(|argument| self.resolve_annotation(argument))),
+            "Callable" => {
+                let returns = argument(1);
+                match arguments.first() {
+                    Some(ASTNode::List(parameters)) => Type::Callable(Box::new(Signature::positional(
+                        parameters.iter().map(|parameter| self.resolve_annotation(parameter)).collect(),
+                        returns,
+                    ))),
+                    _ => Type::Callable(Box::new(Signature::any_arguments(returns))),
+                }
+            }
+            "type" | "Type" => Type::ClassObject(Box::new(argument(0))),
+            "Annotated" | "ClassVar" | "Final" => argument(0),
+            "Literal" => Type::union(arguments.iter().map(|argument| literal_type(argument))),
+            name if self.context.class(name).is_some() => Type::Instance(name.to_string()),
+            _ => Type::Any,
+        }
+    }
+
+    // ----- Variables --------------------------------------------------------
+
+    /// The frames whose narrowed types apply at the current point: the
+    /// innermost frame and the comprehensions it runs inside of.
+    fn flow_frames(&self) -> usize {
+        let mut count = 1;
+        for frame in self.frames.iter().rev() {
+            if frame.kind != FrameKind::Comprehension {
+                break;
+            }
+            count += 1;
+        }
+        count.min(self.frames.len())
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        let flow = self.flow_frames();
+        let innermost = self.frames.len() - 1;
+        for (depth, frame) in self.frames.iter().rev().enumerate() {
+            let index = innermost - depth;
+            if frame.kind == FrameKind::Class && index != innermost {
+                continue;
+            }
+            if frame.globals.contains(name) {
+                return self.outer_type(&self.frames[0], name).unwrap_or(Type::Any);
+            }
+            let found = if depth < flow {
+                frame.types.get(name).or_else(|| frame.declared.get(name)).cloned()
+            } else {
+                self.outer_type(frame, name)
+            };
+            if let Some(ty) = found {
+                return ty;
+            }
+            if frame.locals.contains(name) {
+                return Type::Any;
+            }
+        }
+        builtin_type(name)
+    }
+
+    /// The type of a variable seen from a nested function, which may run at
+    /// any later point and so cannot rely on narrowing.
+    fn outer_type(&self, frame: &Frame, name: &str) -> Option<Type> {
+        if let Some(declared) = frame.declared.get(name) {
+            return Some(declared.clone());
+        }
+        if let Some(recorded) = frame.scope.and_then(|scope| self.variables.get(&(scope, name.to_string()))) {
+            return Some(recorded.clone());
+        }
+        frame.types.get(name).cloned()
+    }
+
+    fn record(&mut self, scope: Option<ScopeId>, name: &str, ty: &Type) {
+        if let Some(scope) = scope {
+            let key = (scope, name.to_string());
+            let merged = match self.variables.remove(&key) {
+                Some(existing) => Type::union([existing, ty.clone()]),
+                None => ty.clone(),
+            };
+            self.variables.insert(key, merged);
+        }
+    }
+
+    fn declare_variable(&mut self, name: &str, ty: Type) {
+        let index = self.assignment_frame(name);
+        let frame = &mut self.frames[index];
+        frame.declared.insert(name.to_string(), ty.clone());
+        let scope = frame.scope;
+        self.variables.insert((scope.unwrap_or(usize::MAX), name.to_string()), ty);
+        if scope.is_none() {
+            self.variables.remove(&(usize::MAX, name.to_string()));
+        }
+    }
+
+    fn assignment_frame(&self, name: &str) -> usize {
+        if self.frame().globals.contains(name) {
+            0
+        } else {
+            self.frames.len() - 1
+        }
+    }
+
+    /// Assigns a value of type `ty` to a variable, checking it against the
+    /// variable's declared type.
+    fn assign_variable(&mut self, name: &str, ty: Type) {
+        let index = self.assignment_frame(name);
+        let declared = self.frames[index].declared.get(name).cloned();
+        let current = match declared {
+            Some(declared) if !self.context.is_assignable(&ty, &declared) => {
+                self.error(format!(
+                    "Incompatible types in assignment (expression has type \"{}\", variable has type \"{}\")",
+                    ty, declared
+                ));
+                declared
+            }
+            // Assigning to a union narrows it to the assigned type.
+            Some(declared @ Type::Union(_)) if ty != Type::Any => self.context.narrow_to(&declared, &ty),
+            Some(declared) => declared,
+            None => ty,
+        };
+        let frame = &mut self.frames[index];
+        let scope = frame.scope;
+        let is_declared = frame.declared.contains_key(name);
+        frame.types.insert(name.to_string(), current.clone());
+        if !is_declared {
+            self.record(scope, name, &current);
+        }
+    }
+
+    fn assign_target(&mut self, target: &ASTNode, ty: Type) {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => self.assign_variable(name, ty),
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                let element_types: Vec<Type> = match &ty {
+                    Type::Tuple(types) if types.len() == elements.len() => types.clone(),
+                    other => vec![other.element_type().unwrap_or(Type::Any); elements.len()],
+                };
+                for (element, ty) in elements.iter().zip(element_types) {
+                    match element {
+                        ASTNode::Starred(inner) => self.assign_target(inner, Type::List(Box::new(ty))),
+                        element => self.assign_target(element, ty),
+                    }
+                }
+            }
+            ASTNode::Starred(inner) => self.assign_target(inner, Type::List(Box::new(ty))),
+            ASTNode::Attribute { value, attribute } => {
+                let owner = self.infer(value);
+                if let Type::Instance(class) = &owner {
+                    if let Some(expected) = self.context.member(class, attribute) {
+                        if !matches!(expected, Type::Callable(_)) && !self.context.is_assignable(&ty, &expected) {
+                            self.error(format!(
+                                "Incompatible types in assignment (expression has type \"{}\", variable has type \"{}\")",
+                                ty, expected
+                            ));
+                        }
+                    }
+                }
+            }
+            ASTNode::Subscript { value, index } => {
+                let container = self.infer(value);
+                let index_type = self.infer(index);
+                let expected = match &container {
+                    Type::List(element) if self.context.is_assignable(&index_type, &Type::Int) => Some(element.as_ref().clone()),
+                    Type::Dict(_, value) => Some(value.as_ref().clone()),
+                    Type::Instance(class) => self.typed_dict_item(class, index),
+                    _ => None,
+                };
+                if let Some(expected) = expected {
+                    if !self.context.is_assignable(&ty, &expected) {
+                        self.error(format!(
+                            "Incompatible types in assignment (expression has type \"{}\", target has type \"{}\")",
+                            ty, expected
+                        ));
+                    }
+                }
+            }
+            other => {
+                self.infer(other);
+            }
+        }
+    }
+
+    // ----- Statements -------------------------------------------------------
+
+    fn check_block(&mut self, statements: &[ASTNode]) {
+        for statement in statements {
+            if self.frame().terminated {
+                break;
+            }
+            let span = statement.span().unwrap_or(self.span);
+            let outer = std::mem::replace(&mut self.span, span);
+            self.check_statement(statement);
+            self.span = outer;
+        }
+    }
+
+    fn check_statement(&mut self, statement: &ASTNode) {
+        match statement {
+            ASTNode::Program(statements) => self.check_block(statements),
+            ASTNode::VariableDeclaration { name, value, .. } => {
+                let ty = self.infer(value);
+                self.assign_variable(name, ty);
+            }
+            ASTNode::ExpressionStatement { expression, .. } => {
+                self.infer(expression);
+            }
+            ASTNode::Assignment { targets, value, .. } => {
+                let ty = match targets.as_slice() {
+                    [ASTNode::Expression(ASTNodeType::Identifier(name))] => {
+                        let index = self.assignment_frame(name);
+                        match self.frames[index].declared.get(name).cloned() {
+                            Some(declared) => self.check_value(value, &declared).unwrap_or_else(|actual| actual),
+                            None => self.infer(value),
+                        }
+                    }
+                    _ => self.infer(value),
+                };
+                for target in targets {
+                    self.assign_target(target, ty.clone());
+                }
+            }
+            ASTNode::AugmentedAssignment { target, operator, value, .. } => {
+                let left = self.infer(target);
+                let right = self.infer(value);
+                let result = self.binary_type(operator, &left, &right);
+                self.expressions.insert(node_key(statement), result.clone());
+                self.assign_target(target, result);
+            }
+            ASTNode::AnnotatedAssignment { target, annotation, value, .. } => {
+                let declared = self.resolve_annotation(annotation);
+                if let ASTNode::Expression(ASTNodeType::Identifier(name)) = target.as_ref() {
+                    if self.frame().kind != FrameKind::Class {
+                        self.declare_variable(name, declared.clone());
+                    }
+                    if let Some(value) = value {
+                        match self.check_value(value, &declared) {
+                            Ok(actual) => {
+                                let narrowed = match &declared {
+                                    Type::Union(_) if actual != Type::Any => self.context.narrow_to(&declared, &actual),
+                                    _ => declared,
+                                };
+                                self.frame_mut().types.insert(name.clone(), narrowed);
+                            }
+                            Err(actual) => self.error(format!(
+                                "Incompatible types in assignment (expression has type \"{}\", variable has type \"{}\")",
+                                actual, declared
+                            )),
+                        }
+                    }
+                } else if let Some(value) = value {
+                    let ty = self.infer(value);
+                    self.assign_target(target, ty);
+                }
+            }
+            ASTNode::If { condition, body, orelse, .. } => self.check_if(condition, body, orelse),
+            ASTNode::While { condition, body, orelse, .. } => {
+                self.infer(condition);
+                let (positive, negative) = self.narrowing(condition);
+                let before = self.frame().types.clone();
+                self.apply_narrowing(&positive);
+                self.check_block(body);
+                let body_types = self.finish_branch(before.clone());
+                self.merge(&before, vec![before.clone(), body_types]);
+                self.apply_narrowing(&negative);
+                self.check_block(orelse);
+            }
+            ASTNode::For { target, iterable, body, orelse, .. } => {
+                let iterable_type = self.infer(iterable);
+                let element = self.element_type(&iterable_type);
+                let before = self.frame().types.clone();
+                self.assign_target(target, element);
+                self.check_block(body);
+                let body_types = self.finish_branch(before.clone());
+                self.merge(&before, vec![before.clone(), body_types]);
+                self.check_block(orelse);
+            }
+            ASTNode::FunctionDefinition(function) => self.check_function(function),
+            ASTNode::ClassDefinition(class) => self.check_class(class),
+            ASTNode::Return { value, .. } => {
+                let expected = self.frame().returns.clone();
+                match (value, expected) {
+                    (Some(value), Some(expected)) => {
+                        if let Err(actual) = self.check_value(value, &expected) {
+                            self.error(format!("Incompatible return value type (got \"{}\", expected \"{}\")", actual, expected));
+                        }
+                    }
+                    (Some(value), None) => {
+                        self.infer(value);
+                    }
+                    (None, Some(expected)) => {
+                        if !self.context.is_assignable(&Type::None, &expected) {
+                            self.error("Return value expected".to_string());
+                        }
+                    }
+                    (None, None) => {}
+                }
+                self.frame_mut().terminated = true;
+            }
+            ASTNode::Try { body, handlers, orelse, finalbody, .. } => self.check_try(body, handlers, orelse, finalbody),
+            ASTNode::With { items, body, .. } => {
+                for item in items {
+                    let manager = self.infer(&item.context);
+                    let entered = match &manager {
+                        Type::Instance(class) => match self.context.member(class, "__enter__") {
+                            Some(Type::Callable(signature)) => signature.returns.clone(),
+                            _ => Type::Any,
+                        },
+                        _ => Type::Any,
+                    };
+                    if let Some(target) = &item.target {
+                        self.assign_target(target, entered);
+                    }
+                }
+                self.check_block(body);
+            }
+            ASTNode::Raise { exception, cause, .. } => {
+                for value in [exception, cause].into_iter().flatten() {
+                    self.infer(value);
+                }
+                self.frame_mut().terminated = true;
+            }
+            ASTNode::Break { .. } | ASTNode::Continue { .. } => self.frame_mut().terminated = true,
+            ASTNode::Global { names, .. } => {
+                for name in names {
+                    self.frame_mut().globals.insert(name.clone());
+                }
+            }
+            ASTNode::Import { names, .. } | ASTNode::ImportFrom { names, .. } => {
+                for name in names.iter().filter(|name| name.name != "*") {
+                    self.assign_variable(name.bound_name(), Type::Any);
+                }
+            }
+            ASTNode::Nonlocal { .. } | ASTNode::Pass { .. } => {}
+            expression => {
+                self.infer(expression);
+            }
+        }
+    }
+
+    fn check_try(&mut self, body: &[ASTNode], handlers: &[ExceptHandler], orelse: &[ASTNode], finalbody: &[ASTNode]) {
+        let before = self.frame().types.clone();
+        self.check_block(body);
+        let body_terminated = self.frame().terminated;
+        let body_types = self.finish_branch(before.clone());
+
+        // A handler can start after any statement of the body, so it sees
+        // both the types from before the body and the ones from its end.
+        self.merge(&before, vec![before.clone(), body_types.clone()]);
+        let handler_entry = self.frame().types.clone();
+        let mut live = Vec::new();
+        for handler in handlers {
+            let outer = std::mem::replace(&mut self.span, handler.span);
+            let caught = match &handler.exception_type {
+                Some(exception_type) => match self.infer(exception_type) {
+                    Type::ClassObject(instance) => *instance,
+                    Type::Tuple(classes) => Type::union(classes.into_iter().map(|class| match class {
+                        Type::ClassObject(instance) => *instance,
+                        _ => Type::Any,
+                    })),
+                    _ => Type::Any,
+                },
+                None => Type::Any,
+            };
+            if let Some(name) = &handler.name {
+                self.assign_variable(name, caught);
+            }
+            self.span = outer;
+            self.check_block(&handler.body);
+            let terminated = self.frame().terminated;
+            let types = self.finish_branch(handler_entry.clone());
+            if !terminated {
+                live.push(types);
+            }
+        }
+
+        self.frame_mut().types = body_types;
+        if !body_terminated {
+            self.check_block(orelse);
+            let terminated = self.frame().terminated;
+            let types = self.finish_branch(before.clone());
+            if !terminated {
+                live.push(types);
+            }
+        }
+
+        if live.is_empty() {
+            self.frame_mut().types = handler_entry;
+            self.check_block(finalbody);
+            self.frame_mut().terminated = true;
+        } else {
+            self.merge(&before, live);
+            self.check_block(finalbody);
+        }
+    }
+
+    fn check_if(&mut self, condition: &ASTNode, body: &[ASTNode], orelse: &[ASTNode]) {
+        self.infer(condition);
+        let (positive, negative) = self.narrowing(condition);
+        let before = self.frame().types.clone();
+
+        self.apply_narrowing(&positive);
+        self.check_block(body);
+        let body_terminated = self.frame().terminated;
+        let body_types = self.finish_branch(before.clone());
+
+        self.apply_narrowing(&negative);
+        self.check_block(orelse);
+        let else_terminated = self.frame().terminated;
+        let else_types = self.finish_branch(before.clone());
+
+        let live: Vec<_> = [(body_types, body_terminated), (else_types, else_terminated)]
+            .into_iter()
+            .filter(|(_, terminated)| !terminated)
+            .map(|(types, _)| types)
+            .collect();
+        if live.is_empty() {
+            self.frame_mut().terminated = true;
+        } else {
+            self.merge(&before, live);
+        }
+    }
+
+    /// Ends a branch: returns its final types and resets the frame to
+    /// `before`.
+    fn finish_branch(&mut self, before: HashMap<String, Type>) -> HashMap<String, Type> {
+        let frame = self.frame_mut();
+        frame.terminated = false;
+        std::mem::replace(&mut frame.types, before)
+    }
+
+    /// Joins the types at the end of the branches that fall through.
+    fn merge(&mut self, before: &HashMap<String, Type>, branches: Vec<HashMap<String, Type>>) {
+        let mut names: Vec<&String> = branches.iter().flat_map(|types| types.keys()).collect();
+        names.sort();
+        names.dedup();
+        let mut merged = HashMap::new();
+        for name in names {
+            let types = branches.iter().filter_map(|types| types.get(name).or_else(|| before.get(name)).cloned());
+            merged.insert(name.clone(), Type::union(types));
+        }
+        self.frame_mut().types = merged;
+    }
+
+    fn apply_narrowing(&mut self, narrowing: &[(String, Type)]) {
+        for (name, ty) in narrowing {
+            self.frame_mut().types.insert(name.clone(), ty.clone());
+        }
+    }
+
+    /// The narrowing implied by `condition`.
+    fn narrowing(&mut self, condition: &ASTNode) -> Narrowing {
+        match condition {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => {
+                let ty = self.lookup(name);
+                if ty.contains_none() {
+                    (vec![(name.clone(), ty.without_none())], Vec::new())
+                } else {
+                    (Vec::new(), Vec::new())
+                }
+            }
+            ASTNode::UnaryOperation { operator, operand } if operator == "not" => {
+                let (positive, negative) = self.narrowing(operand);
+                (negative, positive)
+            }
+            ASTNode::BooleanOperation { operator, values } => {
+                let saved = self.frame().types.clone();
+                let mut collected = Vec::new();
+                for value in values {
+                    let (positive, negative) = self.narrowing(value);
+                    let step = if operator == "and" { positive } else { negative };
+                    self.apply_narrowing(&step);
+                    collected.extend(step);
+                }
+                self.frame_mut().types = saved;
+                if operator == "and" {
+                    (collected, Vec::new())
+                } else {
+                    (Vec::new(), collected)
+                }
+            }
+            ASTNode::Comparison { left, operators, comparators } if operators.len() == 1 => {
+                let (subject, other) = match (left.as_ref(), &comparators[0]) {
+                    (ASTNode::Expression(ASTNodeType::Identifier(name)), other) => (name, other),
+                    (other, ASTNode::Expression(ASTNodeType::Identifier(name))) => (name, other),
+                    _ => return (Vec::new(), Vec::new()),
+                };
+                if !matches!(other, ASTNode::NoneLiteral) || !matches!(operators[0].as_str(), "is" | "is not" | "==" | "!=") {
+                    return (Vec::new(), Vec::new());
+                }
+                let ty = self.lookup(subject);
+                let is_none = (subject.clone(), if ty == Type::Any { Type::None } else { self.context.narrow_to(&ty, &Type::None) });
+                let is_not_none = (subject.clone(), ty.without_none());
+                if matches!(operators[0].as_str(), "is" | "==") {
+                    (vec![is_none], vec![is_not_none])
+                } else {
+                    (vec![is_not_none], vec![is_none])
+                }
+            }
+            ASTNode::Call { function, arguments, keywords, .. }
+                if keywords.is_empty()
+                    && arguments.len() == 2
+                    && matches!(function.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if name == "isinstance") =>
+            {
+                let ASTNode::Expression(ASTNodeType::Identifier(subject)) = &arguments[0] else {
+                    return (Vec::new(), Vec::new());
+                };
+                let classes = match &arguments[1] {
+                    ASTNode::Tuple(elements) => elements.iter().collect(),
+                    other => vec![other],
+                };
+                let target = Type::union(classes.into_iter().map(|class| match self.infer(class) {
+                    Type::ClassObject(instance) => *instance,
+                    _ => Type::Any,
+                }));
+                let ty = self.lookup(subject);
+                (
+                    vec![(subject.clone(), self.context.narrow_to(&ty, &target))],
+                    vec![(subject.clone(), self.context.exclude(&ty, &target))],
+                )
+            }
+            _ => (Vec::new(), Vec::new()),
+        }
+    }
+
+    fn check_function(&mut self, function: &FunctionDefinition) {
+        for decorator in &function.decorators {
+            self.infer(decorator);
+        }
+        for parameter in &function.parameters {
+            if let Some(default) = &parameter.default {
+                let default_type = self.infer(default);
+                if let Some(annotation) = &parameter.annotation {
+                    let expected = self.resolve_annotation(annotation);
+                    if !self.context.is_assignable(&default_type, &expected) {
+                        self.error(format!(
+                            "Incompatible default for argument \"{}\" (default has type \"{}\", argument has type \"{}\")",
+                            parameter.name, default_type, expected
+                        ));
+                    }
+                }
+            }
+        }
+
+        let in_class = self.frame().kind == FrameKind::Class;
+        let class_name = if in_class { self.class_name() } else { None };
+        let signature = self.signature(function, class_name.as_deref());
+        let function_type = self.function_type(function, class_name.as_deref());
+        if !in_class {
+            self.assign_variable(&function.name, function_type);
+        }
+
+        let scope = self.table.definition_scope(&function.name, function.span);
+        let mut frame = Frame::new(FrameKind::Function, scope, self.table);
+        let annotated = function.returns.is_some() || function.parameters.iter().any(|parameter| parameter.annotation.is_some());
+        frame.returns = function.returns.as_ref().map(|_| signature.returns.clone());
+        if function.name == "__init__" && annotated {
+            frame.returns = Some(Type::None);
+        }
+        for (parameter, declared) in function.parameters.iter().zip(signature.parameters.iter().flatten()) {
+            let ty = parameter_binding_type(parameter, &declared.annotation);
+            frame.declared.insert(parameter.name.clone(), ty.clone());
+            if let Some(scope) = scope {
+                self.variables.insert((scope, parameter.name.clone()), ty);
+            }
+        }
+        if let Some(scope) = scope {
+            self.signatures.insert(scope, signature);
+        }
+
+        self.frames.push(frame);
+        self.check_block(&function.body);
+        self.frames.pop();
+    }
+
+    /// The name of the class whose body is being checked.
+    fn class_name(&self) -> Option<String> {
+        let scope = self.frame().scope?;
+        Some(self.table.scope(scope).name.clone())
+    }
+
+    fn check_class(&mut self, class: &ClassDefinition) {
+        for node in class.decorators.iter().chain(&class.bases) {
+            self.infer(node);
+        }
+        if self.context.class(&class.name).is_none() {
+            self.context.add_class(ClassInfo::new(&class.name, ClassKind::Regular));
+            self.declare_class(class);
+        }
+        self.assign_variable(&class.name, Type::ClassObject(Box::new(Type::Instance(class.name.clone()))));
+
+        let scope = self.table.definition_scope(&class.name, class.span);
+        self.frames.push(Frame::new(FrameKind::Class, scope, self.table));
+        self.check_block(&class.body);
+        self.frames.pop();
+    }
+
+    // ----- Expressions ------------------------------------------------------
+
+    /// Checks `value` against an expected type, returning the inferred type
+    /// as `Err` when it is not assignable. Displays (`[...]`, `{...}`) are
+    /// checked element by element, so `x: list[float] = [1]` is accepted.
+    fn check_value(&mut self, value: &ASTNode, expected: &Type) -> Result<Type, Type> {
+        let result = self.check_value_type(value, expected);
+        if let Ok(ty) = &result {
+            self.expressions.insert(node_key(value), ty.clone());
+        }
+        result
+    }
+
+    fn check_value_type(&mut self, value: &ASTNode, expected: &Type) -> Result<Type, Type> {
+        match (value, expected) {
+            (ASTNode::Dict(entries), Type::Instance(class))
+                if self.context.class(class).is_some_and(|info| info.kind == ClassKind::TypedDict) =>
+            {
+                self.check_typed_dict_literal(class, entries);
+                Ok(expected.clone())
+            }
+            (ASTNode::List(elements), Type::List(element)) | (ASTNode::Set(elements), Type::Set(element)) => {
+                let mut compatible = true;
+                for item in elements {
+                    compatible &= self.check_value(item, element).is_ok();
+                }
+                if compatible {
+                    Ok(expected.clone())
+                } else {
+                    Err(self.infer(value))
+                }
+            }
+            (ASTNode::Dict(entries), Type::Dict(key, item)) => {
+                let mut compatible = true;
+                for (entry_key, entry_value) in entries {
+                    compatible &= self.check_value(entry_key, key).is_ok();
+                    compatible &= self.check_value(entry_value, item).is_ok();
+                }
+                if compatible {
+                    Ok(expected.clone())
+                } else {
+                    Err(self.infer(value))
+                }
+            }
+            (value, Type::Union(members)) if matches!(value, ASTNode::List(_) | ASTNode::Set(_) | ASTNode::Dict(_)) => {
+                let actual = self.infer(value);
+                if self.context.is_assignable(&actual, expected) {
+                    return Ok(actual);
+                }
+                let saved = self.diagnostics.len();
+                for member in members {
+                    if self.check_value(value, member).is_ok() {
+                        self.diagnostics.truncate(saved);
+                        return Ok(member.clone());
+                    }
+                    self.diagnostics.truncate(saved);
+                }
+                Err(actual)
+            }
+            _ => {
+                let actual = self.infer(value);
+                if self.context.is_assignable(&actual, expected) {
+                    Ok(actual)
+                } else {
+                    Err(actual)
+                }
+            }
+        }
+    }
+
+    fn check_typed_dict_literal(&mut self, class: &str, entries: &[(ASTNode, ASTNode)]) {
+        let fields = self.typed_dict_fields(class);
+        let mut seen = Vec::new();
+        for (key, value) in entries {
+            let ASTNode::StringLiteral(key) = key else {
+                self.infer(key);
+                self.infer(value);
+                self.error(format!("Expected TypedDict key to be string literal for \"{}\"", class));
+                continue;
+            };
+            seen.push(key.clone());
+            match fields.iter().find(|(field, _)| field == key) {
+                Some((_, expected)) => {
+                    if let Err(actual) = self.check_value(value, expected) {
+                        self.error(format!(
+                            "Incompatible types (expression has type \"{}\", TypedDict item \"{}\" has type \"{}\")",
+                            actual, key, expected
+                        ));
+                    }
+                }
+                None => {
+                    self.infer(value);
+                    self.error(format!("Extra key \"{}\" for TypedDict \"{}\"", key, class));
+                }
+            }
+        }
+        for (field, _) in &fields {
+            if !seen.contains(field) {
+                self.error(format!("Missing key \"{}\" for TypedDict \"{}\"", field, class));
+            }
+        }
+    }
+
+    /// The keys of a `TypedDict`, including inherited ones, those of the
+    /// bases first.
+    fn typed_dict_fields(&self, class: &str) -> Vec<(String, Type)> {
+        let ancestors = self.context.ancestors(class);
+        let classes = ancestors.iter().rev().filter_map(|ancestor| self.context.class(ancestor));
+        classes.flat_map(|info| info.fields.iter().cloned()).collect()
+    }
+
+    fn typed_dict_item(&mut self, class: &str, index: &ASTNode) -> Option<Type> {
+        if self.context.class(class).is_none_or(|info| info.kind != ClassKind::TypedDict) {
+            return None;
+        }
+        let ASTNode::StringLiteral(key) = index else { return Some(Type::Any) };
+        let item = self.typed_dict_fields(class).into_iter().find(|(field, _)| field == key).map(|(_, ty)| ty);
+        if item.is_none() {
+            self.error(format!("TypedDict \"{}\" has no key \"{}\"", class, key));
+        }
+        Some(item.unwrap_or(Type::Any))
+    }
+
+    fn element_type(&mut self, iterable: &Type) -> Type {
+        match iterable.element_type() {
+            Some(element) => element,
+            None => match iterable {
+                Type::Instance(class) if self.context.is_open(class) => Type::Any,
+                Type::Instance(_) | Type::Int | Type::Float | Type::Bool | Type::None => {
+                    self.error(format!("\"{}\" has no attribute \"__iter__\" (not iterable)", iterable));
+                    Type::Any
+                }
+                _ => Type::Any,
+            },
+        }
+    }
+
+    /// Infers the type of an expression, reporting type errors inside it.
+    /// The type is remembered for later stages; when a node is checked more
+    /// than once, the last check wins.
+    pub fn infer(&mut self, expression: &ASTNode) -> Type {
+        let ty = self.infer_expression(expression);
+        self.expressions.insert(node_key(expression), ty.clone());
+        ty
+    }
+
+    fn infer_expression(&mut self, expression: &ASTNode) -> Type {
+        match expression {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => self.lookup(name),
+            ASTNode::Expression(ASTNodeType::Number(_)) | ASTNode::Number(_) | ASTNode::BigNumber(_) => Type::Int,
+            ASTNode::Expression(ASTNodeType::BinaryOperation { operator, left, right })
+            | ASTNode::BinaryOperation { left, operator, right } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                self.binary_type(operator, &left, &right)
+            }
+            ASTNode::Float(_) => Type::Float,
+            ASTNode::Imaginary(_) => Type::Instance("complex".to_string()),
+            ASTNode::StringLiteral(_) => Type::Str,
+            ASTNode::Bytes(_) => Type::Instance("bytes".to_string()),
+            ASTNode::JoinedStr(parts) => {
+                for part in parts {
+                    self.infer(part);
+                }
+                Type::Str
+            }
+            ASTNode::FormattedValue { value, format_spec, .. } => {
+                self.infer(value);
+                if let Some(format_spec) = format_spec {
+                    self.infer(format_spec);
+                }
+                Type::Str
+            }
+            ASTNode::Boolean(_) => Type::Bool,
+            ASTNode::NoneLiteral => Type::None,
+            ASTNode::Ellipsis | ASTNode::Starred(_) => Type::Any,
+            ASTNode::UnaryOperation { operator, operand } => {
+                let operand = self.infer(operand);
+                self.unary_type(operator, &operand)
+            }
+            ASTNode::BooleanOperation { operator, values } => {
+                let saved = self.frame().types.clone();
+                let mut types = Vec::new();
+                for (index, value) in values.iter().enumerate() {
+                    let ty = self.infer(value);
+                    let last = index + 1 == values.len();
+                    // `a or b` only evaluates to `a` when `a` is truthy, which
+                    // `None` never is.
+                    types.push(if operator == "or" && !last { ty.without_none() } else { ty });
+                    let (positive, negative) = self.narrowing(value);
+                    self.apply_narrowing(if operator == "and" { &positive } else { &negative });
+                }
+                self.frame_mut().types = saved;
+                Type::union(types)
+            }
+            ASTNode::Comparison { left, comparators, .. } => {
+                self.infer(left);
+                for comparator in comparators {
+                    self.infer(comparator);
+                }
+                Type::Bool
+            }
+            ASTNode::Conditional { condition, body, orelse } => {
+                self.infer(condition);
+                let (positive, negative) = self.narrowing(condition);
+                let saved = self.frame().types.clone();
+                self.apply_narrowing(&positive);
+                let body = self.infer(body);
+                self.frame_mut().types = saved.clone();
+                self.apply_narrowing(&negative);
+                let orelse = self.infer(orelse);
+                self.frame_mut().types = saved;
+                Type::union([body, orelse])
+            }
+            ASTNode::Call { function, arguments, keywords, span } => {
+                let outer = std::mem::replace(&mut self.span, *span);
+                let ty = self.infer_call(function, arguments, keywords);
+                self.span = outer;
+                ty
+            }
+            ASTNode::Attribute { value, attribute } => {
+                let owner = self.infer(value);
+                self.attribute_type(&owner, attribute)
+            }
+            ASTNode::Subscript { value, index } => {
+                let container = self.infer(value);
+                self.subscript_type(&container, index)
+            }
+            ASTNode::Slice { lower, upper, step } => {
+                for part in [lower, upper, step].into_iter().flatten() {
+                    self.infer(part);
+                }
+                Type::Instance("slice".to_string())
+            }
+            ASTNode::List(elements) => {
+                let element = self.join_elements(elements);
+                Type::List(Box::new(element))
+            }
+            ASTNode::Set(elements) => {
+                let element = self.join_elements(elements);
+                Type::Set(Box::new(element))
+            }
+            ASTNode::Tuple(elements) => Type::Tuple(elements.iter().map(|element| self.infer(element)).collect()),
+            ASTNode::Dict(entries) => {
+                let keys: Vec<Type> = entries.iter().map(|(key, _)| self.infer(key)).collect();
+                let values: Vec<Type> = entries.iter().map(|(_, value)| self.infer(value)).collect();
+                let key = if keys.is_empty() { Type::Any } else { Type::union(keys) };
+                let value = if values.is_empty() { Type::Any } else { Type::union(values) };
+                Type::Dict(Box::new(key), Box::new(value))
+            }
+            ASTNode::Comprehension { kind, element, generators } => self.infer_comprehension(*kind, element, generators),
+            ASTNode::Lambda { parameters, body } => self.infer_lambda(parameters, body),
+            statement => {
+                self.check_statement(statement);
+                Type::None
+            }
+        }
+    }
+
+    fn join_elements(&mut self, elements: &[ASTNode]) -> Type {
+        if elements.is_empty() {
+            return Type::Any;
+        }
+        let types: Vec<Type> = elements
+            .iter()
+            .map(|element| match element {
+                ASTNode::Starred(inner) => {
+                    let ty = self.infer(inner);
+                    self.element_type(&ty)
+                }
+                element => self.infer(element),
+            })
+            .collect();
+        // `[1, 2.5]` is a `list[float]`, as the int is promoted.
+        if types.contains(&Type::Float) && types.iter().all(Type::is_numeric) {
+            return Type::Float;
+        }
+        Type::union(types)
+    }
+
+    fn infer_comprehension(&mut self, kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator]) -> Type {
+        let first_iterable = generators.first().map(|generator| self.infer(&generator.iterable));
+        self.frames.push(Frame::new(FrameKind::Comprehension, None, self.table));
+        for (index, generator) in generators.iter().enumerate() {
+            let iterable = match (&first_iterable, index) {
+                (Some(first), 0) => first.clone(),
+                _ => self.infer(&generator.iterable),
+            };
+            let element = self.element_type(&iterable);
+            self.assign_target(&generator.target, element);
+            for condition in &generator.conditions {
+                self.infer(condition);
+                let (positive, _) = self.narrowing(condition);
+                self.apply_narrowing(&positive);
+            }
+        }
+        let element = self.infer(element);
+        self.frames.pop();
+        match kind {
+            ComprehensionKind::List => Type::List(Box::new(element)),
+            ComprehensionKind::Set => Type::Set(Box::new(element)),
+            ComprehensionKind::Generator => Type::Any,
+        }
+    }
+
+    fn infer_lambda(&mut self, parameters: &[Parameter], body: &ASTNode) -> Type {
+        for default in parameters.iter().filter_map(|parameter| parameter.default.as_ref()) {
+            self.infer(default);
+        }
+        let mut frame = Frame::new(FrameKind::Function, None, self.table);
+        for parameter in parameters {
+            frame.declared.insert(parameter.name.clone(), Type::Any);
+        }
+        self.frames.push(frame);
+        let returns = self.infer(body);
+        self.frames.pop();
+        let parameters = parameters
+            .iter()
+            .map(|parameter| SignatureParameter {
+                name: Some(parameter.name.clone()),
+                kind: parameter.kind,
+                annotation: Type::Any,
+                has_default: parameter.default.is_some(),
+            })
+            .collect();
+        Type::Callable(Box::new(Signature { parameters: Some(parameters), returns }))
+    }
+
+    fn infer_call(&mut self, function: &ASTNode, arguments: &[ASTNode], keywords: &[KeywordArgument]) -> Type {
+        // `d.get(key, default)` can only be `None` if the default is.
+        if let (ASTNode::Attribute { value, attribute }, [key, default]) = (function, arguments) {
+            if attribute == "get" && keywords.is_empty() {
+                let reported = self.diagnostics.len();
+                if let Type::Dict(_, item) = self.infer(value) {
+                    self.infer(key);
+                    let default = self.infer(default);
+                    return Type::union([*item, default]);
+                }
+                self.diagnostics.truncate(reported);
+            }
+        }
+        let callee = self.infer(function);
+        let name = match function {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => name.clone(),
+            ASTNode::Attribute { attribute, .. } => attribute.clone(),
+            _ => String::new(),
+        };
+        self.call_type(&name, &callee, arguments, keywords)
+    }
+
+    fn call_type(&mut self, name: &str, callee: &Type, arguments: &[ASTNode], keywords: &[KeywordArgument]) -> Type {
+        match callee {
+            Type::Callable(signature) => self.check_arguments(name, signature, arguments, keywords),
+            Type::ClassObject(instance) => match instance.as_ref() {
+                Type::Instance(class) => {
+                    if let Some(Type::Callable(initializer)) = self.context.member(class, "__init__") {
+                        self.check_arguments(class, &initializer, arguments, keywords);
+                    } else {
+                        self.infer_arguments(arguments, keywords);
+                    }
+                    instance.as_ref().clone()
+                }
+                Type::List(_) | Type::Set(_) if arguments.len() == 1 => {
+                    let iterable = self.infer(&arguments[0]);
+                    let element = Box::new(self.element_type(&iterable));
+                    match instance.as_ref() {
+                        Type::List(_) => Type::List(element),
+                        _ => Type::Set(element),
+                    }
+                }
+                other => {
+                    self.infer_arguments(arguments, keywords);
+                    other.clone()
+                }
+            },
+            Type::Union(members) if callee.contains_none() => {
+                self.error(format!("\"None\" not callable (callee has type \"{}\")", callee));
+                let callable = Type::union(members.iter().filter(|member| **member != Type::None).cloned());
+                self.call_type(name, &callable, arguments, keywords)
+            }
+            Type::Any | Type::TypeVar(_) | Type::Union(_) => {
+                self.infer_arguments(arguments, keywords);
+                Type::Any
+            }
+            Type::Instance(class) => {
+                self.infer_arguments(arguments, keywords);
+                match self.context.member(class, "__call__") {
+                    Some(Type::Callable(signature)) => signature.returns.clone(),
+                    _ if self.context.is_open(class) => Type::Any,
+                    _ => {
+                        self.error(format!("\"{}\" not callable", callee));
+                        Type::Any
+                    }
+                }
+            }
+            other => {
+                self.infer_arguments(arguments, keywords);
+                self.error(format!("\"{}\" not callable", other));
+                Type::Any
+            }
+        }
+    }
+
+    fn infer_arguments(&mut self, arguments: &[ASTNode], keywords: &[KeywordArgument]) {
+        for argument in arguments {
+            self.infer(argument);
+        }
+        for keyword in keywords {
+            self.infer(&keyword.value);
+        }
+    }
+
+    /// Matches the arguments of a call to the callee's parameters, reporting
+    /// arguments without a parameter and required parameters without an
+    /// argument, then checks the types of the matched arguments and returns
+    /// the call's result type with type variables solved. Arity is not
+    /// checked past a `*args` or `**kwargs` argument, whose length is unknown.
+    fn check_arguments(&mut self, name: &str, signature: &Signature, arguments: &[ASTNode], keywords: &[KeywordArgument]) -> Type {
+        let Some(parameters) = &signature.parameters else {
+            self.infer_arguments(arguments, keywords);
+            return signature.returns.clone();
+        };
+
+        let positional: Vec<usize> = (0..parameters.len())
+            .filter(|&index| matches!(parameters[index].kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword))
+            .collect();
+        let var_positional = parameters.iter().find(|parameter| parameter.kind == ParameterKind::VarPositional);
+        let var_keyword = parameters.iter().find(|parameter| parameter.kind == ParameterKind::VarKeyword);
+        let callee = if name.is_empty() { String::new() } else { format!(" for \"{}\"", name) };
+
+        let mut matched: Vec<(String, &ASTNode, &SignatureParameter)> = Vec::new();
+        let mut filled = vec![false; parameters.len()];
+        let mut unpacked = false;
+        let mut too_many = false;
+        for (index, argument) in arguments.iter().enumerate() {
+            if matches!(argument, ASTNode::Starred(_)) {
+                unpacked = true;
+            }
+            let parameter = match positional.get(index) {
+                _ if unpacked => None,
+                Some(&position) => {
+                    filled[position] = true;
+                    Some(&parameters[position])
+                }
+                None => var_positional,
+            };
+            match parameter {
+                Some(parameter) => matched.push((format!("Argument {}", index + 1), argument, parameter)),
+                None => {
+                    too_many |= !unpacked;
+                    self.infer(argument);
+                }
+            }
+        }
+        if too_many {
+            let has_keyword_only = parameters.iter().any(|parameter| parameter.kind == ParameterKind::KeywordOnly);
+            self.error(format!("Too many {}arguments{}", if has_keyword_only { "positional " } else { "" }, callee));
+        }
+
+        let mut unpacked_keywords = false;
+        for keyword in keywords {
+            let Some(keyword_name) = &keyword.name else {
+                unpacked_keywords = true;
+                self.infer(&keyword.value);
+                continue;
+            };
+            let position = parameters.iter().position(|parameter| {
+                parameter.name.as_ref() == Some(keyword_name)
+                    && matches!(parameter.kind, ParameterKind::PositionalOrKeyword | ParameterKind::KeywordOnly)
+            });
+            let parameter = match position {
+                Some(position) if filled[position] => {
+                    let subject = if name.is_empty() { "Function".to_string() } else { format!("\"{}\"", name) };
+                    self.error(format!("{} gets multiple values for keyword argument \"{}\"", subject, keyword_name));
+                    None
+                }
+                Some(position) => {
+                    filled[position] = true;
+                    Some(&parameters[position])
+                }
+                None if var_keyword.is_some() => var_keyword,
+                None => {
+                    self.error(format!("Unexpected keyword argument \"{}\"{}", keyword_name, callee));
+                    None
+                }
+            };
+            match parameter {
+                Some(parameter) => matched.push((format!("Argument \"{}\"", keyword_name), &keyword.value, parameter)),
+                None => {
+                    self.infer(&keyword.value);
+                }
+            }
+        }
+
+        let missing = |kind: fn(ParameterKind) -> bool| {
+            parameters
+                .iter()
+                .zip(&filled)
+                .filter(|(parameter, filled)| kind(parameter.kind) && !parameter.has_default && !**filled)
+                .map(|(parameter, _)| parameter)
+                .collect::<Vec<_>>()
+        };
+        let missing_positional = missing(|kind| matches!(kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword));
+        if !unpacked && !unpacked_keywords && !missing_positional.is_empty() {
+            let names: Option<Vec<String>> = missing_positional
+                .iter()
+                .map(|parameter| match (&parameter.name, parameter.kind) {
+                    (Some(name), ParameterKind::PositionalOrKeyword) => Some(format!("\"{}\"", name)),
+                    _ => None,
+                })
+                .collect();
+            match names {
+                Some(names) => {
+                    let plural = if names.len() > 1 { "s" } else { "" };
+                    let callee = if name.is_empty() { String::new() } else { format!(" to \"{}\"", name) };
+                    self.error(format!("Missing positional argument{} {} in call{}", plural, names.join(", "), callee));
+                }
+                None => self.error(format!("Too few arguments{}", callee)),
+            }
+        }
+        if !unpacked_keywords {
+            for parameter in missing(|kind| kind == ParameterKind::KeywordOnly) {
+                let parameter_name = parameter.name.as_deref().unwrap_or_default();
+                self.error(format!("Missing named argument \"{}\"{}", parameter_name, callee));
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        let mut checked = Vec::new();
+        for (label, argument, parameter) in &matched {
+            let expected = &parameter.annotation;
+            // Displays are only checked against concrete types: against
+            // `list[T]` their own type is what binds `T`.
+            let actual = if expected.is_generic() {
+                self.infer(argument)
+            } else {
+                match self.check_value(argument, expected) {
+                    Ok(actual) | Err(actual) => actual,
+                }
+            };
+            self.context.infer_type_variables(expected, &actual, &mut bindings);
+            checked.push((label, actual, expected));
+        }
+        for (label, actual, expected) in checked {
+            let expected = expected.substitute(&bindings);
+            if !self.context.is_assignable(&actual, &expected) {
+                let callee = if name.is_empty() { String::new() } else { format!(" to \"{}\"", name) };
+                self.error(format!("{}{} has incompatible type \"{}\"; expected \"{}\"", label, callee, actual, expected));
+            }
+        }
+        signature.returns.substitute(&bindings)
+    }
+
+    fn attribute_type(&mut self, owner: &Type, attribute: &str) -> Type {
+        match owner {
+            Type::Any | Type::TypeVar(_) | Type::ClassObject(_) => Type::Any,
+            Type::Union(members) => {
+                if owner.contains_none() {
+                    self.error(format!("Item \"None\" of \"{}\" has no attribute \"{}\"", owner, attribute));
+                }
+                let members: Vec<Type> = members.iter().filter(|member| **member != Type::None).cloned().collect();
+                Type::union(members.iter().map(|member| self.attribute_type(member, attribute)))
+            }
+            Type::Instance(class) => match self.context.member(class, attribute) {
+                Some(member) => member,
+                None if self.context.is_open(class) => Type::Any,
+                None => {
+                    self.error(format!("\"{}\" has no attribute \"{}\"", class, attribute));
+                    Type::Any
+                }
+            },
+            Type::None => {
+                self.error(format!("\"None\" has no attribute \"{}\"", attribute));
+                Type::Any
+            }
+            other => builtin_method(other, attribute),
+        }
+    }
+
+    fn subscript_type(&mut self, container: &Type, index: &ASTNode) -> Type {
+        let index_type = self.infer(index);
+        match container {
+            Type::Any | Type::TypeVar(_) => Type::Any,
+            Type::ClassObject(_) => container.clone(),
+            Type::List(element) => {
+                if matches!(index_type, Type::Instance(ref name) if name == "slice") {
+                    container.clone()
+                } else {
+                    self.check_index(container, &index_type, &Type::Int);
+                    element.as_ref().clone()
+                }
+            }
+            Type::Str => {
+                if !matches!(index_type, Type::Instance(ref name) if name == "slice") {
+                    self.check_index(container, &index_type, &Type::Int);
+                }
+                Type::Str
+            }
+            Type::Dict(key, value) => {
+                self.check_index(container, &index_type, key);
+                value.as_ref().clone()
+            }
+            Type::Tuple(elements) => match (index, index_type) {
+                (ASTNode::Number(position), _) if (*position as usize) < elements.len() && *position >= 0 => {
+                    elements[*position as usize].clone()
+                }
+                (_, Type::Instance(name)) if name == "slice" => Type::Any,
+                _ => Type::union(elements.clone()),
+            },
+            Type::Instance(class) => match self.typed_dict_item(class, index) {
+                Some(item) => item,
+                None => match self.context.member(class, "__getitem__") {
+                    Some(Type::Callable(signature)) => signature.returns.clone(),
+                    _ if self.context.is_open(class) => Type::Any,
+                    _ => {
+                        self.error(format!("Value of type \"{}\" is not indexable", container));
+                        Type::Any
+                    }
+                },
+            },
+            Type::Union(members) if container.contains_none() => {
+                self.error(format!("Value of type \"{}\" is not indexable", container));
+                let rest = Type::union(members.iter().filter(|member| **member != Type::None).cloned());
+                self.subscript_type(&rest, index)
+            }
+            Type::Union(_) => Type::Any,
+            other => {
+                self.error(format!("Value of type \"{}\" is not indexable", other));
+                Type::Any
+            }
+        }
+    }
+
+    fn check_index(&mut self, container: &Type, index: &Type, expected: &Type) {
+        if !self.context.is_assignable(index, expected) {
+            self.error(format!("Invalid index type \"{}\" for \"{}\"; expected type \"{}\"", index, container, expected));
+        }
+    }
+
+    fn unary_type(&mut self, operator: &str, operand: &Type) -> Type {
+        match (operator, operand) {
+            ("not", _) => Type::Bool,
+            (_, Type::Any) | (_, Type::TypeVar(_)) => Type::Any,
+            ("-" | "+", Type::Int | Type::Bool) | ("~", Type::Int | Type::Bool) => Type::Int,
+            ("-" | "+", Type::Float) => Type::Float,
+            (_, Type::Instance(class)) if self.context.is_open(class) => Type::Any,
+            _ => {
+                self.error(format!("Unsupported operand type for unary {} (\"{}\")", operator, operand));
+                Type::Any
+            }
+        }
+    }
+
+    /// The result type of a binary operator, reporting unsupported operand
+    /// types. Unions are checked member by member.
+    fn binary_type(&mut self, operator: &str, left: &Type, right: &Type) -> Type {
+        let mut results = Vec::new();
+        for left_member in left.members() {
+            for right_member in right.members() {
+                match self.binary_member_type(operator, &left_member, &right_member) {
+                    Some(result) => results.push(result),
+                    None => {
+                        self.error(format!(
+                            "Unsupported operand types for {} (\"{}\" and \"{}\")",
+                            operator, left_member, right_member
+                        ));
+                        return Type::Any;
+                    }
+                }
+            }
+        }
+        Type::union(results)
+    }
+
+    fn binary_member_type(&self, operator: &str, left: &Type, right: &Type) -> Option<Type> {
+        use Type::{Any, Bool, Dict, Float, Instance, Int, List, Set, Str, Tuple, TypeVar};
+        let integral = |ty: &Type| matches!(ty, Int | Bool);
+        match (left, right) {
+            (Any, _) | (_, Any) | (TypeVar(_), _) | (_, TypeVar(_)) => Some(Any),
+            (left, right) if left.is_numeric() && right.is_numeric() => match operator {
+                "/" => Some(Float),
+                "+" | "-" | "*" | "//" | "%" | "**" => {
+                    Some(if matches!(left, Float) || matches!(right, Float) { Float } else { Int })
+                }
+                "<<" | ">>" | "&" | "|" | "^" if integral(left) && integral(right) => {
+                    Some(if matches!((left, right), (Bool, Bool)) && operator != "<<" && operator != ">>" { Bool } else { Int })
+                }
+                _ => None,
+            },
+            (Str, Str) if operator == "+" => Some(Str),
+            (Str, other) | (other, Str) if operator == "*" && integral(other) => Some(Str),
+            (Str, _) if operator == "%" => Some(Str),
+            (List(a), List(b)) if operator == "+" => Some(List(Box::new(Type::union([a.as_ref().clone(), b.as_ref().clone()])))),
+            (List(_), other) if operator == "*" && integral(other) => Some(left.clone()),
+            (other, List(_)) if operator == "*" && integral(other) => Some(right.clone()),
+            (Tuple(a), Tuple(b)) if operator == "+" => Some(Tuple(a.iter().chain(b).cloned().collect())),
+            (Set(a), Set(b)) if matches!(operator, "|" | "&" | "-" | "^") => {
+                Some(Set(Box::new(Type::union([a.as_ref().clone(), b.as_ref().clone()]))))
+            }
+            (Dict(..), Dict(..)) if operator == "|" => Some(left.clone()),
+            (Instance(class), _) => {
+                let method = dunder_method(operator)?;
+                match self.context.member(class, method) {
+                    Some(Type::Callable(signature)) => Some(signature.returns.clone()),
+                    _ if self.context.is_open(class) => Some(Any),
+                    _ => None,
+                }
+            }
+            (_, Instance(class)) if self.context.is_open(class) => Some(Any),
+            _ => None,
+        }
+    }
+}
+
+/// The type a parameter has inside its function body.
+fn parameter_binding_type(parameter: &Parameter, annotation: &Type) -> Type {
+    match parameter.kind {
+        ParameterKind::VarPositional => Type::Any,
+        ParameterKind::VarKeyword if parameter.annotation.is_some() => {
+            Type::Dict(Box::new(Type::Str), Box::new(annotation.clone()))
+        }
+        ParameterKind::VarKeyword => Type::Dict(Box::new(Type::Str), Box::new(Type::Any)),
+        _ => annotation.clone(),
+    }
+}
+
+fn receiver_parameter(annotation: Type) -> SignatureParameter {
+    SignatureParameter { name: None, kind: ParameterKind::PositionalOnly, annotation, has_default: false }
+}
+
+/// Decorators that keep a method's signature as written.
+const METHOD_DECORATORS: &[&str] = &["staticmethod", "classmethod", "property", "abstractmethod", "setter"];
+
+/// Whether the decorator is `pybolt.inline` or `pybolt.noinline`, which
+/// return the function they are given.
+fn is_inlining_hint(decorator: &ASTNode) -> bool {
+    match decorator {
+        ASTNode::Attribute { value, attribute } => {
+            matches!(value.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if name == "pybolt")
+                && matches!(attribute.as_str(), "inline" | "noinline")
+        }
+        _ => false,
+    }
+}
+
+/// The name an annotation or base class refers to, looking through module
+/// attributes such as `typing.Optional`.
+fn annotation_name(node: &ASTNode) -> Option<&str> {
+    match node {
+        ASTNode::Expression(ASTNodeType::Identifier(name)) => Some(name),
+        ASTNode::Attribute { attribute, .. } => Some(attribute),
+        ASTNode::Subscript { value, .. } => annotation_name(value),
+        ASTNode::NoneLiteral => Some("None"),
+        _ => None,
+    }
+}
+
+/// Whether an expression names the `typing` module, whose `Protocol`,
+/// `TypedDict` and `Generic` may be used as `typing.Protocol`.
+fn is_typing(node: &ASTNode) -> bool {
+    matches!(node, ASTNode::Expression(ASTNodeType::Identifier(name)) if name == "typing" || name == "typing_extensions")
+}
+
+/// The type of a literal, or `Any` for other expressions.
+fn literal_type(node: &ASTNode) -> Type {
+    match node {
+        ASTNode::Number(_) | ASTNode::BigNumber(_) | ASTNode::Expression(ASTNodeType::Number(_)) => Type::Int,
+        ASTNode::Float(_) => Type::Float,
+        ASTNode::Imaginary(_) => Type::Instance("complex".to_string()),
+        ASTNode::StringLiteral(_) | ASTNode::JoinedStr(_) => Type::Str,
+        ASTNode::Bytes(_) => Type::Instance("bytes".to_string()),
+        ASTNode::Boolean(_) => Type::Bool,
+        ASTNode::NoneLiteral => Type::None,
+        _ => Type::Any,
+    }
+}
+
+fn dunder_method(operator: &str) -> Option<&'static str> {
+    Some(match operator {
+        "+" => "__add__",
+        "-" => "__sub__",
+        "*" => "__mul__",
+        "/" => "__truediv__",
+        "//" => "__floordiv__",
+        "%" => "__mod__",
+        "**" => "__pow__",
+        "@" => "__matmul__",
+        "&" => "__and__",
+        "|" => "__or__",
+        "^" => "__xor__",
+        "<<" => "__lshift__",
+        ">>" => "__rshift__",
+        _ => return None,
+    })
+}
+
+/// The types of the commonly used methods of builtin types; others are
+/// `Any`.
+fn builtin_method(owner: &Type, attribute: &str) -> Type {
+    let method = |parameters: Vec<Type>, returns: Type| Type::Callable(Box::new(Signature::positional(parameters, returns)));
+    let variadic = |returns: Type| Type::Callable(Box::new(Signature::any_arguments(returns)));
+    match (owner, attribute) {
+        (Type::Str, "upper" | "lower" | "strip" | "lstrip" | "rstrip" | "title" | "capitalize") => method(vec![], Type::Str),
+        (Type::Str, "startswith" | "endswith") => method(vec![Type::Str], Type::Bool),
+        (Type::Str, "isdigit" | "isalpha" | "isspace" | "isupper" | "islower") => method(vec![], Type::Bool),
+        (Type::Str, "split" | "splitlines") => variadic(Type::List(Box::new(Type::Str))),
+        (Type::Str, "join") => method(vec![Type::Any], Type::Str),
+        (Type::Str, "replace" | "format") => variadic(Type::Str),
+        (Type::Str, "find" | "index" | "count") => variadic(Type::Int),
+        (Type::List(element), "append") => method(vec![element.as_ref().clone()], Type::None),
+        (Type::List(_), "extend") => method(vec![Type::Any], Type::None),
+        (Type::List(element), "insert") => method(vec![Type::Int, element.as_ref().clone()], Type::None),
+        (Type::List(element), "pop") => variadic(element.as_ref().clone()),
+        (Type::List(_), "index" | "count") => variadic(Type::Int),
+        (Type::List(_), "sort" | "reverse" | "clear") => variadic(Type::None),
+        (Type::List(_), "copy") => method(vec![], owner.clone()),
+        (Type::Set(element), "add" | "discard" | "remove") => method(vec![element.as_ref().clone()], Type::None),
+        (Type::Dict(_, value), "get") => variadic(Type::optional(value.as_ref().clone())),
+        (Type::Dict(key, _), "keys") => method(vec![], Type::List(key.clone())),
+        (Type::Dict(_, value), "values") => method(vec![], Type::List(value.clone())),
+        (Type::Dict(key, value), "items") => {
+            method(vec![], Type::List(Box::new(Type::Tuple(vec![key.as_ref().clone(), value.as_ref().clone()]))))
+        }
+        (Type::Dict(_, value), "pop" | "setdefault") => variadic(value.as_ref().clone()),
+        (Type::Int, "bit_length") => method(vec![], Type::Int),
+        (Type::Float, "is_integer") => method(vec![], Type::Bool),
+        _ => Type::Any,
+    }
+}
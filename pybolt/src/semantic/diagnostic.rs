@@ -0,0 +1,47 @@
+use crate::lexer::lexer::{line_column, Span};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by a semantic pass, attached to the span of the statement
+/// (or call) it was found in.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// The code of the lint rule that produced the diagnostic, if any.
+    pub code: Option<&'static str>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Error, message: message.into(), span, code: None }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic { severity: Severity::Warning, message: message.into(), span, code: None }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Formats the diagnostic as `line:column: error: message`, followed by
+    /// the lint code in brackets when there is one.
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = line_column(source, self.span.start);
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match self.code {
+            Some(code) => format!("{}:{}: {}: {} [{}]", line, column, severity, self.message, code),
+            None => format!("{}:{}: {}: {}", line, column, severity, self.message),
+        }
+    }
+}
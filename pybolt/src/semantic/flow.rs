@@ -0,0 +1,579 @@
+use std::collections::BTreeSet;
+
+use crate::lexer::lexer::Span;
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler, FunctionDefinition,
+    Parameter,
+};
+use crate::parser::visitor::{walk_node, walk_parameters, Visitor};
+use crate::semantic::builtins::is_builtin;
+use crate::semantic::diagnostic::Diagnostic;
+use crate::semantic::lint::UNREACHABLE_CODE;
+use crate::semantic::scope::{ScopeId, ScopeKind, SymbolKind, SymbolTable};
+
+/// The variables assigned on the paths reaching a point of the program.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Assigned {
+    /// Assigned on every path.
+    definitely: BTreeSet<String>,
+    /// Assigned on at least one path.
+    maybe: BTreeSet<String>,
+}
+
+/// The flow state at a point: `None` when no path reaches it.
+type State = Option<Assigned>;
+
+fn join(a: State, b: State) -> State {
+    match (a, b) {
+        (None, other) | (other, None) => other,
+        (Some(a), Some(b)) => Some(Assigned {
+            definitely: a.definitely.intersection(&b.definitely).cloned().collect(),
+            maybe: a.maybe.union(&b.maybe).cloned().collect(),
+        }),
+    }
+}
+
+fn assign(state: &mut State, name: &str) {
+    if let Some(assigned) = state {
+        assigned.definitely.insert(name.to_string());
+        assigned.maybe.insert(name.to_string());
+    }
+}
+
+fn unassign(state: &mut State, name: &str) {
+    if let Some(assigned) = state {
+        assigned.definitely.remove(name);
+        assigned.maybe.remove(name);
+    }
+}
+
+/// The states flowing out of a loop body through `break` and `continue`.
+#[derive(Default)]
+struct LoopExits {
+    breaks: State,
+    continues: State,
+}
+
+/// Flow-sensitive checks that run after scopes are resolved: names that are
+/// unbound or possibly unbound where they are read, statements that can
+/// never run, and functions that can fall off their end despite a return
+/// annotation.
+///
+/// Only variables bound in the scope being analyzed are tracked. Reads of
+/// enclosing or global variables from a nested function are not, since the
+/// function may run at any later point.
+pub struct FlowAnalyzer<'a> {
+    table: &'a SymbolTable,
+    scope: ScopeId,
+    /// The variables whose reads are checked in the current scope.
+    tracked: BTreeSet<String>,
+    loops: Vec<LoopExits>,
+    /// Cleared while a loop body is analyzed to reach a fixed point, so each
+    /// problem is only reported by the final pass.
+    reporting: bool,
+    span: Span,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> FlowAnalyzer<'a> {
+    pub fn analyze(program: &ASTNode, table: &'a SymbolTable) -> Vec<Diagnostic> {
+        let statements = match program {
+            ASTNode::Program(statements) => statements.as_slice(),
+            other => std::slice::from_ref(other),
+        };
+        let mut analyzer = FlowAnalyzer {
+            table,
+            scope: table.root(),
+            tracked: BTreeSet::new(),
+            loops: Vec::new(),
+            reporting: true,
+            span: Span::default(),
+            diagnostics: Vec::new(),
+        };
+        analyzer.tracked = analyzer.tracked_names(table.root(), statements);
+        analyzer.block(statements, Some(Assigned::default()));
+        analyzer.diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        analyzer.diagnostics.dedup();
+        analyzer.diagnostics
+    }
+
+    /// The variables of `scope` whose reads can be checked. Module and class
+    /// namespaces fall back to globals and builtins, so only function locals
+    /// and module variables that nothing else can bind are tracked.
+    fn tracked_names(&self, scope: ScopeId, statements: &[ASTNode]) -> BTreeSet<String> {
+        let scope = self.table.scope(scope);
+        let symbols = scope.symbols().iter().filter(|symbol| symbol.is_assigned && !symbol.is_parameter);
+        match scope.kind {
+            ScopeKind::Function => symbols
+                .filter(|symbol| matches!(symbol.kind, SymbolKind::Local | SymbolKind::Cell))
+                .map(|symbol| symbol.name.clone())
+                .collect(),
+            ScopeKind::Module if !has_star_import(statements) => {
+                let declared_global = self.names_declared_global();
+                symbols
+                    .filter(|symbol| !is_builtin(&symbol.name) && !declared_global.contains(&symbol.name))
+                    .map(|symbol| symbol.name.clone())
+                    .collect()
+            }
+            _ => BTreeSet::new(),
+        }
+    }
+
+    /// Module variables that a function may bind through `global`.
+    fn names_declared_global(&self) -> BTreeSet<String> {
+        self.table
+            .scopes()
+            .iter()
+            .filter(|scope| scope.kind != ScopeKind::Module)
+            .flat_map(|scope| scope.symbols())
+            .filter(|symbol| symbol.kind == SymbolKind::Global && symbol.is_assigned)
+            .map(|symbol| symbol.name.clone())
+            .collect()
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.reporting {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Checks the variables read by an expression against `state`.
+    fn uses(&mut self, expression: &ASTNode, state: &State) {
+        let Some(assigned) = state else { return };
+        let mut collector = UseCollector::default();
+        collector.visit_node(expression);
+        let mut reported = BTreeSet::new();
+        for name in collector.names {
+            if !self.tracked.contains(&name) || assigned.definitely.contains(&name) || !reported.insert(name.clone()) {
+                continue;
+            }
+            let in_function = self.table.scope(self.scope).kind == ScopeKind::Function;
+            let diagnostic = match (assigned.maybe.contains(&name), in_function) {
+                (false, true) => Diagnostic::error(format!("local variable '{}' referenced before assignment", name), self.span),
+                (false, false) => Diagnostic::error(format!("name '{}' is not defined", name), self.span),
+                (true, true) => {
+                    Diagnostic::warning(format!("local variable '{}' may be referenced before assignment", name), self.span)
+                }
+                (true, false) => Diagnostic::warning(format!("name '{}' may be undefined", name), self.span),
+            };
+            self.report(diagnostic);
+        }
+    }
+
+    fn assign_target(&mut self, target: &ASTNode, state: &mut State) {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => assign(state, name),
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                for element in elements {
+                    self.assign_target(element, state);
+                }
+            }
+            ASTNode::Starred(inner) => self.assign_target(inner, state),
+            other => self.uses(other, state),
+        }
+    }
+
+    /// Analyzes a block, reporting the first statement that no path reaches.
+    fn block(&mut self, statements: &[ASTNode], mut state: State) -> State {
+        for statement in statements {
+            let outer = self.span;
+            self.span = statement.span().unwrap_or(outer);
+            if state.is_none() {
+                self.report(Diagnostic::warning("Statement is unreachable", self.span).with_code(UNREACHABLE_CODE));
+                self.span = outer;
+                return None;
+            }
+            state = self.statement(statement, state);
+            self.span = outer;
+        }
+        state
+    }
+
+    fn statement(&mut self, statement: &ASTNode, mut state: State) -> State {
+        match statement {
+            ASTNode::Program(statements) => return self.block(statements, state),
+            ASTNode::VariableDeclaration { name, value, .. } => {
+                self.uses(value, &state);
+                assign(&mut state, name);
+            }
+            ASTNode::ExpressionStatement { expression, .. } => self.uses(expression, &state),
+            ASTNode::Assignment { targets, value, .. } => {
+                self.uses(value, &state);
+                for target in targets {
+                    self.assign_target(target, &mut state);
+                }
+            }
+            ASTNode::AugmentedAssignment { target, value, .. } => {
+                self.uses(target, &state);
+                self.uses(value, &state);
+                self.assign_target(target, &mut state);
+            }
+            ASTNode::AnnotatedAssignment { target, value: Some(value), .. } => {
+                self.uses(value, &state);
+                self.assign_target(target, &mut state);
+            }
+            ASTNode::AnnotatedAssignment { value: None, .. } => {}
+            ASTNode::If { condition, body, orelse, .. } => {
+                self.uses(condition, &state);
+                let body_state = self.block(body, state.clone());
+                let else_state = self.block(orelse, state);
+                return join(body_state, else_state);
+            }
+            ASTNode::While { condition, body, orelse, .. } => {
+                let infinite = is_always_true(condition);
+                let (head, exits) = self.analyze_loop(state, |analyzer, head| {
+                    analyzer.uses(condition, head);
+                    analyzer.block(body, head.clone())
+                });
+                let normal_exit = if infinite { None } else { self.block(orelse, head) };
+                return join(normal_exit, exits.breaks);
+            }
+            ASTNode::For { target, iterable, body, orelse, .. } => {
+                self.uses(iterable, &state);
+                let (head, exits) = self.analyze_loop(state, |analyzer, head| {
+                    let mut entry = head.clone();
+                    analyzer.assign_target(target, &mut entry);
+                    analyzer.block(body, entry)
+                });
+                let normal_exit = self.block(orelse, head);
+                return join(normal_exit, exits.breaks);
+            }
+            ASTNode::Try { body, handlers, orelse, finalbody, .. } => {
+                return self.try_statement(body, handlers, orelse, finalbody, state);
+            }
+            ASTNode::With { items, body, .. } => {
+                for item in items {
+                    self.uses(&item.context, &state);
+                    if let Some(target) = &item.target {
+                        self.assign_target(target, &mut state);
+                    }
+                }
+                return self.block(body, state);
+            }
+            ASTNode::FunctionDefinition(function) => {
+                for decorator in &function.decorators {
+                    self.uses(decorator, &state);
+                }
+                self.defaults(&function.parameters, &state);
+                self.function(function);
+                assign(&mut state, &function.name);
+            }
+            ASTNode::ClassDefinition(class) => {
+                for node in class.decorators.iter().chain(&class.bases) {
+                    self.uses(node, &state);
+                }
+                for keyword in &class.keywords {
+                    self.uses(&keyword.value, &state);
+                }
+                self.class(class);
+                assign(&mut state, &class.name);
+            }
+            ASTNode::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.uses(value, &state);
+                }
+                return None;
+            }
+            ASTNode::Raise { exception, cause, .. } => {
+                for value in [exception, cause].into_iter().flatten() {
+                    self.uses(value, &state);
+                }
+                return None;
+            }
+            ASTNode::Break { .. } => {
+                if let Some(exits) = self.loops.last_mut() {
+                    exits.breaks = join(exits.breaks.take(), state);
+                }
+                return None;
+            }
+            ASTNode::Continue { .. } => {
+                if let Some(exits) = self.loops.last_mut() {
+                    exits.continues = join(exits.continues.take(), state);
+                }
+                return None;
+            }
+            ASTNode::Import { names, .. } | ASTNode::ImportFrom { names, .. } => {
+                for name in names.iter().filter(|name| name.name != "*") {
+                    assign(&mut state, name.bound_name());
+                }
+            }
+            ASTNode::Global { .. } | ASTNode::Nonlocal { .. } | ASTNode::Pass { .. } => {}
+            expression => self.uses(expression, &state),
+        }
+        state
+    }
+
+    /// Runs a loop body to a fixed point. Returns the state at the loop head
+    /// (where the condition is tested or the next item is fetched) and the
+    /// `break` states of the final pass.
+    fn analyze_loop(&mut self, entry: State, mut body: impl FnMut(&mut Self, &State) -> State) -> (State, LoopExits) {
+        let reporting = self.reporting;
+        self.reporting = false;
+        let mut head = entry.clone();
+        loop {
+            self.loops.push(LoopExits::default());
+            let end = body(self, &head);
+            let exits = self.loops.pop().unwrap_or_default();
+            let next = join(join(entry.clone(), end), exits.continues);
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        self.reporting = reporting;
+
+        self.loops.push(LoopExits::default());
+        body(self, &head);
+        let exits = self.loops.pop().unwrap_or_default();
+        (head, exits)
+    }
+
+    fn try_statement(
+        &mut self,
+        body: &[ASTNode],
+        handlers: &[ExceptHandler],
+        orelse: &[ASTNode],
+        finalbody: &[ASTNode],
+        state: State,
+    ) -> State {
+        // An exception can be raised before any statement of the body, so a
+        // handler only knows what was assigned before the `try`, plus that
+        // anything assigned in the body might have been.
+        let mut raised = state.clone();
+        if let Some(assigned) = &mut raised {
+            assigned.maybe.extend(assigned_names(body));
+        }
+
+        let body_end = self.block(body, state);
+        let mut normal = self.block(orelse, body_end);
+        for handler in handlers {
+            if let Some(exception_type) = &handler.exception_type {
+                let outer = std::mem::replace(&mut self.span, handler.span);
+                self.uses(exception_type, &raised);
+                self.span = outer;
+            }
+            let mut entry = raised.clone();
+            if let Some(name) = &handler.name {
+                assign(&mut entry, name);
+            }
+            let mut end = self.block(&handler.body, entry);
+            // `except E as name` deletes `name` when the handler ends.
+            if let Some(name) = &handler.name {
+                unassign(&mut end, name);
+            }
+            normal = join(normal, end);
+        }
+        if finalbody.is_empty() {
+            return normal;
+        }
+
+        // `finally` also runs while an exception propagates. Report what
+        // it reads against both ways in, but continue from the normal one.
+        if let Some(assigned) = &mut raised {
+            assigned.maybe.extend(handlers.iter().flat_map(|handler| assigned_names(&handler.body)));
+        }
+        self.block(finalbody, join(normal.clone(), raised));
+        let normal = normal?;
+        let reporting = std::mem::replace(&mut self.reporting, false);
+        let end = self.block(finalbody, Some(normal));
+        self.reporting = reporting;
+        end
+    }
+
+    /// Default values are evaluated where the `def` or `lambda` runs.
+    fn defaults(&mut self, parameters: &[Parameter], state: &State) {
+        for default in parameters.iter().filter_map(|parameter| parameter.default.as_ref()) {
+            self.uses(default, state);
+        }
+    }
+
+    fn function(&mut self, function: &FunctionDefinition) {
+        let Some(scope) = self.table.definition_scope(&function.name, function.span) else { return };
+        let tracked = self.tracked_names(scope, &function.body);
+        let outer_scope = std::mem::replace(&mut self.scope, scope);
+        let outer_tracked = std::mem::replace(&mut self.tracked, tracked);
+        let outer_loops = std::mem::take(&mut self.loops);
+
+        let end = self.block(&function.body, Some(Assigned::default()));
+        if end.is_some() {
+            self.check_missing_return(function);
+        }
+
+        self.scope = outer_scope;
+        self.tracked = outer_tracked;
+        self.loops = outer_loops;
+    }
+
+    /// Reports a function that can reach the end of its body although its
+    /// return annotation does not allow `None`.
+    fn check_missing_return(&mut self, function: &FunctionDefinition) {
+        let Some(returns) = &function.returns else { return };
+        if is_trivial_body(&function.body) {
+            return;
+        }
+        let message = match annotation_name(returns) {
+            Some("None") | Some("Any") => return,
+            Some("NoReturn") | Some("Never") => "Implicit return in function which does not return",
+            _ => "Missing return statement",
+        };
+        self.report(Diagnostic::error(message, function.span));
+    }
+
+    /// Class bodies are not tracked, since their reads fall back to globals,
+    /// but their methods and reachability still are.
+    fn class(&mut self, class: &ClassDefinition) {
+        let Some(scope) = self.table.definition_scope(&class.name, class.span) else { return };
+        let outer_scope = std::mem::replace(&mut self.scope, scope);
+        let outer_tracked = std::mem::take(&mut self.tracked);
+        let outer_loops = std::mem::take(&mut self.loops);
+        self.block(&class.body, Some(Assigned::default()));
+        self.scope = outer_scope;
+        self.tracked = outer_tracked;
+        self.loops = outer_loops;
+    }
+}
+
+/// Collects the names an expression reads when it is evaluated, in order.
+/// Lambda bodies run later and are skipped; comprehensions run immediately,
+/// so their reads of enclosing variables count.
+#[derive(Default)]
+struct UseCollector {
+    names: Vec<String>,
+    /// Loop variables of the comprehensions being visited.
+    bound: Vec<String>,
+}
+
+impl Visitor for UseCollector {
+    fn visit_identifier(&mut self, name: &str) {
+        if !self.bound.iter().any(|bound| bound == name) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Parameter], _body: &ASTNode) {
+        walk_parameters(self, parameters);
+    }
+
+    fn visit_comprehension(&mut self, _kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator]) {
+        let depth = self.bound.len();
+        for generator in generators {
+            self.visit_node(&generator.iterable);
+            let mut targets = Vec::new();
+            collect_target_names(&generator.target, &mut targets);
+            self.bound.extend(targets);
+            for condition in &generator.conditions {
+                self.visit_node(condition);
+            }
+        }
+        self.visit_node(element);
+        self.bound.truncate(depth);
+    }
+}
+
+fn collect_target_names(target: &ASTNode, names: &mut Vec<String>) {
+    match target {
+        ASTNode::Expression(ASTNodeType::Identifier(name)) => names.push(name.clone()),
+        ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+            for element in elements {
+                collect_target_names(element, names);
+            }
+        }
+        ASTNode::Starred(inner) => collect_target_names(inner, names),
+        _ => {}
+    }
+}
+
+/// Every name a block binds, including in nested blocks but not in nested
+/// functions or classes (other than their own names).
+fn assigned_names(statements: &[ASTNode]) -> Vec<String> {
+    #[derive(Default)]
+    struct Bindings {
+        names: Vec<String>,
+    }
+
+    impl Visitor for Bindings {
+        fn visit_node(&mut self, node: &ASTNode) {
+            match node {
+                ASTNode::VariableDeclaration { name, .. } => self.names.push(name.clone()),
+                ASTNode::Assignment { targets, .. } => {
+                    for target in targets {
+                        collect_target_names(target, &mut self.names);
+                    }
+                }
+                ASTNode::AugmentedAssignment { target, .. } | ASTNode::For { target, .. } => {
+                    collect_target_names(target, &mut self.names)
+                }
+                ASTNode::AnnotatedAssignment { target, value: Some(_), .. } => {
+                    collect_target_names(target, &mut self.names)
+                }
+                ASTNode::With { items, .. } => {
+                    for target in items.iter().filter_map(|item| item.target.as_ref()) {
+                        collect_target_names(target, &mut self.names);
+                    }
+                }
+                ASTNode::FunctionDefinition(function) => {
+                    self.names.push(function.name.clone());
+                    return;
+                }
+                ASTNode::ClassDefinition(class) => {
+                    self.names.push(class.name.clone());
+                    return;
+                }
+                ASTNode::Import { names, .. } | ASTNode::ImportFrom { names, .. } => {
+                    self.names.extend(names.iter().map(|name| name.bound_name().to_string()));
+                }
+                _ => {}
+            }
+            walk_node(self, node);
+        }
+
+        fn visit_except_handler(&mut self, handler: &ExceptHandler) {
+            self.names.extend(handler.name.clone());
+            self.visit_block(&handler.body);
+        }
+    }
+
+    let mut bindings = Bindings::default();
+    bindings.visit_block(statements);
+    bindings.names
+}
+
+fn has_star_import(statements: &[ASTNode]) -> bool {
+    statements
+        .iter()
+        .any(|statement| matches!(statement, ASTNode::ImportFrom { names, .. } if names.iter().any(|name| name.name == "*")))
+}
+
+fn is_always_true(condition: &ASTNode) -> bool {
+    match condition {
+        ASTNode::Boolean(value) => *value,
+        ASTNode::Number(value) | ASTNode::Expression(ASTNodeType::Number(value)) => *value != 0,
+        ASTNode::BigNumber(_) => true,
+        _ => false,
+    }
+}
+
+/// A body with nothing but a docstring, `pass`, `...` or a `raise`, such as
+/// a protocol member or an abstract method.
+fn is_trivial_body(body: &[ASTNode]) -> bool {
+    body.iter().all(|statement| match statement {
+        ASTNode::Pass { .. } | ASTNode::Raise { .. } => true,
+        ASTNode::ExpressionStatement { expression, .. } => {
+            matches!(expression.as_ref(), ASTNode::Ellipsis | ASTNode::StringLiteral(_))
+        }
+        _ => false,
+    })
+}
+
+/// The name a return annotation refers to, looking through `typing.X`,
+/// subscripts such as `Optional[int]` and string annotations.
+fn annotation_name(node: &ASTNode) -> Option<&str> {
+    match node {
+        ASTNode::Expression(ASTNodeType::Identifier(name)) => Some(name),
+        ASTNode::Attribute { attribute, .. } => Some(attribute),
+        ASTNode::Subscript { value, .. } => annotation_name(value),
+        ASTNode::NoneLiteral => Some("None"),
+        ASTNode::StringLiteral(value) => Some(value.trim()),
+        _ => None,
+    }
+}
@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::lexer::lexer::{line_column, Span};
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler, FunctionDefinition,
+    ImportAlias, Parameter, WithItem,
+};
+use crate::parser::visitor::{walk_node, walk_parameters, Visitor};
+use crate::semantic::builtins::is_builtin;
+use crate::semantic::diagnostic::{Diagnostic, Severity};
+use crate::semantic::scope::{ScopeId, ScopeKind, SymbolKind, SymbolTable};
+
+pub const UNUSED_IMPORT: &str = "PB001";
+pub const UNUSED_VARIABLE: &str = "PB002";
+pub const UNUSED_ARGUMENT: &str = "PB003";
+pub const SHADOWED_BUILTIN: &str = "PB004";
+pub const MUTABLE_DEFAULT: &str = "PB005";
+pub const IS_LITERAL: &str = "PB006";
+pub const BARE_EXCEPT: &str = "PB007";
+pub const UNREACHABLE_CODE: &str = "PB008";
+pub const REDEFINED_FUNCTION: &str = "PB009";
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LintLevel {
+    /// The rule does not report anything.
+    Allow,
+    /// Findings are warnings.
+    Warn,
+    /// Findings are errors, which fail the analysis.
+    Deny,
+}
+
+#[derive(Debug)]
+pub struct LintRule {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub default_level: LintLevel,
+    pub description: &'static str,
+}
+
+pub const RULES: &[LintRule] = &[
+    LintRule {
+        code: UNUSED_IMPORT,
+        name: "unused-import",
+        default_level: LintLevel::Warn,
+        description: "a module or name is imported but never used",
+    },
+    LintRule {
+        code: UNUSED_VARIABLE,
+        name: "unused-variable",
+        default_level: LintLevel::Warn,
+        description: "a local variable is assigned but never read",
+    },
+    LintRule {
+        code: UNUSED_ARGUMENT,
+        name: "unused-argument",
+        default_level: LintLevel::Allow,
+        description: "a function argument is never read",
+    },
+    LintRule {
+        code: SHADOWED_BUILTIN,
+        name: "shadowed-builtin",
+        default_level: LintLevel::Warn,
+        description: "a variable, argument, function or import hides a builtin",
+    },
+    LintRule {
+        code: MUTABLE_DEFAULT,
+        name: "mutable-default",
+        default_level: LintLevel::Warn,
+        description: "a default argument value is a list, dict or set shared between calls",
+    },
+    LintRule {
+        code: IS_LITERAL,
+        name: "is-literal",
+        default_level: LintLevel::Warn,
+        description: "`is` or `is not` compares against a literal",
+    },
+    LintRule {
+        code: BARE_EXCEPT,
+        name: "bare-except",
+        default_level: LintLevel::Warn,
+        description: "`except:` also catches KeyboardInterrupt and SystemExit",
+    },
+    LintRule {
+        code: UNREACHABLE_CODE,
+        name: "unreachable-code",
+        default_level: LintLevel::Warn,
+        description: "a statement can never run",
+    },
+    LintRule {
+        code: REDEFINED_FUNCTION,
+        name: "redefined-function",
+        default_level: LintLevel::Warn,
+        description: "a function or class is redefined before its first definition is used",
+    },
+];
+
+/// Looks a rule up by code (`PB001`) or name (`unused-import`).
+pub fn rule(code_or_name: &str) -> Option<&'static LintRule> {
+    RULES.iter().find(|rule| rule.code.eq_ignore_ascii_case(code_or_name) || rule.name == code_or_name)
+}
+
+/// The level of every lint rule.
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    levels: HashMap<&'static str, LintLevel>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig { levels: RULES.iter().map(|rule| (rule.code, rule.default_level)).collect() }
+    }
+}
+
+impl LintConfig {
+    /// Sets the level of a rule, given by code or name, or of every rule
+    /// with `all`.
+    pub fn set(&mut self, code_or_name: &str, level: LintLevel) -> Result<(), String> {
+        if code_or_name == "all" {
+            for value in self.levels.values_mut() {
+                *value = level;
+            }
+            return Ok(());
+        }
+        let rule = rule(code_or_name).ok_or_else(|| format!("Unknown lint rule '{}'", code_or_name))?;
+        self.levels.insert(rule.code, level);
+        Ok(())
+    }
+
+    pub fn level(&self, code: &str) -> LintLevel {
+        self.levels.get(code).copied().unwrap_or(LintLevel::Warn)
+    }
+
+    /// Applies the configured levels and the `# pybolt: ignore[CODE]`
+    /// comments of `source` to the diagnostics that carry a lint code.
+    /// A bare `# pybolt: ignore` silences every rule on its line.
+    pub fn apply(&self, source: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let suppressions = suppressions(source);
+        diagnostics
+            .into_iter()
+            .filter_map(|mut diagnostic| {
+                let Some(code) = diagnostic.code else { return Some(diagnostic) };
+                let (line, _) = line_column(source, diagnostic.span.start);
+                if let Some(codes) = suppressions.get(&line) {
+                    if codes.is_none() || codes.as_ref().is_some_and(|codes| codes.iter().any(|c| c == code)) {
+                        return None;
+                    }
+                }
+                diagnostic.severity = match self.level(code) {
+                    LintLevel::Allow => return None,
+                    LintLevel::Warn => Severity::Warning,
+                    LintLevel::Deny => Severity::Error,
+                };
+                Some(diagnostic)
+            })
+            .collect()
+    }
+}
+
+/// The suppression comment of each line: `None` for a bare
+/// `# pybolt: ignore`, or the listed codes.
+fn suppressions(source: &str) -> HashMap<usize, Option<Vec<String>>> {
+    let pattern = Regex::new(r"#\s*pybolt:\s*ignore(?:\[([A-Za-z0-9_\-,\s]*)\])?").expect("valid suppression pattern");
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let captures = pattern.captures(line)?;
+            let codes = captures.get(1).map(|codes| {
+                codes
+                    .as_str()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|code| !code.is_empty())
+                    .map(|code| rule(code).map_or(code.to_string(), |rule| rule.code.to_string()))
+                    .collect()
+            });
+            Some((index + 1, codes))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingKind {
+    Import,
+    Assignment,
+    /// A target of tuple unpacking or a `for` loop, which is often bound
+    /// only to get at the other values.
+    Unpacked,
+    Handler,
+    Parameter,
+    Definition,
+}
+
+#[derive(Debug)]
+struct Binding {
+    scope: ScopeId,
+    name: String,
+    /// How the binding is shown in messages: the full dotted name for
+    /// `import a.b`.
+    display: String,
+    kind: BindingKind,
+    span: Span,
+}
+
+/// Collects the lint findings of a program. Unreachable code is reported by
+/// the flow analysis, and levels and suppressions are applied afterwards by
+/// `LintConfig::apply`.
+pub struct Linter<'a> {
+    table: &'a SymbolTable,
+    source: &'a str,
+    scope: ScopeId,
+    bindings: Vec<Binding>,
+    /// Functions whose arguments are not reported: stubs and methods.
+    stubs: Vec<ScopeId>,
+    span: Span,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Linter<'a> {
+    pub fn lint(program: &ASTNode, table: &'a SymbolTable, source: &'a str) -> Vec<Diagnostic> {
+        let mut linter = Linter {
+            table,
+            source,
+            scope: table.root(),
+            bindings: Vec::new(),
+            stubs: Vec::new(),
+            span: Span::default(),
+            diagnostics: Vec::new(),
+        };
+        linter.visit_node(program);
+        linter.check_bindings();
+        linter.diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        linter.diagnostics
+    }
+
+    fn report(&mut self, code: &'static str, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic::warning(message, span).with_code(code));
+    }
+
+    fn bind(&mut self, name: &str, display: &str, kind: BindingKind, span: Span) {
+        self.bindings.push(Binding { scope: self.scope, name: name.to_string(), display: display.to_string(), kind, span });
+    }
+
+    fn bind_target(&mut self, target: &ASTNode, kind: BindingKind) {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => self.bind(name, name, kind, self.span),
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                for element in elements {
+                    self.bind_target(element, BindingKind::Unpacked);
+                }
+            }
+            ASTNode::Starred(inner) => self.bind_target(inner, BindingKind::Unpacked),
+            other => self.visit_node(other),
+        }
+    }
+
+    fn in_scope(&mut self, scope: Option<ScopeId>, body: impl FnOnce(&mut Self)) {
+        let Some(scope) = scope else { return };
+        let outer = std::mem::replace(&mut self.scope, scope);
+        body(self);
+        self.scope = outer;
+    }
+
+    /// Whether the binding of `name` in `scope` is read, either there or by
+    /// a nested scope that refers to it.
+    fn is_read(&self, scope: ScopeId, name: &str) -> bool {
+        let owner = self.table.scope(scope);
+        if owner.symbol(name).is_some_and(|symbol| symbol.is_used) {
+            return true;
+        }
+        let reference = if owner.kind == ScopeKind::Module { SymbolKind::Global } else { SymbolKind::Free };
+        self.is_read_below(scope, name, reference)
+    }
+
+    fn is_read_below(&self, scope: ScopeId, name: &str, reference: SymbolKind) -> bool {
+        self.table.scope(scope).children.iter().any(|&child| match self.table.scope(child).symbol(name) {
+            Some(symbol) if symbol.kind == reference => symbol.is_used || self.is_read_below(child, name, reference),
+            Some(_) => false,
+            None => self.is_read_below(child, name, reference),
+        })
+    }
+
+    fn check_bindings(&mut self) {
+        let mut seen: Vec<(ScopeId, &str)> = Vec::new();
+        let mut findings = Vec::new();
+        for binding in &self.bindings {
+            if seen.contains(&(binding.scope, binding.name.as_str())) {
+                continue;
+            }
+            seen.push((binding.scope, binding.name.as_str()));
+            let scope = self.table.scope(binding.scope);
+            let is_local = scope
+                .symbol(&binding.name)
+                .is_some_and(|symbol| matches!(symbol.kind, SymbolKind::Local | SymbolKind::Cell));
+
+            if scope.kind != ScopeKind::Class && is_builtin(&binding.name) && !binding.name.starts_with("__") {
+                let what = match binding.kind {
+                    BindingKind::Parameter => "argument",
+                    BindingKind::Definition => "definition",
+                    BindingKind::Import => "import",
+                    _ => "variable",
+                };
+                findings.push((SHADOWED_BUILTIN, format!("{} '{}' shadows a builtin", what, binding.name), binding.span));
+            }
+
+            if binding.name.starts_with('_') || self.is_read(binding.scope, &binding.name) {
+                continue;
+            }
+            let in_function = scope.kind == ScopeKind::Function && is_local;
+            match binding.kind {
+                BindingKind::Import if in_function || scope.kind == ScopeKind::Module => {
+                    findings.push((UNUSED_IMPORT, format!("'{}' imported but unused", binding.display), binding.span));
+                }
+                BindingKind::Assignment | BindingKind::Handler if in_function => findings.push((
+                    UNUSED_VARIABLE,
+                    format!("local variable '{}' is assigned to but never used", binding.name),
+                    binding.span,
+                )),
+                BindingKind::Parameter if !self.stubs.contains(&binding.scope) => {
+                    findings.push((UNUSED_ARGUMENT, format!("unused argument '{}'", binding.name), binding.span));
+                }
+                _ => {}
+            }
+        }
+        for (code, message, span) in findings {
+            self.report(code, message, span);
+        }
+    }
+
+    fn check_defaults(&mut self, parameters: &[Parameter]) {
+        for parameter in parameters {
+            if parameter.default.as_ref().is_some_and(is_mutable) {
+                self.report(MUTABLE_DEFAULT, format!("mutable default value for argument '{}'", parameter.name), self.span);
+            }
+        }
+    }
+
+    /// Reports functions and classes that are defined again in the same
+    /// block before anything used the first definition.
+    fn check_redefinitions(&mut self, statements: &[ASTNode]) {
+        let mut unused: Vec<(String, Span)> = Vec::new();
+        for statement in statements {
+            // Decorators, defaults and bases run before the new definition is
+            // bound, so `@value.setter` and `class A(A)` use the previous one.
+            let (name, decorators, header): (&str, &[ASTNode], Vec<ASTNode>) = match statement {
+                ASTNode::FunctionDefinition(function) => {
+                    let defaults = function.parameters.iter().filter_map(|parameter| parameter.default.clone());
+                    (&function.name, &function.decorators, function.decorators.iter().cloned().chain(defaults).collect())
+                }
+                ASTNode::ClassDefinition(class) => {
+                    let keywords = class.keywords.iter().map(|keyword| keyword.value.clone());
+                    let header = class.decorators.iter().chain(&class.bases).cloned().chain(keywords).collect();
+                    (&class.name, &class.decorators, header)
+                }
+                other => {
+                    let mentioned = mentioned_names(std::slice::from_ref(other));
+                    unused.retain(|(name, _)| !mentioned.contains(name));
+                    continue;
+                }
+            };
+            let mentioned = mentioned_names(&header);
+            unused.retain(|(name, _)| !mentioned.contains(name));
+
+            let is_overload = decorators.iter().any(|decorator| decorator_name(decorator) == Some("overload"));
+            if let Some(position) = unused.iter().position(|(previous, _)| previous == name) {
+                let (_, span) = unused.remove(position);
+                if !is_overload {
+                    let (line, _) = line_column(self.source, span.start);
+                    let message = format!("redefinition of unused '{}' from line {}", name, line);
+                    let span = statement.span().unwrap_or(self.span);
+                    self.report(REDEFINED_FUNCTION, message, span);
+                }
+            }
+            if !is_overload {
+                unused.push((name.to_string(), statement.span().unwrap_or(self.span)));
+            }
+            // A definition may refer to an earlier one inside its body.
+            let mentioned = mentioned_names(std::slice::from_ref(statement));
+            unused.retain(|(previous, _)| previous == name || !mentioned.contains(previous));
+        }
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn visit_node(&mut self, node: &ASTNode) {
+        match node.span() {
+            Some(span) => {
+                let outer = std::mem::replace(&mut self.span, span);
+                walk_node(self, node);
+                self.span = outer;
+            }
+            None => walk_node(self, node),
+        }
+    }
+
+    fn visit_program(&mut self, statements: &[ASTNode]) {
+        self.check_redefinitions(statements);
+        for statement in statements {
+            self.visit_node(statement);
+        }
+    }
+
+    fn visit_block(&mut self, statements: &[ASTNode]) {
+        self.check_redefinitions(statements);
+        for statement in statements {
+            self.visit_node(statement);
+        }
+    }
+
+    fn visit_variable_declaration(&mut self, name: &str, value: &ASTNode, span: Span) {
+        self.visit_node(value);
+        self.bind(name, name, BindingKind::Assignment, span);
+    }
+
+    fn visit_assignment(&mut self, targets: &[ASTNode], value: &ASTNode, _span: Span) {
+        self.visit_node(value);
+        for target in targets {
+            self.bind_target(target, BindingKind::Assignment);
+        }
+    }
+
+    fn visit_annotated_assignment(&mut self, target: &ASTNode, annotation: &ASTNode, value: Option<&ASTNode>, _span: Span) {
+        self.visit_node(annotation);
+        if let Some(value) = value {
+            self.visit_node(value);
+            self.bind_target(target, BindingKind::Assignment);
+        }
+    }
+
+    fn visit_with(&mut self, items: &[WithItem], body: &[ASTNode], _span: Span) {
+        for item in items {
+            self.visit_node(&item.context);
+            if let Some(target) = &item.target {
+                self.bind_target(target, BindingKind::Unpacked);
+            }
+        }
+        self.visit_block(body);
+    }
+
+    fn visit_for(&mut self, target: &ASTNode, iterable: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], _span: Span) {
+        self.visit_node(iterable);
+        self.bind_target(target, BindingKind::Unpacked);
+        self.visit_block(body);
+        self.visit_block(orelse);
+    }
+
+    fn visit_import(&mut self, names: &[ImportAlias], span: Span) {
+        for name in names {
+            let display = name.alias.as_deref().unwrap_or(&name.name);
+            self.bind(name.bound_name(), display, BindingKind::Import, span);
+        }
+    }
+
+    fn visit_import_from(&mut self, module: &str, names: &[ImportAlias], span: Span) {
+        if module == "__future__" {
+            return;
+        }
+        for name in names.iter().filter(|name| name.name != "*") {
+            self.bind(name.bound_name(), name.bound_name(), BindingKind::Import, span);
+        }
+    }
+
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        for decorator in &function.decorators {
+            self.visit_node(decorator);
+        }
+        walk_parameters(self, &function.parameters);
+        if let Some(returns) = &function.returns {
+            self.visit_node(returns);
+        }
+        self.check_defaults(&function.parameters);
+        self.bind(&function.name, &function.name, BindingKind::Definition, function.span);
+
+        let is_method = self.table.scope(self.scope).kind == ScopeKind::Class;
+        let scope = self.table.definition_scope(&function.name, function.span);
+        self.in_scope(scope, |linter| {
+            if is_method || is_stub(&function.body) {
+                linter.stubs.push(linter.scope);
+            }
+            for parameter in &function.parameters {
+                linter.bind(&parameter.name, &parameter.name, BindingKind::Parameter, function.span);
+            }
+            linter.visit_block(&function.body);
+        });
+    }
+
+    fn visit_class_definition(&mut self, class: &ClassDefinition) {
+        for node in class.decorators.iter().chain(&class.bases) {
+            self.visit_node(node);
+        }
+        for keyword in &class.keywords {
+            self.visit_node(&keyword.value);
+        }
+        self.bind(&class.name, &class.name, BindingKind::Definition, class.span);
+        let scope = self.table.definition_scope(&class.name, class.span);
+        self.in_scope(scope, |linter| linter.visit_block(&class.body));
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Parameter], body: &ASTNode) {
+        walk_parameters(self, parameters);
+        self.check_defaults(parameters);
+        self.visit_node(body);
+    }
+
+    /// Comprehension variables live in their own scope and are not linted.
+    fn visit_comprehension(&mut self, _kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator]) {
+        for generator in generators {
+            self.visit_node(&generator.iterable);
+            for condition in &generator.conditions {
+                self.visit_node(condition);
+            }
+        }
+        self.visit_node(element);
+    }
+
+    fn visit_except_handler(&mut self, handler: &ExceptHandler) {
+        match &handler.exception_type {
+            Some(exception_type) => self.visit_node(exception_type),
+            None => self.report(BARE_EXCEPT, "do not use bare 'except'".to_string(), handler.span),
+        }
+        if let Some(name) = &handler.name {
+            self.bind(name, name, BindingKind::Handler, handler.span);
+        }
+        self.visit_block(&handler.body);
+    }
+
+    fn visit_comparison(&mut self, left: &ASTNode, operators: &[String], comparators: &[ASTNode]) {
+        let mut operands = std::iter::once(left).chain(comparators);
+        let mut previous = operands.next();
+        for (operator, right) in operators.iter().zip(operands) {
+            if matches!(operator.as_str(), "is" | "is not") && (previous.is_some_and(is_literal) || is_literal(right)) {
+                let suggestion = if operator == "is" { "==" } else { "!=" };
+                let message = format!("\"{}\" with a literal. Did you mean \"{}\"?", operator, suggestion);
+                self.report(IS_LITERAL, message, self.span);
+            }
+            previous = Some(right);
+        }
+        self.visit_node(left);
+        for comparator in comparators {
+            self.visit_node(comparator);
+        }
+    }
+}
+
+/// Every identifier read in the statements, including in nested functions.
+fn mentioned_names(statements: &[ASTNode]) -> Vec<String> {
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visitor for Names {
+        fn visit_identifier(&mut self, name: &str) {
+            self.0.push(name.to_string());
+        }
+    }
+
+    let mut names = Names::default();
+    for statement in statements {
+        names.visit_node(statement);
+    }
+    names.0
+}
+
+fn decorator_name(decorator: &ASTNode) -> Option<&str> {
+    match decorator {
+        ASTNode::Expression(ASTNodeType::Identifier(name)) => Some(name),
+        ASTNode::Attribute { attribute, .. } => Some(attribute),
+        ASTNode::Call { function, .. } => decorator_name(function),
+        _ => None,
+    }
+}
+
+/// Literals whose identity is an implementation detail. `None`, `True`,
+/// `False` and `...` are singletons and fine to compare with `is`.
+fn is_literal(node: &ASTNode) -> bool {
+    matches!(
+        node,
+        ASTNode::Number(_)
+            | ASTNode::BigNumber(_)
+            | ASTNode::Expression(ASTNodeType::Number(_))
+            | ASTNode::Float(_)
+            | ASTNode::Imaginary(_)
+            | ASTNode::StringLiteral(_)
+            | ASTNode::Bytes(_)
+            | ASTNode::JoinedStr(_)
+            | ASTNode::List(_)
+            | ASTNode::Tuple(_)
+            | ASTNode::Set(_)
+            | ASTNode::Dict(_)
+    )
+}
+
+fn is_mutable(default: &ASTNode) -> bool {
+    match default {
+        ASTNode::List(_) | ASTNode::Set(_) | ASTNode::Dict(_) => true,
+        ASTNode::Comprehension { kind, .. } => *kind != ComprehensionKind::Generator,
+        ASTNode::Call { function, .. } => {
+            matches!(function.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if matches!(name.as_str(), "list" | "dict" | "set"))
+        }
+        _ => false,
+    }
+}
+
+/// A body that only documents an interface: a docstring, `pass`, `...` or
+/// a `raise`.
+fn is_stub(body: &[ASTNode]) -> bool {
+    body.iter().all(|statement| match statement {
+        ASTNode::Pass { .. } | ASTNode::Raise { .. } => true,
+        ASTNode::ExpressionStatement { expression, .. } => {
+            matches!(expression.as_ref(), ASTNode::Ellipsis | ASTNode::StringLiteral(_))
+        }
+        _ => false,
+    })
+}
@@ -0,0 +1,8 @@
+pub mod builtins;
+pub mod checker;
+pub mod diagnostic;
+pub mod flow;
+pub mod lint;
+pub mod scope;
+pub mod semantic;
+pub mod types;
@@ -0,0 +1,620 @@
+use crate::lexer::lexer::Span;
+use crate::parser::parser::{
+    ASTNode, ASTNodeType, ClassDefinition, ComprehensionGenerator, ComprehensionKind, ExceptHandler, FunctionDefinition,
+    ImportAlias, Parameter, WithItem,
+};
+use crate::parser::visitor::{walk_node, walk_parameters, Visitor};
+use crate::semantic::builtins::is_builtin;
+use crate::semantic::diagnostic::Diagnostic;
+
+/// Index of a scope in its `SymbolTable`.
+pub type ScopeId = usize;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ScopeKind {
+    Module,
+    Class,
+    /// A `def` or a `lambda`.
+    Function,
+    /// A list, set or generator comprehension, which gets its own scope for
+    /// its loop variables.
+    Comprehension,
+}
+
+/// Where a name is looked up at runtime, following Python's LEGB rule.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolKind {
+    /// A local of a function or comprehension (stored in a fast slot), or a
+    /// name in a class namespace.
+    Local,
+    /// A local that is captured by a nested function and so lives in a cell.
+    Cell,
+    /// A variable of an enclosing function, reached through the closure.
+    Free,
+    /// A module-level name, either bound at module level or declared `global`.
+    Global,
+    Builtin,
+}
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// For locals of functions and comprehensions, the fast local slot. For
+    /// cells and free variables, the index in the closure, with cells
+    /// numbered before free variables. `None` for everything else.
+    pub slot: Option<usize>,
+    pub is_parameter: bool,
+    /// Bound in this scope by an assignment, loop target, definition or
+    /// parameter.
+    pub is_assigned: bool,
+    /// Read in this scope (not counting reads from nested scopes).
+    pub is_used: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    /// The function or class name, `<lambda>`, `<listcomp>`, `<setcomp>`,
+    /// `<genexpr>` or `<module>`.
+    pub name: String,
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>,
+    /// Span of the statement that defines the scope.
+    pub span: Span,
+    symbols: Vec<Symbol>,
+}
+
+impl Scope {
+    fn new(kind: ScopeKind, name: &str, parent: Option<ScopeId>, span: Span) -> Self {
+        Scope { kind, name: name.to_string(), parent, children: Vec::new(), span, symbols: Vec::new() }
+    }
+
+    /// The symbols of the scope in the order they first appear, parameters
+    /// first.
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn symbol(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    fn symbol_mut(&mut self, name: &str) -> Option<&mut Symbol> {
+        self.symbols.iter_mut().find(|symbol| symbol.name == name)
+    }
+
+    fn symbols_of_kind(&self, kind: SymbolKind) -> impl Iterator<Item = &Symbol> {
+        self.symbols.iter().filter(move |symbol| symbol.kind == kind)
+    }
+
+    /// Fast locals in slot order.
+    pub fn locals(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols_of_kind(SymbolKind::Local)
+    }
+
+    /// Captured locals in closure order.
+    pub fn cells(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols_of_kind(SymbolKind::Cell)
+    }
+
+    /// Free variables in closure order.
+    pub fn frees(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols_of_kind(SymbolKind::Free)
+    }
+
+    fn has_fast_locals(&self) -> bool {
+        matches!(self.kind, ScopeKind::Function | ScopeKind::Comprehension)
+    }
+}
+
+/// The scopes of a program and the resolution of every name in them.
+///
+/// The module is scope 0. The other scopes are numbered in the order their
+/// bodies are entered, in evaluation order: decorators, default values and the
+/// first iterable of a comprehension come before the body they belong to. A
+/// pass that walks the AST the same way can pair each `def`, `class`,
+/// `lambda` and comprehension with its scope by counting.
+#[derive(Debug, Clone)]
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+}
+
+impl SymbolTable {
+    /// Builds the symbol table of a program, returning the errors found while
+    /// resolving names alongside it.
+    pub fn build(program: &ASTNode) -> (SymbolTable, Vec<Diagnostic>) {
+        let mut collector = Collector::new();
+        collector.visit_node(program);
+        let Collector { scopes, facts, .. } = collector;
+        let mut resolver = Resolver { scopes, facts, diagnostics: Vec::new() };
+        resolver.resolve();
+        let Resolver { scopes, mut diagnostics, .. } = resolver;
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        (SymbolTable { scopes }, diagnostics)
+    }
+
+    pub fn root(&self) -> ScopeId {
+        0
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id]
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    /// The symbol `name` refers to in `scope`, if the name appears there.
+    pub fn lookup(&self, scope: ScopeId, name: &str) -> Option<&Symbol> {
+        self.scopes[scope].symbol(name)
+    }
+
+    /// The scope of the `def` or `class` statement named `name` at `span`.
+    pub fn definition_scope(&self, name: &str, span: Span) -> Option<ScopeId> {
+        self.scopes.iter().position(|scope| {
+            matches!(scope.kind, ScopeKind::Function | ScopeKind::Class) && scope.name == name && scope.span == span
+        })
+    }
+
+    /// The first function scope with the given name.
+    pub fn find_function(&self, name: &str) -> Option<ScopeId> {
+        self.scopes.iter().position(|scope| scope.kind == ScopeKind::Function && scope.name == name)
+    }
+}
+
+/// A binding or a use of a name, numbered in evaluation order.
+#[derive(Debug, Clone)]
+struct Occurrence {
+    name: String,
+    order: usize,
+    span: Span,
+}
+
+/// What the collector saw in one scope, before names are resolved.
+#[derive(Debug, Default, Clone)]
+struct ScopeFacts {
+    parameters: Vec<String>,
+    bindings: Vec<Occurrence>,
+    uses: Vec<Occurrence>,
+    globals: Vec<Occurrence>,
+    nonlocals: Vec<Occurrence>,
+    /// Names declared local by an annotation without a value (`x: int`).
+    annotated: Vec<String>,
+    /// Every name in the scope, in first-seen order.
+    names: Vec<String>,
+}
+
+impl ScopeFacts {
+    fn note_name(&mut self, name: &str) {
+        if !self.names.iter().any(|existing| existing == name) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn first(occurrences: &[Occurrence], name: &str) -> Option<usize> {
+        occurrences.iter().filter(|occurrence| occurrence.name == name).map(|occurrence| occurrence.order).min()
+    }
+
+    fn declares(occurrences: &[Occurrence], name: &str) -> bool {
+        occurrences.iter().any(|occurrence| occurrence.name == name)
+    }
+}
+
+/// First pass: builds the scope tree and records every binding, use and
+/// declaration in it.
+struct Collector {
+    scopes: Vec<Scope>,
+    facts: Vec<ScopeFacts>,
+    current: ScopeId,
+    order: usize,
+    span: Span,
+}
+
+impl Collector {
+    fn new() -> Self {
+        Collector {
+            scopes: vec![Scope::new(ScopeKind::Module, "<module>", None, Span::default())],
+            facts: vec![ScopeFacts::default()],
+            current: 0,
+            order: 0,
+            span: Span::default(),
+        }
+    }
+
+    fn occurrence(&mut self, name: &str) -> Occurrence {
+        self.order += 1;
+        Occurrence { name: name.to_string(), order: self.order, span: self.span }
+    }
+
+    fn bind(&mut self, name: &str) {
+        let occurrence = self.occurrence(name);
+        let facts = &mut self.facts[self.current];
+        facts.note_name(name);
+        facts.bindings.push(occurrence);
+    }
+
+    fn use_name(&mut self, name: &str) {
+        let occurrence = self.occurrence(name);
+        let facts = &mut self.facts[self.current];
+        facts.note_name(name);
+        facts.uses.push(occurrence);
+    }
+
+    /// Binds the names in an assignment target; attribute and subscript
+    /// targets only read their operands.
+    fn bind_target(&mut self, target: &ASTNode) {
+        match target {
+            ASTNode::Expression(ASTNodeType::Identifier(name)) => self.bind(name),
+            ASTNode::Tuple(elements) | ASTNode::List(elements) => {
+                for element in elements {
+                    self.bind_target(element);
+                }
+            }
+            ASTNode::Starred(value) => self.bind_target(value),
+            other => self.visit_node(other),
+        }
+    }
+
+    /// Runs `body` inside a new child scope of the current one.
+    fn with_scope(&mut self, kind: ScopeKind, name: &str, body: impl FnOnce(&mut Self)) {
+        let id = self.scopes.len();
+        self.scopes.push(Scope::new(kind, name, Some(self.current), self.span));
+        self.facts.push(ScopeFacts::default());
+        self.scopes[self.current].children.push(id);
+
+        let parent = self.current;
+        self.current = id;
+        body(self);
+        self.current = parent;
+    }
+
+    fn bind_parameters(&mut self, parameters: &[Parameter]) {
+        for parameter in parameters {
+            self.facts[self.current].parameters.push(parameter.name.clone());
+            self.bind(&parameter.name);
+        }
+    }
+
+}
+
+impl Visitor for Collector {
+    fn visit_node(&mut self, node: &ASTNode) {
+        match node.span() {
+            Some(span) => {
+                let outer = std::mem::replace(&mut self.span, span);
+                walk_node(self, node);
+                self.span = outer;
+            }
+            None => walk_node(self, node),
+        }
+    }
+
+    fn visit_variable_declaration(&mut self, name: &str, value: &ASTNode, _span: Span) {
+        self.visit_node(value);
+        self.bind(name);
+    }
+
+    fn visit_assignment(&mut self, targets: &[ASTNode], value: &ASTNode, _span: Span) {
+        self.visit_node(value);
+        for target in targets {
+            self.bind_target(target);
+        }
+    }
+
+    fn visit_augmented_assignment(&mut self, target: &ASTNode, _operator: &str, value: &ASTNode, _span: Span) {
+        self.visit_node(target);
+        self.visit_node(value);
+        if let ASTNode::Expression(ASTNodeType::Identifier(name)) = target {
+            self.bind(name);
+        }
+    }
+
+    fn visit_annotated_assignment(&mut self, target: &ASTNode, annotation: &ASTNode, value: Option<&ASTNode>, _span: Span) {
+        self.visit_node(annotation);
+        match (value, target) {
+            (Some(value), target) => {
+                self.visit_node(value);
+                self.bind_target(target);
+            }
+            (None, ASTNode::Expression(ASTNodeType::Identifier(name))) => {
+                let facts = &mut self.facts[self.current];
+                facts.note_name(name);
+                facts.annotated.push(name.clone());
+            }
+            (None, target) => self.visit_node(target),
+        }
+    }
+
+    fn visit_import(&mut self, names: &[ImportAlias], _span: Span) {
+        for name in names {
+            self.bind(name.bound_name());
+        }
+    }
+
+    fn visit_import_from(&mut self, _module: &str, names: &[ImportAlias], _span: Span) {
+        for name in names.iter().filter(|name| name.name != "*") {
+            self.bind(name.bound_name());
+        }
+    }
+
+    fn visit_with(&mut self, items: &[WithItem], body: &[ASTNode], _span: Span) {
+        for item in items {
+            self.visit_node(&item.context);
+            if let Some(target) = &item.target {
+                self.bind_target(target);
+            }
+        }
+        self.visit_block(body);
+    }
+
+    fn visit_for(&mut self, target: &ASTNode, iterable: &ASTNode, body: &[ASTNode], orelse: &[ASTNode], _span: Span) {
+        self.visit_node(iterable);
+        self.bind_target(target);
+        self.visit_block(body);
+        self.visit_block(orelse);
+    }
+
+    fn visit_except_handler(&mut self, handler: &ExceptHandler) {
+        let outer = std::mem::replace(&mut self.span, handler.span);
+        if let Some(exception_type) = &handler.exception_type {
+            self.visit_node(exception_type);
+        }
+        if let Some(name) = &handler.name {
+            self.bind(name);
+        }
+        self.span = outer;
+        self.visit_block(&handler.body);
+    }
+
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        for decorator in &function.decorators {
+            self.visit_node(decorator);
+        }
+        walk_parameters(self, &function.parameters);
+        if let Some(returns) = &function.returns {
+            self.visit_node(returns);
+        }
+        self.with_scope(ScopeKind::Function, &function.name, |collector| {
+            collector.bind_parameters(&function.parameters);
+            collector.visit_block(&function.body);
+        });
+        self.bind(&function.name);
+    }
+
+    fn visit_class_definition(&mut self, class: &ClassDefinition) {
+        for node in class.decorators.iter().chain(&class.bases) {
+            self.visit_node(node);
+        }
+        for keyword in &class.keywords {
+            self.visit_node(&keyword.value);
+        }
+        self.with_scope(ScopeKind::Class, &class.name, |collector| collector.visit_block(&class.body));
+        self.bind(&class.name);
+    }
+
+    fn visit_lambda(&mut self, parameters: &[Parameter], body: &ASTNode) {
+        walk_parameters(self, parameters);
+        self.with_scope(ScopeKind::Function, "<lambda>", |collector| {
+            collector.bind_parameters(parameters);
+            collector.visit_node(body);
+        });
+    }
+
+    /// The first iterable is evaluated in the enclosing scope; everything
+    /// else runs in the comprehension's own scope.
+    fn visit_comprehension(&mut self, kind: ComprehensionKind, element: &ASTNode, generators: &[ComprehensionGenerator]) {
+        let name = match kind {
+            ComprehensionKind::List => "<listcomp>",
+            ComprehensionKind::Set => "<setcomp>",
+            ComprehensionKind::Generator => "<genexpr>",
+        };
+        if let Some(first) = generators.first() {
+            self.visit_node(&first.iterable);
+        }
+        self.with_scope(ScopeKind::Comprehension, name, |collector| {
+            for (index, generator) in generators.iter().enumerate() {
+                if index > 0 {
+                    collector.visit_node(&generator.iterable);
+                }
+                collector.bind_target(&generator.target);
+                for condition in &generator.conditions {
+                    collector.visit_node(condition);
+                }
+            }
+            collector.visit_node(element);
+        });
+    }
+
+    fn visit_global(&mut self, names: &[String], _span: Span) {
+        for name in names {
+            let occurrence = self.occurrence(name);
+            let facts = &mut self.facts[self.current];
+            facts.note_name(name);
+            facts.globals.push(occurrence);
+        }
+    }
+
+    fn visit_nonlocal(&mut self, names: &[String], _span: Span) {
+        for name in names {
+            let occurrence = self.occurrence(name);
+            let facts = &mut self.facts[self.current];
+            facts.note_name(name);
+            facts.nonlocals.push(occurrence);
+        }
+    }
+
+    fn visit_identifier(&mut self, name: &str) {
+        self.use_name(name);
+    }
+}
+
+/// Second pass: resolves every name of every scope, parents before children.
+struct Resolver {
+    scopes: Vec<Scope>,
+    facts: Vec<ScopeFacts>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolver {
+    fn resolve(&mut self) {
+        // Scopes are numbered in pre-order, so enclosing scopes are always
+        // resolved before the scopes nested in them.
+        for id in 0..self.scopes.len() {
+            self.check_declarations(id);
+            let names = self.facts[id].names.clone();
+            for name in &names {
+                self.resolve_name(id, name);
+            }
+        }
+        for id in 0..self.scopes.len() {
+            self.assign_slots(id);
+        }
+    }
+
+    fn error(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic::error(message, span));
+    }
+
+    fn check_declarations(&mut self, id: ScopeId) {
+        let facts = self.facts[id].clone();
+        for (keyword, declarations) in [("global", &facts.globals), ("nonlocal", &facts.nonlocals)] {
+            for declaration in declarations {
+                let name = &declaration.name;
+                if keyword == "nonlocal" && self.scopes[id].kind == ScopeKind::Module {
+                    self.error("nonlocal declaration not allowed at module level".to_string(), declaration.span);
+                } else if facts.parameters.contains(name) {
+                    self.error(format!("name '{}' is parameter and {}", name, keyword), declaration.span);
+                } else if ScopeFacts::first(&facts.uses, name).is_some_and(|order| order < declaration.order) {
+                    self.error(format!("name '{}' is used prior to {} declaration", name, keyword), declaration.span);
+                } else if ScopeFacts::first(&facts.bindings, name).is_some_and(|order| order < declaration.order) {
+                    self.error(format!("name '{}' is assigned to before {} declaration", name, keyword), declaration.span);
+                }
+            }
+        }
+        for declaration in &facts.nonlocals {
+            if ScopeFacts::declares(&facts.globals, &declaration.name) {
+                self.error(format!("name '{}' is nonlocal and global", declaration.name), declaration.span);
+            }
+        }
+    }
+
+    fn resolve_name(&mut self, id: ScopeId, name: &str) {
+        let facts = &self.facts[id];
+        let is_parameter = facts.parameters.iter().any(|parameter| parameter == name);
+        let is_assigned = ScopeFacts::declares(&facts.bindings, name) || facts.annotated.iter().any(|annotated| annotated == name);
+        let is_used = ScopeFacts::declares(&facts.uses, name);
+        let nonlocal = facts.nonlocals.iter().find(|occurrence| occurrence.name == name).cloned();
+
+        let kind = if ScopeFacts::declares(&facts.globals, name) {
+            SymbolKind::Global
+        } else if let Some(declaration) = nonlocal.filter(|_| self.scopes[id].kind != ScopeKind::Module) {
+            if !self.capture(id, name) {
+                self.error(format!("no binding for nonlocal '{}' found", name), declaration.span);
+            }
+            SymbolKind::Free
+        } else if is_assigned {
+            match self.scopes[id].kind {
+                ScopeKind::Module => SymbolKind::Global,
+                _ => SymbolKind::Local,
+            }
+        } else if self.scopes[id].kind != ScopeKind::Module && self.capture(id, name) {
+            SymbolKind::Free
+        } else {
+            self.global_or_builtin(id, name)
+        };
+
+        self.scopes[id].symbols.push(Symbol {
+            name: name.to_string(),
+            kind,
+            slot: None,
+            is_parameter,
+            is_assigned,
+            is_used,
+        });
+    }
+
+    /// Looks for `name` in the functions enclosing `id`, skipping class
+    /// scopes. When found, the defining local becomes a cell and every
+    /// function in between passes it through as a free variable.
+    fn capture(&mut self, id: ScopeId, name: &str) -> bool {
+        let mut path = Vec::new();
+        let mut current = self.scopes[id].parent;
+        while let Some(scope) = current {
+            let enclosing = &self.scopes[scope];
+            if enclosing.kind == ScopeKind::Module {
+                return false;
+            }
+            if enclosing.has_fast_locals() {
+                match enclosing.symbol(name).map(|symbol| symbol.kind) {
+                    Some(SymbolKind::Local) | Some(SymbolKind::Cell) | Some(SymbolKind::Free) => {
+                        if let Some(symbol) = self.scopes[scope].symbol_mut(name) {
+                            if symbol.kind == SymbolKind::Local {
+                                symbol.kind = SymbolKind::Cell;
+                            }
+                        }
+                        for intermediate in path {
+                            self.pass_through(intermediate, name);
+                        }
+                        return true;
+                    }
+                    _ => path.push(scope),
+                }
+            }
+            current = enclosing.parent;
+        }
+        false
+    }
+
+    fn pass_through(&mut self, id: ScopeId, name: &str) {
+        let scope = &mut self.scopes[id];
+        match scope.symbol_mut(name) {
+            Some(symbol) => symbol.kind = SymbolKind::Free,
+            None => scope.symbols.push(Symbol {
+                name: name.to_string(),
+                kind: SymbolKind::Free,
+                slot: None,
+                is_parameter: false,
+                is_assigned: false,
+                is_used: false,
+            }),
+        }
+    }
+
+    fn global_or_builtin(&mut self, id: ScopeId, name: &str) -> SymbolKind {
+        let bound_globally = ScopeFacts::declares(&self.facts[0].bindings, name)
+            || self.facts.iter().any(|facts| {
+                ScopeFacts::declares(&facts.globals, name) && ScopeFacts::declares(&facts.bindings, name)
+            });
+        if bound_globally {
+            return SymbolKind::Global;
+        }
+        if is_builtin(name) {
+            return SymbolKind::Builtin;
+        }
+        let first_use = self.facts[id].uses.iter().find(|occurrence| occurrence.name == name);
+        if let Some(span) = first_use.map(|occurrence| occurrence.span) {
+            self.error(format!("name '{}' is not defined", name), span);
+        }
+        SymbolKind::Global
+    }
+
+    fn assign_slots(&mut self, id: ScopeId) {
+        let scope = &mut self.scopes[id];
+        if !scope.has_fast_locals() {
+            return;
+        }
+        let (mut local, mut closure) = (0, 0);
+        for symbol in scope.symbols.iter_mut().filter(|symbol| symbol.kind == SymbolKind::Local) {
+            symbol.slot = Some(local);
+            local += 1;
+        }
+        for kind in [SymbolKind::Cell, SymbolKind::Free] {
+            for symbol in scope.symbols.iter_mut().filter(|symbol| symbol.kind == kind) {
+                symbol.slot = Some(closure);
+                closure += 1;
+            }
+        }
+    }
+}
@@ -1,14 +1,81 @@
+use crate::hir::hir::Module;
+use crate::hir::lower::Lowerer;
 use crate::parser::parser::Parser;
+use crate::semantic::checker::{TypeChecker, TypeInfo};
+use crate::semantic::diagnostic::{Diagnostic, Severity};
+use crate::semantic::flow::FlowAnalyzer;
+use crate::semantic::lint::{LintConfig, Linter};
+use crate::semantic::scope::SymbolTable;
 
-pub struct SemanticAnalyzer;
+pub struct SemanticAnalyzer {
+    symbol_table: Option<SymbolTable>,
+    type_info: Option<TypeInfo>,
+    hir: Option<Module>,
+    diagnostics: Vec<Diagnostic>,
+    lint_config: LintConfig,
+}
+
+impl Default for SemanticAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl SemanticAnalyzer {
     pub fn new() -> Self {
-        SemanticAnalyzer
+        Self::with_lint_config(LintConfig::default())
     }
 
+    pub fn with_lint_config(lint_config: LintConfig) -> Self {
+        SemanticAnalyzer { symbol_table: None, type_info: None, hir: None, diagnostics: Vec::new(), lint_config }
+    }
+
+    /// Builds the symbol table of the parsed program, type checks it and
+    /// checks that every variable is assigned before it is read, then runs
+    /// the lint rules at their configured levels. Fails with every error
+    /// found, one `line:column: error: message` per line; warnings are
+    /// kept in `diagnostics` for the caller to report. A program without
+    /// errors is then lowered to HIR for IR generation.
     pub fn analyze(&mut self, parser: &Parser) -> Result<(), String> {
-        println!("Semantic analysis completed.");
+        let program = parser.get_ast();
+        let (symbol_table, mut diagnostics) = SymbolTable::build(&program);
+        let (type_info, type_diagnostics) = TypeChecker::check(&program, &symbol_table);
+        diagnostics.extend(type_diagnostics);
+        diagnostics.extend(FlowAnalyzer::analyze(&program, &symbol_table));
+        diagnostics.extend(Linter::lint(&program, &symbol_table, parser.source()));
+        let mut diagnostics = self.lint_config.apply(parser.source(), diagnostics);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+        let errors: Vec<String> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.render(parser.source()))
+            .collect();
+        self.hir = errors.is_empty().then(|| Lowerer::lower(&program, &symbol_table, &type_info));
+        self.symbol_table = Some(symbol_table);
+        self.type_info = Some(type_info);
+        self.diagnostics = diagnostics;
+        if !errors.is_empty() {
+            return Err(errors.join("\n"));
+        }
         Ok(())
     }
+
+    /// The symbol table built by the last call to `analyze`.
+    pub fn symbol_table(&self) -> Option<&SymbolTable> {
+        self.symbol_table.as_ref()
+    }
+
+    /// The types inferred by the last call to `analyze`.
+    pub fn type_info(&self) -> Option<&TypeInfo> {
+        self.type_info.as_ref()
+    }
+
+    /// The HIR lowered by the last call to `analyze`, if it found no errors.
+    pub fn hir(&self) -> Option<&Module> {
+        self.hir.as_ref()
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 }
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parser::parser::ParameterKind;
+
+/// A static type, following PEP 484. `Any` is compatible with every type in
+/// both directions, which is what makes the checker gradual.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    /// The empty type: the value of an expression that never completes, or
+    /// what is left after narrowing excludes every possibility.
+    Never,
+    None,
+    Bool,
+    Int,
+    Float,
+    Str,
+    List(Box<Type>),
+    Set(Box<Type>),
+    Dict(Box<Type>, Box<Type>),
+    Tuple(Vec<Type>),
+    /// Always flattened and deduplicated; build it with `Type::union`.
+    Union(Vec<Type>),
+    Callable(Box<Signature>),
+    TypeVar(String),
+    /// An instance of a class, protocol or `TypedDict`, by class name.
+    Instance(String),
+    /// A class object, `type[T]`. Calling it constructs a `T`.
+    ClassObject(Box<Type>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// `None` for `Callable[..., R]`, which accepts any arguments.
+    pub parameters: Option<Vec<SignatureParameter>>,
+    pub returns: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureParameter {
+    /// `None` for the parameters of a `Callable[[...], R]` annotation.
+    pub name: Option<String>,
+    pub kind: ParameterKind,
+    pub annotation: Type,
+    pub has_default: bool,
+}
+
+impl Signature {
+    /// The signature of `Callable[[parameters], returns]`.
+    pub fn positional(parameters: Vec<Type>, returns: Type) -> Self {
+        let parameters = parameters
+            .into_iter()
+            .map(|annotation| SignatureParameter {
+                name: None,
+                kind: ParameterKind::PositionalOnly,
+                annotation,
+                has_default: false,
+            })
+            .collect();
+        Signature { parameters: Some(parameters), returns }
+    }
+
+    /// The signature of `Callable[..., returns]`.
+    pub fn any_arguments(returns: Type) -> Self {
+        Signature { parameters: None, returns }
+    }
+
+    /// The signature with its first parameter (`self` or `cls`) removed, as
+    /// seen through an instance.
+    pub fn bound(&self) -> Signature {
+        let parameters = self.parameters.as_ref().map(|parameters| {
+            let mut parameters = parameters.clone();
+            if parameters.first().is_some_and(|first| {
+                matches!(first.kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword)
+            }) {
+                parameters.remove(0);
+            }
+            parameters
+        });
+        Signature { parameters, returns: self.returns.clone() }
+    }
+
+    fn positional_parameters(&self) -> impl Iterator<Item = &SignatureParameter> {
+        self.parameters.iter().flatten().filter(|parameter| {
+            matches!(parameter.kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword)
+        })
+    }
+}
+
+impl Type {
+    /// Builds a union, flattening nested unions and dropping duplicates and
+    /// `Never`. A union containing `Any` is `Any`.
+    pub fn union(types: impl IntoIterator<Item = Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        for ty in types {
+            let flattened = match ty {
+                Type::Union(inner) => inner,
+                Type::Never => Vec::new(),
+                other => vec![other],
+            };
+            for member in flattened {
+                if member == Type::Any {
+                    return Type::Any;
+                }
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+        }
+        match members.len() {
+            0 => Type::Never,
+            1 => members.pop().expect("one member"),
+            _ => Type::Union(members),
+        }
+    }
+
+    pub fn optional(ty: Type) -> Type {
+        Type::union([ty, Type::None])
+    }
+
+    pub fn members(&self) -> Vec<Type> {
+        match self {
+            Type::Union(members) => members.clone(),
+            Type::Never => Vec::new(),
+            other => vec![other.clone()],
+        }
+    }
+
+    pub fn contains_none(&self) -> bool {
+        self.members().contains(&Type::None)
+    }
+
+    pub fn without_none(&self) -> Type {
+        Type::union(self.members().into_iter().filter(|member| *member != Type::None))
+    }
+
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Type::Bool | Type::Int | Type::Float)
+    }
+
+    /// The type produced by iterating over a value of this type, if known.
+    pub fn element_type(&self) -> Option<Type> {
+        match self {
+            Type::Any => Some(Type::Any),
+            Type::List(element) | Type::Set(element) | Type::Dict(element, _) => Some(element.as_ref().clone()),
+            Type::Tuple(elements) => Some(Type::union(elements.clone())),
+            Type::Str => Some(Type::Str),
+            Type::Instance(name) if name == "range" => Some(Type::Int),
+            Type::Union(members) => {
+                let elements: Option<Vec<Type>> = members.iter().map(Type::element_type).collect();
+                elements.map(Type::union)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the type mentions a type variable.
+    pub fn is_generic(&self) -> bool {
+        match self {
+            Type::TypeVar(_) => true,
+            Type::List(element) | Type::Set(element) | Type::ClassObject(element) => element.is_generic(),
+            Type::Dict(key, value) => key.is_generic() || value.is_generic(),
+            Type::Tuple(elements) | Type::Union(elements) => elements.iter().any(Type::is_generic),
+            Type::Callable(signature) => {
+                signature.returns.is_generic()
+                    || signature.parameters.iter().flatten().any(|parameter| parameter.annotation.is_generic())
+            }
+            _ => false,
+        }
+    }
+
+    /// Replaces type variables by their bindings, and unbound ones by `Any`.
+    pub fn substitute(&self, bindings: &HashMap<String, Type>) -> Type {
+        match self {
+            Type::TypeVar(name) => bindings.get(name).cloned().unwrap_or(Type::Any),
+            Type::List(element) => Type::List(Box::new(element.substitute(bindings))),
+            Type::Set(element) => Type::Set(Box::new(element.substitute(bindings))),
+            Type::Dict(key, value) => Type::Dict(Box::new(key.substitute(bindings)), Box::new(value.substitute(bindings))),
+            Type::Tuple(elements) => Type::Tuple(elements.iter().map(|element| element.substitute(bindings)).collect()),
+            Type::Union(members) => Type::union(members.iter().map(|member| member.substitute(bindings))),
+            Type::Callable(signature) => Type::Callable(Box::new(Signature {
+                parameters: signature.parameters.as_ref().map(|parameters| {
+                    parameters
+                        .iter()
+                        .map(|parameter| SignatureParameter {
+                            annotation: parameter.annotation.substitute(bindings),
+                            ..parameter.clone()
+                        })
+                        .collect()
+                }),
+                returns: signature.returns.substitute(bindings),
+            })),
+            Type::ClassObject(instance) => Type::ClassObject(Box::new(instance.substitute(bindings))),
+            other => other.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "Any"),
+            Type::Never => write!(f, "NoReturn"),
+            Type::None => write!(f, "None"),
+            Type::Bool => write!(f, "bool"),
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Str => write!(f, "str"),
+            Type::List(element) => write!(f, "list[{}]", element),
+            Type::Set(element) => write!(f, "set[{}]", element),
+            Type::Dict(key, value) => write!(f, "dict[{}, {}]", key, value),
+            Type::Tuple(elements) if elements.is_empty() => write!(f, "tuple[()]"),
+            Type::Tuple(elements) => write!(f, "tuple[{}]", join(elements)),
+            Type::Union(members) => {
+                let members: Vec<String> = members.iter().map(ToString::to_string).collect();
+                write!(f, "{}", members.join(" | "))
+            }
+            Type::Callable(signature) => match &signature.parameters {
+                None => write!(f, "Callable[..., {}]", signature.returns),
+                Some(parameters) => {
+                    let parameters: Vec<Type> = parameters.iter().map(|parameter| parameter.annotation.clone()).collect();
+                    write!(f, "Callable[[{}], {}]", join(&parameters), signature.returns)
+                }
+            },
+            Type::TypeVar(name) | Type::Instance(name) => write!(f, "{}", name),
+            Type::ClassObject(instance) => write!(f, "type[{}]", instance),
+        }
+    }
+}
+
+fn join(types: &[Type]) -> String {
+    types.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClassKind {
+    Regular,
+    /// A `typing.Protocol`, matched structurally.
+    Protocol,
+    /// A `typing.TypedDict`, whose fields are dictionary keys.
+    TypedDict,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    pub name: String,
+    pub kind: ClassKind,
+    pub bases: Vec<String>,
+    /// Whether a base class is unknown (for example imported), in which case
+    /// any attribute may exist.
+    pub has_unknown_base: bool,
+    /// Annotated class attributes, `TypedDict` keys and attributes assigned
+    /// through `self` in methods.
+    pub fields: Vec<(String, Type)>,
+    /// Method signatures, including `self`.
+    pub methods: Vec<(String, Signature)>,
+}
+
+impl ClassInfo {
+    pub fn new(name: &str, kind: ClassKind) -> Self {
+        ClassInfo {
+            name: name.to_string(),
+            kind,
+            bases: Vec::new(),
+            has_unknown_base: false,
+            fields: Vec::new(),
+            methods: Vec::new(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Type> {
+        self.fields.iter().find(|(field, _)| field == name).map(|(_, ty)| ty)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&Signature> {
+        self.methods.iter().find(|(method, _)| method == name).map(|(_, signature)| signature)
+    }
+}
+
+/// The classes of a program, and the subtyping rules that need them.
+#[derive(Debug, Clone, Default)]
+pub struct TypeContext {
+    classes: HashMap<String, ClassInfo>,
+}
+
+impl TypeContext {
+    pub fn class(&self, name: &str) -> Option<&ClassInfo> {
+        self.classes.get(name)
+    }
+
+    pub fn class_mut(&mut self, name: &str) -> Option<&mut ClassInfo> {
+        self.classes.get_mut(name)
+    }
+
+    pub fn add_class(&mut self, class: ClassInfo) {
+        self.classes.insert(class.name.clone(), class);
+    }
+
+    /// Class `name` and the classes it inherits from, depth first. Each
+    /// comes once, so that the bases of redefined classes, which may form a
+    /// cycle, are walked to the end.
+    pub fn ancestors(&self, name: &str) -> Vec<String> {
+        let mut ancestors = vec![name.to_string()];
+        let mut index = 0;
+        while index < ancestors.len() {
+            let bases = self.class(&ancestors[index]).map(|class| class.bases.clone()).unwrap_or_default();
+            index += 1;
+            let new: Vec<String> = bases.into_iter().filter(|base| !ancestors.contains(base)).collect();
+            ancestors.splice(index..index, new);
+        }
+        ancestors
+    }
+
+    /// Whether class `name` is `ancestor` or inherits from it.
+    pub fn is_subclass(&self, name: &str, ancestor: &str) -> bool {
+        self.ancestors(name).iter().any(|class| class == ancestor)
+    }
+
+    /// Looks up an attribute on an instance of class `name`, searching base
+    /// classes. Methods are returned bound, without `self`.
+    pub fn member(&self, name: &str, attribute: &str) -> Option<Type> {
+        self.ancestors(name).iter().filter_map(|class| self.class(class)).find_map(|class| {
+            if let Some(field) = class.field(attribute) {
+                return Some(field.substitute(&HashMap::new()));
+            }
+            class.method(attribute).map(|method| Type::Callable(Box::new(method.bound())).substitute(&HashMap::new()))
+        })
+    }
+
+    /// Whether instances of class `name` may have attributes nobody declared.
+    pub fn is_open(&self, name: &str) -> bool {
+        self.ancestors(name).iter().any(|class| self.class(class).is_none_or(|class| class.has_unknown_base))
+    }
+
+    /// Whether a value of type `source` can be used where `target` is
+    /// expected.
+    pub fn is_assignable(&self, source: &Type, target: &Type) -> bool {
+        match (source, target) {
+            (Type::Any, _) | (_, Type::Any) | (Type::Never, _) => true,
+            (Type::Union(members), _) => members.iter().all(|member| self.is_assignable(member, target)),
+            (_, Type::Union(members)) => members.iter().any(|member| self.is_assignable(source, member)),
+            (_, Type::Instance(name)) if name == "object" => true,
+            (Type::Bool, Type::Int | Type::Float) | (Type::Int, Type::Float) => true,
+            (Type::List(source), Type::List(target)) | (Type::Set(source), Type::Set(target)) => {
+                self.is_equivalent(source, target)
+            }
+            (Type::Dict(source_key, source_value), Type::Dict(target_key, target_value)) => {
+                self.is_equivalent(source_key, target_key) && self.is_equivalent(source_value, target_value)
+            }
+            (Type::Tuple(source), Type::Tuple(target)) => {
+                source.len() == target.len()
+                    && source.iter().zip(target).all(|(source, target)| self.is_assignable(source, target))
+            }
+            (Type::Callable(source), Type::Callable(target)) => self.is_signature_assignable(source, target),
+            (Type::ClassObject(source), Type::ClassObject(target)) => self.is_assignable(source, target),
+            (Type::ClassObject(instance), Type::Callable(target)) => {
+                target.parameters.is_none() && self.is_assignable(instance, &target.returns)
+            }
+            (Type::Instance(source), Type::Instance(target)) => {
+                self.is_subclass(source, target) || self.implements_protocol(source, target)
+            }
+            (source, target) => source == target,
+        }
+    }
+
+    /// Invariant compatibility, used for the element types of mutable
+    /// containers.
+    pub fn is_equivalent(&self, a: &Type, b: &Type) -> bool {
+        a == b || (self.is_assignable(a, b) && self.is_assignable(b, a))
+    }
+
+    fn is_signature_assignable(&self, source: &Signature, target: &Signature) -> bool {
+        if !self.is_assignable(&source.returns, &target.returns) {
+            return false;
+        }
+        let (Some(source_parameters), Some(target_parameters)) = (&source.parameters, &target.parameters) else {
+            return true;
+        };
+        let accepts_extra = source_parameters.iter().any(|parameter| parameter.kind == ParameterKind::VarPositional);
+        let source_positional: Vec<_> = source.positional_parameters().collect();
+        let target_positional: Vec<_> = target.positional_parameters().collect();
+        if target_positional.len() > source_positional.len() && !accepts_extra {
+            return false;
+        }
+        let compatible = target_positional
+            .iter()
+            .zip(&source_positional)
+            .all(|(target, source)| self.is_assignable(&target.annotation, &source.annotation));
+        let extra_have_defaults = source_positional.iter().skip(target_positional.len()).all(|parameter| parameter.has_default);
+        let required_keywords_given = source_parameters
+            .iter()
+            .filter(|parameter| parameter.kind == ParameterKind::KeywordOnly && !parameter.has_default)
+            .all(|parameter| target_parameters.iter().any(|other| other.name == parameter.name));
+        compatible && extra_have_defaults && required_keywords_given
+    }
+
+    /// Structural check of class `name` against protocol `protocol`.
+    fn implements_protocol(&self, name: &str, protocol: &str) -> bool {
+        let Some(protocol_info) = self.class(protocol).filter(|class| class.kind == ClassKind::Protocol) else {
+            return false;
+        };
+        let methods_match = protocol_info.methods.iter().all(|(method, signature)| {
+            self.member(name, method).is_some_and(|member| {
+                self.is_assignable(&member, &Type::Callable(Box::new(signature.bound())))
+            })
+        });
+        let fields_match = protocol_info
+            .fields
+            .iter()
+            .all(|(field, ty)| self.member(name, field).is_some_and(|member| self.is_equivalent(&member, ty)));
+        methods_match && fields_match
+    }
+
+    /// Narrows `ty` to the members that are instances of `target`, as after a
+    /// successful `isinstance` check.
+    pub fn narrow_to(&self, ty: &Type, target: &Type) -> Type {
+        if *ty == Type::Any {
+            return target.clone();
+        }
+        let kept: Vec<Type> = ty
+            .members()
+            .into_iter()
+            .filter_map(|member| {
+                if self.is_assignable(&member, target) {
+                    Some(member)
+                } else if self.is_assignable(target, &member) {
+                    Some(target.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        Type::union(kept)
+    }
+
+    /// Removes the members of `ty` that are instances of `target`, as after a
+    /// failed `isinstance` check.
+    pub fn exclude(&self, ty: &Type, target: &Type) -> Type {
+        if *ty == Type::Any || *target == Type::Any {
+            return ty.clone();
+        }
+        Type::union(ty.members().into_iter().filter(|member| !self.is_assignable(member, target)))
+    }
+
+    /// Binds the type variables of `parameter` by matching it against
+    /// `argument`.
+    pub fn infer_type_variables(&self, parameter: &Type, argument: &Type, bindings: &mut HashMap<String, Type>) {
+        match (parameter, argument) {
+            (Type::TypeVar(name), argument) => {
+                let bound = match bindings.remove(name) {
+                    Some(existing) if self.is_assignable(argument, &existing) => existing,
+                    Some(existing) if self.is_assignable(&existing, argument) => argument.clone(),
+                    Some(existing) => Type::union([existing, argument.clone()]),
+                    None => argument.clone(),
+                };
+                bindings.insert(name.clone(), bound);
+            }
+            (Type::List(parameter), Type::List(argument)) | (Type::Set(parameter), Type::Set(argument)) => {
+                self.infer_type_variables(parameter, argument, bindings)
+            }
+            (Type::Dict(parameter_key, parameter_value), Type::Dict(argument_key, argument_value)) => {
+                self.infer_type_variables(parameter_key, argument_key, bindings);
+                self.infer_type_variables(parameter_value, argument_value, bindings);
+            }
+            (Type::Tuple(parameters), Type::Tuple(arguments)) if parameters.len() == arguments.len() => {
+                for (parameter, argument) in parameters.iter().zip(arguments) {
+                    self.infer_type_variables(parameter, argument, bindings);
+                }
+            }
+            (Type::Union(members), argument) => {
+                // `Optional[T]` binds `T` to the argument without its `None`.
+                let variables: Vec<&Type> = members.iter().filter(|member| matches!(member, Type::TypeVar(_))).collect();
+                if let [variable] = variables.as_slice() {
+                    let rest = Type::union(members.iter().filter(|member| !matches!(member, Type::TypeVar(_))).cloned());
+                    let remaining = Type::union(argument.members().into_iter().filter(|member| !self.is_assignable(member, &rest)));
+                    if remaining != Type::Never {
+                        self.infer_type_variables(variable, &remaining, bindings);
+                    }
+                }
+            }
+            (Type::Callable(parameter), Type::Callable(argument)) => {
+                if let (Some(parameters), Some(arguments)) = (&parameter.parameters, &argument.parameters) {
+                    for (parameter, argument) in parameters.iter().zip(arguments) {
+                        self.infer_type_variables(&parameter.annotation, &argument.annotation, bindings);
+                    }
+                }
+                self.infer_type_variables(&parameter.returns, &argument.returns, bindings);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod analysis_tests {
+    use std::collections::BTreeSet;
+    use std::rc::Rc;
+
+    use pybolt::analysis::analysis::AnalysisManager;
+    use pybolt::analysis::dominators::{DominanceFrontiers, DominatorTree, PostDominatorTree};
+    use pybolt::analysis::liveness::Liveness;
+    use pybolt::analysis::loops::LoopNest;
+    use pybolt::analysis::ranges::{Interval, ValueRanges, INFINITY, NEG_INFINITY};
+    use pybolt::ir::ir::{Block, CompareOp, Function, Value};
+    use pybolt::ir::parser;
+
+    /// Blocks are numbered in the order they are written, and so are values:
+    /// %n is 0, %zero 1, %i 2, %total 3, and so on.
+    const NESTED: &str = "fn @nested(i64) -> i64 {\n\
+                          entry(%n: i64):\n\
+                          \x20   %zero: i64 = const 0\n\
+                          \x20   br outer(%zero, %zero)\n\
+                          outer(%i: i64, %total: i64):\n\
+                          \x20   %more: bool = lt %i, %n\n\
+                          \x20   cond_br %more, inner_pre, done\n\
+                          inner_pre:\n\
+                          \x20   br inner(%zero, %total)\n\
+                          inner(%j: i64, %sum: i64):\n\
+                          \x20   %inner_more: bool = lt %j, %i\n\
+                          \x20   cond_br %inner_more, inner_body, outer_latch\n\
+                          inner_body:\n\
+                          \x20   %next_sum: i64 = add %sum, %j\n\
+                          \x20   %one: i64 = const 1\n\
+                          \x20   %next_j: i64 = add %j, %one\n\
+                          \x20   br inner(%next_j, %next_sum)\n\
+                          outer_latch:\n\
+                          \x20   %step: i64 = const 1\n\
+                          \x20   %next_i: i64 = add %i, %step\n\
+                          \x20   br outer(%next_i, %sum)\n\
+                          done:\n\
+                          \x20   ret %total\n\
+                          }\n";
+
+    fn nested() -> Function {
+        parser::parse(NESTED).unwrap().functions.remove(0)
+    }
+
+    fn blocks(numbers: &[u32]) -> Vec<Block> {
+        numbers.iter().map(|number| Block(*number)).collect()
+    }
+
+    fn values(numbers: &[u32]) -> BTreeSet<Value> {
+        numbers.iter().map(|number| Value(*number)).collect()
+    }
+
+    #[test]
+    fn test_dominator_tree() {
+        let function = nested();
+        let dominators = DominatorTree::compute(&function);
+        let idoms: Vec<Option<Block>> = function.layout.iter().map(|block| dominators.idom(*block)).collect();
+        assert_eq!(idoms, vec![None, Some(Block(0)), Some(Block(1)), Some(Block(2)), Some(Block(3)), Some(Block(3)), Some(Block(1))]);
+        assert_eq!(dominators.children(Block(1)), blocks(&[2, 6]));
+        assert_eq!(dominators.children(Block(3)), blocks(&[4, 5]));
+        assert!(dominators.dominates(Block(2), Block(5)));
+        assert!(dominators.dominates(Block(5), Block(5)));
+        assert!(!dominators.strictly_dominates(Block(5), Block(5)));
+        assert!(!dominators.dominates(Block(4), Block(5)));
+        assert_eq!(dominators.reverse_postorder()[..2], blocks(&[0, 1]));
+    }
+
+    #[test]
+    fn test_post_dominator_tree() {
+        let function = nested();
+        let post_dominators = PostDominatorTree::compute(&function);
+        let ipdoms: Vec<Option<Block>> = function.layout.iter().map(|block| post_dominators.ipdom(*block)).collect();
+        assert_eq!(ipdoms, vec![Some(Block(1)), Some(Block(6)), Some(Block(3)), Some(Block(5)), Some(Block(3)), Some(Block(1)), None]);
+        assert!(post_dominators.post_dominates(Block(6), Block(0)));
+        assert!(!post_dominators.post_dominates(Block(4), Block(3)));
+
+        let function = parser::parse("fn @spin() -> none {\nbb0:\n    br bb1\nbb1:\n    br bb1\n}\n").unwrap().functions.remove(0);
+        let post_dominators = PostDominatorTree::compute(&function);
+        assert!(!post_dominators.reaches_exit(Block(1)));
+        assert_eq!(post_dominators.ipdom(Block(0)), None);
+    }
+
+    #[test]
+    fn test_dominance_frontiers() {
+        let function = nested();
+        let frontiers = DominanceFrontiers::compute(&function, &DominatorTree::compute(&function));
+        let all: Vec<&[Block]> = function.layout.iter().map(|block| frontiers.frontier(*block)).collect();
+        let expected = [blocks(&[]), blocks(&[1]), blocks(&[1]), blocks(&[1, 3]), blocks(&[3]), blocks(&[1]), blocks(&[])];
+        assert_eq!(all, expected.iter().map(Vec::as_slice).collect::<Vec<_>>());
+        assert_eq!(frontiers.iterated(&blocks(&[4])), [Block(1), Block(3)].into());
+    }
+
+    #[test]
+    fn test_loop_nest() {
+        let function = nested();
+        let loops = LoopNest::compute(&function, &DominatorTree::compute(&function));
+        let top_level = loops.top_level();
+        assert_eq!(top_level.len(), 1);
+        let outer = loops.loop_data(top_level[0]);
+        assert_eq!(outer.header, Block(1));
+        assert_eq!(outer.blocks, blocks(&[1, 2, 3, 4, 5]));
+        assert_eq!(outer.latches, blocks(&[5]));
+        assert_eq!(outer.depth, 1);
+        let inner = loops.loop_data(outer.children[0]);
+        assert_eq!((inner.header, inner.blocks.clone(), inner.depth), (Block(3), blocks(&[3, 4]), 2));
+        assert_eq!(inner.parent, Some(top_level[0]));
+
+        let depths: Vec<u32> = function.layout.iter().map(|block| loops.depth(*block)).collect();
+        assert_eq!(depths, vec![0, 1, 1, 2, 2, 1, 0]);
+        assert!(loops.is_header(Block(3)) && !loops.is_header(Block(4)));
+        assert_eq!(loops.exits(&function, top_level[0]), vec![(Block(1), Block(6))]);
+        assert_eq!(loops.exits(&function, outer.children[0]), vec![(Block(3), Block(5))]);
+        assert_eq!(loops.preheader(&function, top_level[0]), Some(Block(0)));
+        assert_eq!(loops.preheader(&function, outer.children[0]), Some(Block(2)));
+    }
+
+    #[test]
+    fn test_liveness() {
+        let function = nested();
+        let liveness = Liveness::compute(&function);
+        // Block parameters are defined on entry, so %i and %total are not
+        // live into `outer`, but %n and %zero are used around the loop.
+        assert_eq!(liveness.live_in(Block(1)), &values(&[0, 1]));
+        assert_eq!(liveness.live_out(Block(1)), &values(&[0, 1, 2, 3]));
+        assert_eq!(liveness.live_in(Block(3)), &values(&[0, 1, 2]));
+        assert_eq!(liveness.live_out(Block(4)), &values(&[0, 1, 2]));
+        assert_eq!(liveness.live_in(Block(6)), &values(&[3]));
+        assert!(liveness.live_out(Block(6)).is_empty());
+        assert!(liveness.is_live_out(Value(0), Block(5)));
+        assert!(!liveness.is_live_out(Value(6), Block(5)));
+    }
+
+    #[test]
+    fn test_value_ranges() {
+        // %x is 0, %i 4, %square 6, %next 7 and %y 10.
+        let source = "fn @ranges(int) -> int {\n\
+                      entry(%x: int):\n\
+                      \x20   %zero: i64 = const 0\n\
+                      \x20   %hundred: i64 = const 100\n\
+                      \x20   %one: i64 = const 1\n\
+                      \x20   br header(%zero)\n\
+                      header(%i: i64):\n\
+                      \x20   %more: bool = lt %i, %hundred\n\
+                      \x20   cond_br %more, body, done\n\
+                      body:\n\
+                      \x20   %square: i64 = mul %i, %i\n\
+                      \x20   %next: i64 = add %i, %one\n\
+                      \x20   br header(%next)\n\
+                      done:\n\
+                      \x20   %ten: int = const 10\n\
+                      \x20   %small: bool = le %x, %ten\n\
+                      \x20   cond_br %small, low, high\n\
+                      low:\n\
+                      \x20   %y: int = mul %x, %ten\n\
+                      \x20   ret %y\n\
+                      high:\n\
+                      \x20   ret %x\n\
+                      }\n";
+        let function = parser::parse(source).unwrap().functions.remove(0);
+        let ranges = ValueRanges::compute(&function, &DominatorTree::compute(&function));
+        // The counter is widened around the loop, then narrowed back by the
+        // comparison of the header, and is below 100 in the body.
+        assert_eq!(ranges.interval(Value(4)), Some(Interval::new(0, 100)));
+        assert_eq!(ranges.interval_in(Value(4), Block(2)), Some(Interval::new(0, 99)));
+        assert_eq!(ranges.interval(Value(6)), Some(Interval::new(0, 9801)));
+        assert_eq!(ranges.interval(Value(7)), Some(Interval::new(1, 100)));
+        assert_eq!(ranges.interval(Value(5)), None);
+
+        // Parameters may be any integer, unless a branch tells otherwise.
+        assert_eq!(ranges.interval(Value(0)), Some(Interval::FULL));
+        assert_eq!(ranges.interval_in(Value(0), Block(4)), Some(Interval::new(NEG_INFINITY, 10)));
+        assert_eq!(ranges.interval_in(Value(0), Block(5)), Some(Interval::new(11, INFINITY)));
+        assert_eq!(ranges.interval(Value(10)), Some(Interval::new(NEG_INFINITY, 100)));
+
+        let (small, large) = (Interval::new(0, 10), Interval::new(11, INFINITY));
+        assert_eq!(small.compare(CompareOp::Lt, &large), Some(true));
+        assert_eq!(large.compare(CompareOp::Le, &small), Some(false));
+        assert_eq!(small.compare(CompareOp::Eq, &Interval::constant(5)), None);
+        assert!(Interval::I64.add(&Interval::constant(1)).high == INFINITY && !Interval::FULL.fits_i64());
+        assert_eq!(Interval::new(-7, 20).floor_div(&Interval::new(2, 4)), Interval::new(-4, 10));
+        assert_eq!(Interval::new(-7, 20).modulo(&Interval::constant(5)), Interval::new(0, 4));
+    }
+
+    #[test]
+    fn test_analyses_are_cached_until_invalidated() {
+        let module = parser::parse(NESTED).unwrap();
+        let function = &module.functions[0];
+        let mut analyses = AnalysisManager::new();
+        let dominators = analyses.dominators(function);
+        let loops = analyses.loops(function);
+        assert!(Rc::ptr_eq(&dominators, &analyses.dominators(function)));
+        assert!(Rc::ptr_eq(&loops, &analyses.loops(function)));
+        analyses.invalidate("nested");
+        assert!(!Rc::ptr_eq(&dominators, &analyses.dominators(function)));
+        assert!(!Rc::ptr_eq(&loops, &analyses.loops(function)));
+    }
+}
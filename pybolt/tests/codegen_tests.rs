@@ -1,9 +1,10 @@
 #[cfg(test)]
 mod codegen_tests {
-    use crate::codegen::codegen::CodeGenerator;
-    use crate::ir::ir::IRGenerator;
-    use crate::parser::parser::Parser;
-    use crate::lexer::lexer::Lexer;
+    use pybolt::codegen::codegen::CodeGenerator;
+    use pybolt::ir::generator::IRGenerator;
+    use pybolt::parser::parser::Parser;
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::semantic::semantic::SemanticAnalyzer;
 
     #[test]
     fn test_codegen() {
@@ -11,11 +12,13 @@ mod codegen_tests {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
 
         let mut ir_generator = IRGenerator::new();
-        ir_generator.generate(&parser).unwrap();
+        ir_generator.generate(analyzer.hir().unwrap()).unwrap();
 
         let mut code_generator = CodeGenerator::new();
-        assert!(code_generator.generate(&ir_generator).is_ok());
+        assert!(code_generator.generate(ir_generator.get_ir()).is_ok());
     }
 }
@@ -0,0 +1,168 @@
+#[cfg(test)]
+mod hir_tests {
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::parser::parser::Parser;
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+
+    fn lower(source: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
+        analyzer.hir().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_programs_with_errors_are_not_lowered() {
+        let mut analyzer = SemanticAnalyzer::new();
+        let mut parser = Parser::new(Lexer::new("x = 1\n"));
+        parser.parse().unwrap();
+        analyzer.analyze(&parser).unwrap();
+        assert!(analyzer.hir().is_some());
+
+        let mut parser = Parser::new(Lexer::new("def f():\n    nonlocal missing\nprint(undefined)\n"));
+        parser.parse().unwrap();
+        assert!(analyzer.analyze(&parser).is_err());
+        assert!(analyzer.hir().is_none());
+        assert!(!analyzer.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_augmented_assignment_evaluates_targets_once() {
+        let source = "count = 0\n\
+                      count += 1\n\
+                      class C:\n\
+                      \x20   def __init__(self) -> None:\n\
+                      \x20       self.items = [1]\n\
+                      c = C()\n\
+                      c.items[0] *= 2\n";
+        assert_eq!(
+            lower(source).lines().collect::<Vec<_>>(),
+            vec![
+                "count@global = 0  # int",
+                "count@global = count@global += 1  # int",
+                "C@global = <class C [scope 1]>  # type[C]",
+                "    class C [scope 1]:",
+                "        __init__@local = <def __init__ [scope 2]>  # Callable[[C], None]",
+                "            def __init__(self@local0: C) -> None [scope 2]:",
+                "                self@local0.items = [1]  # list[int]",
+                "c@global = C@global()  # C",
+                "$0 = c@global.items  # Any",
+                "$1 = 0  # int",
+                "$0[$1] = $0[$1] *= 2  # Any",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comprehensions_become_functions() {
+        let source = "rows = [[1, 2], [3]]\n\
+                      flat = [x for row in rows if row for x in row if x > 1]\n\
+                      chars = {c for c in 'ab'}\n\
+                      total = sum(x * 2 for x in flat)\n";
+        assert_eq!(
+            lower(source).lines().collect::<Vec<_>>(),
+            vec![
+                "rows@global = [[1, 2], [3]]  # list[list[int]]",
+                "flat@global = <def <listcomp> [scope 1]>(rows@global)  # list[int]",
+                "    def <listcomp>($0: list[list[int]]) -> list[int] [scope 1]:",
+                "        $1 = []  # list[int]",
+                "        for row@local0 in $0:",
+                "            if row@local0:",
+                "                for x@local1 in row@local0:",
+                "                    if x@local1 > 1:",
+                "                        $1.append(x@local1)  # Any",
+                "        return $1  # list[int]",
+                "chars@global = <def <setcomp> [scope 2]>('ab')  # set[str]",
+                "    def <setcomp>($0: str) -> set[str] [scope 2]:",
+                "        $1 = {*()}  # set[str]",
+                "        for c@local0 in $0:",
+                "            $1.add(c@local0)  # Any",
+                "        return $1  # set[str]",
+                "total@global = sum@builtin(<def <genexpr> [scope 3]>(flat@global))  # Any",
+                "    def <genexpr>($0: list[int]) -> Any [scope 3, generator]:",
+                "        for x@local0 in $0:",
+                "            yield x@local0 * 2  # Any",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_statement_follows_pep_343() {
+        let source = "class Lock:\n\
+                      \x20   def __enter__(self) -> int:\n\
+                      \x20       return 1\n\
+                      \x20   def __exit__(self, kind, value, traceback) -> bool:\n\
+                      \x20       return False\n\
+                      with Lock() as n:\n\
+                      \x20   print(n)\n";
+        assert_eq!(
+            lower(source).lines().collect::<Vec<_>>(),
+            vec![
+                "Lock@global = <class Lock [scope 1]>  # type[Lock]",
+                "    class Lock [scope 1]:",
+                "        __enter__@local = <def __enter__ [scope 2]>  # Callable[[Lock], int]",
+                "            def __enter__(self@local0: Lock) -> int [scope 2]:",
+                "                return 1  # int",
+                "        __exit__@local = <def __exit__ [scope 3]>  # Callable[[Lock, Any, Any, Any], bool]",
+                "            def __exit__(self@local0: Lock, kind@local1: Any, value@local2: Any, traceback@local3: Any) -> bool [scope 3]:",
+                "                return False  # bool",
+                "$0 = Lock@global()  # Lock",
+                "$1 = $0.__exit__  # Any",
+                "$2 = $0.__enter__()  # int",
+                "$3 = True  # bool",
+                "try:",
+                "    try:",
+                "        n@global = $2  # int",
+                "        print@builtin(n@global)  # None",
+                "    except as $4:",
+                "        $3 = False  # bool",
+                "        if not $1($4.__class__, $4, $4.__traceback__):",
+                "            raise",
+                "finally:",
+                "    if $3:",
+                "        $1(None, None, None)  # Any",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_imports_decorators_and_closures() {
+        let source = "import os.path\n\
+                      import json as j\n\
+                      from typing import List, Optional\n\
+                      def memo(f):\n\
+                      \x20   return f\n\
+                      def counter(start: int = 0):\n\
+                      \x20   count = start\n\
+                      \x20   @memo\n\
+                      \x20   def bump(step=1):\n\
+                      \x20       nonlocal count\n\
+                      \x20       count += step\n\
+                      \x20       return count\n\
+                      \x20   return bump, lambda: count\n";
+        assert_eq!(
+            lower(source).lines().collect::<Vec<_>>(),
+            vec![
+                "os@global = __import__('os.path')  # Any",
+                "j@global = import_module('json')  # Any",
+                "$0 = import_module('typing')  # Any",
+                "List@global = $0.List  # Any",
+                "Optional@global = $0.Optional  # Any",
+                "memo@global = <def memo [scope 1]>  # Callable[[Any], Any]",
+                "    def memo(f@local0: Any) -> Any [scope 1]:",
+                "        return f@local0  # Any",
+                "counter@global = <def counter [scope 2]>  # Callable[[int], Any]",
+                "    def counter(start@local0: int = 0) -> Any [scope 2, cells: count]:",
+                "        count@cell0 = start@local0  # int",
+                "        bump@local1 = memo@global(<def bump [scope 3]>)  # Any",
+                "            def bump(step@local0: Any = 1) -> Any [scope 3, frees: count]:",
+                "                count@free0 = count@free0 += step@local0  # Any",
+                "                return count@free0  # Any",
+                "        return (bump@local1, <def <lambda> [scope 4]>)  # tuple[Any, Callable[[], int]]",
+                "            def <lambda>() -> int [scope 4, frees: count]:",
+                "                return count@free0  # int",
+            ]
+        );
+    }
+}
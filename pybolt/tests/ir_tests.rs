@@ -0,0 +1,681 @@
+#[cfg(test)]
+mod ir_tests {
+    use pybolt::codegen::codegen::CodeGenerator;
+    use pybolt::ir::builder::FunctionBuilder;
+    use pybolt::ir::generator::IRGenerator;
+    use pybolt::ir::ir::{BinaryOp, CompareOp, Function, InlineHint, InstKind, Module, Type};
+    use pybolt::ir::parser;
+    use pybolt::lexer::lexer::{line_column, Lexer};
+    use pybolt::optimizer::optimizer::{OptLevel, Optimizer};
+    use pybolt::parser::parser::Parser;
+    use pybolt::runtime::runtime::{float_arithmetic, float_repr, floor_divide, modulo, Arithmetic, Instruction, Runtime, Value};
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+
+    fn generate(source: &str) -> Module {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
+        let mut ir_generator = IRGenerator::new();
+        ir_generator.generate(analyzer.hir().unwrap()).unwrap()
+    }
+
+    fn run(module: &Module) -> Runtime {
+        let mut code_generator = CodeGenerator::new();
+        let instructions = code_generator.generate(module).unwrap();
+        let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
+        runtime.run().unwrap();
+        runtime
+    }
+
+    #[test]
+    fn test_builder_adds_block_params_for_loop_variables() {
+        let mut function = Function::new("sum_to", &[Type::I64], Type::I64);
+        let mut builder = FunctionBuilder::new(&mut function);
+        let n = builder.params()[0];
+        let total = builder.declare_variable(Type::I64);
+        let i = builder.declare_variable(Type::I64);
+        let zero = builder.iconst(0);
+        builder.def_var(total, zero);
+        builder.def_var(i, zero);
+        let header = builder.create_block();
+        let body = builder.create_block();
+        let exit = builder.create_block();
+        builder.br(header, Vec::new());
+
+        builder.switch_to_block(header);
+        let current = builder.use_var(i);
+        let condition = builder.compare(CompareOp::Lt, current, n);
+        builder.cond_br(condition, body, Vec::new(), exit, Vec::new());
+
+        builder.switch_to_block(body);
+        builder.seal_block(body);
+        let (sum, index) = (builder.use_var(total), builder.use_var(i));
+        let sum = builder.binary(BinaryOp::Add, sum, index);
+        builder.def_var(total, sum);
+        let one = builder.iconst(1);
+        let index = builder.binary(BinaryOp::Add, index, one);
+        builder.def_var(i, index);
+        builder.br(header, Vec::new());
+        builder.seal_block(header);
+
+        builder.switch_to_block(exit);
+        builder.seal_block(exit);
+        let result = builder.use_var(total);
+        builder.ret(Some(result));
+        builder.finish();
+
+        assert_eq!(
+            function.to_string().lines().collect::<Vec<_>>(),
+            vec![
+                "fn @sum_to(i64) -> i64 {",
+                "bb0(%0: i64):",
+                "    %1: i64 = const 0",
+                "    br bb1(%1, %1)",
+                "bb1(%2: i64, %3: i64):",
+                "    %4: bool = lt %2, %0",
+                "    cond_br %4, bb2, bb3",
+                "bb2:",
+                "    %5: i64 = add %3, %2",
+                "    %6: i64 = const 1",
+                "    %7: i64 = add %2, %6",
+                "    br bb1(%7, %5)",
+                "bb3:",
+                "    ret %3",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loops_and_branches_become_blocks() {
+        let source = "def collatz(n: int) -> int:\n\
+                      \x20   steps = 0\n\
+                      \x20   while n != 1:\n\
+                      \x20       if n % 2 == 0:\n\
+                      \x20           n = n // 2\n\
+                      \x20       else:\n\
+                      \x20           n = 3 * n + 1\n\
+                      \x20       steps += 1\n\
+                      \x20   return steps\n\
+                      print(collatz(27))\n";
+        let module = generate(source);
+        assert_eq!(
+            module.function("collatz").unwrap().to_string().lines().collect::<Vec<_>>(),
+            vec![
+                "fn @collatz(int) -> int {",
+                "bb0(%0: int):",
+                "    %1: int = const 0",
+                "    br bb1(%0, %1)",
+                "bb1(%2: int, %3: int):",
+                "    %4: int = const 1",
+                "    %5: bool = ne %2, %4",
+                "    cond_br %5, bb2, bb6",
+                "bb2:",
+                "    %6: int = const 2",
+                "    %7: int = mod %2, %6",
+                "    %8: int = const 0",
+                "    %9: bool = eq %7, %8",
+                "    cond_br %9, bb3, bb4",
+                "bb3:",
+                "    %10: int = const 2",
+                "    %11: int = floordiv %2, %10",
+                "    br bb5(%11)",
+                "bb4:",
+                "    %12: int = const 3",
+                "    %13: int = mul %12, %2",
+                "    %14: int = const 1",
+                "    %15: int = add %13, %14",
+                "    br bb5(%15)",
+                "bb5(%16: int):",
+                "    %17: int = const 1",
+                "    %18: int = add %3, %17",
+                "    br bb1(%16, %18)",
+                "bb6:",
+                "    ret %3",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_programs_run_after_code_generation() {
+        let source = "count = 0\n\
+                      def bump(by: int) -> None:\n\
+                      \x20   global count\n\
+                      \x20   count += by\n\
+                      def fib(n: int) -> int:\n\
+                      \x20   a = 0\n\
+                      \x20   b = 1\n\
+                      \x20   for _ in range(n):\n\
+                      \x20       t = a + b\n\
+                      \x20       a = b\n\
+                      \x20       b = t\n\
+                      \x20   return a\n\
+                      bump(1)\n\
+                      bump(by=fib(10))\n\
+                      quotient = -7 // 2\n\
+                      remainder = -7 % 2\n\
+                      inside = 1 < count < 100 and not quotient > 0\n\
+                      def report() -> bool:\n\
+                      \x20   return inside and quotient < remainder\n\
+                      print(report())\n";
+        let runtime = run(&generate(source));
+        assert_eq!(runtime.get_var_value("count"), Some(&Value::Int(56)));
+        assert_eq!(runtime.get_var_value("quotient"), Some(&Value::Int(-4)));
+        assert_eq!(runtime.get_var_value("remainder"), Some(&Value::Int(1)));
+        assert_eq!(runtime.get_var_value("inside"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_traceback_shows_inlined_frames() {
+        let source = "def divide(a: int, b: int) -> int:\n\
+                      \x20   return a // b\n\
+                      def half_of(x: int) -> int:\n\
+                      \x20   return divide(x, 0) + 1\n\
+                      print(half_of(3))\n";
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(generate(source)).unwrap();
+        assert_eq!(optimized.functions.len(), 1, "both functions are inlined");
+        let mut code_generator = CodeGenerator::new();
+        let instructions = code_generator.generate(&optimized).unwrap();
+        let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
+        assert_eq!(runtime.run().unwrap_err(), "ZeroDivisionError: integer division or modulo by zero");
+        let frames: Vec<(String, usize)> = code_generator
+            .traceback(runtime.traceback())
+            .into_iter()
+            .map(|frame| (frame.function, line_column(source, frame.span.start).0))
+            .collect();
+        assert_eq!(frames, [("__main__".to_string(), 5), ("half_of".to_string(), 4), ("divide".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_traceback_names_the_functions_specializations_copy() {
+        let source = "import pybolt\n\
+                      @pybolt.noinline\n\
+                      def divide(a, b):\n\
+                      \x20   return a // b\n\
+                      @pybolt.noinline\n\
+                      def half_of(x, y):\n\
+                      \x20   return divide(x, y) + 1\n\
+                      values = [3, 0]\n\
+                      print(half_of(4, 2))\n\
+                      print(half_of(values[0], values[1]))\n";
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(generate(source)).unwrap();
+        assert!(optimized.function("half_of.int.int").is_some() && optimized.function("divide.int.int").is_some(), "{}", optimized);
+        let mut code_generator = CodeGenerator::new();
+        let instructions = code_generator.generate(&optimized).unwrap();
+        let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
+        assert_eq!(runtime.run().unwrap_err(), "ZeroDivisionError: integer division or modulo by zero");
+        // The frames are those of the copies, under the names of the program.
+        let frames: Vec<(String, usize)> = code_generator
+            .traceback(runtime.traceback())
+            .into_iter()
+            .map(|frame| (frame.function, line_column(source, frame.span.start).0))
+            .collect();
+        assert_eq!(frames, [("__main__".to_string(), 10), ("half_of".to_string(), 7), ("divide".to_string(), 4)]);
+    }
+
+    #[test]
+    fn test_traceback_keeps_inlined_frames_of_rewritten_instructions() {
+        let source = "def divide(a, b):\n\
+                      \x20   return a // b\n\
+                      def half_of(x):\n\
+                      \x20   return divide(x, 0) + 1\n\
+                      print(half_of(3))\n";
+        // The division of unboxed ints replaces the inlined one, and the
+        // inlined call of `divide` to its specialization shows no frame.
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(generate(source)).unwrap();
+        assert_eq!(optimized.functions.len(), 1, "both functions are inlined");
+        let mut code_generator = CodeGenerator::new();
+        let instructions = code_generator.generate(&optimized).unwrap();
+        let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
+        assert_eq!(runtime.run().unwrap_err(), "ZeroDivisionError: integer division or modulo by zero");
+        let frames: Vec<(String, usize)> = code_generator
+            .traceback(runtime.traceback())
+            .into_iter()
+            .map(|frame| (frame.function, line_column(source, frame.span.start).0))
+            .collect();
+        assert_eq!(frames, [("__main__".to_string(), 5), ("half_of".to_string(), 4), ("divide".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_integer_literals_larger_than_64_bits() {
+        let source = "x = 100000000000000000000\n\
+                      y = -x + 1\n\
+                      h = 0xFFFF_FFFF_FFFF_FFFF_FF\n\
+                      n = -9223372036854775808\n\
+                      def show() -> None:\n\
+                      \x20   print(x, y, h, n)\n\
+                      show()\n";
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let runtime = run(&Optimizer::with_level(level).optimize(generate(source)).unwrap());
+            let repr = |name: &str| runtime.get_var_value(name).unwrap().repr();
+            assert_eq!(repr("x"), "100000000000000000000");
+            assert_eq!(repr("y"), "-99999999999999999999");
+            assert_eq!(repr("h"), "4722366482869645213695");
+            assert_eq!(runtime.get_var_value("n"), Some(&Value::Int(i64::MIN)));
+        }
+    }
+
+    #[test]
+    fn test_floats_strings_and_objects_run() {
+        let source = "def describe(n: int):\n\
+                      \x20   if n < 0:\n\
+                      \x20       return 'negative'\n\
+                      \x20   return n\n\
+                      half = 7 / 2\n\
+                      floored = -7.5 // 2\n\
+                      name = 'Py' + 'Bolt'\n\
+                      twice = name * 2\n\
+                      value = 1\n\
+                      value = describe(-1)\n\
+                      positive = describe(3)\n\
+                      same = 2 == 2.0 and None == None\n\
+                      def show() -> None:\n\
+                      \x20   print(half, floored, twice, value, positive, same)\n\
+                      show()\n";
+        let module = generate(source);
+        let text = module.to_string();
+        assert!(text.contains("fn @describe(int) -> object {"), "{}", text);
+        assert!(text.contains("= box"), "{}", text);
+        let runtime = run(&module);
+        assert_eq!(runtime.get_var_value("half"), Some(&Value::Float(3.5)));
+        assert_eq!(runtime.get_var_value("floored"), Some(&Value::Float(-4.0)));
+        assert_eq!(runtime.get_var_value("twice"), Some(&Value::Str("PyBoltPyBolt".into())));
+        assert_eq!(runtime.get_var_value("value"), Some(&Value::Str("negative".into())));
+        assert_eq!(runtime.get_var_value("positive"), Some(&Value::Int(3)));
+        assert_eq!(runtime.get_var_value("same"), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_float_arithmetic_follows_python() {
+        assert_eq!(float_arithmetic(Arithmetic::FloorDiv, -7.5, 2.0), Ok(-4.0));
+        assert_eq!(float_arithmetic(Arithmetic::Mod, -7.5, 2.0), Ok(0.5));
+        assert_eq!(float_arithmetic(Arithmetic::Mod, 7.5, -2.0), Ok(-0.5));
+        assert!(float_arithmetic(Arithmetic::Div, 1.0, 0.0).unwrap_err().starts_with("ZeroDivisionError"));
+        assert_eq!(float_repr(1.0), "1.0");
+        assert_eq!(float_repr(0.1), "0.1");
+        assert_eq!(float_repr(1e16), "1e+16");
+        assert_eq!(float_repr(1.5e-5), "1.5e-05");
+        assert_eq!(float_repr(-0.0), "-0.0");
+        assert_eq!(float_repr(f64::INFINITY), "inf");
+    }
+
+    #[test]
+    fn test_ints_grow_past_64_bits() {
+        let source = "def factorial(n: int) -> int:\n\
+                      \x20   result = 1\n\
+                      \x20   for i in range(2, n + 1):\n\
+                      \x20       result *= i\n\
+                      \x20   return result\n\
+                      big = factorial(25)\n\
+                      quotient = big // -7\n\
+                      remainder = (big + 1) % -13\n\
+                      ratio = big / 3\n\
+                      folded = 9223372036854775807 * 3 // -2\n\
+                      folded_remainder = 9223372036854775807 * 3 % -2\n\
+                      def show() -> None:\n\
+                      \x20   print(big, quotient, remainder, ratio, folded, folded_remainder)\n\
+                      show()\n";
+        let expected = [
+            ("big", "15511210043330985984000000"),
+            ("quotient", "-2215887149047283712000000"),
+            ("remainder", "-12"),
+            ("ratio", "5.170403347776996e+24"),
+            ("folded", "-13835058055282163711"),
+            ("folded_remainder", "-1"),
+        ];
+        let module = generate(source);
+        let optimized = Optimizer::new().optimize(module.clone()).unwrap();
+        assert!(optimized.to_string().contains("const -13835058055282163711"), "{}", optimized);
+        // Folding gives what the runtime computes, which is what Python does.
+        for module in [module, optimized] {
+            let runtime = run(&module);
+            for (name, value) in expected {
+                assert_eq!(runtime.get_var_value(name).map(ToString::to_string).as_deref(), Some(value), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unsupported_types_are_reported() {
+        for (source, what) in [("names = {'PyBolt'}\n", "set[str]"), ("name = 'x'\ntext = f'{name}'\n", "f-strings"), ("data = b'xy'\n", "bytes")] {
+            let mut parser = Parser::new(Lexer::new(source));
+            parser.parse().unwrap();
+            let mut analyzer = SemanticAnalyzer::new();
+            analyzer.analyze(&parser).unwrap();
+            let error = IRGenerator::new().generate(analyzer.hir().unwrap()).unwrap_err();
+            assert!(error.contains("does not support") && error.contains(what), "{}", error);
+        }
+        // An f-string without replacement fields is a string.
+        let runtime = run(&generate("text = f'{{PyBolt}}'\ndef show() -> str:\n    return text\n"));
+        assert_eq!(runtime.get_var_value("text"), Some(&Value::Str("{PyBolt}".into())));
+    }
+
+    #[test]
+    fn test_division_follows_python() {
+        assert_eq!(floor_divide(7, 2), Ok(3));
+        assert_eq!(floor_divide(-7, 2), Ok(-4));
+        assert_eq!(floor_divide(7, -2), Ok(-4));
+        assert_eq!(modulo(-7, 2), Ok(1));
+        assert_eq!(modulo(7, -2), Ok(-1));
+        assert!(floor_divide(1, 0).unwrap_err().starts_with("ZeroDivisionError"));
+        assert!(floor_divide(i64::MIN, -1).unwrap_err().starts_with("OverflowError"));
+    }
+
+    #[test]
+    fn test_constant_folding_keeps_division_by_zero() {
+        let module = generate("x = 6 * 7\ny = x // 0\n");
+        let optimized = Optimizer::new().optimize(module).unwrap();
+        let text = optimized.to_string();
+        assert!(text.contains("const 42"), "{}", text);
+        assert!(text.contains("floordiv"), "{}", text);
+    }
+
+    #[test]
+    fn test_constants_propagate_through_variables_and_branches() {
+        let source = "result = 0\n\
+                      def f(n: int) -> int:\n\
+                      \x20   x = 2\n\
+                      \x20   y = x * 3\n\
+                      \x20   if y > 5:\n\
+                      \x20       return n * y\n\
+                      \x20   return n // 0\n\
+                      def g() -> None:\n\
+                      \x20   global result\n\
+                      \x20   result = f(7)\n\
+                      g()\n";
+        // Without inlining, which would fold `f` into `g`.
+        let mut optimizer = Optimizer::new();
+        optimizer.disable("inline").unwrap();
+        let optimized = optimizer.optimize(generate(source)).unwrap();
+        let f = optimized.function("f").unwrap();
+        assert_eq!(f.layout.len(), 2, "{}", f);
+        let text = f.to_string();
+        assert!(text.contains("const 6\n") && !text.contains("cond_br") && !text.contains("floordiv"), "{}", text);
+        assert_eq!(run(&optimized).get_var_value("result"), Some(&Value::Int(42)));
+    }
+
+    #[test]
+    fn test_range_loops_count_in_64_bits() {
+        let source = "total = 0\n\
+                      count = 0\n\
+                      def main() -> None:\n\
+                      \x20   global total, count\n\
+                      \x20   for i in range(1000):\n\
+                      \x20       total = total + i * 3\n\
+                      \x20   for i in range(4):\n\
+                      \x20       count = count + i\n\
+                      \x20   for i in range(100000):\n\
+                      \x20       unused = i * i\n\
+                      main()\n";
+        for level in [OptLevel::O2, OptLevel::O3] {
+            let optimized = Optimizer::with_level(level).optimize(generate(source)).unwrap();
+            let text = optimized.to_string();
+            // The counter is an `i64`, the multiplication an addition, and
+            // the loop computing nothing is gone.
+            assert!(text.contains("i64 = add") && !text.contains("mul") && !text.contains("100000"), "{:?}: {}", level, text);
+            let runtime = run(&optimized);
+            assert_eq!(runtime.get_var_value("total"), Some(&Value::Int(1498500)));
+            assert_eq!(runtime.get_var_value("count"), Some(&Value::Int(6)));
+        }
+    }
+
+    #[test]
+    fn test_int_operations_that_fit_skip_overflow_checks() {
+        let source = "total = 0\n\
+                      hits = 0\n\
+                      def main() -> None:\n\
+                      \x20   global total, hits\n\
+                      \x20   for i in range(1000):\n\
+                      \x20       total = total + (i // 3) * (i % 7)\n\
+                      \x20       if i >= 1000:\n\
+                      \x20           hits = hits + 1\n\
+                      main()\n";
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(generate(source)).unwrap();
+        let text = optimized.to_string();
+        // The counter is below 1000 in the loop, so `(i // 3) * (i % 7)` is
+        // at most 1998 and `i >= 1000` never holds; the total may grow past
+        // 64 bits as far as intervals tell.
+        assert!(text.contains("i64 = floordiv") && text.contains("i64 = mod") && text.contains("i64 = mul"), "{}", text);
+        assert!(text.contains("int = add") && !text.contains("ge ") && !text.contains("load_global @hits"), "{}", text);
+        let runtime = run(&optimized);
+        assert_eq!(runtime.get_var_value("total"), Some(&Value::Int(498837)));
+        assert_eq!(runtime.get_var_value("hits"), Some(&Value::Int(0)));
+    }
+
+    #[test]
+    fn test_range_len_loops_skip_bounds_checks() {
+        let source = "import pybolt\n\
+                      @pybolt.noinline\n\
+                      def total(xs: list[int]) -> int:\n\
+                      \x20   s = 0\n\
+                      \x20   for i in range(len(xs)):\n\
+                      \x20       s += xs[i]\n\
+                      \x20   return s + xs[-1]\n\
+                      squares = [1, 4, 9, 16]\n\
+                      def main() -> None:\n\
+                      \x20   squares[0] = 0\n\
+                      \x20   print(total(squares))\n\
+                      \x20   print(squares[4])\n\
+                      main()\n";
+        // Only the check of `xs[-1]`, which counts from the end, is left.
+        for (level, checks) in [(OptLevel::O0, 2), (OptLevel::O2, 1)] {
+            let optimized = Optimizer::with_level(level).optimize(generate(source)).unwrap();
+            let text = optimized.function("total").unwrap().to_string();
+            assert_eq!(text.matches("check_index").count(), checks, "{:?}: {}", level, text);
+            let instructions = CodeGenerator::new().generate(&optimized).unwrap();
+            let mut runtime = Runtime::new(instructions.into_iter().map(Instruction::from).collect());
+            assert_eq!(runtime.run(), Err("IndexError: list index out of range".to_string()));
+            assert_eq!(runtime.get_var_value("squares").unwrap().to_string(), "[0, 4, 9, 16]");
+        }
+    }
+
+    #[test]
+    fn test_functions_without_annotations_are_specialized_for_ints() {
+        let source = "def add(a, b):\n\
+                      \x20   return a + b\n\
+                      total = 0\n\
+                      half = 0.0\n\
+                      text = \"\"\n\
+                      def main() -> None:\n\
+                      \x20   global total, half, text\n\
+                      \x20   for i in range(10):\n\
+                      \x20       total = add(total, i)\n\
+                      \x20   half = add(1.5, 2)\n\
+                      \x20   text = add(\"a\", \"b\")\n\
+                      main()\n";
+        let module = generate(source);
+        let specialized = Optimizer::with_pipeline("specialize,unbox,dce").unwrap().optimize(module.clone()).unwrap();
+        // The copies add the unboxed values.
+        let ints = specialized.function("add.int.int").unwrap();
+        assert_eq!(ints.param_types(), vec![Type::Int, Type::Int]);
+        assert!(ints.to_string().contains("int = add"), "{}", ints);
+        let floats = specialized.function("add.float.int").unwrap();
+        assert_eq!(floats.param_types(), vec![Type::F64, Type::Int]);
+        assert!(floats.to_string().contains("f64 = add"), "{}", floats);
+        // The generic function guards the classes of its arguments.
+        assert!(specialized.function("add").unwrap().to_string().contains("guard"), "{}", specialized);
+
+        for optimized in [specialized, Optimizer::with_level(OptLevel::O2).optimize(module).unwrap()] {
+            let runtime = run(&optimized);
+            assert_eq!(runtime.get_var_value("total"), Some(&Value::Int(45)));
+            assert_eq!(runtime.get_var_value("half"), Some(&Value::Float(3.5)));
+            assert_eq!(runtime.get_var_value("text"), Some(&Value::Str("ab".into())));
+        }
+    }
+
+    #[test]
+    fn test_specializations_made_in_later_rounds_box_unboxed_returns() {
+        let source = "import pybolt\n\
+                      @pybolt.noinline\n\
+                      def halve(a, b):\n\
+                      \x20   return a // b\n\
+                      @pybolt.noinline\n\
+                      def bump(x):\n\
+                      \x20   return halve(x, 2) + 1\n\
+                      values = [30, \"x\"]\n\
+                      result = bump(8) + halve(9, 3) + bump(values[0])\n\
+                      def report() -> None:\n\
+                      \x20   print(result)\n";
+        // `halve.int.int` returns an `int` once unboxed, before the next
+        // round specializes the call of `bump.int` to it.
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(generate(source)).unwrap();
+        assert_eq!(optimized.function("halve.int.int").unwrap().return_type, Type::Int);
+        assert_eq!(run(&optimized).get_var_value("result"), Some(&Value::Int(24)));
+    }
+
+    #[test]
+    fn test_temporary_tuples_are_replaced_by_their_items() {
+        let source = "def fib(n: int) -> int:\n\
+                      \x20   a, b = 0, 1\n\
+                      \x20   for i in range(n):\n\
+                      \x20       a, b = b, a + b\n\
+                      \x20   return a\n\
+                      def minmax(x: int, y: int):\n\
+                      \x20   if x < y:\n\
+                      \x20       return x, y\n\
+                      \x20   return y, x\n\
+                      result = 0\n\
+                      pair = (0, 0)\n\
+                      def main() -> None:\n\
+                      \x20   global result, pair\n\
+                      \x20   lo, hi = minmax(9, 2)\n\
+                      \x20   result = fib(20) + hi - lo\n\
+                      \x20   pair = minmax(4, 3)\n\
+                      main()\n";
+        let module = generate(source);
+        assert!(module.to_string().contains("tuple"), "{}", module);
+        let optimized = Optimizer::with_level(OptLevel::O2).optimize(module.clone()).unwrap();
+        // Only the tuples stored to `pair` are made.
+        let tuples = optimized.functions.iter().flat_map(|function| function.layout_insts().filter(|inst| matches!(function.inst(*inst).kind, InstKind::Tuple(_))));
+        assert_eq!(tuples.count(), 2, "{}", optimized);
+        for module in [module, optimized] {
+            let runtime = run(&module);
+            assert_eq!(runtime.get_var_value("result"), Some(&Value::Int(6772)));
+            assert_eq!(runtime.get_var_value("pair").map(Value::to_string), Some("(3, 4)".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_inlined_code_keeps_the_frames_of_its_functions() {
+        let source = "import pybolt\n\
+                      @pybolt.inline\n\
+                      def half(x: int) -> int:\n\
+                      \x20   return x // 2\n\
+                      def quarter(x: int) -> int:\n\
+                      \x20   return half(half(x))\n\
+                      result = 0\n\
+                      def main() -> None:\n\
+                      \x20   global result\n\
+                      \x20   result = quarter(40) + quarter(8)\n\
+                      main()\n";
+        let module = generate(source);
+        assert_eq!(module.function("half").unwrap().inline, InlineHint::Always);
+        assert_eq!(module.function("quarter").unwrap().inline, InlineHint::Default);
+
+        let optimized = Optimizer::with_pipeline("inline").unwrap().optimize(module).unwrap();
+        assert_eq!(optimized.functions.len(), 1, "{}", optimized);
+        let main = &optimized.functions[0];
+        let division = main.layout_insts().find(|inst| matches!(main.inst(*inst).kind, InstKind::Binary { op: BinaryOp::FloorDiv, .. })).unwrap();
+        let frames: Vec<_> = main
+            .frames(division)
+            .into_iter()
+            .map(|frame| (frame.function, &source[frame.span.start..frame.span.end]))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                ("__main__".to_string(), "main()"),
+                ("main".to_string(), "result = quarter(40) + quarter(8)"),
+                ("quarter".to_string(), "return half(half(x))"),
+                ("half".to_string(), "return x // 2"),
+            ]
+        );
+        assert_eq!(run(&optimized).get_var_value("result"), Some(&Value::Int(12)));
+    }
+
+    #[test]
+    fn test_printed_ir_parses_back() {
+        let source = "count = 0\n\
+                      def step(n: int) -> int:\n\
+                      \x20   global count\n\
+                      \x20   count += 1\n\
+                      \x20   return n // 2 if n % 2 == 0 else 3 * n + 1\n\
+                      n = 27\n\
+                      while n != 1:\n\
+                      \x20   n = step(n)\n\
+                      print(count, 0 < n <= 1 or not n)\n";
+        let text = generate(source).to_string();
+        assert_eq!(parser::parse(&text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn test_object_ir_parses_back() {
+        let text = "fn @f(object) -> f64 {\n\
+                    bb0(%0: object):\n\
+                    \x20   %1: bool = guard %0, float\n\
+                    \x20   cond_br %1, bb1, bb2\n\
+                    bb1:\n\
+                    \x20   %2: f64 = unbox %0\n\
+                    \x20   ret %2\n\
+                    bb2:\n\
+                    \x20   %3: object = const \"a; \\\"b\\\"\\n\"\n\
+                    \x20   %4: bool = truth %3\n\
+                    \x20   %5: f64 = const 1e-7\n\
+                    \x20   %6: f64 = cast %4\n\
+                    \x20   %7: f64 = div %5, %6\n\
+                    \x20   ret %7\n\
+                    }\n";
+        let module = parser::parse(text).unwrap();
+        assert_eq!(module.to_string(), text);
+        let optimized = Optimizer::new().optimize(module).unwrap().to_string();
+        assert!(optimized.contains("const 1e-7\n    ret"), "{}", optimized);
+    }
+
+    #[test]
+    fn test_hand_written_ir_is_renumbered() {
+        let text = "fn @abs(i64) -> i64 {  ; the absolute value\n\
+                    entry(%x: i64):\n\
+                    \x20   %zero: i64 = const 0\n\
+                    \x20   %negative: bool = lt %x, %zero\n\
+                    \x20   cond_br %negative, flip, done(%x)\n\
+                    flip:\n\
+                    \x20   %flipped: i64 = neg %x\n\
+                    \x20   br done(%flipped)\n\
+                    done(%result: i64):\n\
+                    \x20   ret %result\n\
+                    }\n";
+        let module = parser::parse(text).unwrap();
+        assert_eq!(
+            module.to_string().lines().collect::<Vec<_>>(),
+            vec![
+                "fn @abs(i64) -> i64 {",
+                "bb0(%0: i64):",
+                "    %1: i64 = const 0",
+                "    %2: bool = lt %0, %1",
+                "    cond_br %2, bb1, bb2(%0)",
+                "bb1:",
+                "    %3: i64 = neg %0",
+                "    br bb2(%3)",
+                "bb2(%4: i64):",
+                "    ret %4",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ir_parse_errors() {
+        let error = |text: &str| parser::parse(text).unwrap_err();
+        assert_eq!(error("fn @f() -> i64 {\nbb0:\n    ret %x\n}\n"), "line 3: undefined value %x");
+        assert_eq!(
+            error("fn @f() -> i64 {\nbb0:\n    %a: i64 = const 1\n    %a: i64 = const 2\n    ret %a\n}\n"),
+            "line 4: value %a is defined twice"
+        );
+        assert_eq!(error("fn @f() -> none {\nbb0:\n    jump bb0\n}\n"), "line 3: unknown instruction 'jump'");
+        assert_eq!(error("fn @f() -> none {\nbb0:\n    br bb1\n}\n"), "line 3: undefined block bb1");
+        assert_eq!(error("fn @f(i64) -> none {\nbb0:\n    ret\n}\n"), "line 2: the parameters of the entry block must match the function's");
+        assert_eq!(error("fn @f() -> none {\nbb0:\n    ret\n"), "line 1: function @f is not closed with '}'");
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod lexer_tests {
-    use crate::lexer::lexer::Lexer;
+    use pybolt::lexer::lexer::Lexer;
 
     #[test]
     fn test_lexer() {
@@ -0,0 +1,183 @@
+#[cfg(test)]
+mod lint_tests {
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::parser::parser::Parser;
+    use pybolt::semantic::lint::{LintConfig, LintLevel};
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+
+    fn lint_with(source: &str, config: LintConfig) -> Result<Vec<String>, String> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::with_lint_config(config);
+        analyzer.analyze(&parser)?;
+        Ok(analyzer.diagnostics().iter().map(|diagnostic| diagnostic.render(source)).collect())
+    }
+
+    fn lint(source: &str) -> Vec<String> {
+        lint_with(source, LintConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_unused_imports_and_variables() {
+        let source = "import os\n\
+                      import os.path\n\
+                      from json import dumps, loads\n\
+                      def run(values):\n\
+                      \x20   import re\n\
+                      \x20   total = 0\n\
+                      \x20   first, rest = values[0], values[1:]\n\
+                      \x20   _ignored = 1\n\
+                      \x20   try:\n\
+                      \x20       total = int(first)\n\
+                      \x20   except ValueError as error:\n\
+                      \x20       pass\n\
+                      \x20   return dumps(rest)\n\
+                      def outer():\n\
+                      \x20   count = 0\n\
+                      \x20   def inner():\n\
+                      \x20       return count + len(os.sep)\n\
+                      \x20   return inner\n";
+        assert_eq!(
+            lint(source),
+            vec![
+                "3:1: warning: 'loads' imported but unused [PB001]",
+                "5:5: warning: 're' imported but unused [PB001]",
+                "6:5: warning: local variable 'total' is assigned to but never used [PB002]",
+                "11:5: warning: local variable 'error' is assigned to but never used [PB002]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_variables_used_in_f_strings_are_used() {
+        let source = "def greet(name):\n\
+                      \x20   greeting = 'hello'\n\
+                      \x20   width = 10\n\
+                      \x20   unused = f'{name}'\n\
+                      \x20   return f'{greeting}, {name:>{width}}!'\n";
+        assert_eq!(lint(source), vec!["4:5: warning: local variable 'unused' is assigned to but never used [PB002]"]);
+    }
+
+    #[test]
+    fn test_unused_arguments_are_allowed_by_default() {
+        let source = "class Shape:\n\
+                      \x20   def area(self, scale):\n\
+                      \x20       return 0\n\
+                      def stub(value):\n\
+                      \x20   ...\n\
+                      def used(a, b, _c):\n\
+                      \x20   return a\n";
+        assert!(lint(source).is_empty());
+        let mut config = LintConfig::default();
+        config.set("unused-argument", LintLevel::Warn).unwrap();
+        assert_eq!(lint_with(source, config).unwrap(), vec!["6:1: warning: unused argument 'b' [PB003]"]);
+    }
+
+    #[test]
+    fn test_shadowed_builtins_and_mutable_defaults() {
+        let source = "def list(items=[], seen=set(), pairs={}, count=0, rows=None):\n\
+                      \x20   id = len(items)\n\
+                      \x20   return id\n\
+                      class Config:\n\
+                      \x20   type = 'config'\n\
+                      \x20   def __init__(self, size=tuple()):\n\
+                      \x20       self.size = size\n";
+        assert_eq!(
+            lint(source),
+            vec![
+                "1:1: warning: mutable default value for argument 'items' [PB005]",
+                "1:1: warning: mutable default value for argument 'seen' [PB005]",
+                "1:1: warning: mutable default value for argument 'pairs' [PB005]",
+                "1:1: warning: definition 'list' shadows a builtin [PB004]",
+                "2:5: warning: variable 'id' shadows a builtin [PB004]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_is_literal_and_bare_except() {
+        let source = "def check(value):\n\
+                      \x20   try:\n\
+                      \x20       if value is 1 or value is not 'a':\n\
+                      \x20           return True\n\
+                      \x20   except:\n\
+                      \x20       pass\n\
+                      \x20   return value is None or value is True\n";
+        assert_eq!(
+            lint(source),
+            vec![
+                "3:9: warning: \"is\" with a literal. Did you mean \"==\"? [PB006]",
+                "3:9: warning: \"is not\" with a literal. Did you mean \"!=\"? [PB006]",
+                "5:5: warning: do not use bare 'except' [PB007]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_redefined_functions() {
+        let source = "from typing import overload\n\
+                      def parse(text):\n\
+                      \x20   return text\n\
+                      def parse(text):\n\
+                      \x20   return text.strip()\n\
+                      def helper():\n\
+                      \x20   return 1\n\
+                      print(helper())\n\
+                      def helper():\n\
+                      \x20   return 2\n\
+                      @overload\n\
+                      def size(value: int) -> int: ...\n\
+                      @overload\n\
+                      def size(value: str) -> int: ...\n\
+                      def size(value):\n\
+                      \x20   return 0\n\
+                      class Point:\n\
+                      \x20   @property\n\
+                      \x20   def x(self):\n\
+                      \x20       return 0\n\
+                      \x20   @x.setter\n\
+                      \x20   def x(self, value):\n\
+                      \x20       pass\n";
+        assert_eq!(lint(source), vec!["4:1: warning: redefinition of unused 'parse' from line 2 [PB009]"]);
+    }
+
+    #[test]
+    fn test_classes_based_on_the_class_they_redefine() {
+        let source = "class Base:\n\
+                      \x20   pass\n\
+                      class Base(Base):\n\
+                      \x20   pass\n\
+                      class Meta(type):\n\
+                      \x20   pass\n\
+                      class Meta(metaclass=Meta):\n\
+                      \x20   pass\n\
+                      class Node:\n\
+                      \x20   pass\n\
+                      class Node:\n\
+                      \x20   pass\n";
+        assert_eq!(lint(source), vec!["11:1: warning: redefinition of unused 'Node' from line 9 [PB009]"]);
+    }
+
+    #[test]
+    fn test_levels_and_suppression_comments() {
+        let source = "import os  # pybolt: ignore[PB001]\n\
+                      import sys  # pybolt: ignore[unused-import, PB004]\n\
+                      import re  # pybolt: ignore[PB004]\n\
+                      import json  # pybolt: ignore\n\
+                      def f():\n\
+                      \x20   return 1\n\
+                      \x20   print('never')  # pybolt: ignore[PB008]\n";
+        assert_eq!(lint(source), vec!["3:1: warning: 're' imported but unused [PB001]"]);
+
+        let mut config = LintConfig::default();
+        config.set("PB001", LintLevel::Deny).unwrap();
+        assert_eq!(lint_with(source, config).unwrap_err(), "3:1: error: 're' imported but unused [PB001]");
+
+        let mut config = LintConfig::default();
+        config.set("all", LintLevel::Allow).unwrap();
+        assert!(lint_with("import re\ndef f():\n    return 1\n    f()\n", config).unwrap().is_empty());
+
+        let mut config = LintConfig::default();
+        assert_eq!(config.set("PB999", LintLevel::Deny).unwrap_err(), "Unknown lint rule 'PB999'");
+    }
+}
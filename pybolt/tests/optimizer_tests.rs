@@ -1,9 +1,54 @@
 #[cfg(test)]
 mod optimizer_tests {
-    use crate::optimizer::optimizer::Optimizer;
-    use crate::ir::ir::IRGenerator;
-    use crate::parser::parser::Parser;
-    use crate::lexer::lexer::Lexer;
+    use std::cell::RefCell;
+    use std::fs;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use pybolt::analysis::dominators::DominatorTree;
+    use pybolt::ir::ir::Module;
+    use pybolt::optimizer::instrumentation::diff;
+    use pybolt::lexer::lexer::Span;
+    use pybolt::optimizer::optimizer::{ConstantFolding, DeadCodeElimination, OptLevel, Optimization, Optimizer, PassContext};
+    use pybolt::optimizer::remarks::{self, Remark, RemarkFilter, RemarkFormat, RemarkKind};
+    use pybolt::optimizer::gvn::GlobalValueNumbering;
+    use pybolt::optimizer::induction::{InductionVariableSimplification, StrengthReduction};
+    use pybolt::optimizer::inliner::Inliner;
+    use pybolt::optimizer::licm::LoopInvariantCodeMotion;
+    use pybolt::optimizer::loop_deletion::LoopDeletion;
+    use pybolt::optimizer::narrowing::RangeNarrowing;
+    use pybolt::optimizer::sccp::Sccp;
+    use pybolt::optimizer::specialize::TypeSpecialization;
+    use pybolt::optimizer::sroa::ScalarReplacement;
+    use pybolt::optimizer::unboxing::Unboxing;
+    use pybolt::optimizer::unroll::LoopUnrolling;
+    use pybolt::ir::generator::IRGenerator;
+    use pybolt::ir::parser;
+    use pybolt::ir::verifier::verify;
+    use pybolt::parser::parser::Parser;
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+
+    /// Runs `pass` on `tests/golden/<name>.ir` and compares the result with
+    /// `tests/golden/<name>.expected`. Set `PYBOLT_BLESS=1` to write the
+    /// result to the expected file instead.
+    fn check_golden(name: &str, pass: &dyn Optimization) {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+        let input = fs::read_to_string(directory.join(format!("{}.ir", name))).unwrap();
+        let mut output = parser::parse(&input).unwrap();
+        verify(&output).unwrap();
+        assert!(pass.apply(&mut output, &mut PassContext::new()).unwrap(), "{} does not change {}", pass.name(), name);
+        verify(&output).unwrap();
+        let output = output.to_string();
+        let expected_path = directory.join(format!("{}.expected", name));
+        if std::env::var_os("PYBOLT_BLESS").is_some() {
+            fs::write(&expected_path, &output).unwrap();
+        }
+        let expected = fs::read_to_string(&expected_path).unwrap();
+        assert_eq!(output, expected, "{} does not match {}", name, expected_path.display());
+        // The output must itself be valid input.
+        assert_eq!(parser::parse(&output).unwrap().to_string(), output);
+    }
 
     #[test]
     fn test_optimization() {
@@ -11,11 +56,381 @@ mod optimizer_tests {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer);
         parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
 
         let mut ir_generator = IRGenerator::new();
-        ir_generator.generate(&parser).unwrap();
+        ir_generator.generate(analyzer.hir().unwrap()).unwrap();
 
         let mut optimizer = Optimizer::new();
-        assert!(optimizer.optimize(&ir_generator).is_ok());
+        assert!(optimizer.optimize(ir_generator.get_ir().clone()).is_ok());
+    }
+
+    #[test]
+    fn test_constant_folding_golden() {
+        check_golden("constant_folding", &ConstantFolding);
+    }
+
+    #[test]
+    fn test_dead_code_elimination_golden() {
+        check_golden("dead_code_elimination", &DeadCodeElimination);
+    }
+
+    #[test]
+    fn test_sccp_golden() {
+        check_golden("sccp", &Sccp);
+    }
+
+    #[test]
+    fn test_gvn_golden() {
+        check_golden("gvn", &GlobalValueNumbering);
+    }
+
+    #[test]
+    fn test_inliner_golden() {
+        check_golden("inliner", &Inliner::new());
+    }
+
+    #[test]
+    fn test_licm_golden() {
+        check_golden("licm", &LoopInvariantCodeMotion);
+    }
+
+    #[test]
+    fn test_indvars_golden() {
+        check_golden("indvars", &InductionVariableSimplification);
+    }
+
+    #[test]
+    fn test_strength_reduction_golden() {
+        check_golden("strength_reduction", &StrengthReduction);
+    }
+
+    #[test]
+    fn test_range_narrowing_golden() {
+        check_golden("range_narrowing", &RangeNarrowing);
+    }
+
+    #[test]
+    fn test_bounds_checks_golden() {
+        check_golden("bounds_checks", &RangeNarrowing);
+    }
+
+    #[test]
+    fn test_loop_unroll_golden() {
+        check_golden("loop_unroll", &LoopUnrolling::new());
+    }
+
+    #[test]
+    fn test_loop_deletion_golden() {
+        check_golden("loop_deletion", &LoopDeletion);
+    }
+
+    #[test]
+    fn test_specialize_golden() {
+        check_golden("specialize", &TypeSpecialization);
+    }
+
+    #[test]
+    fn test_sroa_golden() {
+        check_golden("sroa", &ScalarReplacement);
+    }
+
+    #[test]
+    fn test_unbox_golden() {
+        check_golden("unbox", &Unboxing);
+    }
+
+    /// Folding `%sum` leaves its operands unused, which only a second run
+    /// of `dce` can remove when it comes first.
+    const FOLDS_INTO_DEAD_CODE: &str = "fn @f() -> i64 {\n\
+                                        bb0:\n\
+                                        \x20   %a: i64 = const 2\n\
+                                        \x20   %b: i64 = const 3\n\
+                                        \x20   %sum: i64 = add %a, %b\n\
+                                        \x20   ret %sum\n\
+                                        }\n";
+
+    fn optimize(optimizer: &mut Optimizer, text: &str) -> String {
+        optimizer.optimize(parser::parse(text).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_passes_report_whether_they_changed_the_ir() {
+        let mut module = parser::parse(FOLDS_INTO_DEAD_CODE).unwrap();
+        let mut context = PassContext::new();
+        assert!(!DeadCodeElimination.apply(&mut module, &mut context).unwrap());
+        assert!(ConstantFolding.apply(&mut module, &mut context).unwrap());
+        assert!(!ConstantFolding.apply(&mut module, &mut context).unwrap());
+        assert!(DeadCodeElimination.apply(&mut module, &mut context).unwrap());
+        assert!(!DeadCodeElimination.apply(&mut module, &mut context).unwrap());
+    }
+
+    #[test]
+    fn test_pipelines_run_to_a_fixed_point() {
+        let folded = "fn @f() -> i64 {\nbb0:\n    %0: i64 = const 5\n    ret %0\n}\n";
+        assert_eq!(optimize(&mut Optimizer::with_pipeline("dce,fold").unwrap(), FOLDS_INTO_DEAD_CODE), folded);
+        let mut once = Optimizer::with_pipeline("dce,fold").unwrap();
+        once.set_max_iterations(1);
+        assert!(optimize(&mut once, FOLDS_INTO_DEAD_CODE).contains("const 3"));
+        assert_eq!(optimize(&mut Optimizer::with_level(OptLevel::O1), FOLDS_INTO_DEAD_CODE), folded);
+
+        let unchanged = parser::parse(FOLDS_INTO_DEAD_CODE).unwrap().to_string();
+        assert_eq!(optimize(&mut Optimizer::with_level(OptLevel::O0), FOLDS_INTO_DEAD_CODE), unchanged);
+    }
+
+    #[test]
+    fn test_pipelines_are_configurable() {
+        assert!(OptLevel::O0.pipeline().is_empty());
+        assert_eq!(Optimizer::with_level(OptLevel::O2).pipeline(), OptLevel::O2.pipeline());
+        assert!(OptLevel::O3.pipeline().contains(&"loop-unroll") && !OptLevel::O2.pipeline().contains(&"loop-unroll"));
+        assert!(OptLevel::O2.pipeline().contains(&"specialize") && !OptLevel::Os.pipeline().contains(&"specialize"));
+        let mut optimizer = Optimizer::with_pipeline("fold, dead-code-elimination").unwrap();
+        assert_eq!(optimizer.pipeline(), vec!["constant-folding", "dead-code-elimination"]);
+        optimizer.disable("fold").unwrap();
+        assert_eq!(optimizer.pipeline(), vec!["dead-code-elimination"]);
+        optimizer.enable("dce").unwrap();
+        optimizer.enable("constant-folding").unwrap();
+        assert_eq!(optimizer.pipeline(), vec!["dead-code-elimination", "constant-folding"]);
+        assert_eq!(optimizer.enable("vectorize").unwrap_err(), "Unknown optimization pass 'vectorize'");
+        assert_eq!(Optimizer::with_pipeline("fold,vectorize").err(), Some("Unknown optimization pass 'vectorize'".to_string()));
+        assert!(Optimizer::with_pipeline("").unwrap().pipeline().is_empty());
+    }
+
+    /// Records the dominator tree of the first function it is given.
+    struct RecordDominators(Rc<RefCell<Vec<Rc<DominatorTree>>>>);
+
+    impl Optimization for RecordDominators {
+        fn name(&self) -> &'static str {
+            "record-dominators"
+        }
+
+        fn apply(&self, ir: &mut Module, context: &mut PassContext) -> Result<bool, String> {
+            self.0.borrow_mut().push(context.analyses.dominators(&ir.functions[0]));
+            Ok(false)
+        }
+    }
+
+    #[test]
+    fn test_analyses_are_kept_until_a_pass_changes_the_ir() {
+        let recorded = Rc::new(RefCell::new(Vec::new()));
+        let record = || Box::new(RecordDominators(recorded.clone()));
+        let mut optimizer =
+            Optimizer::with_optimizations(vec![record(), Box::new(DeadCodeElimination), record(), Box::new(ConstantFolding), record()]);
+        optimize(&mut optimizer, FOLDS_INTO_DEAD_CODE);
+        let recorded = recorded.borrow();
+        assert!(Rc::ptr_eq(&recorded[0], &recorded[1]));
+        assert!(!Rc::ptr_eq(&recorded[1], &recorded[2]));
+    }
+
+    #[test]
+    fn test_passes_are_timed_and_counted() {
+        let mut optimizer = Optimizer::with_pipeline("dce,fold").unwrap();
+        optimize(&mut optimizer, FOLDS_INTO_DEAD_CODE);
+        let statistics = optimizer.statistics();
+        let dce = statistics.timing("dead-code-elimination").unwrap();
+        assert_eq!((dce.runs, dce.changes), (3, 1));
+        let fold = statistics.timing("constant-folding").unwrap();
+        assert_eq!((fold.runs, fold.changes), (3, 1));
+        assert_eq!(statistics.counter("constant-folding", "constants folded"), 1);
+        assert_eq!(statistics.counter("dead-code-elimination", "instructions removed"), 2);
+        assert_eq!(statistics.timings().map(|(name, _)| name).collect::<Vec<_>>(), vec!["dead-code-elimination", "constant-folding"]);
+        assert_eq!(statistics.to_string(), "1 constant-folding - constants folded\n2 dead-code-elimination - instructions removed\n");
+        let report = statistics.timing_report().to_string();
+        assert!(report.starts_with("pass                   runs  changed        time\ndead-code-elimination     3        1"), "{}", report);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), "");
+        let before = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let after = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n12\n13\n";
+        assert_eq!(
+            diff(before, after),
+            "@@ -1,5 +1,5 @@\n 1\n 2\n-3\n+three\n 4\n 5\n@@ -9,4 +9,4 @@\n 9\n 10\n-11\n 12\n+13\n"
+        );
+        // Changes closer than twice the context share a hunk.
+        assert_eq!(diff("a\nb\nc\nd\ne\n", "A\nb\nc\nd\nE\n"), "@@ -1,5 +1,5 @@\n-a\n+A\n b\n c\n d\n-e\n+E\n");
+        assert_eq!(diff("", "x\n"), "@@ -1,0 +1,1 @@\n+x\n");
+    }
+
+    #[test]
+    fn test_passes_explain_what_they_did() {
+        let text = "fn @f() -> int {\n\
+                    bb0:\n\
+                    \x20   %a: int = const 7\n\
+                    \x20   %zero: int = const 0\n\
+                    \x20   %b: int = neg %a\n\
+                    \x20   %c: int = floordiv %b, %zero\n\
+                    \x20   ret %c\n\
+                    }\n";
+        let mut optimizer = Optimizer::with_pipeline("fold,dce").unwrap();
+        optimize(&mut optimizer, text);
+        let remarks: Vec<_> = optimizer.remarks().iter().map(|remark| (remark.kind, remark.pass, remark.message.as_str())).collect();
+        assert_eq!(
+            remarks,
+            vec![
+                (RemarkKind::Passed, "constant-folding", "folded `neg` into `-7`"),
+                (RemarkKind::Missed, "constant-folding", "could not fold `floordiv` of constants: it raises at runtime"),
+                (RemarkKind::Passed, "dead-code-elimination", "removed unused `const`"),
+            ]
+        );
+        assert!(optimizer.remarks().iter().all(|remark| remark.function == "f"));
+    }
+
+    #[test]
+    fn test_remarks_render_as_text_yaml_and_json() {
+        let source = "x = 1\ny = 'it''s' * 2\n";
+        let remark = Remark {
+            kind: RemarkKind::Missed,
+            pass: "constant-folding",
+            function: "__main__".to_string(),
+            span: Span::new(6, 21),
+            message: "could not fold `mul`: it's a string".to_string(),
+        };
+        let remarks = [remark];
+        assert_eq!(
+            remarks::render(&remarks, source, RemarkFormat::Human),
+            "2:1: remark: could not fold `mul`: it's a string [-Rpass-missed=constant-folding]\n"
+        );
+        assert_eq!(
+            remarks::render(&remarks, source, RemarkFormat::Yaml),
+            "--- !Missed\n\
+             Pass:            constant-folding\n\
+             Function:        '__main__'\n\
+             DebugLoc:        { Line: 2, Column: 1 }\n\
+             Message:         'could not fold `mul`: it''s a string'\n\
+             ...\n"
+        );
+        assert_eq!(
+            remarks::render(&remarks, source, RemarkFormat::Json),
+            "[{\"kind\":\"missed\",\"pass\":\"constant-folding\",\"function\":\"__main__\",\"line\":2,\"column\":1,\
+             \"span\":[6,21],\"message\":\"could not fold `mul`: it's a string\"}]\n"
+        );
+        assert_eq!(remarks::render(&[], source, RemarkFormat::Json), "[]\n");
+    }
+
+    #[test]
+    fn test_remark_filters() {
+        let remark = |kind, pass| Remark { kind, pass, function: "f".to_string(), span: Span::default(), message: String::new() };
+        let mut filter = RemarkFilter::new();
+        assert!(!filter.is_enabled());
+        filter.add("pass=fold|inline").unwrap();
+        filter.add("pass-missed=.*").unwrap();
+        assert!(filter.is_enabled());
+        assert!(filter.matches(&remark(RemarkKind::Passed, "constant-folding")));
+        assert!(!filter.matches(&remark(RemarkKind::Passed, "dead-code-elimination")));
+        assert!(filter.matches(&remark(RemarkKind::Missed, "dead-code-elimination")));
+        assert!(!filter.matches(&remark(RemarkKind::Analysis, "constant-folding")));
+        assert_eq!(filter.add("vectorize=.*").unwrap_err(), "Unknown remark kind 'vectorize', expected pass, pass-missed or pass-analysis");
+        assert!(filter.add("pass-analysis=(").unwrap_err().starts_with("Invalid remark pattern '('"));
+    }
+
+    #[test]
+    fn test_gvn_explains_loads_it_keeps() {
+        let text = "fn @f() -> int {\n\
+                    bb0:\n\
+                    \x20   %a: int = load_global @g\n\
+                    \x20   %b: int = load_global @g\n\
+                    \x20   %c: int = call @f()\n\
+                    \x20   %d: int = load_global @g\n\
+                    \x20   %sum: int = add %a, %b\n\
+                    \x20   %total: int = add %sum, %d\n\
+                    \x20   ret %total\n\
+                    }\n";
+        let mut module = parser::parse(text).unwrap();
+        let mut context = PassContext::new();
+        assert!(GlobalValueNumbering.apply(&mut module, &mut context).unwrap());
+        let messages: Vec<_> = context.remarks.iter().map(|remark| (remark.kind, remark.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![
+                (RemarkKind::Passed, "removed `load_global @g`: the value of the global is already known"),
+                (RemarkKind::Missed, "could not reuse the earlier value of global `g`: a `call` in between may change it"),
+            ]
+        );
+        let counters: Vec<_> = context.statistics.counters().map(|(_, counter, amount)| (counter, amount)).collect();
+        assert_eq!(counters, vec![("redundant loads removed", 1)]);
+        assert_eq!(module.functions[0].instruction_count(), 5);
+    }
+
+    #[test]
+    fn test_sroa_explains_tuples_it_keeps() {
+        // The tuples `%l` and `%r` are only returned, through `%m`; `%p`
+        // is also passed to a call, which is what is reported.
+        let text = "fn @f(bool, object, object) -> object {\n\
+                    bb0(%c: bool, %x: object, %y: object):\n\
+                    \x20   %p: object = tuple %x, %y\n\
+                    \x20   %n: none = call @g(%p)\n\
+                    \x20   cond_br %c, left, right\n\
+                    left:\n\
+                    \x20   %l: object = tuple %x, %y\n\
+                    \x20   br merge(%l)\n\
+                    right:\n\
+                    \x20   br merge(%p)\n\
+                    merge(%m: object):\n\
+                    \x20   ret %m\n\
+                    }\n\
+                    fn @g(object) -> none {\n\
+                    bb0(%t: object):\n\
+                    \x20   %none: none = const none\n\
+                    \x20   ret %none\n\
+                    }\n";
+        let mut module = parser::parse(text).unwrap();
+        let mut context = PassContext::new();
+        assert!(!ScalarReplacement.apply(&mut module, &mut context).unwrap());
+        let messages: Vec<_> = context.remarks.iter().map(|remark| (remark.kind, remark.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![
+                (RemarkKind::Missed, "kept a tuple on the heap: it is passed to `g`"),
+                (
+                    RemarkKind::Missed,
+                    "kept a tuple on the heap: it is returned, and only goes where `f` is inlined into a caller that unpacks it"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inliner_keeps_large_and_recursive_calls() {
+        let text = "fn @f(int) -> int {\n\
+                    bb0(%x: int):\n\
+                    \x20   %a: int = call @big(%x)\n\
+                    \x20   %b: int = call @big(%a)\n\
+                    \x20   %c: int = call @f(%b)\n\
+                    \x20   ret %c\n\
+                    }\n\
+                    fn @big(int) -> int {\n\
+                    bb0(%x: int):\n\
+                    \x20   %y: int = mul %x, %x\n\
+                    \x20   %z: int = add %y, %x\n\
+                    \x20   %w: int = sub %z, %x\n\
+                    \x20   ret %w\n\
+                    }\n";
+        let mut module = parser::parse(text).unwrap();
+        let mut context = PassContext::new();
+        let inliner = Inliner { threshold: 1, recursion_limit: 0 };
+        assert!(!inliner.apply(&mut module, &mut context).unwrap());
+        let messages: Vec<_> = context.remarks.iter().map(|remark| (remark.kind, remark.message.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![
+                (RemarkKind::Missed, "did not inline `big` into `f`: the leaf function has 3 instructions, over the threshold of 2"),
+                (RemarkKind::Missed, "did not inline `f` into `f`: it is recursive, and the recursion limit is 0"),
+            ]
+        );
+
+        // With a limit of 1, `f` is inlined into itself once, and the call
+        // it brings along is not.
+        let inliner = Inliner { threshold: 10, recursion_limit: 1 };
+        assert!(inliner.apply(&mut module, &mut context).unwrap());
+        verify(&module).unwrap();
+        let counters: Vec<_> = context.statistics.counters().map(|(_, counter, amount)| (counter, amount)).collect();
+        assert_eq!(counters, vec![("calls inlined", 3), ("functions removed", 1)]);
+        assert!(!inliner.apply(&mut module, &mut context).unwrap());
+        let f = module.function("f").unwrap().to_string();
+        assert_eq!(f.matches("call @f").count(), 1, "{}", f);
+        assert!(!f.contains("call @big"), "{}", f);
     }
 }
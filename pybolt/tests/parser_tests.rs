@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod parser_tests {
-    use crate::parser::parser::Parser;
-    use crate::lexer::lexer::Lexer;
+    use pybolt::parser::cst::{CstElement, SyntaxKind};
+    use pybolt::parser::parser::{ASTNode, ASTNodeType, Parser};
+    use pybolt::parser::serialize;
+    use pybolt::parser::visitor::{walk_node_mut, Visitor, VisitorMut};
+    use pybolt::lexer::lexer::Lexer;
 
     #[test]
     fn test_parser() {
@@ -10,4 +13,255 @@ mod parser_tests {
         let mut parser = Parser::new(lexer);
         assert!(parser.parse().is_ok());
     }
+
+    #[test]
+    fn test_operator_precedence() {
+        let mut parser = Parser::new(Lexer::new("let x = 1 + 2 * 3;"));
+        let ast = parser.parse().unwrap();
+        let ASTNode::Program(statements) = ast else { panic!("expected a program") };
+        let ASTNode::VariableDeclaration { value, .. } = &statements[0] else { panic!("expected a declaration") };
+        let ASTNode::BinaryOperation { operator, right, .. } = value.as_ref() else { panic!("expected a binary operation") };
+        assert_eq!(operator, "+");
+        assert!(matches!(right.as_ref(), ASTNode::BinaryOperation { operator, .. } if operator == "*"));
+    }
+
+    #[test]
+    fn test_cst_round_trip() {
+        let input = "# leading comment\nlet  x = ( 1 +2 )*3 ;  # trailing\n\nx\n";
+        let mut parser = Parser::new(Lexer::new(input));
+        let cst = parser.parse_cst().unwrap();
+        assert_eq!(cst.to_source(), input);
+    }
+
+    #[test]
+    fn test_cst_keeps_parentheses() {
+        let mut parser = Parser::new(Lexer::new("let x = (1 + 2) * 3;"));
+        let cst = parser.parse_cst().unwrap();
+        let declaration = cst.child_nodes().next().unwrap();
+        assert_eq!(declaration.kind, SyntaxKind::VariableDeclaration);
+        let product = declaration.child_nodes().next().unwrap();
+        assert_eq!(product.kind, SyntaxKind::BinaryExpression);
+        let operand = product.child_nodes().next().unwrap();
+        assert_eq!(operand.kind, SyntaxKind::ParenthesizedExpression);
+        assert_eq!(operand.to_source(), " (1 + 2)");
+        assert!(matches!(operand.children.first(), Some(CstElement::Token(token)) if token.token.lexeme == "("));
+    }
+
+    #[derive(Default)]
+    struct NameCollector {
+        names: Vec<String>,
+        numbers: Vec<i64>,
+    }
+
+    impl Visitor for NameCollector {
+        fn visit_identifier(&mut self, name: &str) {
+            self.names.push(name.to_string());
+        }
+
+        fn visit_number(&mut self, value: i64) {
+            self.numbers.push(value);
+        }
+    }
+
+    #[test]
+    fn test_visitor_walks_every_node() {
+        let mut parser = Parser::new(Lexer::new("let x = 1; x + (y * 2);"));
+        let ast = parser.parse().unwrap();
+        let mut collector = NameCollector::default();
+        collector.visit_node(&ast);
+        assert_eq!(collector.names, vec!["x", "y"]);
+        assert_eq!(collector.numbers, vec![1, 2]);
+    }
+
+    struct DoubleToAddition;
+
+    impl VisitorMut for DoubleToAddition {
+        fn visit_node_mut(&mut self, node: &mut ASTNode) {
+            walk_node_mut(self, node);
+            if let ASTNode::BinaryOperation { left, operator, right } = node {
+                if operator == "*" && matches!(right.as_ref(), ASTNode::Number(2)) {
+                    *node = ASTNode::BinaryOperation { left: left.clone(), operator: "+".to_string(), right: left.clone() };
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_rewrites_nodes() {
+        let mut parser = Parser::new(Lexer::new("y * 2;"));
+        let mut ast = parser.parse().unwrap();
+        DoubleToAddition.visit_node_mut(&mut ast);
+        let ASTNode::Program(statements) = ast else { panic!("expected a program") };
+        let ASTNode::ExpressionStatement { expression, .. } = &statements[0] else { panic!("expected an expression statement") };
+        let ASTNode::BinaryOperation { left, operator, right } = expression.as_ref() else { panic!("expected a binary operation") };
+        assert_eq!(operator, "+");
+        assert!(matches!(left.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if name == "y"));
+        assert!(matches!(right.as_ref(), ASTNode::Expression(ASTNodeType::Identifier(name)) if name == "y"));
+    }
+
+    #[test]
+    fn test_ast_to_json() {
+        let mut parser = Parser::new(Lexer::new("let x = 1 + y;"));
+        let ast = parser.parse().unwrap();
+        assert_eq!(
+            serialize::to_json(&ast),
+            r#"{"type":"Program","body":[{"type":"VariableDeclaration","name":"x","value":{"type":"BinaryOperation","left":{"type":"Number","value":1},"operator":"+","right":{"type":"Identifier","name":"y"}},"span":[0,13]}]}"#
+        );
+    }
+
+    #[test]
+    fn test_ast_dump_matches_cpython() {
+        let mut parser = Parser::new(Lexer::new("x = y = 1 + 2 * 3\nx\n"));
+        let ast = parser.parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Assign(targets=[Name(id='x', ctx=Store()), Name(id='y', ctx=Store())], \
+             value=BinOp(left=Constant(value=1), op=Add(), right=BinOp(left=Constant(value=2), op=Mult(), \
+             right=Constant(value=3)))), Expr(value=Name(id='x', ctx=Load()))], type_ignores=[])"
+        );
+    }
+
+    const FUNCTION_SOURCE: &str = "@cache\n\
+        def area(w, h=2, *, scale: float = 1.0) -> float:\n\
+        \x20   if w > 0 and not h is None:  # positive\n\
+        \x20       return w * h * scale\n\
+        \n\
+        \x20   return [i for i in range(w) if i % 2]\n";
+
+    #[test]
+    fn test_python_function_matches_cpython() {
+        let mut parser = Parser::new(Lexer::new(FUNCTION_SOURCE));
+        let ast = parser.parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[FunctionDef(name='area', args=arguments(posonlyargs=[], args=[arg(arg='w'), arg(arg='h')], \
+             kwonlyargs=[arg(arg='scale', annotation=Name(id='float', ctx=Load()))], kw_defaults=[Constant(value=1.0)], \
+             defaults=[Constant(value=2)]), body=[If(test=BoolOp(op=And(), values=[Compare(left=Name(id='w', ctx=Load()), \
+             ops=[Gt()], comparators=[Constant(value=0)]), UnaryOp(op=Not(), operand=Compare(left=Name(id='h', ctx=Load()), \
+             ops=[Is()], comparators=[Constant(value=None)]))]), body=[Return(value=BinOp(left=BinOp(left=Name(id='w', \
+             ctx=Load()), op=Mult(), right=Name(id='h', ctx=Load())), op=Mult(), right=Name(id='scale', ctx=Load())))], \
+             orelse=[]), Return(value=ListComp(elt=Name(id='i', ctx=Load()), generators=[comprehension(target=Name(id='i', \
+             ctx=Store()), iter=Call(func=Name(id='range', ctx=Load()), args=[Name(id='w', ctx=Load())], keywords=[]), \
+             ifs=[BinOp(left=Name(id='i', ctx=Load()), op=Mod(), right=Constant(value=2))], is_async=0)]))], \
+             decorator_list=[Name(id='cache', ctx=Load())], returns=Name(id='float', ctx=Load()))], type_ignores=[])"
+        );
+    }
+
+    #[test]
+    fn test_cst_round_trip_with_indentation() {
+        let input = format!("{}class Point(Base):\n    def __init__(self, x, y):\n        self.x, self.y = x, y\n", FUNCTION_SOURCE);
+        let mut parser = Parser::new(Lexer::new(&input));
+        let cst = parser.parse_cst().unwrap();
+        assert_eq!(cst.to_source(), input);
+        let kinds: Vec<_> = cst.child_nodes().map(|node| node.kind).collect();
+        assert_eq!(kinds, vec![SyntaxKind::FunctionDefinition, SyntaxKind::ClassDefinition]);
+    }
+
+    #[test]
+    fn test_try_statement_matches_cpython() {
+        let input = "try:\n    x = load()\nexcept (KeyError, ValueError) as error:\n    raise RuntimeError() from error\n\
+                     except:\n    raise\nelse:\n    pass\nfinally:\n    close()\n";
+        let mut parser = Parser::new(Lexer::new(input));
+        let ast = parser.parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Try(body=[Assign(targets=[Name(id='x', ctx=Store())], value=Call(func=Name(id='load', ctx=Load()), \
+             args=[], keywords=[]))], handlers=[ExceptHandler(type=Tuple(elts=[Name(id='KeyError', ctx=Load()), \
+             Name(id='ValueError', ctx=Load())], ctx=Load()), name='error', body=[Raise(exc=Call(func=Name(id='RuntimeError', \
+             ctx=Load()), args=[], keywords=[]), cause=Name(id='error', ctx=Load()))]), ExceptHandler(body=[Raise()])], \
+             orelse=[Pass()], finalbody=[Expr(value=Call(func=Name(id='close', ctx=Load()), args=[], keywords=[]))])], \
+             type_ignores=[])"
+        );
+        let cst = Parser::new(Lexer::new(input)).parse_cst().unwrap();
+        assert_eq!(cst.to_source(), input);
+
+        let error = Parser::new(Lexer::new("try:\n    pass\nx = 1\n")).parse().unwrap_err();
+        assert_eq!(error, "expected 'except' or 'finally' block");
+    }
+
+    #[test]
+    fn test_with_statement_matches_cpython() {
+        let input = "with open('a') as f, lock:\n    pass\n";
+        let mut parser = Parser::new(Lexer::new(input));
+        let ast = parser.parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[With(items=[withitem(context_expr=Call(func=Name(id='open', ctx=Load()), args=[Constant(value='a')], \
+             keywords=[]), optional_vars=Name(id='f', ctx=Store())), withitem(context_expr=Name(id='lock', ctx=Load()))], \
+             body=[Pass()])], type_ignores=[])"
+        );
+        let cst = Parser::new(Lexer::new(input)).parse_cst().unwrap();
+        assert_eq!(cst.to_source(), input);
+    }
+
+    #[test]
+    fn test_inconsistent_dedent_is_an_error() {
+        let mut parser = Parser::new(Lexer::new("if x:\n        y = 1\n    z = 2\n"));
+        assert_eq!(parser.parse().unwrap_err(), "Unindent does not match any outer indentation level");
+    }
+
+    #[test]
+    fn test_prefixed_and_imaginary_literals() {
+        let input = "x = 0x1F + 0o17 + 0B101 + 0xdead_beef\ny = 1j + 2.5J + 1e3j\n";
+        let cst = Parser::new(Lexer::new(input)).parse_cst().unwrap();
+        assert_eq!(cst.to_source(), input);
+        let ast = Parser::new(Lexer::new(input)).parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Assign(targets=[Name(id='x', ctx=Store())], value=BinOp(left=BinOp(left=BinOp(\
+             left=Constant(value=31), op=Add(), right=Constant(value=15)), op=Add(), right=Constant(value=5)), \
+             op=Add(), right=Constant(value=3735928559))), Assign(targets=[Name(id='y', ctx=Store())], \
+             value=BinOp(left=BinOp(left=Constant(value=1j), op=Add(), right=Constant(value=2.5j)), op=Add(), \
+             right=Constant(value=1000j)))], type_ignores=[])"
+        );
+    }
+
+    #[test]
+    fn test_integer_literals_of_any_size() {
+        let input = "x = 100000000000000000000 + 0x1_0000_0000_0000_0000\n";
+        let ast = Parser::new(Lexer::new(input)).parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Assign(targets=[Name(id='x', ctx=Store())], value=BinOp(left=Constant(\
+             value=100000000000000000000), op=Add(), right=Constant(value=18446744073709551616)))], type_ignores=[])"
+        );
+    }
+
+    #[test]
+    fn test_f_strings_and_bytes_match_cpython() {
+        let input = "f\"a{b!r:>{w}} {c=}\" 'z'\nb'\\x00A\\101\\u'\nf'{x, y}{{}}' rf'\\n{f\"{d}\"}'\n";
+        let ast = Parser::new(Lexer::new(input)).parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Expr(value=JoinedStr(values=[Constant(value='a'), FormattedValue(value=Name(id='b', ctx=Load()), \
+             conversion=114, format_spec=JoinedStr(values=[Constant(value='>'), FormattedValue(value=Name(id='w', ctx=Load()), \
+             conversion=-1)])), Constant(value=' c='), FormattedValue(value=Name(id='c', ctx=Load()), conversion=114), \
+             Constant(value='z')])), Expr(value=Constant(value=b'\\x00AA\\\\u')), Expr(value=JoinedStr(values=[\
+             FormattedValue(value=Tuple(elts=[Name(id='x', ctx=Load()), Name(id='y', ctx=Load())], ctx=Load()), \
+             conversion=-1), Constant(value='{}\\\\n'), FormattedValue(value=JoinedStr(values=[FormattedValue(\
+             value=Name(id='d', ctx=Load()), conversion=-1)]), conversion=-1)]))], type_ignores=[])"
+        );
+    }
+
+    #[test]
+    fn test_escape_sequences_are_decoded() {
+        let input = "x = '\\x41\\101\\u00e9\\U0001F600\\N{BULLET}\\a\\0\\q'\n";
+        let ast = Parser::new(Lexer::new(input)).parse().unwrap();
+        assert_eq!(
+            serialize::dump(&ast),
+            "Module(body=[Assign(targets=[Name(id='x', ctx=Store())], value=Constant(value='AAé😀•\\x07\\x00\\\\q'))], type_ignores=[])"
+        );
+        for (input, error) in [
+            ("'\\x4'", "Truncated \\x escape"),
+            ("'\\N{NO SUCH NAME}'", "Unknown Unicode character name 'NO SUCH NAME'"),
+            ("b'é'", "Bytes can only contain ASCII literal characters"),
+            ("'a' b'b'", "Cannot mix bytes and nonbytes literals"),
+            ("f'{}'", "f-string: empty expression not allowed"),
+            ("f'a}'", "f-string: single '}' is not allowed"),
+            ("f'{a!x}'", "f-string: invalid conversion character: expected 's', 'r', or 'a'"),
+            ("f'{a b}'", "f-string: unexpected 'b'"),
+        ] {
+            assert_eq!(Parser::new(Lexer::new(input)).parse().unwrap_err(), error, "{}", input);
+        }
+    }
 }
@@ -1,10 +1,187 @@
 #[cfg(test)]
 mod runtime_tests {
-    use crate::runtime::runtime::Runtime;
+    use std::rc::Rc;
+
+    use pybolt::runtime::bigint::BigInt;
+    use pybolt::runtime::runtime::{
+        arithmetic, check_index, compare, int_arithmetic, int_negate, len, Arithmetic, Comparison, Instruction, Runtime, Value,
+    };
+
+    fn power(base: i64, exponent: u32) -> BigInt {
+        (0..exponent).fold(BigInt::from(1), |product, _| &product * &BigInt::from(base))
+    }
+
+    fn big(value: BigInt) -> Value {
+        Value::BigInt(Rc::new(value))
+    }
+
+    /// Python's `//` and `%` on integers small enough for an `i128`.
+    fn floor_div_mod(left: i128, right: i128) -> (i128, i128) {
+        let (quotient, remainder) = (left / right, left % right);
+        if remainder != 0 && (remainder < 0) != (right < 0) {
+            (quotient - 1, remainder + right)
+        } else {
+            (quotient, remainder)
+        }
+    }
 
     #[test]
     fn test_runtime() {
-        let mut runtime = Runtime::new();
+        let mut runtime = Runtime::new(Vec::new());
         assert!(runtime.run().is_ok());
     }
+
+    #[test]
+    fn test_big_integers_match_i128() {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            // Mostly values around 64 bits, where the fast path ends.
+            let bits = [8, 31, 32, 33, 62, 63, 64][(state % 7) as usize];
+            let value = (state >> 1) as i128 & ((1i128 << bits) - 1);
+            if state & 1 == 0 { value } else { -value }
+        };
+        for _ in 0..2000 {
+            let (left, right) = (next(), next());
+            let (big_left, big_right) = (BigInt::parse(&left.to_string()).unwrap(), BigInt::parse(&right.to_string()).unwrap());
+            assert_eq!(big_left.to_string(), left.to_string());
+            assert_eq!((&big_left + &big_right).to_string(), (left + right).to_string());
+            assert_eq!((&big_left - &big_right).to_string(), (left - right).to_string());
+            assert_eq!((&big_left * &big_right).to_string(), (left * right).to_string());
+            assert_eq!(big_left.cmp(&big_right), left.cmp(&right));
+            if right != 0 {
+                let (quotient, remainder) = big_left.div_mod_floor(&big_right).unwrap();
+                let expected = floor_div_mod(left, right);
+                assert_eq!((quotient.to_string(), remainder.to_string()), (expected.0.to_string(), expected.1.to_string()));
+            }
+        }
+        // Divisors of several digits, where the quotient estimate is wrong.
+        let dividend = &power(2, 200) - &BigInt::from(1);
+        let divisor = &power(2, 100) - &BigInt::from(1);
+        let (quotient, remainder) = dividend.div_mod_floor(&divisor).unwrap();
+        assert_eq!(quotient, &power(2, 100) + &BigInt::from(1));
+        assert!(remainder.is_zero());
+    }
+
+    #[test]
+    fn test_int_arithmetic_falls_back_to_big_integers() {
+        let max = Value::Int(i64::MAX);
+        let sum = int_arithmetic(Arithmetic::Add, &max, &Value::Int(1)).unwrap();
+        assert_eq!(sum, big(power(2, 63)));
+        assert_eq!(sum.to_string(), "9223372036854775808");
+        // Results that fit are small again.
+        assert_eq!(int_arithmetic(Arithmetic::Sub, &sum, &Value::Int(1)), Ok(max));
+        assert_eq!(int_arithmetic(Arithmetic::FloorDiv, &Value::Int(i64::MIN), &Value::Int(-1)), Ok(sum.clone()));
+        assert_eq!(int_arithmetic(Arithmetic::Mod, &Value::Int(i64::MIN), &Value::Int(-1)), Ok(Value::Int(0)));
+        assert_eq!(int_negate(&Value::Int(i64::MIN)), Ok(sum));
+        assert_eq!(int_arithmetic(Arithmetic::Add, &Value::Bool(true), &Value::Int(1)), Ok(Value::Int(2)));
+
+        let huge = big(&BigInt::from(-1) * &power(10, 20));
+        let seven = Value::Int(7);
+        assert_eq!(int_arithmetic(Arithmetic::FloorDiv, &huge, &seven).unwrap().to_string(), "-14285714285714285715");
+        assert_eq!(int_arithmetic(Arithmetic::Mod, &huge, &seven), Ok(Value::Int(5)));
+        let positive = big(power(10, 20));
+        assert_eq!(int_arithmetic(Arithmetic::Mod, &positive, &Value::Int(-7)), Ok(Value::Int(-5)));
+        assert!(int_arithmetic(Arithmetic::Mod, &positive, &Value::Int(0)).unwrap_err().starts_with("ZeroDivisionError"));
+    }
+
+    #[test]
+    fn test_int_division_and_conversion_round_as_python() {
+        let divide = |left: BigInt, right: BigInt| int_arithmetic(Arithmetic::Div, &big(left), &big(right));
+        assert_eq!(divide(&power(10, 30) + &BigInt::from(1), BigInt::from(3)), Ok(Value::Float(3.333333333333333e29)));
+        assert_eq!(divide(power(10, 400), power(10, 399)), Ok(Value::Float(10.0)));
+        assert_eq!(divide(BigInt::from(1), power(10, 310)), Ok(Value::Float(1e-310)));
+        assert_eq!(divide(BigInt::from(1), power(2, 1074)), Ok(Value::Float(5e-324)));
+        // Exactly half of the smallest float rounds to even, which is zero.
+        assert_eq!(divide(BigInt::from(1), power(2, 1075)), Ok(Value::Float(0.0)));
+        assert_eq!(divide(BigInt::from(3), power(2, 1076)), Ok(Value::Float(5e-324)));
+        assert_eq!(divide(&BigInt::from(-1) * &power(10, 25), BigInt::from(7)), Ok(Value::Float(-1.4285714285714285e24)));
+        assert!(divide(power(2, 1024), BigInt::from(1)).unwrap_err().starts_with("OverflowError"));
+
+        assert_eq!((&power(2, 53) + &BigInt::from(1)).to_f64(), Some(9007199254740992.0));
+        assert_eq!((&power(2, 53) + &BigInt::from(3)).to_f64(), Some(9007199254740996.0));
+        assert_eq!((&power(2, 1024) - &power(2, 971)).to_f64(), Some(f64::MAX));
+        assert_eq!((&power(2, 1024) - &power(2, 970)).to_f64(), None);
+        assert_eq!(BigInt::from_f64(-1e20), BigInt::parse("-100000000000000000000"));
+        assert_eq!(BigInt::from_f64(2.5), Some(BigInt::from(2)));
+    }
+
+    #[test]
+    fn test_ints_compare_exactly_with_floats() {
+        let exact = |comparison, left: &Value, right: &Value| compare(comparison, left, right).unwrap();
+        let odd = Value::Int((1 << 53) + 1);
+        assert!(!exact(Comparison::Equal, &odd, &Value::Float(9007199254740992.0)));
+        assert!(exact(Comparison::Greater, &odd, &Value::Float(9007199254740992.0)));
+        let googol = big(power(10, 100));
+        assert!(exact(Comparison::Greater, &googol, &Value::Float(1e99)));
+        assert!(exact(Comparison::Less, &googol, &Value::Float(f64::INFINITY)));
+        // The float nearest to 10**100 is a little larger.
+        assert!(exact(Comparison::Less, &googol, &Value::Float(1e100)));
+        assert!(exact(Comparison::Equal, &big(power(10, 20)), &Value::Float(1e20)));
+        assert!(!exact(Comparison::Equal, &googol, &Value::Float(f64::NAN)));
+        assert!(exact(Comparison::NotEqual, &googol, &Value::Float(f64::NAN)));
+        assert!(exact(Comparison::Less, &Value::Float(-0.5), &Value::Int(0)));
+        assert!(exact(Comparison::Greater, &Value::Float(1e20 + 16384.0), &big(power(10, 20))));
+    }
+
+    #[test]
+    fn test_tuples_print_compare_and_unpack_as_python() {
+        let tuple = |items: Vec<Value>| Value::Tuple(items.into());
+        let items = tuple(vec![Value::Int(1), Value::Str("it's".into()), Value::Float(2.0), Value::None, tuple(vec![Value::Bool(true)])]);
+        assert_eq!(items.to_string(), "(1, \"it's\", 2.0, None, (True,))");
+        assert_eq!(tuple(vec![Value::Str("a\nb\\".into())]).to_string(), "('a\\nb\\\\',)");
+        assert_eq!(tuple(Vec::new()).to_string(), "()");
+
+        let (one_two, one_three) = (tuple(vec![Value::Int(1), Value::Int(2)]), tuple(vec![Value::Int(1), Value::Int(3)]));
+        assert!(compare(Comparison::Less, &one_two, &one_three).unwrap());
+        assert!(compare(Comparison::Less, &tuple(vec![Value::Int(1)]), &one_two).unwrap());
+        assert!(compare(Comparison::Equal, &one_two, &tuple(vec![Value::Int(1), Value::Float(2.0)])).unwrap());
+        let concatenated = arithmetic(Arithmetic::Add, &one_two, &tuple(vec![Value::Int(3)])).unwrap();
+        assert_eq!(concatenated.to_string(), "(1, 2, 3)");
+
+        let unpack = |value: Value, length| {
+            let program = vec![
+                Instruction::LoadImmediate { var: "t".to_string(), value },
+                Instruction::Unpack { dest: "x".to_string(), src: "t".to_string(), index: 0, length },
+            ];
+            Runtime::new(program).run()
+        };
+        assert!(unpack(one_two.clone(), 2).is_ok());
+        assert!(unpack(Value::Str("ab".into()), 2).is_ok());
+        assert_eq!(unpack(one_two.clone(), 3), Err("ValueError: not enough values to unpack (expected 3, got 2)".to_string()));
+        assert_eq!(unpack(one_two, 1), Err("ValueError: too many values to unpack (expected 1)".to_string()));
+        assert_eq!(unpack(Value::Int(5), 2), Err("TypeError: cannot unpack non-iterable int object".to_string()));
+    }
+
+    #[test]
+    fn test_lists_are_shared_and_checked_as_python() {
+        let int = |value: i64| Value::Int(value);
+        assert_eq!(check_index(&int(2), &int(3)), Ok(int(2)));
+        assert_eq!(check_index(&int(-1), &int(3)), Ok(int(2)));
+        assert_eq!(check_index(&int(3), &int(3)), Err("IndexError: list index out of range".to_string()));
+        assert_eq!(check_index(&int(-4), &int(3)), Err("IndexError: list index out of range".to_string()));
+        assert_eq!(check_index(&big(power(2, 70)), &int(3)), Err("IndexError: cannot fit 'int' into an index-sized integer".to_string()));
+        assert_eq!(len(&Value::Str("héllo".into())), Ok(int(5)));
+        assert_eq!(len(&int(1)), Err("TypeError: object of type 'int' has no len()".to_string()));
+
+        let register = |name: &str| name.to_string();
+        let program = vec![
+            Instruction::LoadImmediate { var: register("one"), value: int(1) },
+            Instruction::LoadImmediate { var: register("two"), value: int(2) },
+            Instruction::LoadImmediate { var: register("last"), value: int(-1) },
+            Instruction::MakeList { dest: register("xs"), items: vec![register("one"), register("two")] },
+            Instruction::Copy { dest: register("ys"), src: register("xs") },
+            Instruction::Len { dest: register("n"), src: register("ys") },
+            Instruction::CheckIndex { dest: register("at"), index: register("last"), length: register("n") },
+            Instruction::SetItem { list: register("ys"), index: register("at"), src: register("one") },
+            Instruction::GetItem { dest: register("item"), src: register("xs"), index: register("at") },
+        ];
+        let mut runtime = Runtime::new(program);
+        runtime.run().unwrap();
+        assert_eq!(runtime.get_var_value("xs").unwrap().to_string(), "[1, 1]");
+        assert_eq!(runtime.get_var_value("item"), Some(&int(1)));
+        assert_eq!(runtime.get_var_value("n"), Some(&int(2)));
+    }
 }
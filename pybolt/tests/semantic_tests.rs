@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod semantic_tests {
-    use crate::semantic::semantic::SemanticAnalyzer;
-    use crate::parser::parser::Parser;
-    use crate::lexer::lexer::Lexer;
+    use pybolt::semantic::scope::{ScopeKind, SymbolKind, SymbolTable};
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+    use pybolt::parser::parser::Parser;
+    use pybolt::lexer::lexer::Lexer;
 
     #[test]
     fn test_semantic_analysis() {
@@ -14,4 +15,271 @@ mod semantic_tests {
         let mut analyzer = SemanticAnalyzer::new();
         assert!(analyzer.analyze(&parser).is_ok());
     }
+
+    fn analyze(source: &str) -> Result<SymbolTable, String> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse()?;
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser)?;
+        Ok(analyzer.symbol_table().unwrap().clone())
+    }
+
+    fn kind(table: &SymbolTable, function: &str, name: &str) -> SymbolKind {
+        let scope = table.find_function(function).unwrap();
+        table.lookup(scope, name).unwrap().kind
+    }
+
+    #[test]
+    fn test_legb_resolution() {
+        let table = analyze(
+            "limit = 10\n\
+             def outer(n):\n\
+             \x20   step = 2\n\
+             \x20   def inner(i):\n\
+             \x20       total = i * step\n\
+             \x20       return min(total, limit)\n\
+             \x20   return inner(n)\n",
+        )
+        .unwrap();
+        assert_eq!(kind(&table, "inner", "total"), SymbolKind::Local);
+        assert_eq!(kind(&table, "inner", "step"), SymbolKind::Free);
+        assert_eq!(kind(&table, "inner", "limit"), SymbolKind::Global);
+        assert_eq!(kind(&table, "inner", "min"), SymbolKind::Builtin);
+        assert_eq!(kind(&table, "outer", "step"), SymbolKind::Cell);
+        assert_eq!(kind(&table, "outer", "inner"), SymbolKind::Local);
+        assert_eq!(table.lookup(table.root(), "outer").unwrap().kind, SymbolKind::Global);
+    }
+
+    #[test]
+    fn test_local_slots() {
+        let table = analyze("def f(a, b):\n    c = a + b\n    def g():\n        return a\n    return c\n").unwrap();
+        let scope = table.scope(table.find_function("f").unwrap());
+        let locals: Vec<_> = scope.locals().map(|symbol| (symbol.name.as_str(), symbol.slot)).collect();
+        assert_eq!(locals, vec![("b", Some(0)), ("c", Some(1)), ("g", Some(2))]);
+        let cells: Vec<_> = scope.cells().map(|symbol| (symbol.name.as_str(), symbol.slot)).collect();
+        assert_eq!(cells, vec![("a", Some(0))]);
+        assert!(scope.symbol("a").unwrap().is_parameter);
+    }
+
+    #[test]
+    fn test_global_and_nonlocal_declarations() {
+        let table = analyze(
+            "count = 0\n\
+             def bump():\n\
+             \x20   global count\n\
+             \x20   count += 1\n\
+             def counter():\n\
+             \x20   n = 0\n\
+             \x20   def increment():\n\
+             \x20       nonlocal n\n\
+             \x20       n += 1\n\
+             \x20       return n\n\
+             \x20   return increment\n",
+        )
+        .unwrap();
+        assert_eq!(kind(&table, "bump", "count"), SymbolKind::Global);
+        assert_eq!(kind(&table, "increment", "n"), SymbolKind::Free);
+        assert_eq!(kind(&table, "counter", "n"), SymbolKind::Cell);
+    }
+
+    #[test]
+    fn test_class_scope_is_skipped_by_closures() {
+        let table = analyze(
+            "def make():\n\
+             \x20   value = 1\n\
+             \x20   class Box:\n\
+             \x20       value = 2\n\
+             \x20       def get(self):\n\
+             \x20           return value\n\
+             \x20   return Box\n",
+        )
+        .unwrap();
+        assert_eq!(kind(&table, "get", "value"), SymbolKind::Free);
+        assert_eq!(kind(&table, "make", "value"), SymbolKind::Cell);
+        let class = table.scopes().iter().position(|scope| scope.kind == ScopeKind::Class).unwrap();
+        assert_eq!(table.lookup(class, "value").unwrap().kind, SymbolKind::Local);
+    }
+
+    #[test]
+    fn test_free_variable_passes_through_intermediate_functions() {
+        let table = analyze(
+            "def a():\n\
+             \x20   x = 1\n\
+             \x20   def b():\n\
+             \x20       def c():\n\
+             \x20           return x\n\
+             \x20       return c\n\
+             \x20   return b\n",
+        )
+        .unwrap();
+        assert_eq!(kind(&table, "c", "x"), SymbolKind::Free);
+        assert_eq!(kind(&table, "b", "x"), SymbolKind::Free);
+        assert_eq!(kind(&table, "a", "x"), SymbolKind::Cell);
+    }
+
+    #[test]
+    fn test_comprehension_scope() {
+        let table = analyze("def f(items):\n    return [item * 2 for item in items if item]\n").unwrap();
+        let comprehension = table.scopes().iter().position(|scope| scope.kind == ScopeKind::Comprehension).unwrap();
+        assert_eq!(table.scope(comprehension).name, "<listcomp>");
+        assert_eq!(table.lookup(comprehension, "item").unwrap().kind, SymbolKind::Local);
+        assert!(table.lookup(comprehension, "items").is_none());
+        assert!(table.lookup(table.find_function("f").unwrap(), "item").is_none());
+    }
+
+    #[test]
+    fn test_nonlocal_binding_not_found() {
+        let error = analyze("def f():\n    def g():\n        nonlocal missing\n        missing = 1\n").unwrap_err();
+        assert_eq!(error, "3:9: error: no binding for nonlocal 'missing' found");
+    }
+
+    #[test]
+    fn test_nonlocal_does_not_see_globals() {
+        let error = analyze("x = 1\ndef f():\n    nonlocal x\n").unwrap_err();
+        assert_eq!(error, "3:5: error: no binding for nonlocal 'x' found");
+    }
+
+    #[test]
+    fn test_used_before_assignment() {
+        let error = analyze("x = 1\ndef f():\n    print(x)\n    x = 2\n").unwrap_err();
+        assert_eq!(error, "3:5: error: local variable 'x' referenced before assignment");
+        let error = analyze("def f():\n    total = total + 1\n").unwrap_err();
+        assert_eq!(error, "2:5: error: local variable 'total' referenced before assignment");
+    }
+
+    #[test]
+    fn test_loop_reads_are_not_used_before_assignment() {
+        assert!(analyze("def f(n):\n    while n:\n        if n < 5:\n            print(last)\n        last = n\n        n -= 1\n").is_ok());
+    }
+
+    /// Rendered warnings of a program that analyzes without errors.
+    fn warnings(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
+        analyzer.diagnostics().iter().map(|diagnostic| diagnostic.render(source)).collect()
+    }
+
+    #[test]
+    fn test_possibly_unbound_names() {
+        let source = "def pick(flag):\n\
+                      \x20   if flag:\n\
+                      \x20       value = 1\n\
+                      \x20   return value\n\
+                      def both(flag):\n\
+                      \x20   if flag:\n\
+                      \x20       value = 1\n\
+                      \x20   else:\n\
+                      \x20       value = 2\n\
+                      \x20   return value\n\
+                      for item in []:\n\
+                      \x20   pass\n\
+                      print(item)\n";
+        assert_eq!(
+            warnings(source),
+            vec![
+                "4:5: warning: local variable 'value' may be referenced before assignment",
+                "13:1: warning: name 'item' may be undefined",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_try_and_loop_assignments() {
+        let source = "def parse(text):\n\
+                      \x20   try:\n\
+                      \x20       number = int(text)\n\
+                      \x20   except ValueError as error:\n\
+                      \x20       print(error)\n\
+                      \x20       raise\n\
+                      \x20   return number\n\
+                      def first(n):\n\
+                      \x20   while True:\n\
+                      \x20       if n > 10:\n\
+                      \x20           found = n\n\
+                      \x20           break\n\
+                      \x20       n += 1\n\
+                      \x20   return found\n\
+                      def cleanup():\n\
+                      \x20   try:\n\
+                      \x20       handle = open('f')\n\
+                      \x20   finally:\n\
+                      \x20       print(handle)\n";
+        assert_eq!(warnings(source), vec!["19:9: warning: local variable 'handle' may be referenced before assignment"]);
+        let error = analyze("try:\n    pass\nexcept ValueError as error:\n    pass\nprint(error)\n").unwrap_err();
+        assert_eq!(error, "5:1: error: name 'error' is not defined");
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let source = "def f(items):\n\
+                      \x20   for item in items:\n\
+                      \x20       continue\n\
+                      \x20       print(item)\n\
+                      \x20   return 1\n\
+                      \x20   print('done')\n\
+                      def g():\n\
+                      \x20   while True:\n\
+                      \x20       pass\n\
+                      \x20   g()\n\
+                      def h():\n\
+                      \x20   raise ValueError()\n\
+                      \x20   h()\n";
+        assert_eq!(
+            warnings(source),
+            vec![
+                "4:9: warning: Statement is unreachable [PB008]",
+                "6:5: warning: Statement is unreachable [PB008]",
+                "10:5: warning: Statement is unreachable [PB008]",
+                "13:5: warning: Statement is unreachable [PB008]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let error = analyze("def sign(x: int) -> int:\n    if x > 0:\n        return 1\n    elif x < 0:\n        return -1\n").unwrap_err();
+        assert_eq!(error, "1:1: error: Missing return statement");
+        assert!(analyze("def sign(x: int) -> int:\n    if x > 0:\n        return 1\n    return 0\n").is_ok());
+        assert!(analyze("def log(x: int) -> None:\n    if x:\n        print(x)\n").is_ok());
+        assert!(analyze("def abstract(x: int) -> int:\n    ...\n").is_ok());
+        assert!(analyze("def fail() -> int:\n    while True:\n        pass\n").is_ok());
+    }
+
+    #[test]
+    fn test_undefined_names_are_reported_before_codegen() {
+        let error = analyze("print(total)\ntotal = 1\n").unwrap_err();
+        assert_eq!(error, "1:1: error: name 'total' is not defined");
+    }
+
+    #[test]
+    fn test_undefined_name() {
+        let error = analyze("def f():\n    return undefined_name\n").unwrap_err();
+        assert_eq!(error, "2:5: error: name 'undefined_name' is not defined");
+    }
+
+    #[test]
+    fn test_every_builtin_exception_and_warning_is_defined() {
+        let source = "def load(path):\n\
+                      \x20   try:\n\
+                      \x20       return open(path).read()\n\
+                      \x20   except (FileNotFoundError, PermissionError, UnicodeDecodeError) as error:\n\
+                      \x20       raise ImportError(path) from error\n\
+                      \x20   except KeyboardInterrupt:\n\
+                      \x20       raise SystemExit(1)\n\
+                      \x20   except OSError:\n\
+                      \x20       raise DeprecationWarning(path) if __debug__ else UserWarning(path)\n";
+        assert!(analyze(source).is_ok(), "{:?}", analyze(source).err());
+    }
+
+    #[test]
+    fn test_declaration_errors() {
+        let error = analyze("def f(x):\n    global x\n").unwrap_err();
+        assert_eq!(error, "2:5: error: name 'x' is parameter and global");
+        let error = analyze("def f():\n    y = 1\n    global y\n").unwrap_err();
+        assert_eq!(error, "3:5: error: name 'y' is assigned to before global declaration");
+        let error = analyze("nonlocal z\n").unwrap_err();
+        assert_eq!(error, "1:1: error: nonlocal declaration not allowed at module level");
+    }
 }
@@ -0,0 +1,306 @@
+#[cfg(test)]
+mod type_checker_tests {
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::parser::parser::Parser;
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+    use pybolt::semantic::types::Type;
+
+    fn check(source: &str) -> Result<SemanticAnalyzer, String> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse()?;
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser)?;
+        Ok(analyzer)
+    }
+
+    fn errors(source: &str) -> String {
+        match check(source) {
+            Ok(_) => String::new(),
+            Err(errors) => errors,
+        }
+    }
+
+    #[test]
+    fn test_unannotated_code_is_not_checked() {
+        let source = "def add(a, b):\n\
+                      \x20   return a + b\n\
+                      x = add(1, 'two')\n\
+                      y = x.anything\n";
+        assert_eq!(errors(source), "");
+    }
+
+    #[test]
+    fn test_incompatible_assignment() {
+        let source = "count: int = 0\n\
+                      count = 'many'\n";
+        assert_eq!(
+            errors(source),
+            "2:1: error: Incompatible types in assignment (expression has type \"str\", variable has type \"int\")"
+        );
+    }
+
+    #[test]
+    fn test_argument_and_return_types() {
+        let source = "def scale(value: float, factor: int = 2) -> float:\n\
+                      \x20   return value * factor\n\
+                      def name(value: int) -> str:\n\
+                      \x20   return value\n\
+                      scale(1)\n\
+                      scale('1')\n\
+                      scale(1.5, factor=2.5)\n";
+        assert_eq!(
+            errors(source),
+            "4:5: error: Incompatible return value type (got \"int\", expected \"str\")\n\
+             6:1: error: Argument 1 to \"scale\" has incompatible type \"str\"; expected \"float\"\n\
+             7:1: error: Argument \"factor\" to \"scale\" has incompatible type \"float\"; expected \"int\""
+        );
+    }
+
+    #[test]
+    fn test_optional_narrowing() {
+        let source = "from typing import Optional\n\
+                      class Node:\n\
+                      \x20   def __init__(self, value: int):\n\
+                      \x20       self.value = value\n\
+                      def first(node: Optional[Node]) -> int:\n\
+                      \x20   if node is None:\n\
+                      \x20       return 0\n\
+                      \x20   return node.value\n\
+                      def second(node: Node | None) -> int:\n\
+                      \x20   if isinstance(node, Node) and node.value > 0:\n\
+                      \x20       return node.value\n\
+                      \x20   return -1\n\
+                      def third(node: Optional[Node]) -> int:\n\
+                      \x20   return node.value\n";
+        assert_eq!(
+            errors(source),
+            "14:5: error: Item \"None\" of \"Node | None\" has no attribute \"value\""
+        );
+    }
+
+    #[test]
+    fn test_generic_functions() {
+        let source = "from typing import TypeVar\n\
+                      T = TypeVar('T')\n\
+                      def first(items: list[T]) -> T:\n\
+                      \x20   return items[0]\n\
+                      name: str = first(['a', 'b'])\n\
+                      number: str = first([1, 2])\n";
+        assert_eq!(
+            errors(source),
+            "6:1: error: Incompatible types in assignment (expression has type \"int\", variable has type \"str\")"
+        );
+    }
+
+    #[test]
+    fn test_protocols_are_structural() {
+        let source = "from typing import Protocol\n\
+                      class Shape(Protocol):\n\
+                      \x20   def area(self) -> float: ...\n\
+                      class Square:\n\
+                      \x20   def area(self) -> float:\n\
+                      \x20       return 4.0\n\
+                      class Label:\n\
+                      \x20   def text(self) -> str:\n\
+                      \x20       return 'label'\n\
+                      def total(shape: Shape) -> float:\n\
+                      \x20   return shape.area()\n\
+                      total(Square())\n\
+                      total(Label())\n";
+        assert_eq!(
+            errors(source),
+            "13:1: error: Argument 1 to \"total\" has incompatible type \"Label\"; expected \"Shape\""
+        );
+    }
+
+    #[test]
+    fn test_typed_dict_keys() {
+        let source = "from typing import TypedDict\n\
+                      class Movie(TypedDict):\n\
+                      \x20   title: str\n\
+                      \x20   year: int\n\
+                      good: Movie = {'title': 'Up', 'year': 2009}\n\
+                      bad: Movie = {'title': 'Up', 'rating': 5}\n\
+                      good['director']\n";
+        assert_eq!(
+            errors(source),
+            "6:1: error: Extra key \"rating\" for TypedDict \"Movie\"\n\
+             6:1: error: Missing key \"year\" for TypedDict \"Movie\"\n\
+             7:1: error: TypedDict \"Movie\" has no key \"director\""
+        );
+    }
+
+    #[test]
+    fn test_callable_compatibility() {
+        let source = "from typing import Callable\n\
+                      def apply(f: Callable[[int], int], value: int) -> int:\n\
+                      \x20   return f(value)\n\
+                      def double(x: int) -> int:\n\
+                      \x20   return x * 2\n\
+                      def shout(x: str) -> str:\n\
+                      \x20   return x.upper()\n\
+                      apply(double, 1)\n\
+                      apply(lambda x: x + 1, 1)\n\
+                      apply(shout, 1)\n";
+        assert_eq!(
+            errors(source),
+            "10:1: error: Argument 1 to \"apply\" has incompatible type \"Callable[[str], str]\"; expected \"Callable[[int], int]\""
+        );
+    }
+
+    #[test]
+    fn test_call_arity() {
+        let source = "def area(width, height=1):\n\
+                      \x20   return width * height\n\
+                      def pair(a, b, /):\n\
+                      \x20   return a\n\
+                      def collect(first, *rest):\n\
+                      \x20   return rest\n\
+                      class Point:\n\
+                      \x20   def __init__(self, x, y):\n\
+                      \x20       self.x = x\n\
+                      \x20   def move(self, dx, dy=0):\n\
+                      \x20       return self\n\
+                      area(1)\n\
+                      area(1, 2, 3)\n\
+                      area()\n\
+                      pair(1)\n\
+                      collect(1, 2, 3, 4)\n\
+                      collect()\n\
+                      collect(*[1, 2])\n\
+                      Point(1, 2).move()\n\
+                      Point(1)\n\
+                      len([], [])\n";
+        assert_eq!(
+            errors(source),
+            "13:1: error: Too many arguments for \"area\"\n\
+             14:1: error: Missing positional argument \"width\" in call to \"area\"\n\
+             15:1: error: Too few arguments for \"pair\"\n\
+             17:1: error: Missing positional argument \"first\" in call to \"collect\"\n\
+             19:1: error: Missing positional argument \"dx\" in call to \"move\"\n\
+             20:1: error: Missing positional argument \"y\" in call to \"Point\"\n\
+             21:1: error: Too many arguments for \"len\""
+        );
+    }
+
+    #[test]
+    fn test_builtin_call_arity() {
+        let source = "for i in range(10):\n\
+                      \x20   pass\n\
+                      for i in range(0, 10, 2):\n\
+                      \x20   pass\n\
+                      for i in range():\n\
+                      \x20   pass\n\
+                      range(0, 10, 2, 1)\n\
+                      min([1, 2], key=abs)\n\
+                      max(1, 2, 3)\n\
+                      max()\n\
+                      next(iter([1]), None)\n\
+                      getattr(1)\n\
+                      zip([1], [2], strict=True)\n\
+                      pow(2, 10, mod=7)\n\
+                      reversed([1], [2])\n";
+        assert_eq!(
+            errors(source),
+            "5:10: error: Too few arguments for \"range\"\n\
+             7:1: error: Too many arguments for \"range\"\n\
+             10:1: error: Too few arguments for \"max\"\n\
+             12:1: error: Too few arguments for \"getattr\"\n\
+             15:1: error: Too many arguments for \"reversed\""
+        );
+    }
+
+    #[test]
+    fn test_keyword_arguments() {
+        let source = "def fetch(url, retries=3, *, timeout, verbose=False):\n\
+                      \x20   return url\n\
+                      def configure(name, **options):\n\
+                      \x20   return options\n\
+                      def pair(a, b, /):\n\
+                      \x20   return a\n\
+                      fetch('a', timeout=1)\n\
+                      fetch(url='a', retries=1, timeout=1)\n\
+                      fetch('a', url='b', timeout=1)\n\
+                      fetch('a', delay=1, timeout=1)\n\
+                      fetch('a')\n\
+                      fetch('a', 1, 2, timeout=1)\n\
+                      fetch('a', **{'timeout': 1})\n\
+                      configure('a', colour='red')\n\
+                      pair(1, b=2)\n\
+                      print('a', 'b', sep='', end='')\n\
+                      print('a', colour='red')\n\
+                      sorted([2, 1], reverse=True)\n";
+        assert_eq!(
+            errors(source),
+            "9:1: error: \"fetch\" gets multiple values for keyword argument \"url\"\n\
+             10:1: error: Unexpected keyword argument \"delay\" for \"fetch\"\n\
+             11:1: error: Missing named argument \"timeout\" for \"fetch\"\n\
+             12:1: error: Too many positional arguments for \"fetch\"\n\
+             15:1: error: Unexpected keyword argument \"b\" for \"pair\"\n\
+             15:1: error: Too few arguments for \"pair\"\n\
+             17:1: error: Unexpected keyword argument \"colour\" for \"print\""
+        );
+    }
+
+    #[test]
+    fn test_unsupported_operands() {
+        let source = "def label(count: int, suffix: str) -> str:\n\
+                      \x20   return count + suffix\n";
+        assert_eq!(
+            errors(source),
+            "2:5: error: Unsupported operand types for + (\"int\" and \"str\")"
+        );
+    }
+
+    #[test]
+    fn test_inferred_variable_types() {
+        let source = "total = 0\n\
+                      ratio = total / 2\n\
+                      names = [str(n) for n in range(3)]\n\
+                      maybe = None\n\
+                      if total:\n\
+                      \x20   maybe = 'set'\n";
+        let analyzer = check(source).unwrap();
+        let table = analyzer.symbol_table().unwrap();
+        let info = analyzer.type_info().unwrap();
+        let root = table.root();
+        assert_eq!(info.variable(root, "total"), Some(&Type::Int));
+        assert_eq!(info.variable(root, "ratio"), Some(&Type::Float));
+        assert_eq!(info.variable(root, "names"), Some(&Type::List(Box::new(Type::Str))));
+        assert_eq!(info.variable(root, "maybe").unwrap().to_string(), "None | str");
+    }
+
+    #[test]
+    fn test_classes_with_bases_from_other_modules_are_open() {
+        let source = "import base\n\
+                      class install(base.install):\n\
+                      \x20   def m(self):\n\
+                      \x20       return self.x\n";
+        assert_eq!(errors(source), "");
+    }
+
+    #[test]
+    fn test_redefined_classes_with_cyclic_bases_are_open() {
+        let source = "class A:\n\
+                      \x20   pass\n\
+                      class A(A):\n\
+                      \x20   def m(self):\n\
+                      \x20       return self.x\n\
+                      class B(A):\n\
+                      \x20   pass\n\
+                      class A(B):\n\
+                      \x20   pass\n\
+                      y = B().z\n";
+        assert_eq!(errors(source), "");
+    }
+
+    #[test]
+    fn test_attributes_set_to_none_take_values_later() {
+        let source = "class Options:\n\
+                      \x20   def __init__(self):\n\
+                      \x20       self.layout = None\n\
+                      \x20   def kind(self):\n\
+                      \x20       return self.layout.lower()\n";
+        assert_eq!(errors(source), "");
+    }
+}
@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod verifier_tests {
+    use pybolt::ir::generator::IRGenerator;
+    use pybolt::ir::ir::Module;
+    use pybolt::ir::parser;
+    use pybolt::ir::verifier::verify;
+    use pybolt::lexer::lexer::Lexer;
+    use pybolt::optimizer::optimizer::{Optimization, Optimizer, PassContext};
+    use pybolt::parser::parser::Parser;
+    use pybolt::semantic::semantic::SemanticAnalyzer;
+
+    /// The first line of the verifier's error for `text`.
+    fn first_error(text: &str) -> String {
+        let error = verify(&parser::parse(text).unwrap()).unwrap_err();
+        error.lines().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_generated_ir_verifies() {
+        let source = "total = 0\n\
+                      def add(n: int) -> None:\n\
+                      \x20   global total\n\
+                      \x20   total += n\n\
+                      for i in range(10):\n\
+                      \x20   if i % 3 == 0 or i > 7:\n\
+                      \x20       continue\n\
+                      \x20   add(i if i < 5 else -i)\n\
+                      print(total)\n";
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse().unwrap();
+        let mut analyzer = SemanticAnalyzer::new();
+        analyzer.analyze(&parser).unwrap();
+        let module = IRGenerator::new().generate(analyzer.hir().unwrap()).unwrap();
+        assert_eq!(verify(&module), Ok(()));
+    }
+
+    #[test]
+    fn test_use_not_dominated_by_definition() {
+        let text = "fn @f(bool) -> i64 {\n\
+                    bb0(%c: bool):\n\
+                    \x20   cond_br %c, bb1, bb2\n\
+                    bb1:\n\
+                    \x20   %x: i64 = const 1\n\
+                    \x20   br bb2\n\
+                    bb2:\n\
+                    \x20   ret %x\n\
+                    }\n";
+        assert_eq!(first_error(text), "error in @f: a use of %1 in bb2 is not dominated by its definition");
+        let text = "fn @f() -> i64 {\n\
+                    bb0:\n\
+                    \x20   %y: i64 = add %x, %x\n\
+                    \x20   %x: i64 = const 1\n\
+                    \x20   ret %y\n\
+                    }\n";
+        assert_eq!(first_error(text), "error in @f: a use of %1 in bb0 is not dominated by its definition");
+    }
+
+    #[test]
+    fn test_block_arguments_match_parameters() {
+        let text = "fn @f() -> none {\n\
+                    bb0:\n\
+                    \x20   %x: i64 = const 1\n\
+                    \x20   br bb1(%x)\n\
+                    bb1(%a: i64, %b: bool):\n\
+                    \x20   ret\n\
+                    }\n";
+        assert_eq!(first_error(text), "error in @f: bb0 passes 1 arguments to bb1, which has 2 parameters");
+        let text = "fn @f() -> none {\n\
+                    bb0:\n\
+                    \x20   %x: i64 = const 1\n\
+                    \x20   br bb1(%x)\n\
+                    bb1(%a: bool):\n\
+                    \x20   ret\n\
+                    }\n";
+        assert_eq!(first_error(text), "error in @f: the argument for %1 from bb0 (%0) is i64, expected bool");
+    }
+
+    #[test]
+    fn test_types_are_checked() {
+        let text = "fn @f(bool) -> i64 {\n\
+                    bb0(%c: bool):\n\
+                    \x20   %one: i64 = const 1\n\
+                    \x20   %sum: i64 = add %c, %one\n\
+                    \x20   %r: bool = call @f(%one)\n\
+                    \x20   ret %c\n\
+                    }\n";
+        let error = verify(&parser::parse(text).unwrap()).unwrap_err();
+        assert_eq!(
+            error.lines().take_while(|line| !line.is_empty()).collect::<Vec<_>>(),
+            vec![
+                "error in @f: the left operand of add (%0) is bool, expected int, i64, f64 or object",
+                "error in @f: argument 1 of the call of @f (%1) is i64, expected bool",
+                "error in @f: %3 is bool, expected i64",
+                "error in @f: the returned value (%0) is bool, expected i64",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_boxing_is_checked() {
+        let text = "fn @f(object, i64) -> none {\n\
+                    bb0(%o: object, %i: i64):\n\
+                    \x20   %a: object = box %o\n\
+                    \x20   %b: object = unbox %o\n\
+                    \x20   %c: bool = guard %i, int\n\
+                    \x20   %d: i64 = div %i, %i\n\
+                    \x20   %e: i64 = cast %o\n\
+                    \x20   ret\n\
+                    }\n";
+        let error = verify(&parser::parse(text).unwrap()).unwrap_err();
+        assert_eq!(
+            error.lines().take_while(|line| !line.is_empty()).collect::<Vec<_>>(),
+            vec![
+                "error in @f: the operand of box (%0) is object, expected int, i64, f64, bool, ptr or none",
+                "error in @f: %3 is unboxed into an object",
+                "error in @f: the operand of guard (%1) is i64, expected object",
+                "error in @f: the left operand of div (%1) is i64, expected int, f64 or object",
+                "error in @f: the operand of cast (%0) is object, expected bool, i64 or int",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_terminator_prints_function() {
+        let text = "fn @f() -> none {\n\
+                    bb0:\n\
+                    \x20   %x: i64 = const 1\n\
+                    }\n";
+        let error = verify(&parser::parse(text).unwrap()).unwrap_err();
+        assert_eq!(
+            error.lines().collect::<Vec<_>>(),
+            vec![
+                "error in @f: bb0 has no terminator",
+                "",
+                "fn @f() -> none {",
+                "bb0:",
+                "    %0: i64 = const 1",
+                "    ; missing terminator",
+                "}",
+            ]
+        );
+    }
+
+    /// A broken pass, which deletes the definition of every returned value.
+    struct DeleteReturnedValues;
+
+    impl Optimization for DeleteReturnedValues {
+        fn name(&self) -> &'static str {
+            "delete-returned-values"
+        }
+
+        fn apply(&self, ir: &mut Module, _context: &mut PassContext) -> Result<bool, String> {
+            for function in &mut ir.functions {
+                for block in function.layout.clone() {
+                    let returned = function.terminator(block).map(|terminator| terminator.operands()).unwrap_or_default();
+                    let insts = function.block(block).insts.clone();
+                    function.block_mut(block).insts =
+                        insts.into_iter().filter(|inst| !function.result(*inst).is_some_and(|result| returned.contains(&result))).collect();
+                }
+            }
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_optimizer_catches_broken_pass() {
+        let module = parser::parse("fn @f() -> i64 {\nbb0:\n    %x: i64 = const 1\n    ret %x\n}\n").unwrap();
+        let error = Optimizer::with_optimizations(vec![Box::new(DeleteReturnedValues)]).optimize(module).unwrap_err();
+        assert!(error.starts_with("Invalid IR after delete-returned-values:\nerror in @f: bb0 uses value 0, which was deleted"), "{}", error);
+    }
+}